@@ -0,0 +1,26 @@
+CREATE TABLE local_identities (
+    id UUID PRIMARY KEY,
+    email VARCHAR NOT NULL UNIQUE,
+    username VARCHAR NOT NULL UNIQUE,
+    password_hash VARCHAR NOT NULL,
+    verified BOOLEAN NOT NULL DEFAULT FALSE,
+    mfa_secret VARCHAR,
+    registration_time TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE local_identity_recovery_codes (
+    identity_id UUID NOT NULL REFERENCES local_identities(id) ON DELETE CASCADE,
+    code_hash VARCHAR NOT NULL,
+    used_tm TIMESTAMPTZ,
+    PRIMARY KEY(identity_id, code_hash)
+);
+
+CREATE TABLE local_identity_tokens (
+    token_hash VARCHAR PRIMARY KEY,
+    identity_id UUID NOT NULL REFERENCES local_identities(id) ON DELETE CASCADE,
+    purpose VARCHAR NOT NULL,
+    issue_tm TIMESTAMPTZ NOT NULL,
+    expiration_tm TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX ON local_identity_tokens(identity_id, purpose);
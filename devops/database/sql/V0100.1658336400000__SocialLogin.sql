@@ -0,0 +1,38 @@
+CREATE TABLE user_login_identities (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    provider VARCHAR NOT NULL,
+    provider_user_id VARCHAR NOT NULL,
+    display_name VARCHAR NOT NULL,
+    create_tm TIMESTAMPTZ NOT NULL,
+    last_login_tm TIMESTAMPTZ,
+    UNIQUE(provider, provider_user_id)
+);
+
+CREATE INDEX ON user_login_identities(user_id);
+
+CREATE TABLE social_login_states (
+    state_hash VARCHAR PRIMARY KEY,
+    provider VARCHAR NOT NULL,
+    link_user_id BIGINT REFERENCES users(id) ON DELETE CASCADE,
+    expiration_tm TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE social_login_pending_registrations (
+    id_hash VARCHAR PRIMARY KEY,
+    provider VARCHAR NOT NULL,
+    provider_user_id VARCHAR NOT NULL,
+    display_name VARCHAR NOT NULL,
+    email VARCHAR,
+    username VARCHAR,
+    verification_hash VARCHAR UNIQUE,
+    expiration_tm TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE social_login_refresh_tokens (
+    token_hash VARCHAR PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expiration_tm TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX ON social_login_refresh_tokens(user_id);
@@ -0,0 +1,18 @@
+CREATE TABLE user_login_sessions (
+    id UUID PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    machine_id VARCHAR,
+    ip_addr VARCHAR,
+    user_agent VARCHAR,
+    city VARCHAR,
+    country VARCHAR,
+    create_tm TIMESTAMPTZ NOT NULL,
+    last_used_tm TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX ON user_login_sessions(user_id);
+
+ALTER TABLE user_sessions
+ADD COLUMN login_session_id UUID REFERENCES user_login_sessions(id) ON DELETE CASCADE;
+
+CREATE INDEX ON user_sessions(login_session_id);
@@ -0,0 +1,10 @@
+CREATE TABLE wow_match_view_stat_summaries (
+    view_id UUID NOT NULL REFERENCES wow_match_view(id) ON DELETE CASCADE,
+    unit_guid VARCHAR NOT NULL,
+    damage_dealt BIGINT NOT NULL,
+    damage_received BIGINT NOT NULL,
+    heals BIGINT NOT NULL,
+    PRIMARY KEY(view_id, unit_guid)
+);
+
+CREATE INDEX ON wow_match_view_stat_summaries(unit_guid);
@@ -0,0 +1,20 @@
+CREATE TABLE csgo_event_container_round_tracks (
+    container_id BIGINT NOT NULL REFERENCES csgo_event_container(id) ON DELETE CASCADE,
+    round_num INTEGER NOT NULL,
+    tick_rate REAL NOT NULL,
+    start_tick INTEGER NOT NULL,
+    end_tick INTEGER,
+    sample_count INTEGER NOT NULL,
+    track BYTEA NOT NULL,
+    PRIMARY KEY(container_id, round_num)
+);
+
+CREATE TABLE csgo_event_container_heatmap_cells (
+    container_id BIGINT NOT NULL REFERENCES csgo_event_container(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL,
+    kind INTEGER NOT NULL,
+    cell_x INTEGER NOT NULL,
+    cell_y INTEGER NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY(container_id, user_id, kind, cell_x, cell_y)
+);
@@ -0,0 +1,41 @@
+CREATE TABLE csgo_event_container_round_utility (
+    container_id BIGINT NOT NULL REFERENCES csgo_event_container(id) ON DELETE CASCADE,
+    round_num INTEGER NOT NULL,
+    utility_id INTEGER NOT NULL,
+    tm TIMESTAMPTZ NOT NULL,
+    kind INTEGER NOT NULL,
+    thrower INTEGER,
+    x REAL NOT NULL,
+    y REAL NOT NULL,
+    z REAL NOT NULL,
+    PRIMARY KEY(container_id, round_num, utility_id),
+    FOREIGN KEY(container_id, thrower) REFERENCES csgo_event_container_players(container_id, user_id) ON DELETE CASCADE
+);
+
+CREATE TABLE csgo_event_container_round_utility_damage (
+    container_id BIGINT NOT NULL,
+    round_num INTEGER NOT NULL,
+    utility_id INTEGER NOT NULL,
+    tm TIMESTAMPTZ NOT NULL,
+    receiver INTEGER NOT NULL,
+    damage_health INTEGER NOT NULL,
+    damage_armor INTEGER NOT NULL,
+    FOREIGN KEY(container_id, round_num, utility_id) REFERENCES csgo_event_container_round_utility(container_id, round_num, utility_id) ON DELETE CASCADE,
+    FOREIGN KEY(container_id, receiver) REFERENCES csgo_event_container_players(container_id, user_id) ON DELETE CASCADE
+);
+
+CREATE INDEX ON csgo_event_container_round_utility_damage(container_id, round_num, utility_id);
+
+CREATE TABLE csgo_event_container_round_blinds (
+    container_id BIGINT NOT NULL REFERENCES csgo_event_container(id) ON DELETE CASCADE,
+    round_num INTEGER NOT NULL,
+    tm TIMESTAMPTZ NOT NULL,
+    victim INTEGER NOT NULL,
+    attacker INTEGER,
+    duration REAL NOT NULL,
+    utility_id INTEGER,
+    FOREIGN KEY(container_id, victim) REFERENCES csgo_event_container_players(container_id, user_id) ON DELETE CASCADE,
+    FOREIGN KEY(container_id, attacker) REFERENCES csgo_event_container_players(container_id, user_id) ON DELETE CASCADE
+);
+
+CREATE INDEX ON csgo_event_container_round_blinds(container_id);
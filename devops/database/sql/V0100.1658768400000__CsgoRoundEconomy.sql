@@ -0,0 +1,3 @@
+ALTER TABLE csgo_event_container_round_player_stats
+ADD COLUMN start_money INTEGER,
+ADD COLUMN spent INTEGER;
@@ -0,0 +1,34 @@
+-- Tracks which matches have already been rolled into the deck analytics so that
+-- re-parsing the same logs doesn't double count.
+CREATE TABLE hearthstone_deck_analytics_matches (
+    match_uuid UUID NOT NULL,
+    user_id BIGINT NOT NULL,
+    deck_version_id BIGINT NOT NULL REFERENCES hearthstone_deck_versions(version_id) ON DELETE CASCADE,
+    PRIMARY KEY(match_uuid, user_id),
+    FOREIGN KEY(match_uuid, user_id) REFERENCES hearthstone_match_view(match_uuid, user_id) ON DELETE CASCADE
+);
+
+-- play_order: 0 = unknown, 1 = went first, 2 = went second.
+-- opponent_class is UNKNOWN when we couldn't determine the opponent's class.
+CREATE TABLE hearthstone_deck_matchup_stats (
+    deck_version_id BIGINT NOT NULL REFERENCES hearthstone_deck_versions(version_id) ON DELETE CASCADE,
+    opponent_class VARCHAR NOT NULL,
+    play_order SMALLINT NOT NULL,
+    games INTEGER NOT NULL DEFAULT 0,
+    wins INTEGER NOT NULL DEFAULT 0,
+    total_duration_seconds BIGINT NOT NULL DEFAULT 0,
+    total_turns BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY(deck_version_id, opponent_class, play_order)
+);
+
+CREATE TABLE hearthstone_deck_card_stats (
+    deck_version_id BIGINT NOT NULL REFERENCES hearthstone_deck_versions(version_id) ON DELETE CASCADE,
+    card_id VARCHAR NOT NULL,
+    mulligan_offered INTEGER NOT NULL DEFAULT 0,
+    mulligan_kept INTEGER NOT NULL DEFAULT 0,
+    drawn_games INTEGER NOT NULL DEFAULT 0,
+    drawn_wins INTEGER NOT NULL DEFAULT 0,
+    played_games INTEGER NOT NULL DEFAULT 0,
+    played_wins INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY(deck_version_id, card_id)
+);
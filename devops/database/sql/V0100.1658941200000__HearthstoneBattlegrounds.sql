@@ -0,0 +1,34 @@
+-- hero_card_id and placement are NULL when we couldn't find the player's hero in the logs.
+-- tier_curve is the comma separated list of rounds in which the player reached tavern tier 2, 3, ...
+-- rating_change is only known once the user's next Battlegrounds match comes in.
+CREATE TABLE hearthstone_battlegrounds_matches (
+    match_uuid UUID NOT NULL,
+    user_id BIGINT NOT NULL,
+    match_time TIMESTAMPTZ NOT NULL,
+    hero_card_id VARCHAR,
+    placement INTEGER,
+    dominant_tribe VARCHAR NOT NULL,
+    tier_curve VARCHAR NOT NULL,
+    rating_before INTEGER,
+    rating_change INTEGER,
+    PRIMARY KEY(match_uuid, user_id),
+    FOREIGN KEY(match_uuid, user_id) REFERENCES hearthstone_match_view(match_uuid, user_id) ON DELETE CASCADE
+);
+
+CREATE INDEX ON hearthstone_battlegrounds_matches(user_id, match_time);
+
+-- phase: 0 = shop, 1 = combat.
+CREATE TABLE hearthstone_battlegrounds_turns (
+    match_uuid UUID NOT NULL,
+    user_id BIGINT NOT NULL,
+    turn INTEGER NOT NULL,
+    round INTEGER NOT NULL,
+    phase SMALLINT NOT NULL,
+    tavern_tier INTEGER NOT NULL,
+    hero_power_uses INTEGER NOT NULL,
+    opponent_player_id INTEGER,
+    opponent_hero_card_id VARCHAR,
+    board JSONB NOT NULL,
+    PRIMARY KEY(match_uuid, user_id, turn),
+    FOREIGN KEY(match_uuid, user_id) REFERENCES hearthstone_battlegrounds_matches(match_uuid, user_id) ON DELETE CASCADE
+);
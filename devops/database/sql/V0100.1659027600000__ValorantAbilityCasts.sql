@@ -0,0 +1,13 @@
+-- Match totals from the player's stats in the match details. Matches stored before this table existed won't have a row.
+CREATE TABLE valorant_match_player_ability_casts (
+    match_uuid UUID NOT NULL,
+    puuid VARCHAR NOT NULL,
+    grenade_casts INTEGER NOT NULL,
+    ability1_casts INTEGER NOT NULL,
+    ability2_casts INTEGER NOT NULL,
+    ultimate_casts INTEGER NOT NULL,
+    PRIMARY KEY(match_uuid, puuid),
+    FOREIGN KEY(match_uuid, puuid) REFERENCES valorant_match_players(match_uuid, puuid) ON DELETE CASCADE
+);
+
+CREATE INDEX ON valorant_match_player_ability_casts(puuid);
@@ -0,0 +1,8 @@
+CREATE TABLE riot_api_response_cache (
+    cache_key VARCHAR PRIMARY KEY,
+    response TEXT NOT NULL,
+    cached_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ
+);
+
+CREATE INDEX ON riot_api_response_cache(expires_at);
@@ -0,0 +1,37 @@
+CREATE TABLE team_subscriptions (
+    id BIGSERIAL PRIMARY KEY,
+    stripe_subscription VARCHAR NOT NULL UNIQUE,
+    squad_id BIGINT REFERENCES squads(id) ON DELETE SET NULL,
+    owner_user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    tier VARCHAR NOT NULL,
+    seats INTEGER NOT NULL,
+    start_tm TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    end_tm TIMESTAMPTZ
+);
+
+CREATE INDEX ON team_subscriptions(squad_id);
+CREATE INDEX ON team_subscriptions(end_tm);
+
+CREATE TABLE team_subscription_seats (
+    team_subscription_id BIGINT NOT NULL REFERENCES team_subscriptions(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    assigned_tm TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY(team_subscription_id, user_id)
+);
+
+CREATE INDEX ON team_subscription_seats(user_id);
+
+CREATE TABLE gift_subscriptions (
+    id BIGSERIAL PRIMARY KEY,
+    source_user_id BIGINT REFERENCES users(id) ON DELETE SET NULL,
+    dest_user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    tier VARCHAR NOT NULL,
+    stripe_checkout_session VARCHAR UNIQUE,
+    created_tm TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    start_tm TIMESTAMPTZ NOT NULL,
+    end_tm TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX ON gift_subscriptions(dest_user_id, end_tm);
+CREATE INDEX ON gift_subscriptions(source_user_id);
+CREATE INDEX ON gift_subscriptions(end_tm);
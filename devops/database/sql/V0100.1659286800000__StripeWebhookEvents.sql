@@ -0,0 +1,14 @@
+CREATE TABLE stripe_webhook_events (
+    event_id VARCHAR PRIMARY KEY,
+    event_type VARCHAR NOT NULL,
+    payload JSONB NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error VARCHAR,
+    received_tm TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_attempt_tm TIMESTAMPTZ,
+    processed_tm TIMESTAMPTZ
+);
+
+CREATE INDEX ON stripe_webhook_events(status, received_tm);
+CREATE INDEX ON stripe_webhook_events(event_type, received_tm);
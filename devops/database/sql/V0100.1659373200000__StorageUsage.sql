@@ -0,0 +1,21 @@
+-- One row per stored object (or per combat log partition) so usage can be broken down and re-measured individually.
+CREATE TABLE storage_usage (
+    -- vod, clip, thumbnail, combat_log
+    resource_type VARCHAR NOT NULL,
+    -- The object path in the bucket for VODs or the partition ID for combat logs.
+    resource_key VARCHAR NOT NULL,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    video_uuid UUID REFERENCES vods(video_uuid) ON DELETE CASCADE,
+    partition_id VARCHAR REFERENCES combat_logs(partition_id) ON DELETE CASCADE,
+    game INTEGER,
+    bucket VARCHAR,
+    bytes BIGINT NOT NULL,
+    tracked_tm TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    reconciled_tm TIMESTAMPTZ,
+    PRIMARY KEY(resource_type, resource_key)
+);
+
+CREATE INDEX ON storage_usage(user_id, resource_type);
+CREATE INDEX ON storage_usage(video_uuid);
+CREATE INDEX ON storage_usage(partition_id);
+CREATE INDEX ON storage_usage(reconciled_tm NULLS FIRST);
@@ -0,0 +1,69 @@
+CREATE TABLE feature_flag_definitions (
+    flag_key VARCHAR PRIMARY KEY,
+    -- bool, int, float, string, json
+    flag_type VARCHAR NOT NULL,
+    default_value JSONB NOT NULL,
+    description VARCHAR,
+    created_tm TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_tm TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Rules get checked in priority order (lowest first) and the first rule where every condition matches wins.
+CREATE TABLE feature_flag_rules (
+    id BIGSERIAL PRIMARY KEY,
+    flag_key VARCHAR NOT NULL REFERENCES feature_flag_definitions(flag_key) ON DELETE CASCADE,
+    priority INTEGER NOT NULL DEFAULT 0,
+    conditions JSONB NOT NULL DEFAULT '[]'::JSONB,
+    value JSONB NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_tm TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX ON feature_flag_rules(flag_key, priority);
+
+CREATE TABLE feature_flag_overrides (
+    flag_key VARCHAR NOT NULL REFERENCES feature_flag_definitions(flag_key) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    value JSONB NOT NULL,
+    created_tm TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY(flag_key, user_id)
+);
+
+CREATE INDEX ON feature_flag_overrides(user_id);
+
+-- No foreign key on the flag so the history sticks around after the flag gets deleted.
+CREATE TABLE feature_flag_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    flag_key VARCHAR NOT NULL,
+    admin_user_id BIGINT REFERENCES users(id) ON DELETE SET NULL,
+    action VARCHAR NOT NULL,
+    old_value JSONB,
+    new_value JSONB,
+    tm TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX ON feature_flag_audit_log(flag_key, tm DESC);
+CREATE INDEX ON feature_flag_audit_log(tm DESC);
+
+-- The flags that already live in user_feature_flags. The per-user values in that table stay the baseline for these
+-- (instead of the default here) so only rules and overrides change them.
+INSERT INTO feature_flag_definitions (flag_key, flag_type, default_value, description)
+VALUES
+    ('max_record_pixel_y', 'int', '720', 'Maximum recording resolution (height).'),
+    ('max_record_fps', 'int', '60', 'Maximum recording FPS.'),
+    ('allow_record_upload', 'bool', 'true', NULL),
+    ('allow_wow_combat_log_upload', 'bool', 'true', NULL),
+    ('enable_user_profiles', 'bool', 'true', NULL),
+    ('disable_sentry', 'bool', 'false', NULL),
+    ('max_bitrate_kbps', 'int', '6000', 'Maximum recording bitrate.'),
+    ('can_instant_clip', 'bool', 'true', NULL),
+    ('disable_es_search', 'bool', 'false', NULL),
+    ('mandatory_watermark', 'bool', 'true', NULL),
+    ('watermark_min_size', 'float', '0.01', NULL),
+    ('vod_priority', 'int', '5', 'RabbitMQ priority used when processing the user''s VODs.'),
+    ('early_access', 'bool', 'false', NULL),
+    ('vod_retention', 'int', '604800', 'Seconds before VODs expire (null for no expiration).'),
+    ('max_squad_size', 'int', '20', 'Maximum number of members in squads owned by the user (null for no limit).'),
+    ('max_clip_seconds', 'int', '120', NULL),
+    ('allow_vp9', 'bool', 'false', NULL),
+    ('allow_separate_audio_channels', 'bool', 'false', NULL);
@@ -0,0 +1,28 @@
+-- Filled in from the stream.online/stream.offline EventSub notifications. The ID is Twitch's stream ID.
+CREATE TABLE twitch_streams (
+    id VARCHAR PRIMARY KEY,
+    twitch_user_id VARCHAR NOT NULL REFERENCES twitch_accounts(twitch_user_id) ON DELETE CASCADE,
+    started_tm TIMESTAMPTZ NOT NULL,
+    ended_tm TIMESTAMPTZ,
+    linked_tm TIMESTAMPTZ
+);
+
+CREATE INDEX ON twitch_streams(twitch_user_id, started_tm DESC);
+CREATE INDEX ON twitch_streams(twitch_user_id) WHERE ended_tm IS NULL;
+
+-- Where a match shows up in a Twitch video (archive or clip). Offsets are seconds into the Twitch video.
+CREATE TABLE twitch_match_links (
+    stream_id VARCHAR NOT NULL REFERENCES twitch_streams(id) ON DELETE CASCADE,
+    match_uuid UUID NOT NULL REFERENCES matches(uuid) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    twitch_video_id VARCHAR NOT NULL,
+    -- archive or clip
+    video_type VARCHAR NOT NULL,
+    url VARCHAR NOT NULL,
+    start_offset_seconds BIGINT NOT NULL,
+    end_offset_seconds BIGINT NOT NULL,
+    PRIMARY KEY(twitch_video_id, match_uuid, user_id)
+);
+
+CREATE INDEX ON twitch_match_links(match_uuid);
+CREATE INDEX ON twitch_match_links(stream_id);
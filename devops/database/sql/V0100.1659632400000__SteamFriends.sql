@@ -0,0 +1,23 @@
+-- Friend lists pulled from GetFriendList for Steam accounts linked to SquadOV users.
+CREATE TABLE steam_friends (
+    steam_id BIGINT NOT NULL REFERENCES steam_users_cache(steam_id) ON DELETE CASCADE,
+    friend_steam_id BIGINT NOT NULL,
+    friend_since TIMESTAMPTZ,
+    PRIMARY KEY(steam_id, friend_steam_id)
+);
+
+CREATE INDEX ON steam_friends(friend_steam_id);
+
+CREATE TABLE steam_friend_list_syncs (
+    steam_id BIGINT PRIMARY KEY REFERENCES steam_users_cache(steam_id) ON DELETE CASCADE,
+    last_sync_time TIMESTAMPTZ NOT NULL,
+    -- Steam doesn't give out friend lists for private profiles.
+    is_private BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+-- Whether the user can show up in other users' friend suggestions. Users without a row use the defaults.
+CREATE TABLE user_friend_suggestion_settings (
+    user_id BIGINT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    suggest_to_steam_friends BOOLEAN NOT NULL DEFAULT TRUE,
+    suggest_to_played_with BOOLEAN NOT NULL DEFAULT TRUE
+);
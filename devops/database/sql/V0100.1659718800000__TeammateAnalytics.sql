@@ -0,0 +1,27 @@
+-- The outcome of every match a user has a VOD for along with everyone else who was in the match.
+-- Rebuilt whenever the VOD gets synced.
+CREATE TABLE teammate_match_outcomes (
+    match_uuid UUID NOT NULL REFERENCES matches(uuid) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    game INTEGER NOT NULL,
+    match_time TIMESTAMPTZ NOT NULL,
+    -- NULL if we don't know who won.
+    won BOOLEAN,
+    PRIMARY KEY(match_uuid, user_id)
+);
+
+CREATE INDEX ON teammate_match_outcomes(user_id, match_time DESC);
+
+CREATE TABLE teammate_match_players (
+    match_uuid UUID NOT NULL,
+    user_id BIGINT NOT NULL,
+    -- riot (PUUID), steam (Steam ID), wow (character GUID) or hearthstone (BattleTag).
+    identity_type VARCHAR NOT NULL,
+    identity_id VARCHAR NOT NULL,
+    display_name VARCHAR,
+    is_teammate BOOLEAN NOT NULL,
+    PRIMARY KEY(match_uuid, user_id, identity_type, identity_id),
+    FOREIGN KEY(match_uuid, user_id) REFERENCES teammate_match_outcomes(match_uuid, user_id) ON DELETE CASCADE
+);
+
+CREATE INDEX ON teammate_match_players(user_id);
@@ -0,0 +1,2 @@
+ALTER TABLE local_identity_tokens
+ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
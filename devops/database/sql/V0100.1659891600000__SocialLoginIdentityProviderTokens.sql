@@ -0,0 +1,2 @@
+-- Social logins now get their tokens from the identity provider like every other login.
+DROP TABLE social_login_refresh_tokens;
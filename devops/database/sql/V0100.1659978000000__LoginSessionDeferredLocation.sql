@@ -0,0 +1,6 @@
+-- The location of a login session is looked up in the background so that logging in never waits on ipstack.
+ALTER TABLE user_login_sessions
+ADD COLUMN location_resolved BOOLEAN NOT NULL DEFAULT TRUE,
+ADD COLUMN notify_new_device BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX ON user_login_sessions(create_tm) WHERE NOT location_resolved;
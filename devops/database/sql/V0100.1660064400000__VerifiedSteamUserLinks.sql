@@ -0,0 +1,9 @@
+-- Steam accounts a user has proven they own by signing in through Steam (OpenID).
+-- Unlike steam_user_links (which is reported by the client), this is safe to use to attribute data to a user.
+CREATE VIEW view_verified_steam_user_links (
+    user_id,
+    steam_id
+) AS
+SELECT user_id, provider_user_id::BIGINT
+FROM user_login_identities
+WHERE provider = 'steam';
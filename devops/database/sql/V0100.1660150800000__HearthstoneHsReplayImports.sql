@@ -0,0 +1,10 @@
+-- Imported HSReplay files don't have any game server info so those matches are identified by the
+-- SHA-256 of the file instead.
+ALTER TABLE hearthstone_matches
+ALTER COLUMN server_ip DROP NOT NULL;
+
+CREATE TABLE hearthstone_hsreplay_imports (
+    file_hash VARCHAR PRIMARY KEY,
+    match_uuid UUID UNIQUE NOT NULL REFERENCES hearthstone_matches (match_uuid) ON DELETE CASCADE,
+    create_tm TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,19 @@
+-- Ability usage comes from each round's player stats rather than the match totals. NULL for rounds
+-- that haven't been (back)filled yet.
+DROP TABLE valorant_match_player_ability_casts;
+
+ALTER TABLE valorant_match_round_player_stats
+ADD COLUMN grenade_casts INTEGER,
+ADD COLUMN ability1_casts INTEGER,
+ADD COLUMN ability2_casts INTEGER,
+ADD COLUMN ultimate_casts INTEGER;
+
+-- Matches stored before we kept track of ability usage need to have their details pulled again.
+CREATE TABLE valorant_match_ability_backfill (
+    match_uuid UUID PRIMARY KEY REFERENCES valorant_match_uuid_link(match_uuid) ON DELETE CASCADE,
+    queued_tm TIMESTAMPTZ
+);
+
+INSERT INTO valorant_match_ability_backfill (match_uuid)
+SELECT match_uuid
+FROM valorant_matches;
@@ -0,0 +1,20 @@
+-- Participant frames used to be stored with y = x. The real positions have to be pulled from Riot again.
+CREATE TABLE lol_match_timeline_position_repair (
+    match_uuid UUID PRIMARY KEY REFERENCES lol_match_timeline(match_uuid) ON DELETE CASCADE,
+    queued_tm TIMESTAMPTZ
+);
+
+-- No real match has every participant frame sitting on the diagonal.
+INSERT INTO lol_match_timeline_position_repair (match_uuid)
+SELECT match_uuid
+FROM lol_match_timeline_participant_frames
+WHERE x IS NOT NULL
+    AND y IS NOT NULL
+GROUP BY match_uuid
+HAVING BOOL_AND(x = y);
+
+-- Hide the bad positions from the ward heatmap until the match gets repaired.
+UPDATE lol_match_timeline_participant_frames AS pf
+SET y = NULL
+FROM lol_match_timeline_position_repair AS r
+WHERE r.match_uuid = pf.match_uuid;
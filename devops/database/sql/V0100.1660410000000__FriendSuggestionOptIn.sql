@@ -0,0 +1,4 @@
+-- Users have to opt in before they show up in anyone else's friend suggestions. Existing rows were saved by the user so they stay as is.
+ALTER TABLE user_friend_suggestion_settings
+    ALTER COLUMN suggest_to_steam_friends SET DEFAULT FALSE,
+    ALTER COLUMN suggest_to_played_with SET DEFAULT FALSE;
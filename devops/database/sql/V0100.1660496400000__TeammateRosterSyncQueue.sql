@@ -0,0 +1,14 @@
+-- VODs whose match roster needs to be (re)built for the teammate analytics. Gets filled in whenever match data is stored.
+CREATE TABLE teammate_roster_sync_queue (
+    video_uuid UUID PRIMARY KEY REFERENCES vods(video_uuid) ON DELETE CASCADE,
+    queued_tm TIMESTAMPTZ
+);
+
+-- Backfill everything that was recorded before the teammate analytics existed.
+INSERT INTO teammate_roster_sync_queue (video_uuid)
+SELECT v.video_uuid
+FROM vods AS v
+WHERE v.match_uuid IS NOT NULL
+    AND v.user_uuid IS NOT NULL
+    AND NOT v.is_clip
+ON CONFLICT DO NOTHING;
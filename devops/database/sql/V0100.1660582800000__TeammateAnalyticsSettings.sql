@@ -0,0 +1,5 @@
+-- Whether the user's SquadOV account shows up in other users' teammate analytics. Users without a row stay anonymous.
+CREATE TABLE user_teammate_analytics_settings (
+    user_id BIGINT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    show_in_teammate_analytics BOOLEAN NOT NULL DEFAULT FALSE
+);
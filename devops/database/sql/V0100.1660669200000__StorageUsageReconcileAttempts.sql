@@ -0,0 +1,6 @@
+-- When we last tried to measure a cloud VOD that has nothing tracked. VODs that can't be measured (e.g. the file is
+-- missing) go to the back of the line so they don't keep the rest of the backfill from getting through.
+CREATE TABLE storage_usage_reconcile_attempts (
+    video_uuid UUID PRIMARY KEY REFERENCES vods(video_uuid) ON DELETE CASCADE,
+    last_reconcile_attempt_tm TIMESTAMPTZ NOT NULL
+);
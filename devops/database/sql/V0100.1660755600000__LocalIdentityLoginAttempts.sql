@@ -0,0 +1,3 @@
+ALTER TABLE local_identities
+ADD COLUMN failed_login_attempts INTEGER NOT NULL DEFAULT 0,
+ADD COLUMN login_locked_until_tm TIMESTAMPTZ;
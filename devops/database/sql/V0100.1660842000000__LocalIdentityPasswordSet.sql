@@ -0,0 +1,14 @@
+ALTER TABLE local_identities
+ADD COLUMN password_set BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Identities created for social logins got a random password. Those were registered when the user signed up (or first
+-- logged in) through a third party so they're never older than the user's first linked login identity.
+UPDATE local_identities AS li
+SET password_set = FALSE
+FROM users AS u
+WHERE u.email = li.email
+    AND li.registration_time >= (
+        SELECT MIN(uli.create_tm) - INTERVAL '1 minute'
+        FROM user_login_identities AS uli
+        WHERE uli.user_id = u.id
+    );
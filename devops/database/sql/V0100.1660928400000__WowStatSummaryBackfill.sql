@@ -0,0 +1,16 @@
+-- Matches that were parsed before we stored the stat summary in the database. The summary is still in the
+-- summary.avro report so it only needs to be copied over.
+CREATE TABLE wow_match_view_stat_summary_backfill (
+    view_id UUID PRIMARY KEY REFERENCES wow_match_view(id) ON DELETE CASCADE,
+    queued_tm TIMESTAMPTZ
+);
+
+INSERT INTO wow_match_view_stat_summary_backfill (view_id)
+SELECT wmv.id
+FROM wow_match_view AS wmv
+WHERE wmv.combat_log_partition_id IS NOT NULL
+    AND NOT EXISTS (
+        SELECT 1
+        FROM wow_match_view_stat_summaries AS wss
+        WHERE wss.view_id = wmv.id
+    );
@@ -0,0 +1,157 @@
+-- Each match now keeps what it contributed to the deck analytics so that it can be taken back out when the match
+-- gets deleted (or re-parsed). The aggregates are kept up to date by the triggers below rather than by the
+-- application so that cascading deletes are handled too.
+--
+-- We don't know what the matches that were already counted contributed so everything gets rebuilt through
+-- hearthstone_deck_analytics_backfill from the raw power logs.
+TRUNCATE hearthstone_deck_analytics_matches;
+TRUNCATE hearthstone_deck_matchup_stats;
+TRUNCATE hearthstone_deck_card_stats;
+
+ALTER TABLE hearthstone_deck_analytics_matches
+ADD COLUMN opponent_class VARCHAR NOT NULL,
+ADD COLUMN play_order SMALLINT NOT NULL,
+ADD COLUMN won BOOLEAN NOT NULL,
+ADD COLUMN duration_seconds BIGINT NOT NULL,
+ADD COLUMN num_turns BIGINT NOT NULL;
+
+-- deck_version_id and won are copied from the match so the delete trigger still has them when the match row
+-- is deleted first (e.g. via a cascade).
+CREATE TABLE hearthstone_deck_analytics_match_cards (
+    match_uuid UUID NOT NULL,
+    user_id BIGINT NOT NULL,
+    card_id VARCHAR NOT NULL,
+    deck_version_id BIGINT NOT NULL,
+    won BOOLEAN NOT NULL,
+    mulligan_offered INTEGER NOT NULL,
+    mulligan_kept INTEGER NOT NULL,
+    drawn BOOLEAN NOT NULL,
+    played BOOLEAN NOT NULL,
+    PRIMARY KEY(match_uuid, user_id, card_id),
+    FOREIGN KEY(match_uuid, user_id) REFERENCES hearthstone_deck_analytics_matches(match_uuid, user_id) ON DELETE CASCADE
+);
+
+CREATE OR REPLACE FUNCTION trigger_hearthstone_deck_matchup_stats()
+    RETURNS trigger AS
+$$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO squadov.hearthstone_deck_matchup_stats (
+            deck_version_id,
+            opponent_class,
+            play_order,
+            games,
+            wins,
+            total_duration_seconds,
+            total_turns
+        )
+        VALUES (
+            NEW.deck_version_id,
+            NEW.opponent_class,
+            NEW.play_order,
+            1,
+            CASE WHEN NEW.won THEN 1 ELSE 0 END,
+            NEW.duration_seconds,
+            NEW.num_turns
+        )
+        ON CONFLICT (deck_version_id, opponent_class, play_order) DO UPDATE SET
+            games = hearthstone_deck_matchup_stats.games + EXCLUDED.games,
+            wins = hearthstone_deck_matchup_stats.wins + EXCLUDED.wins,
+            total_duration_seconds = hearthstone_deck_matchup_stats.total_duration_seconds + EXCLUDED.total_duration_seconds,
+            total_turns = hearthstone_deck_matchup_stats.total_turns + EXCLUDED.total_turns;
+        RETURN NEW;
+    END IF;
+
+    UPDATE squadov.hearthstone_deck_matchup_stats
+    SET games = games - 1,
+        wins = wins - CASE WHEN OLD.won THEN 1 ELSE 0 END,
+        total_duration_seconds = total_duration_seconds - OLD.duration_seconds,
+        total_turns = total_turns - OLD.num_turns
+    WHERE deck_version_id = OLD.deck_version_id
+        AND opponent_class = OLD.opponent_class
+        AND play_order = OLD.play_order;
+
+    DELETE FROM squadov.hearthstone_deck_matchup_stats
+    WHERE deck_version_id = OLD.deck_version_id
+        AND opponent_class = OLD.opponent_class
+        AND play_order = OLD.play_order
+        AND games <= 0;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_hearthstone_deck_matchup_stats
+    AFTER INSERT OR DELETE ON hearthstone_deck_analytics_matches
+    FOR EACH ROW
+    EXECUTE FUNCTION trigger_hearthstone_deck_matchup_stats();
+
+-- Deletes only ever update existing rows. Inserting here while the deck version itself is being deleted would
+-- violate the foreign key.
+CREATE OR REPLACE FUNCTION trigger_hearthstone_deck_card_stats()
+    RETURNS trigger AS
+$$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO squadov.hearthstone_deck_card_stats (
+            deck_version_id,
+            card_id,
+            mulligan_offered,
+            mulligan_kept,
+            drawn_games,
+            drawn_wins,
+            played_games,
+            played_wins
+        )
+        VALUES (
+            NEW.deck_version_id,
+            NEW.card_id,
+            NEW.mulligan_offered,
+            NEW.mulligan_kept,
+            CASE WHEN NEW.drawn THEN 1 ELSE 0 END,
+            CASE WHEN NEW.drawn AND NEW.won THEN 1 ELSE 0 END,
+            CASE WHEN NEW.played THEN 1 ELSE 0 END,
+            CASE WHEN NEW.played AND NEW.won THEN 1 ELSE 0 END
+        )
+        ON CONFLICT (deck_version_id, card_id) DO UPDATE SET
+            mulligan_offered = hearthstone_deck_card_stats.mulligan_offered + EXCLUDED.mulligan_offered,
+            mulligan_kept = hearthstone_deck_card_stats.mulligan_kept + EXCLUDED.mulligan_kept,
+            drawn_games = hearthstone_deck_card_stats.drawn_games + EXCLUDED.drawn_games,
+            drawn_wins = hearthstone_deck_card_stats.drawn_wins + EXCLUDED.drawn_wins,
+            played_games = hearthstone_deck_card_stats.played_games + EXCLUDED.played_games,
+            played_wins = hearthstone_deck_card_stats.played_wins + EXCLUDED.played_wins;
+        RETURN NEW;
+    END IF;
+
+    UPDATE squadov.hearthstone_deck_card_stats
+    SET mulligan_offered = mulligan_offered - OLD.mulligan_offered,
+        mulligan_kept = mulligan_kept - OLD.mulligan_kept,
+        drawn_games = drawn_games - (CASE WHEN OLD.drawn THEN 1 ELSE 0 END),
+        drawn_wins = drawn_wins - (CASE WHEN OLD.drawn AND OLD.won THEN 1 ELSE 0 END),
+        played_games = played_games - (CASE WHEN OLD.played THEN 1 ELSE 0 END),
+        played_wins = played_wins - (CASE WHEN OLD.played AND OLD.won THEN 1 ELSE 0 END)
+    WHERE deck_version_id = OLD.deck_version_id
+        AND card_id = OLD.card_id;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_hearthstone_deck_card_stats
+    AFTER INSERT OR DELETE ON hearthstone_deck_analytics_match_cards
+    FOR EACH ROW
+    EXECUTE FUNCTION trigger_hearthstone_deck_card_stats();
+
+-- Matches that need to be (re-)counted. This is every match with a deck that we still have the logs for.
+CREATE TABLE hearthstone_deck_analytics_backfill (
+    match_uuid UUID NOT NULL,
+    user_id BIGINT NOT NULL,
+    queued_tm TIMESTAMPTZ,
+    PRIMARY KEY(match_uuid, user_id),
+    FOREIGN KEY(match_uuid, user_id) REFERENCES hearthstone_match_view(match_uuid, user_id) ON DELETE CASCADE
+);
+
+INSERT INTO hearthstone_deck_analytics_backfill (match_uuid, user_id)
+SELECT DISTINCT hmud.match_uuid, hmud.user_id
+FROM hearthstone_match_user_deck AS hmud
+INNER JOIN hearthstone_raw_power_logs AS hrpl
+    ON hrpl.match_uuid = hmud.match_uuid
+        AND hrpl.user_id = hmud.user_id;
@@ -19,6 +19,7 @@ use squadov_common::{
         LOG_FLUSH,
     },
     aws::s3,
+    storage,
 };
 use rusoto_core::Region;
 use rusoto_secretsmanager::{
@@ -95,6 +96,12 @@ impl SharedClient {
             id=&Uuid::new_v4(),
         );
         s3::s3_multipart_upload_data(self.s3.as_ref(), Cursor::new(compressed_data), compressed_data_size, &self.combat_log_bucket, &key).await?;
+
+        // The object is already in S3 at this point so failing here would just get the same data uploaded again. Anything
+        // we miss gets picked up by the storage reconciliation.
+        if let Err(err) = storage::track_combat_log_storage_usage(&*self.pool, partition, &key, compressed_data_size as i64).await {
+            log::warn!("Failed to track combat log storage usage [{}]: {:?}", &key, err);
+        }
         Ok(())
     }
 
@@ -2,6 +2,26 @@ use crate::SquadOvError;
 use sqlx::{Executor, Postgres};
 use chrono::{DateTime, Utc};
 
+pub async fn does_combat_log_exist<'a, T>(ex: T, partition_id: &str) -> Result<bool, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar(
+            "
+            SELECT EXISTS (
+                SELECT 1
+                FROM squadov.combat_logs
+                WHERE partition_id = $1
+            )
+            "
+        )
+            .bind(partition_id)
+            .fetch_one(ex)
+            .await?
+    )
+}
+
 pub async fn create_combat_log<'a, T>(ex: T, partition_id: &str, user_id: i64, start_time: DateTime<Utc>, cl_data: serde_json::Value) -> Result<(), SquadOvError>
 where
     T: Executor<'a, Database = Postgres>
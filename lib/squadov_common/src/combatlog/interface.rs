@@ -6,9 +6,13 @@ use crate::{
     },
 };
 use std::sync::Arc;
+use rusoto_core::RusotoError;
 use rusoto_s3::{
     S3,
     GetObjectRequest,
+    HeadObjectError,
+    HeadObjectRequest,
+    ListObjectsV2Request,
 };
 use serde::{Serialize, de::DeserializeOwned};
 use avro_rs::{
@@ -60,6 +64,48 @@ impl CombatLogInterface {
         Ok(())
     }
 
+    // Everything stored for a combat log: the raw/parsed logs from the parser and the generated reports.
+    pub async fn get_partition_size_bytes(&self, partition_id: &str) -> Result<i64, SquadOvError> {
+        let mut total: i64 = 0;
+        for form in &["Raw", "Parsed", "Flush", "Report"] {
+            let mut continuation_token: Option<String> = None;
+            loop {
+                let req = ListObjectsV2Request{
+                    bucket: self.bucket.clone(),
+                    continuation_token: continuation_token.clone(),
+                    prefix: Some(format!("form={}/partition={}/", form, partition_id)),
+                    ..ListObjectsV2Request::default()
+                };
+
+                let resp = (*self.aws).as_ref().unwrap().s3.list_objects_v2(req).await?;
+                total += resp.contents.unwrap_or(vec![]).iter().map(|x| { x.size.unwrap_or(0) }).sum::<i64>();
+
+                if resp.is_truncated.unwrap_or(false) {
+                    continuation_token = resp.next_continuation_token;
+                } else {
+                    break;
+                }
+            }
+        }
+        Ok(total)
+    }
+
+    // Returns None if the object doesn't exist anymore.
+    pub async fn get_object_size_bytes(&self, key: &str) -> Result<Option<i64>, SquadOvError> {
+        let req = HeadObjectRequest{
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            ..HeadObjectRequest::default()
+        };
+
+        match (*self.aws).as_ref().unwrap().s3.head_object(req).await {
+            Ok(resp) => Ok(Some(resp.content_length.unwrap_or(0))),
+            Err(RusotoError::Service(HeadObjectError::NoSuchKey(_))) => Ok(None),
+            Err(RusotoError::Unknown(resp)) if resp.status.as_u16() == 404 => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
     fn get_key(partition_id: &str, canonical_type: i32, filename: &str) -> String {
         format!("form=Report/partition={}/canonical={}/{}", partition_id, canonical_type, filename)
     }
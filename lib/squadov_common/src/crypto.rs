@@ -1,3 +1,6 @@
+pub mod password;
+pub mod totp;
+
 use crate::SquadOvError;
 use sha2::Sha256;
 use hkdf::Hkdf;
@@ -7,4 +10,4 @@ pub fn hash_str_to_i64(input: &str) -> Result<i64, SquadOvError> {
     let mut okm = [0u8; 8];
     hk.expand("SquadOV Poggers".as_bytes(), &mut okm).map_err(|x| { SquadOvError::InternalError(format!("Failed to expand OKM: {:?}", x)) })?;
     Ok(i64::from_le_bytes(okm))
-}
\ No newline at end of file
+}
@@ -0,0 +1,76 @@
+use crate::SquadOvError;
+use openssl::{
+    hash::MessageDigest,
+    pkcs5::pbkdf2_hmac,
+    rand::rand_bytes,
+    memcmp,
+};
+
+const PASSWORD_HASH_ALGORITHM: &str = "pbkdf2_sha256";
+const PASSWORD_HASH_ITERATIONS: usize = 310000;
+const PASSWORD_SALT_BYTES: usize = 16;
+const PASSWORD_HASH_BYTES: usize = 32;
+
+fn pbkdf2_sha256(password: &str, salt: &[u8], iterations: usize) -> Result<Vec<u8>, SquadOvError> {
+    let mut key = vec![0u8; PASSWORD_HASH_BYTES];
+    pbkdf2_hmac(password.as_bytes(), salt, iterations, MessageDigest::sha256(), &mut key)?;
+    Ok(key)
+}
+
+// Hashes the password into a self-describing string of the form ALGORITHM$ITERATIONS$SALT$HASH
+// so that we can bump the iteration count later without invalidating existing passwords.
+pub fn hash_password(password: &str) -> Result<String, SquadOvError> {
+    let mut salt = [0u8; PASSWORD_SALT_BYTES];
+    rand_bytes(&mut salt)?;
+
+    let key = pbkdf2_sha256(password, &salt, PASSWORD_HASH_ITERATIONS)?;
+    Ok(format!(
+        "{}${}${}${}",
+        PASSWORD_HASH_ALGORITHM,
+        PASSWORD_HASH_ITERATIONS,
+        base64::encode(&salt),
+        base64::encode(&key),
+    ))
+}
+
+pub fn verify_password(password: &str, stored: &str) -> Result<bool, SquadOvError> {
+    let parts: Vec<&str> = stored.split('$').collect();
+    if parts.len() != 4 || parts[0] != PASSWORD_HASH_ALGORITHM {
+        return Err(SquadOvError::InternalError(String::from("Unknown password hash format.")));
+    }
+
+    let iterations = parts[1].parse::<usize>()?;
+    let salt = base64::decode(parts[2])?;
+    let expected = base64::decode(parts[3])?;
+
+    let key = pbkdf2_sha256(password, &salt, iterations)?;
+    Ok(key.len() == expected.len() && memcmp::eq(&key, &expected))
+}
+
+// Generates a random token suitable for things like email verification links and refresh tokens.
+pub fn generate_secure_token(num_bytes: usize) -> Result<String, SquadOvError> {
+    let mut buffer = vec![0u8; num_bytes];
+    rand_bytes(&mut buffer)?;
+    Ok(base64::encode_config(&buffer, base64::URL_SAFE_NO_PAD))
+}
+
+// Tokens are stored hashed in the database so that a database leak doesn't let anyone use them.
+pub fn hash_token(token: &str) -> String {
+    use sha2::{Sha256, Digest};
+    let mut hasher = Sha256::new();
+    hasher.update(token.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_hash_verify() {
+        let hash = hash_password("hunter2").unwrap();
+        assert!(verify_password("hunter2", &hash).unwrap());
+        assert!(!verify_password("hunter3", &hash).unwrap());
+        assert!(verify_password("hunter2", "bcrypt$1$abc$def").is_err());
+    }
+}
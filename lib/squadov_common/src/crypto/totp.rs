@@ -0,0 +1,108 @@
+use crate::{
+    SquadOvError,
+    encode::{base32_encode, base32_decode},
+};
+use openssl::{
+    hash::MessageDigest,
+    pkey::PKey,
+    sign::Signer,
+    rand::rand_bytes,
+};
+
+// RFC 6238 defaults - these are what every authenticator app assumes when given an otpauth:// URI
+// without any additional parameters.
+const TOTP_STEP_SECONDS: i64 = 30;
+const TOTP_DIGITS: u32 = 6;
+const TOTP_SECRET_BYTES: usize = 20;
+
+pub fn generate_totp_secret() -> Result<String, SquadOvError> {
+    let mut secret = [0u8; TOTP_SECRET_BYTES];
+    rand_bytes(&mut secret)?;
+    Ok(base32_encode(&secret))
+}
+
+// RFC 4226 HOTP using HMAC-SHA1.
+fn hotp(secret: &[u8], counter: u64, digits: u32) -> Result<u32, SquadOvError> {
+    let key = PKey::hmac(secret)?;
+    let mut signer = Signer::new(MessageDigest::sha1(), &key)?;
+    signer.update(&counter.to_be_bytes())?;
+    let hmac = signer.sign_to_vec()?;
+
+    let offset = (hmac[hmac.len() - 1] & 0x0F) as usize;
+    let binary = ((hmac[offset] as u32 & 0x7F) << 24)
+        | ((hmac[offset + 1] as u32) << 16)
+        | ((hmac[offset + 2] as u32) << 8)
+        | (hmac[offset + 3] as u32);
+    Ok(binary % 10u32.pow(digits))
+}
+
+fn totp_at(secret: &[u8], unix_time: i64, digits: u32) -> Result<u32, SquadOvError> {
+    hotp(secret, (unix_time / TOTP_STEP_SECONDS) as u64, digits)
+}
+
+pub fn generate_totp_code(secret_base32: &str, unix_time: i64) -> Result<String, SquadOvError> {
+    let secret = base32_decode(secret_base32)?;
+    Ok(format!("{:0width$}", totp_at(&secret, unix_time, TOTP_DIGITS)?, width=TOTP_DIGITS as usize))
+}
+
+// Checks the code against the current time step as well as the steps immediately before and after
+// to allow for some clock drift between the server and the user's device.
+pub fn verify_totp_code(secret_base32: &str, code: &str, unix_time: i64) -> Result<bool, SquadOvError> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS as usize {
+        return Ok(false);
+    }
+
+    for drift in -1..=1 {
+        if generate_totp_code(secret_base32, unix_time + drift * TOTP_STEP_SECONDS)? == code {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc6238_sha1() {
+        struct TestDatum {
+            time: i64,
+            output: u32,
+        }
+
+        // Test vectors from RFC 6238 Appendix B (SHA1).
+        let test_data = vec![
+            TestDatum{
+                time: 59,
+                output: 94287082,
+            },
+            TestDatum{
+                time: 1111111109,
+                output: 7081804,
+            },
+            TestDatum{
+                time: 1234567890,
+                output: 89005924,
+            },
+            TestDatum{
+                time: 20000000000,
+                output: 65353130,
+            },
+        ];
+
+        for td in &test_data {
+            assert_eq!(totp_at(b"12345678901234567890", td.time, 8).unwrap(), td.output);
+        }
+    }
+
+    #[test]
+    fn test_verify_totp_code_drift() {
+        let secret = base32_encode(b"12345678901234567890");
+        assert!(verify_totp_code(&secret, "287082", 59).unwrap());
+        assert!(verify_totp_code(&secret, "287082", 59 + 30).unwrap());
+        assert!(!verify_totp_code(&secret, "287082", 59 + 90).unwrap());
+        assert!(!verify_totp_code(&secret, "28708", 59).unwrap());
+    }
+}
@@ -11,6 +11,9 @@ pub mod db;
 pub mod schema;
 pub mod rabbitmq;
 pub mod summary;
+pub mod positions;
+pub mod utility;
+pub mod economy;
 
 use crate::SquadOvError;
 use sqlx::{Executor, Postgres};
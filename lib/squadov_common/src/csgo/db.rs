@@ -9,6 +9,7 @@ use crate::{
             CsgoTeam,
             CsgoRoundWin,
             CsgoDemoHitGroup,
+            CsgoDemoUtilityKind,
         },
         gsi::CsgoGsiMatchState,
         schema::{
@@ -20,9 +21,20 @@ use crate::{
             CsgoCommonRoundPlayerStats,
             CsgoCommonRoundKill,
             CsgoCommonRoundDamage,
+            CsgoCommonRoundUtility,
+            CsgoCommonRoundUtilityDamage,
+            CsgoCommonRoundBlind,
         },
         summary::CsgoPlayerMatchSummary,
         weapon::CsgoWeapon,
+        economy::CsgoEconomyPlayerRound,
+        positions::{
+            CsgoRoundTrack,
+            CsgoHeatmapAccumulator,
+            CsgoHeatmapKind,
+            serialize_position_samples,
+            deserialize_position_samples,
+        },
     },
     matches::MatchPlayerPair,
     steam::SteamAccount,
@@ -197,6 +209,102 @@ async fn get_csgo_round_data_for_container(ex: &PgPool, container_id: i64) -> Re
             }
         });
 
+    let mut round_utility_damage: HashMap<(i32, i32), Vec<CsgoCommonRoundUtilityDamage>> = HashMap::new();
+    sqlx::query_as::<_, (i32, i32, DateTime<Utc>, i32, i32, i32)>(
+        "
+        SELECT round_num, utility_id, tm, receiver, damage_health, damage_armor
+        FROM squadov.csgo_event_container_round_utility_damage
+        WHERE container_id = $1
+        ORDER BY tm ASC
+        "
+    )
+        .bind(container_id)
+        .fetch_all(&*ex)
+        .await?
+        .into_iter()
+        .for_each(|(round_num, utility_id, tm, receiver, damage_health, damage_armor)| {
+            round_utility_damage.entry((round_num, utility_id)).or_insert(vec![]).push(CsgoCommonRoundUtilityDamage{
+                tm,
+                receiver,
+                damage_health,
+                damage_armor,
+            });
+        });
+
+    let mut round_utility: HashMap<i32, Vec<CsgoCommonRoundUtility>> = HashMap::new();
+    sqlx::query_as::<_, (i32, i32, DateTime<Utc>, i32, Option<i32>, f32, f32, f32)>(
+        "
+        SELECT round_num, utility_id, tm, kind, thrower, x, y, z
+        FROM squadov.csgo_event_container_round_utility
+        WHERE container_id = $1
+        ORDER BY tm ASC
+        "
+    )
+        .bind(container_id)
+        .fetch_all(&*ex)
+        .await?
+        .into_iter()
+        .for_each(|(round_num, utility_id, tm, kind, thrower, x, y, z)| {
+            let kind = match CsgoDemoUtilityKind::try_from(kind) {
+                Ok(k) => k,
+                Err(_) => return,
+            };
+
+            round_utility.entry(round_num).or_insert(vec![]).push(CsgoCommonRoundUtility{
+                container_id,
+                round_num,
+                utility_id,
+                tm,
+                kind,
+                thrower,
+                x,
+                y,
+                z,
+                damage: round_utility_damage.remove(&(round_num, utility_id)).unwrap_or(vec![]),
+            });
+        });
+
+    let mut round_blinds: HashMap<i32, Vec<CsgoCommonRoundBlind>> = HashMap::new();
+    sqlx::query_as::<_, (i32, DateTime<Utc>, i32, Option<i32>, f32, Option<i32>)>(
+        "
+        SELECT round_num, tm, victim, attacker, duration, utility_id
+        FROM squadov.csgo_event_container_round_blinds
+        WHERE container_id = $1
+        ORDER BY tm ASC
+        "
+    )
+        .bind(container_id)
+        .fetch_all(&*ex)
+        .await?
+        .into_iter()
+        .for_each(|(round_num, tm, victim, attacker, duration, utility_id)| {
+            round_blinds.entry(round_num).or_insert(vec![]).push(CsgoCommonRoundBlind{
+                container_id,
+                round_num,
+                tm,
+                victim,
+                attacker,
+                duration,
+                utility_id,
+            });
+        });
+
+    let round_player_econ: HashMap<(i32, i32), (Option<i32>, Option<i32>)> = sqlx::query_as::<_, (i32, i32, Option<i32>, Option<i32>)>(
+        "
+        SELECT round_num, user_id, start_money, spent
+        FROM squadov.csgo_event_container_round_player_stats
+        WHERE container_id = $1
+        "
+    )
+        .bind(container_id)
+        .fetch_all(&*ex)
+        .await?
+        .into_iter()
+        .map(|(round_num, user_id, start_money, spent)| {
+            ((round_num, user_id), (start_money, spent))
+        })
+        .collect();
+
     let mut round_player_stats: HashMap<i32, Vec<_>> = HashMap::new();
     sqlx::query!(
         "
@@ -256,7 +364,9 @@ async fn get_csgo_round_data_for_container(ex: &PgPool, container_id: i64) -> Re
                             team: CsgoTeam::try_from(x.team).unwrap_or(CsgoTeam::TeamSpectate),
                             weapons: x.weapons.into_iter().map(|y| {
                                 CsgoWeapon::try_from(y).unwrap_or(CsgoWeapon::Unknown)
-                            }).collect()
+                            }).collect(),
+                            start_money: round_player_econ.get(&(x.round_num, x.user_id)).and_then(|y| { y.0 }),
+                            spent: round_player_econ.get(&(x.round_num, x.user_id)).and_then(|y| { y.1 }),
                         }
                     }).collect()
                 } else {
@@ -302,6 +412,8 @@ async fn get_csgo_round_data_for_container(ex: &PgPool, container_id: i64) -> Re
                 } else {
                     vec![]
                 },
+                utility: round_utility.remove(&x.round_num).unwrap_or(vec![]),
+                blinds: round_blinds.remove(&x.round_num).unwrap_or(vec![]),
             }
         }).collect()
     )
@@ -706,7 +818,9 @@ pub async fn store_csgo_gsi_events_for_view(ex: &mut Transaction<'_, Postgres>,
 
 pub async fn store_csgo_demo_events_for_view(ex: &mut Transaction<'_, Postgres>, view_uuid: &Uuid, demo: &CsgoDemo, ref_timestamp: &DateTime<Utc>) -> Result<(), SquadOvError> {
     let common = CsgoCommonEventContainer::from_demo(demo, ref_timestamp)?;
-    store_csgo_common_events_for_view(ex, view_uuid, &common).await?;
+    let container_id = store_csgo_common_events_for_view(ex, view_uuid, &common).await?;
+    store_csgo_demo_round_tracks_for_container(&mut *ex, container_id, demo).await?;
+    store_csgo_demo_heatmap_for_container(&mut *ex, container_id, demo).await?;
     sqlx::query!(
         "
         UPDATE squadov.csgo_match_views
@@ -720,6 +834,254 @@ pub async fn store_csgo_demo_events_for_view(ex: &mut Transaction<'_, Postgres>,
     Ok(())
 }
 
+async fn store_csgo_demo_round_tracks_for_container(ex: &mut Transaction<'_, Postgres>, container_id: i64, demo: &CsgoDemo) -> Result<(), SquadOvError> {
+    let tick_rate = demo.tick_rate();
+    for round in &demo.rounds {
+        if round.positions.is_empty() {
+            continue;
+        }
+
+        sqlx::query!(
+            "
+            INSERT INTO squadov.csgo_event_container_round_tracks (
+                container_id,
+                round_num,
+                tick_rate,
+                start_tick,
+                end_tick,
+                sample_count,
+                track
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT DO NOTHING
+            ",
+            container_id,
+            round.round_num as i32,
+            tick_rate,
+            round.round_start_tick,
+            round.round_end_tick,
+            round.positions.len() as i32,
+            serialize_position_samples(&round.positions)?,
+        )
+            .execute(&mut *ex)
+            .await?;
+    }
+    Ok(())
+}
+
+async fn store_csgo_demo_heatmap_for_container(ex: &mut Transaction<'_, Postgres>, container_id: i64, demo: &CsgoDemo) -> Result<(), SquadOvError> {
+    let mut acc = CsgoHeatmapAccumulator::default();
+    for round in &demo.rounds {
+        acc.add_samples(&round.positions);
+        for k in &round.kills {
+            if let Some(pos) = &k.victim_position {
+                acc.add(k.victim, CsgoHeatmapKind::Death, pos);
+            }
+
+            if let (Some(killer), Some(pos)) = (k.killer, &k.killer_position) {
+                acc.add(killer, CsgoHeatmapKind::Kill, pos);
+            }
+        }
+    }
+
+    if acc.cells.is_empty() {
+        return Ok(());
+    }
+
+    let mut user_ids: Vec<i32> = Vec::with_capacity(acc.cells.len());
+    let mut kinds: Vec<i32> = Vec::with_capacity(acc.cells.len());
+    let mut cell_xs: Vec<i32> = Vec::with_capacity(acc.cells.len());
+    let mut cell_ys: Vec<i32> = Vec::with_capacity(acc.cells.len());
+    let mut counts: Vec<i32> = Vec::with_capacity(acc.cells.len());
+    for (key, count) in acc.cells {
+        user_ids.push(key.user_id);
+        kinds.push(key.kind as i32);
+        cell_xs.push(key.cell_x);
+        cell_ys.push(key.cell_y);
+        counts.push(count);
+    }
+
+    sqlx::query!(
+        "
+        INSERT INTO squadov.csgo_event_container_heatmap_cells (
+            container_id,
+            user_id,
+            kind,
+            cell_x,
+            cell_y,
+            count
+        )
+        SELECT $1, t.user_id, t.kind, t.cell_x, t.cell_y, t.count
+        FROM UNNEST($2::INTEGER[], $3::INTEGER[], $4::INTEGER[], $5::INTEGER[], $6::INTEGER[]) AS t(user_id, kind, cell_x, cell_y, count)
+        ON CONFLICT DO NOTHING
+        ",
+        container_id,
+        &user_ids,
+        &kinds,
+        &cell_xs,
+        &cell_ys,
+        &counts,
+    )
+        .execute(&mut *ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_csgo_round_track_for_view<'a, T>(ex: T, view_uuid: &Uuid, round_num: i32) -> Result<CsgoRoundTrack, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    let row = sqlx::query!(
+        "
+        SELECT rt.tick_rate, rt.start_tick, rt.end_tick, rt.track
+        FROM squadov.csgo_event_container_round_tracks AS rt
+        INNER JOIN squadov.csgo_event_container AS cec
+            ON cec.id = rt.container_id
+        WHERE cec.view_uuid = $1
+            AND cec.event_source = $2
+            AND rt.round_num = $3
+        ORDER BY cec.id DESC
+        LIMIT 1
+        ",
+        view_uuid,
+        CsgoEventSource::Demo as i32,
+        round_num,
+    )
+        .fetch_optional(ex)
+        .await?
+        .ok_or(SquadOvError::NotFound)?;
+
+    Ok(CsgoRoundTrack{
+        round_num,
+        tick_rate: row.tick_rate,
+        start_tick: row.start_tick,
+        end_tick: row.end_tick,
+        samples: deserialize_position_samples(&row.track)?,
+    })
+}
+
+// Returns (kind, cell x, cell y, count) for every cell the user has touched on the given map across all their demos.
+pub async fn get_csgo_user_heatmap_cells<'a, T>(ex: T, user_id: i64, map: &str) -> Result<Vec<(CsgoHeatmapKind, i32, i32, i64)>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query!(
+            r#"
+            WITH containers AS (
+                SELECT DISTINCT ON (cmv.view_uuid) cec.id
+                FROM squadov.csgo_match_views AS cmv
+                INNER JOIN squadov.csgo_event_container AS cec
+                    ON cec.view_uuid = cmv.view_uuid
+                WHERE cmv.user_id = $1
+                    AND cmv.map = $2
+                    AND cec.event_source = $3
+                ORDER BY cmv.view_uuid, cec.id DESC
+            )
+            SELECT hc.kind, hc.cell_x, hc.cell_y, SUM(hc.count)::BIGINT AS "count!"
+            FROM containers AS c
+            INNER JOIN squadov.csgo_event_container_players AS cecp
+                ON cecp.container_id = c.id
+            INNER JOIN squadov.steam_user_links AS sul
+                ON sul.steam_id = cecp.steam_id
+                    AND sul.user_id = $1
+            INNER JOIN squadov.csgo_event_container_heatmap_cells AS hc
+                ON hc.container_id = c.id
+                    AND hc.user_id = cecp.user_id
+            GROUP BY hc.kind, hc.cell_x, hc.cell_y
+            "#,
+            user_id,
+            map,
+            CsgoEventSource::Demo as i32,
+        )
+            .fetch_all(ex)
+            .await?
+            .into_iter()
+            .filter_map(|x| {
+                CsgoHeatmapKind::try_from(x.kind).ok().map(|kind| { (kind, x.cell_x, x.cell_y, x.count) })
+            })
+            .collect()
+    )
+}
+
+const CSGO_ECONOMY_ROW_QUERY: &'static str = "
+    SELECT
+        cec.view_uuid,
+        cec.event_source,
+        cmv.mode,
+        rps.round_num,
+        cecp.steam_id,
+        rps.team,
+        r.winning_team,
+        rps.equipment_value,
+        rps.money,
+        rps.start_money,
+        rps.spent
+    FROM squadov.csgo_event_container AS cec
+    INNER JOIN squadov.csgo_match_views AS cmv
+        ON cmv.view_uuid = cec.view_uuid
+    INNER JOIN squadov.csgo_event_container_round_player_stats AS rps
+        ON rps.container_id = cec.id
+    INNER JOIN squadov.csgo_event_container_players AS cecp
+        ON cecp.container_id = rps.container_id
+            AND cecp.user_id = rps.user_id
+    INNER JOIN squadov.csgo_event_container_rounds AS r
+        ON r.container_id = rps.container_id
+            AND r.round_num = rps.round_num
+";
+
+pub async fn get_csgo_economy_rows_for_view(ex: &PgPool, view_uuid: &Uuid) -> Result<Vec<CsgoEconomyPlayerRound>, SquadOvError> {
+    Ok(
+        sqlx::query_as::<_, CsgoEconomyPlayerRound>(&format!("
+            {base}
+            WHERE cec.view_uuid = $1
+            ",
+            base=CSGO_ECONOMY_ROW_QUERY,
+        ))
+            .bind(view_uuid)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub async fn get_csgo_economy_rows_for_user(ex: &PgPool, user_id: i64) -> Result<Vec<CsgoEconomyPlayerRound>, SquadOvError> {
+    Ok(
+        sqlx::query_as::<_, CsgoEconomyPlayerRound>(&format!("
+            {base}
+            WHERE cmv.user_id = $1
+                AND cmv.match_uuid IS NOT NULL
+            ",
+            base=CSGO_ECONOMY_ROW_QUERY,
+        ))
+            .bind(user_id)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+// Only the Steam accounts the user proved they own by signing in through Steam. The client reported
+// links in steam_user_links aren't verified so they can't be used to decide whose point of view a match is.
+pub async fn get_steam_ids_for_user<'a, T>(ex: T, user_id: i64) -> Result<HashSet<i64>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query!(
+            r#"
+            SELECT steam_id AS "steam_id!"
+            FROM squadov.view_verified_steam_user_links
+            WHERE user_id = $1
+            "#,
+            user_id,
+        )
+            .fetch_all(ex)
+            .await?
+            .into_iter()
+            .map(|x| { x.steam_id })
+            .collect()
+    )
+}
+
 async fn store_csgo_common_players_for_container(ex: &mut Transaction<'_, Postgres>, container_id: i64, players: &[CsgoCommonPlayer]) -> Result<HashSet<i32>, SquadOvError> {
     if players.is_empty() {
         return Ok(HashSet::new());
@@ -856,7 +1218,9 @@ async fn store_csgo_common_rounds_for_container(ex: &mut Transaction<'_, Postgre
             has_helmet,
             team,
             weapons,
-            money
+            money,
+            start_money,
+            spent
         )
         VALUES 
     "));
@@ -901,6 +1265,53 @@ async fn store_csgo_common_rounds_for_container(ex: &mut Transaction<'_, Postgre
         VALUES 
     "));
 
+    let mut round_utility_sql: Vec<String> = Vec::new();
+    let mut added_round_utility: i32 = 0;
+    round_utility_sql.push(String::from("
+        INSERT INTO squadov.csgo_event_container_round_utility (
+            container_id,
+            round_num,
+            utility_id,
+            tm,
+            kind,
+            thrower,
+            x,
+            y,
+            z
+        )
+        VALUES 
+    "));
+
+    let mut round_utility_damage_sql: Vec<String> = Vec::new();
+    let mut added_round_utility_damage: i32 = 0;
+    round_utility_damage_sql.push(String::from("
+        INSERT INTO squadov.csgo_event_container_round_utility_damage (
+            container_id,
+            round_num,
+            utility_id,
+            tm,
+            receiver,
+            damage_health,
+            damage_armor
+        )
+        VALUES 
+    "));
+
+    let mut round_blinds_sql: Vec<String> = Vec::new();
+    let mut added_round_blinds: i32 = 0;
+    round_blinds_sql.push(String::from("
+        INSERT INTO squadov.csgo_event_container_round_blinds (
+            container_id,
+            round_num,
+            tm,
+            victim,
+            attacker,
+            duration,
+            utility_id
+        )
+        VALUES 
+    "));
+
     for r in rounds {
         rounds_sql.push(format!("(
             {container_id},
@@ -955,7 +1366,9 @@ async fn store_csgo_common_rounds_for_container(ex: &mut Transaction<'_, Postgre
                     {has_helmet},
                     {team},
                     {weapons},
-                    {money}
+                    {money},
+                    {start_money},
+                    {spent}
                 )",
                     container_id=container_id,
                     round_num=ps.round_num,
@@ -975,6 +1388,8 @@ async fn store_csgo_common_rounds_for_container(ex: &mut Transaction<'_, Postgre
                     team=ps.team as i32,
                     weapons=crate::sql_format_integer_array(&ps.weapons.iter().map(|x| { *x as i32 }).collect::<Vec<i32>>()),
                     money=crate::sql_format_option_value(&ps.money),
+                    start_money=crate::sql_format_option_value(&ps.start_money),
+                    spent=crate::sql_format_option_value(&ps.spent),
                 ));
                 round_stats_sql.push(String::from(","));
                 added_round_stats += 1;
@@ -1074,6 +1489,86 @@ async fn store_csgo_common_rounds_for_container(ex: &mut Transaction<'_, Postgre
             round_damage_sql.push(String::from(","));
             added_round_damage += 1;
         }
+
+        for u in &r.utility {
+            // Keep the grenade around even if we don't know who threw it so that we still know where it landed.
+            let thrower = u.thrower.filter(|x| { valid_players.contains(x) });
+            round_utility_sql.push(format!("(
+                {container_id},
+                {round_num},
+                {utility_id},
+                {tm},
+                {kind},
+                {thrower},
+                {x},
+                {y},
+                {z}
+            )",
+                container_id=container_id,
+                round_num=u.round_num,
+                utility_id=u.utility_id,
+                tm=crate::sql_format_time(&u.tm),
+                kind=u.kind as i32,
+                thrower=crate::sql_format_option_value(&thrower),
+                x=u.x,
+                y=u.y,
+                z=u.z,
+            ));
+            round_utility_sql.push(String::from(","));
+            added_round_utility += 1;
+
+            for d in &u.damage {
+                if !valid_players.contains(&d.receiver) {
+                    continue;
+                }
+
+                round_utility_damage_sql.push(format!("(
+                    {container_id},
+                    {round_num},
+                    {utility_id},
+                    {tm},
+                    {receiver},
+                    {damage_health},
+                    {damage_armor}
+                )",
+                    container_id=container_id,
+                    round_num=u.round_num,
+                    utility_id=u.utility_id,
+                    tm=crate::sql_format_time(&d.tm),
+                    receiver=d.receiver,
+                    damage_health=d.damage_health,
+                    damage_armor=d.damage_armor,
+                ));
+                round_utility_damage_sql.push(String::from(","));
+                added_round_utility_damage += 1;
+            }
+        }
+
+        for b in &r.blinds {
+            if !valid_players.contains(&b.victim) {
+                continue;
+            }
+
+            round_blinds_sql.push(format!("(
+                {container_id},
+                {round_num},
+                {tm},
+                {victim},
+                {attacker},
+                {duration},
+                {utility_id}
+            )",
+                container_id=container_id,
+                round_num=b.round_num,
+                tm=crate::sql_format_time(&b.tm),
+                victim=b.victim,
+                attacker=crate::sql_format_option_value(&b.attacker.filter(|x| { valid_players.contains(x) })),
+                duration=b.duration,
+                utility_id=crate::sql_format_option_value(&b.utility_id),
+            ));
+            round_blinds_sql.push(String::from(","));
+            added_round_blinds += 1;
+        }
     }
 
     rounds_sql.truncate(rounds_sql.len() - 1);
@@ -1097,10 +1592,28 @@ async fn store_csgo_common_rounds_for_container(ex: &mut Transaction<'_, Postgre
         round_damage_sql.push(String::from(" ON CONFLICT DO NOTHING"));
         sqlx::query(&round_damage_sql.join("")).execute(&mut *ex).await?;
     }
+
+    if added_round_utility > 0 {
+        round_utility_sql.truncate(round_utility_sql.len() - 1);
+        round_utility_sql.push(String::from(" ON CONFLICT DO NOTHING"));
+        sqlx::query(&round_utility_sql.join("")).execute(&mut *ex).await?;
+    }
+
+    if added_round_utility_damage > 0 {
+        round_utility_damage_sql.truncate(round_utility_damage_sql.len() - 1);
+        round_utility_damage_sql.push(String::from(" ON CONFLICT DO NOTHING"));
+        sqlx::query(&round_utility_damage_sql.join("")).execute(&mut *ex).await?;
+    }
+
+    if added_round_blinds > 0 {
+        round_blinds_sql.truncate(round_blinds_sql.len() - 1);
+        round_blinds_sql.push(String::from(" ON CONFLICT DO NOTHING"));
+        sqlx::query(&round_blinds_sql.join("")).execute(&mut *ex).await?;
+    }
     Ok(())
 }
 
-pub async fn store_csgo_common_events_for_view(ex: &mut Transaction<'_, Postgres>, view_uuid: &Uuid, events: &CsgoCommonEventContainer) -> Result<(), SquadOvError> {
+pub async fn store_csgo_common_events_for_view(ex: &mut Transaction<'_, Postgres>, view_uuid: &Uuid, events: &CsgoCommonEventContainer) -> Result<i64, SquadOvError> {
     let event_container_id = sqlx::query!(
         "
         INSERT INTO squadov.csgo_event_container (
@@ -1122,5 +1635,42 @@ pub async fn store_csgo_common_events_for_view(ex: &mut Transaction<'_, Postgres
 
     let valid_players = store_csgo_common_players_for_container(&mut *ex, event_container_id, &events.players).await?;
     store_csgo_common_rounds_for_container(&mut *ex, event_container_id, &events.rounds, &valid_players).await?;
-    Ok(())
-}
\ No newline at end of file
+    Ok(event_container_id)
+}
+
+// Steam ID, Steam name and team (as of the last round they played) of every player in the user's latest events for the match.
+pub async fn get_csgo_match_player_teams<'a, T>(ex: T, match_uuid: &Uuid, user_id: i64) -> Result<Vec<(i64, Option<String>, i32)>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, (i64, Option<String>, i32)>(
+            "
+            WITH container AS (
+                SELECT cec.id
+                FROM squadov.csgo_match_views AS cmv
+                INNER JOIN squadov.csgo_event_container AS cec
+                    ON cec.view_uuid = cmv.view_uuid
+                WHERE cmv.match_uuid = $1
+                    AND cmv.user_id = $2
+                ORDER BY cec.id DESC
+                LIMIT 1
+            )
+            SELECT DISTINCT ON (ccp.user_id) ccp.steam_id, suc.steam_name, rps.team
+            FROM container
+            INNER JOIN squadov.csgo_event_container_players AS ccp
+                ON ccp.container_id = container.id
+            INNER JOIN squadov.csgo_event_container_round_player_stats AS rps
+                ON rps.container_id = ccp.container_id
+                    AND rps.user_id = ccp.user_id
+            LEFT JOIN squadov.steam_users_cache AS suc
+                ON suc.steam_id = ccp.steam_id
+            ORDER BY ccp.user_id, rps.round_num DESC
+            "
+        )
+            .bind(match_uuid)
+            .bind(user_id)
+            .fetch_all(ex)
+            .await?
+    )
+}
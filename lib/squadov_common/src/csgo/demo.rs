@@ -35,6 +35,11 @@ use super::math::{
     parse_csgo_qangle,
 };
 use super::weapon::{CsgoWeapon, csgo_string_to_weapon};
+use super::positions::{
+    CsgoPositionSample,
+    CsgoPositionEntityKind,
+    CSGO_POSITION_SAMPLE_HZ,
+};
 use std::collections::{HashMap, VecDeque};
 use std::sync::{RwLock, Arc};
 use num_enum::TryFromPrimitive;
@@ -46,6 +51,20 @@ const MAX_USERDATA_BITS: usize = 14;
 
 const CSGO_PLAYER_MAX_WEAPONS: i32 = 64;
 const CSGO_WEAPON_ID_MASK: i32 = 0x7FF;
+// Weapon entity classes that don't follow the CWeapon* naming scheme.
+const CSGO_NON_PREFIXED_WEAPON_CLASSES: &'static [&'static str] = &[
+    "CAK47",
+    "CC4",
+    "CDEagle",
+    "CKnife",
+    "CKnifeGG",
+    "CFlashbang",
+    "CHEGrenade",
+    "CSmokeGrenade",
+    "CMolotovGrenade",
+    "CIncendiaryGrenade",
+    "CDecoyGrenade",
+];
 
 #[derive(Debug)]
 pub struct CsgoDemoHeader {
@@ -294,6 +313,9 @@ pub struct CsgoDemoKill {
     pub wallbang: bool,
     pub noscope: bool,
     pub weapon: CsgoWeapon,
+    // Where the players were at the exact tick of the kill (if we know).
+    pub victim_position: Option<CsgoVector>,
+    pub killer_position: Option<CsgoVector>,
 }
 
 #[derive(Debug, Clone, Copy, TryFromPrimitive, Serialize_repr)]
@@ -323,6 +345,38 @@ pub struct CsgoDemoDamage {
     pub hitgroup: CsgoDemoHitGroup,
 }
 
+#[derive(Debug, Clone, Copy, Serialize_repr, TryFromPrimitive, PartialEq)]
+#[repr(i32)]
+pub enum CsgoDemoUtilityKind {
+    He,
+    Flashbang,
+    Smoke,
+    // Where the molotov/incendiary burst.
+    Molotov,
+    // Where the resulting fire started burning (this can differ from where the molotov burst).
+    Fire,
+}
+
+#[derive(Debug)]
+pub struct CsgoDemoUtility {
+    pub tick: i32,
+    pub kind: CsgoDemoUtilityKind,
+    pub thrower: Option<i32>,
+    pub entity_id: Option<i32>,
+    pub position: CsgoVector,
+}
+
+#[derive(Debug)]
+pub struct CsgoDemoBlind {
+    pub tick: i32,
+    pub victim: i32,
+    pub attacker: Option<i32>,
+    // The flashbang projectile that caused the blind.
+    pub entity_id: Option<i32>,
+    // In seconds.
+    pub duration: f32,
+}
+
 #[derive(Debug)]
 pub struct CsgoDemoRoundPlayerInfo {
     pub kills: i32,
@@ -343,6 +397,9 @@ pub struct CsgoDemoRoundPlayerInfo {
     pub has_helmet: bool,
     pub money: i32,
     pub team: CsgoTeam,
+    // Money at the start of the round (i.e. before buying) and how much of it was spent.
+    pub start_money: Option<i32>,
+    pub spent: Option<i32>,
 }
 
 impl Default for CsgoDemoRoundPlayerInfo {
@@ -366,6 +423,8 @@ impl Default for CsgoDemoRoundPlayerInfo {
             has_helmet: false,
             money: 0,
             team: CsgoTeam::TeamSpectate,
+            start_money: None,
+            spent: None,
         }
     }
 }
@@ -392,6 +451,11 @@ pub struct CsgoDemoRound {
     // Players in this round and their econ/weapons.
     pub players: HashMap<i32, CsgoDemoRoundPlayerInfo>,
     pub round_frozen: bool,
+    // Positions of players and dropped weapons sampled at CSGO_POSITION_SAMPLE_HZ.
+    pub positions: Vec<CsgoPositionSample>,
+    // Grenade detonations and the players they blinded.
+    pub utility: Vec<CsgoDemoUtility>,
+    pub blinds: Vec<CsgoDemoBlind>,
 }
 
 impl Default for CsgoDemoRound {
@@ -409,11 +473,65 @@ impl Default for CsgoDemoRound {
             damage: vec![],
             players: HashMap::new(),
             round_frozen: false,
+            positions: vec![],
+            utility: vec![],
+            blinds: vec![],
         }
     }
 }
 
+// How far apart (in seconds) a grenade detonation and the damage/blind it caused are allowed to be.
+const CSGO_UTILITY_DETONATE_WINDOW_SECONDS: f32 = 0.5;
+// Fires burn for 7 seconds - give it a bit of leeway.
+const CSGO_UTILITY_FIRE_WINDOW_SECONDS: f32 = 8.0;
+
 impl CsgoDemoRound {
+    // Figure out which grenade (index into self.utility) caused the given damage, if any.
+    // This needs to happen after the fact since player_hurt can come before the detonate event on the same tick.
+    pub fn find_utility_for_damage(&self, damage: &CsgoDemoDamage, tick_rate: f32) -> Option<usize> {
+        let attacker = damage.attacker?;
+        let (kinds, window): (&[CsgoDemoUtilityKind], f32) = match damage.weapon {
+            CsgoWeapon::He => (&[CsgoDemoUtilityKind::He], CSGO_UTILITY_DETONATE_WINDOW_SECONDS),
+            CsgoWeapon::Molotov | CsgoWeapon::Incendiary => (&[CsgoDemoUtilityKind::Fire, CsgoDemoUtilityKind::Molotov], CSGO_UTILITY_FIRE_WINDOW_SECONDS),
+            _ => return None,
+        };
+        let window_ticks = (window * tick_rate).ceil() as i32;
+        let detonate_ticks = (CSGO_UTILITY_DETONATE_WINDOW_SECONDS * tick_rate).ceil() as i32;
+
+        // Prefer the kinds in order (e.g. the fire over the molotov burst) and then the closest in time.
+        for kind in kinds {
+            let best = self.utility.iter().enumerate()
+                .filter(|(_, u)| {
+                    u.kind == *kind && u.thrower == Some(attacker) &&
+                        u.tick - detonate_ticks <= damage.tick && damage.tick - u.tick <= window_ticks
+                })
+                .min_by_key(|(_, u)| { (damage.tick - u.tick).abs() })
+                .map(|(idx, _)| { idx });
+
+            if best.is_some() {
+                return best;
+            }
+        }
+        None
+    }
+
+    pub fn find_utility_for_blind(&self, blind: &CsgoDemoBlind, tick_rate: f32) -> Option<usize> {
+        let flashes = self.utility.iter().enumerate().filter(|(_, u)| { u.kind == CsgoDemoUtilityKind::Flashbang });
+        if let Some(entity_id) = blind.entity_id {
+            if let Some((idx, _)) = flashes.clone().find(|(_, u)| { u.entity_id == Some(entity_id) }) {
+                return Some(idx);
+            }
+        }
+
+        // Older demos don't have the entity ID on player_blind so fall back to the thrower + time.
+        let attacker = blind.attacker?;
+        let window_ticks = (CSGO_UTILITY_DETONATE_WINDOW_SECONDS * tick_rate).ceil() as i32;
+        flashes
+            .filter(|(_, u)| { u.thrower == Some(attacker) && (u.tick - blind.tick).abs() <= window_ticks })
+            .min_by_key(|(_, u)| { (u.tick - blind.tick).abs() })
+            .map(|(idx, _)| { idx })
+    }
+
     
     fn plant_bomb(&mut self, tick: i32, site: CsgoDemoBombSite, player: i32) {
         let mut new_state = CsgoDemoBombState::default();
@@ -515,6 +633,7 @@ pub struct CsgoDemo {
     pub player_info: HashMap<i32, CsgoDemoPlayerInfo>,
     model_precache: HashMap<i32, String>,
     pub entities: CsgoEntityScene,
+    last_position_sample_tick: Option<i32>,
 }
 
 impl Default for CsgoDemo {
@@ -527,12 +646,28 @@ impl Default for CsgoDemo {
             player_info: HashMap::new(),
             model_precache: HashMap::new(),
             entities: CsgoEntityScene::default(),
+            last_position_sample_tick: None,
         }
     }
 }
 
 type CsgoParsedGameEventMessage = HashMap<String, csvc_msg_game_event::KeyT>;
 
+fn parse_csgo_utility_event(tick: i32, kind: CsgoDemoUtilityKind, mut msg: CsgoParsedGameEventMessage) -> CsgoDemoUtility {
+    CsgoDemoUtility{
+        tick,
+        kind,
+        // inferno_startburn doesn't tell us who threw it.
+        thrower: msg.remove("userid").map(|x| { x.val_short() }),
+        entity_id: msg.remove("entityid").map(|x| { x.val_short() }),
+        position: CsgoVector{
+            x: msg.remove("x").map(|x| { x.val_float() }).unwrap_or(0.0),
+            y: msg.remove("y").map(|x| { x.val_float() }).unwrap_or(0.0),
+            z: msg.remove("z").map(|x| { x.val_float() }).unwrap_or(0.0),
+        },
+    }
+}
+
 fn parse_csgo_game_event_message(event: CsvcMsgGameEvent, desc: &csvc_msg_game_event_list::DescriptorT) -> Result<CsgoParsedGameEventMessage, SquadOvError> {
     let mut msg = HashMap::new();
 
@@ -566,10 +701,12 @@ impl CsgoDemo {
                         // Determine who died and who killed them (and how they died).
                         // We want to keep these events associated with rounds.
                         let mut msg = parse_csgo_game_event_message(event, desc)?;
+                        let victim = msg.remove("userid").ok_or(SquadOvError::NotFound)?.val_short();
+                        let killer = msg.remove("attacker").map(|x| { x.val_short() });
                         let kill = CsgoDemoKill{
                             tick,
-                            victim: msg.remove("userid").ok_or(SquadOvError::NotFound)?.val_short(),
-                            killer: msg.remove("attacker").map(|x| { x.val_short() }),
+                            victim,
+                            killer,
                             assister: msg.remove("assister").map(|x| { x.val_short() }),
                             flash_assist: msg.remove("assistedflash").map(|x| { x.val_bool() }).unwrap_or(false),
                             headshot: msg.remove("headshot").map(|x| { x.val_bool() }).unwrap_or(false),
@@ -578,6 +715,8 @@ impl CsgoDemo {
                             wallbang: msg.remove("penetrated").map(|x| { x.val_short() }).unwrap_or(0) > 0,
                             noscope: msg.remove("noscope").map(|x| { x.val_bool() }).unwrap_or(false),
                             weapon: csgo_string_to_weapon(&msg.remove("weapon").map(|x| { String::from(x.val_string()) }).unwrap_or(String::new())),
+                            victim_position: Self::get_player_position(&self.player_info, &self.entities, victim),
+                            killer_position: killer.and_then(|x| { Self::get_player_position(&self.player_info, &self.entities, x) }),
                         };
                         round.kills.push(kill);
                     }
@@ -603,6 +742,46 @@ impl CsgoDemo {
                     }
                 }
             },
+            "hegrenade_detonate" | "flashbang_detonate" | "smokegrenade_detonate" | "molotov_detonate" | "inferno_startburn" => {
+                log::debug!("csgo utility {} at: {}", event_name, tick);
+                let tick_rate = self.tick_rate();
+                if let Some(round) = self.rounds.get_mut(current_round_idx) {
+                    if round.round_frozen {
+                        let kind = match event_name {
+                            "hegrenade_detonate" => CsgoDemoUtilityKind::He,
+                            "flashbang_detonate" => CsgoDemoUtilityKind::Flashbang,
+                            "smokegrenade_detonate" => CsgoDemoUtilityKind::Smoke,
+                            "molotov_detonate" => CsgoDemoUtilityKind::Molotov,
+                            _ => CsgoDemoUtilityKind::Fire,
+                        };
+
+                        let mut utility = parse_csgo_utility_event(tick, kind, parse_csgo_game_event_message(event, desc)?);
+                        if kind == CsgoDemoUtilityKind::Fire && utility.thrower.is_none() {
+                            // The fire comes from whichever molotov burst most recently.
+                            let window_ticks = (CSGO_UTILITY_DETONATE_WINDOW_SECONDS * tick_rate).ceil() as i32;
+                            utility.thrower = round.utility.iter().rev()
+                                .find(|u| { u.kind == CsgoDemoUtilityKind::Molotov && tick - u.tick <= window_ticks })
+                                .and_then(|u| { u.thrower });
+                        }
+                        round.utility.push(utility);
+                    }
+                }
+            },
+            "player_blind" => {
+                log::debug!("csgo player blind at: {}", tick);
+                if let Some(round) = self.rounds.get_mut(current_round_idx) {
+                    if round.round_frozen {
+                        let mut msg = parse_csgo_game_event_message(event, desc)?;
+                        round.blinds.push(CsgoDemoBlind{
+                            tick,
+                            victim: msg.remove("userid").ok_or(SquadOvError::NotFound)?.val_short(),
+                            attacker: msg.remove("attacker").map(|x| { x.val_short() }),
+                            entity_id: msg.remove("entityid").map(|x| { x.val_short() }),
+                            duration: msg.remove("blind_duration").map(|x| { x.val_float() }).unwrap_or(0.0),
+                        });
+                    }
+                }
+            },
             "round_start" => {
                 log::debug!("csgo round start at: {}", tick);
 
@@ -621,9 +800,13 @@ impl CsgoDemo {
                 };
                 new_round.round_start_tick = tick;
 
-                // Populate player info.
-                for (uid, _player) in &self.player_info {
-                    new_round.players.insert(*uid, CsgoDemoRoundPlayerInfo::default());
+                // Populate player info. The round's money has already been handed out by the time round_start comes in.
+                for (uid, player) in &self.player_info {
+                    let mut info = CsgoDemoRoundPlayerInfo::default();
+                    if let Some(player_entity) = self.entities.get_entity(player.entity_id) {
+                        info.start_money = player_entity.get_prop("m_iAccount").and_then(|x| { x.value.v_i32 });
+                    }
+                    new_round.players.insert(*uid, info);
                 }
 
                 if overwrite {
@@ -738,6 +921,10 @@ impl CsgoDemo {
                                         player_round_info.kill_reward = prop.value.v_i32.unwrap_or(0);
                                     }
                                 }
+
+                                if let Some(spent) = player_entity.get_prop("m_iCashSpentThisRound").and_then(|x| { x.value.v_i32 }) {
+                                    player_round_info.spent = Some(spent);
+                                }
                             }
                         }
                     }
@@ -901,6 +1088,13 @@ impl CsgoDemo {
                                 if let Some(account_prop) = player_entity.get_prop("m_iAccount") {
                                     player_round_info.money = account_prop.value.v_i32.unwrap_or(0);
                                 }
+
+                                if let Some(equipment_prop) = player_entity.get_prop("m_unCurrentEquipmentValue") {
+                                    player_round_info.equipment_value = equipment_prop.value.v_i32.unwrap_or(0);
+                                }
+
+                                // This gets overwritten at the end of the round if the demo has the total spent for the round.
+                                player_round_info.spent = player_round_info.start_money.map(|x| { std::cmp::max(x - player_round_info.money, 0) });
                             }
                         }
                     }
@@ -1043,13 +1237,196 @@ impl CsgoDemo {
         Ok(())
     }
 
+    pub fn string_tables(&self) -> &[CsgoDemoStringTable] {
+        &self.string_tables
+    }
+
     pub fn on_data_table(&mut self, table: Arc<RwLock<CsgoDemoDataTable>>) -> Result<(), SquadOvError> {
         self.entities.connect_data_table(table);
         Ok(())
     }
 
-    pub fn handle_entity_update(&mut self, data: CsvcMsgPacketEntities) -> Result<(), SquadOvError> {
+    pub fn handle_entity_update(&mut self, tick: i32, data: CsvcMsgPacketEntities) -> Result<(), SquadOvError> {
         self.entities.handle_entity_update(data)?;
+
+        let interval = self.position_sample_interval();
+        if self.last_position_sample_tick.map(|x| { tick - x >= interval }).unwrap_or(true) {
+            self.sample_positions(tick)?;
+            self.last_position_sample_tick = Some(tick);
+        }
+        Ok(())
+    }
+
+    pub fn tick_rate(&self) -> f32 {
+        if self.header.playback_time > 0.0 && self.header.playback_ticks > 0 {
+            self.header.playback_ticks as f32 / self.header.playback_time
+        } else {
+            // Default matchmaking tick rate.
+            64.0
+        }
+    }
+
+    fn position_sample_interval(&self) -> i32 {
+        std::cmp::max((self.tick_rate() / CSGO_POSITION_SAMPLE_HZ).round() as i32, 1)
+    }
+
+    // This needs to take the individual fields instead of &self so that we can call it while holding a mutable reference to the current round.
+    fn get_player_position(player_info: &HashMap<i32, CsgoDemoPlayerInfo>, entities: &CsgoEntityScene, user_id: i32) -> Option<CsgoVector> {
+        player_info.get(&user_id)
+            .and_then(|x| { entities.get_entity(x.entity_id) })
+            .and_then(|x| { x.get_origin() })
+    }
+
+    fn get_weapon_for_entity_handle(&self, handle: i32) -> Result<Option<CsgoWeapon>, SquadOvError> {
+        let weapon_entity_id = handle & CSGO_WEAPON_ID_MASK;
+        if weapon_entity_id == CSGO_WEAPON_ID_MASK {
+            return Ok(None);
+        }
+
+        Ok(if let Some(weapon_entity) = self.entities.get_entity(weapon_entity_id) {
+            self.entities.get_class_name(weapon_entity.class as i32)?.map(|x| { weapon_entity.to_weapon(&x) })
+        } else {
+            None
+        })
+    }
+
+    fn sample_positions(&mut self, tick: i32) -> Result<(), SquadOvError> {
+        let current_round_idx = if self.rounds.is_empty() {
+            return Ok(());
+        } else {
+            self.rounds.len() - 1
+        };
+
+        // Nothing to track once the round is over.
+        if self.rounds[current_round_idx].round_end_tick.is_some() {
+            return Ok(());
+        }
+
+        let entity_to_user: HashMap<i32, i32> = self.player_info.iter().map(|(uid, p)| { (p.entity_id, *uid) }).collect();
+        let mut samples: Vec<CsgoPositionSample> = vec![];
+        for entity in self.entities.all_entities() {
+            let class_name = match self.entities.get_class_name(entity.class as i32)? {
+                Some(x) => x,
+                None => continue,
+            };
+
+            if class_name == "CCSPlayer" {
+                let origin = match entity.get_origin() {
+                    Some(x) => x,
+                    None => continue,
+                };
+                let (pitch, yaw) = entity.get_eye_angles();
+
+                samples.push(CsgoPositionSample{
+                    tick,
+                    entity_id: entity.id,
+                    user_id: entity_to_user.get(&entity.id).cloned(),
+                    kind: CsgoPositionEntityKind::Player,
+                    x: origin.x,
+                    y: origin.y,
+                    z: origin.z,
+                    yaw,
+                    pitch,
+                    // m_lifeState is LIFE_ALIVE (0) when the player is alive.
+                    alive: entity.get_prop("m_lifeState").and_then(|x| { x.value.v_i32 }).unwrap_or(1) == 0,
+                    weapon: match entity.get_prop("m_hActiveWeapon").and_then(|x| { x.value.v_i32 }) {
+                        Some(handle) => self.get_weapon_for_entity_handle(handle)?.map(|x| { x as i32 }),
+                        None => None,
+                    },
+                });
+            } else if class_name.starts_with("CWeapon") || CSGO_NON_PREFIXED_WEAPON_CLASSES.contains(&class_name.as_str()) {
+                // Weapons that are being held by a player don't need to be tracked separately.
+                let owner = entity.get_prop("m_hOwnerEntity").and_then(|x| { x.value.v_i32 }).unwrap_or(CSGO_WEAPON_ID_MASK) & CSGO_WEAPON_ID_MASK;
+                if owner != CSGO_WEAPON_ID_MASK {
+                    continue;
+                }
+
+                let origin = match entity.get_origin() {
+                    Some(x) => x,
+                    None => continue,
+                };
+
+                samples.push(CsgoPositionSample{
+                    tick,
+                    entity_id: entity.id,
+                    user_id: None,
+                    kind: CsgoPositionEntityKind::DroppedWeapon,
+                    x: origin.x,
+                    y: origin.y,
+                    z: origin.z,
+                    yaw: 0.0,
+                    pitch: 0.0,
+                    alive: false,
+                    weapon: Some(entity.to_weapon(&class_name) as i32),
+                });
+            }
+        }
+
+        self.rounds[current_round_idx].positions.extend(samples);
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utility(tick: i32, kind: CsgoDemoUtilityKind, thrower: i32, entity_id: i32) -> CsgoDemoUtility {
+        CsgoDemoUtility{
+            tick,
+            kind,
+            thrower: Some(thrower),
+            entity_id: Some(entity_id),
+            position: CsgoVector{x: 0.0, y: 0.0, z: 0.0},
+        }
+    }
+
+    fn damage(tick: i32, attacker: i32, weapon: CsgoWeapon) -> CsgoDemoDamage {
+        CsgoDemoDamage{
+            tick,
+            attacker: Some(attacker),
+            receiver: 5,
+            remaining_health: 50,
+            remaining_armor: 0,
+            damage_health: 50,
+            damage_armor: 0,
+            weapon,
+            hitgroup: CsgoDemoHitGroup::Generic,
+        }
+    }
+
+    #[test]
+    fn test_link_utility_damage() {
+        let mut round = CsgoDemoRound::default();
+        round.utility.push(utility(100, CsgoDemoUtilityKind::He, 1, 200));
+        round.utility.push(utility(100, CsgoDemoUtilityKind::He, 2, 201));
+        round.utility.push(utility(300, CsgoDemoUtilityKind::Molotov, 1, 202));
+        round.utility.push(utility(301, CsgoDemoUtilityKind::Fire, 1, 203));
+
+        // Damage can show up on the same tick just before the detonate event.
+        assert_eq!(round.find_utility_for_damage(&damage(100, 2, CsgoWeapon::He), 64.0), Some(1));
+        assert_eq!(round.find_utility_for_damage(&damage(600, 1, CsgoWeapon::Incendiary), 64.0), Some(3));
+        // Way too late for the HE to have done this and gunfire is never utility damage.
+        assert_eq!(round.find_utility_for_damage(&damage(400, 1, CsgoWeapon::He), 64.0), None);
+        assert_eq!(round.find_utility_for_damage(&damage(100, 1, CsgoWeapon::Ak47), 64.0), None);
+    }
+
+    #[test]
+    fn test_link_utility_blind() {
+        let mut round = CsgoDemoRound::default();
+        round.utility.push(utility(100, CsgoDemoUtilityKind::Flashbang, 1, 200));
+        round.utility.push(utility(102, CsgoDemoUtilityKind::Flashbang, 1, 201));
+
+        let mut blind = CsgoDemoBlind{
+            tick: 100,
+            victim: 3,
+            attacker: Some(1),
+            entity_id: Some(201),
+            duration: 2.5,
+        };
+        assert_eq!(round.find_utility_for_blind(&blind, 64.0), Some(1));
+
+        blind.entity_id = None;
+        assert_eq!(round.find_utility_for_blind(&blind, 64.0), Some(0));
+    }
+}
@@ -0,0 +1,443 @@
+use crate::csgo::{
+    demo::CsgoTeam,
+    schema::CsgoEventSource,
+};
+use serde::{Serialize, Deserialize};
+use serde_repr::Serialize_repr;
+use num_enum::TryFromPrimitive;
+use uuid::Uuid;
+use std::collections::{HashMap, HashSet, BTreeMap};
+use std::convert::TryFrom;
+
+// Loss bonus rules (since the 2019 update): every team starts a half with one loss already counted, a loss
+// adds one to the counter and a win only takes one away.
+const CSGO_LOSS_BONUS_BASE: i32 = 1400;
+const CSGO_LOSS_BONUS_INCREMENT: i32 = 500;
+const CSGO_LOSS_BONUS_MAX_COUNT: i32 = 5;
+const CSGO_LOSS_BONUS_HALF_START_COUNT: i32 = 1;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CsgoEconomyConfig {
+    // Round numbers start at 0 so by default these are the first round of each half of a competitive (MR15) match.
+    // Overtime rounds aren't pistol rounds.
+    pub pistol_rounds: Vec<i32>,
+    // Game modes that play a different number of rounds per half (e.g. Wingman is MR8).
+    pub mode_pistol_rounds: HashMap<String, Vec<i32>>,
+    // All thresholds are per player averages across the team.
+    pub eco_max_equipment_value: i32,
+    pub full_buy_min_equipment_value: i32,
+    // Fraction of the available money that needs to be spent for a buy that isn't a full buy to count as a force buy.
+    pub force_min_spend_ratio: f32,
+}
+
+impl Default for CsgoEconomyConfig {
+    fn default() -> Self {
+        Self {
+            pistol_rounds: vec![0, 15],
+            mode_pistol_rounds: vec![
+                (String::from("scrimcomp2v2"), vec![0, 8]),
+            ].into_iter().collect(),
+            eco_max_equipment_value: 1500,
+            full_buy_min_equipment_value: 4000,
+            force_min_spend_ratio: 0.75,
+        }
+    }
+}
+
+impl CsgoEconomyConfig {
+    pub fn is_pistol_round(&self, mode: &str, round_num: i32) -> bool {
+        self.mode_pistol_rounds.get(mode).unwrap_or(&self.pistol_rounds).contains(&round_num)
+    }
+}
+
+#[derive(Serialize_repr, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, TryFromPrimitive)]
+#[repr(i32)]
+pub enum CsgoBuyType {
+    Pistol,
+    Eco,
+    Force,
+    HalfBuy,
+    FullBuy,
+}
+
+// One player's economy for one round out of one event container.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct CsgoEconomyPlayerRound {
+    pub view_uuid: Uuid,
+    pub event_source: i32,
+    // The game mode of the view (e.g. competitive) so we know which rounds start a half.
+    pub mode: String,
+    pub round_num: i32,
+    pub steam_id: i64,
+    pub team: i32,
+    pub winning_team: Option<i32>,
+    pub equipment_value: Option<i32>,
+    // Money left over after buying.
+    pub money: Option<i32>,
+    pub start_money: Option<i32>,
+    pub spent: Option<i32>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoTeamRoundEconomy {
+    pub round_num: i32,
+    pub team: CsgoTeam,
+    pub buy_type: CsgoBuyType,
+    pub players: i32,
+    // These are all totals across the players on the team that we know about.
+    pub equipment_value: i32,
+    pub start_money: Option<i32>,
+    pub spent: Option<i32>,
+    pub remaining_money: Option<i32>,
+    pub won: Option<bool>,
+    // The loss counter going into this round and how much each player will get if the team loses this round.
+    pub loss_count: i32,
+    pub loss_bonus: i32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoBuyTypeStats {
+    pub buy_type: CsgoBuyType,
+    pub rounds: i32,
+    pub wins: i32,
+    pub win_rate: f64,
+}
+
+#[derive(Default, Debug)]
+struct CsgoBuyTypeAccumulator {
+    stats: BTreeMap<CsgoBuyType, (i32, i32)>,
+}
+
+impl CsgoBuyTypeAccumulator {
+    fn add(&mut self, buy_type: CsgoBuyType, won: bool) {
+        let entry = self.stats.entry(buy_type).or_insert((0, 0));
+        entry.0 += 1;
+        if won {
+            entry.1 += 1;
+        }
+    }
+
+    fn merge(&mut self, other: &CsgoBuyTypeAccumulator) {
+        for (buy_type, (rounds, wins)) in &other.stats {
+            let entry = self.stats.entry(*buy_type).or_insert((0, 0));
+            entry.0 += rounds;
+            entry.1 += wins;
+        }
+    }
+
+    fn finish(&self) -> Vec<CsgoBuyTypeStats> {
+        self.stats.iter().map(|(buy_type, (rounds, wins))| {
+            CsgoBuyTypeStats{
+                buy_type: *buy_type,
+                rounds: *rounds,
+                wins: *wins,
+                win_rate: if *rounds > 0 { *wins as f64 / *rounds as f64 } else { 0.0 },
+            }
+        }).collect()
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoMatchEconomy {
+    // Sorted by round and then team - this is what the team economy graph gets drawn from.
+    pub team_rounds: Vec<CsgoTeamRoundEconomy>,
+    // Across both teams.
+    pub buy_types: Vec<CsgoBuyTypeStats>,
+    // Only the rounds played by the team the user was on.
+    pub user_buy_types: Vec<CsgoBuyTypeStats>,
+    #[serde(skip)]
+    user_acc: CsgoBuyTypeAccumulator,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoUserEconomySummary {
+    pub matches: i32,
+    pub buy_types: Vec<CsgoBuyTypeStats>,
+}
+
+pub fn classify_csgo_buy(config: &CsgoEconomyConfig, mode: &str, round_num: i32, players: i32, equipment_value: i32, start_money: Option<i32>, spent: Option<i32>, remaining_money: Option<i32>) -> CsgoBuyType {
+    if config.is_pistol_round(mode, round_num) {
+        return CsgoBuyType::Pistol;
+    }
+
+    if players <= 0 {
+        return CsgoBuyType::Eco;
+    }
+
+    let players = players as f32;
+    let avg_equipment = equipment_value as f32 / players;
+
+    if avg_equipment >= config.full_buy_min_equipment_value as f32 {
+        return CsgoBuyType::FullBuy;
+    }
+
+    if avg_equipment <= config.eco_max_equipment_value as f32 {
+        return CsgoBuyType::Eco;
+    }
+
+    let spend_ratio = match (start_money, spent, remaining_money) {
+        (Some(start), Some(spent), _) if start > 0 => spent as f32 / start as f32,
+        (_, _, Some(remaining)) if remaining + equipment_value > 0 => equipment_value as f32 / (remaining + equipment_value) as f32,
+        _ => 0.0,
+    };
+
+    if spend_ratio >= config.force_min_spend_ratio {
+        CsgoBuyType::Force
+    } else {
+        CsgoBuyType::HalfBuy
+    }
+}
+
+fn is_playing_team(team: CsgoTeam) -> bool {
+    match team {
+        CsgoTeam::TeamCT | CsgoTeam::TeamT => true,
+        _ => false,
+    }
+}
+
+// Demo data always wins over GSI data for the same player in the same round.
+fn merge_economy_rows(rows: Vec<CsgoEconomyPlayerRound>) -> Vec<CsgoEconomyPlayerRound> {
+    let mut merged: BTreeMap<(i32, i64), CsgoEconomyPlayerRound> = BTreeMap::new();
+    for r in rows {
+        let key = (r.round_num, r.steam_id);
+        let replace = match merged.get(&key) {
+            Some(existing) => existing.event_source != CsgoEventSource::Demo as i32 && r.event_source == CsgoEventSource::Demo as i32,
+            None => true,
+        };
+
+        if replace {
+            merged.insert(key, r);
+        }
+    }
+    merged.into_iter().map(|(_, v)| { v }).collect()
+}
+
+impl CsgoMatchEconomy {
+    // The rows should all be from the same view (but can be from both the GSI and demo containers).
+    pub fn from_rows(rows: Vec<CsgoEconomyPlayerRound>, user_steam_ids: &HashSet<i64>, config: &CsgoEconomyConfig) -> Self {
+        let rows = merge_economy_rows(rows);
+
+        // (round, team) -> rows
+        let mut team_rounds: BTreeMap<(i32, i32), Vec<&CsgoEconomyPlayerRound>> = BTreeMap::new();
+        let mut user_teams: HashMap<i32, i32> = HashMap::new();
+        let mut round_winners: HashMap<i32, i32> = HashMap::new();
+        for r in &rows {
+            let team = CsgoTeam::try_from(r.team).unwrap_or(CsgoTeam::TeamSpectate);
+            if !is_playing_team(team) {
+                continue;
+            }
+
+            if user_steam_ids.contains(&r.steam_id) {
+                user_teams.insert(r.round_num, r.team);
+            }
+
+            if let Some(winner) = r.winning_team {
+                // Prefer the demo's idea of who won.
+                if r.event_source == CsgoEventSource::Demo as i32 || !round_winners.contains_key(&r.round_num) {
+                    round_winners.insert(r.round_num, winner);
+                }
+            }
+            team_rounds.entry((r.round_num, r.team)).or_insert(vec![]).push(r);
+        }
+
+        let mut ret = Self {
+            team_rounds: vec![],
+            buy_types: vec![],
+            user_buy_types: vec![],
+            user_acc: CsgoBuyTypeAccumulator::default(),
+        };
+
+        let mut all_acc = CsgoBuyTypeAccumulator::default();
+        let mut loss_counts: HashMap<i32, i32> = HashMap::new();
+        for ((round_num, team), players) in team_rounds {
+            let sum_opt = |f: &dyn Fn(&CsgoEconomyPlayerRound) -> Option<i32>| -> Option<i32> {
+                players.iter().map(|x| { f(x) }).fold(None, |acc, x| {
+                    match (acc, x) {
+                        (Some(a), Some(b)) => Some(a + b),
+                        (None, Some(b)) => Some(b),
+                        (a, None) => a,
+                    }
+                })
+            };
+
+            let equipment_value = sum_opt(&|x| { x.equipment_value }).unwrap_or(0);
+            let start_money = sum_opt(&|x| { x.start_money });
+            let spent = sum_opt(&|x| { x.spent });
+            let remaining_money = sum_opt(&|x| { x.money });
+            // Every row comes from the same view so they all have the same mode.
+            let mode = players.first().map(|x| { x.mode.as_str() }).unwrap_or("");
+            let buy_type = classify_csgo_buy(config, mode, round_num, players.len() as i32, equipment_value, start_money, spent, remaining_money);
+            let won = round_winners.get(&round_num).map(|x| { *x == team });
+
+            // The loss counter resets whenever the teams switch sides (i.e. on the pistol round).
+            let loss_count = loss_counts.entry(team).or_insert(CSGO_LOSS_BONUS_HALF_START_COUNT);
+            if buy_type == CsgoBuyType::Pistol {
+                *loss_count = CSGO_LOSS_BONUS_HALF_START_COUNT;
+            }
+            let current_loss_count = *loss_count;
+
+            match won {
+                Some(true) => *loss_count = std::cmp::max(*loss_count - 1, 0),
+                Some(false) => *loss_count = std::cmp::min(*loss_count + 1, CSGO_LOSS_BONUS_MAX_COUNT),
+                None => (),
+            };
+
+            if let Some(won) = won {
+                all_acc.add(buy_type, won);
+                if user_teams.get(&round_num) == Some(&team) {
+                    ret.user_acc.add(buy_type, won);
+                }
+            }
+
+            ret.team_rounds.push(CsgoTeamRoundEconomy{
+                round_num,
+                team: CsgoTeam::try_from(team).unwrap_or(CsgoTeam::TeamSpectate),
+                buy_type,
+                players: players.len() as i32,
+                equipment_value,
+                start_money,
+                spent,
+                remaining_money,
+                won,
+                loss_count: current_loss_count,
+                loss_bonus: CSGO_LOSS_BONUS_BASE + CSGO_LOSS_BONUS_INCREMENT * (std::cmp::min(current_loss_count + 1, CSGO_LOSS_BONUS_MAX_COUNT) - 1),
+            });
+        }
+
+        ret.buy_types = all_acc.finish();
+        ret.user_buy_types = ret.user_acc.finish();
+        ret
+    }
+}
+
+impl CsgoUserEconomySummary {
+    pub fn from_rows(rows: Vec<CsgoEconomyPlayerRound>, user_steam_ids: &HashSet<i64>, config: &CsgoEconomyConfig) -> Self {
+        let mut views: HashMap<Uuid, Vec<CsgoEconomyPlayerRound>> = HashMap::new();
+        for r in rows {
+            views.entry(r.view_uuid.clone()).or_insert(vec![]).push(r);
+        }
+
+        let mut acc = CsgoBuyTypeAccumulator::default();
+        let matches = views.len() as i32;
+        for (_, rows) in views {
+            let economy = CsgoMatchEconomy::from_rows(rows, user_steam_ids, config);
+            acc.merge(&economy.user_acc);
+        }
+
+        Self {
+            matches,
+            buy_types: acc.finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_buy() {
+        let config = CsgoEconomyConfig::default();
+        assert_eq!(classify_csgo_buy(&config, "competitive", 0, 5, 1000, Some(4000), Some(0), Some(4000)), CsgoBuyType::Pistol);
+        assert_eq!(classify_csgo_buy(&config, "competitive", 3, 5, 25000, Some(30000), Some(20000), Some(10000)), CsgoBuyType::FullBuy);
+        assert_eq!(classify_csgo_buy(&config, "competitive", 3, 5, 3000, Some(12000), Some(1000), Some(11000)), CsgoBuyType::Eco);
+        assert_eq!(classify_csgo_buy(&config, "competitive", 3, 5, 14000, Some(14000), Some(13000), Some(1000)), CsgoBuyType::Force);
+        assert_eq!(classify_csgo_buy(&config, "competitive", 3, 5, 14000, Some(25000), Some(12000), Some(13000)), CsgoBuyType::HalfBuy);
+        // GSI style data without the start money.
+        assert_eq!(classify_csgo_buy(&config, "competitive", 3, 1, 2800, None, None, Some(200)), CsgoBuyType::Force);
+        // A team that's broke after the pistol round isn't on another pistol round.
+        assert_eq!(classify_csgo_buy(&config, "competitive", 1, 5, 1000, Some(4000), Some(0), Some(4000)), CsgoBuyType::Eco);
+        // Pistol rounds are pistol rounds no matter how much money there is (e.g. money from the end of the half isn't reset in some custom configs).
+        assert_eq!(classify_csgo_buy(&config, "competitive", 15, 5, 4000, Some(50000), Some(4000), Some(46000)), CsgoBuyType::Pistol);
+        // Wingman halves are shorter.
+        assert_eq!(classify_csgo_buy(&config, "scrimcomp2v2", 8, 2, 1600, Some(1600), Some(1600), Some(0)), CsgoBuyType::Pistol);
+        assert_eq!(classify_csgo_buy(&config, "scrimcomp2v2", 15, 2, 1600, Some(1600), Some(1600), Some(0)), CsgoBuyType::Eco);
+    }
+
+    #[test]
+    fn test_loss_bonus_and_demo_priority() {
+        let view_uuid = Uuid::new_v4();
+        let row = |round_num: i32, steam_id: i64, team: CsgoTeam, winner: CsgoTeam, event_source: CsgoEventSource, equipment_value: i32| {
+            CsgoEconomyPlayerRound{
+                view_uuid: view_uuid.clone(),
+                event_source: event_source as i32,
+                mode: String::from("competitive"),
+                round_num,
+                steam_id,
+                team: team as i32,
+                winning_team: Some(winner as i32),
+                equipment_value: Some(equipment_value),
+                money: Some(0),
+                start_money: None,
+                spent: None,
+            }
+        };
+
+        let rows = vec![
+            row(0, 1, CsgoTeam::TeamCT, CsgoTeam::TeamT, CsgoEventSource::Demo, 800),
+            row(0, 2, CsgoTeam::TeamT, CsgoTeam::TeamT, CsgoEventSource::Demo, 800),
+            // GSI thinks the user had a full buy but the demo knows better.
+            row(1, 1, CsgoTeam::TeamCT, CsgoTeam::TeamT, CsgoEventSource::Gsi, 5000),
+            row(1, 1, CsgoTeam::TeamCT, CsgoTeam::TeamT, CsgoEventSource::Demo, 2500),
+            row(1, 2, CsgoTeam::TeamT, CsgoTeam::TeamT, CsgoEventSource::Demo, 4500),
+            row(2, 1, CsgoTeam::TeamCT, CsgoTeam::TeamCT, CsgoEventSource::Demo, 5000),
+            row(2, 2, CsgoTeam::TeamT, CsgoTeam::TeamCT, CsgoEventSource::Demo, 5000),
+        ];
+
+        let user: HashSet<i64> = vec![1].into_iter().collect();
+        let economy = CsgoMatchEconomy::from_rows(rows, &user, &CsgoEconomyConfig::default());
+        let ct: Vec<&CsgoTeamRoundEconomy> = economy.team_rounds.iter().filter(|x| { x.team as i32 == CsgoTeam::TeamCT as i32 }).collect();
+        assert_eq!(ct.len(), 3);
+        assert_eq!(ct[0].buy_type, CsgoBuyType::Pistol);
+        assert_eq!(ct[1].buy_type, CsgoBuyType::Force);
+        assert_eq!(ct[1].equipment_value, 2500);
+        assert_eq!(ct[1].loss_bonus, 2400);
+        assert_eq!(ct[2].loss_bonus, 2900);
+        assert_eq!(economy.user_buy_types.iter().find(|x| { x.buy_type == CsgoBuyType::FullBuy }).map(|x| { x.wins }), Some(1));
+    }
+
+    #[test]
+    fn test_loss_bonus_resets_at_half() {
+        let view_uuid = Uuid::new_v4();
+        let row = |round_num: i32, steam_id: i64, team: CsgoTeam, winner: CsgoTeam| {
+            CsgoEconomyPlayerRound{
+                view_uuid: view_uuid.clone(),
+                event_source: CsgoEventSource::Demo as i32,
+                mode: String::from("competitive"),
+                round_num,
+                steam_id,
+                team: team as i32,
+                winning_team: Some(winner as i32),
+                equipment_value: Some(1000),
+                money: Some(0),
+                start_money: None,
+                spent: None,
+            }
+        };
+
+        // The CTs lose every round of the first half.
+        let mut rows: Vec<CsgoEconomyPlayerRound> = vec![];
+        for round_num in 0..15 {
+            rows.push(row(round_num, 1, CsgoTeam::TeamCT, CsgoTeam::TeamT));
+            rows.push(row(round_num, 2, CsgoTeam::TeamT, CsgoTeam::TeamT));
+        }
+        rows.push(row(15, 1, CsgoTeam::TeamT, CsgoTeam::TeamT));
+        rows.push(row(15, 2, CsgoTeam::TeamCT, CsgoTeam::TeamT));
+
+        let user: HashSet<i64> = vec![1].into_iter().collect();
+        let economy = CsgoMatchEconomy::from_rows(rows, &user, &CsgoEconomyConfig::default());
+        let ct: Vec<&CsgoTeamRoundEconomy> = economy.team_rounds.iter().filter(|x| { x.team as i32 == CsgoTeam::TeamCT as i32 }).collect();
+        assert_eq!(ct.len(), 16);
+        // Round 1 is an eco (not another pistol round) so the loss counter keeps going.
+        assert_eq!(ct[1].buy_type, CsgoBuyType::Eco);
+        assert_eq!(ct[1].loss_count, 2);
+        assert_eq!(ct[14].loss_count, CSGO_LOSS_BONUS_MAX_COUNT);
+        assert_eq!(ct[15].buy_type, CsgoBuyType::Pistol);
+        assert_eq!(ct[15].loss_count, CSGO_LOSS_BONUS_HALF_START_COUNT);
+    }
+}
@@ -49,6 +49,30 @@ impl CsgoEntity {
     pub fn to_weapon(&self, class_name: &str) -> CsgoWeapon {
         csgo_string_to_weapon(class_name)
     }
+
+    // Players send their origin split up as a 2D vector and the Z coordinate (in different tables depending on
+    // whether the demo was recorded from their POV) while every other entity uses the base entity's origin.
+    pub fn get_origin(&self) -> Option<CsgoVector> {
+        for prefix in &["cslocaldata", "csnonlocaldata"] {
+            if let Some(xy) = self.get_prop(&format!("{}.m_vecOrigin", prefix)).and_then(|x| { x.value.v_vec.as_ref() }) {
+                return Some(CsgoVector{
+                    x: xy.x,
+                    y: xy.y,
+                    z: self.get_prop(&format!("{}.m_vecOrigin[2]", prefix)).and_then(|x| { x.value.v_float }).unwrap_or(0.0),
+                });
+            }
+        }
+
+        self.get_prop("m_vecOrigin").and_then(|x| { x.value.v_vec.clone() })
+    }
+
+    // Returns (pitch, yaw).
+    pub fn get_eye_angles(&self) -> (f32, f32) {
+        (
+            self.get_prop("m_angEyeAngles[0]").and_then(|x| { x.value.v_float }).unwrap_or(0.0),
+            self.get_prop("m_angEyeAngles[1]").and_then(|x| { x.value.v_float }).unwrap_or(0.0),
+        )
+    }
 }
 
 // TODO: Add event emission support for entities for when
@@ -163,6 +187,10 @@ impl CsgoEntityScene {
         self.data_table = Some(table);
     }
 
+    pub fn data_table(&self) -> Option<Arc<RwLock<CsgoDemoDataTable>>> {
+        self.data_table.clone()
+    }
+
     pub fn add_entity_callback(&mut self, class: &str, cb: Box<dyn CsgoEntityCallback + Send + Sync>) {
         if !self.callbacks.contains_key(class) {
             self.callbacks.insert(class.to_string(), vec![]);
@@ -177,6 +205,10 @@ impl CsgoEntityScene {
         self.entities.get(&id)
     }
 
+    pub fn all_entities(&self) -> impl Iterator<Item=&CsgoEntity> {
+        self.entities.values()
+    }
+
     pub fn get_class_name(&self, id: i32) -> Result<Option<String>, SquadOvError> {
         Ok(if let Some(dt) = &self.data_table {
             if let Some(class) = dt.read()?.get_server_class_from_id(id) {
@@ -48,6 +48,16 @@ const CSGO_DEMO_HEADER_SIZE: usize = 1072;
 const CSGO_DEMO_CMD_HEADER_SIZE: usize = 6;
 const CSGO_DEMO_CMD_INFO_SIZE: usize = 152;
 
+fn csgo_packet_message_name(cmd: u32) -> String {
+    if let Some(ncmd) = NetMessages::from_i32(cmd as i32) {
+        format!("{:?}", ncmd)
+    } else if let Some(scmd) = SvcMessages::from_i32(cmd as i32) {
+        format!("{:?}", scmd)
+    } else {
+        format!("Unknown({})", cmd)
+    }
+}
+
 struct CsgoDemoRawFile<'a> {
     // Raw byte data
     reader: BitReader<'a>,
@@ -65,7 +75,7 @@ impl<'a> CsgoDemoRawFile<'a> {
         }
     }
 
-    fn get_raw_payload_data(&mut self, max_size: usize, context: &str) -> Result<&[u8], SquadOvError> {
+    fn get_raw_payload_data(&mut self, max_size: usize, context: &str) -> Result<&'a [u8], SquadOvError> {
         let payload_len = le_i32(self.reader.read_aligned_bytes(4)?)?.1;
         if max_size > 0 && payload_len as usize > max_size {
             return Err(SquadOvError::InternalError(format!("{} size {} > {}", context, payload_len, max_size)));
@@ -125,42 +135,50 @@ impl<'a> CsgoDemoRawFile<'a> {
             // This needs to happen here to increment the pointer.
             let raw_buffer = self.reader.read_aligned_bytes(size as usize)?;
 
-            if let Some(_ncmd) = NetMessages::from_i32(cmd as i32) {
-                // Not much of importance happens within NetMessages I think.
-            } else if let Some(scmd) = SvcMessages::from_i32(cmd as i32) {
-                match scmd {
-                    SvcMessages::SvcGameEventList => {
-                        self.event_list = Some(CsvcMsgGameEventList::decode(raw_buffer)?);
-                    },
-                    SvcMessages::SvcGameEvent => {
-                        let game_event = CsvcMsgGameEvent::decode(raw_buffer)?;
-                        if let Some(event_id) = game_event.eventid {
-                            if let Some(descriptor) = self.find_event_descriptor(event_id) {
-                                demo.handle_game_event(tick, game_event, descriptor)?;
-                            }
-                        }
-                    },
-                    SvcMessages::SvcCreateStringTable => {
-                        let string_table_msg = CsvcMsgCreateStringTable::decode(raw_buffer)?;
-                        demo.handle_string_table_create(string_table_msg)?;
-                    },
-                    SvcMessages::SvcUpdateStringTable => {
-                        let string_table_msg = CsvcMsgUpdateStringTable::decode(raw_buffer)?;
-                        demo.handle_string_table_update(string_table_msg)?;
-                    },
-                    // Handling the svc_PacketEntities message is crucial for tracking the state of players and other entities (weapons, nades, etc.).
-                    SvcMessages::SvcPacketEntities => {
-                        let msg = CsvcMsgPacketEntities::decode(raw_buffer)?;
-                        demo.handle_entity_update(msg)?;
-                    },
-                    SvcMessages::SvcSendTable => {
-                        let msg = CsvcMsgSendTable::decode(raw_buffer)?;
-                        if let Some(dt) = &self.data_table {
-                            dt.write()?.receive_table(msg)?;
+            self.read_demo_packet_message(tick, cmd, raw_buffer, demo).map_err(|err| {
+                SquadOvError::InternalError(format!("CS:GO Demo failed to handle packet message [{}]: {}", csgo_packet_message_name(cmd), err))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn read_demo_packet_message(&mut self, tick: i32, cmd: u32, raw_buffer: &[u8], demo: &mut CsgoDemo) -> Result<(), SquadOvError> {
+        if let Some(_ncmd) = NetMessages::from_i32(cmd as i32) {
+            // Not much of importance happens within NetMessages I think.
+        } else if let Some(scmd) = SvcMessages::from_i32(cmd as i32) {
+            match scmd {
+                SvcMessages::SvcGameEventList => {
+                    self.event_list = Some(CsvcMsgGameEventList::decode(raw_buffer)?);
+                },
+                SvcMessages::SvcGameEvent => {
+                    let game_event = CsvcMsgGameEvent::decode(raw_buffer)?;
+                    if let Some(event_id) = game_event.eventid {
+                        if let Some(descriptor) = self.find_event_descriptor(event_id) {
+                            demo.handle_game_event(tick, game_event, descriptor)?;
                         }
-                    },
-                    _ => (),
-                }
+                    }
+                },
+                SvcMessages::SvcCreateStringTable => {
+                    let string_table_msg = CsvcMsgCreateStringTable::decode(raw_buffer)?;
+                    demo.handle_string_table_create(string_table_msg)?;
+                },
+                SvcMessages::SvcUpdateStringTable => {
+                    let string_table_msg = CsvcMsgUpdateStringTable::decode(raw_buffer)?;
+                    demo.handle_string_table_update(string_table_msg)?;
+                },
+                // Handling the svc_PacketEntities message is crucial for tracking the state of players and other entities (weapons, nades, etc.).
+                SvcMessages::SvcPacketEntities => {
+                    let msg = CsvcMsgPacketEntities::decode(raw_buffer)?;
+                    demo.handle_entity_update(tick, msg)?;
+                },
+                SvcMessages::SvcSendTable => {
+                    let msg = CsvcMsgSendTable::decode(raw_buffer)?;
+                    if let Some(dt) = &self.data_table {
+                        dt.write()?.receive_table(msg)?;
+                    }
+                },
+                _ => (),
             }
         }
 
@@ -193,29 +211,47 @@ impl<'a> CsgoDemoRawFile<'a> {
 
     fn read_body(&mut self, demo: &mut CsgoDemo) -> Result<(), SquadOvError> {
         loop {
-            let cmd_header = self.read_command_header()?;
-            match cmd_header.cmd {
-                CsgoDemoCmdMessage::Stop => {
-                    log::debug!("CSGO Demo Stop");
-                    break;
-                },
-                CsgoDemoCmdMessage::SignOn | CsgoDemoCmdMessage::Packet => self.read_demo_packet(cmd_header.tick, demo)?,
-                CsgoDemoCmdMessage::ConsoleCmd => self.read_demo_console_cmd()?,
-                CsgoDemoCmdMessage::DataTables => {
-                    self.read_demo_data_table()?;
-                    if let Some(dt) = self.data_table.as_ref() {
-                        demo.on_data_table(dt.clone())?;
-                    }
-                },
-                CsgoDemoCmdMessage::StringTables => self.read_demo_string_table()?,
-                CsgoDemoCmdMessage::UserCmd => self.read_demo_user_cmd()?,
-                _ => {
-                    log::debug!("CSGO Demo OTHER CMD - Not yet supported");
-                },
-            };
+            // Keep track of where each command starts so that a parse failure can point at exactly where it went wrong.
+            let cmd_offset = self.reader.loc_bytes();
+            let cmd_header = self.read_command_header().map_err(|err| {
+                SquadOvError::InternalError(format!("CS:GO Demo failed to read command header at offset {}: {}", cmd_offset, err))
+            })?;
+            let cmd_name = format!("{:?}", cmd_header.cmd);
+            let cmd_tick = cmd_header.tick;
+            let finished = self.read_body_command(cmd_header, demo).map_err(|err| {
+                SquadOvError::InternalError(format!("CS:GO Demo failed to parse {} command at offset {} (tick {}): {}", cmd_name, cmd_offset, cmd_tick, err))
+            })?;
+
+            if finished {
+                break;
+            }
         }
         Ok(())
     }
+
+    // Returns whether or not we've reached the end of the demo.
+    fn read_body_command(&mut self, cmd_header: CsgoDemoCmdHeader, demo: &mut CsgoDemo) -> Result<bool, SquadOvError> {
+        match cmd_header.cmd {
+            CsgoDemoCmdMessage::Stop => {
+                log::debug!("CSGO Demo Stop");
+                return Ok(true);
+            },
+            CsgoDemoCmdMessage::SignOn | CsgoDemoCmdMessage::Packet => self.read_demo_packet(cmd_header.tick, demo)?,
+            CsgoDemoCmdMessage::ConsoleCmd => self.read_demo_console_cmd()?,
+            CsgoDemoCmdMessage::DataTables => {
+                self.read_demo_data_table()?;
+                if let Some(dt) = self.data_table.as_ref() {
+                    demo.on_data_table(dt.clone())?;
+                }
+            },
+            CsgoDemoCmdMessage::StringTables => self.read_demo_string_table()?,
+            CsgoDemoCmdMessage::UserCmd => self.read_demo_user_cmd()?,
+            _ => {
+                log::debug!("CSGO Demo OTHER CMD - Not yet supported");
+            },
+        };
+        Ok(false)
+    }
 }
 
 struct CsgoCCSTeamHandler {
@@ -349,4 +385,70 @@ impl CsgoDemoParser {
         let mut file = std::fs::File::open(path)?;
         Ok(CsgoDemoParser::from_file(&mut file)?)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csgo::positions::CsgoPositionEntityKind;
+    use crate::csgo::weapon::CsgoWeapon;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_data");
+        path.push("csgo");
+        path.push(name);
+        path
+    }
+
+    #[test]
+    fn test_parse_minimal_demo_fixture() {
+        let demo = CsgoDemoParser::from_path(&fixture("minimal.dem")).unwrap();
+        assert_eq!(demo.header.map_name, "de_dust2");
+        assert_eq!(demo.header.playback_ticks, 64);
+        assert_eq!(demo.tick_rate(), 64.0);
+        // No game events or entities so there's nothing to sample.
+        assert!(demo.rounds.iter().all(|r| { r.positions.is_empty() }));
+    }
+
+    // Generated by test_data/csgo/generate_two_rounds_demo.py.
+    #[test]
+    fn test_parse_two_rounds_demo_fixture() {
+        let demo = CsgoDemoParser::from_path(&fixture("two_rounds.dem")).unwrap();
+        assert_eq!(demo.game_start_tick, Some(64));
+        assert_eq!(demo.rounds.len(), 2);
+
+        // Positions get sampled at most every 8 ticks (8Hz at 64 tick) and only while the round is in progress.
+        let round = &demo.rounds[0];
+        assert_eq!(round.positions.len(), 12);
+        let mut ticks: Vec<i32> = round.positions.iter().map(|x| { x.tick }).collect();
+        ticks.dedup();
+        // Tick 140 is too soon after the previous sample and tick 208 comes in after the round ended.
+        assert_eq!(ticks, vec![72, 136, 144, 152]);
+
+        let ct: Vec<_> = round.positions.iter().filter(|x| { x.user_id == Some(3) }).collect();
+        assert_eq!(ct.len(), 4);
+        assert_eq!(ct[0].kind, CsgoPositionEntityKind::Player);
+        assert_eq!((ct[2].x, ct[2].y, ct[2].z), (1050.0, -150.0, 0.0));
+        assert_eq!(ct[2].yaw, 270.0);
+        assert!(ct[2].alive);
+        // They died on tick 150.
+        assert!(!ct[3].alive);
+
+        let dropped: Vec<_> = round.positions.iter().filter(|x| { x.kind == CsgoPositionEntityKind::DroppedWeapon }).collect();
+        assert_eq!(dropped.len(), 4);
+        assert!(dropped.iter().all(|x| { x.user_id.is_none() && x.weapon == Some(CsgoWeapon::Ak47 as i32) && (x.x, x.y) == (50.0, 60.0) }));
+
+        // Kills record where everyone was at the time.
+        let kill = &round.kills[0];
+        let victim = kill.victim_position.as_ref().unwrap();
+        let killer = kill.killer_position.as_ref().unwrap();
+        assert_eq!((victim.x, victim.y, victim.z), (1050.0, -150.0, 0.0));
+        assert_eq!((killer.x, killer.y, killer.z), (-290.0, 905.0, 64.0));
+
+        let round = &demo.rounds[1];
+        assert_eq!(round.positions.len(), 9);
+        assert!(round.positions.iter().all(|x| { x.tick >= round.round_start_tick && x.tick <= round.round_end_tick.unwrap() }));
+    }
+}
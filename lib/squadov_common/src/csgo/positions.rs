@@ -0,0 +1,243 @@
+use crate::SquadOvError;
+use super::math::CsgoVector;
+use serde::{Serialize, Deserialize};
+use serde_repr::{Serialize_repr, Deserialize_repr};
+use avro_rs::{
+    Codec,
+    Reader,
+    Schema,
+    Writer,
+};
+use num_enum::TryFromPrimitive;
+use std::collections::HashMap;
+
+// How often (per second of game time) we want to record the position of every entity we're tracking.
+pub const CSGO_POSITION_SAMPLE_HZ: f32 = 8.0;
+// Size (in world units) of each cell in the heatmap grid.
+pub const CSGO_HEATMAP_CELL_SIZE: f32 = 64.0;
+
+const CSGO_POSITION_SAMPLE_SCHEMA: &'static str = r#"
+{
+    "type": "record",
+    "name": "CsgoPositionSample",
+    "fields": [
+        {"name": "tick", "type": "int"},
+        {"name": "entity_id", "type": "int"},
+        {"name": "user_id", "type": ["null", "int"]},
+        {"name": "kind", "type": "int"},
+        {"name": "x", "type": "float"},
+        {"name": "y", "type": "float"},
+        {"name": "z", "type": "float"},
+        {"name": "yaw", "type": "float"},
+        {"name": "pitch", "type": "float"},
+        {"name": "alive", "type": "boolean"},
+        {"name": "weapon", "type": ["null", "int"]}
+    ]
+}
+"#;
+
+lazy_static! {
+    static ref POSITION_SAMPLE_SCHEMA: Schema = Schema::parse_str(CSGO_POSITION_SAMPLE_SCHEMA).unwrap();
+}
+
+#[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr, TryFromPrimitive, PartialEq)]
+#[repr(i32)]
+pub enum CsgoPositionEntityKind {
+    Player,
+    // A weapon that isn't being held by anyone.
+    DroppedWeapon,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoPositionSample {
+    pub tick: i32,
+    pub entity_id: i32,
+    // Only set for players - this is the demo user ID that's used everywhere else (kills, damage, etc.).
+    pub user_id: Option<i32>,
+    pub kind: CsgoPositionEntityKind,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub alive: bool,
+    // The active weapon for players and the weapon itself for dropped weapons.
+    pub weapon: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoRoundTrack {
+    pub round_num: i32,
+    pub tick_rate: f32,
+    pub start_tick: i32,
+    pub end_tick: Option<i32>,
+    pub samples: Vec<CsgoPositionSample>,
+}
+
+// The avro serialization uses the snake case field names so we need a separate struct from the camelCase one we send to the client.
+#[derive(Serialize, Deserialize)]
+struct CsgoAvroPositionSample {
+    tick: i32,
+    entity_id: i32,
+    user_id: Option<i32>,
+    kind: i32,
+    x: f32,
+    y: f32,
+    z: f32,
+    yaw: f32,
+    pitch: f32,
+    alive: bool,
+    weapon: Option<i32>,
+}
+
+pub fn serialize_position_samples(samples: &[CsgoPositionSample]) -> Result<Vec<u8>, SquadOvError> {
+    let mut writer = Writer::with_codec(&POSITION_SAMPLE_SCHEMA, Vec::new(), Codec::Snappy);
+    for s in samples {
+        writer.append_ser(CsgoAvroPositionSample{
+            tick: s.tick,
+            entity_id: s.entity_id,
+            user_id: s.user_id,
+            kind: s.kind as i32,
+            x: s.x,
+            y: s.y,
+            z: s.z,
+            yaw: s.yaw,
+            pitch: s.pitch,
+            alive: s.alive,
+            weapon: s.weapon,
+        })?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+pub fn deserialize_position_samples(data: &[u8]) -> Result<Vec<CsgoPositionSample>, SquadOvError> {
+    let reader = Reader::new(data)?;
+    let mut samples: Vec<CsgoPositionSample> = vec![];
+    for value in reader {
+        let raw = avro_rs::from_value::<CsgoAvroPositionSample>(&value?)?;
+        samples.push(CsgoPositionSample{
+            tick: raw.tick,
+            entity_id: raw.entity_id,
+            user_id: raw.user_id,
+            kind: CsgoPositionEntityKind::try_from_primitive(raw.kind).map_err(|x| { SquadOvError::InternalError(format!("Invalid CSGO position kind: {:?}", x)) })?,
+            x: raw.x,
+            y: raw.y,
+            z: raw.z,
+            yaw: raw.yaw,
+            pitch: raw.pitch,
+            alive: raw.alive,
+            weapon: raw.weapon,
+        });
+    }
+    Ok(samples)
+}
+
+#[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr, TryFromPrimitive, PartialEq, Eq, Hash)]
+#[repr(i32)]
+pub enum CsgoHeatmapKind {
+    Position,
+    Kill,
+    Death,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CsgoHeatmapCellKey {
+    pub user_id: i32,
+    pub kind: CsgoHeatmapKind,
+    pub cell_x: i32,
+    pub cell_y: i32,
+}
+
+pub fn position_to_heatmap_cell(x: f32, y: f32) -> (i32, i32) {
+    ((x / CSGO_HEATMAP_CELL_SIZE).floor() as i32, (y / CSGO_HEATMAP_CELL_SIZE).floor() as i32)
+}
+
+// Accumulates counts for every (player, kind, cell) that we see in a single demo.
+#[derive(Debug, Default)]
+pub struct CsgoHeatmapAccumulator {
+    pub cells: HashMap<CsgoHeatmapCellKey, i32>,
+}
+
+impl CsgoHeatmapAccumulator {
+    pub fn add(&mut self, user_id: i32, kind: CsgoHeatmapKind, pos: &CsgoVector) {
+        let (cell_x, cell_y) = position_to_heatmap_cell(pos.x, pos.y);
+        *self.cells.entry(CsgoHeatmapCellKey{
+            user_id,
+            kind,
+            cell_x,
+            cell_y,
+        }).or_insert(0) += 1;
+    }
+
+    // Only live players contribute to the position heatmap.
+    pub fn add_samples(&mut self, samples: &[CsgoPositionSample]) {
+        for s in samples {
+            if s.kind != CsgoPositionEntityKind::Player || !s.alive {
+                continue;
+            }
+
+            if let Some(user_id) = s.user_id {
+                self.add(user_id, CsgoHeatmapKind::Position, &CsgoVector{x: s.x, y: s.y, z: s.z});
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(tick: i32, user_id: Option<i32>, kind: CsgoPositionEntityKind, x: f32, y: f32, alive: bool) -> CsgoPositionSample {
+        CsgoPositionSample{
+            tick,
+            entity_id: 1,
+            user_id,
+            kind,
+            x,
+            y,
+            z: 0.0,
+            yaw: 90.0,
+            pitch: 0.0,
+            alive,
+            weapon: None,
+        }
+    }
+
+    #[test]
+    fn test_position_sample_avro_roundtrip() {
+        let samples = vec![
+            sample(0, Some(2), CsgoPositionEntityKind::Player, 100.5, -20.0, true),
+            sample(8, None, CsgoPositionEntityKind::DroppedWeapon, -1000.0, 512.0, false),
+        ];
+
+        let data = serialize_position_samples(&samples).unwrap();
+        let out = deserialize_position_samples(&data).unwrap();
+        assert_eq!(out.len(), samples.len());
+        for (a, b) in samples.iter().zip(out.iter()) {
+            assert_eq!(a.tick, b.tick);
+            assert_eq!(a.user_id, b.user_id);
+            assert_eq!(a.kind, b.kind);
+            assert_eq!(a.x, b.x);
+            assert_eq!(a.y, b.y);
+            assert_eq!(a.alive, b.alive);
+        }
+    }
+
+    #[test]
+    fn test_heatmap_ignores_dead_players_and_weapons() {
+        let mut acc = CsgoHeatmapAccumulator::default();
+        acc.add_samples(&[
+            sample(0, Some(2), CsgoPositionEntityKind::Player, 10.0, 10.0, true),
+            sample(8, Some(2), CsgoPositionEntityKind::Player, 20.0, 20.0, true),
+            sample(16, Some(2), CsgoPositionEntityKind::Player, 20.0, 20.0, false),
+            sample(16, None, CsgoPositionEntityKind::DroppedWeapon, 20.0, 20.0, false),
+            sample(24, Some(3), CsgoPositionEntityKind::Player, -10.0, 70.0, true),
+        ]);
+
+        assert_eq!(acc.cells.len(), 2);
+        assert_eq!(acc.cells.get(&CsgoHeatmapCellKey{user_id: 2, kind: CsgoHeatmapKind::Position, cell_x: 0, cell_y: 0}), Some(&2));
+        assert_eq!(acc.cells.get(&CsgoHeatmapCellKey{user_id: 3, kind: CsgoHeatmapKind::Position, cell_x: -1, cell_y: 1}), Some(&1));
+    }
+}
@@ -12,6 +12,7 @@ use crate::{
         rabbitmq::ElasticSearchJobInterface,
     },
     vod::db as vdb,
+    teammates::db as tdb,
 };
 use sqlx::postgres::{PgPool};
 use serde::{Serialize, Deserialize};
@@ -73,6 +74,7 @@ impl CsgoRabbitmqInterface {
         tx.commit().await?;
 
         if let Ok((match_uuid, user_id)) = db::find_csgo_match_user_from_view_id(&*self.db, view_uuid).await {
+            tdb::queue_teammate_roster_sync(&*self.db, &match_uuid).await?;
             if let Ok(video_uuid) = vdb::get_vod_id_from_match_user(&*self.db, &match_uuid, user_id).await {
                 self.es_itf.request_sync_vod(vec![video_uuid]).await?;
             }
@@ -6,6 +6,7 @@ use crate::{
         CsgoTeam,
         CsgoRoundWin,
         CsgoDemoHitGroup,
+        CsgoDemoUtilityKind,
     },
     csgo::weapon::{
         CsgoWeapon,
@@ -77,6 +78,8 @@ pub struct CsgoCommonRoundPlayerStats {
     pub has_helmet: Option<bool>,
     pub team: CsgoTeam,
     pub weapons: Vec<CsgoWeapon>,
+    pub start_money: Option<i32>,
+    pub spent: Option<i32>,
 }
 
 #[derive(Serialize)]
@@ -113,6 +116,44 @@ pub struct CsgoCommonRoundDamage {
     pub hitgroup: CsgoDemoHitGroup,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoCommonRoundUtilityDamage {
+    pub tm: DateTime<Utc>,
+    pub receiver: i32,
+    pub damage_health: i32,
+    pub damage_armor: i32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoCommonRoundUtility {
+    pub container_id: i64,
+    pub round_num: i32,
+    // Unique within the round.
+    pub utility_id: i32,
+    pub tm: DateTime<Utc>,
+    pub kind: CsgoDemoUtilityKind,
+    pub thrower: Option<i32>,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    // Damage that we were able to attribute to this grenade.
+    pub damage: Vec<CsgoCommonRoundUtilityDamage>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoCommonRoundBlind {
+    pub container_id: i64,
+    pub round_num: i32,
+    pub tm: DateTime<Utc>,
+    pub victim: i32,
+    pub attacker: Option<i32>,
+    pub duration: f32,
+    pub utility_id: Option<i32>,
+}
+
 #[derive(Serialize)]
 #[serde(rename_all="camelCase")]
 pub struct CsgoCommonRound {
@@ -133,6 +174,8 @@ pub struct CsgoCommonRound {
     pub player_stats: Vec<CsgoCommonRoundPlayerStats>,
     pub kills: Vec<CsgoCommonRoundKill>,
     pub damage: Vec<CsgoCommonRoundDamage>,
+    pub utility: Vec<CsgoCommonRoundUtility>,
+    pub blinds: Vec<CsgoCommonRoundBlind>,
 }
 
 #[derive(Serialize)]
@@ -164,7 +207,7 @@ impl CsgoCommonEventContainer {
             players: vec![],
         };
 
-        let tick_rate = demo.header.playback_ticks as f32 / demo.header.playback_time;
+        let tick_rate = demo.tick_rate();
         let tick_to_timestamp = |tick| {
             ref_timestamp.clone() + chrono::Duration::milliseconds((tick as f32 / tick_rate * 1000.0) as i64)
         };
@@ -205,6 +248,8 @@ impl CsgoCommonEventContainer {
                 player_stats: vec![],
                 kills: vec![],
                 damage: vec![],
+                utility: vec![],
+                blinds: vec![],
             };
 
             if let Some(bomb_state) = &round.bomb_state {
@@ -252,6 +297,44 @@ impl CsgoCommonEventContainer {
                 new_round.damage.push(new_damage);
             }
 
+            for (idx, u) in round.utility.iter().enumerate() {
+                new_round.utility.push(CsgoCommonRoundUtility{
+                    container_id: 0,
+                    round_num: round.round_num as i32,
+                    utility_id: idx as i32,
+                    tm: tick_to_timestamp(u.tick),
+                    kind: u.kind,
+                    thrower: u.thrower,
+                    x: u.position.x,
+                    y: u.position.y,
+                    z: u.position.z,
+                    damage: vec![],
+                });
+            }
+
+            for d in &round.damage {
+                if let Some(idx) = round.find_utility_for_damage(d, tick_rate) {
+                    new_round.utility[idx].damage.push(CsgoCommonRoundUtilityDamage{
+                        tm: tick_to_timestamp(d.tick),
+                        receiver: d.receiver,
+                        damage_health: d.damage_health,
+                        damage_armor: d.damage_armor,
+                    });
+                }
+            }
+
+            for b in &round.blinds {
+                new_round.blinds.push(CsgoCommonRoundBlind{
+                    container_id: 0,
+                    round_num: round.round_num as i32,
+                    tm: tick_to_timestamp(b.tick),
+                    victim: b.victim,
+                    attacker: b.attacker,
+                    duration: b.duration,
+                    utility_id: round.find_utility_for_blind(b, tick_rate).map(|x| { x as i32 }),
+                });
+            }
+
             for (user_id, p) in &round.players {
                 let is_mvp = if let Some(mvp_id) = round.round_mvp {
                     mvp_id == *user_id
@@ -288,6 +371,8 @@ impl CsgoCommonEventContainer {
                     has_helmet: Some(p.has_helmet),
                     team: p.team,
                     weapons: p.weapons.clone(),
+                    start_money: p.start_money,
+                    spent: p.spent,
                 };
                 new_round.player_stats.push(new_stats);
             }
@@ -369,6 +454,8 @@ impl CsgoCommonEventContainer {
                     player_stats: vec![],
                     kills: vec![],
                     damage: vec![],
+                    utility: vec![],
+                    blinds: vec![],
                 };
 
                 let mut round_player_stats: HashMap<i64, CsgoCommonRoundPlayerStats> = HashMap::new();
@@ -396,6 +483,9 @@ impl CsgoCommonEventContainer {
                             weapons: p.weapons.iter().map(|x| {
                                 csgo_string_to_weapon(&x.name)
                             }).collect(),
+                            // GSI only gives us a snapshot of the player's money, not how much they had before buying.
+                            start_money: None,
+                            spent: None,
                         };
                         round_player_stats.insert(steamid, pround);
                     }
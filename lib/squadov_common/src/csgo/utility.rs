@@ -0,0 +1,370 @@
+use crate::csgo::{
+    demo::{
+        CsgoTeam,
+        CsgoDemoUtilityKind,
+    },
+    schema::{
+        CsgoCommonEventContainer,
+        CsgoCommonRound,
+    },
+};
+use serde::Serialize;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+#[derive(Serialize, Default)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoRoundUtilityDamage {
+    pub round_num: i32,
+    pub damage: i32,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoPlayerUtilityReport {
+    pub user_id: i32,
+    pub flash_assists: i32,
+    pub flashes_thrown: i32,
+    pub enemies_blinded: i32,
+    // Total seconds of blindness inflicted on enemies.
+    pub enemy_blind_duration: f32,
+    pub team_flashes: i32,
+    pub team_blind_duration: f32,
+    // Damage dealt to enemies by this player's grenades and fires.
+    pub utility_damage: i32,
+    pub round_utility_damage: Vec<CsgoRoundUtilityDamage>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoUtilityLanding {
+    pub round_num: i32,
+    pub tm: DateTime<Utc>,
+    pub kind: CsgoDemoUtilityKind,
+    pub thrower: Option<i32>,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoUtilityReport {
+    pub players: Vec<CsgoPlayerUtilityReport>,
+    // Where smokes, molotovs and fires ended up.
+    pub landings: Vec<CsgoUtilityLanding>,
+}
+
+fn get_round_teams(round: &CsgoCommonRound) -> HashMap<i32, CsgoTeam> {
+    round.player_stats.iter().map(|x| { (x.user_id, x.team) }).collect()
+}
+
+// Unknown/spectator teams are never considered to be on the same team as anyone else.
+fn is_same_team(teams: &HashMap<i32, CsgoTeam>, a: i32, b: i32) -> bool {
+    match (teams.get(&a), teams.get(&b)) {
+        (Some(ta), Some(tb)) => match (ta, tb) {
+            (CsgoTeam::TeamCT, CsgoTeam::TeamCT) | (CsgoTeam::TeamT, CsgoTeam::TeamT) => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+impl CsgoUtilityReport {
+    pub fn from_container(container: &CsgoCommonEventContainer) -> Self {
+        let mut players: HashMap<i32, CsgoPlayerUtilityReport> = container.players.iter().map(|x| {
+            (x.user_id, CsgoPlayerUtilityReport{
+                user_id: x.user_id,
+                ..CsgoPlayerUtilityReport::default()
+            })
+        }).collect();
+        let mut landings: Vec<CsgoUtilityLanding> = vec![];
+
+        let mut rounds: Vec<&CsgoCommonRound> = container.rounds.iter().collect();
+        rounds.sort_by_key(|x| { x.round_num });
+
+        for round in rounds {
+            let teams = get_round_teams(round);
+
+            for k in &round.kills {
+                if !k.flash_assist.unwrap_or(false) {
+                    continue;
+                }
+
+                if let Some(p) = k.assister.and_then(|x| { players.get_mut(&x) }) {
+                    p.flash_assists += 1;
+                }
+            }
+
+            for b in &round.blinds {
+                let attacker = match b.attacker {
+                    Some(x) if x != b.victim => x,
+                    _ => continue,
+                };
+
+                if let Some(p) = players.get_mut(&attacker) {
+                    if is_same_team(&teams, attacker, b.victim) {
+                        p.team_flashes += 1;
+                        p.team_blind_duration += b.duration;
+                    } else {
+                        p.enemies_blinded += 1;
+                        p.enemy_blind_duration += b.duration;
+                    }
+                }
+            }
+
+            let mut round_damage: HashMap<i32, i32> = HashMap::new();
+            for u in &round.utility {
+                match u.kind {
+                    CsgoDemoUtilityKind::Smoke | CsgoDemoUtilityKind::Molotov | CsgoDemoUtilityKind::Fire => landings.push(CsgoUtilityLanding{
+                        round_num: round.round_num,
+                        tm: u.tm.clone(),
+                        kind: u.kind,
+                        thrower: u.thrower,
+                        x: u.x,
+                        y: u.y,
+                        z: u.z,
+                    }),
+                    _ => (),
+                };
+
+                let thrower = match u.thrower {
+                    Some(x) => x,
+                    None => continue,
+                };
+
+                if u.kind == CsgoDemoUtilityKind::Flashbang {
+                    if let Some(p) = players.get_mut(&thrower) {
+                        p.flashes_thrown += 1;
+                    }
+                }
+
+                for d in &u.damage {
+                    if d.receiver == thrower || is_same_team(&teams, thrower, d.receiver) {
+                        continue;
+                    }
+                    *round_damage.entry(thrower).or_insert(0) += d.damage_health;
+                }
+            }
+
+            for (user_id, damage) in round_damage {
+                if let Some(p) = players.get_mut(&user_id) {
+                    p.utility_damage += damage;
+                    p.round_utility_damage.push(CsgoRoundUtilityDamage{
+                        round_num: round.round_num,
+                        damage,
+                    });
+                }
+            }
+        }
+
+        let mut players: Vec<CsgoPlayerUtilityReport> = players.into_iter().map(|(_, v)| { v }).collect();
+        players.sort_by_key(|x| { x.user_id });
+        Self {
+            players,
+            landings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csgo::{
+        schema::{
+            CsgoCommonPlayer,
+            CsgoCommonRoundPlayerStats,
+            CsgoCommonRoundKill,
+            CsgoCommonRoundBlind,
+            CsgoCommonRoundUtility,
+            CsgoCommonRoundUtilityDamage,
+            CsgoEventSource,
+        },
+        weapon::CsgoWeapon,
+    };
+    use crate::steam::SteamAccount;
+    use chrono::TimeZone;
+    use uuid::Uuid;
+
+    fn tm() -> DateTime<Utc> {
+        Utc.timestamp(0, 0)
+    }
+
+    fn player(user_id: i32) -> CsgoCommonPlayer {
+        CsgoCommonPlayer{
+            container_id: 0,
+            user_id,
+            steam_account: SteamAccount{
+                steam_id: user_id as i64,
+                name: format!("player{}", user_id),
+                profile_image_url: None,
+            },
+            kills: 0,
+            deaths: 0,
+            assists: 0,
+            mvps: 0,
+        }
+    }
+
+    fn player_stats(round_num: i32, user_id: i32, team: CsgoTeam) -> CsgoCommonRoundPlayerStats {
+        CsgoCommonRoundPlayerStats{
+            container_id: 0,
+            round_num,
+            user_id,
+            kills: 0,
+            deaths: 0,
+            assists: 0,
+            mvp: false,
+            equipment_value: None,
+            money: None,
+            headshot_kills: None,
+            utility_damage: None,
+            enemies_flashed: None,
+            damage: None,
+            armor: None,
+            has_defuse: None,
+            has_helmet: None,
+            team,
+            weapons: vec![],
+            start_money: None,
+            spent: None,
+        }
+    }
+
+    fn blind(round_num: i32, victim: i32, attacker: Option<i32>, duration: f32) -> CsgoCommonRoundBlind {
+        CsgoCommonRoundBlind{
+            container_id: 0,
+            round_num,
+            tm: tm(),
+            victim,
+            attacker,
+            duration,
+            utility_id: None,
+        }
+    }
+
+    fn utility(round_num: i32, utility_id: i32, kind: CsgoDemoUtilityKind, thrower: Option<i32>, damage: &[(i32, i32)]) -> CsgoCommonRoundUtility {
+        CsgoCommonRoundUtility{
+            container_id: 0,
+            round_num,
+            utility_id,
+            tm: tm(),
+            kind,
+            thrower,
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+            damage: damage.iter().map(|(receiver, damage_health)| {
+                CsgoCommonRoundUtilityDamage{
+                    tm: tm(),
+                    receiver: *receiver,
+                    damage_health: *damage_health,
+                    damage_armor: 0,
+                }
+            }).collect(),
+        }
+    }
+
+    fn round(round_num: i32) -> CsgoCommonRound {
+        CsgoCommonRound{
+            container_id: 0,
+            round_num,
+            tm_round_start: None,
+            tm_round_play: None,
+            tm_round_end: None,
+            bomb_state: None,
+            tm_bomb_plant: None,
+            bomb_plant_user: None,
+            bomb_plant_site: None,
+            tm_bomb_event: None,
+            bomb_event_user: None,
+            winning_team: None,
+            round_win_reason: None,
+            round_mvp: None,
+            // Players 1 and 2 are on T and player 3 is on CT.
+            player_stats: vec![
+                player_stats(round_num, 1, CsgoTeam::TeamT),
+                player_stats(round_num, 2, CsgoTeam::TeamT),
+                player_stats(round_num, 3, CsgoTeam::TeamCT),
+            ],
+            kills: vec![],
+            damage: vec![],
+            utility: vec![],
+            blinds: vec![],
+        }
+    }
+
+    #[test]
+    fn test_utility_report_from_container() {
+        let mut first = round(0);
+        first.kills.push(CsgoCommonRoundKill{
+            container_id: 0,
+            round_num: 0,
+            tm: tm(),
+            victim: Some(3),
+            killer: Some(2),
+            assister: Some(1),
+            flash_assist: Some(true),
+            headshot: None,
+            smoke: None,
+            blind: None,
+            wallbang: None,
+            noscope: None,
+            weapon: Some(CsgoWeapon::Ak47),
+        });
+        first.blinds = vec![
+            blind(0, 3, Some(1), 2.0),
+            blind(0, 2, Some(1), 1.5),
+            // Flashing yourself doesn't count for anything.
+            blind(0, 1, Some(1), 3.0),
+            blind(0, 3, None, 1.0),
+        ];
+        first.utility = vec![
+            utility(0, 0, CsgoDemoUtilityKind::Flashbang, Some(1), &[]),
+            // Team damage doesn't count towards utility damage.
+            utility(0, 1, CsgoDemoUtilityKind::He, Some(2), &[(3, 40), (1, 10)]),
+            utility(0, 2, CsgoDemoUtilityKind::Molotov, Some(3), &[(1, 20)]),
+            utility(0, 3, CsgoDemoUtilityKind::Smoke, None, &[]),
+        ];
+
+        // Rounds should get processed in order regardless of how they're stored.
+        let mut second = round(1);
+        second.utility = vec![
+            utility(1, 0, CsgoDemoUtilityKind::He, Some(2), &[(3, 25)]),
+        ];
+
+        let container = CsgoCommonEventContainer{
+            id: 0,
+            view_uuid: Uuid::nil(),
+            event_source: CsgoEventSource::Demo,
+            rounds: vec![second, first],
+            players: vec![player(3), player(1), player(2)],
+        };
+
+        let report = CsgoUtilityReport::from_container(&container);
+        assert_eq!(report.players.iter().map(|x| { x.user_id }).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        let p1 = &report.players[0];
+        assert_eq!(p1.flash_assists, 1);
+        assert_eq!(p1.flashes_thrown, 1);
+        assert_eq!(p1.enemies_blinded, 1);
+        assert_eq!(p1.enemy_blind_duration, 2.0);
+        assert_eq!(p1.team_flashes, 1);
+        assert_eq!(p1.team_blind_duration, 1.5);
+        assert_eq!(p1.utility_damage, 0);
+
+        let p2 = &report.players[1];
+        assert_eq!(p2.utility_damage, 65);
+        assert_eq!(p2.round_utility_damage.iter().map(|x| { (x.round_num, x.damage) }).collect::<Vec<_>>(), vec![(0, 40), (1, 25)]);
+
+        let p3 = &report.players[2];
+        assert_eq!(p3.utility_damage, 20);
+        assert_eq!(p3.flashes_thrown, 0);
+
+        // Only smokes, molotovs and fires are landings.
+        assert_eq!(report.landings.iter().map(|x| { (x.round_num, x.kind, x.thrower) }).collect::<Vec<_>>(), vec![
+            (0, CsgoDemoUtilityKind::Molotov, Some(3)),
+            (0, CsgoDemoUtilityKind::Smoke, None),
+        ]);
+    }
+}
@@ -19,4 +19,9 @@ pub struct DiscordUser {
     pub username: String,
     pub discriminator: String,
     pub avatar: Option<String>,
+    // Only available when the user granted us the email scope.
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub verified: Option<bool>,
 }
\ No newline at end of file
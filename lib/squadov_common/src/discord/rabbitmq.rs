@@ -11,6 +11,7 @@ use crate::{
         bot::DiscordBotConfig,
         db,
     },
+    features,
     subscriptions::{
         self,
         SquadOvSubTiers,
@@ -111,18 +112,9 @@ impl DiscordTaskConsumer {
                 },
             }
 
-            // Check whether or not the user is early access based on their feature flag.
-            let early_access = sqlx::query!(
-                "
-                SELECT early_access
-                FROM squadov.user_feature_flags
-                WHERE user_id = $1
-                ",
-                user_id,
-            )
-                .fetch_one(&*self.db)
-                .await?
-                .early_access;
+            // Check whether or not the user is early access based on their feature flag. Not cached since this usually
+            // runs right after the user's subscription (and thus their flags) changed.
+            let early_access = features::get_evaluated_feature_flags(&*self.db, user_id, None).await?.flags.early_access;
 
             if early_access {
                 self.http.http().add_member_role(self.config.server_id, discord_user_id, self.config.roles.early_access, None).await?;
@@ -7,6 +7,7 @@ use crate::{
         RabbitMqListener,
         RabbitMqConfig,
         RABBITMQ_DEFAULT_PRIORITY,
+        RABBITMQ_LOW_PRIORITY,
     },
     elastic::{
         ElasticSearchConfig,
@@ -29,6 +30,7 @@ use crate::{
         db as vdb,
     },
     combatlog::interface::CombatLogInterface,
+    teammates,
 };
 use std::{
     sync::Arc,
@@ -66,6 +68,9 @@ pub enum ElasticSearchSyncTask {
     UpdateVodCopies{
         video_uuid: Vec<Uuid>,
     },
+    SyncTeammateRoster{
+        video_uuid: Uuid,
+    },
 }
 
 pub struct ElasticSearchJobInterface {
@@ -275,11 +280,43 @@ impl ElasticSearchJobInterface {
         Ok(())
     }
 
+    // The VOD document already has everything we need to know about who was in the match so this is where
+    // the teammate analytics get kept up to date.
+    async fn sync_teammate_roster(&self, video_uuid: &Uuid, doc: &elastic::vod::ESVodDocument) -> Result<(), SquadOvError> {
+        let roster = teammates::roster::build_match_roster(&*self.db, doc).await?;
+        let mut tx = self.db.begin().await?;
+        if let Some(roster) = roster {
+            teammates::db::store_match_roster(&mut tx, &roster).await?;
+        }
+        teammates::db::finish_teammate_roster_sync(&mut tx, video_uuid).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // For VODs that were queued up for a roster sync (see teammates::db::queue_teammate_roster_sync) without needing
+    // to go through a full sync to ES.
+    pub async fn request_sync_teammate_roster(&self, video_uuid: &Uuid) -> Result<(), SquadOvError> {
+        self.rmq.publish(&self.mqconfig.elasticsearch_queue, serde_json::to_vec(&ElasticSearchSyncTask::SyncTeammateRoster{
+            video_uuid: video_uuid.clone(),
+        })?, RABBITMQ_LOW_PRIORITY, ES_MAX_AGE_SECONDS).await;
+        Ok(())
+    }
+
+    async fn handle_sync_teammate_roster(&self, video_uuid: &Uuid) -> Result<(), SquadOvError> {
+        let doc = elastic::vod::build_es_vod_document(&*self.db, video_uuid, self.cl_itf.as_ref().unwrap().clone()).await?;
+        self.sync_teammate_roster(video_uuid, &doc).await
+    }
+
     pub async fn handle_sync_vod(&self, video_uuid: &[Uuid]) -> Result<(), SquadOvError> {
         // TODO: Actually batch?
         for id in video_uuid {
             match elastic::vod::build_es_vod_document(&*self.db, id, self.cl_itf.as_ref().unwrap().clone()).await {
                 Ok(doc) => {
+                    match self.sync_teammate_roster(id, &doc).await {
+                        Ok(_) => (),
+                        Err(err) => log::warn!("Failed to sync teammate roster: {} - {}", id, err),
+                    }
+
                     self.es_client.as_ref().unwrap().add_or_update_document(&self.esconfig.as_ref().unwrap().vod_index_write, id.to_string().as_str(), serde_json::to_value(doc)?).await?;
 
                     // Actually remember when we last sync'd this data.
@@ -384,7 +421,8 @@ impl RabbitMqListener for ElasticSearchJobInterface {
                         Err(err) => log::warn!("Failed to update VOD copy: {}", err),
                     }
                 }
-            }
+            },
+            ElasticSearchSyncTask::SyncTeammateRoster{video_uuid} => self.handle_sync_teammate_roster(&video_uuid).await?,
         };
         Ok(())
     }
@@ -7,6 +7,7 @@ pub struct EmailConfig {
     pub postmark_api_key: String,
     pub invite_template: String,
     pub welcome_template: String,
+    pub new_device_template: Option<String>,
 }
 
 pub struct EmailClient {
@@ -1,3 +1,5 @@
 mod url;
+mod base32;
 
-pub use self::url::*;
\ No newline at end of file
+pub use self::url::*;
+pub use self::base32::*;
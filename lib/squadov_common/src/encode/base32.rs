@@ -0,0 +1,80 @@
+use crate::SquadOvError;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+// RFC 4648 base32 encoding without padding. This is the format authenticator apps expect for TOTP secrets.
+pub fn base32_encode(input: &[u8]) -> String {
+    let mut ret = String::with_capacity((input.len() * 8 + 4) / 5);
+    let mut buffer: u32 = 0;
+    let mut bits: u32 = 0;
+
+    for b in input {
+        buffer = (buffer << 8) | (*b as u32);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            ret.push(BASE32_ALPHABET[((buffer >> bits) & 0x1F) as usize] as char);
+        }
+    }
+
+    if bits > 0 {
+        ret.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1F) as usize] as char);
+    }
+    ret
+}
+
+pub fn base32_decode(input: &str) -> Result<Vec<u8>, SquadOvError> {
+    let mut ret: Vec<u8> = Vec::with_capacity(input.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits: u32 = 0;
+
+    // Be lenient with the input since users tend to copy secrets with spaces/lowercase characters.
+    for ch in input.chars().filter(|x| { !x.is_whitespace() && *x != '=' }) {
+        let upper = ch.to_ascii_uppercase() as u8;
+        let value = BASE32_ALPHABET.iter().position(|x| { *x == upper }).ok_or(SquadOvError::BadRequest)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            ret.push(((buffer >> bits) & 0xFF) as u8);
+        }
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base32_round_trip() {
+        struct TestDatum {
+            input: &'static str,
+            output: &'static str,
+        }
+
+        let test_data = vec![
+            TestDatum{
+                input: "",
+                output: "",
+            },
+            TestDatum{
+                input: "f",
+                output: "MY",
+            },
+            TestDatum{
+                input: "foobar",
+                output: "MZXW6YTBOI",
+            },
+            TestDatum{
+                input: "12345678901234567890",
+                output: "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ",
+            },
+        ];
+
+        for td in &test_data {
+            assert_eq!(base32_encode(td.input.as_bytes()), td.output);
+            assert_eq!(base32_decode(td.output).unwrap(), td.input.as_bytes());
+        }
+    }
+}
@@ -10,11 +10,14 @@ use openssl;
 use serde::Serialize;
 use rusoto_core::RusotoError;
 use rusoto_credential::CredentialsError;
+use crate::storage::StorageQuotaExceeded;
 
 #[derive(Serialize)]
-struct ErrorBody {
+struct ErrorBody<'a> {
     #[serde(rename="duplicateFlag")]
-    duplicate_flag: bool
+    duplicate_flag: bool,
+    #[serde(rename="quotaExceeded", skip_serializing_if="Option::is_none")]
+    quota_exceeded: Option<&'a StorageQuotaExceeded>,
 }
 
 #[derive(Debug, Display, PartialEq)]
@@ -43,6 +46,8 @@ pub enum SquadOvError {
     Failover,
     #[display(fmt = "[SquadovError] Switch Queue")]
     SwitchQueue(String),
+    #[display(fmt = "[SquadovError] Storage Quota Exceeded")]
+    QuotaExceeded(Box<StorageQuotaExceeded>),
 }
 
 impl std::error::Error for SquadOvError {}
@@ -50,7 +55,11 @@ impl std::error::Error for SquadOvError {}
 impl error::ResponseError for SquadOvError {
     fn error_response(&self) -> HttpResponse {
         let body = ErrorBody{
-            duplicate_flag: *self == SquadOvError::Duplicate
+            duplicate_flag: *self == SquadOvError::Duplicate,
+            quota_exceeded: match self {
+                SquadOvError::QuotaExceeded(x) => Some(x.as_ref()),
+                _ => None,
+            },
         };
 
         HttpResponseBuilder::new(self.status_code()).json(&body)
@@ -67,7 +76,7 @@ impl error::ResponseError for SquadOvError {
             SquadOvError::Defer(_) | SquadOvError::Failover | SquadOvError::SwitchQueue(_) => StatusCode::SERVICE_UNAVAILABLE,
             SquadOvError::RateLimit => StatusCode::TOO_MANY_REQUESTS,
             SquadOvError::TwoFactor(_) => StatusCode::ACCEPTED,
-            SquadOvError::Forbidden => StatusCode::FORBIDDEN,
+            SquadOvError::Forbidden | SquadOvError::QuotaExceeded(_) => StatusCode::FORBIDDEN,
         }
     }
 }
@@ -378,4 +387,16 @@ impl From<serenity::prelude::SerenityError> for SquadOvError {
             _ => Self::InternalError(format!("Serenity Error {:?}", err)),
         }
     }
-}
\ No newline at end of file
+}
+
+impl From<xml::reader::Error> for SquadOvError {
+    fn from(err: xml::reader::Error) -> Self {
+        return Self::InternalError(format!("XML Reader {:?}", err))
+    }
+}
+
+impl From<xml::writer::Error> for SquadOvError {
+    fn from(err: xml::writer::Error) -> Self {
+        return Self::InternalError(format!("XML Writer {:?}", err))
+    }
+}
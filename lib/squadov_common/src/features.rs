@@ -0,0 +1,463 @@
+// Generic feature flags. Each flag has a typed definition with a default value; rules can target the flag at
+// specific users, squads, subscription tiers, client versions, a percentage of users, or early access users; and
+// admins can override the flag for individual users. Overrides beat rules and rules beat the default.
+pub mod db;
+mod user;
+
+pub use user::*;
+
+use crate::{
+    SquadOvError,
+    subscriptions::SquadOvSubTiers,
+};
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Display, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all="snake_case")]
+pub enum FeatureFlagType {
+    #[display(fmt="bool")]
+    Bool,
+    #[display(fmt="int")]
+    Int,
+    #[display(fmt="float")]
+    Float,
+    #[display(fmt="string")]
+    String,
+    #[display(fmt="json")]
+    Json,
+}
+
+impl FromStr for FeatureFlagType {
+    type Err = SquadOvError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "bool" => FeatureFlagType::Bool,
+            "int" => FeatureFlagType::Int,
+            "float" => FeatureFlagType::Float,
+            "string" => FeatureFlagType::String,
+            "json" => FeatureFlagType::Json,
+            _ => return Err(SquadOvError::BadRequest),
+        })
+    }
+}
+
+impl FeatureFlagType {
+    // Null is allowed for everything but booleans so that flags can be "unset" (e.g. no limit).
+    pub fn validate(&self, value: &serde_json::Value) -> Result<(), SquadOvError> {
+        let valid = match self {
+            FeatureFlagType::Bool => value.is_boolean(),
+            FeatureFlagType::Int => value.is_i64() || value.is_null(),
+            FeatureFlagType::Float => value.is_number() || value.is_null(),
+            FeatureFlagType::String => value.is_string() || value.is_null(),
+            FeatureFlagType::Json => true,
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(SquadOvError::BadRequest)
+        }
+    }
+}
+
+// Keys end up as JSON keys when the flags get sent to the client so keep them simple.
+pub fn validate_feature_flag_key(key: &str) -> Result<(), SquadOvError> {
+    if key.is_empty() || key == "user_id" || !key.chars().all(|c| { c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' }) {
+        return Err(SquadOvError::BadRequest);
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all="camelCase")]
+pub struct FeatureFlagDefinition {
+    pub flag_key: String,
+    pub flag_type: FeatureFlagType,
+    pub default_value: serde_json::Value,
+    pub description: Option<String>,
+    pub created_tm: DateTime<Utc>,
+    pub updated_tm: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag="type", rename_all="camelCase")]
+pub enum FeatureFlagCondition {
+    #[serde(rename_all="camelCase")]
+    Users{
+        user_ids: Vec<i64>,
+    },
+    #[serde(rename_all="camelCase")]
+    Squads{
+        squad_ids: Vec<i64>,
+    },
+    Tiers{
+        tiers: Vec<SquadOvSubTiers>,
+    },
+    // Inclusive on both ends. Users we don't know the client version for never match.
+    ClientVersion{
+        min: Option<String>,
+        max: Option<String>,
+    },
+    // Between 0 and 100. Each user lands in the same bucket every time for a given flag.
+    Percentage{
+        percent: f64,
+    },
+    #[serde(rename_all="camelCase")]
+    EarlyAccess{
+        early_access: bool,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all="camelCase")]
+pub struct FeatureFlagRule {
+    pub id: i64,
+    pub flag_key: String,
+    pub priority: i32,
+    pub conditions: Vec<FeatureFlagCondition>,
+    pub value: serde_json::Value,
+    pub enabled: bool,
+    pub created_tm: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all="camelCase")]
+pub struct FeatureFlagOverride {
+    pub flag_key: String,
+    pub user_id: i64,
+    pub value: serde_json::Value,
+    pub created_tm: DateTime<Utc>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all="camelCase")]
+pub struct FeatureFlagAuditEntry {
+    pub id: i64,
+    pub flag_key: String,
+    pub admin_user_id: Option<i64>,
+    pub action: String,
+    pub old_value: Option<serde_json::Value>,
+    pub new_value: Option<serde_json::Value>,
+    pub tm: DateTime<Utc>,
+}
+
+// Everything about the user that rules can target.
+#[derive(Clone, Debug)]
+pub struct FeatureFlagContext {
+    pub user_id: i64,
+    pub squad_ids: Vec<i64>,
+    pub tier: SquadOvSubTiers,
+    pub client_version: Option<String>,
+    pub early_access: bool,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag="type", rename_all="camelCase")]
+pub enum FeatureFlagSource {
+    Default,
+    #[serde(rename_all="camelCase")]
+    Rule{
+        rule_id: i64,
+    },
+    Override,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all="camelCase")]
+pub struct EvaluatedFeatureFlag {
+    pub flag_key: String,
+    pub value: serde_json::Value,
+    pub source: FeatureFlagSource,
+}
+
+// All the definitions and enabled rules (sorted by priority). This is everything needed to evaluate flags for any
+// user besides their overrides.
+#[derive(Clone, Debug, Default)]
+pub struct FeatureFlagConfig {
+    pub definitions: Vec<FeatureFlagDefinition>,
+    pub rules: HashMap<String, Vec<FeatureFlagRule>>,
+}
+
+impl FeatureFlagConfig {
+    pub fn new(definitions: Vec<FeatureFlagDefinition>, rules: Vec<FeatureFlagRule>) -> Self {
+        let mut grouped: HashMap<String, Vec<FeatureFlagRule>> = HashMap::new();
+        for r in rules.into_iter().filter(|x| { x.enabled }) {
+            grouped.entry(r.flag_key.clone()).or_insert(vec![]).push(r);
+        }
+
+        for rules in grouped.values_mut() {
+            rules.sort_by(|a, b| { a.priority.cmp(&b.priority).then(a.id.cmp(&b.id)) });
+        }
+
+        Self {
+            definitions,
+            rules: grouped,
+        }
+    }
+
+    // Lets callers skip looking up squads/tiers when no rule cares about them.
+    pub fn has_condition<F>(&self, f: F) -> bool
+    where
+        F: Fn(&FeatureFlagCondition) -> bool
+    {
+        self.rules.values().flatten().any(|r| { r.conditions.iter().any(|c| { f(c) }) })
+    }
+
+    pub fn evaluate(&self, ctx: &FeatureFlagContext, overrides: &HashMap<String, serde_json::Value>) -> Vec<EvaluatedFeatureFlag> {
+        self.definitions.iter().map(|d| {
+            evaluate_feature_flag(d, self.rules.get(&d.flag_key).map(|x| { x.as_slice() }).unwrap_or(&[]), overrides.get(&d.flag_key), ctx)
+        }).collect()
+    }
+}
+
+fn parse_client_version(v: &str) -> Option<Vec<u64>> {
+    v.trim().trim_start_matches('v').split('.').map(|x| { x.parse::<u64>().ok() }).collect()
+}
+
+// Compares dotted versions numerically (so 0.10.0 > 0.9.1). Missing trailing parts count as 0.
+pub fn compare_client_versions(a: &str, b: &str) -> Option<Ordering> {
+    let a = parse_client_version(a)?;
+    let b = parse_client_version(b)?;
+    for i in 0..std::cmp::max(a.len(), b.len()) {
+        let x = a.get(i).unwrap_or(&0);
+        let y = b.get(i).unwrap_or(&0);
+        if x != y {
+            return Some(x.cmp(y));
+        }
+    }
+    Some(Ordering::Equal)
+}
+
+// A stable number in [0, 100) for the user/flag pair. Including the flag key means the same users don't end up in
+// every rollout first.
+pub fn feature_flag_rollout_bucket(flag_key: &str, user_id: i64) -> f64 {
+    let hash = Sha256::digest(format!("{}:{}", flag_key, user_id).as_bytes());
+    let n = u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]);
+    (n % 10000) as f64 / 100.0
+}
+
+impl FeatureFlagCondition {
+    pub fn validate(&self) -> Result<(), SquadOvError> {
+        let valid = match self {
+            FeatureFlagCondition::ClientVersion{min, max} => {
+                min.iter().chain(max.iter()).all(|x| { parse_client_version(x).is_some() })
+            },
+            FeatureFlagCondition::Percentage{percent} => *percent >= 0.0 && *percent <= 100.0,
+            _ => true,
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(SquadOvError::BadRequest)
+        }
+    }
+
+    pub fn matches(&self, flag_key: &str, ctx: &FeatureFlagContext) -> bool {
+        match self {
+            FeatureFlagCondition::Users{user_ids} => user_ids.contains(&ctx.user_id),
+            FeatureFlagCondition::Squads{squad_ids} => squad_ids.iter().any(|x| { ctx.squad_ids.contains(x) }),
+            FeatureFlagCondition::Tiers{tiers} => tiers.contains(&ctx.tier),
+            FeatureFlagCondition::ClientVersion{min, max} => {
+                if let Some(version) = ctx.client_version.as_ref() {
+                    let above_min = min.as_ref().map(|x| { compare_client_versions(version, x).map(|o| { o != Ordering::Less }).unwrap_or(false) }).unwrap_or(true);
+                    let below_max = max.as_ref().map(|x| { compare_client_versions(version, x).map(|o| { o != Ordering::Greater }).unwrap_or(false) }).unwrap_or(true);
+                    above_min && below_max
+                } else {
+                    false
+                }
+            },
+            FeatureFlagCondition::Percentage{percent} => feature_flag_rollout_bucket(flag_key, ctx.user_id) < *percent,
+            FeatureFlagCondition::EarlyAccess{early_access} => ctx.early_access == *early_access,
+        }
+    }
+}
+
+pub fn evaluate_feature_flag(definition: &FeatureFlagDefinition, rules: &[FeatureFlagRule], user_override: Option<&serde_json::Value>, ctx: &FeatureFlagContext) -> EvaluatedFeatureFlag {
+    if let Some(value) = user_override {
+        return EvaluatedFeatureFlag{
+            flag_key: definition.flag_key.clone(),
+            value: value.clone(),
+            source: FeatureFlagSource::Override,
+        };
+    }
+
+    for r in rules {
+        if r.enabled && r.conditions.iter().all(|c| { c.matches(&definition.flag_key, ctx) }) {
+            return EvaluatedFeatureFlag{
+                flag_key: definition.flag_key.clone(),
+                value: r.value.clone(),
+                source: FeatureFlagSource::Rule{
+                    rule_id: r.id,
+                },
+            };
+        }
+    }
+
+    EvaluatedFeatureFlag{
+        flag_key: definition.flag_key.clone(),
+        value: definition.default_value.clone(),
+        source: FeatureFlagSource::Default,
+    }
+}
+
+// Flag keys are snake case but the flags get sent to the client camel cased (to match the other JSON we send).
+pub fn feature_flag_key_to_json_key(key: &str) -> String {
+    let mut ret = String::new();
+    let mut upper = false;
+    for c in key.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            ret.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            ret.push(c);
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(key: &str, default_value: serde_json::Value) -> FeatureFlagDefinition {
+        FeatureFlagDefinition{
+            flag_key: key.to_string(),
+            flag_type: FeatureFlagType::Json,
+            default_value,
+            description: None,
+            created_tm: Utc::now(),
+            updated_tm: Utc::now(),
+        }
+    }
+
+    fn rule(id: i64, key: &str, priority: i32, conditions: Vec<FeatureFlagCondition>, value: serde_json::Value) -> FeatureFlagRule {
+        FeatureFlagRule{
+            id,
+            flag_key: key.to_string(),
+            priority,
+            conditions,
+            value,
+            enabled: true,
+            created_tm: Utc::now(),
+        }
+    }
+
+    fn context(user_id: i64) -> FeatureFlagContext {
+        FeatureFlagContext{
+            user_id,
+            squad_ids: vec![7],
+            tier: SquadOvSubTiers::Silver,
+            client_version: Some(String::from("0.12.3")),
+            early_access: false,
+        }
+    }
+
+    #[test]
+    fn test_validate_type() {
+        assert!(FeatureFlagType::Bool.validate(&serde_json::json!(true)).is_ok());
+        assert!(FeatureFlagType::Bool.validate(&serde_json::Value::Null).is_err());
+        assert!(FeatureFlagType::Int.validate(&serde_json::json!(5)).is_ok());
+        assert!(FeatureFlagType::Int.validate(&serde_json::json!(5.5)).is_err());
+        assert!(FeatureFlagType::Int.validate(&serde_json::Value::Null).is_ok());
+        assert!(FeatureFlagType::Float.validate(&serde_json::json!(5)).is_ok());
+        assert!(FeatureFlagType::String.validate(&serde_json::json!(5)).is_err());
+        assert!(FeatureFlagType::Json.validate(&serde_json::json!({"a": [1]})).is_ok());
+        assert_eq!(FeatureFlagType::from_str(&format!("{}", FeatureFlagType::Float)).unwrap(), FeatureFlagType::Float);
+    }
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_feature_flag_key("max_clip_seconds").is_ok());
+        assert!(validate_feature_flag_key("user_id").is_err());
+        assert!(validate_feature_flag_key("maxClipSeconds").is_err());
+        assert!(validate_feature_flag_key("").is_err());
+    }
+
+    #[test]
+    fn test_validate_condition() {
+        assert!(FeatureFlagCondition::Percentage{percent: 50.0}.validate().is_ok());
+        assert!(FeatureFlagCondition::Percentage{percent: 101.0}.validate().is_err());
+        assert!(FeatureFlagCondition::ClientVersion{min: Some(String::from("0.1.0")), max: None}.validate().is_ok());
+        assert!(FeatureFlagCondition::ClientVersion{min: None, max: Some(String::from("latest"))}.validate().is_err());
+    }
+
+    #[test]
+    fn test_compare_client_versions() {
+        assert_eq!(compare_client_versions("0.10.0", "0.9.1"), Some(Ordering::Greater));
+        assert_eq!(compare_client_versions("v1.2", "1.2.0"), Some(Ordering::Equal));
+        assert_eq!(compare_client_versions("1.2.0-beta", "1.2.0"), None);
+    }
+
+    #[test]
+    fn test_rollout_bucket_is_stable() {
+        let a = feature_flag_rollout_bucket("new_thing", 42);
+        assert_eq!(a, feature_flag_rollout_bucket("new_thing", 42));
+        assert!(a >= 0.0 && a < 100.0);
+
+        // Roughly the right fraction of users should fall under a percentage.
+        let count = (0..10000).filter(|x| { feature_flag_rollout_bucket("new_thing", *x) < 25.0 }).count();
+        assert!(count > 2200 && count < 2800);
+    }
+
+    #[test]
+    fn test_evaluation_order() {
+        let config = FeatureFlagConfig::new(
+            vec![definition("max_clip_seconds", serde_json::json!(120))],
+            vec![
+                rule(1, "max_clip_seconds", 10, vec![FeatureFlagCondition::Tiers{tiers: vec![SquadOvSubTiers::Silver, SquadOvSubTiers::Gold]}], serde_json::json!(300)),
+                rule(2, "max_clip_seconds", 0, vec![FeatureFlagCondition::Squads{squad_ids: vec![7]}, FeatureFlagCondition::ClientVersion{min: Some(String::from("0.13.0")), max: None}], serde_json::json!(600)),
+                rule(3, "max_clip_seconds", 5, vec![FeatureFlagCondition::Users{user_ids: vec![2]}], serde_json::json!(900)),
+            ],
+        );
+
+        // Rule 2 doesn't match because of the client version so the tier rule wins.
+        let flags = config.evaluate(&context(1), &HashMap::new());
+        assert_eq!(flags[0].value, serde_json::json!(300));
+        assert_eq!(flags[0].source, FeatureFlagSource::Rule{rule_id: 1});
+
+        // Lower priority numbers get checked first.
+        let flags = config.evaluate(&context(2), &HashMap::new());
+        assert_eq!(flags[0].value, serde_json::json!(900));
+
+        let mut ctx = context(1);
+        ctx.tier = SquadOvSubTiers::Basic;
+        ctx.client_version = None;
+        let flags = config.evaluate(&ctx, &HashMap::new());
+        assert_eq!(flags[0].value, serde_json::json!(120));
+        assert_eq!(flags[0].source, FeatureFlagSource::Default);
+
+        let mut overrides = HashMap::new();
+        overrides.insert(String::from("max_clip_seconds"), serde_json::json!(30));
+        let flags = config.evaluate(&context(2), &overrides);
+        assert_eq!(flags[0].value, serde_json::json!(30));
+        assert_eq!(flags[0].source, FeatureFlagSource::Override);
+    }
+
+    #[test]
+    fn test_condition_serialization() {
+        let conditions: Vec<FeatureFlagCondition> = serde_json::from_value(serde_json::json!([
+            {"type": "tiers", "tiers": ["GOLD", "diamond"]},
+            {"type": "percentage", "percent": 12.5},
+            {"type": "earlyAccess", "earlyAccess": true},
+            {"type": "clientVersion", "min": "0.1.0", "max": null},
+        ])).unwrap();
+        assert_eq!(conditions[0], FeatureFlagCondition::Tiers{tiers: vec![SquadOvSubTiers::Gold, SquadOvSubTiers::Diamond]});
+        assert_eq!(conditions[2], FeatureFlagCondition::EarlyAccess{early_access: true});
+    }
+
+    #[test]
+    fn test_json_key() {
+        assert_eq!(feature_flag_key_to_json_key("max_record_pixel_y"), "maxRecordPixelY");
+        assert_eq!(feature_flag_key_to_json_key("allow_vp9"), "allowVp9");
+    }
+}
@@ -0,0 +1,502 @@
+use crate::{
+    SquadOvError,
+    features::{
+        FeatureFlagAuditEntry,
+        FeatureFlagCondition,
+        FeatureFlagDefinition,
+        FeatureFlagOverride,
+        FeatureFlagRule,
+        FeatureFlagType,
+    },
+};
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres};
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
+
+#[derive(sqlx::FromRow)]
+struct FeatureFlagDefinitionRow {
+    flag_key: String,
+    flag_type: String,
+    default_value: serde_json::Value,
+    description: Option<String>,
+    created_tm: DateTime<Utc>,
+    updated_tm: DateTime<Utc>,
+}
+
+impl TryFrom<FeatureFlagDefinitionRow> for FeatureFlagDefinition {
+    type Error = SquadOvError;
+
+    fn try_from(x: FeatureFlagDefinitionRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            flag_key: x.flag_key,
+            flag_type: FeatureFlagType::from_str(&x.flag_type)?,
+            default_value: x.default_value,
+            description: x.description,
+            created_tm: x.created_tm,
+            updated_tm: x.updated_tm,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct FeatureFlagRuleRow {
+    id: i64,
+    flag_key: String,
+    priority: i32,
+    conditions: serde_json::Value,
+    value: serde_json::Value,
+    enabled: bool,
+    created_tm: DateTime<Utc>,
+}
+
+impl TryFrom<FeatureFlagRuleRow> for FeatureFlagRule {
+    type Error = SquadOvError;
+
+    fn try_from(x: FeatureFlagRuleRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: x.id,
+            flag_key: x.flag_key,
+            priority: x.priority,
+            conditions: serde_json::from_value(x.conditions)?,
+            value: x.value,
+            enabled: x.enabled,
+            created_tm: x.created_tm,
+        })
+    }
+}
+
+pub async fn list_feature_flag_definitions<'a, T>(ex: T) -> Result<Vec<FeatureFlagDefinition>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, FeatureFlagDefinitionRow>(
+        "
+        SELECT *
+        FROM squadov.feature_flag_definitions
+        ORDER BY flag_key
+        "
+    )
+        .fetch_all(ex)
+        .await?
+        .into_iter()
+        .map(|x| { x.try_into() })
+        .collect()
+}
+
+pub async fn get_feature_flag_definition<'a, T>(ex: T, flag_key: &str) -> Result<FeatureFlagDefinition, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, FeatureFlagDefinitionRow>(
+        "
+        SELECT *
+        FROM squadov.feature_flag_definitions
+        WHERE flag_key = $1
+        "
+    )
+        .bind(flag_key)
+        .fetch_optional(ex)
+        .await?
+        .ok_or(SquadOvError::NotFound)?
+        .try_into()
+}
+
+pub async fn create_feature_flag_definition<'a, T>(ex: T, flag_key: &str, flag_type: FeatureFlagType, default_value: &serde_json::Value, description: Option<&str>) -> Result<FeatureFlagDefinition, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, FeatureFlagDefinitionRow>(
+        "
+        INSERT INTO squadov.feature_flag_definitions (
+            flag_key,
+            flag_type,
+            default_value,
+            description
+        ) VALUES (
+            $1,
+            $2,
+            $3,
+            $4
+        )
+        RETURNING *
+        "
+    )
+        .bind(flag_key)
+        .bind(format!("{}", flag_type))
+        .bind(default_value)
+        .bind(description)
+        .fetch_one(ex)
+        .await?
+        .try_into()
+}
+
+// The type can't be changed since existing rules and overrides would no longer make sense.
+pub async fn update_feature_flag_definition<'a, T>(ex: T, flag_key: &str, default_value: &serde_json::Value, description: Option<&str>) -> Result<FeatureFlagDefinition, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, FeatureFlagDefinitionRow>(
+        "
+        UPDATE squadov.feature_flag_definitions
+        SET default_value = $2,
+            description = $3,
+            updated_tm = NOW()
+        WHERE flag_key = $1
+        RETURNING *
+        "
+    )
+        .bind(flag_key)
+        .bind(default_value)
+        .bind(description)
+        .fetch_optional(ex)
+        .await?
+        .ok_or(SquadOvError::NotFound)?
+        .try_into()
+}
+
+pub async fn delete_feature_flag_definition<'a, T>(ex: T, flag_key: &str) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        DELETE FROM squadov.feature_flag_definitions
+        WHERE flag_key = $1
+        "
+    )
+        .bind(flag_key)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn list_feature_flag_rules<'a, T>(ex: T, flag_key: Option<&str>) -> Result<Vec<FeatureFlagRule>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, FeatureFlagRuleRow>(
+        "
+        SELECT *
+        FROM squadov.feature_flag_rules
+        WHERE $1::VARCHAR IS NULL OR flag_key = $1
+        ORDER BY flag_key, priority, id
+        "
+    )
+        .bind(flag_key)
+        .fetch_all(ex)
+        .await?
+        .into_iter()
+        .map(|x| { x.try_into() })
+        .collect()
+}
+
+pub async fn get_feature_flag_rule<'a, T>(ex: T, flag_key: &str, rule_id: i64) -> Result<FeatureFlagRule, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, FeatureFlagRuleRow>(
+        "
+        SELECT *
+        FROM squadov.feature_flag_rules
+        WHERE flag_key = $1
+            AND id = $2
+        "
+    )
+        .bind(flag_key)
+        .bind(rule_id)
+        .fetch_optional(ex)
+        .await?
+        .ok_or(SquadOvError::NotFound)?
+        .try_into()
+}
+
+pub async fn create_feature_flag_rule<'a, T>(ex: T, flag_key: &str, priority: i32, conditions: &[FeatureFlagCondition], value: &serde_json::Value, enabled: bool) -> Result<FeatureFlagRule, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, FeatureFlagRuleRow>(
+        "
+        INSERT INTO squadov.feature_flag_rules (
+            flag_key,
+            priority,
+            conditions,
+            value,
+            enabled
+        ) VALUES (
+            $1,
+            $2,
+            $3,
+            $4,
+            $5
+        )
+        RETURNING *
+        "
+    )
+        .bind(flag_key)
+        .bind(priority)
+        .bind(serde_json::to_value(conditions)?)
+        .bind(value)
+        .bind(enabled)
+        .fetch_one(ex)
+        .await?
+        .try_into()
+}
+
+pub async fn update_feature_flag_rule<'a, T>(ex: T, flag_key: &str, rule_id: i64, priority: i32, conditions: &[FeatureFlagCondition], value: &serde_json::Value, enabled: bool) -> Result<FeatureFlagRule, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, FeatureFlagRuleRow>(
+        "
+        UPDATE squadov.feature_flag_rules
+        SET priority = $3,
+            conditions = $4,
+            value = $5,
+            enabled = $6
+        WHERE flag_key = $1
+            AND id = $2
+        RETURNING *
+        "
+    )
+        .bind(flag_key)
+        .bind(rule_id)
+        .bind(priority)
+        .bind(serde_json::to_value(conditions)?)
+        .bind(value)
+        .bind(enabled)
+        .fetch_optional(ex)
+        .await?
+        .ok_or(SquadOvError::NotFound)?
+        .try_into()
+}
+
+pub async fn delete_feature_flag_rule<'a, T>(ex: T, flag_key: &str, rule_id: i64) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        DELETE FROM squadov.feature_flag_rules
+        WHERE flag_key = $1
+            AND id = $2
+        "
+    )
+        .bind(flag_key)
+        .bind(rule_id)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn list_feature_flag_overrides<'a, T>(ex: T, flag_key: &str) -> Result<Vec<FeatureFlagOverride>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, (String, i64, serde_json::Value, DateTime<Utc>)>(
+            "
+            SELECT flag_key, user_id, value, created_tm
+            FROM squadov.feature_flag_overrides
+            WHERE flag_key = $1
+            ORDER BY user_id
+            "
+        )
+            .bind(flag_key)
+            .fetch_all(ex)
+            .await?
+            .into_iter()
+            .map(|x| {
+                FeatureFlagOverride{
+                    flag_key: x.0,
+                    user_id: x.1,
+                    value: x.2,
+                    created_tm: x.3,
+                }
+            })
+            .collect()
+    )
+}
+
+pub async fn get_user_feature_flag_overrides<'a, T>(ex: T, user_id: i64) -> Result<HashMap<String, serde_json::Value>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, (String, serde_json::Value)>(
+            "
+            SELECT flag_key, value
+            FROM squadov.feature_flag_overrides
+            WHERE user_id = $1
+            "
+        )
+            .bind(user_id)
+            .fetch_all(ex)
+            .await?
+            .into_iter()
+            .collect()
+    )
+}
+
+pub async fn get_feature_flag_override<'a, T>(ex: T, flag_key: &str, user_id: i64) -> Result<Option<serde_json::Value>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar(
+            "
+            SELECT value
+            FROM squadov.feature_flag_overrides
+            WHERE flag_key = $1
+                AND user_id = $2
+            "
+        )
+            .bind(flag_key)
+            .bind(user_id)
+            .fetch_optional(ex)
+            .await?
+    )
+}
+
+pub async fn set_feature_flag_override<'a, T>(ex: T, flag_key: &str, user_id: i64, value: &serde_json::Value) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        INSERT INTO squadov.feature_flag_overrides (
+            flag_key,
+            user_id,
+            value
+        ) VALUES (
+            $1,
+            $2,
+            $3
+        )
+        ON CONFLICT (flag_key, user_id) DO UPDATE
+            SET value = EXCLUDED.value,
+                created_tm = NOW()
+        "
+    )
+        .bind(flag_key)
+        .bind(user_id)
+        .bind(value)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_feature_flag_override<'a, T>(ex: T, flag_key: &str, user_id: i64) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        DELETE FROM squadov.feature_flag_overrides
+        WHERE flag_key = $1
+            AND user_id = $2
+        "
+    )
+        .bind(flag_key)
+        .bind(user_id)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn add_feature_flag_audit_entry<'a, T>(ex: T, flag_key: &str, admin_user_id: i64, action: &str, old_value: Option<serde_json::Value>, new_value: Option<serde_json::Value>) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        INSERT INTO squadov.feature_flag_audit_log (
+            flag_key,
+            admin_user_id,
+            action,
+            old_value,
+            new_value
+        ) VALUES (
+            $1,
+            $2,
+            $3,
+            $4,
+            $5
+        )
+        "
+    )
+        .bind(flag_key)
+        .bind(admin_user_id)
+        .bind(action)
+        .bind(old_value)
+        .bind(new_value)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn list_feature_flag_audit_entries<'a, T>(ex: T, flag_key: Option<&str>, start: i64, end: i64) -> Result<Vec<FeatureFlagAuditEntry>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    #[derive(sqlx::FromRow)]
+    struct Row {
+        id: i64,
+        flag_key: String,
+        admin_user_id: Option<i64>,
+        action: String,
+        old_value: Option<serde_json::Value>,
+        new_value: Option<serde_json::Value>,
+        tm: DateTime<Utc>,
+    }
+
+    Ok(
+        sqlx::query_as::<_, Row>(
+            "
+            SELECT *
+            FROM squadov.feature_flag_audit_log
+            WHERE $1::VARCHAR IS NULL OR flag_key = $1
+            ORDER BY tm DESC, id DESC
+            LIMIT $2 OFFSET $3
+            "
+        )
+            .bind(flag_key)
+            .bind(end - start)
+            .bind(start)
+            .fetch_all(ex)
+            .await?
+            .into_iter()
+            .map(|x| {
+                FeatureFlagAuditEntry{
+                    id: x.id,
+                    flag_key: x.flag_key,
+                    admin_user_id: x.admin_user_id,
+                    action: x.action,
+                    old_value: x.old_value,
+                    new_value: x.new_value,
+                    tm: x.tm,
+                }
+            })
+            .collect()
+    )
+}
+
+// Squads the user is in (for squad targeted rules).
+pub async fn get_feature_flag_squad_ids<'a, T>(ex: T, user_id: i64) -> Result<Vec<i64>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar(
+            "
+            SELECT squad_id
+            FROM squadov.squad_role_assignments
+            WHERE user_id = $1
+            "
+        )
+            .bind(user_id)
+            .fetch_all(ex)
+            .await?
+    )
+}
@@ -0,0 +1,234 @@
+// The per-user flags that predate the generic feature flags plus the glue that applies rules and overrides on top of them.
+use crate::{
+    SquadOvError,
+    rabbitmq::RABBITMQ_DEFAULT_PRIORITY,
+    features::{
+        self,
+        db as fdb,
+        EvaluatedFeatureFlag,
+        FeatureFlagCondition,
+        FeatureFlagConfig,
+        FeatureFlagContext,
+        FeatureFlagSource,
+    },
+    subscriptions::{
+        self,
+        SquadOvSubTiers,
+    },
+};
+use serde::{Serialize, Deserialize};
+use sqlx::{Executor, Postgres, postgres::PgPool};
+use std::sync::Arc;
+use std::collections::HashMap;
+use cached::{TimedCache, proc_macro::cached};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FeatureFlags {
+    pub user_id: i64,
+    pub max_record_pixel_y: i32,
+    pub max_record_fps: i32,
+    pub allow_record_upload: bool,
+    pub allow_wow_combat_log_upload: bool,
+    pub enable_user_profiles: bool,
+    pub disable_sentry: bool,
+    pub max_bitrate_kbps: i32,
+    pub can_instant_clip: bool,
+    pub disable_es_search: bool,
+    pub mandatory_watermark: bool,
+    pub watermark_min_size: f64,
+    pub vod_priority: i16,
+    pub early_access: bool,
+    pub vod_retention: Option<i64>,
+    pub max_squad_size: Option<i64>,
+    pub max_clip_seconds: i64,
+    pub allow_vp9: bool,
+    pub allow_separate_audio_channels: bool,
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self {
+            user_id: -1,
+            max_record_pixel_y: 720,
+            max_record_fps: 60,
+            allow_record_upload: true,
+            allow_wow_combat_log_upload: true,
+            enable_user_profiles: true,
+            disable_sentry: false,
+            max_bitrate_kbps: 6000,
+            can_instant_clip: true,
+            disable_es_search: false,
+            mandatory_watermark: true,
+            watermark_min_size: 0.01,
+            vod_priority: RABBITMQ_DEFAULT_PRIORITY as i16,
+            early_access: false,
+            vod_retention: Some(chrono::Duration::days(7).num_seconds()),
+            max_squad_size: Some(20),
+            max_clip_seconds: 120,
+            allow_vp9: false,
+            allow_separate_audio_channels: false,
+        }
+    }
+}
+
+// These are the per-user values stored for the user (mostly set by their subscription tier). Use
+// get_evaluated_feature_flags to get what the user actually ends up with after rules and overrides.
+pub async fn get_feature_flags<'a, T>(ex: T, user_id: i64) -> Result<FeatureFlags, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as!(
+            FeatureFlags,
+            "
+            SELECT *
+            FROM squadov.user_feature_flags
+            WHERE user_id = $1
+            ",
+            user_id,
+        )
+            .fetch_optional(ex)
+            .await?
+            .unwrap_or(FeatureFlags{
+                user_id,
+                ..FeatureFlags::default()
+            })
+    )
+}
+
+pub async fn update_feature_flags<'a, T>(ex: T, user_id: i64, flags: FeatureFlags) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query!(
+        "
+        UPDATE squadov.user_feature_flags
+        SET max_record_pixel_y = $2,
+            max_record_fps = $3,
+            allow_record_upload = $4,
+            allow_wow_combat_log_upload = $5,
+            enable_user_profiles = $6,
+            disable_sentry = $7,
+            max_bitrate_kbps = $8,
+            can_instant_clip = $9,
+            disable_es_search = $10,
+            mandatory_watermark = $11,
+            watermark_min_size = $12,
+            vod_priority = $13,
+            early_access = $14,
+            vod_retention = $15,
+            max_squad_size = $16,
+            allow_vp9 = $17,
+            allow_separate_audio_channels = $18
+        WHERE user_id = $1
+        ",
+        user_id,
+        flags.max_record_pixel_y,
+        flags.max_record_fps,    
+        flags.allow_record_upload,
+        flags.allow_wow_combat_log_upload,
+        flags.enable_user_profiles,
+        flags.disable_sentry,
+        flags.max_bitrate_kbps,
+        flags.can_instant_clip,
+        flags.disable_es_search,
+        flags.mandatory_watermark,
+        flags.watermark_min_size,
+        flags.vod_priority,
+        flags.early_access,
+        flags.vod_retention,
+        flags.max_squad_size,
+        flags.allow_vp9,
+        flags.allow_separate_audio_channels,
+    )
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+// Flags that don't have a dedicated field in FeatureFlags get tacked onto the end so that the JSON stays the same
+// shape as before for clients that don't know about them.
+#[derive(Serialize, Clone, Debug)]
+pub struct EvaluatedFeatureFlags {
+    #[serde(flatten)]
+    pub flags: FeatureFlags,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+// This gets hit for every session lookup so admin changes to definitions and rules can take a bit to show up.
+#[cached(
+    result=true,
+    type = "TimedCache<(), Arc<FeatureFlagConfig>>",
+    create = "{ TimedCache::with_lifespan_and_capacity(30, 1) }",
+    convert = r#"{ () }"#
+)]
+async fn get_feature_flag_config(pool: &PgPool) -> Result<Arc<FeatureFlagConfig>, SquadOvError> {
+    Ok(Arc::new(FeatureFlagConfig::new(
+        fdb::list_feature_flag_definitions(pool).await?,
+        fdb::list_feature_flag_rules(pool, None).await?,
+    )))
+}
+
+pub async fn evaluate_feature_flags_for_user(pool: &PgPool, user_id: i64, early_access: bool, client_version: Option<&str>) -> Result<Vec<EvaluatedFeatureFlag>, SquadOvError> {
+    let config = get_feature_flag_config(pool).await?;
+    let ctx = FeatureFlagContext{
+        user_id,
+        squad_ids: if config.has_condition(|x| { matches!(x, FeatureFlagCondition::Squads{..}) }) {
+            fdb::get_feature_flag_squad_ids(pool, user_id).await?
+        } else {
+            vec![]
+        },
+        tier: if config.has_condition(|x| { matches!(x, FeatureFlagCondition::Tiers{..}) }) {
+            subscriptions::get_user_sub_tier(pool, user_id).await?
+        } else {
+            SquadOvSubTiers::Basic
+        },
+        client_version: client_version.map(|x| { x.to_string() }),
+        early_access,
+    };
+    Ok(config.evaluate(&ctx, &fdb::get_user_feature_flag_overrides(pool, user_id).await?))
+}
+
+pub async fn get_evaluated_feature_flags(pool: &PgPool, user_id: i64, client_version: Option<&str>) -> Result<EvaluatedFeatureFlags, SquadOvError> {
+    let base = get_feature_flags(pool, user_id).await?;
+    let mut legacy = serde_json::to_value(&base)?;
+    let mut extra: HashMap<String, serde_json::Value> = HashMap::new();
+
+    for f in evaluate_feature_flags_for_user(pool, user_id, base.early_access, client_version).await? {
+        let key = features::feature_flag_key_to_json_key(&f.flag_key);
+        if let Some(existing) = legacy.get_mut(&key) {
+            // The stored per-user value is the baseline for flags in FeatureFlags rather than the definition's default.
+            if f.source != FeatureFlagSource::Default {
+                *existing = f.value;
+            }
+        } else {
+            extra.insert(key, f.value);
+        }
+    }
+
+    Ok(EvaluatedFeatureFlags{
+        flags: match serde_json::from_value::<FeatureFlags>(legacy) {
+            Ok(x) => x,
+            Err(err) => {
+                // Shouldn't happen since values get checked against the flag type but a bad rule shouldn't lock anyone out.
+                log::warn!("Failed to apply feature flag rules for user {}: {:?}", user_id, err);
+                base
+            }
+        },
+        extra,
+    })
+}
+
+// For enforcing flags on the server. Client version rules never match here since there's no client to go off of and
+// changes to the user's flags (or their overrides) can take a bit to show up.
+#[cached(
+    result=true,
+    type = "TimedCache<i64, Arc<EvaluatedFeatureFlags>>",
+    create = "{ TimedCache::with_lifespan_and_capacity(30, 10000) }",
+    convert = r#"{ user_id }"#
+)]
+pub async fn get_cached_evaluated_feature_flags(pool: &PgPool, user_id: i64) -> Result<Arc<EvaluatedFeatureFlags>, SquadOvError> {
+    Ok(Arc::new(get_evaluated_feature_flags(pool, user_id, None).await?))
+}
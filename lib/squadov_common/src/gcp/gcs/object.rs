@@ -1,6 +1,6 @@
 use crate::SquadOvError;
 use reqwest::{StatusCode, header::HeaderMap};
-use serde::Serialize;
+use serde::{Serialize, Deserialize};
 use byteorder::{ByteOrder, BigEndian};
 use rand::Rng;
 use actix_web::web::Bytes;
@@ -104,6 +104,37 @@ impl super::GCSClient {
         Ok(())
     }
     
+    pub async fn get_object_size(&self, bucket_id: &str, path: &str) -> Result<i64, SquadOvError> {
+        let client = self.http.read()?.create_http_client()?;
+
+        // Only the size is needed out of the object resource (which GCS returns as a string).
+        #[derive(Deserialize)]
+        struct ObjectResource {
+            size: String,
+        }
+
+        let resp = client.get(
+            &format!(
+                "{}/b/{}/o/{}",
+                super::STORAGE_BASE_URL,
+                bucket_id,
+                crate::url_encode(path),
+            ))
+            .send()
+            .await?;
+
+        if resp.status() != StatusCode::OK {
+            let status = resp.status().as_u16();
+            return Err(match status {
+                404 => SquadOvError::NotFound,
+                _ => SquadOvError::InternalError(format!("GCS Get Object Size Error: {} - {}", status, resp.text().await?))
+            });
+        }
+
+        let obj: ObjectResource = resp.json().await?;
+        Ok(obj.size.parse::<i64>()?)
+    }
+
     pub async fn download_object(&self, bucket_id: &str, path: &str) -> Result<Vec<u8>, SquadOvError> {
         let client = self.http.read()?.create_http_client()?;
 
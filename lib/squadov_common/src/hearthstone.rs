@@ -2,6 +2,9 @@ pub mod power_parser;
 pub mod game_state;
 pub mod game_packet;
 pub mod db;
+pub mod deck_analytics;
+pub mod battlegrounds;
+pub mod hsreplay;
 
 mod game_type;
 mod format_type;
@@ -0,0 +1,444 @@
+use crate::hearthstone::game_state::{
+    HearthstoneGameLog,
+    HearthstoneGameSnapshot,
+    HearthstoneEntity,
+    BlockType,
+};
+use serde::{Serialize, Deserialize};
+use serde_repr::{Serialize_repr, Deserialize_repr};
+use num_enum::TryFromPrimitive;
+use std::collections::BTreeMap;
+
+pub const HEARTHSTONE_BATTLEGROUNDS_NO_TRIBE: &'static str = "NONE";
+// Highest tavern tier that we track in the tier curve.
+const HEARTHSTONE_BATTLEGROUNDS_MAX_TAVERN_TIER: i32 = 6;
+
+#[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq, TryFromPrimitive)]
+#[repr(i16)]
+pub enum HearthstoneBattlegroundsPhase {
+    Shop,
+    Combat,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all="camelCase")]
+pub struct HearthstoneBattlegroundsMinion {
+    pub entity_id: i32,
+    pub card_id: String,
+    pub race: Option<String>,
+    pub attack: i32,
+    pub health: i32,
+    pub golden: bool,
+}
+
+// Battlegrounds alternates between shopping and combat. Each one is its own turn as far as the game is concerned
+// so turns 1 and 2 are the shopping phase and the combat of round 1.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct HearthstoneBattlegroundsTurn {
+    pub turn: i32,
+    pub round: i32,
+    pub phase: HearthstoneBattlegroundsPhase,
+    pub tavern_tier: i32,
+    pub hero_power_uses: i32,
+    // The player's board at the end of the shopping phase. Combat turns use the board the player went into combat with.
+    pub board: Vec<HearthstoneBattlegroundsMinion>,
+    pub opponent_player_id: Option<i32>,
+    pub opponent_hero_card_id: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct HearthstoneBattlegroundsMatchAnalysis {
+    pub hero_card_id: Option<String>,
+    pub placement: Option<i32>,
+    pub dominant_tribe: String,
+    // The round in which tavern tier 2, 3, ... was reached.
+    pub tier_rounds: Vec<i32>,
+    pub turns: Vec<HearthstoneBattlegroundsTurn>,
+}
+
+fn tag_i32(e: &HearthstoneEntity, tag: &str) -> Option<i32> {
+    e.tags.get(tag).and_then(|x| { x.parse::<i32>().ok() })
+}
+
+fn tag_is(e: &HearthstoneEntity, tag: &str, value: &str) -> bool {
+    e.tags.get(tag).map(|x| { x == value }).unwrap_or(false)
+}
+
+fn player_entity(st: &HearthstoneGameSnapshot, player_id: i32) -> Option<&HearthstoneEntity> {
+    st.player_id_to_entity_id.get(&player_id).and_then(|eid| { st.entities.get(eid) })
+}
+
+// Heroes can get replaced over the course of the game so the latest hero is the one with the largest entity ID.
+fn latest_hero<'a, F>(st: &'a HearthstoneGameSnapshot, f: F) -> Option<&'a HearthstoneEntity>
+where
+    F: Fn(&HearthstoneEntity) -> bool
+{
+    st.entities.values()
+        .filter(|e| { tag_is(e, "CARDTYPE", "HERO") && f(*e) })
+        .max_by_key(|e| { e.entity_id })
+}
+
+fn local_hero(st: &HearthstoneGameSnapshot, local_player_id: i32) -> Option<&HearthstoneEntity> {
+    latest_hero(st, |e| { tag_i32(e, "CONTROLLER") == Some(local_player_id) && tag_is(e, "ZONE", "PLAY") })
+}
+
+fn local_board(st: &HearthstoneGameSnapshot, local_player_id: i32) -> Vec<HearthstoneBattlegroundsMinion> {
+    let mut minions: Vec<&HearthstoneEntity> = st.entities.values()
+        .filter(|e| {
+            tag_i32(e, "CONTROLLER") == Some(local_player_id) && tag_is(e, "ZONE", "PLAY") && tag_is(e, "CARDTYPE", "MINION")
+        })
+        .collect();
+    minions.sort_by_key(|e| { (tag_i32(e, "ZONE_POSITION").unwrap_or(0), e.entity_id) });
+    minions.into_iter().map(|e| {
+        HearthstoneBattlegroundsMinion{
+            entity_id: e.entity_id,
+            card_id: e.card_id().unwrap_or(String::new()),
+            race: e.tags.get("CARDRACE").cloned().filter(|x| { x != "INVALID" }),
+            attack: tag_i32(e, "ATK").unwrap_or(0),
+            health: tag_i32(e, "HEALTH").unwrap_or(0) - tag_i32(e, "DAMAGE").unwrap_or(0),
+            golden: tag_is(e, "PREMIUM", "1"),
+        }
+    }).collect()
+}
+
+// Amalgams (ALL) count towards every tribe so they don't decide which tribe the player was going for.
+fn dominant_tribe(board: &[HearthstoneBattlegroundsMinion]) -> String {
+    let mut counts: BTreeMap<&str, i32> = BTreeMap::new();
+    for m in board {
+        if let Some(race) = m.race.as_deref() {
+            if race != "ALL" {
+                *counts.entry(race).or_default() += 1;
+            }
+        }
+    }
+
+    let mut best: Option<(&str, i32)> = None;
+    for (race, count) in counts {
+        if best.map(|(_, c)| { count > c }).unwrap_or(true) {
+            best = Some((race, count));
+        }
+    }
+    best.map(|(race, _)| { race.to_string() }).unwrap_or(String::from(HEARTHSTONE_BATTLEGROUNDS_NO_TRIBE))
+}
+
+pub fn tier_curve_to_string(tier_rounds: &[i32]) -> String {
+    tier_rounds.iter().map(|x| { x.to_string() }).collect::<Vec<String>>().join(",")
+}
+
+impl HearthstoneBattlegroundsMatchAnalysis {
+    pub fn from_game_log(log: &HearthstoneGameLog, local_player_id: i32) -> Self {
+        let st = &log.current_state;
+
+        // A snapshot is taken at the end of every turn so the last snapshot we have for any given turn is the state at the end of that turn.
+        let mut end_of_turn: BTreeMap<i32, &HearthstoneGameSnapshot> = BTreeMap::new();
+        for s in &log.snapshots {
+            if let Some(aux) = s.aux_data.as_ref() {
+                if aux.current_turn > 0 {
+                    end_of_turn.insert(aux.current_turn, s);
+                }
+            }
+        }
+
+        // Figure out which turn each hero power was used on using the range of actions each snapshot covers.
+        let mut hero_power_uses: BTreeMap<i32, i32> = BTreeMap::new();
+        for b in log.blocks.values() {
+            if !matches!(b.block_type, BlockType::Power) {
+                continue;
+            }
+
+            let is_local_hero_power = st.entities.get(&b.entity_id)
+                .map(|e| { tag_is(e, "CARDTYPE", "HERO_POWER") && tag_i32(e, "CONTROLLER") == Some(local_player_id) })
+                .unwrap_or(false);
+            if !is_local_hero_power {
+                continue;
+            }
+
+            let turn = end_of_turn.iter()
+                .find(|(_, s)| { s.aux_data.as_ref().map(|x| { x.last_action_index as i32 >= b.start_action_index }).unwrap_or(false) })
+                .map(|(turn, _)| { *turn });
+            if let Some(turn) = turn {
+                *hero_power_uses.entry(turn).or_default() += 1;
+            }
+        }
+
+        let mut turns: Vec<HearthstoneBattlegroundsTurn> = vec![];
+        let mut last_shop: Option<HearthstoneBattlegroundsTurn> = None;
+        for (turn, s) in &end_of_turn {
+            let phase = if turn % 2 == 1 { HearthstoneBattlegroundsPhase::Shop } else { HearthstoneBattlegroundsPhase::Combat };
+            let player = player_entity(s, local_player_id);
+            let tavern_tier = player.and_then(|e| { tag_i32(e, "PLAYER_TECH_LEVEL") }).unwrap_or(1);
+
+            let (board, opponent_player_id) = match phase {
+                HearthstoneBattlegroundsPhase::Shop => (
+                    local_board(s, local_player_id),
+                    player.and_then(|e| { tag_i32(e, "NEXT_OPPONENT_PLAYER_ID") }).filter(|x| { *x > 0 }),
+                ),
+                // The combat copies of the player's minions are gone by the end of combat.
+                HearthstoneBattlegroundsPhase::Combat => match last_shop.as_ref() {
+                    Some(x) => (x.board.clone(), x.opponent_player_id),
+                    None => (local_board(s, local_player_id), None),
+                },
+            };
+
+            let opponent_hero_card_id = opponent_player_id
+                .and_then(|pid| { latest_hero(st, |e| { tag_i32(e, "PLAYER_ID") == Some(pid) }) })
+                .and_then(|e| { e.card_id() });
+
+            let t = HearthstoneBattlegroundsTurn{
+                turn: *turn,
+                round: (turn + 1) / 2,
+                phase,
+                tavern_tier,
+                hero_power_uses: hero_power_uses.get(turn).copied().unwrap_or(0),
+                board,
+                opponent_player_id,
+                opponent_hero_card_id,
+            };
+
+            if phase == HearthstoneBattlegroundsPhase::Shop {
+                last_shop = Some(t.clone());
+            }
+            turns.push(t);
+        }
+
+        let mut tier_rounds: Vec<i32> = vec![];
+        for tier in 2..=HEARTHSTONE_BATTLEGROUNDS_MAX_TAVERN_TIER {
+            match turns.iter().find(|t| { t.tavern_tier >= tier }) {
+                Some(t) => tier_rounds.push(t.round),
+                None => break,
+            };
+        }
+
+        let hero = local_hero(st, local_player_id);
+        Self {
+            hero_card_id: hero.and_then(|e| { e.card_id() }),
+            placement: hero.and_then(|e| { tag_i32(e, "PLAYER_LEADERBOARD_PLACE") }).filter(|x| { *x > 0 }),
+            dominant_tribe: dominant_tribe(last_shop.as_ref().map(|x| { x.board.as_slice() }).unwrap_or(&[])),
+            tier_rounds,
+            turns,
+        }
+    }
+}
+
+// What we return for a single match: the stored summary along with the round-by-round board history.
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct HearthstoneBattlegroundsMatchHistory {
+    pub hero_card_id: Option<String>,
+    pub placement: Option<i32>,
+    pub dominant_tribe: String,
+    pub tier_curve: String,
+    pub rating_before: Option<i32>,
+    pub rating_change: Option<i32>,
+    pub turns: Vec<HearthstoneBattlegroundsTurn>,
+}
+
+// One row out of hearthstone_battlegrounds_matches.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct HearthstoneBattlegroundsMatchRow {
+    pub hero_card_id: String,
+    pub placement: i32,
+    pub dominant_tribe: String,
+    pub tier_curve: String,
+    pub rating_change: Option<i32>,
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct HearthstoneBattlegroundsPlacementStats {
+    pub games: i64,
+    pub avg_placement: Option<f64>,
+    // Percentage of games where the player finished in the top 4.
+    pub top4_rate: Option<f64>,
+    pub wins: i64,
+    pub avg_rating_change: Option<f64>,
+    #[serde(skip)]
+    total_placement: i64,
+    #[serde(skip)]
+    top4: i64,
+    #[serde(skip)]
+    rated_games: i64,
+    #[serde(skip)]
+    total_rating_change: i64,
+}
+
+fn ratio(num: i64, den: i64) -> Option<f64> {
+    if den > 0 {
+        Some(num as f64 / den as f64)
+    } else {
+        None
+    }
+}
+
+impl HearthstoneBattlegroundsPlacementStats {
+    fn add(&mut self, row: &HearthstoneBattlegroundsMatchRow) {
+        self.games += 1;
+        self.total_placement += row.placement as i64;
+        if row.placement <= 4 {
+            self.top4 += 1;
+        }
+
+        if row.placement == 1 {
+            self.wins += 1;
+        }
+
+        if let Some(change) = row.rating_change {
+            self.rated_games += 1;
+            self.total_rating_change += change as i64;
+        }
+
+        self.avg_placement = ratio(self.total_placement, self.games);
+        self.top4_rate = ratio(self.top4, self.games);
+        self.avg_rating_change = ratio(self.total_rating_change, self.rated_games);
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all="camelCase")]
+pub struct HearthstoneBattlegroundsAnalytics {
+    pub overall: HearthstoneBattlegroundsPlacementStats,
+    pub by_hero: BTreeMap<String, HearthstoneBattlegroundsPlacementStats>,
+    pub by_tribe: BTreeMap<String, HearthstoneBattlegroundsPlacementStats>,
+    // Keyed by the comma separated list of rounds in which each tavern tier was reached (e.g. "2,4,6").
+    pub by_tier_curve: BTreeMap<String, HearthstoneBattlegroundsPlacementStats>,
+}
+
+impl HearthstoneBattlegroundsAnalytics {
+    pub fn from_rows(rows: &[HearthstoneBattlegroundsMatchRow]) -> Self {
+        let mut ret = Self::default();
+        for row in rows {
+            ret.overall.add(row);
+            ret.by_hero.entry(row.hero_card_id.clone()).or_default().add(row);
+            ret.by_tribe.entry(row.dominant_tribe.clone()).or_default().add(row);
+            ret.by_tier_curve.entry(row.tier_curve.clone()).or_default().add(row);
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hearthstone::game_state::{
+        HearthstoneGameBlock,
+        HearthstoneGameSnapshotAuxData,
+        game_step::GameStep,
+    };
+    use uuid::Uuid;
+
+    fn entity(id: i32, card_id: Option<&str>, tags: &[(&str, &str)]) -> HearthstoneEntity {
+        HearthstoneEntity{
+            entity_id: id,
+            tags: tags.iter().map(|(k, v)| { (k.to_string(), v.to_string()) }).collect(),
+            attributes: card_id.map(|c| { vec![(String::from("CardID"), c.to_string())].into_iter().collect() }).unwrap_or_default(),
+        }
+    }
+
+    fn snapshot(turn: i32, last_action_index: usize, entities: Vec<HearthstoneEntity>) -> HearthstoneGameSnapshot {
+        let mut s = HearthstoneGameSnapshot::new();
+        s.game_entity_id = 1;
+        s.player_id_to_entity_id.insert(5, 20);
+        s.player_id_to_entity_id.insert(13, 21);
+        for e in entities {
+            s.entities.insert(e.entity_id, e);
+        }
+        s.aux_data = Some(HearthstoneGameSnapshotAuxData{
+            current_turn: turn,
+            step: GameStep::MainNext,
+            current_player_id: 5,
+            last_action_index,
+        });
+        s
+    }
+
+    fn minion(id: i32, card_id: &str, race: &str, pos: &str) -> HearthstoneEntity {
+        entity(id, Some(card_id), &[("CONTROLLER", "5"), ("ZONE", "PLAY"), ("CARDTYPE", "MINION"), ("CARDRACE", race), ("ZONE_POSITION", pos), ("ATK", "2"), ("HEALTH", "3")])
+    }
+
+    #[test]
+    fn test_match_analysis() {
+        let hero = entity(30, Some("TB_BaconShop_HERO_56"), &[("CONTROLLER", "5"), ("ZONE", "PLAY"), ("CARDTYPE", "HERO"), ("PLAYER_LEADERBOARD_PLACE", "3")]);
+        let hero_power = entity(31, Some("TB_BaconShop_HP_065"), &[("CONTROLLER", "5"), ("ZONE", "PLAY"), ("CARDTYPE", "HERO_POWER")]);
+        let opponent = entity(40, Some("TB_BaconShop_HERO_18"), &[("PLAYER_ID", "7"), ("CARDTYPE", "HERO")]);
+
+        let mut log = HearthstoneGameLog::new();
+        log.snapshots.push(snapshot(1, 9, vec![
+            entity(20, None, &[("PLAYER_TECH_LEVEL", "1"), ("NEXT_OPPONENT_PLAYER_ID", "7")]),
+            hero.clone(),
+            minion(50, "BGS_004", "DEMON", "1"),
+        ]));
+        log.snapshots.push(snapshot(2, 19, vec![
+            entity(20, None, &[("PLAYER_TECH_LEVEL", "1")]),
+            hero.clone(),
+        ]));
+        log.snapshots.push(snapshot(3, 29, vec![
+            entity(20, None, &[("PLAYER_TECH_LEVEL", "2"), ("NEXT_OPPONENT_PLAYER_ID", "0")]),
+            hero.clone(),
+            minion(50, "BGS_004", "DEMON", "2"),
+            minion(51, "BGS_019", "DRAGON", "1"),
+            minion(52, "BGS_039", "DRAGON", "3"),
+            minion(53, "BGS_069", "ALL", "4"),
+        ]));
+
+        log.current_state = log.snapshots.last().unwrap().clone();
+        log.current_state.entities.insert(31, hero_power);
+        log.current_state.entities.insert(40, opponent);
+
+        let block_id = Uuid::new_v4();
+        log.blocks.insert(block_id.clone(), HearthstoneGameBlock{
+            block_id,
+            start_action_index: 25,
+            end_action_index: 26,
+            block_type: BlockType::Power,
+            parent_block: None,
+            entity_id: 31,
+        });
+
+        let analysis = HearthstoneBattlegroundsMatchAnalysis::from_game_log(&log, 5);
+        assert_eq!(analysis.hero_card_id.as_deref(), Some("TB_BaconShop_HERO_56"));
+        assert_eq!(analysis.placement, Some(3));
+        assert_eq!(analysis.dominant_tribe, "DRAGON");
+        assert_eq!(analysis.tier_rounds, vec![2]);
+        assert_eq!(analysis.turns.len(), 3);
+
+        assert_eq!(analysis.turns[0].phase, HearthstoneBattlegroundsPhase::Shop);
+        assert_eq!(analysis.turns[0].opponent_hero_card_id.as_deref(), Some("TB_BaconShop_HERO_18"));
+        assert_eq!(analysis.turns[1].phase, HearthstoneBattlegroundsPhase::Combat);
+        assert_eq!(analysis.turns[1].round, 1);
+        assert_eq!(analysis.turns[1].board, analysis.turns[0].board);
+        assert_eq!(analysis.turns[1].opponent_player_id, Some(7));
+        assert_eq!(analysis.turns[2].opponent_player_id, None);
+        assert_eq!(analysis.turns[2].hero_power_uses, 1);
+        assert_eq!(analysis.turns[2].board.iter().map(|x| { x.card_id.as_str() }).collect::<Vec<&str>>(), vec!["BGS_019", "BGS_004", "BGS_039", "BGS_069"]);
+    }
+
+    #[test]
+    fn test_analytics_from_rows() {
+        let row = |hero: &str, placement: i32, tribe: &str, curve: &str, rating_change: Option<i32>| {
+            HearthstoneBattlegroundsMatchRow{
+                hero_card_id: hero.to_string(),
+                placement,
+                dominant_tribe: tribe.to_string(),
+                tier_curve: curve.to_string(),
+                rating_change,
+            }
+        };
+
+        let analytics = HearthstoneBattlegroundsAnalytics::from_rows(&[
+            row("HERO_A", 1, "DRAGON", "2,4", Some(60)),
+            row("HERO_A", 6, "MURLOC", "2,4", Some(-20)),
+            row("HERO_B", 4, "DRAGON", "3,5", None),
+        ]);
+
+        assert_eq!(analytics.overall.games, 3);
+        assert_eq!(analytics.overall.wins, 1);
+        assert_eq!(analytics.overall.avg_placement, Some(11.0 / 3.0));
+        assert_eq!(analytics.overall.top4_rate, Some(2.0 / 3.0));
+        assert_eq!(analytics.overall.avg_rating_change, Some(20.0));
+        assert_eq!(analytics.by_hero["HERO_A"].avg_placement, Some(3.5));
+        assert_eq!(analytics.by_tribe["DRAGON"].avg_placement, Some(2.5));
+        assert_eq!(analytics.by_tier_curve["3,5"].games, 1);
+        assert_eq!(analytics.by_tier_curve["3,5"].avg_rating_change, None);
+    }
+}
@@ -1,4 +1,4 @@
-use sqlx::{Executor, Postgres};
+use sqlx::{Executor, Postgres, Transaction};
 use crate::{
     SquadOvError,
     hearthstone::{
@@ -13,10 +13,25 @@ use crate::{
         },
         HearthstonePlayer,
         HearthstonePlayerMedalInfo,
+        deck_analytics::{
+            HearthstoneMatchDeckAnalysis,
+            HearthstoneDeckMatchupRow,
+            HearthstoneDeckCardRow,
+        },
+        battlegrounds::{
+            HearthstoneBattlegroundsMatchAnalysis,
+            HearthstoneBattlegroundsMatchRow,
+            HearthstoneBattlegroundsMatchHistory,
+            HearthstoneBattlegroundsTurn,
+            HearthstoneBattlegroundsMinion,
+            HearthstoneBattlegroundsPhase,
+            tier_curve_to_string,
+        },
         game_state::{
             HearthstoneGameSnapshot,
             HearthstoneGameSnapshotAuxData,
             HearthstoneEntity,
+            HearthstoneGameBlock,
             game_step::GameStep,
         },
     },
@@ -314,6 +329,87 @@ where
     Ok(snapshot)
 }
 
+pub async fn get_hearthstone_game_blocks_for_match<'a, T>(ex: T, match_uuid: &Uuid, user_id: i64) -> Result<Vec<HearthstoneGameBlock>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres> + Copy
+{
+    Ok(sqlx::query_as::<_, HearthstoneGameBlock>(
+        "
+        SELECT
+            block_id,
+            start_action_index,
+            end_action_index,
+            block_type,
+            parent_block,
+            entity_id
+        FROM squadov.hearthstone_blocks
+        WHERE match_uuid = $1 AND user_id = $2
+        ORDER BY start_action_index ASC
+        ",
+    )
+        .bind(match_uuid)
+        .bind(user_id)
+        .fetch_all(ex)
+        .await?)
+}
+
+pub async fn get_hearthstone_match_for_hsreplay_import<'a, T>(ex: T, file_hash: &str) -> Result<Option<Uuid>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(sqlx::query!(
+        "
+        SELECT match_uuid
+        FROM squadov.hearthstone_hsreplay_imports
+        WHERE file_hash = $1
+        ",
+        file_hash,
+    )
+        .fetch_optional(ex)
+        .await?
+        .map(|x| { x.match_uuid }))
+}
+
+pub async fn store_hearthstone_hsreplay_import(ex: &mut Transaction<'_, Postgres>, file_hash: &str, match_uuid: &Uuid) -> Result<(), SquadOvError> {
+    sqlx::query!(
+        "
+        INSERT INTO squadov.hearthstone_hsreplay_imports (
+            file_hash,
+            match_uuid
+        )
+        VALUES (
+            $1,
+            $2
+        )
+        ",
+        file_hash,
+        match_uuid,
+    )
+        .execute(&mut *ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn is_hearthstone_match_viewed_by_user<'a, T>(ex: T, match_uuid: &Uuid, user_id: i64) -> Result<bool, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(sqlx::query!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM squadov.hearthstone_match_view
+            WHERE match_uuid = $1 AND user_id = $2
+        ) AS "exists!"
+        "#,
+        match_uuid,
+        user_id,
+    )
+        .fetch_one(ex)
+        .await?
+        .exists)
+}
+
 pub async fn get_hearthstone_game_packet<'a, T>(ex: T, match_uuid: &Uuid, user_id: i64) -> Result<HearthstoneGamePacket, SquadOvError>
 where
     T: Executor<'a, Database = Postgres> + Copy
@@ -375,4 +471,511 @@ where
         metadata: metadata,
         latest_snapshot: latest_snapshot
     })
-}
\ No newline at end of file
+}
+pub struct HearthstoneMatchDeckInfo {
+    pub deck_id: i64,
+    pub deck_version_id: i64,
+    pub local_player_id: i32,
+}
+
+pub async fn get_hearthstone_match_deck_info<'a, T>(ex: T, match_uuid: &Uuid, user_id: i64) -> Result<Option<HearthstoneMatchDeckInfo>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as!(
+            HearthstoneMatchDeckInfo,
+            "
+            SELECT
+                hdv.deck_id,
+                hmud.deck_version_id,
+                hmp.player_match_id AS \"local_player_id\"
+            FROM squadov.hearthstone_match_user_deck AS hmud
+            INNER JOIN squadov.hearthstone_deck_versions AS hdv
+                ON hdv.version_id = hmud.deck_version_id
+            INNER JOIN squadov.hearthstone_match_view AS hmv
+                ON hmv.match_uuid = hmud.match_uuid
+                AND hmv.user_id = hmud.user_id
+            INNER JOIN squadov.hearthstone_match_players AS hmp
+                ON hmp.view_uuid = hmv.view_uuid
+                AND hmp.user_id = hmv.user_id
+            WHERE hmud.match_uuid = $1 AND hmud.user_id = $2
+            ",
+            match_uuid,
+            user_id,
+        )
+            .fetch_optional(ex)
+            .await?
+    )
+}
+
+// Replaces whatever the match contributed to the pre-aggregated deck stats before so it's safe to call this again if
+// the logs for the match get re-parsed. The aggregates themselves are kept up to date by triggers on these tables so
+// that deleting a match (or anything it cascades from) takes it back out of the stats as well.
+pub async fn store_hearthstone_deck_analytics_for_match(ex: &mut Transaction<'_, Postgres>, match_uuid: &Uuid, user_id: i64, deck_version_id: i64, analysis: &HearthstoneMatchDeckAnalysis) -> Result<(), SquadOvError> {
+    sqlx::query!(
+        "
+        DELETE FROM squadov.hearthstone_deck_analytics_matches
+        WHERE match_uuid = $1
+            AND user_id = $2
+        ",
+        match_uuid,
+        user_id,
+    )
+        .execute(&mut *ex)
+        .await?;
+
+    sqlx::query!(
+        "
+        INSERT INTO squadov.hearthstone_deck_analytics_matches (
+            match_uuid,
+            user_id,
+            deck_version_id,
+            opponent_class,
+            play_order,
+            won,
+            duration_seconds,
+            num_turns
+        )
+        VALUES (
+            $1,
+            $2,
+            $3,
+            $4,
+            $5,
+            $6,
+            $7,
+            $8
+        )
+        ",
+        match_uuid,
+        user_id,
+        deck_version_id,
+        &analysis.opponent_class,
+        analysis.play_order as i16,
+        analysis.won,
+        analysis.duration_seconds,
+        analysis.num_turns as i64,
+    )
+        .execute(&mut *ex)
+        .await?;
+
+    if analysis.cards.is_empty() {
+        return Ok(());
+    }
+
+    let mut card_ids: Vec<String> = vec![];
+    let mut mulligan_offered: Vec<i32> = vec![];
+    let mut mulligan_kept: Vec<i32> = vec![];
+    let mut drawn: Vec<bool> = vec![];
+    let mut played: Vec<bool> = vec![];
+    for (card_id, stats) in &analysis.cards {
+        card_ids.push(card_id.clone());
+        mulligan_offered.push(stats.mulligan_offered);
+        mulligan_kept.push(stats.mulligan_kept);
+        drawn.push(stats.drawn);
+        played.push(stats.played);
+    }
+
+    sqlx::query!(
+        "
+        INSERT INTO squadov.hearthstone_deck_analytics_match_cards (
+            match_uuid,
+            user_id,
+            card_id,
+            deck_version_id,
+            won,
+            mulligan_offered,
+            mulligan_kept,
+            drawn,
+            played
+        )
+        SELECT $1, $2, inp.card_id, $3, $4, inp.mulligan_offered, inp.mulligan_kept, inp.drawn, inp.played
+        FROM UNNEST($5::VARCHAR[], $6::INTEGER[], $7::INTEGER[], $8::BOOLEAN[], $9::BOOLEAN[])
+            AS inp(card_id, mulligan_offered, mulligan_kept, drawn, played)
+        ",
+        match_uuid,
+        user_id,
+        deck_version_id,
+        analysis.won,
+        &card_ids,
+        &mulligan_offered,
+        &mulligan_kept,
+        &drawn,
+        &played,
+    )
+        .execute(&mut *ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_hearthstone_deck_matchup_rows<'a, T>(ex: T, deck_id: i64, user_id: i64, version_id: Option<i64>) -> Result<Vec<HearthstoneDeckMatchupRow>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as!(
+            HearthstoneDeckMatchupRow,
+            "
+            SELECT
+                hdms.deck_version_id,
+                hdms.opponent_class,
+                hdms.play_order,
+                hdms.games,
+                hdms.wins,
+                hdms.total_duration_seconds,
+                hdms.total_turns
+            FROM squadov.hearthstone_deck_matchup_stats AS hdms
+            INNER JOIN squadov.hearthstone_deck_versions AS hdv
+                ON hdv.version_id = hdms.deck_version_id
+            INNER JOIN squadov.hearthstone_decks AS hd
+                ON hd.deck_id = hdv.deck_id
+            WHERE hd.deck_id = $1
+                AND hd.user_id = $2
+                AND ($3::BIGINT IS NULL OR hdv.version_id = $3)
+            ",
+            deck_id,
+            user_id,
+            version_id,
+        )
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub async fn get_hearthstone_deck_card_rows<'a, T>(ex: T, deck_id: i64, user_id: i64, version_id: Option<i64>) -> Result<Vec<HearthstoneDeckCardRow>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as!(
+            HearthstoneDeckCardRow,
+            "
+            SELECT
+                hdcs.deck_version_id,
+                hdcs.card_id,
+                hdcs.mulligan_offered,
+                hdcs.mulligan_kept,
+                hdcs.drawn_games,
+                hdcs.drawn_wins,
+                hdcs.played_games,
+                hdcs.played_wins
+            FROM squadov.hearthstone_deck_card_stats AS hdcs
+            INNER JOIN squadov.hearthstone_deck_versions AS hdv
+                ON hdv.version_id = hdcs.deck_version_id
+            INNER JOIN squadov.hearthstone_decks AS hd
+                ON hd.deck_id = hdv.deck_id
+            WHERE hd.deck_id = $1
+                AND hd.user_id = $2
+                AND ($3::BIGINT IS NULL OR hdv.version_id = $3)
+            ",
+            deck_id,
+            user_id,
+            version_id,
+        )
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub struct HearthstoneDeckAnalyticsBackfillMatch {
+    pub match_uuid: Uuid,
+    pub user_id: i64,
+}
+
+// Marks the returned matches as queued so they don't get picked up again while their backfill is still pending. Matches that
+// have been queued for a day without finishing are assumed to have been lost and are picked up again.
+pub async fn get_hearthstone_matches_for_deck_analytics_backfill<'a, T>(ex: T, limit: i64) -> Result<Vec<HearthstoneDeckAnalyticsBackfillMatch>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as!(
+            HearthstoneDeckAnalyticsBackfillMatch,
+            "
+            UPDATE squadov.hearthstone_deck_analytics_backfill AS hdab
+            SET queued_tm = NOW()
+            FROM (
+                SELECT match_uuid, user_id
+                FROM squadov.hearthstone_deck_analytics_backfill
+                WHERE queued_tm IS NULL OR queued_tm < (NOW() - INTERVAL '1 day')
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            ) AS sub
+            WHERE sub.match_uuid = hdab.match_uuid
+                AND sub.user_id = hdab.user_id
+            RETURNING hdab.match_uuid, hdab.user_id
+            ",
+            limit,
+        )
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub async fn finish_hearthstone_deck_analytics_backfill<'a, T>(ex: T, match_uuid: &Uuid, user_id: i64) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query!(
+        "
+        DELETE FROM squadov.hearthstone_deck_analytics_backfill
+        WHERE match_uuid = $1
+            AND user_id = $2
+        ",
+        match_uuid,
+        user_id,
+    )
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+pub struct HearthstoneMatchLocalPlayer {
+    pub player_id: i32,
+    pub battlegrounds_rating: Option<i32>,
+}
+
+pub async fn get_hearthstone_match_local_player<'a, T>(ex: T, match_uuid: &Uuid, user_id: i64) -> Result<Option<HearthstoneMatchLocalPlayer>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, (i32, Option<i32>)>(
+            "
+            SELECT hmp.player_match_id, hmp.battlegrounds_rating
+            FROM squadov.hearthstone_match_players AS hmp
+            INNER JOIN squadov.hearthstone_match_view AS hmv
+                ON hmv.view_uuid = hmp.view_uuid
+                AND hmv.user_id = hmp.user_id
+            WHERE hmv.match_uuid = $1 AND hmv.user_id = $2
+            "
+        )
+            .bind(match_uuid)
+            .bind(user_id)
+            .fetch_optional(ex)
+            .await?
+            .map(|(player_id, battlegrounds_rating)| {
+                HearthstoneMatchLocalPlayer{
+                    player_id,
+                    battlegrounds_rating,
+                }
+            })
+    )
+}
+
+// Replaces whatever we had stored for the match previously so that re-parsing the logs is safe. The rating change
+// of a match is the difference between the rating going into the match and the rating going into the next match so
+// we need to fill in the previous match's rating change as well.
+pub async fn store_hearthstone_battlegrounds_match(ex: &mut Transaction<'_, Postgres>, match_uuid: &Uuid, user_id: i64, rating_before: Option<i32>, analysis: &HearthstoneBattlegroundsMatchAnalysis) -> Result<(), SquadOvError> {
+    sqlx::query(
+        "
+        INSERT INTO squadov.hearthstone_battlegrounds_matches (
+            match_uuid,
+            user_id,
+            match_time,
+            hero_card_id,
+            placement,
+            dominant_tribe,
+            tier_curve,
+            rating_before
+        )
+        SELECT $1, $2, hm.match_time, $3, $4, $5, $6, $7
+        FROM squadov.hearthstone_matches AS hm
+        WHERE hm.match_uuid = $1
+        ON CONFLICT (match_uuid, user_id) DO UPDATE SET
+            hero_card_id = EXCLUDED.hero_card_id,
+            placement = EXCLUDED.placement,
+            dominant_tribe = EXCLUDED.dominant_tribe,
+            tier_curve = EXCLUDED.tier_curve,
+            rating_before = EXCLUDED.rating_before
+        "
+    )
+        .bind(match_uuid)
+        .bind(user_id)
+        .bind(&analysis.hero_card_id)
+        .bind(analysis.placement)
+        .bind(&analysis.dominant_tribe)
+        .bind(tier_curve_to_string(&analysis.tier_rounds))
+        .bind(rating_before)
+        .execute(&mut *ex)
+        .await?;
+
+    sqlx::query(
+        "
+        UPDATE squadov.hearthstone_battlegrounds_matches AS hbm
+        SET rating_change = (
+            SELECT nxt.rating_before - hbm.rating_before
+            FROM squadov.hearthstone_battlegrounds_matches AS nxt
+            WHERE nxt.user_id = hbm.user_id
+                AND nxt.match_time > hbm.match_time
+                AND nxt.rating_before IS NOT NULL
+            ORDER BY nxt.match_time ASC
+            LIMIT 1
+        )
+        WHERE hbm.user_id = $2
+            AND hbm.match_uuid IN (
+                SELECT match_uuid
+                FROM squadov.hearthstone_battlegrounds_matches
+                WHERE user_id = $2
+                    AND match_time <= (
+                        SELECT match_time
+                        FROM squadov.hearthstone_battlegrounds_matches
+                        WHERE match_uuid = $1 AND user_id = $2
+                    )
+                ORDER BY match_time DESC
+                LIMIT 2
+            )
+        "
+    )
+        .bind(match_uuid)
+        .bind(user_id)
+        .execute(&mut *ex)
+        .await?;
+
+    sqlx::query(
+        "
+        DELETE FROM squadov.hearthstone_battlegrounds_turns
+        WHERE match_uuid = $1 AND user_id = $2
+        "
+    )
+        .bind(match_uuid)
+        .bind(user_id)
+        .execute(&mut *ex)
+        .await?;
+
+    if analysis.turns.is_empty() {
+        return Ok(());
+    }
+
+    let mut turns: Vec<i32> = vec![];
+    let mut rounds: Vec<i32> = vec![];
+    let mut phases: Vec<i16> = vec![];
+    let mut tavern_tiers: Vec<i32> = vec![];
+    let mut hero_power_uses: Vec<i32> = vec![];
+    let mut opponent_player_ids: Vec<Option<i32>> = vec![];
+    let mut opponent_hero_card_ids: Vec<Option<String>> = vec![];
+    let mut boards: Vec<String> = vec![];
+    for t in &analysis.turns {
+        turns.push(t.turn);
+        rounds.push(t.round);
+        phases.push(t.phase as i16);
+        tavern_tiers.push(t.tavern_tier);
+        hero_power_uses.push(t.hero_power_uses);
+        opponent_player_ids.push(t.opponent_player_id);
+        opponent_hero_card_ids.push(t.opponent_hero_card_id.clone());
+        boards.push(serde_json::to_string(&t.board)?);
+    }
+
+    sqlx::query(
+        "
+        INSERT INTO squadov.hearthstone_battlegrounds_turns (
+            match_uuid,
+            user_id,
+            turn,
+            round,
+            phase,
+            tavern_tier,
+            hero_power_uses,
+            opponent_player_id,
+            opponent_hero_card_id,
+            board
+        )
+        SELECT $1, $2, inp.turn, inp.round, inp.phase, inp.tavern_tier, inp.hero_power_uses, inp.opponent_player_id, inp.opponent_hero_card_id, inp.board::JSONB
+        FROM UNNEST($3::INTEGER[], $4::INTEGER[], $5::SMALLINT[], $6::INTEGER[], $7::INTEGER[], $8::INTEGER[], $9::VARCHAR[], $10::VARCHAR[])
+            AS inp(turn, round, phase, tavern_tier, hero_power_uses, opponent_player_id, opponent_hero_card_id, board)
+        "
+    )
+        .bind(match_uuid)
+        .bind(user_id)
+        .bind(&turns)
+        .bind(&rounds)
+        .bind(&phases)
+        .bind(&tavern_tiers)
+        .bind(&hero_power_uses)
+        .bind(&opponent_player_ids)
+        .bind(&opponent_hero_card_ids)
+        .bind(&boards)
+        .execute(&mut *ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_hearthstone_battlegrounds_match_history<'a, T>(ex: T, match_uuid: &Uuid, user_id: i64) -> Result<Option<HearthstoneBattlegroundsMatchHistory>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres> + Copy
+{
+    let summary = sqlx::query_as::<_, (Option<String>, Option<i32>, String, String, Option<i32>, Option<i32>)>(
+        "
+        SELECT hero_card_id, placement, dominant_tribe, tier_curve, rating_before, rating_change
+        FROM squadov.hearthstone_battlegrounds_matches
+        WHERE match_uuid = $1 AND user_id = $2
+        "
+    )
+        .bind(match_uuid)
+        .bind(user_id)
+        .fetch_optional(ex)
+        .await?;
+
+    let (hero_card_id, placement, dominant_tribe, tier_curve, rating_before, rating_change) = match summary {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+
+    let turns = sqlx::query_as::<_, (i32, i32, i16, i32, i32, Option<i32>, Option<String>, sqlx::types::Json<Vec<HearthstoneBattlegroundsMinion>>)>(
+        "
+        SELECT turn, round, phase, tavern_tier, hero_power_uses, opponent_player_id, opponent_hero_card_id, board
+        FROM squadov.hearthstone_battlegrounds_turns
+        WHERE match_uuid = $1 AND user_id = $2
+        ORDER BY turn ASC
+        "
+    )
+        .bind(match_uuid)
+        .bind(user_id)
+        .fetch_all(ex)
+        .await?
+        .into_iter()
+        .map(|(turn, round, phase, tavern_tier, hero_power_uses, opponent_player_id, opponent_hero_card_id, board)| {
+            Ok(HearthstoneBattlegroundsTurn{
+                turn,
+                round,
+                phase: HearthstoneBattlegroundsPhase::try_from(phase)?,
+                tavern_tier,
+                hero_power_uses,
+                board: board.0,
+                opponent_player_id,
+                opponent_hero_card_id,
+            })
+        })
+        .collect::<Result<Vec<HearthstoneBattlegroundsTurn>, SquadOvError>>()?;
+
+    Ok(Some(HearthstoneBattlegroundsMatchHistory{
+        hero_card_id,
+        placement,
+        dominant_tribe,
+        tier_curve,
+        rating_before,
+        rating_change,
+        turns,
+    }))
+}
+
+pub async fn get_hearthstone_battlegrounds_match_rows<'a, T>(ex: T, user_id: i64) -> Result<Vec<HearthstoneBattlegroundsMatchRow>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, HearthstoneBattlegroundsMatchRow>(
+            "
+            SELECT hero_card_id, placement, dominant_tribe, tier_curve, rating_change
+            FROM squadov.hearthstone_battlegrounds_matches
+            WHERE user_id = $1
+                AND hero_card_id IS NOT NULL
+                AND placement IS NOT NULL
+            "
+        )
+            .bind(user_id)
+            .fetch_all(ex)
+            .await?
+    )
+}
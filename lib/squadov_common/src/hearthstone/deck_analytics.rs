@@ -0,0 +1,479 @@
+use crate::hearthstone::game_state::{
+    HearthstoneGameLog,
+    HearthstoneGameSnapshot,
+    HearthstoneEntity,
+    BlockType,
+    game_step::GameStep,
+};
+use serde::Serialize;
+use serde_repr::Serialize_repr;
+use num_enum::TryFromPrimitive;
+use std::collections::{HashMap, HashSet, BTreeMap};
+use std::convert::TryFrom;
+
+const HEARTHSTONE_COIN_CARD_ID: &'static str = "GAME_005";
+pub const HEARTHSTONE_UNKNOWN_CLASS: &'static str = "UNKNOWN";
+
+#[derive(Serialize_repr, Debug, Clone, Copy, PartialEq, TryFromPrimitive)]
+#[repr(i16)]
+pub enum HearthstonePlayOrder {
+    Unknown,
+    First,
+    Second,
+}
+
+// How one card from the deck fared in a single match.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct HearthstoneCardMatchStats {
+    // Copies of the card that were offered in the mulligan and how many of those copies were kept.
+    pub mulligan_offered: i32,
+    pub mulligan_kept: i32,
+    pub drawn: bool,
+    pub played: bool,
+}
+
+// Everything we need out of a single match to update the pre-aggregated deck analytics.
+#[derive(Debug)]
+pub struct HearthstoneMatchDeckAnalysis {
+    pub won: bool,
+    pub opponent_class: String,
+    pub play_order: HearthstonePlayOrder,
+    pub duration_seconds: i64,
+    pub num_turns: i32,
+    pub cards: HashMap<String, HearthstoneCardMatchStats>,
+}
+
+fn entity_controller(e: &HearthstoneEntity) -> Option<i32> {
+    e.tags.get("CONTROLLER").and_then(|x| { x.parse::<i32>().ok() })
+}
+
+fn entity_zone(e: &HearthstoneEntity) -> Option<&str> {
+    e.tags.get("ZONE").map(|x| { x.as_str() })
+}
+
+fn snapshot_step(s: &HearthstoneGameSnapshot) -> GameStep {
+    s.aux_data.as_ref().map(|x| { x.step }).unwrap_or(GameStep::Invalid)
+}
+
+// Deck cards (by entity ID) controlled by the given player that are in the player's hand in the given snapshot.
+fn deck_cards_in_hand(s: &HearthstoneGameSnapshot, player_id: i32, deck_card_ids: &HashSet<String>) -> HashMap<i32, String> {
+    s.entities.values()
+        .filter(|e| { entity_controller(e) == Some(player_id) && entity_zone(e) == Some("HAND") })
+        .filter_map(|e| { e.card_id().filter(|c| { deck_card_ids.contains(c) }).map(|c| { (e.entity_id, c) }) })
+        .collect()
+}
+
+fn find_opponent_class(st: &HearthstoneGameSnapshot, local_player_id: i32) -> String {
+    // The opponent's hero is the first hero entity they control - this matches how we find the hero for a player's VOD.
+    // Heroes that get replaced mid-game (e.g. by a hero card) still belong to the same class.
+    let mut heroes: Vec<&HearthstoneEntity> = st.entities.values()
+        .filter(|e| {
+            let controller = entity_controller(e);
+            controller.is_some() && controller != Some(local_player_id) && st.player_id_to_entity_id.contains_key(&controller.unwrap())
+                && e.tags.get("CARDTYPE").map(|x| { x == "HERO" }).unwrap_or(false)
+                && (entity_zone(e) == Some("PLAY") || entity_zone(e) == Some("GRAVEYARD"))
+        })
+        .collect();
+    heroes.sort_by_key(|e| { e.entity_id });
+    heroes.first()
+        .and_then(|e| { e.tags.get("CLASS").cloned() })
+        .unwrap_or(String::from(HEARTHSTONE_UNKNOWN_CLASS))
+}
+
+fn find_play_order(st: &HearthstoneGameSnapshot, local_player_id: i32) -> HearthstonePlayOrder {
+    for (pid, eid) in &st.player_id_to_entity_id {
+        let is_first = st.entities.get(eid)
+            .and_then(|e| { e.tags.get("FIRST_PLAYER") })
+            .map(|x| { x == "1" })
+            .unwrap_or(false);
+
+        if is_first {
+            return if *pid == local_player_id {
+                HearthstonePlayOrder::First
+            } else {
+                HearthstonePlayOrder::Second
+            };
+        }
+    }
+
+    // Older logs might not have FIRST_PLAYER set so fall back to who got the coin.
+    for e in st.entities.values() {
+        if e.card_id().as_deref() == Some(HEARTHSTONE_COIN_CARD_ID) {
+            return if entity_controller(e) == Some(local_player_id) {
+                HearthstonePlayOrder::Second
+            } else {
+                HearthstonePlayOrder::First
+            };
+        }
+    }
+    HearthstonePlayOrder::Unknown
+}
+
+impl HearthstoneMatchDeckAnalysis {
+    pub fn from_game_log(log: &HearthstoneGameLog, local_player_id: i32, winner_player_id: Option<i32>, deck_card_ids: &HashSet<String>) -> Self {
+        let st = &log.current_state;
+        let mut cards: HashMap<String, HearthstoneCardMatchStats> = HashMap::new();
+
+        // The mulligan snapshot is taken when the game first enters the mulligan step so it contains the offered hand.
+        // The snapshot right after that is the start of the first turn so whatever is still in hand from the offered
+        // hand was kept (replaced cards get shuffled back into the deck).
+        let mulligan_idx = log.snapshots.iter().position(|s| { snapshot_step(s) == GameStep::BeginMulligan });
+        if let Some(idx) = mulligan_idx {
+            let offered = deck_cards_in_hand(&log.snapshots[idx], local_player_id, deck_card_ids);
+            let after = log.snapshots.iter().skip(idx + 1).find(|s| { snapshot_step(s) != GameStep::BeginMulligan }).unwrap_or(st);
+            let kept = deck_cards_in_hand(after, local_player_id, deck_card_ids);
+            for (eid, card_id) in offered {
+                let stats = cards.entry(card_id).or_default();
+                stats.mulligan_offered += 1;
+                if kept.contains_key(&eid) {
+                    stats.mulligan_kept += 1;
+                }
+            }
+        }
+
+        // Look up the final card ID/controller for entities since cards in the deck are hidden until they're drawn.
+        let local_deck_card = |eid: i32| -> Option<String> {
+            let e = st.entities.get(&eid)?;
+            if entity_controller(e) != Some(local_player_id) {
+                return None;
+            }
+            e.card_id().filter(|c| { deck_card_ids.contains(c) })
+        };
+
+        for a in &log.actions {
+            let eid = match a.real_entity_id {
+                Some(x) => x,
+                None => continue,
+            };
+
+            if a.tags.get("ZONE").map(|x| { x == "HAND" }).unwrap_or(false) {
+                if let Some(card_id) = local_deck_card(eid) {
+                    cards.entry(card_id).or_default().drawn = true;
+                }
+            }
+        }
+
+        for b in log.blocks.values() {
+            if !matches!(b.block_type, BlockType::Play) {
+                continue;
+            }
+
+            if let Some(card_id) = local_deck_card(b.entity_id) {
+                let stats = cards.entry(card_id).or_default();
+                stats.played = true;
+                // Anything that was played obviously had to be drawn as well.
+                stats.drawn = true;
+            }
+        }
+
+        let duration_seconds = match (log.actions.first(), log.actions.last()) {
+            (Some(first), Some(last)) => (last.tm - first.tm).num_seconds().max(0),
+            _ => 0,
+        };
+
+        let num_turns = st.entities.get(&st.game_entity_id)
+            .and_then(|e| { e.tags.get("TURN") })
+            .and_then(|x| { x.parse::<i32>().ok() })
+            .unwrap_or(0);
+
+        Self {
+            won: winner_player_id == Some(local_player_id),
+            opponent_class: find_opponent_class(st, local_player_id),
+            play_order: find_play_order(st, local_player_id),
+            duration_seconds,
+            num_turns,
+            cards,
+        }
+    }
+}
+
+// One row out of hearthstone_deck_matchup_stats.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct HearthstoneDeckMatchupRow {
+    pub deck_version_id: i64,
+    pub opponent_class: String,
+    pub play_order: i16,
+    pub games: i32,
+    pub wins: i32,
+    pub total_duration_seconds: i64,
+    pub total_turns: i64,
+}
+
+// One row out of hearthstone_deck_card_stats.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct HearthstoneDeckCardRow {
+    pub deck_version_id: i64,
+    pub card_id: String,
+    pub mulligan_offered: i32,
+    pub mulligan_kept: i32,
+    pub drawn_games: i32,
+    pub drawn_wins: i32,
+    pub played_games: i32,
+    pub played_wins: i32,
+}
+
+fn ratio(num: i64, den: i64) -> Option<f64> {
+    if den > 0 {
+        Some(num as f64 / den as f64)
+    } else {
+        None
+    }
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct HearthstoneWinRateStats {
+    pub games: i64,
+    pub wins: i64,
+    pub win_rate: Option<f64>,
+    pub avg_duration_seconds: Option<f64>,
+    pub avg_turns: Option<f64>,
+    #[serde(skip)]
+    total_duration_seconds: i64,
+    #[serde(skip)]
+    total_turns: i64,
+}
+
+impl HearthstoneWinRateStats {
+    fn add(&mut self, row: &HearthstoneDeckMatchupRow) {
+        self.games += row.games as i64;
+        self.wins += row.wins as i64;
+        self.total_duration_seconds += row.total_duration_seconds;
+        self.total_turns += row.total_turns;
+        self.win_rate = ratio(self.wins, self.games);
+        self.avg_duration_seconds = ratio(self.total_duration_seconds, self.games);
+        self.avg_turns = ratio(self.total_turns, self.games);
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all="camelCase")]
+pub struct HearthstoneMatchupBreakdown {
+    pub overall: HearthstoneWinRateStats,
+    pub by_opponent_class: BTreeMap<String, HearthstoneWinRateStats>,
+    pub going_first: HearthstoneWinRateStats,
+    pub going_second: HearthstoneWinRateStats,
+}
+
+impl HearthstoneMatchupBreakdown {
+    fn add(&mut self, row: &HearthstoneDeckMatchupRow) {
+        self.overall.add(row);
+        self.by_opponent_class.entry(row.opponent_class.clone()).or_default().add(row);
+        match HearthstonePlayOrder::try_from(row.play_order).unwrap_or(HearthstonePlayOrder::Unknown) {
+            HearthstonePlayOrder::First => self.going_first.add(row),
+            HearthstonePlayOrder::Second => self.going_second.add(row),
+            HearthstonePlayOrder::Unknown => (),
+        };
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct HearthstoneDeckVersionAnalytics {
+    pub version_id: i64,
+    pub stats: HearthstoneMatchupBreakdown,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all="camelCase")]
+pub struct HearthstoneDeckCardAnalytics {
+    pub card_id: String,
+    pub mulligan_offered: i64,
+    pub mulligan_kept: i64,
+    pub mulligan_keep_rate: Option<f64>,
+    pub drawn_games: i64,
+    pub drawn_win_rate: Option<f64>,
+    pub played_games: i64,
+    pub played_win_rate: Option<f64>,
+    #[serde(skip)]
+    drawn_wins: i64,
+    #[serde(skip)]
+    played_wins: i64,
+}
+
+impl HearthstoneDeckCardAnalytics {
+    fn add(&mut self, row: &HearthstoneDeckCardRow) {
+        self.mulligan_offered += row.mulligan_offered as i64;
+        self.mulligan_kept += row.mulligan_kept as i64;
+        self.drawn_games += row.drawn_games as i64;
+        self.drawn_wins += row.drawn_wins as i64;
+        self.played_games += row.played_games as i64;
+        self.played_wins += row.played_wins as i64;
+        self.mulligan_keep_rate = ratio(self.mulligan_kept, self.mulligan_offered);
+        self.drawn_win_rate = ratio(self.drawn_wins, self.drawn_games);
+        self.played_win_rate = ratio(self.played_wins, self.played_games);
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct HearthstoneDeckAnalytics {
+    pub deck_id: i64,
+    // Stats across every version of the deck.
+    pub stats: HearthstoneMatchupBreakdown,
+    pub versions: Vec<HearthstoneDeckVersionAnalytics>,
+    pub cards: Vec<HearthstoneDeckCardAnalytics>,
+}
+
+impl HearthstoneDeckAnalytics {
+    pub fn from_rows(deck_id: i64, matchups: &[HearthstoneDeckMatchupRow], cards: &[HearthstoneDeckCardRow]) -> Self {
+        let mut stats = HearthstoneMatchupBreakdown::default();
+        let mut versions: BTreeMap<i64, HearthstoneMatchupBreakdown> = BTreeMap::new();
+        for row in matchups {
+            stats.add(row);
+            versions.entry(row.deck_version_id).or_default().add(row);
+        }
+
+        let mut card_stats: BTreeMap<String, HearthstoneDeckCardAnalytics> = BTreeMap::new();
+        for row in cards {
+            card_stats.entry(row.card_id.clone()).or_insert_with(|| {
+                HearthstoneDeckCardAnalytics{
+                    card_id: row.card_id.clone(),
+                    ..HearthstoneDeckCardAnalytics::default()
+                }
+            }).add(row);
+        }
+
+        Self {
+            deck_id,
+            stats,
+            versions: versions.into_iter().map(|(version_id, stats)| {
+                HearthstoneDeckVersionAnalytics{
+                    version_id,
+                    stats,
+                }
+            }).collect(),
+            cards: card_stats.into_iter().map(|(_, v)| { v }).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hearthstone::game_state::{
+        HearthstoneGameAction,
+        HearthstoneGameBlock,
+        HearthstoneGameSnapshotAuxData,
+    };
+    use chrono::{Utc, Duration};
+    use uuid::Uuid;
+
+    fn entity(id: i32, card_id: Option<&str>, tags: &[(&str, &str)]) -> HearthstoneEntity {
+        HearthstoneEntity{
+            entity_id: id,
+            tags: tags.iter().map(|(k, v)| { (k.to_string(), v.to_string()) }).collect(),
+            attributes: card_id.map(|c| { vec![(String::from("CardID"), c.to_string())].into_iter().collect() }).unwrap_or_default(),
+        }
+    }
+
+    fn snapshot(step: GameStep, entities: Vec<HearthstoneEntity>) -> HearthstoneGameSnapshot {
+        let mut s = HearthstoneGameSnapshot::new();
+        s.game_entity_id = 1;
+        s.player_id_to_entity_id.insert(1, 2);
+        s.player_id_to_entity_id.insert(2, 3);
+        for e in entities {
+            s.entities.insert(e.entity_id, e);
+        }
+        s.aux_data = Some(HearthstoneGameSnapshotAuxData{
+            current_turn: 1,
+            step,
+            current_player_id: 1,
+            last_action_index: 0,
+        });
+        s
+    }
+
+    #[test]
+    fn test_match_analysis() {
+        let deck: HashSet<String> = vec!["CARD_A", "CARD_B", "CARD_C"].into_iter().map(String::from).collect();
+        let mut log = HearthstoneGameLog::new();
+
+        // Offered A (10) and B (11), replaced B for C (12).
+        log.snapshots.push(snapshot(GameStep::BeginMulligan, vec![
+            entity(10, Some("CARD_A"), &[("CONTROLLER", "1"), ("ZONE", "HAND")]),
+            entity(11, Some("CARD_B"), &[("CONTROLLER", "1"), ("ZONE", "HAND")]),
+        ]));
+        log.snapshots.push(snapshot(GameStep::MainReady, vec![
+            entity(10, Some("CARD_A"), &[("CONTROLLER", "1"), ("ZONE", "HAND")]),
+            entity(11, Some("CARD_B"), &[("CONTROLLER", "1"), ("ZONE", "DECK")]),
+            entity(12, Some("CARD_C"), &[("CONTROLLER", "1"), ("ZONE", "HAND")]),
+        ]));
+
+        log.current_state = snapshot(GameStep::FinalGameover, vec![
+            entity(1, None, &[("TURN", "9")]),
+            entity(2, None, &[("FIRST_PLAYER", "1")]),
+            entity(3, None, &[]),
+            entity(10, Some("CARD_A"), &[("CONTROLLER", "1"), ("ZONE", "GRAVEYARD")]),
+            entity(11, Some("CARD_B"), &[("CONTROLLER", "1"), ("ZONE", "DECK")]),
+            entity(12, Some("CARD_C"), &[("CONTROLLER", "1"), ("ZONE", "HAND")]),
+            entity(20, Some("HERO_08"), &[("CONTROLLER", "2"), ("ZONE", "PLAY"), ("CARDTYPE", "HERO"), ("CLASS", "MAGE")]),
+        ]);
+
+        let start = Utc::now();
+        for (i, eid) in vec![10, 12].into_iter().enumerate() {
+            let mut a = HearthstoneGameAction::default();
+            a.tm = start + Duration::seconds(i as i64 * 300);
+            a.real_entity_id = Some(eid);
+            a.tags.insert(String::from("ZONE"), String::from("HAND"));
+            log.actions.push(a);
+        }
+
+        let block_id = Uuid::new_v4();
+        log.blocks.insert(block_id.clone(), HearthstoneGameBlock{
+            block_id,
+            start_action_index: 0,
+            end_action_index: 0,
+            block_type: BlockType::Play,
+            parent_block: None,
+            entity_id: 10,
+        });
+
+        let analysis = HearthstoneMatchDeckAnalysis::from_game_log(&log, 1, Some(1), &deck);
+        assert!(analysis.won);
+        assert_eq!(analysis.opponent_class, "MAGE");
+        assert_eq!(analysis.play_order, HearthstonePlayOrder::First);
+        assert_eq!(analysis.duration_seconds, 300);
+        assert_eq!(analysis.num_turns, 9);
+        assert_eq!(analysis.cards.get("CARD_A"), Some(&HearthstoneCardMatchStats{mulligan_offered: 1, mulligan_kept: 1, drawn: true, played: true}));
+        assert_eq!(analysis.cards.get("CARD_B"), Some(&HearthstoneCardMatchStats{mulligan_offered: 1, mulligan_kept: 0, drawn: false, played: false}));
+        assert_eq!(analysis.cards.get("CARD_C"), Some(&HearthstoneCardMatchStats{mulligan_offered: 0, mulligan_kept: 0, drawn: true, played: false}));
+    }
+
+    #[test]
+    fn test_analytics_from_rows() {
+        let matchup = |version: i64, class: &str, order: HearthstonePlayOrder, games: i32, wins: i32| {
+            HearthstoneDeckMatchupRow{
+                deck_version_id: version,
+                opponent_class: class.to_string(),
+                play_order: order as i16,
+                games,
+                wins,
+                total_duration_seconds: games as i64 * 600,
+                total_turns: games as i64 * 10,
+            }
+        };
+
+        let analytics = HearthstoneDeckAnalytics::from_rows(5, &[
+            matchup(1, "MAGE", HearthstonePlayOrder::First, 2, 2),
+            matchup(1, "MAGE", HearthstonePlayOrder::Second, 2, 0),
+            matchup(2, "PRIEST", HearthstonePlayOrder::First, 4, 1),
+        ], &[
+            HearthstoneDeckCardRow{deck_version_id: 1, card_id: String::from("CARD_A"), mulligan_offered: 4, mulligan_kept: 1, drawn_games: 2, drawn_wins: 1, played_games: 1, played_wins: 1},
+            HearthstoneDeckCardRow{deck_version_id: 2, card_id: String::from("CARD_A"), mulligan_offered: 0, mulligan_kept: 0, drawn_games: 2, drawn_wins: 0, played_games: 0, played_wins: 0},
+        ]);
+
+        assert_eq!(analytics.stats.overall.games, 8);
+        assert_eq!(analytics.stats.overall.win_rate, Some(3.0 / 8.0));
+        assert_eq!(analytics.stats.overall.avg_duration_seconds, Some(600.0));
+        assert_eq!(analytics.stats.going_first.games, 6);
+        assert_eq!(analytics.stats.going_second.win_rate, Some(0.0));
+        assert_eq!(analytics.stats.by_opponent_class["MAGE"].win_rate, Some(0.5));
+        assert_eq!(analytics.versions.len(), 2);
+        assert_eq!(analytics.versions[1].stats.overall.wins, 1);
+        assert_eq!(analytics.cards.len(), 1);
+        assert_eq!(analytics.cards[0].mulligan_keep_rate, Some(0.25));
+        assert_eq!(analytics.cards[0].drawn_win_rate, Some(0.25));
+        assert_eq!(analytics.cards[0].played_win_rate, Some(1.0));
+    }
+}
@@ -433,7 +433,7 @@ impl HearthstoneGameLog {
         match self.current_blocks.pop() {
             Some(x) => {
                 let block = self.blocks.get_mut(&x).unwrap();
-                block.end_action_index = (self.actions.len() - 1) as i32;
+                block.end_action_index = self.actions.len() as i32 - 1;
                 ()
             },
             None => ()
@@ -466,7 +466,7 @@ impl HearthstoneGameLog {
         // that'll be useful for us in presenting information to the user.
         let mut new_snapshot = self.current_state.clone();
         new_snapshot.uuid = Uuid::new_v4();
-        new_snapshot.extract_aux_data(self.actions.len() - 1);
+        new_snapshot.extract_aux_data(self.actions.len().saturating_sub(1));
         self.snapshots.push(new_snapshot);
     }
 
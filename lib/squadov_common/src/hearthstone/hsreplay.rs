@@ -0,0 +1,801 @@
+use crate::{
+    SquadOvError,
+    hearthstone::{
+        GameType,
+        FormatType,
+        game_state::{
+            HearthstoneGameLog,
+            HearthstoneGameAction,
+            HearthstoneGameBlock,
+            ActionType,
+            BlockType,
+            EntityId,
+            game_step::GameStep,
+        },
+    },
+};
+use chrono::{DateTime, Utc, NaiveTime, Duration, SecondsFormat};
+use regex::Regex;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use uuid::Uuid;
+use xml::{
+    reader::{EventReader, XmlEvent as ReadEvent},
+    writer::{EmitterConfig, EventWriter, XmlEvent as WriteEvent},
+};
+
+const HSREPLAY_VERSION: &'static str = "1.7";
+const HSREPLAY_HEADER: &'static str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!DOCTYPE hsreplay SYSTEM \"https://hearthsim.info/hsreplay/dtd/hsreplay-1.7.dtd\">\n";
+
+// HSReplay identifies tags (and enum values) by their numeric ID while the power logs (and thus everything we store)
+// use the name. This only covers the tags we actually care about - anything else gets passed through as is (the power
+// logs already print unknown tags using their numeric ID).
+const HSREPLAY_GAME_TAGS: &'static [(&'static str, i32)] = &[
+    ("PREMIUM", 12),
+    ("PLAYSTATE", 17),
+    ("LAST_AFFECTED_BY", 18),
+    ("STEP", 19),
+    ("TURN", 20),
+    ("FATIGUE", 22),
+    ("CURRENT_PLAYER", 23),
+    ("FIRST_PLAYER", 24),
+    ("RESOURCES_USED", 25),
+    ("RESOURCES", 26),
+    ("HERO_ENTITY", 27),
+    ("MAXHANDSIZE", 28),
+    ("STARTHANDSIZE", 29),
+    ("PLAYER_ID", 30),
+    ("TEAM_ID", 31),
+    ("DEFENDING", 36),
+    ("PROPOSED_DEFENDER", 37),
+    ("ATTACKING", 38),
+    ("PROPOSED_ATTACKER", 39),
+    ("EXHAUSTED", 43),
+    ("DAMAGE", 44),
+    ("HEALTH", 45),
+    ("ATK", 47),
+    ("COST", 48),
+    ("ZONE", 49),
+    ("CONTROLLER", 50),
+    ("OWNER", 51),
+    ("ENTITY_ID", 53),
+    ("MAXRESOURCES", 176),
+    ("CARD_SET", 183),
+    ("DURABILITY", 187),
+    ("WINDFURY", 189),
+    ("TAUNT", 190),
+    ("STEALTH", 191),
+    ("SPELLPOWER", 192),
+    ("DIVINE_SHIELD", 194),
+    ("CHARGE", 197),
+    ("NEXT_STEP", 198),
+    ("CLASS", 199),
+    ("CARDRACE", 200),
+    ("FACTION", 201),
+    ("CARDTYPE", 202),
+    ("RARITY", 203),
+    ("FREEZE", 208),
+    ("DEATHRATTLE", 217),
+    ("BATTLECRY", 218),
+    ("SECRET", 219),
+    ("COMBO", 220),
+    ("FROZEN", 260),
+    ("JUST_PLAYED", 261),
+    ("ZONE_POSITION", 263),
+    ("NUM_TURNS_IN_PLAY", 271),
+    ("ARMOR", 292),
+    ("TEMP_RESOURCES", 295),
+    ("OVERLOAD_OWED", 296),
+    ("NUM_ATTACKS_THIS_TURN", 297),
+    ("MULLIGAN_STATE", 305),
+    ("CREATOR", 313),
+    ("OVERLOAD_LOCKED", 393),
+    ("NUM_CARDS_DRAWN_THIS_TURN", 399),
+];
+
+const HSREPLAY_ZONE_VALUES: &'static [(&'static str, i32)] = &[
+    ("INVALID", 0),
+    ("PLAY", 1),
+    ("DECK", 2),
+    ("HAND", 3),
+    ("GRAVEYARD", 4),
+    ("REMOVEDFROMGAME", 5),
+    ("SETASIDE", 6),
+    ("SECRET", 7),
+];
+
+const HSREPLAY_CARDTYPE_VALUES: &'static [(&'static str, i32)] = &[
+    ("INVALID", 0),
+    ("GAME", 1),
+    ("PLAYER", 2),
+    ("HERO", 3),
+    ("MINION", 4),
+    ("SPELL", 5),
+    ("ENCHANTMENT", 6),
+    ("WEAPON", 7),
+    ("ITEM", 8),
+    ("TOKEN", 9),
+    ("HERO_POWER", 10),
+];
+
+const HSREPLAY_PLAYSTATE_VALUES: &'static [(&'static str, i32)] = &[
+    ("INVALID", 0),
+    ("PLAYING", 1),
+    ("WINNING", 2),
+    ("LOSING", 3),
+    ("WON", 4),
+    ("LOST", 5),
+    ("TIED", 6),
+    ("DISCONNECTED", 7),
+    ("CONCEDED", 8),
+];
+
+// Used for both STEP and NEXT_STEP.
+const HSREPLAY_STEP_VALUES: &'static [(&'static str, i32)] = &[
+    ("INVALID", 0),
+    ("BEGIN_FIRST", 1),
+    ("BEGIN_SHUFFLE", 2),
+    ("BEGIN_DRAW", 3),
+    ("BEGIN_MULLIGAN", 4),
+    ("MAIN_BEGIN", 5),
+    ("MAIN_READY", 6),
+    ("MAIN_RESOURCE", 7),
+    ("MAIN_DRAW", 8),
+    ("MAIN_START", 9),
+    ("MAIN_ACTION", 10),
+    ("MAIN_COMBAT", 11),
+    ("MAIN_END", 12),
+    ("MAIN_NEXT", 13),
+    ("FINAL_WRAPUP", 14),
+    ("FINAL_GAMEOVER", 15),
+    ("MAIN_CLEANUP", 16),
+    ("MAIN_START_TRIGGERS", 17),
+];
+
+const HSREPLAY_MULLIGAN_VALUES: &'static [(&'static str, i32)] = &[
+    ("INVALID", 0),
+    ("INPUT", 1),
+    ("DEALING", 2),
+    ("WAITING", 3),
+    ("DONE", 4),
+];
+
+const HSREPLAY_CLASS_VALUES: &'static [(&'static str, i32)] = &[
+    ("INVALID", 0),
+    ("DEATHKNIGHT", 1),
+    ("DRUID", 2),
+    ("HUNTER", 3),
+    ("MAGE", 4),
+    ("PALADIN", 5),
+    ("PRIEST", 6),
+    ("ROGUE", 7),
+    ("SHAMAN", 8),
+    ("WARLOCK", 9),
+    ("WARRIOR", 10),
+    ("DREAM", 11),
+    ("NEUTRAL", 12),
+    ("WHIZBANG", 13),
+    ("DEMONHUNTER", 14),
+];
+
+const HSREPLAY_RARITY_VALUES: &'static [(&'static str, i32)] = &[
+    ("INVALID", 0),
+    ("COMMON", 1),
+    ("FREE", 2),
+    ("RARE", 3),
+    ("EPIC", 4),
+    ("LEGENDARY", 5),
+];
+
+const HSREPLAY_FACTION_VALUES: &'static [(&'static str, i32)] = &[
+    ("INVALID", 0),
+    ("HORDE", 1),
+    ("ALLIANCE", 2),
+    ("NEUTRAL", 3),
+];
+
+fn hsreplay_value_table(tag: &str) -> Option<&'static [(&'static str, i32)]> {
+    Some(match tag {
+        "ZONE" => HSREPLAY_ZONE_VALUES,
+        "CARDTYPE" => HSREPLAY_CARDTYPE_VALUES,
+        "STEP" | "NEXT_STEP" => HSREPLAY_STEP_VALUES,
+        "PLAYSTATE" => HSREPLAY_PLAYSTATE_VALUES,
+        "MULLIGAN_STATE" => HSREPLAY_MULLIGAN_VALUES,
+        "CLASS" => HSREPLAY_CLASS_VALUES,
+        "RARITY" => HSREPLAY_RARITY_VALUES,
+        "FACTION" => HSREPLAY_FACTION_VALUES,
+        _ => return None,
+    })
+}
+
+fn tag_to_hsreplay(tag: &str, value: &str) -> (String, String) {
+    let tag_id = HSREPLAY_GAME_TAGS.iter()
+        .find(|(name, _)| { *name == tag })
+        .map(|(_, id)| { id.to_string() })
+        .unwrap_or(tag.to_string());
+
+    let value_id = hsreplay_value_table(tag).and_then(|t| { t.iter().find(|(name, _)| { *name == value }).map(|(_, id)| { *id }) });
+
+    (tag_id, value_id.map(|x| { x.to_string() }).unwrap_or(value.to_string()))
+}
+
+fn tag_from_hsreplay(tag: &str, value: &str) -> (String, String) {
+    let tag_name = tag.parse::<i32>().ok()
+        .and_then(|id| { HSREPLAY_GAME_TAGS.iter().find(|(_, x)| { *x == id }) })
+        .map(|(name, _)| { name.to_string() })
+        .unwrap_or(tag.to_string());
+
+    let value_name = value.parse::<i32>().ok().and_then(|id| {
+        hsreplay_value_table(&tag_name).and_then(|t| { t.iter().find(|(_, x)| { *x == id }).map(|(name, _)| { name.to_string() }) })
+    });
+
+    (tag_name, value_name.unwrap_or(value.to_string()))
+}
+
+fn block_type_from_hsreplay(id: i32) -> BlockType {
+    match id {
+        1 => BlockType::Attack,
+        2 => BlockType::Joust,
+        3 => BlockType::Power,
+        5 => BlockType::Trigger,
+        6 => BlockType::Deaths,
+        7 => BlockType::Play,
+        8 => BlockType::Fatigue,
+        9 => BlockType::Ritual,
+        10 => BlockType::RevealCard,
+        11 => BlockType::GameReset,
+        12 => BlockType::MoveMinion,
+        _ => BlockType::Invalid,
+    }
+}
+
+fn hsreplay_ts(tm: &DateTime<Utc>) -> String {
+    tm.format("%H:%M:%S%.6f").to_string()
+}
+
+fn write_start<W: Write>(w: &mut EventWriter<W>, name: &str, attrs: &[(&str, String)]) -> Result<(), SquadOvError> {
+    let mut el = WriteEvent::start_element(name);
+    for (k, v) in attrs {
+        el = el.attr(*k, v.as_str());
+    }
+    w.write(el)?;
+    Ok(())
+}
+
+fn write_end<W: Write>(w: &mut EventWriter<W>) -> Result<(), SquadOvError> {
+    w.write(WriteEvent::end_element())?;
+    Ok(())
+}
+
+fn write_tags<W: Write>(w: &mut EventWriter<W>, tags: &HashMap<String, String>) -> Result<(), SquadOvError> {
+    let mut keys: Vec<&String> = tags.keys().collect();
+    keys.sort();
+    for k in keys {
+        let (tag, value) = tag_to_hsreplay(k, &tags[k]);
+        write_start(w, "Tag", &[("tag", tag), ("value", value)])?;
+        write_end(w)?;
+    }
+    Ok(())
+}
+
+// [hi=144115193835963207 lo=30722021]
+fn parse_game_account_id(s: &str) -> (String, String) {
+    lazy_static! {
+        static ref RE: Regex = Regex::new("hi=(\\d+)\\s+lo=(\\d+)").unwrap();
+    }
+
+    match RE.captures(s) {
+        Some(c) => (
+            c.get(1).map_or("0", |m| m.as_str()).to_string(),
+            c.get(2).map_or("0", |m| m.as_str()).to_string(),
+        ),
+        None => (String::from("0"), String::from("0")),
+    }
+}
+
+// Figures out which actions are directly inside which block so that we can recreate the nesting that
+// the power logs originally had.
+struct HsReplayExporter<'a> {
+    log: &'a HearthstoneGameLog,
+    children: HashMap<Option<Uuid>, Vec<&'a HearthstoneGameBlock>>,
+    player_names: HashMap<i32, String>,
+}
+
+impl<'a> HsReplayExporter<'a> {
+    fn new(log: &'a HearthstoneGameLog) -> Self {
+        let mut children: HashMap<Option<Uuid>, Vec<&'a HearthstoneGameBlock>> = HashMap::new();
+        for b in log.blocks.values() {
+            children.entry(b.parent_block.clone()).or_default().push(b);
+        }
+
+        for v in children.values_mut() {
+            // Empty blocks at the same index must've been opened before the non-empty block that starts there.
+            v.sort_by_key(|b| { (b.start_action_index, b.end_action_index.max(b.start_action_index - 1)) });
+        }
+
+        Self {
+            log,
+            children,
+            player_names: log.current_state.player_name_to_player_id.iter().map(|(name, id)| { (*id, name.clone()) }).collect(),
+        }
+    }
+
+    // Returns the [start, end) range of actions that are in the block.
+    fn block_range(&self, b: &HearthstoneGameBlock, parent_start: usize, parent_end: usize) -> (usize, usize) {
+        let start = (b.start_action_index.max(0) as usize).max(parent_start).min(parent_end);
+        // The end index is only set once the block is closed. A block that was never closed (e.g. the logs stopped
+        // mid-block) runs until the end of its parent.
+        let end = if b.end_action_index >= b.start_action_index - 1 {
+            (b.end_action_index + 1).max(0) as usize
+        } else {
+            parent_end
+        };
+        (start, end.min(parent_end).max(start))
+    }
+
+    fn write_block_contents<W: Write>(&self, w: &mut EventWriter<W>, parent: Option<Uuid>, start: usize, end: usize) -> Result<(), SquadOvError> {
+        let mut idx = start;
+        if let Some(children) = self.children.get(&parent) {
+            for child in children {
+                let (child_start, child_end) = self.block_range(child, idx, end);
+                self.write_actions(w, idx, child_start)?;
+
+                let ts = self.log.actions.get(child_start).or(self.log.actions.get(child_start.max(1) - 1)).map(|x| { hsreplay_ts(&x.tm) });
+                let mut attrs = vec![
+                    ("entity", child.entity_id.to_string()),
+                    ("type", (child.block_type as i32).to_string()),
+                ];
+                if let Some(ts) = ts {
+                    attrs.insert(0, ("ts", ts));
+                }
+
+                write_start(w, "Block", &attrs)?;
+                self.write_block_contents(w, Some(child.block_id.clone()), child_start, child_end)?;
+                write_end(w)?;
+                idx = child_end;
+            }
+        }
+        self.write_actions(w, idx, end)
+    }
+
+    fn write_actions<W: Write>(&self, w: &mut EventWriter<W>, start: usize, end: usize) -> Result<(), SquadOvError> {
+        for a in &self.log.actions[start.min(end)..end] {
+            self.write_action(w, a)?;
+        }
+        Ok(())
+    }
+
+    fn write_action<W: Write>(&self, w: &mut EventWriter<W>, a: &HearthstoneGameAction) -> Result<(), SquadOvError> {
+        // Actions that we couldn't resolve to an entity never modified the game state so there's nothing to export.
+        let entity_id = match a.real_entity_id {
+            Some(x) => x.to_string(),
+            None => return Ok(()),
+        };
+        let ts = hsreplay_ts(&a.tm);
+        let card_id = a.attributes.get("CardID").cloned().unwrap_or(String::new());
+
+        match a.action_type {
+            ActionType::CreateGame => {
+                write_start(w, "GameEntity", &[("id", entity_id)])?;
+                write_tags(w, &a.tags)?;
+                write_end(w)?;
+            },
+            ActionType::CreatePlayer => {
+                let player_id = a.attributes.get("PlayerID").cloned().unwrap_or(String::from("0"));
+                let (hi, lo) = parse_game_account_id(a.attributes.get("GameAccountId").map(|x| { x.as_str() }).unwrap_or(""));
+                let name = player_id.parse::<i32>().ok().and_then(|x| { self.player_names.get(&x) }).cloned().unwrap_or(String::new());
+                write_start(w, "Player", &[
+                    ("id", entity_id),
+                    ("playerID", player_id),
+                    ("accountHi", hi),
+                    ("accountLo", lo),
+                    ("name", name),
+                ])?;
+                write_tags(w, &a.tags)?;
+                write_end(w)?;
+            },
+            ActionType::FullEntity => {
+                let mut attrs = vec![("ts", ts), ("id", entity_id)];
+                if !card_id.is_empty() {
+                    attrs.push(("cardID", card_id));
+                }
+                write_start(w, "FullEntity", &attrs)?;
+                write_tags(w, &a.tags)?;
+                write_end(w)?;
+            },
+            ActionType::ShowEntity => {
+                write_start(w, "ShowEntity", &[("ts", ts), ("entity", entity_id), ("cardID", card_id)])?;
+                write_tags(w, &a.tags)?;
+                write_end(w)?;
+            },
+            ActionType::TagChange => {
+                let mut keys: Vec<&String> = a.tags.keys().collect();
+                keys.sort();
+                for k in keys {
+                    let (tag, value) = tag_to_hsreplay(k, &a.tags[k]);
+                    write_start(w, "TagChange", &[("ts", ts.clone()), ("entity", entity_id.clone()), ("tag", tag), ("value", value)])?;
+                    write_end(w)?;
+                }
+            },
+        };
+        Ok(())
+    }
+}
+
+pub fn export_hsreplay_xml(log: &HearthstoneGameLog, game_type: GameType, format_type: FormatType, scenario_id: i32) -> Result<String, SquadOvError> {
+    let mut buf: Vec<u8> = HSREPLAY_HEADER.as_bytes().to_vec();
+    {
+        let mut w = EmitterConfig::new()
+            .perform_indent(true)
+            .write_document_declaration(false)
+            .create_writer(&mut buf);
+
+        write_start(&mut w, "HSReplay", &[("version", String::from(HSREPLAY_VERSION))])?;
+
+        let start_time = log.actions.first().map(|x| { x.tm.clone() }).unwrap_or_else(Utc::now);
+        write_start(&mut w, "Game", &[
+            ("ts", start_time.to_rfc3339_opts(SecondsFormat::Micros, true)),
+            ("type", (game_type as i32).to_string()),
+            ("format", (format_type as i32).to_string()),
+            ("scenarioID", scenario_id.to_string()),
+        ])?;
+
+        let exporter = HsReplayExporter::new(log);
+        exporter.write_block_contents(&mut w, None, 0, log.actions.len())?;
+
+        write_end(&mut w)?;
+        write_end(&mut w)?;
+    }
+    Ok(String::from_utf8(buf)?)
+}
+
+pub struct HearthstoneReplay {
+    pub game_type: GameType,
+    pub format_type: FormatType,
+    pub scenario_id: i32,
+    pub start_time: DateTime<Utc>,
+    // Player ID -> player name.
+    pub players: HashMap<i32, String>,
+    pub log: HearthstoneGameLog,
+}
+
+impl HearthstoneReplay {
+    pub fn match_winner_player_id(&self) -> Option<i32> {
+        self.log.current_state.get_match_winner_player_id()
+    }
+
+    // HSReplay files don't tell us who recorded the game. The recording player is the only one whose
+    // starting hand is visible so we go with whoever has the most known cards in hand during the mulligan.
+    pub fn guess_local_player_id(&self) -> Option<i32> {
+        let snapshot = self.log.snapshots.iter()
+            .find(|s| { s.aux_data.as_ref().map(|x| { x.step == GameStep::BeginMulligan }).unwrap_or(false) })
+            .unwrap_or(&self.log.current_state);
+
+        let mut known_cards: HashMap<i32, i32> = HashMap::new();
+        for e in snapshot.entities.values() {
+            let in_hand = e.tags.get("ZONE").map(|x| { x == "HAND" }).unwrap_or(false);
+            let known = e.card_id().map(|x| { !x.is_empty() }).unwrap_or(false);
+            if !in_hand || !known {
+                continue;
+            }
+
+            if let Some(controller) = e.tags.get("CONTROLLER").and_then(|x| { x.parse::<i32>().ok() }) {
+                *known_cards.entry(controller).or_insert(0) += 1;
+            }
+        }
+
+        known_cards.into_iter().max_by_key(|(pid, cnt)| { (*cnt, -*pid) }).map(|(pid, _)| { pid })
+    }
+}
+
+struct HsReplayImporter {
+    replay: HearthstoneReplay,
+    last_tm: DateTime<Utc>,
+    // The entity that's currently being built up by <Tag> elements.
+    pending: Option<HearthstoneGameAction>,
+    // Ignore everything inside of elements we don't know how to handle (e.g. Options, MetaData).
+    skip_depth: usize,
+    in_game: bool,
+    finished_game: bool,
+}
+
+impl HsReplayImporter {
+    fn new() -> Self {
+        Self {
+            replay: HearthstoneReplay{
+                game_type: GameType::Unknown,
+                format_type: FormatType::Unknown,
+                scenario_id: 0,
+                start_time: Utc::now(),
+                players: HashMap::new(),
+                log: HearthstoneGameLog::new(),
+            },
+            last_tm: Utc::now(),
+            pending: None,
+            skip_depth: 0,
+            in_game: false,
+            finished_game: false,
+        }
+    }
+
+    fn parse_ts(&mut self, ts: Option<&String>) -> DateTime<Utc> {
+        if let Some(ts) = ts {
+            if let Ok(x) = DateTime::parse_from_rfc3339(ts) {
+                self.last_tm = x.with_timezone(&Utc);
+            } else if let Ok(t) = NaiveTime::parse_from_str(ts, "%H:%M:%S%.f") {
+                // Only the time of day is stored per action so we need to watch out for games that cross midnight.
+                if let Some(mut tm) = self.last_tm.date().and_time(t) {
+                    if tm < self.last_tm {
+                        tm = tm + Duration::days(1);
+                    }
+                    self.last_tm = tm;
+                }
+            }
+        }
+        self.last_tm.clone()
+    }
+
+    fn advance(&mut self, action: HearthstoneGameAction) {
+        self.replay.log.advance(vec![action]);
+    }
+
+    fn flush_pending(&mut self) {
+        if let Some(action) = self.pending.take() {
+            self.advance(action);
+        }
+    }
+
+    fn new_action(&self, tm: DateTime<Utc>, action_type: ActionType, entity_id: EntityId, attributes: Vec<(&str, String)>) -> HearthstoneGameAction {
+        HearthstoneGameAction{
+            tm,
+            action_type,
+            entity_id,
+            current_block_id: None,
+            real_entity_id: None,
+            tags: HashMap::new(),
+            attributes: attributes.into_iter().map(|(k, v)| { (k.to_string(), v) }).collect(),
+        }
+    }
+
+    fn on_start(&mut self, name: &str, attrs: HashMap<String, String>) -> Result<(), SquadOvError> {
+        if self.skip_depth > 0 {
+            self.skip_depth += 1;
+            return Ok(());
+        }
+
+        let get_i32 = |key: &str| -> Result<i32, SquadOvError> {
+            Ok(attrs.get(key).ok_or(SquadOvError::BadRequest)?.parse::<i32>()?)
+        };
+
+        if !self.in_game {
+            match name {
+                "HSReplay" => (),
+                "Game" if !self.finished_game => {
+                    self.in_game = true;
+                    self.replay.game_type = GameType::try_from(get_i32("type").unwrap_or(0)).unwrap_or(GameType::Unknown);
+                    self.replay.format_type = FormatType::try_from(get_i32("format").unwrap_or(0)).unwrap_or(FormatType::Unknown);
+                    self.replay.scenario_id = get_i32("scenarioID").unwrap_or(0);
+                    self.replay.start_time = self.parse_ts(attrs.get("ts"));
+                },
+                _ => self.skip_depth = 1,
+            };
+            return Ok(());
+        }
+
+        let tm = self.parse_ts(attrs.get("ts"));
+        match name {
+            "GameEntity" => {
+                self.flush_pending();
+                let id = get_i32("id")?;
+                self.pending = Some(self.new_action(tm, ActionType::CreateGame, EntityId::NewGameEntity(id), vec![("EntityID", id.to_string())]));
+            },
+            "Player" => {
+                self.flush_pending();
+                let id = get_i32("id")?;
+                let player_id = get_i32("playerID")?;
+                let account = format!(
+                    "[hi={} lo={}]",
+                    attrs.get("accountHi").map(|x| { x.as_str() }).unwrap_or("0"),
+                    attrs.get("accountLo").map(|x| { x.as_str() }).unwrap_or("0"),
+                );
+
+                if let Some(name) = attrs.get("name").filter(|x| { !x.is_empty() }) {
+                    self.replay.players.insert(player_id, name.clone());
+                    self.replay.log.set_player_map(&self.replay.players);
+                }
+
+                self.pending = Some(self.new_action(tm, ActionType::CreatePlayer, EntityId::NewPlayer{entity_id: id, player_id}, vec![
+                    ("EntityID", id.to_string()),
+                    ("PlayerID", player_id.to_string()),
+                    ("GameAccountId", account),
+                ]));
+            },
+            "FullEntity" => {
+                self.flush_pending();
+                let id = get_i32("id")?;
+                self.pending = Some(self.new_action(tm, ActionType::FullEntity, EntityId::New(id), vec![
+                    ("ID", id.to_string()),
+                    ("CardID", attrs.get("cardID").cloned().unwrap_or(String::new())),
+                ]));
+            },
+            "ShowEntity" => {
+                self.flush_pending();
+                let entity = attrs.get("entity").cloned().ok_or(SquadOvError::BadRequest)?;
+                self.pending = Some(self.new_action(tm, ActionType::ShowEntity, EntityId::Existing(entity.clone()), vec![
+                    ("Entity", entity),
+                    ("CardID", attrs.get("cardID").cloned().unwrap_or(String::new())),
+                ]));
+            },
+            "Tag" => {
+                if let Some(pending) = self.pending.as_mut() {
+                    let (tag, value) = tag_from_hsreplay(
+                        attrs.get("tag").map(|x| { x.as_str() }).unwrap_or(""),
+                        attrs.get("value").map(|x| { x.as_str() }).unwrap_or(""),
+                    );
+                    pending.tags.insert(tag, value);
+                }
+            },
+            "TagChange" => {
+                self.flush_pending();
+                let entity = attrs.get("entity").cloned().ok_or(SquadOvError::BadRequest)?;
+                let mut action = self.new_action(tm, ActionType::TagChange, EntityId::Existing(entity.clone()), vec![("Entity", entity)]);
+                let (tag, value) = tag_from_hsreplay(
+                    attrs.get("tag").map(|x| { x.as_str() }).unwrap_or(""),
+                    attrs.get("value").map(|x| { x.as_str() }).unwrap_or(""),
+                );
+                action.tags.insert(tag, value);
+                self.advance(action);
+            },
+            "Block" => {
+                self.flush_pending();
+                let entity = attrs.get("entity").cloned().unwrap_or(String::from("0"));
+                self.replay.log.push_block(block_type_from_hsreplay(get_i32("type").unwrap_or(0)), &EntityId::Existing(entity));
+            },
+            _ => {
+                self.flush_pending();
+                self.skip_depth = 1;
+            },
+        };
+        Ok(())
+    }
+
+    fn on_end(&mut self, name: &str) {
+        if self.skip_depth > 0 {
+            self.skip_depth -= 1;
+            return;
+        }
+
+        if !self.in_game {
+            return;
+        }
+
+        match name {
+            "GameEntity" | "Player" | "FullEntity" | "ShowEntity" => self.flush_pending(),
+            "Block" => {
+                self.flush_pending();
+                self.replay.log.pop_block();
+            },
+            "Game" => {
+                self.flush_pending();
+                self.in_game = false;
+                self.finished_game = true;
+            },
+            _ => (),
+        }
+    }
+}
+
+// Only the first game in the file gets imported.
+pub fn import_hsreplay_xml<R: Read>(data: R) -> Result<HearthstoneReplay, SquadOvError> {
+    let mut importer = HsReplayImporter::new();
+    for ev in EventReader::new(data) {
+        match ev? {
+            ReadEvent::StartElement{name, attributes, ..} => {
+                let attrs: HashMap<String, String> = attributes.into_iter().map(|x| { (x.name.local_name, x.value) }).collect();
+                importer.on_start(&name.local_name, attrs)?;
+            },
+            ReadEvent::EndElement{name} => importer.on_end(&name.local_name),
+            _ => (),
+        }
+    }
+
+    importer.flush_pending();
+    if importer.replay.log.actions.is_empty() {
+        return Err(SquadOvError::InternalError(String::from("HSReplay file does not contain a game.")));
+    }
+
+    importer.replay.log.finish();
+    Ok(importer.replay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hearthstone::{
+        HearthstoneRawLog,
+        power_parser::HearthstonePowerLogParser,
+    };
+    use chrono::TimeZone;
+    use std::path::PathBuf;
+
+    fn fixture_power_log() -> Vec<HearthstoneRawLog> {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_data");
+        path.push("hearthstone");
+        path.push("power_log.txt");
+
+        // Power.log lines look like: D 20:00:00.1234567 GameState.DebugPrintPower() - ...
+        let day = Utc.ymd(2022, 7, 26);
+        std::fs::read_to_string(path).unwrap().lines().filter(|x| { !x.trim().is_empty() }).map(|line| {
+            let tokens: Vec<&str> = line.splitn(3, ' ').collect();
+            HearthstoneRawLog{
+                time: day.and_time(NaiveTime::parse_from_str(tokens[1], "%H:%M:%S%.f").unwrap()).unwrap(),
+                section: String::from("Power"),
+                log: tokens[2].to_string(),
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn test_tag_conversion() {
+        assert_eq!(tag_to_hsreplay("ZONE", "HAND"), (String::from("49"), String::from("3")));
+        assert_eq!(tag_to_hsreplay("STEP", "BEGIN_MULLIGAN"), (String::from("19"), String::from("4")));
+        assert_eq!(tag_to_hsreplay("1234", "1"), (String::from("1234"), String::from("1")));
+        assert_eq!(tag_from_hsreplay("49", "3"), (String::from("ZONE"), String::from("HAND")));
+        assert_eq!(tag_from_hsreplay("198", "10"), (String::from("NEXT_STEP"), String::from("MAIN_ACTION")));
+        assert_eq!(tag_from_hsreplay("44", "3"), (String::from("DAMAGE"), String::from("3")));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut parser = HearthstonePowerLogParser::new(false);
+        parser.parse(&fixture_power_log()).unwrap();
+        let original = parser.fsm.game.read().unwrap();
+
+        let xml = export_hsreplay_xml(&original, parser.state.game_type, parser.state.format_type, parser.state.scenario_id).unwrap();
+        assert!(xml.contains("<HSReplay version=\"1.7\">"));
+        assert!(xml.contains("<Block"));
+
+        let replay = import_hsreplay_xml(xml.as_bytes()).unwrap();
+        assert_eq!(replay.game_type, GameType::Ranked);
+        assert_eq!(replay.scenario_id, 2);
+        assert_eq!(replay.match_winner_player_id(), original.current_state.get_match_winner_player_id());
+        assert!(replay.match_winner_player_id().is_some());
+        assert_eq!(replay.guess_local_player_id(), Some(1));
+
+        let expected = &original.current_state;
+        let actual = &replay.log.current_state;
+        assert_eq!(actual.tm, expected.tm);
+        assert_eq!(actual.game_entity_id, expected.game_entity_id);
+        assert_eq!(actual.player_name_to_player_id, expected.player_name_to_player_id);
+        assert_eq!(actual.player_id_to_entity_id, expected.player_id_to_entity_id);
+        assert_eq!(actual.entities.len(), expected.entities.len());
+        for (id, e) in &expected.entities {
+            let other = actual.entities.get(id).unwrap();
+            assert_eq!(other.tags, e.tags, "Tag mismatch for entity {}", id);
+            assert_eq!(other.card_id(), e.card_id(), "Card mismatch for entity {}", id);
+        }
+
+        assert_eq!(replay.log.blocks.len(), original.blocks.len());
+        assert_eq!(replay.log.snapshots.len(), original.snapshots.len());
+    }
+
+    #[test]
+    fn test_import_empty_block_before_any_action() {
+        // Closing a block before any action has been recorded used to underflow the block's end index.
+        let xml = r#"<HSReplay version="1.7">
+            <Game ts="2022-07-26T20:00:00.000000Z" type="7" format="2" scenarioID="2">
+                <Block entity="1" type="5"></Block>
+                <GameEntity id="1"><Tag tag="49" value="1"/></GameEntity>
+            </Game>
+        </HSReplay>"#;
+
+        let replay = import_hsreplay_xml(xml.as_bytes()).unwrap();
+        assert_eq!(replay.log.actions.len(), 1);
+        assert_eq!(replay.log.blocks.len(), 1);
+        let block = replay.log.blocks.values().next().unwrap();
+        assert_eq!(block.start_action_index, 0);
+        assert!(block.end_action_index < block.start_action_index);
+    }
+}
@@ -1,6 +1,7 @@
 pub mod power_fsm;
 use crate::hearthstone::{HearthstoneRawLog, GameType, FormatType};
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 use std::fmt;
 use std::collections::HashMap;
 
@@ -25,6 +26,12 @@ impl Default for HearthstoneGameState {
     }
 }
 
+impl HearthstoneGameState {
+    pub fn player_map(&self) -> &HashMap<i32, String> {
+        &self.player_map
+    }
+}
+
 impl fmt::Display for HearthstoneGameState {
     fn fmt(&self,  f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "[Game: {} Format: {} Scenario: {} Players: {:?}]", self.game_type, self.format_type, self.scenario_id, self.player_map)
@@ -62,6 +69,17 @@ impl PowerLog {
     }
 }
 
+// A line that we failed to parse while parsing leniently.
+#[derive(Clone, Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct HearthstonePowerLogDiagnostic {
+    // Index of the line in the logs that were passed to the parser.
+    pub index: usize,
+    pub tm: DateTime<Utc>,
+    pub log: String,
+    pub error: String,
+}
+
 pub struct HearthstonePowerLogParser {
     // State that doesn't change past the first time we find it in the logs.
     pub state: HearthstoneGameState,
@@ -69,6 +87,11 @@ pub struct HearthstonePowerLogParser {
     // FSM for parsing game state power lines. This FSM records all the tasks
     // performed as well as snapshots of the state of the game at certain points.
     pub fsm: power_fsm::PowerFsm,
+
+    // When set, lines that fail to parse are recorded in the diagnostics and skipped instead of failing the whole parse.
+    // This is only meant for offline tooling, we don't want to store a partially parsed game.
+    pub lenient: bool,
+    pub diagnostics: Vec<HearthstonePowerLogDiagnostic>,
 }
 
 impl HearthstonePowerLogParser {
@@ -77,7 +100,9 @@ impl HearthstonePowerLogParser {
             state: HearthstoneGameState{
                 ..Default::default()
             },
-            fsm: power_fsm::PowerFsm::new(store_raw)
+            fsm: power_fsm::PowerFsm::new(store_raw),
+            lenient: false,
+            diagnostics: Vec::new(),
         }
     }
 
@@ -90,21 +115,19 @@ impl HearthstonePowerLogParser {
     }
 
     pub fn parse(&mut self, logs: &[HearthstoneRawLog]) -> Result<(), crate::SquadOvError> {
-        for log in logs {
-            // Parse the log even further into the power log format.
-            let pl = PowerLog::new(log);
-
-            if pl.is_none() {
-                continue;
-            }
-            let pl = pl.unwrap();
-
-            if self.parse_game_state_print_game(&pl)? {
-                continue;
-            }
+        for (index, raw) in logs.iter().enumerate() {
+            if let Err(err) = self.parse_line(raw) {
+                if !self.lenient {
+                    return Err(err);
+                }
 
-            if self.parse_game_state_print_power(&log.time, &pl)? {
-                continue;
+                log::warn!("Failed to parse Hearthstone power log line {} [{}]: {:?}", index, &raw.log, err);
+                self.diagnostics.push(HearthstonePowerLogDiagnostic{
+                    index,
+                    tm: raw.time.clone(),
+                    log: raw.log.clone(),
+                    error: err.to_string(),
+                });
             }
         }
         self.fsm.finish()?;
@@ -117,12 +140,31 @@ impl HearthstonePowerLogParser {
         return Ok(())
     }
 
+    fn parse_line(&mut self, log: &HearthstoneRawLog) -> Result<(), crate::SquadOvError> {
+        // Parse the log even further into the power log format.
+        let pl = match PowerLog::new(log) {
+            Some(x) => x,
+            None => return Ok(())
+        };
+
+        if self.parse_game_state_print_game(&pl)? {
+            return Ok(());
+        }
+
+        self.parse_game_state_print_power(&log.time, &pl)?;
+        Ok(())
+    }
+
     fn parse_game_state_print_game(&mut self, log: &PowerLog) -> Result<bool, crate::SquadOvError> {
         if log.func != "GameState.DebugPrintGame()" {
             return Ok(false);
         }
 
         let tokens: Vec<&str> = log.log.split('=').collect();
+        if tokens.len() < 2 {
+            return Ok(true);
+        }
+
         if tokens[0] == "GameType" {
             self.state.game_type = tokens[1].parse()?;
         } else if tokens[0] == "FormatType" {
@@ -133,11 +175,15 @@ impl HearthstonePowerLogParser {
             // Special case where we're splitting a log line that looks like
             // PlayerID=ID, PlayerName=PLAYER_NAME
             let resplit: Vec<&str> = log.log.split(", ").collect();
+            if resplit.len() < 2 {
+                return Err(crate::SquadOvError::InternalError(format!("Unexpected player line: {}", &log.log)));
+            }
+
             let tokens: Vec<&str> = resplit[0].split('=').collect();
             let id = tokens[1].parse()?;
 
             let tokens: Vec<&str> = resplit[1].split('=').collect();
-            let name: String = tokens[1].to_string();
+            let name: String = tokens.get(1).ok_or(crate::SquadOvError::InternalError(format!("Missing player name: {}", &log.log)))?.to_string();
 
             self.state.player_map.insert(id, name);
 
@@ -80,6 +80,12 @@ impl PowerFsmStateInfo {
             attrs: HashMap::new(),
         }
     }
+
+    // States should grab everything they need to generate their actions when they're created so that
+    // a malformed line gets reported on the line it's actually on.
+    pub fn required_attr(&self, key: &str) -> Result<&String, crate::SquadOvError> {
+        self.attrs.get(key).ok_or(crate::SquadOvError::InternalError(format!("Missing {} attribute", key)))
+    }
 }
 
 // We have a "hierarchical" finite state machine where we expect to start the parsing at some root state
@@ -191,9 +197,9 @@ impl PowerLogAction {
             })
         } else {
             let action_tokens : Vec<&str> = log.log.split_whitespace().collect();
-            let action = match action_tokens[0].parse() {
-                Ok(x) => x,
-                Err(_) => return None
+            let action = match action_tokens.first().map(|x| { x.parse() }) {
+                Some(Ok(x)) => x,
+                _ => return None
             };
 
             Some(Self {
@@ -227,20 +233,20 @@ impl PowerLogTagAttribute {
     }
 }
 
-fn power_log_action_to_fsm_state(tm: &DateTime<Utc>, action: &PowerLogAction, st: Arc<RwLock<HearthstoneGameLog>>) -> Box<dyn PowerFsmState + Send + Sync> {
+fn power_log_action_to_fsm_state(tm: &DateTime<Utc>, action: &PowerLogAction, st: Arc<RwLock<HearthstoneGameLog>>) -> Result<Box<dyn PowerFsmState + Send + Sync>, crate::SquadOvError> {
     let mut info = PowerFsmStateInfo::new(tm);
     info.attrs = action.attrs.clone();
 
-    match action.action {
+    Ok(match action.action {
         PowerFsmAction::CreateGame => Box::new(create_game::CreateGameState::new(info)),
-        PowerFsmAction::CreateGameEntity => Box::new(create_game::CreateGameEntityState::new(info)),
-        PowerFsmAction::CreatePlayerEntity => Box::new(create_game::CreateGamePlayerState::new(info)),
-        PowerFsmAction::FullEntity => Box::new(full_entity::FullEntityState::new(info)),
-        PowerFsmAction::ShowEntity => Box::new(show_entity::ShowEntityState::new(info)),
-        PowerFsmAction::TagChange => Box::new(tag_change::TagChangeState::new(info)),
-        PowerFsmAction::BlockStart => Box::new(block_state::BlockState::new(info, st, action.indent_level)),
+        PowerFsmAction::CreateGameEntity => Box::new(create_game::CreateGameEntityState::new(info)?),
+        PowerFsmAction::CreatePlayerEntity => Box::new(create_game::CreateGamePlayerState::new(info)?),
+        PowerFsmAction::FullEntity => Box::new(full_entity::FullEntityState::new(info)?),
+        PowerFsmAction::ShowEntity => Box::new(show_entity::ShowEntityState::new(info)?),
+        PowerFsmAction::TagChange => Box::new(tag_change::TagChangeState::new(info)?),
+        PowerFsmAction::BlockStart => Box::new(block_state::BlockState::new(info, st, action.indent_level)?),
         _ => Box::new(null_state::NullState::new())
-    }
+    })
 }
 
 struct RawLog {
@@ -327,7 +333,7 @@ impl PowerFsm {
             // Note that the exception to this rule are the actions that denote an end of the block in which case
             // popping the latest block off is sufficient.
             if !is_fsm_action_block_end(&parsed_action.action) {
-                let next_state = power_log_action_to_fsm_state(tm, &parsed_action, self.game.clone());
+                let next_state = power_log_action_to_fsm_state(tm, &parsed_action, self.game.clone())?;
                 self.push_state(next_state)?;
             } else if self.states.len() > 1 {
                 // If it is an block end then we need to pop off ANOTHER state as the previous pop off
@@ -77,20 +77,20 @@ impl PowerFsmState for BlockState {
     }
 }
 impl BlockState {
-    pub fn new(info: PowerFsmStateInfo, st: Arc<RwLock<HearthstoneGameLog>>, indent_level: i32) -> Self {
+    pub fn new(info: PowerFsmStateInfo, st: Arc<RwLock<HearthstoneGameLog>>, indent_level: i32) -> Result<Self, crate::SquadOvError> {
         let block_type = match &info.attrs.get("BlockType") {
             Some(x) => x.parse().unwrap_or(BlockType::Invalid),
             None => BlockType::Invalid,
         };
 
-        let entity = EntityId::Existing(info.attrs.get("Entity").unwrap().to_string());
-        Self {
+        let entity = EntityId::Existing(info.required_attr("Entity")?.clone());
+        Ok(Self {
             info,
             game: st.clone(),
             block_type: block_type,
             entity_id: entity,
             has_actions: false,
             indent_level
-        }
+        })
     }
 }
\ No newline at end of file
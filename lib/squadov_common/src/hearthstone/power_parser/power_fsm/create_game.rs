@@ -25,7 +25,8 @@ impl CreateGameState {
 }
 
 pub struct CreateGameEntityState {
-    info: PowerFsmStateInfo
+    info: PowerFsmStateInfo,
+    entity_id: i32
 }
 impl PowerFsmState for CreateGameEntityState {
     fn get_state_uuid(&self) -> Uuid {
@@ -41,9 +42,7 @@ impl PowerFsmState for CreateGameEntityState {
             HearthstoneGameAction {
                 tm: self.info.tm.clone(),
                 action_type: ActionType::CreateGame,
-                entity_id: EntityId::NewGameEntity(
-                    self.info.attrs.get("EntityID").unwrap().parse().unwrap()
-                ),
+                entity_id: EntityId::NewGameEntity(self.entity_id),
                 real_entity_id: None,
                 current_block_id: None,
                 tags: self.info.tags.clone(),
@@ -54,15 +53,19 @@ impl PowerFsmState for CreateGameEntityState {
 }
 
 impl CreateGameEntityState {
-    pub fn new(info: PowerFsmStateInfo) -> Self {
-        Self {
+    pub fn new(info: PowerFsmStateInfo) -> Result<Self, crate::SquadOvError> {
+        let entity_id = info.required_attr("EntityID")?.parse()?;
+        Ok(Self {
             info,
-        }
+            entity_id,
+        })
     }
 }
 
 pub struct CreateGamePlayerState {
-    info: PowerFsmStateInfo
+    info: PowerFsmStateInfo,
+    entity_id: i32,
+    player_id: i32
 }
 impl PowerFsmState for CreateGamePlayerState {
     fn get_state_uuid(&self) -> Uuid {
@@ -79,8 +82,8 @@ impl PowerFsmState for CreateGamePlayerState {
                 tm: self.info.tm.clone(),
                 action_type: ActionType::CreatePlayer,
                 entity_id: EntityId::NewPlayer{
-                    entity_id: self.info.attrs.get("EntityID").unwrap().parse().unwrap(),
-                    player_id: self.info.attrs.get("PlayerID").unwrap().parse().unwrap(),
+                    entity_id: self.entity_id,
+                    player_id: self.player_id,
                 },
                 real_entity_id: None,
                 current_block_id: None,
@@ -91,9 +94,13 @@ impl PowerFsmState for CreateGamePlayerState {
     }
 }
 impl CreateGamePlayerState {
-    pub fn new(info: PowerFsmStateInfo) -> Self {
-        Self {
+    pub fn new(info: PowerFsmStateInfo) -> Result<Self, crate::SquadOvError> {
+        let entity_id = info.required_attr("EntityID")?.parse()?;
+        let player_id = info.required_attr("PlayerID")?.parse()?;
+        Ok(Self {
             info,
-        }
+            entity_id,
+            player_id,
+        })
     }
 }
\ No newline at end of file
@@ -4,6 +4,7 @@ use uuid::Uuid;
 
 pub struct FullEntityState {
     info: PowerFsmStateInfo,
+    entity_id: i32,
 }
 
 impl PowerFsmState for FullEntityState {
@@ -20,7 +21,7 @@ impl PowerFsmState for FullEntityState {
             HearthstoneGameAction {
                 tm: self.info.tm.clone(),
                 action_type: ActionType::FullEntity,
-                entity_id: EntityId::New(self.info.tags.get("ENTITY_ID").unwrap().parse().unwrap()),
+                entity_id: EntityId::New(self.info.tags.get("ENTITY_ID").and_then(|x| { x.parse().ok() }).unwrap_or(self.entity_id)),
                 real_entity_id: None,
                 current_block_id: None,
                 tags: self.info.tags.clone(),
@@ -35,9 +36,12 @@ impl PowerFsmState for FullEntityState {
 }
 
 impl FullEntityState {
-    pub fn new(info: PowerFsmStateInfo) -> Self {
-        Self {
+    pub fn new(info: PowerFsmStateInfo) -> Result<Self, crate::SquadOvError> {
+        // The ENTITY_ID tag should match the ID on the FULL_ENTITY line but we won't know that until we've seen the tags.
+        let entity_id = info.required_attr("ID")?.parse()?;
+        Ok(Self {
             info,
-        }
+            entity_id,
+        })
     }
 }
\ No newline at end of file
@@ -4,6 +4,7 @@ use uuid::Uuid;
 
 pub struct ShowEntityState {
     info: PowerFsmStateInfo,
+    entity_id: EntityId,
 }
 
 impl PowerFsmState for ShowEntityState {
@@ -20,7 +21,7 @@ impl PowerFsmState for ShowEntityState {
             HearthstoneGameAction {
                 tm: self.info.tm.clone(),
                 action_type: ActionType::ShowEntity,
-                entity_id: EntityId::Existing(self.info.attrs.get("Entity").unwrap().to_string()),
+                entity_id: self.entity_id.clone(),
                 real_entity_id: None,
                 current_block_id: None,
                 tags: self.info.tags.clone(),
@@ -35,9 +36,11 @@ impl PowerFsmState for ShowEntityState {
 }
 
 impl ShowEntityState {
-    pub fn new(info: PowerFsmStateInfo) -> Self {
-        Self {
+    pub fn new(info: PowerFsmStateInfo) -> Result<Self, crate::SquadOvError> {
+        let entity_id = EntityId::Existing(info.required_attr("Entity")?.clone());
+        Ok(Self {
             info,
-        }
+            entity_id,
+        })
     }
 }
\ No newline at end of file
@@ -4,6 +4,7 @@ use uuid::Uuid;
 
 pub struct TagChangeState {
     info: PowerFsmStateInfo,
+    entity_id: EntityId,
 }
 
 impl PowerFsmState for TagChangeState {   
@@ -20,7 +21,7 @@ impl PowerFsmState for TagChangeState {
             HearthstoneGameAction {
                 tm: self.info.tm.clone(),
                 action_type: ActionType::TagChange,
-                entity_id: EntityId::Existing(self.info.attrs.get("Entity").unwrap().to_string()),
+                entity_id: self.entity_id.clone(),
                 real_entity_id: None,
                 current_block_id: None,
                 tags: self.info.tags.clone(),
@@ -31,9 +32,11 @@ impl PowerFsmState for TagChangeState {
 }
 
 impl TagChangeState {
-    pub fn new(info: PowerFsmStateInfo) -> Self {
+    pub fn new(info: PowerFsmStateInfo) -> Result<Self, crate::SquadOvError> {
+        let entity_id = EntityId::Existing(info.required_attr("Entity")?.clone());
         let mut ret = Self {
             info,
+            entity_id,
         };
 
         // Tag changes are a bit weird in that the tags will be confused as attributes  by default
@@ -48,6 +51,6 @@ impl TagChangeState {
         ret.info.attrs.remove("tag");
         ret.info.attrs.remove("value");
 
-        ret
+        Ok(ret)
     }
 }
\ No newline at end of file
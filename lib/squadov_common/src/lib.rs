@@ -53,6 +53,8 @@ pub mod combatlog;
 pub mod elastic;
 pub mod stripe;
 pub mod crypto;
+pub mod features;
+pub mod teammates;
 
 pub use error::*;
 pub use parse::*;
@@ -115,9 +115,12 @@ impl<'a> BitReader<'a> {
         Ok(String::from_utf8(raw_data)?)
     }
 
-    pub fn read_aligned_bytes(&mut self, bytes: usize) -> Result<&[u8], SquadOvError> {
+    // The returned bytes borrow from the underlying buffer rather than the reader so the reader
+    // can keep being used while they're alive.
+    pub fn read_aligned_bytes(&mut self, bytes: usize) -> Result<&'a [u8], SquadOvError> {
         let bits = bytes * 8;
-        let res = self.view.get(self.ptr..self.ptr+bits).ok_or(SquadOvError::BadRequest)?.as_raw_slice();
+        let view: &'a BitSlice<Lsb0, u8> = self.view;
+        let res = view.get(self.ptr..self.ptr+bits).ok_or(SquadOvError::BadRequest)?.as_raw_slice();
         self.advance_bits(bits);
         Ok(res)
     }
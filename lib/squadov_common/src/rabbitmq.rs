@@ -56,6 +56,10 @@ pub struct RabbitMqConfig {
     pub enable_sharing: bool,
     pub sharing_queue: String,
     pub discord_queue: String,
+    #[serde(default)]
+    pub enable_stripe: bool,
+    #[serde(default)]
+    pub stripe_queue: String,
     pub enable_elasticsearch: bool,
     pub elasticsearch_queue: String,
     pub elasticsearch_workers: i32,
@@ -231,6 +235,14 @@ impl RabbitMqConnectionBundle {
                 ).await?;
             }
 
+            if !config.stripe_queue.is_empty() {
+                ch.queue_declare(
+                    &config.stripe_queue,
+                    queue_opts.clone(),
+                    default_table.clone(),
+                ).await?;
+            }
+
             if let Some(addtl) = &config.additional_queues {
                 for q in addtl {
                     ch.queue_declare(
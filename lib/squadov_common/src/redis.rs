@@ -1,3 +1,4 @@
+use crate::SquadOvError;
 use serde::Deserialize;
 
 #[derive(Deserialize,Debug,Clone)]
@@ -6,3 +7,19 @@ pub struct RedisConfig {
     pub pool_size: usize,
     pub timeout_ms: u64,
 }
+
+pub fn create_redis_pool(config: &RedisConfig) -> Result<deadpool_redis::Pool, SquadOvError> {
+    deadpool_redis::Config{
+        url: Some(config.url.clone()),
+        pool: Some(deadpool::managed::PoolConfig{
+            max_size: config.pool_size,
+            timeouts: deadpool::managed::Timeouts{
+                wait: Some(std::time::Duration::from_millis(config.timeout_ms)),
+                create: Some(std::time::Duration::from_millis(config.timeout_ms)),
+                recycle: Some(std::time::Duration::from_millis(config.timeout_ms)),
+            },
+        }),
+        connection: None,
+    }.create_pool(Some(deadpool_redis::Runtime::Tokio1))
+        .map_err(|x| { SquadOvError::InternalError(format!("Failed to create Redis pool: {:?}", x)) })
+}
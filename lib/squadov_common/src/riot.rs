@@ -1,7 +1,9 @@
 pub mod api;
 pub mod db;
 pub mod games;
+pub mod mock;
 pub mod rso;
+pub mod stats;
 
 use crate::SquadOvError;
 use serde::{Serialize, Deserialize};
@@ -1,16 +1,22 @@
 use async_trait::async_trait;
 
 mod account;
+mod cache;
+mod ratelimit;
 mod valorant;
 mod lol;
 mod tft;
 mod summoner;
 
-use serde::{Serialize, Deserialize};
+pub use cache::*;
+pub use ratelimit::*;
+
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
 use std::sync::Arc;
+use std::collections::HashMap;
 use crate::{
     SquadOvError,
-    rabbitmq::{RabbitMqInterface, RabbitMqListener, RabbitMqConfig},
+    rabbitmq::{RabbitMqInterface, RabbitMqListener, RabbitMqConfig, RABBITMQ_HIGH_PRIORITY},
     riot::db,
     elastic::{
         rabbitmq::ElasticSearchJobInterface,
@@ -18,10 +24,10 @@ use crate::{
 };
 use sqlx::postgres::{PgPool};
 use reqwest::header;
-use tokio::sync::{Semaphore};
 use reqwest::{StatusCode, Response};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
+use sha2::{Sha256, Digest};
 
 #[derive(Deserialize,Debug,Clone)]
 pub struct ApiKeyLimit {
@@ -46,13 +52,52 @@ pub struct RiotConfig {
     pub valorant_api_key: RiotApiKeyConfig,
     pub lol_api_key: RiotApiKeyConfig,
     pub tft_api_key: RiotApiKeyConfig,
+    #[serde(default)]
+    pub endpoints: RiotApiEndpointConfig,
+    #[serde(default)]
+    pub cache: RiotApiCacheConfig,
+    #[serde(default)]
+    pub rate_limit: RiotRateLimiterConfig,
+}
+
+#[derive(Deserialize,Debug,Clone)]
+#[serde(default)]
+pub struct RiotApiEndpointConfig {
+    // {region} gets replaced with the routing value or platform (e.g. americas or na1).
+    pub default_base_url: String,
+    // Overrides for specific routing values/platforms (lower case).
+    pub base_urls: HashMap<String, String>,
+    pub auth_url: String,
+}
+
+impl Default for RiotApiEndpointConfig {
+    fn default() -> Self {
+        Self {
+            default_base_url: String::from("https://{region}.api.riotgames.com"),
+            base_urls: HashMap::new(),
+            auth_url: String::from("https://auth.riotgames.com"),
+        }
+    }
+}
+
+impl RiotApiEndpointConfig {
+    pub fn base_url(&self, region: &str) -> String {
+        match self.base_urls.get(&region.to_lowercase()) {
+            Some(x) => x.clone(),
+            None => self.default_base_url.replace("{region}", region),
+        }
+    }
 }
 
 pub struct RiotApiHandler {
     api_key: RiotApiKeyConfig,
-    burst_threshold: Arc<Semaphore>,
-    bulk_threshold: Arc<Semaphore>,
+    limiter: RiotRateLimiter,
     db: Arc<PgPool>,
+    endpoints: RiotApiEndpointConfig,
+    cache: Option<Arc<dyn RiotApiCache>>,
+    cache_config: RiotApiCacheConfig,
+    // Riot encrypts IDs differently for every API key so cached responses can't be shared between keys.
+    cache_namespace: String,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -94,6 +139,9 @@ pub enum RiotApiTask {
         platform: String,
         game_id: i64,
     },
+    LolMatchPositionRepair{
+        match_uuid: Uuid,
+    },
     ValorantBackfill{
         puuid: String
     },
@@ -107,7 +155,10 @@ pub enum RiotApiTask {
     ValorantMatchPlayerCacheData{
         match_uuid: Uuid,
         user_id: i64,
-    }
+    },
+    ValorantMatchAbilityBackfill{
+        match_uuid: Uuid,
+    },
 }
 
 #[derive(Default)]
@@ -117,21 +168,27 @@ struct RiotApiStatus {
 }
 
 impl RiotApiHandler {
-    pub fn new(api_key: RiotApiKeyConfig, db: Arc<PgPool>) -> Self {
-        let burst_threshold = Arc::new(Semaphore::new(api_key.burst_limit.requests));
-        let bulk_threshold = Arc::new(Semaphore::new(api_key.bulk_limit.requests));
-
+    pub fn new(api_key: RiotApiKeyConfig, config: &RiotConfig, cache: Option<Arc<dyn RiotApiCache>>, rate_limit_store: Arc<dyn RiotRateLimitStore>, db: Arc<PgPool>) -> Self {
         log::info!("Riot Burst Limit: {} requests/{} seconds: ", api_key.burst_limit.requests, api_key.burst_limit.seconds);
         log::info!("Riot Bulk Limit: {} requests/{} seconds: ", api_key.bulk_limit.requests, api_key.bulk_limit.seconds);
 
+        let cache_namespace = hex::encode(Sha256::digest(api_key.key.as_bytes()))[0..16].to_string();
+        let limiter = RiotRateLimiter::new(&cache_namespace, &[&api_key.burst_limit, &api_key.bulk_limit], config.rate_limit.clone(), rate_limit_store);
         Self {
             api_key,
-            burst_threshold,
-            bulk_threshold,
+            limiter,
             db,
+            endpoints: config.endpoints.clone(),
+            cache,
+            cache_config: config.cache.clone(),
+            cache_namespace,
         }
     }
 
+    pub fn rate_limit_snapshot(&self) -> RiotRateLimitSnapshot {
+        self.limiter.snapshot()
+    }
+
     // Force a defer if we've manually set a region as being "down".
     pub async fn check_region_status(&self, game: &str, region: &str, allow_failover: bool) -> Result<(), SquadOvError> {
         let game = game.to_lowercase();
@@ -160,55 +217,58 @@ impl RiotApiHandler {
         }
     }
 
-    // Ticking the semaphore removes an available request and adds it back *_limit.seconds later.
-    // This way we can more accurately ensure that within any *seconds period, we only send
-    // *requests. Originally, this was a single thread that looped every *seconds anad refreshed
-    // the number of requests to the max amount; this resulted in a problem where we'd go over
-    // the rate limit due to the fact that we can use more than the rate limit amount within
-    // a given time period (especially if the time period is low).
-    async fn tick_burst_threshold(&self) -> Result<(), SquadOvError> {
-        if !self.api_key.burst_limit.enabled {
-            return Ok(());
-        }
+    // Waits on the rate limiter, sends the request and then lets the limiter learn from the response.
+    // The method should uniquely identify the endpoint (e.g. match-v5.getMatch) since Riot limits each one separately.
+    async fn send_rate_limited(&self, req: reqwest::RequestBuilder, region: &str, method: &str) -> Result<Response, SquadOvError> {
+        self.limiter.acquire(region, method).await;
+        let resp = req.send().await?;
+        self.limiter.update_from_response(region, method, resp.headers(), resp.status()).await;
+        Ok(resp)
+    }
 
-        let permit = self.burst_threshold.acquire().await?;
-        permit.forget();
+    fn build_api_endpoint(&self, region: &str, endpoint: &str) -> String {
+        format!("{}/{}", self.endpoints.base_url(region), endpoint)
+    }
 
-        let api_key = self.api_key.clone();
-        let threshold = self.burst_threshold.clone();
-        tokio::task::spawn(async move {
-            async_std::task::sleep(std::time::Duration::from_secs(api_key.burst_limit.seconds)).await;
-            threshold.add_permits(1);
-        });
-        Ok(())
+    fn build_cache_key(&self, region: &str, endpoint: &str) -> String {
+        format!("riot-api-{}-{}/{}", &self.cache_namespace, region.to_lowercase(), endpoint)
     }
 
-    async fn tick_bulk_threshold(&self) -> Result<(), SquadOvError> {
-        if !self.api_key.bulk_limit.enabled {
-            return Ok(());
+    // GET a Riot API endpoint that's authenticated with our API key. Successful responses are cached (if enabled)
+    // so failures and rate limits will always go back out to Riot.
+    async fn get_riot_api_json<T>(&self, region: &str, method: &str, endpoint: &str, category: RiotApiCacheCategory, context: &str) -> Result<T, SquadOvError>
+    where
+        T: DeserializeOwned
+    {
+        let ttl = self.cache_config.ttl_seconds(category);
+        let cache = self.cache.as_ref().filter(|_| { ttl != 0 });
+        let cache_key = self.build_cache_key(region, endpoint);
+
+        if let Some(cache) = cache {
+            match cache.get(&cache_key).await {
+                Ok(Some(raw)) => match serde_json::from_str::<T>(&raw) {
+                    Ok(x) => return Ok(x),
+                    Err(err) => log::warn!("Failed to parse cached Riot API response [{}]: {:?}", &cache_key, err),
+                },
+                Ok(None) => (),
+                Err(err) => log::warn!("Failed to read Riot API cache [{}]: {:?}", &cache_key, err),
+            }
         }
 
-        let permit = self.bulk_threshold.acquire().await?;
-        permit.forget();
+        let client = self.create_http_client()?;
+        let url = self.build_api_endpoint(region, endpoint);
+        let resp = self.send_rate_limited(client.get(&url), region, method).await?;
 
-        let api_key = self.api_key.clone();
-        let threshold = self.bulk_threshold.clone();
-        tokio::task::spawn(async move {
-            async_std::task::sleep(std::time::Duration::from_secs(api_key.bulk_limit.seconds)).await;
-            threshold.add_permits(1);
-        });
+        let resp = self.check_for_response_error(resp, context).await?;
+        let raw = resp.text().await?;
+        let ret = serde_json::from_str::<T>(&raw)?;
 
-        Ok(())
-    }
-
-    async fn tick_thresholds(&self) -> Result<(), SquadOvError> {
-        self.tick_burst_threshold().await?;
-        self.tick_bulk_threshold().await?;
-        Ok(())
-    }
-
-    fn build_api_endpoint(region: &str, endpoint: &str) -> String {
-        format!("https://{}.api.riotgames.com/{}", region, endpoint)
+        if let Some(cache) = cache {
+            if let Err(err) = cache.set(&cache_key, &raw, ttl).await {
+                log::warn!("Failed to write Riot API cache [{}]: {:?}", &cache_key, err);
+            }
+        }
+        Ok(ret)
     }
 
     fn create_http_client(&self) -> Result<reqwest::Client, SquadOvError> {
@@ -276,9 +336,31 @@ impl RiotApiApplicationInterface {
 
 #[async_trait]
 impl RabbitMqListener for RiotApiApplicationInterface {
-    async fn handle(&self, data: &[u8], queue: &str, _priority: u8) -> Result<(), SquadOvError> {
+    async fn handle(&self, data: &[u8], queue: &str, priority: u8) -> Result<(), SquadOvError> {
         log::info!("Handle Riot RabbitMQ Task: {} [{}]", std::str::from_utf8(data).unwrap_or("failure"), queue);
         let task: RiotApiTask = serde_json::from_slice(data)?;
+        let request_priority = task.request_priority(priority);
+        with_riot_request_priority(request_priority, self.handle_task(task, queue)).await
+    }
+}
+
+impl RiotApiTask {
+    // Matches that users are waiting on get published with a high priority; the same tasks coming out of a backfill don't.
+    fn request_priority(&self, priority: u8) -> RiotRequestPriority {
+        match self {
+            RiotApiTask::LolBackfill{..} | RiotApiTask::LolMatchPositionRepair{..} | RiotApiTask::TftBackfill{..} | RiotApiTask::ValorantBackfill{..} | RiotApiTask::ValorantMatchAbilityBackfill{..} => RiotRequestPriority::Backfill,
+            RiotApiTask::LolMatch{..} | RiotApiTask::TftMatch{..} | RiotApiTask::ValorantMatch{..} if priority < RABBITMQ_HIGH_PRIORITY => RiotRequestPriority::Backfill,
+            _ => RiotRequestPriority::UserFacing,
+        }
+    }
+}
+
+impl RiotApiApplicationInterface {
+    pub fn rate_limit_snapshot(&self) -> RiotRateLimitSnapshot {
+        self.api.rate_limit_snapshot()
+    }
+
+    async fn handle_task(&self, task: RiotApiTask, queue: &str) -> Result<(), SquadOvError> {
         match task {
             RiotApiTask::UnverifiedAccountLink{game_name, tag_line, summoner_name, platform_id, raw_puuid, user_id} => {
                 if game_name.is_some() && tag_line.is_some() {
@@ -311,6 +393,7 @@ impl RabbitMqListener for RiotApiApplicationInterface {
                     _ => return Err(err)
                 }
             },
+            RiotApiTask::LolMatchPositionRepair{match_uuid} => self.repair_lol_match_timeline_positions(&match_uuid).await?,
             RiotApiTask::TftBackfill{puuid, region} => self.backfill_user_tft_matches(&puuid, &region).await?,
             RiotApiTask::TftMatch{platform, region, game_id} => match self.obtain_tft_match_info(&platform, &region, game_id).await {
                 Ok(_) => (),
@@ -334,6 +417,7 @@ impl RabbitMqListener for RiotApiApplicationInterface {
                 db::cache_valorant_player_pov_information(&mut tx, &match_uuid, user_id).await?;
                 tx.commit().await?;
             },
+            RiotApiTask::ValorantMatchAbilityBackfill{match_uuid} => self.backfill_valorant_match_ability_casts(&match_uuid).await?,
         };
         Ok(())
     }
@@ -3,7 +3,7 @@ use crate::{
     rabbitmq::{RABBITMQ_DEFAULT_PRIORITY},
     riot::{RiotAccount, RiotSummonerDto, RiotSummoner, RiotUserInfo, games::VALORANT_SHORTHAND},
 };
-use super::RiotApiTask;
+use super::{RiotApiTask, RiotApiCacheCategory};
 use reqwest::{StatusCode};
 use crate::riot::db;
 use serde::Deserialize;
@@ -13,73 +13,49 @@ const RIOT_MAX_AGE_SECONDS: i64 = 86400; // 1 day
 
 impl super::RiotApiHandler {
     pub async fn get_account_by_puuid(&self, puuid: &str) -> Result<RiotAccount, SquadOvError> {
-        let client = self.create_http_client()?;
-        let endpoint = Self::build_api_endpoint("americas", &format!("riot/account/v1/accounts/by-puuid/{}", puuid));
-        self.tick_thresholds().await?;
-
-        let resp = client.get(&endpoint)
-            .send()
-            .await?;
-
-        if resp.status() != StatusCode::OK {
-            return Err(SquadOvError::InternalError(format!("Failed to obtain Riot acount by PUUID {} - {}", resp.status().as_u16(), resp.text().await?)));
-        }
-
-        Ok(resp.json::<RiotAccount>().await?)
+        self.get_riot_api_json(
+            "americas",
+            "account-v1.getByPuuid",
+            &format!("riot/account/v1/accounts/by-puuid/{}", puuid),
+            RiotApiCacheCategory::Account,
+            "Failed to obtain Riot acount by PUUID",
+        ).await
     }
 
     pub async fn get_account_by_game_name_tag_line(&self, game_name: &str, tag_line: &str) -> Result<RiotAccount, SquadOvError>{
-        let client = self.create_http_client()?;
-        let endpoint = Self::build_api_endpoint("americas", &format!("riot/account/v1/accounts/by-riot-id/{}/{}", game_name, tag_line));
-        self.tick_thresholds().await?;
-
-        let resp = client.get(&endpoint)
-            .send()
-            .await?;
-
-        if resp.status() != StatusCode::OK {
-            return Err(SquadOvError::InternalError(format!("Failed to obtain Riot acount by game name tag line {} - {}", resp.status().as_u16(), resp.text().await?)));
-        }
-
-        Ok(resp.json::<RiotAccount>().await?)
+        self.get_riot_api_json(
+            "americas",
+            "account-v1.getByRiotId",
+            &format!("riot/account/v1/accounts/by-riot-id/{}/{}", game_name, tag_line),
+            RiotApiCacheCategory::Account,
+            "Failed to obtain Riot acount by game name tag line",
+        ).await
     }
 
     pub async fn get_summoner_from_name(&self, summoner_name: &str, platform_id: &str) -> Result<RiotSummonerDto, SquadOvError>{
-        let client = self.create_http_client()?;
-        let endpoint = Self::build_api_endpoint(platform_id, &format!("lol/summoner/v4/summoners/by-name/{}", summoner_name));
-        self.tick_thresholds().await?;
-
-        let resp = client.get(&endpoint)
-            .send()
-            .await?;
-
-        if resp.status() != StatusCode::OK {
-            return Err(SquadOvError::InternalError(format!("Failed to obtain Riot summoner by name {} - {}", resp.status().as_u16(), resp.text().await?)));
-        }
-
-        Ok(resp.json::<RiotSummonerDto>().await?)
+        self.get_riot_api_json(
+            platform_id,
+            "summoner-v4.getBySummonerName",
+            &format!("lol/summoner/v4/summoners/by-name/{}", summoner_name),
+            RiotApiCacheCategory::Account,
+            "Failed to obtain Riot summoner by name",
+        ).await
     }
 
     pub async fn get_active_shard_by_game_for_puuid(&self, game: &str, puuid: &str) -> Result<String, SquadOvError> {
-        let client = self.create_http_client()?;
-        let endpoint = Self::build_api_endpoint("americas", &format!("riot/account/v1/active-shards/by-game/{game}/by-puuid/{puuid}", game=game, puuid=puuid));
-        self.tick_thresholds().await?;
-
-        let resp = client.get(&endpoint)
-            .send()
-            .await?;
-
-        if resp.status() != StatusCode::OK {
-            return Err(SquadOvError::InternalError(format!("Failed to get active shard for game by puuid {} - {}", resp.status().as_u16(), resp.text().await?)));
-        }
-
         #[derive(Deserialize)]
         struct ShardInfo {
             #[serde(rename="activeShard")]
             active_shard: String
         }
 
-        let shard = resp.json::<ShardInfo>().await?;
+        let shard: ShardInfo = self.get_riot_api_json(
+            "americas",
+            "account-v1.getActiveShard",
+            &format!("riot/account/v1/active-shards/by-game/{game}/by-puuid/{puuid}", game=game, puuid=puuid),
+            RiotApiCacheCategory::Account,
+            "Failed to get active shard for game by puuid",
+        ).await?;
         Ok(shard.active_shard)
     }
 
@@ -88,13 +64,8 @@ impl super::RiotApiHandler {
             .timeout(std::time::Duration::from_secs(120))
             .connect_timeout(std::time::Duration::from_secs(60))
             .build()?;
-        let endpoint = Self::build_api_endpoint("americas", "riot/account/v1/accounts/me");
-        self.tick_thresholds().await?;
-
-        let resp = client.get(&endpoint)
-            .bearer_auth(access_token)
-            .send()
-            .await?;
+        let endpoint = self.build_api_endpoint("americas", "riot/account/v1/accounts/me");
+        let resp = self.send_rate_limited(client.get(&endpoint).bearer_auth(access_token), "americas", "account-v1.getByAccessToken").await?;
 
         if resp.status() == StatusCode::TOO_MANY_REQUESTS {
             return Err(SquadOvError::RateLimit);
@@ -118,13 +89,8 @@ impl super::RiotApiHandler {
             .timeout(std::time::Duration::from_secs(120))
             .connect_timeout(std::time::Duration::from_secs(60))
             .build()?;
-        let endpoint = Self::build_api_endpoint(region, "lol/summoner/v4/summoners/me");
-        self.tick_thresholds().await?;
-
-        let resp = client.get(&endpoint)
-            .bearer_auth(access_token)
-            .send()
-            .await?;
+        let endpoint = self.build_api_endpoint(region, "lol/summoner/v4/summoners/me");
+        let resp = self.send_rate_limited(client.get(&endpoint).bearer_auth(access_token), region, "summoner-v4.getByAccessToken").await?;
 
         if resp.status() == StatusCode::TOO_MANY_REQUESTS {
             return Err(SquadOvError::RateLimit);
@@ -152,7 +118,7 @@ impl super::RiotApiHandler {
             .timeout(std::time::Duration::from_secs(120))
             .connect_timeout(std::time::Duration::from_secs(60))
             .build()?;
-        let endpoint = String::from("https://auth.riotgames.com/userinfo");
+        let endpoint = format!("{}/userinfo", &self.endpoints.auth_url);
         let resp = client.get(&endpoint)
             .bearer_auth(access_token)
             .send()
@@ -0,0 +1,211 @@
+use async_trait::async_trait;
+use crate::{
+    SquadOvError,
+    redis::{RedisConfig, create_redis_pool},
+};
+use serde::Deserialize;
+use sqlx::{Executor, Postgres, postgres::PgPool};
+use chrono::{Utc, Duration};
+use std::sync::Arc;
+
+// A TTL of 0 disables caching and a negative TTL caches the response forever.
+pub const RIOT_CACHE_FOREVER: i64 = -1;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all="lowercase")]
+pub enum RiotApiCacheBackend {
+    None,
+    Postgres,
+    Redis,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RiotApiCacheCategory {
+    // Accounts, summoners and active shards.
+    Account,
+    // Lists of match IDs for a player.
+    Matchlist,
+    // Match details and timelines. These never change once the match is over.
+    Match,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RiotApiCacheConfig {
+    pub backend: RiotApiCacheBackend,
+    pub account_ttl_seconds: i64,
+    pub matchlist_ttl_seconds: i64,
+    pub match_ttl_seconds: i64,
+}
+
+impl Default for RiotApiCacheConfig {
+    fn default() -> Self {
+        Self {
+            backend: RiotApiCacheBackend::None,
+            account_ttl_seconds: 3600,
+            matchlist_ttl_seconds: 300,
+            match_ttl_seconds: RIOT_CACHE_FOREVER,
+        }
+    }
+}
+
+impl RiotApiCacheConfig {
+    pub fn ttl_seconds(&self, category: RiotApiCacheCategory) -> i64 {
+        match category {
+            RiotApiCacheCategory::Account => self.account_ttl_seconds,
+            RiotApiCacheCategory::Matchlist => self.matchlist_ttl_seconds,
+            RiotApiCacheCategory::Match => self.match_ttl_seconds,
+        }
+    }
+}
+
+// Stores the raw JSON body of successful responses.
+#[async_trait]
+pub trait RiotApiCache: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<String>, SquadOvError>;
+    async fn set(&self, key: &str, value: &str, ttl_seconds: i64) -> Result<(), SquadOvError>;
+}
+
+pub struct PostgresRiotApiCache {
+    db: Arc<PgPool>,
+}
+
+impl PostgresRiotApiCache {
+    pub fn new(db: Arc<PgPool>) -> Self {
+        Self {
+            db,
+        }
+    }
+}
+
+#[async_trait]
+impl RiotApiCache for PostgresRiotApiCache {
+    async fn get(&self, key: &str) -> Result<Option<String>, SquadOvError> {
+        Ok(
+            sqlx::query_scalar(
+                "
+                SELECT response
+                FROM squadov.riot_api_response_cache
+                WHERE cache_key = $1
+                    AND (expires_at IS NULL OR expires_at > NOW())
+                "
+            )
+                .bind(key)
+                .fetch_optional(&*self.db)
+                .await?
+        )
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl_seconds: i64) -> Result<(), SquadOvError> {
+        let expires_at = if ttl_seconds < 0 {
+            None
+        } else {
+            Some(Utc::now() + Duration::seconds(ttl_seconds))
+        };
+
+        sqlx::query(
+            "
+            INSERT INTO squadov.riot_api_response_cache (
+                cache_key,
+                response,
+                cached_at,
+                expires_at
+            )
+            VALUES (
+                $1,
+                $2,
+                NOW(),
+                $3
+            )
+            ON CONFLICT (cache_key) DO UPDATE SET
+                response = EXCLUDED.response,
+                cached_at = EXCLUDED.cached_at,
+                expires_at = EXCLUDED.expires_at
+            "
+        )
+            .bind(key)
+            .bind(value)
+            .bind(expires_at)
+            .execute(&*self.db)
+            .await?;
+        Ok(())
+    }
+}
+
+// Expired rows are never read again but they don't get removed on their own either. Returns the number of rows removed.
+pub async fn purge_expired_riot_api_cache<'a, T>(ex: T) -> Result<u64, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query(
+            "
+            DELETE FROM squadov.riot_api_response_cache
+            WHERE expires_at < NOW()
+            "
+        )
+            .execute(ex)
+            .await?
+            .rows_affected()
+    )
+}
+
+pub struct RedisRiotApiCache {
+    redis: deadpool_redis::Pool,
+}
+
+impl RedisRiotApiCache {
+    pub fn new(config: &RedisConfig) -> Result<Self, SquadOvError> {
+        let redis = create_redis_pool(config)?;
+        Ok(Self {
+            redis,
+        })
+    }
+}
+
+#[async_trait]
+impl RiotApiCache for RedisRiotApiCache {
+    async fn get(&self, key: &str) -> Result<Option<String>, SquadOvError> {
+        let mut conn = self.redis.get().await?;
+        Ok(
+            deadpool_redis::redis::cmd("GET")
+                .arg(&[key])
+                .query_async(&mut conn)
+                .await?
+        )
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl_seconds: i64) -> Result<(), SquadOvError> {
+        let mut conn = self.redis.get().await?;
+        let mut cmd = deadpool_redis::redis::cmd("SET");
+        cmd.arg(&[key, value]);
+        if ttl_seconds >= 0 {
+            cmd.arg("EX").arg(ttl_seconds);
+        }
+
+        cmd.query_async::<_, ()>(&mut conn).await?;
+        Ok(())
+    }
+}
+
+pub fn create_riot_api_cache(config: &RiotApiCacheConfig, redis: &RedisConfig, db: Arc<PgPool>) -> Result<Option<Arc<dyn RiotApiCache>>, SquadOvError> {
+    Ok(match config.backend {
+        RiotApiCacheBackend::None => None,
+        RiotApiCacheBackend::Postgres => Some(Arc::new(PostgresRiotApiCache::new(db))),
+        RiotApiCacheBackend::Redis => Some(Arc::new(RedisRiotApiCache::new(redis)?)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_ttls() {
+        let config: RiotApiCacheConfig = serde_json::from_str(r#"{"backend": "redis", "matchlist_ttl_seconds": 60}"#).unwrap();
+        assert_eq!(config.backend, RiotApiCacheBackend::Redis);
+        assert_eq!(config.ttl_seconds(RiotApiCacheCategory::Matchlist), 60);
+        assert_eq!(config.ttl_seconds(RiotApiCacheCategory::Account), 3600);
+        assert_eq!(config.ttl_seconds(RiotApiCacheCategory::Match), RIOT_CACHE_FOREVER);
+    }
+}
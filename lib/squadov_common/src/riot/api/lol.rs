@@ -8,52 +8,48 @@ use crate::{
             LolMatchDto,
             LolMatchTimelineDto,
         },
-        api::riot_region_to_routing_with_oce,
+        api::{
+            riot_region_to_routing_with_oce,
+            RiotApiCacheCategory,
+        },
     },
+    teammates::db as tdb,
 };
 use super::RiotApiTask;
 use chrono::{Utc, Duration};
+use uuid::Uuid;
 
 const RIOT_MAX_AGE_SECONDS: i64 = 86400; // 1 day
 
 impl super::RiotApiHandler {
     pub async fn get_lol_matches_for_user(&self, puuid: &str, platform: &str, begin_index: i32, end_index: i32) -> Result<LolMatchlistDto, SquadOvError> {
-        let client = self.create_http_client()?;
-        let endpoint = Self::build_api_endpoint(&riot_region_to_routing_with_oce(platform)?, &format!("lol/match/v5/matches/by-puuid/{}/ids?start={}&count={}", puuid, begin_index, end_index - begin_index));
-        self.tick_thresholds().await?;
-
-        let resp = client.get(&endpoint)
-            .send()
-            .await?;
-
-        let resp = self.check_for_response_error(resp, "Failed to obtain LOL matches for user").await?;
-        Ok(resp.json::<LolMatchlistDto>().await?)
+        self.get_riot_api_json(
+            &riot_region_to_routing_with_oce(platform)?,
+            "match-v5.getMatchIdsByPUUID",
+            &format!("lol/match/v5/matches/by-puuid/{}/ids?start={}&count={}", puuid, begin_index, end_index - begin_index),
+            RiotApiCacheCategory::Matchlist,
+            "Failed to obtain LOL matches for user",
+        ).await
     }
 
     pub async fn get_lol_match(&self, platform: &str, game_id: i64) -> Result<LolMatchDto, SquadOvError> {
-        let client = self.create_http_client()?;
-        let endpoint = Self::build_api_endpoint(&riot_region_to_routing_with_oce(platform)?, &format!("lol/match/v5/matches/{}_{}", platform, game_id));
-        self.tick_thresholds().await?;
-
-        let resp = client.get(&endpoint)
-            .send()
-            .await?;
-
-        let resp = self.check_for_response_error(resp, "Failed to obtain LOL match").await?;
-        Ok(resp.json::<LolMatchDto>().await?)
+        self.get_riot_api_json(
+            &riot_region_to_routing_with_oce(platform)?,
+            "match-v5.getMatch",
+            &format!("lol/match/v5/matches/{}_{}", platform, game_id),
+            RiotApiCacheCategory::Match,
+            "Failed to obtain LOL match",
+        ).await
     }
 
     pub async fn get_lol_match_timeline(&self, platform: &str, game_id: i64) -> Result<LolMatchTimelineDto, SquadOvError> {
-        let client = self.create_http_client()?;
-        let endpoint = Self::build_api_endpoint(&riot_region_to_routing_with_oce(platform)?, &format!("lol/match/v5/matches/{}_{}/timeline", platform, game_id));
-        self.tick_thresholds().await?;
-
-        let resp = client.get(&endpoint)
-            .send()
-            .await?;
-
-        let resp = self.check_for_response_error(resp, "Failed to obtain LOL match timeline").await?;
-        Ok(resp.json::<LolMatchTimelineDto>().await?)
+        self.get_riot_api_json(
+            &riot_region_to_routing_with_oce(platform)?,
+            "match-v5.getTimeline",
+            &format!("lol/match/v5/matches/{}_{}/timeline", platform, game_id),
+            RiotApiCacheCategory::Match,
+            "Failed to obtain LOL match timeline",
+        ).await
     }
 }
 
@@ -121,6 +117,7 @@ impl super::RiotApiApplicationInterface {
             if match_timeline.is_some() {
                 db::store_lol_match_timeline_info(&mut tx, &match_uuid, &match_timeline.as_ref().unwrap().info).await?;
             }
+            tdb::queue_teammate_roster_sync(&mut tx, &match_uuid).await?;
             tx.commit().await?;
 
             self.es_itf.request_sync_match(match_uuid, None).await?;
@@ -130,6 +127,35 @@ impl super::RiotApiApplicationInterface {
         Ok(())
     }
 
+    pub async fn request_repair_lol_match_timeline_positions(&self, match_uuid: &Uuid) -> Result<(), SquadOvError> {
+        self.rmq.publish(&self.mqconfig.lol_queue, serde_json::to_vec(&RiotApiTask::LolMatchPositionRepair{
+            match_uuid: match_uuid.clone(),
+        })?, RABBITMQ_DEFAULT_PRIORITY, RIOT_MAX_AGE_SECONDS).await;
+        Ok(())
+    }
+
+    pub async fn repair_lol_match_timeline_positions(&self, match_uuid: &Uuid) -> Result<(), SquadOvError> {
+        let link = db::get_lol_match_link_from_uuid(&*self.db, match_uuid).await?;
+        log::info!("Repair LoL Match Timeline Positions {} [{}] - {}", link.match_id, &link.platform, match_uuid);
+
+        let match_timeline = match self.api.get_lol_match_timeline(&link.platform, link.match_id).await {
+            Ok(x) => Some(x),
+            Err(err) => match err {
+                // Riot doesn't keep timelines around forever so there's nothing left to repair from.
+                SquadOvError::NotFound => None,
+                _ => return Err(err)
+            }
+        };
+
+        let mut tx = self.db.begin().await?;
+        if let Some(timeline) = match_timeline.as_ref() {
+            db::repair_lol_match_timeline_frame_positions(&mut tx, match_uuid, &timeline.info).await?;
+        }
+        db::finish_lol_match_position_repair(&mut tx, match_uuid).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
     pub async fn request_backfill_user_lol_matches(&self, summoner_name: &str, platform: &str, user_id: i64) -> Result<(), SquadOvError> {
         let summoner = db::get_user_riot_summoner_from_name(&*self.db, user_id, summoner_name).await?.ok_or(SquadOvError::NotFound)?;
         if summoner.last_backfill_lol_time.is_some() {
@@ -0,0 +1,590 @@
+// Client side rate limiting for the Riot API. Riot tells us the limits that apply to every response using the
+// X-App-Rate-Limit (per API key per region) and X-Method-Rate-Limit (per API key per region per endpoint) headers
+// so we learn them as we go instead of relying purely on what's in the config. Each limit becomes a token bucket
+// and a request has to take a token from every bucket that applies to it before it's sent out.
+use async_trait::async_trait;
+use crate::{
+    SquadOvError,
+    redis::{RedisConfig, create_redis_pool},
+};
+use super::ApiKeyLimit;
+use reqwest::{StatusCode, header::HeaderMap};
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex, RwLock};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct RiotRateLimit {
+    pub requests: u32,
+    pub seconds: u64,
+}
+
+// Riot sends limits as a comma separated list of requests:seconds pairs, e.g. "20:1,100:120".
+pub fn parse_riot_rate_limit_header(value: &str) -> Vec<RiotRateLimit> {
+    value.split(',')
+        .filter_map(|x| {
+            let (requests, seconds) = x.trim().split_once(':')?;
+            let requests = requests.trim().parse::<u32>().ok()?;
+            let seconds = seconds.trim().parse::<u64>().ok()?;
+            if requests == 0 || seconds == 0 {
+                None
+            } else {
+                Some(RiotRateLimit{
+                    requests,
+                    seconds,
+                })
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RiotRequestPriority {
+    // Something a user is actively waiting on (e.g. the match they just finished).
+    UserFacing,
+    // Backfills and anything else that can wait.
+    Backfill,
+}
+
+tokio::task_local! {
+    static RIOT_REQUEST_PRIORITY: RiotRequestPriority;
+}
+
+pub fn current_riot_request_priority() -> RiotRequestPriority {
+    RIOT_REQUEST_PRIORITY.try_with(|x| { *x }).unwrap_or(RiotRequestPriority::UserFacing)
+}
+
+// Every Riot API request made by the future uses the given priority.
+pub async fn with_riot_request_priority<F>(priority: RiotRequestPriority, f: F) -> F::Output
+where
+    F: Future
+{
+    RIOT_REQUEST_PRIORITY.scope(priority, f).await
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all="lowercase")]
+pub enum RiotRateLimitBackend {
+    // Only limits requests made by this process.
+    Local,
+    // Shares buckets between every process using the same API key.
+    Redis,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RiotRateLimiterConfig {
+    pub backend: RiotRateLimitBackend,
+    // Fraction of each bucket that backfill requests aren't allowed to use.
+    pub backfill_reserve: f64,
+    // Fraction of each limit that can be sent all at once. The rest gets spread out evenly over the window.
+    pub burst_fraction: f64,
+    // How long to back off on a 429 when Riot doesn't send a Retry-After.
+    pub default_backoff_ms: u64,
+}
+
+impl Default for RiotRateLimiterConfig {
+    fn default() -> Self {
+        Self {
+            backend: RiotRateLimitBackend::Local,
+            backfill_reserve: 0.2,
+            burst_fraction: 0.1,
+            default_backoff_ms: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiotRateLimitBucket {
+    pub key: String,
+    pub capacity: f64,
+    // Tokens per millisecond.
+    pub refill_rate: f64,
+}
+
+impl RiotRateLimitBucket {
+    // The bucket starts with a burst's worth of tokens and refills the rest of the limit over the window so that
+    // we never send more than the limit in any window.
+    pub fn new(key: String, limit: &RiotRateLimit, burst_fraction: f64) -> Self {
+        let requests = limit.requests as f64;
+        let window_ms = (limit.seconds * 1000) as f64;
+        let capacity = (requests * burst_fraction.clamp(0.0, 1.0)).floor().max(1.0);
+        let refill = if requests > capacity { requests - capacity } else { requests };
+        Self {
+            key,
+            capacity,
+            refill_rate: refill / window_ms,
+        }
+    }
+
+    pub fn refill(&self, tokens: f64, elapsed_ms: i64) -> f64 {
+        (tokens + elapsed_ms.max(0) as f64 * self.refill_rate).min(self.capacity)
+    }
+
+    // How long we need to wait until there's enough tokens in the bucket. Lower priority requests need to leave the
+    // reserve in the bucket.
+    pub fn wait_ms(&self, tokens: f64, reserve: f64) -> u64 {
+        let needed = (1.0 + reserve * self.capacity).min(self.capacity);
+        if tokens >= needed {
+            0
+        } else {
+            ((needed - tokens) / self.refill_rate).ceil() as u64
+        }
+    }
+}
+
+#[async_trait]
+pub trait RiotRateLimitStore: Send + Sync {
+    // Takes a token from every bucket if all of them have enough tokens and none of the backoff keys are active.
+    // Otherwise nothing is taken and we return how long to wait (in milliseconds) before trying again.
+    async fn try_acquire(&self, buckets: &[RiotRateLimitBucket], backoff_keys: &[String], reserve: f64) -> Result<u64, SquadOvError>;
+    async fn back_off(&self, key: &str, ms: u64) -> Result<(), SquadOvError>;
+}
+
+#[derive(Default)]
+struct LocalRiotRateLimitState {
+    // Key -> (tokens, last update in ms).
+    buckets: HashMap<String, (f64, i64)>,
+    // Key -> time in ms that the backoff ends.
+    backoffs: HashMap<String, i64>,
+}
+
+#[derive(Default)]
+pub struct LocalRiotRateLimitStore {
+    state: Mutex<LocalRiotRateLimitState>,
+}
+
+impl LocalRiotRateLimitStore {
+    fn try_acquire_at(&self, buckets: &[RiotRateLimitBucket], backoff_keys: &[String], reserve: f64, now: i64) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let mut wait: u64 = backoff_keys.iter()
+            .filter_map(|x| { state.backoffs.get(x) })
+            .map(|x| { (*x - now).max(0) as u64 })
+            .max()
+            .unwrap_or(0);
+
+        let tokens: Vec<f64> = buckets.iter().map(|x| {
+            match state.buckets.get(&x.key) {
+                Some((tokens, ts)) => x.refill(*tokens, now - *ts),
+                None => x.capacity,
+            }
+        }).collect();
+
+        for (b, t) in buckets.iter().zip(tokens.iter()) {
+            wait = wait.max(b.wait_ms(*t, reserve));
+        }
+
+        if wait == 0 {
+            for (b, t) in buckets.iter().zip(tokens.iter()) {
+                state.buckets.insert(b.key.clone(), (*t - 1.0, now));
+            }
+        }
+        wait
+    }
+}
+
+#[async_trait]
+impl RiotRateLimitStore for LocalRiotRateLimitStore {
+    async fn try_acquire(&self, buckets: &[RiotRateLimitBucket], backoff_keys: &[String], reserve: f64) -> Result<u64, SquadOvError> {
+        Ok(self.try_acquire_at(buckets, backoff_keys, reserve, chrono::Utc::now().timestamp_millis()))
+    }
+
+    async fn back_off(&self, key: &str, ms: u64) -> Result<(), SquadOvError> {
+        let until = chrono::Utc::now().timestamp_millis() + ms as i64;
+        let mut state = self.state.lock().unwrap();
+        let entry = state.backoffs.entry(key.to_string()).or_insert(until);
+        *entry = (*entry).max(until);
+        Ok(())
+    }
+}
+
+// KEYS: the buckets followed by the backoff keys.
+// ARGV: number of buckets, reserve, then capacity + refill rate for each bucket.
+// Uses Redis' clock so that every instance agrees on the time.
+const REDIS_ACQUIRE_SCRIPT: &str = r#"
+local now_parts = redis.call('TIME')
+local now = tonumber(now_parts[1]) * 1000 + math.floor(tonumber(now_parts[2]) / 1000)
+local nbuckets = tonumber(ARGV[1])
+local reserve = tonumber(ARGV[2])
+local wait = 0
+local tokens = {}
+for i = 1, nbuckets do
+    local capacity = tonumber(ARGV[1 + i * 2])
+    local rate = tonumber(ARGV[2 + i * 2])
+    local state = redis.call('HMGET', KEYS[i], 'tokens', 'ts')
+    local t = tonumber(state[1])
+    local ts = tonumber(state[2])
+    if t == nil or ts == nil then
+        t = capacity
+    else
+        t = math.min(capacity, t + math.max(0, now - ts) * rate)
+    end
+    tokens[i] = t
+    local needed = math.min(capacity, 1 + reserve * capacity)
+    if t < needed then
+        wait = math.max(wait, math.ceil((needed - t) / rate))
+    end
+end
+for i = nbuckets + 1, #KEYS do
+    local ttl = redis.call('PTTL', KEYS[i])
+    if ttl > 0 then
+        wait = math.max(wait, ttl)
+    end
+end
+if wait == 0 then
+    for i = 1, nbuckets do
+        local capacity = tonumber(ARGV[1 + i * 2])
+        local rate = tonumber(ARGV[2 + i * 2])
+        redis.call('HSET', KEYS[i], 'tokens', tostring(tokens[i] - 1), 'ts', now)
+        redis.call('PEXPIRE', KEYS[i], math.ceil(capacity / rate) + 1000)
+    end
+end
+return wait
+"#;
+
+pub struct RedisRiotRateLimitStore {
+    redis: deadpool_redis::Pool,
+    script: deadpool_redis::redis::Script,
+}
+
+impl RedisRiotRateLimitStore {
+    pub fn new(config: &RedisConfig) -> Result<Self, SquadOvError> {
+        Ok(Self {
+            redis: create_redis_pool(config)?,
+            script: deadpool_redis::redis::Script::new(REDIS_ACQUIRE_SCRIPT),
+        })
+    }
+}
+
+#[async_trait]
+impl RiotRateLimitStore for RedisRiotRateLimitStore {
+    async fn try_acquire(&self, buckets: &[RiotRateLimitBucket], backoff_keys: &[String], reserve: f64) -> Result<u64, SquadOvError> {
+        let mut conn = self.redis.get().await?;
+        let mut invocation = self.script.prepare_invoke();
+        for b in buckets {
+            invocation.key(&b.key);
+        }
+
+        for k in backoff_keys {
+            invocation.key(k);
+        }
+
+        invocation.arg(buckets.len()).arg(reserve);
+        for b in buckets {
+            invocation.arg(b.capacity).arg(b.refill_rate);
+        }
+
+        let wait: i64 = invocation.invoke_async(&mut conn).await?;
+        Ok(wait.max(0) as u64)
+    }
+
+    async fn back_off(&self, key: &str, ms: u64) -> Result<(), SquadOvError> {
+        let mut conn = self.redis.get().await?;
+        // Only ever extend an existing backoff.
+        let current: i64 = deadpool_redis::redis::cmd("PTTL")
+            .arg(key)
+            .query_async(&mut conn)
+            .await?;
+
+        if current < ms as i64 {
+            deadpool_redis::redis::cmd("SET")
+                .arg(key)
+                .arg(1)
+                .arg("PX")
+                .arg(ms)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+pub fn create_riot_rate_limit_store(config: &RiotRateLimiterConfig, redis: &RedisConfig) -> Result<Arc<dyn RiotRateLimitStore>, SquadOvError> {
+    Ok(match config.backend {
+        RiotRateLimitBackend::Local => Arc::new(LocalRiotRateLimitStore::default()),
+        RiotRateLimitBackend::Redis => Arc::new(RedisRiotRateLimitStore::new(redis)?),
+    })
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all="camelCase")]
+pub struct RiotRateLimitCounters {
+    // Requests that went out without waiting.
+    pub allowed: u64,
+    // Requests that had to wait for the limiter.
+    pub delayed: u64,
+    pub delayed_ms: u64,
+    // Delayed requests that were backfills.
+    pub backfill_delayed: u64,
+    // 429s we got back from Riot anyway.
+    pub rate_limited: u64,
+    // Number of times Riot's headers changed a limit.
+    pub limit_updates: u64,
+    // Number of times the shared store failed and we fell back to limiting locally.
+    pub store_errors: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct RiotRateLimitSnapshot {
+    // Keyed by region.
+    pub app_limits: BTreeMap<String, Vec<RiotRateLimit>>,
+    // Keyed by region:method.
+    pub method_limits: BTreeMap<String, Vec<RiotRateLimit>>,
+    pub counters: BTreeMap<String, RiotRateLimitCounters>,
+}
+
+pub struct RiotRateLimiter {
+    // Keeps buckets for different API keys apart when they share a store.
+    namespace: String,
+    config: RiotRateLimiterConfig,
+    // Used for regions where Riot hasn't told us the app limits yet.
+    default_app_limits: Vec<RiotRateLimit>,
+    app_limits: RwLock<HashMap<String, Vec<RiotRateLimit>>>,
+    method_limits: RwLock<HashMap<(String, String), Vec<RiotRateLimit>>>,
+    store: Arc<dyn RiotRateLimitStore>,
+    fallback: LocalRiotRateLimitStore,
+    counters: Mutex<HashMap<String, RiotRateLimitCounters>>,
+}
+
+impl RiotRateLimiter {
+    pub fn new(namespace: &str, key_limits: &[&ApiKeyLimit], config: RiotRateLimiterConfig, store: Arc<dyn RiotRateLimitStore>) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            config,
+            default_app_limits: key_limits.iter()
+                .filter(|x| { x.enabled && x.requests > 0 && x.seconds > 0 })
+                .map(|x| {
+                    RiotRateLimit{
+                        requests: x.requests as u32,
+                        seconds: x.seconds,
+                    }
+                })
+                .collect(),
+            app_limits: RwLock::new(HashMap::new()),
+            method_limits: RwLock::new(HashMap::new()),
+            store,
+            fallback: LocalRiotRateLimitStore::default(),
+            counters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn app_scope(&self, region: &str) -> String {
+        format!("riot-rl-{}-{}:app", &self.namespace, region)
+    }
+
+    fn method_scope(&self, region: &str, method: &str) -> String {
+        format!("riot-rl-{}-{}:method:{}", &self.namespace, region, method)
+    }
+
+    fn backoff_key(scope: &str) -> String {
+        format!("{}:backoff", scope)
+    }
+
+    fn buckets(&self, region: &str, method: &str) -> Vec<RiotRateLimitBucket> {
+        let app_scope = self.app_scope(region);
+        let method_scope = self.method_scope(region, method);
+
+        let mut ret: Vec<RiotRateLimitBucket> = vec![];
+        {
+            let app_limits = self.app_limits.read().unwrap();
+            for l in app_limits.get(region).unwrap_or(&self.default_app_limits) {
+                ret.push(RiotRateLimitBucket::new(format!("{}:{}:{}", &app_scope, l.requests, l.seconds), l, self.config.burst_fraction));
+            }
+        }
+
+        let method_limits = self.method_limits.read().unwrap();
+        if let Some(limits) = method_limits.get(&(region.to_string(), method.to_string())) {
+            for l in limits {
+                ret.push(RiotRateLimitBucket::new(format!("{}:{}:{}", &method_scope, l.requests, l.seconds), l, self.config.burst_fraction));
+            }
+        }
+        ret
+    }
+
+    fn record<F>(&self, region: &str, method: &str, f: F)
+    where
+        F: FnOnce(&mut RiotRateLimitCounters)
+    {
+        let mut counters = self.counters.lock().unwrap();
+        f(counters.entry(format!("{}:{}", region, method)).or_default());
+    }
+
+    // Waits until we're allowed to send a request for the given method in the given region (routing value or platform).
+    pub async fn acquire(&self, region: &str, method: &str) {
+        let region = region.to_lowercase();
+        let priority = current_riot_request_priority();
+        let reserve = if priority == RiotRequestPriority::Backfill { self.config.backfill_reserve } else { 0.0 };
+        let backoff_keys = vec![
+            Self::backoff_key(&self.app_scope(&region)),
+            Self::backoff_key(&self.method_scope(&region, method)),
+        ];
+
+        let mut waited: u64 = 0;
+        loop {
+            let buckets = self.buckets(&region, method);
+            let wait = match self.store.try_acquire(&buckets, &backoff_keys, reserve).await {
+                Ok(x) => x,
+                Err(err) => {
+                    log::warn!("Failed to use Riot rate limit store, falling back to local limits: {:?}", err);
+                    self.record(&region, method, |x| { x.store_errors += 1; });
+                    // The local store never fails.
+                    self.fallback.try_acquire(&buckets, &backoff_keys, reserve).await.unwrap_or(0)
+                }
+            };
+
+            if wait == 0 {
+                break;
+            }
+
+            waited += wait;
+            tokio::time::sleep(std::time::Duration::from_millis(wait)).await;
+        }
+
+        self.record(&region, method, |x| {
+            if waited == 0 {
+                x.allowed += 1;
+            } else {
+                x.delayed += 1;
+                x.delayed_ms += waited;
+                if priority == RiotRequestPriority::Backfill {
+                    x.backfill_delayed += 1;
+                }
+            }
+        });
+    }
+
+    // Learns the current limits from Riot's response headers and backs off if we got rate limited.
+    pub async fn update_from_response(&self, region: &str, method: &str, headers: &HeaderMap, status: StatusCode) {
+        let region = region.to_lowercase();
+        let header_limits = |name: &str| {
+            headers.get(name)
+                .and_then(|x| { x.to_str().ok() })
+                .map(parse_riot_rate_limit_header)
+                .filter(|x| { !x.is_empty() })
+        };
+
+        let mut updates = 0;
+        if let Some(limits) = header_limits("X-App-Rate-Limit") {
+            let mut app_limits = self.app_limits.write().unwrap();
+            if app_limits.get(&region) != Some(&limits) {
+                app_limits.insert(region.clone(), limits);
+                updates += 1;
+            }
+        }
+
+        if let Some(limits) = header_limits("X-Method-Rate-Limit") {
+            let mut method_limits = self.method_limits.write().unwrap();
+            let key = (region.clone(), method.to_string());
+            if method_limits.get(&key) != Some(&limits) {
+                method_limits.insert(key, limits);
+                updates += 1;
+            }
+        }
+
+        if updates > 0 {
+            self.record(&region, method, |x| { x.limit_updates += updates; });
+        }
+
+        if status != StatusCode::TOO_MANY_REQUESTS {
+            return;
+        }
+
+        let retry_ms = headers.get("Retry-After")
+            .and_then(|x| { x.to_str().ok() })
+            .and_then(|x| { x.trim().parse::<u64>().ok() })
+            .map(|x| { x * 1000 })
+            .unwrap_or(self.config.default_backoff_ms);
+
+        // Service limits (or no type at all) come from the endpoint itself so only that method needs to back off.
+        let limit_type = headers.get("X-Rate-Limit-Type").and_then(|x| { x.to_str().ok() }).unwrap_or("service");
+        let scope = if limit_type.eq_ignore_ascii_case("application") {
+            self.app_scope(&region)
+        } else {
+            self.method_scope(&region, method)
+        };
+
+        log::warn!("Riot API rate limited [{} {}] ({}), backing off for {}ms", &region, method, limit_type, retry_ms);
+        self.record(&region, method, |x| { x.rate_limited += 1; });
+
+        let key = Self::backoff_key(&scope);
+        if let Err(err) = self.store.back_off(&key, retry_ms).await {
+            log::warn!("Failed to store Riot rate limit backoff: {:?}", err);
+            self.record(&region, method, |x| { x.store_errors += 1; });
+        }
+        // Always keep track locally too in case the store goes down while we're backing off.
+        let _ = self.fallback.back_off(&key, retry_ms).await;
+    }
+
+    pub fn snapshot(&self) -> RiotRateLimitSnapshot {
+        RiotRateLimitSnapshot{
+            app_limits: self.app_limits.read().unwrap().iter().map(|(k, v)| { (k.clone(), v.clone()) }).collect(),
+            method_limits: self.method_limits.read().unwrap().iter().map(|((r, m), v)| { (format!("{}:{}", r, m), v.clone()) }).collect(),
+            counters: self.counters.lock().unwrap().iter().map(|(k, v)| { (k.clone(), v.clone()) }).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_parse_rate_limit_header() {
+        assert_eq!(parse_riot_rate_limit_header("20:1,100:120"), vec![
+            RiotRateLimit{requests: 20, seconds: 1},
+            RiotRateLimit{requests: 100, seconds: 120},
+        ]);
+        assert_eq!(parse_riot_rate_limit_header(" 500:10 , bad, 0:5"), vec![
+            RiotRateLimit{requests: 500, seconds: 10},
+        ]);
+        assert!(parse_riot_rate_limit_header("").is_empty());
+    }
+
+    #[test]
+    fn test_bucket_never_exceeds_limit() {
+        let limit = RiotRateLimit{requests: 100, seconds: 10};
+        let bucket = RiotRateLimitBucket::new(String::from("test"), &limit, 0.1);
+        assert_eq!(bucket.capacity, 10.0);
+        // A full burst plus a whole window of refills is exactly the limit.
+        assert!((bucket.capacity + bucket.refill_rate * 10000.0 - 100.0).abs() < 1e-6);
+        assert_eq!(bucket.wait_ms(0.5, 0.0), 56);
+        assert_eq!(bucket.wait_ms(1.0, 0.0), 0);
+        // Backfills need to leave 20% of the bucket for everything else.
+        assert_eq!(bucket.wait_ms(2.5, 0.2), 56);
+        assert_eq!(bucket.wait_ms(3.0, 0.2), 0);
+    }
+
+    #[test]
+    fn test_local_store() {
+        let store = LocalRiotRateLimitStore::default();
+        let limit = RiotRateLimit{requests: 20, seconds: 1};
+        let buckets = vec![RiotRateLimitBucket::new(String::from("app"), &limit, 0.1)];
+        assert_eq!(store.try_acquire_at(&buckets, &[], 0.0, 0), 0);
+        assert_eq!(store.try_acquire_at(&buckets, &[], 0.0, 0), 0);
+        // Burst of 2 is used up, refills at 18 per second.
+        assert_eq!(store.try_acquire_at(&buckets, &[], 0.0, 0), 56);
+        assert_eq!(store.try_acquire_at(&buckets, &[], 0.0, 56), 0);
+    }
+
+    #[tokio::test]
+    async fn test_learn_limits_and_priority() {
+        let limiter = RiotRateLimiter::new("test", &[], RiotRateLimiterConfig::default(), Arc::new(LocalRiotRateLimitStore::default()));
+        let mut headers = HeaderMap::new();
+        headers.insert("X-App-Rate-Limit", HeaderValue::from_static("20:1,100:120"));
+        headers.insert("X-Method-Rate-Limit", HeaderValue::from_static("2000:10"));
+        limiter.update_from_response("NA1", "match-v5.getMatch", &headers, StatusCode::OK).await;
+        limiter.update_from_response("NA1", "match-v5.getMatch", &headers, StatusCode::OK).await;
+
+        let snapshot = limiter.snapshot();
+        assert_eq!(snapshot.app_limits.get("na1").unwrap().len(), 2);
+        assert_eq!(snapshot.method_limits.get("na1:match-v5.getMatch").unwrap(), &vec![RiotRateLimit{requests: 2000, seconds: 10}]);
+        assert_eq!(snapshot.counters.get("na1:match-v5.getMatch").unwrap().limit_updates, 2);
+        assert_eq!(limiter.buckets("na1", "match-v5.getMatch").len(), 3);
+
+        assert_eq!(current_riot_request_priority(), RiotRequestPriority::UserFacing);
+        assert_eq!(with_riot_request_priority(RiotRequestPriority::Backfill, async { current_riot_request_priority() }).await, RiotRequestPriority::Backfill);
+    }
+}
@@ -2,23 +2,17 @@ use crate::{
     SquadOvError,
     riot::{RiotSummoner, RiotSummonerDto}
 };
-use reqwest::{StatusCode};
+use super::RiotApiCacheCategory;
 
 impl super::RiotApiHandler {
     pub async fn get_tft_summoner_from_puuid(&self, puuid: &str, platform: &str) -> Result<RiotSummoner, SquadOvError> {
-        let client = self.create_http_client()?;
-        let endpoint = Self::build_api_endpoint(platform, &format!("tft/summoner/v1/summoners/by-puuid/{}", puuid));
-        self.tick_thresholds().await?;
-
-        let resp = client.get(&endpoint)
-            .send()
-            .await?;
-
-        if resp.status() != StatusCode::OK {
-            return Err(SquadOvError::InternalError(format!("Failed to obtain TFT summoner acount by PUUID {} - {}", resp.status().as_u16(), resp.text().await?)));
-        }
-
-        let summoner = resp.json::<RiotSummonerDto>().await?;
+        let summoner: RiotSummonerDto = self.get_riot_api_json(
+            platform,
+            "tft-summoner-v1.getByPUUID",
+            &format!("tft/summoner/v1/summoners/by-puuid/{}", puuid),
+            RiotApiCacheCategory::Account,
+            "Failed to obtain TFT summoner acount by PUUID",
+        ).await?;
         Ok(RiotSummoner{
             puuid: summoner.puuid,
             account_id: Some(summoner.account_id),
@@ -8,34 +8,28 @@ use crate::{
         },
     }
 };
-use super::RiotApiTask;
+use super::{RiotApiTask, RiotApiCacheCategory};
 use chrono::{Utc, Duration};
 
 impl super::RiotApiHandler {
     pub async fn get_tft_matches_for_user(&self, puuid: &str, region: &str, count: i32) -> Result<Vec<String>, SquadOvError> {
-        let client = self.create_http_client()?;
-        let endpoint = Self::build_api_endpoint(&super::riot_region_to_routing_no_oce(region)?, &format!("tft/match/v1/matches/by-puuid/{}/ids?count={}", puuid, count));
-        self.tick_thresholds().await?;
-
-        let resp = client.get(&endpoint)
-            .send()
-            .await?;
-
-        let resp = self.check_for_response_error(resp, "Failed to obtain TFT matches for user").await?;
-        Ok(resp.json::<Vec<String>>().await?)
+        self.get_riot_api_json(
+            &super::riot_region_to_routing_no_oce(region)?,
+            "tft-match-v1.getMatchIdsByPUUID",
+            &format!("tft/match/v1/matches/by-puuid/{}/ids?count={}", puuid, count),
+            RiotApiCacheCategory::Matchlist,
+            "Failed to obtain TFT matches for user",
+        ).await
     }
 
     pub async fn get_tft_match(&self, region: &str, match_id: &str) -> Result<TftMatchDto, SquadOvError> {
-        let client = self.create_http_client()?;
-        let endpoint = Self::build_api_endpoint(&super::riot_region_to_routing_no_oce(region)?, &format!("tft/match/v1/matches/{}", match_id));
-        self.tick_thresholds().await?;
-
-        let resp = client.get(&endpoint)
-            .send()
-            .await?;
-
-        let resp = self.check_for_response_error(resp, "Failed to obtain TFT match").await?;
-        Ok(resp.json::<TftMatchDto>().await?)
+        self.get_riot_api_json(
+            &super::riot_region_to_routing_no_oce(region)?,
+            "tft-match-v1.getMatch",
+            &format!("tft/match/v1/matches/{}", match_id),
+            RiotApiCacheCategory::Match,
+            "Failed to obtain TFT match",
+        ).await
     }
 }
 
@@ -7,9 +7,10 @@ use crate::{
             ValorantMatchlistDto,
             ValorantMatchDto,
         }
-    }
+    },
+    teammates::db as tdb,
 };
-use super::RiotApiTask;
+use super::{RiotApiTask, RiotApiCacheCategory};
 use crate::riot::db;
 use uuid::Uuid;
 
@@ -19,31 +20,25 @@ impl super::RiotApiHandler {
     pub async fn get_valorant_matches_for_user(&self, puuid: &str, shard: &str) -> Result<ValorantMatchlistDto, SquadOvError> {
         self.check_region_status("val", shard, false).await?;
 
-        let client = self.create_http_client()?;
-        let endpoint = Self::build_api_endpoint(shard, &format!("val/match/v1/matchlists/by-puuid/{}", puuid));
-        self.tick_thresholds().await?;
-
-        let resp = client.get(&endpoint)
-            .send()
-            .await?;
-
-        let resp = self.check_for_response_error(resp, "Failed to obtain Valorant matches for user").await?;
-        Ok(resp.json::<ValorantMatchlistDto>().await?)
+        self.get_riot_api_json(
+            shard,
+            "val-match-v1.getMatchlist",
+            &format!("val/match/v1/matchlists/by-puuid/{}", puuid),
+            RiotApiCacheCategory::Matchlist,
+            "Failed to obtain Valorant matches for user",
+        ).await
     }
 
     pub async fn get_valorant_match(&self, match_id: &str, shard: &str, allow_failover: bool) -> Result<ValorantMatchDto, SquadOvError> {
         self.check_region_status("val", shard, allow_failover).await?;
 
-        let client = self.create_http_client()?;
-        let endpoint = Self::build_api_endpoint(shard, &format!("val/match/v1/matches/{}", match_id));
-        self.tick_thresholds().await?;
-
-        let resp = client.get(&endpoint)
-            .send()
-            .await?;
-
-        let resp = self.check_for_response_error(resp, "Failed to obtain Valorant match").await?;
-        Ok(resp.json::<ValorantMatchDto>().await?)
+        self.get_riot_api_json(
+            shard,
+            "val-match-v1.getMatch",
+            &format!("val/match/v1/matches/{}", match_id),
+            RiotApiCacheCategory::Match,
+            "Failed to obtain Valorant match",
+        ).await
     }
 }
 
@@ -125,6 +120,7 @@ impl super::RiotApiApplicationInterface {
                 db::cache_valorant_player_pov_information(&mut tx, &match_uuid, user_id).await?;
             }
 
+            tdb::queue_teammate_roster_sync(&mut tx, &match_uuid).await?;
             tx.commit().await?;
             self.es_itf.request_sync_match(match_uuid, None).await?;
             break;
@@ -146,6 +142,28 @@ impl super::RiotApiApplicationInterface {
         Ok(())
     }
 
+    // Matches that were stored before we kept track of ability usage need to be pulled from the API again
+    // since we never stored the round player stats' ability info.
+    pub async fn backfill_valorant_match_ability_casts(&self, match_uuid: &Uuid) -> Result<(), SquadOvError> {
+        let match_id = db::get_valorant_match_id(&*self.db, match_uuid).await?;
+        let shard = db::get_valorant_match_shard(&*self.db, match_uuid).await?;
+        log::info!("Backfilling Valorant Ability Casts: {} [{}] - {}", &match_id, &shard, match_uuid);
+
+        let valorant_match = self.api.get_valorant_match(&match_id, &shard, false).await?;
+        let mut tx = self.db.begin().await?;
+        db::store_valorant_match_round_ability_casts(&mut tx, match_uuid, &valorant_match).await?;
+        db::finish_valorant_match_ability_backfill(&mut tx, match_uuid).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn request_valorant_match_ability_backfill(&self, match_uuid: &Uuid) -> Result<(), SquadOvError> {
+        self.rmq.publish(&self.mqconfig.valorant_queue, serde_json::to_vec(&RiotApiTask::ValorantMatchAbilityBackfill{
+            match_uuid: match_uuid.clone(),
+        })?, RABBITMQ_DEFAULT_PRIORITY, RIOT_MAX_AGE_SECONDS).await;
+        Ok(())
+    }
+
     pub async fn request_valorant_match_player_cache_data(&self, match_uuid: &Uuid, user_id: i64) -> Result<(), SquadOvError> {
         self.rmq.publish(&self.mqconfig.misc_valorant_queue, serde_json::to_vec(&RiotApiTask::ValorantMatchPlayerCacheData{
             match_uuid: match_uuid.clone(),
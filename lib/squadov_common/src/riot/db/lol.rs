@@ -1,10 +1,14 @@
 mod create;
 mod list;
 mod get;
+mod stats;
+mod timeline;
 
 pub use create::*;
 pub use list::*;
 pub use get::*;
+pub use stats::*;
+pub use timeline::*;
 
 use crate::{
     SquadOvError,
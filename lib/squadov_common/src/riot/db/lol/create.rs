@@ -578,7 +578,7 @@ async fn store_lol_match_timeline_participant_frames<'a>(ex: &mut Transaction<'_
                 current_gold=f.base.current_gold,
                 jungle_minions_killed=f.base.jungle_minions_killed,
                 x=crate::sql_format_option_value(&f.base.position.as_ref().and_then(|x| { Some(x.x) })),
-                y=crate::sql_format_option_value(&f.base.position.as_ref().and_then(|x| { Some(x.x) })),
+                y=crate::sql_format_option_value(&f.base.position.as_ref().and_then(|x| { Some(x.y) })),
             )
         );
         sql.push(",".to_string());
@@ -729,4 +729,46 @@ pub async fn store_lol_match_timeline_info(ex: &mut Transaction<'_, Postgres>, m
     store_lol_match_timeline_participant_frames(&mut *ex, match_uuid, &frames).await?;
     store_lol_match_timeline_events(&mut *ex, match_uuid, &events).await?;
     Ok(())
-}
\ No newline at end of file
+}
+// Only meant for matches in lol_match_timeline_position_repair whose frames were stored with y = x.
+pub async fn repair_lol_match_timeline_frame_positions(ex: &mut Transaction<'_, Postgres>, match_uuid: &Uuid, timeline: &LolMatchTimelineInfoDto) -> Result<(), SquadOvError> {
+    let mut timestamps: Vec<i64> = Vec::new();
+    let mut participant_ids: Vec<i32> = Vec::new();
+    let mut xs: Vec<i32> = Vec::new();
+    let mut ys: Vec<i32> = Vec::new();
+
+    for f in &timeline.frames {
+        for (_, p) in &f.participant_frames {
+            if let Some(pos) = p.position.as_ref() {
+                timestamps.push(f.timestamp);
+                participant_ids.push(p.participant_id);
+                xs.push(pos.x);
+                ys.push(pos.y);
+            }
+        }
+    }
+
+    if timestamps.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        "
+        UPDATE squadov.lol_match_timeline_participant_frames AS pf
+        SET x = t.x,
+            y = t.y
+        FROM UNNEST($2::BIGINT[], $3::INTEGER[], $4::INTEGER[], $5::INTEGER[]) AS t(timestamp, participant_id, x, y)
+        WHERE pf.match_uuid = $1
+            AND pf.timestamp = t.timestamp
+            AND pf.participant_id = t.participant_id
+        ",
+        match_uuid,
+        &timestamps,
+        &participant_ids,
+        &xs,
+        &ys,
+    )
+        .execute(&mut *ex)
+        .await?;
+    Ok(())
+}
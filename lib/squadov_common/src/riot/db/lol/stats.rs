@@ -0,0 +1,66 @@
+use crate::{
+    SquadOvError,
+    riot::stats::lol::{
+        LolStatsFilters,
+        LolPlayerMatchStatsRow,
+    },
+};
+use sqlx::{Executor, Postgres};
+
+// Matches are ordered from newest to oldest before the [start, end) page is taken.
+pub async fn get_lol_player_match_stats_rows<'a, T>(ex: T, puuid: &str, start: i64, end: i64, filters: &LolStatsFilters) -> Result<Vec<LolPlayerMatchStatsRow>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, LolPlayerMatchStatsRow>(
+            "
+            SELECT
+                lmi.match_uuid,
+                lmi.game_creation,
+                lmi.game_duration,
+                lmi.queue_id,
+                lmi.game_version,
+                lmp.champion_id,
+                lmp.lane,
+                lmp.win,
+                lmp.kills,
+                lmp.deaths,
+                lmp.assists,
+                lmp.total_minions_killed,
+                lmp.neutral_minions_kills,
+                lmp.vision_score,
+                lmp.total_damage_dealt_to_champions,
+                team.damage AS team_damage_dealt_to_champions
+            FROM squadov.lol_match_participant_identities AS lmpi
+            INNER JOIN squadov.lol_match_info AS lmi
+                ON lmi.match_uuid = lmpi.match_uuid
+            INNER JOIN squadov.lol_match_participants AS lmp
+                ON lmp.match_uuid = lmpi.match_uuid
+                    AND lmp.participant_id = lmpi.participant_id
+            CROSS JOIN LATERAL (
+                SELECT COALESCE(SUM(tm.total_damage_dealt_to_champions), 0)::BIGINT AS damage
+                FROM squadov.lol_match_participants AS tm
+                WHERE tm.match_uuid = lmp.match_uuid
+                    AND tm.team_id = lmp.team_id
+            ) AS team
+            WHERE lmpi.puuid = $1
+                AND ($2::TIMESTAMPTZ IS NULL OR lmi.game_creation >= $2)
+                AND ($3::TIMESTAMPTZ IS NULL OR lmi.game_creation <= $3)
+                AND ($4::INTEGER[] IS NULL OR lmi.queue_id = ANY($4))
+                AND ($5::VARCHAR[] IS NULL OR SPLIT_PART(lmi.game_version, '.', 1) || '.' || SPLIT_PART(lmi.game_version, '.', 2) = ANY($5))
+            ORDER BY lmi.game_creation DESC
+            LIMIT $6 OFFSET $7
+            "
+        )
+            .bind(puuid)
+            .bind(filters.start_time)
+            .bind(filters.end_time)
+            .bind(filters.queues.as_ref())
+            .bind(filters.patches.as_ref())
+            .bind(end - start)
+            .bind(start)
+            .fetch_all(ex)
+            .await?
+    )
+}
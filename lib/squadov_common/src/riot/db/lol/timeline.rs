@@ -0,0 +1,140 @@
+use crate::{
+    SquadOvError,
+    riot::games::lol::timeline::{
+        LolTimelineParticipantRow,
+        LolTimelineFrameRow,
+        LolTimelineEventRow,
+    },
+};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+pub async fn get_lol_match_timeline_participants<'a, T>(ex: T, match_uuid: &Uuid) -> Result<Vec<LolTimelineParticipantRow>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, LolTimelineParticipantRow>(
+            "
+            SELECT lmp.participant_id, lmp.team_id, lmp.lane
+            FROM squadov.lol_match_participants AS lmp
+            WHERE lmp.match_uuid = $1
+            ORDER BY lmp.participant_id
+            "
+        )
+            .bind(match_uuid)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub async fn get_lol_match_timeline_frame_rows<'a, T>(ex: T, match_uuid: &Uuid) -> Result<Vec<LolTimelineFrameRow>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, LolTimelineFrameRow>(
+            "
+            SELECT
+                pf.timestamp,
+                pf.participant_id,
+                pf.minions_killed,
+                pf.jungle_minions_killed,
+                pf.total_gold,
+                pf.xp,
+                pf.x,
+                pf.y
+            FROM squadov.lol_match_timeline_participant_frames AS pf
+            WHERE pf.match_uuid = $1
+            ORDER BY pf.timestamp, pf.participant_id
+            "
+        )
+            .bind(match_uuid)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+// Only events with one of the given types are returned.
+pub async fn get_lol_match_timeline_event_rows<'a, T>(ex: T, match_uuid: &Uuid, types: &[&str]) -> Result<Vec<LolTimelineEventRow>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, LolTimelineEventRow>(
+            "
+            SELECT
+                ev.timestamp,
+                ev.real_type,
+                ev.team_id,
+                ev.creator_id,
+                ev.killer_id,
+                ev.victim_id,
+                ev.assisting_participant_ids,
+                ev.monster_type,
+                ev.monster_sub_type,
+                ev.building_type,
+                ev.tower_type,
+                ev.lane_type,
+                ev.ward_type,
+                ev.x,
+                ev.y
+            FROM squadov.lol_match_timeline_events AS ev
+            WHERE ev.match_uuid = $1
+                AND ev.real_type = ANY($2)
+            ORDER BY ev.timestamp
+            "
+        )
+            .bind(match_uuid)
+            .bind(types)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+// Marks the returned matches as queued so they don't get picked up again while their repair is still pending. Matches that
+// have been queued for a day without finishing are assumed to have been lost and are picked up again.
+pub async fn get_lol_matches_for_position_repair<'a, T>(ex: T, limit: i64) -> Result<Vec<Uuid>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query!(
+            "
+            UPDATE squadov.lol_match_timeline_position_repair AS lmtpr
+            SET queued_tm = NOW()
+            FROM (
+                SELECT match_uuid
+                FROM squadov.lol_match_timeline_position_repair
+                WHERE queued_tm IS NULL OR queued_tm < (NOW() - INTERVAL '1 day')
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            ) AS sub
+            WHERE sub.match_uuid = lmtpr.match_uuid
+            RETURNING lmtpr.match_uuid
+            ",
+            limit,
+        )
+            .fetch_all(ex)
+            .await?
+            .into_iter()
+            .map(|x| { x.match_uuid })
+            .collect()
+    )
+}
+
+pub async fn finish_lol_match_position_repair<'a, T>(ex: T, match_uuid: &Uuid) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query!(
+        "
+        DELETE FROM squadov.lol_match_timeline_position_repair
+        WHERE match_uuid = $1
+        ",
+        match_uuid,
+    )
+        .execute(ex)
+        .await?;
+    Ok(())
+}
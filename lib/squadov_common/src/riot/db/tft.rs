@@ -1,10 +1,12 @@
 mod create;
 mod list;
 mod get;
+mod stats;
 
 pub use create::*;
 pub use list::*;
 pub use get::*;
+pub use stats::*;
 
 use crate::{
     SquadOvError,
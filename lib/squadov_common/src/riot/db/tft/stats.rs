@@ -0,0 +1,93 @@
+use crate::{
+    SquadOvError,
+    riot::stats::tft::{
+        TftStatsFilters,
+        TftPlayerPlacementRow,
+        TftPlayerTraitRow,
+        TftPlayerUnitRow,
+    },
+};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+// Matches are ordered from newest to oldest before the [start, end) page is taken.
+pub async fn get_tft_player_placement_rows<'a, T>(ex: T, puuid: &str, start: i64, end: i64, filters: &TftStatsFilters) -> Result<Vec<TftPlayerPlacementRow>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, TftPlayerPlacementRow>(
+            "
+            SELECT
+                tmp.match_uuid,
+                tmi.game_datetime,
+                tmp.placement,
+                tmp.companion_species
+            FROM squadov.tft_match_participants AS tmp
+            INNER JOIN squadov.tft_match_info AS tmi
+                ON tmi.match_uuid = tmp.match_uuid
+            WHERE tmp.puuid = $1
+                AND ($2::TIMESTAMPTZ IS NULL OR tmi.game_datetime >= $2)
+                AND ($3::TIMESTAMPTZ IS NULL OR tmi.game_datetime <= $3)
+                AND ($4::INTEGER[] IS NULL OR tmi.queue_id = ANY($4))
+            ORDER BY tmi.game_datetime DESC
+            LIMIT $5 OFFSET $6
+            "
+        )
+            .bind(puuid)
+            .bind(filters.start_time)
+            .bind(filters.end_time)
+            .bind(filters.queues.as_ref())
+            .bind(end - start)
+            .bind(start)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub async fn get_tft_player_active_trait_rows<'a, T>(ex: T, puuid: &str, match_uuids: &[Uuid]) -> Result<Vec<TftPlayerTraitRow>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, TftPlayerTraitRow>(
+            "
+            SELECT
+                tmpt.match_uuid,
+                tmpt.name
+            FROM squadov.tft_match_participant_traits AS tmpt
+            WHERE tmpt.puuid = $1
+                AND tmpt.match_uuid = ANY($2)
+                AND tmpt.tier_current > 0
+            "
+        )
+            .bind(puuid)
+            .bind(match_uuids)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub async fn get_tft_player_unit_rows<'a, T>(ex: T, puuid: &str, match_uuids: &[Uuid]) -> Result<Vec<TftPlayerUnitRow>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, TftPlayerUnitRow>(
+            "
+            SELECT
+                tmpu.match_uuid,
+                tmpu.character_id,
+                tmpu.name,
+                tmpu.items
+            FROM squadov.tft_match_participant_units AS tmpu
+            WHERE tmpu.puuid = $1
+                AND tmpu.match_uuid = ANY($2)
+            "
+        )
+            .bind(puuid)
+            .bind(match_uuids)
+            .fetch_all(ex)
+            .await?
+    )
+}
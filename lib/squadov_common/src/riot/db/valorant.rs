@@ -1,3 +1,4 @@
+mod analytics;
 mod create;
 mod get;
 mod list;
@@ -6,6 +7,7 @@ use crate::{
     SquadOvError,
 };
 
+pub use analytics::*;
 pub use create::*;
 pub use get::*;
 pub use list::*;
@@ -0,0 +1,208 @@
+use crate::{
+    SquadOvError,
+    riot::games::valorant::analytics::{
+        ValorantEconomyPlayerRound,
+        ValorantRoundFirstKill,
+        ValorantPlayerAbilityCasts,
+    },
+};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+// Each of these queries is filtered on either a single match or every match the given puuid played in.
+fn match_filter(alias: &str) -> String {
+    format!("
+        WHERE ($1::UUID IS NULL OR {alias}.match_uuid = $1)
+            AND ($2::VARCHAR IS NULL OR {alias}.match_uuid IN (
+                SELECT pm.match_uuid
+                FROM squadov.valorant_match_players AS pm
+                WHERE pm.puuid = $2
+            ))
+    ",
+        alias=alias,
+    )
+}
+
+async fn get_valorant_economy_rows<'a, T>(ex: T, match_uuid: Option<&Uuid>, puuid: Option<&str>) -> Result<Vec<ValorantEconomyPlayerRound>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, ValorantEconomyPlayerRound>(&format!("
+            SELECT
+                vmrpl.match_uuid,
+                vmrpl.round_num,
+                vmrpl.puuid,
+                vmp.team_id,
+                vmp.character_id,
+                vmrpl.loadout_value,
+                vmrpl.spent_money,
+                vmrpl.remaining_money,
+                vmr.team_round_winner
+            FROM squadov.valorant_match_round_player_loadout AS vmrpl
+            INNER JOIN squadov.valorant_match_players AS vmp
+                ON vmp.match_uuid = vmrpl.match_uuid
+                    AND vmp.puuid = vmrpl.puuid
+            INNER JOIN squadov.valorant_match_rounds AS vmr
+                ON vmr.match_uuid = vmrpl.match_uuid
+                    AND vmr.round_num = vmrpl.round_num
+            {filter}
+            ",
+            filter=match_filter("vmrpl"),
+        ))
+            .bind(match_uuid)
+            .bind(puuid)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+async fn get_valorant_first_kills<'a, T>(ex: T, match_uuid: Option<&Uuid>, puuid: Option<&str>) -> Result<Vec<ValorantRoundFirstKill>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, ValorantRoundFirstKill>(&format!("
+            SELECT DISTINCT ON (vmk.match_uuid, vmk.round_num)
+                vmk.match_uuid,
+                vmk.round_num,
+                vmk.killer_puuid,
+                vmk.victim_puuid,
+                vmk.time_since_round_start_millis
+            FROM squadov.valorant_match_kill AS vmk
+            {filter}
+            ORDER BY vmk.match_uuid, vmk.round_num, vmk.time_since_round_start_millis ASC
+            ",
+            filter=match_filter("vmk"),
+        ))
+            .bind(match_uuid)
+            .bind(puuid)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+// Rounds that we don't know the ability usage of (i.e. matches that haven't been backfilled yet) are left out entirely
+// so they don't count against the per round averages.
+async fn get_valorant_ability_casts<'a, T>(ex: T, match_uuid: Option<&Uuid>, puuid: Option<&str>) -> Result<Vec<ValorantPlayerAbilityCasts>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, ValorantPlayerAbilityCasts>(&format!("
+            SELECT
+                vmrps.match_uuid,
+                vmrps.puuid,
+                vmp.character_id,
+                COUNT(*)::INTEGER AS rounds_played,
+                SUM(vmrps.grenade_casts)::INTEGER AS grenade_casts,
+                SUM(vmrps.ability1_casts)::INTEGER AS ability1_casts,
+                SUM(vmrps.ability2_casts)::INTEGER AS ability2_casts,
+                SUM(vmrps.ultimate_casts)::INTEGER AS ultimate_casts
+            FROM squadov.valorant_match_round_player_stats AS vmrps
+            INNER JOIN squadov.valorant_match_players AS vmp
+                ON vmp.match_uuid = vmrps.match_uuid
+                    AND vmp.puuid = vmrps.puuid
+            {filter}
+                AND ($2::VARCHAR IS NULL OR vmrps.puuid = $2)
+                AND vmrps.grenade_casts IS NOT NULL
+            GROUP BY vmrps.match_uuid, vmrps.puuid, vmp.character_id
+            ",
+            filter=match_filter("vmrps"),
+        ))
+            .bind(match_uuid)
+            .bind(puuid)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub async fn get_valorant_match_economy_rows<'a, T>(ex: T, match_uuid: &Uuid) -> Result<Vec<ValorantEconomyPlayerRound>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    get_valorant_economy_rows(ex, Some(match_uuid), None).await
+}
+
+pub async fn get_valorant_match_first_kills<'a, T>(ex: T, match_uuid: &Uuid) -> Result<Vec<ValorantRoundFirstKill>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    get_valorant_first_kills(ex, Some(match_uuid), None).await
+}
+
+pub async fn get_valorant_match_ability_casts<'a, T>(ex: T, match_uuid: &Uuid) -> Result<Vec<ValorantPlayerAbilityCasts>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    get_valorant_ability_casts(ex, Some(match_uuid), None).await
+}
+
+// Includes every player in every match the puuid played in.
+pub async fn get_valorant_player_economy_rows<'a, T>(ex: T, puuid: &str) -> Result<Vec<ValorantEconomyPlayerRound>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    get_valorant_economy_rows(ex, None, Some(puuid)).await
+}
+
+pub async fn get_valorant_player_first_kills<'a, T>(ex: T, puuid: &str) -> Result<Vec<ValorantRoundFirstKill>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    get_valorant_first_kills(ex, None, Some(puuid)).await
+}
+
+pub async fn get_valorant_player_ability_casts<'a, T>(ex: T, puuid: &str) -> Result<Vec<ValorantPlayerAbilityCasts>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    get_valorant_ability_casts(ex, None, Some(puuid)).await
+}
+
+// Marks the returned matches as queued so they don't get picked up again while their backfill is still pending. Matches that
+// have been queued for a day without finishing are assumed to have been lost and are picked up again.
+pub async fn get_valorant_matches_for_ability_backfill<'a, T>(ex: T, limit: i64) -> Result<Vec<Uuid>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query!(
+            "
+            UPDATE squadov.valorant_match_ability_backfill AS vmab
+            SET queued_tm = NOW()
+            FROM (
+                SELECT match_uuid
+                FROM squadov.valorant_match_ability_backfill
+                WHERE queued_tm IS NULL OR queued_tm < (NOW() - INTERVAL '1 day')
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            ) AS sub
+            WHERE sub.match_uuid = vmab.match_uuid
+            RETURNING vmab.match_uuid
+            ",
+            limit,
+        )
+            .fetch_all(ex)
+            .await?
+            .into_iter()
+            .map(|x| { x.match_uuid })
+            .collect()
+    )
+}
+
+pub async fn finish_valorant_match_ability_backfill<'a, T>(ex: T, match_uuid: &Uuid) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query!(
+        "
+        DELETE FROM squadov.valorant_match_ability_backfill
+        WHERE match_uuid = $1
+        ",
+        match_uuid,
+    )
+        .execute(ex)
+        .await?;
+    Ok(())
+}
@@ -11,6 +11,7 @@ use crate::{
         FlatValorantMatchDamageDto,
         FlatValorantMatchEconomyDto,
         FlatValorantMatchPlayerRoundStatsDto,
+        ValorantMatchPlayerRoundAbilityDto,
         ValorantMatchFilterEvents,
     },
     matches
@@ -258,22 +259,38 @@ async fn store_valorant_match_flat_valorant_match_player_round_stats_dto(ex: &mu
             match_uuid,
             round_num,
             puuid,
-            combat_score
+            combat_score,
+            grenade_casts,
+            ability1_casts,
+            ability2_casts,
+            ultimate_casts
         )
         VALUES
     "));
 
     for st in stats {
+        let casts = |f: fn(&ValorantMatchPlayerRoundAbilityDto) -> i32| -> String {
+            st.ability.as_ref().map(|x| { f(x).to_string() }).unwrap_or(String::from("NULL"))
+        };
+
         sql.push(format!("(
             '{match_uuid}',
             {round_num},
             '{puuid}',
-            {combat_score}
+            {combat_score},
+            {grenade_casts},
+            {ability1_casts},
+            {ability2_casts},
+            {ultimate_casts}
         )",
             match_uuid=match_uuid,
             round_num=st.round_num,
             puuid=&st.puuid,
-            combat_score=st.score
+            combat_score=st.score,
+            grenade_casts=casts(ValorantMatchPlayerRoundAbilityDto::grenade_casts),
+            ability1_casts=casts(ValorantMatchPlayerRoundAbilityDto::ability1_casts),
+            ability2_casts=casts(ValorantMatchPlayerRoundAbilityDto::ability2_casts),
+            ultimate_casts=casts(ValorantMatchPlayerRoundAbilityDto::ultimate_casts),
         ));
 
         sql.push(String::from(","));
@@ -544,6 +561,58 @@ where
     )
 }
 
+// Used to fill in the ability usage of matches that were stored before we kept track of it. Everything else
+// about the match is already stored so only the round player stats get touched.
+pub async fn store_valorant_match_round_ability_casts(ex: &mut Transaction<'_, Postgres>, match_uuid: &Uuid, valorant_match: &ValorantMatchDto) -> Result<(), SquadOvError> {
+    let mut round_nums: Vec<i32> = Vec::new();
+    let mut puuids: Vec<String> = Vec::new();
+    let mut grenade_casts: Vec<i32> = Vec::new();
+    let mut ability1_casts: Vec<i32> = Vec::new();
+    let mut ability2_casts: Vec<i32> = Vec::new();
+    let mut ultimate_casts: Vec<i32> = Vec::new();
+
+    for r in &valorant_match.round_results {
+        for p in &r.player_stats {
+            if let Some(ability) = p.ability.as_ref() {
+                round_nums.push(r.round_num);
+                puuids.push(p.puuid.clone());
+                grenade_casts.push(ability.grenade_casts());
+                ability1_casts.push(ability.ability1_casts());
+                ability2_casts.push(ability.ability2_casts());
+                ultimate_casts.push(ability.ultimate_casts());
+            }
+        }
+    }
+
+    if puuids.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        "
+        UPDATE squadov.valorant_match_round_player_stats AS vmrps
+        SET grenade_casts = t.grenade_casts,
+            ability1_casts = t.ability1_casts,
+            ability2_casts = t.ability2_casts,
+            ultimate_casts = t.ultimate_casts
+        FROM UNNEST($2::INTEGER[], $3::VARCHAR[], $4::INTEGER[], $5::INTEGER[], $6::INTEGER[], $7::INTEGER[]) AS t(round_num, puuid, grenade_casts, ability1_casts, ability2_casts, ultimate_casts)
+        WHERE vmrps.match_uuid = $1
+            AND vmrps.round_num = t.round_num
+            AND vmrps.puuid = t.puuid
+        ",
+        match_uuid,
+        &round_nums,
+        &puuids,
+        &grenade_casts,
+        &ability1_casts,
+        &ability2_casts,
+        &ultimate_casts,
+    )
+        .execute(&mut *ex)
+        .await?;
+    Ok(())
+}
+
 pub async fn cache_valorant_match_information(ex: &mut Transaction<'_, Postgres>, match_uuid: &Uuid) -> Result<(), SquadOvError> {
     // We need to get which agents are on what team and create a regex-searchable string representation.
     // Note that in the case where we're playing deathmatch where there's no teams (and everyone's team id is equivalent to their puuid),
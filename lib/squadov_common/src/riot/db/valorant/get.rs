@@ -42,6 +42,25 @@ where
     )
 }
 
+pub async fn get_valorant_match_id<'a, T>(ex: T, match_uuid: &Uuid) -> Result<String, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query!(
+            "
+            SELECT match_id
+            FROM squadov.valorant_match_uuid_link
+            WHERE match_uuid = $1
+            ",
+            match_uuid,
+        )
+            .fetch_one(ex)
+            .await?
+            .match_id
+    )
+}
+
 pub async fn get_valorant_match_info_dto<'a, T>(ex: T, match_uuid: &Uuid) -> Result<ValorantMatchInfoDto, SquadOvError>
 where
     T: Executor<'a, Database = Postgres>
@@ -277,6 +296,8 @@ async fn get_valorant_match_player_round_stats(ex: &PgPool, match_uuid: &Uuid) -
                     round_num: x.round_num,
                     puuid: x.puuid,
                     score: x.combat_score,
+                    // We only store how often the abilities were used, not the effects Riot gave us.
+                    ability: None,
                 }
             })
             .collect()
@@ -327,6 +348,7 @@ async fn get_valorant_match_round_results(ex: &PgPool, match_uuid: &Uuid) -> Res
                 damage: rdamage.iter().filter(|y| { y.instigator == x.puuid }).map(|y| { y.base.clone() }).collect(),
                 economy: recon.iter().filter(|y| { y.puuid == x.puuid }).next().ok_or(SquadOvError::NotFound)?.base.clone(),
                 score: x.score,
+                ability: x.ability.clone(),
             })
         }).collect::<Result<Vec<ValorantMatchPlayerRoundStatsDto>, SquadOvError>>()?);
     }
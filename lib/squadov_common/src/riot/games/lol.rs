@@ -1,3 +1,5 @@
+pub mod timeline;
+
 use uuid::Uuid;
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, Utc};
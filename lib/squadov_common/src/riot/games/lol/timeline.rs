@@ -0,0 +1,557 @@
+use serde::Serialize;
+use serde_repr::Serialize_repr;
+use std::collections::{BTreeMap, HashMap};
+
+pub const LOL_BLUE_TEAM_ID: i32 = 100;
+pub const LOL_RED_TEAM_ID: i32 = 200;
+
+// Summoner's Rift coordinates go from 0 to a bit under 15000 on both axes.
+const LOL_MAP_SIZE: i32 = 15000;
+
+// How far away (in milliseconds) a frame can be from the time we're looking for before we consider it missing.
+const LOL_FRAME_TOLERANCE_MS: i64 = 30000;
+
+pub const LOL_DEFAULT_WARD_GRID_SIZE: i32 = 10;
+pub const LOL_MAX_WARD_GRID_SIZE: i32 = 50;
+
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct LolTimelineParticipantRow {
+    pub participant_id: i32,
+    pub team_id: i32,
+    pub lane: String,
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Default)]
+pub struct LolTimelineFrameRow {
+    pub timestamp: i64,
+    pub participant_id: i32,
+    pub minions_killed: i32,
+    pub jungle_minions_killed: i32,
+    pub total_gold: i32,
+    pub xp: i32,
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+}
+
+impl LolTimelineFrameRow {
+    fn cs(&self) -> i32 {
+        self.minions_killed + self.jungle_minions_killed
+    }
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Default)]
+pub struct LolTimelineEventRow {
+    pub timestamp: i64,
+    pub real_type: String,
+    pub team_id: Option<i32>,
+    pub creator_id: Option<i32>,
+    pub killer_id: Option<i32>,
+    pub victim_id: Option<i32>,
+    pub assisting_participant_ids: Option<Vec<i32>>,
+    pub monster_type: Option<String>,
+    pub monster_sub_type: Option<String>,
+    pub building_type: Option<String>,
+    pub tower_type: Option<String>,
+    pub lane_type: Option<String>,
+    pub ward_type: Option<String>,
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+}
+
+fn participant_teams(participants: &[LolTimelineParticipantRow]) -> HashMap<i32, i32> {
+    participants.iter().map(|x| { (x.participant_id, x.team_id) }).collect()
+}
+
+// Frame rows keyed by timestamp and then by participant.
+fn frames_by_timestamp(frames: &[LolTimelineFrameRow]) -> BTreeMap<i64, HashMap<i32, &LolTimelineFrameRow>> {
+    let mut ret: BTreeMap<i64, HashMap<i32, &LolTimelineFrameRow>> = BTreeMap::new();
+    for f in frames {
+        ret.entry(f.timestamp).or_default().insert(f.participant_id, f);
+    }
+    ret
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct LolTeamDiffPoint {
+    pub timestamp: i64,
+    // Both are blue side minus red side.
+    pub gold_diff: i64,
+    pub xp_diff: i64,
+}
+
+pub fn compute_lol_team_diffs(participants: &[LolTimelineParticipantRow], frames: &[LolTimelineFrameRow]) -> Vec<LolTeamDiffPoint> {
+    let teams = participant_teams(participants);
+    frames_by_timestamp(frames).into_iter().map(|(timestamp, frame)| {
+        let mut ret = LolTeamDiffPoint{
+            timestamp,
+            gold_diff: 0,
+            xp_diff: 0,
+        };
+
+        for (participant_id, f) in frame {
+            let sign = match teams.get(&participant_id) {
+                Some(&LOL_BLUE_TEAM_ID) => 1,
+                Some(&LOL_RED_TEAM_ID) => -1,
+                _ => continue,
+            };
+            ret.gold_diff += sign * f.total_gold as i64;
+            ret.xp_diff += sign * f.xp as i64;
+        }
+        ret
+    }).collect()
+}
+
+#[derive(Serialize_repr, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum LolObjectiveType {
+    Dragon,
+    Baron,
+    Herald,
+    Tower,
+    Inhibitor,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct LolObjectiveEvent {
+    pub timestamp: i64,
+    pub objective: LolObjectiveType,
+    // The dragon type for dragons and the turret type for towers.
+    pub sub_type: Option<String>,
+    pub lane: Option<String>,
+    pub killer_id: Option<i32>,
+    // The team that took the objective.
+    pub team_id: Option<i32>,
+}
+
+pub fn compute_lol_objective_timeline(participants: &[LolTimelineParticipantRow], events: &[LolTimelineEventRow]) -> Vec<LolObjectiveEvent> {
+    let teams = participant_teams(participants);
+    let mut ret: Vec<LolObjectiveEvent> = events.iter().filter_map(|e| {
+        let killer_team = e.killer_id.and_then(|x| { teams.get(&x).cloned() });
+        let (objective, sub_type, team_id) = match e.real_type.as_str() {
+            "ELITE_MONSTER_KILL" => match e.monster_type.as_deref() {
+                Some("DRAGON") => (LolObjectiveType::Dragon, e.monster_sub_type.clone(), killer_team),
+                Some("BARON_NASHOR") => (LolObjectiveType::Baron, None, killer_team),
+                Some("RIFTHERALD") => (LolObjectiveType::Herald, None, killer_team),
+                _ => return None,
+            },
+            "BUILDING_KILL" => {
+                // The team on building kills is the team that owned the building. Towers can die to minions
+                // so the killer isn't always a participant.
+                let team_id = match e.team_id {
+                    Some(LOL_BLUE_TEAM_ID) => Some(LOL_RED_TEAM_ID),
+                    Some(LOL_RED_TEAM_ID) => Some(LOL_BLUE_TEAM_ID),
+                    _ => killer_team,
+                };
+
+                match e.building_type.as_deref() {
+                    Some("TOWER_BUILDING") => (LolObjectiveType::Tower, e.tower_type.clone(), team_id),
+                    Some("INHIBITOR_BUILDING") => (LolObjectiveType::Inhibitor, None, team_id),
+                    _ => return None,
+                }
+            },
+            _ => return None,
+        };
+
+        Some(LolObjectiveEvent{
+            timestamp: e.timestamp,
+            objective,
+            sub_type,
+            lane: e.lane_type.clone(),
+            killer_id: e.killer_id.filter(|x| { *x > 0 }),
+            team_id,
+        })
+    }).collect();
+    ret.sort_by_key(|x| { x.timestamp });
+    ret
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all="camelCase")]
+pub struct LolLaneDiff {
+    // All relative to the lane opponent.
+    pub cs_diff: i32,
+    pub gold_diff: i32,
+    pub xp_diff: i32,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct LolLanePhaseStats {
+    pub participant_id: i32,
+    pub opponent_id: i32,
+    pub lane: String,
+    pub at10: Option<LolLaneDiff>,
+    pub at15: Option<LolLaneDiff>,
+}
+
+fn frame_near<'a>(frames: &'a BTreeMap<i64, HashMap<i32, &'a LolTimelineFrameRow>>, target: i64) -> Option<&'a HashMap<i32, &'a LolTimelineFrameRow>> {
+    frames.iter()
+        .filter(|(ts, _)| { (**ts - target).abs() <= LOL_FRAME_TOLERANCE_MS })
+        .min_by_key(|(ts, _)| { (**ts - target).abs() })
+        .map(|(_, x)| { x })
+}
+
+fn lane_diff(frame: Option<&HashMap<i32, &LolTimelineFrameRow>>, participant_id: i32, opponent_id: i32) -> Option<LolLaneDiff> {
+    let frame = frame?;
+    let me = frame.get(&participant_id)?;
+    let opp = frame.get(&opponent_id)?;
+    Some(LolLaneDiff{
+        cs_diff: me.cs() - opp.cs(),
+        gold_diff: me.total_gold - opp.total_gold,
+        xp_diff: me.xp - opp.xp,
+    })
+}
+
+// Lane opponents are the players on the other team that Riot put in the same lane. Bot lane has two players on
+// each side so we pair them up by CS at 10 minutes, which lines up carry against carry and support against support.
+pub fn compute_lol_lane_phase(participants: &[LolTimelineParticipantRow], frames: &[LolTimelineFrameRow]) -> Vec<LolLanePhaseStats> {
+    let frames = frames_by_timestamp(frames);
+    let at10 = frame_near(&frames, 10 * 60 * 1000);
+    let at15 = frame_near(&frames, 15 * 60 * 1000);
+
+    let cs_at10 = |participant_id: i32| -> i32 {
+        at10.and_then(|x| { x.get(&participant_id) }).map(|x| { x.cs() }).unwrap_or(0)
+    };
+
+    let mut lanes: BTreeMap<&str, (Vec<i32>, Vec<i32>)> = BTreeMap::new();
+    for p in participants {
+        if p.lane.is_empty() || p.lane == "NONE" {
+            continue;
+        }
+
+        let entry = lanes.entry(p.lane.as_str()).or_default();
+        match p.team_id {
+            LOL_BLUE_TEAM_ID => entry.0.push(p.participant_id),
+            LOL_RED_TEAM_ID => entry.1.push(p.participant_id),
+            _ => (),
+        }
+    }
+
+    let mut ret: Vec<LolLanePhaseStats> = Vec::new();
+    for (lane, (mut blue, mut red)) in lanes {
+        blue.sort_by_key(|x| { (-cs_at10(*x), *x) });
+        red.sort_by_key(|x| { (-cs_at10(*x), *x) });
+
+        for (b, r) in blue.into_iter().zip(red.into_iter()) {
+            for (participant_id, opponent_id) in [(b, r), (r, b)] {
+                ret.push(LolLanePhaseStats{
+                    participant_id,
+                    opponent_id,
+                    lane: lane.to_string(),
+                    at10: lane_diff(at10, participant_id, opponent_id),
+                    at15: lane_diff(at15, participant_id, opponent_id),
+                });
+            }
+        }
+    }
+    ret.sort_by_key(|x| { x.participant_id });
+    ret
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all="camelCase")]
+pub struct LolKillParticipationTimeline {
+    // One entry per frame. Each participant's series lines up with these timestamps.
+    pub timestamps: Vec<i64>,
+    // Kills + assists over the team's kills up until that point. Missing until the team gets a kill.
+    pub participants: BTreeMap<i32, Vec<Option<f64>>>,
+}
+
+pub fn compute_lol_kill_participation(participants: &[LolTimelineParticipantRow], frames: &[LolTimelineFrameRow], events: &[LolTimelineEventRow]) -> LolKillParticipationTimeline {
+    let teams = participant_teams(participants);
+    let mut kills: Vec<&LolTimelineEventRow> = events.iter().filter(|x| { x.real_type == "CHAMPION_KILL" }).collect();
+    kills.sort_by_key(|x| { x.timestamp });
+
+    let mut ret = LolKillParticipationTimeline{
+        timestamps: frames_by_timestamp(frames).keys().cloned().collect(),
+        participants: BTreeMap::new(),
+    };
+
+    let mut team_kills: HashMap<i32, i64> = HashMap::new();
+    let mut involvement: HashMap<i32, i64> = HashMap::new();
+    let mut next_kill = 0;
+    for ts in &ret.timestamps {
+        while next_kill < kills.len() && kills[next_kill].timestamp <= *ts {
+            let k = kills[next_kill];
+            next_kill += 1;
+
+            // Executions don't count towards either team.
+            let killer_team = match k.killer_id.and_then(|x| { teams.get(&x) }) {
+                Some(x) => *x,
+                None => continue,
+            };
+            *team_kills.entry(killer_team).or_insert(0) += 1;
+
+            let assists = k.assisting_participant_ids.as_ref().map(|x| { x.as_slice() }).unwrap_or(&[]);
+            for p in k.killer_id.iter().chain(assists.iter()) {
+                if teams.get(p) == Some(&killer_team) {
+                    *involvement.entry(*p).or_insert(0) += 1;
+                }
+            }
+        }
+
+        for p in participants {
+            let kp = team_kills.get(&p.team_id)
+                .filter(|x| { **x > 0 })
+                .map(|x| { *involvement.get(&p.participant_id).unwrap_or(&0) as f64 / *x as f64 });
+            ret.participants.entry(p.participant_id).or_default().push(kp);
+        }
+    }
+    ret
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all="camelCase")]
+pub struct LolWardGrid {
+    // Row-major with row 0 at the bottom of the map (y = 0).
+    pub placed: Vec<i32>,
+    pub killed: Vec<i32>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct LolWardHeatmap {
+    pub grid_size: i32,
+    pub participants: BTreeMap<i32, LolWardGrid>,
+}
+
+// Riot doesn't give us a position for ward events so we use wherever the participant was in the closest frame.
+// That's only accurate to within a minute of movement but it's good enough for a coarse grid.
+pub fn compute_lol_ward_heatmap(frames: &[LolTimelineFrameRow], events: &[LolTimelineEventRow], grid_size: i32) -> LolWardHeatmap {
+    let grid_size = grid_size.clamp(1, LOL_MAX_WARD_GRID_SIZE);
+    let num_cells = (grid_size * grid_size) as usize;
+
+    let mut positions: HashMap<i32, Vec<(i64, i32, i32)>> = HashMap::new();
+    for f in frames {
+        if let (Some(x), Some(y)) = (f.x, f.y) {
+            positions.entry(f.participant_id).or_default().push((f.timestamp, x, y));
+        }
+    }
+
+    for p in positions.values_mut() {
+        p.sort_by_key(|x| { x.0 });
+    }
+
+    let cell = |v: i32| -> i32 {
+        (v * grid_size / LOL_MAP_SIZE).clamp(0, grid_size - 1)
+    };
+
+    let mut ret = LolWardHeatmap{
+        grid_size,
+        participants: BTreeMap::new(),
+    };
+
+    for e in events {
+        let (participant_id, placed) = match e.real_type.as_str() {
+            "WARD_PLACED" => (e.creator_id, true),
+            "WARD_KILL" => (e.killer_id, false),
+            _ => continue,
+        };
+
+        let participant_id = match participant_id {
+            Some(x) if x > 0 => x,
+            _ => continue,
+        };
+
+        let pos = match (e.x, e.y) {
+            (Some(x), Some(y)) => Some((x, y)),
+            _ => positions.get(&participant_id).and_then(|p| {
+                p.iter().min_by_key(|x| { (x.0 - e.timestamp).abs() }).map(|x| { (x.1, x.2) })
+            }),
+        };
+
+        let (x, y) = match pos {
+            Some(x) => x,
+            None => continue,
+        };
+
+        let grid = ret.participants.entry(participant_id).or_insert_with(|| {
+            LolWardGrid{
+                placed: vec![0; num_cells],
+                killed: vec![0; num_cells],
+            }
+        });
+
+        let idx = (cell(y) * grid_size + cell(x)) as usize;
+        if placed {
+            grid.placed[idx] += 1;
+        } else {
+            grid.killed[idx] += 1;
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn participants() -> Vec<LolTimelineParticipantRow> {
+        vec![
+            LolTimelineParticipantRow{ participant_id: 1, team_id: 100, lane: String::from("MIDDLE") },
+            LolTimelineParticipantRow{ participant_id: 2, team_id: 100, lane: String::from("BOTTOM") },
+            LolTimelineParticipantRow{ participant_id: 3, team_id: 100, lane: String::from("BOTTOM") },
+            LolTimelineParticipantRow{ participant_id: 6, team_id: 200, lane: String::from("MIDDLE") },
+            LolTimelineParticipantRow{ participant_id: 7, team_id: 200, lane: String::from("BOTTOM") },
+            LolTimelineParticipantRow{ participant_id: 8, team_id: 200, lane: String::from("BOTTOM") },
+        ]
+    }
+
+    fn frame(timestamp: i64, participant_id: i32, cs: i32, gold: i32, xp: i32) -> LolTimelineFrameRow {
+        LolTimelineFrameRow{
+            timestamp,
+            participant_id,
+            minions_killed: cs,
+            total_gold: gold,
+            xp,
+            x: Some(1000 * participant_id),
+            y: Some(14000),
+            ..LolTimelineFrameRow::default()
+        }
+    }
+
+    fn frames() -> Vec<LolTimelineFrameRow> {
+        let mut ret = vec![];
+        for (ts, scale) in [(0, 0), (600020, 1), (900031, 2)] {
+            ret.push(frame(ts, 1, 80 * scale, 4000 * scale, 5000 * scale));
+            ret.push(frame(ts, 2, 90 * scale, 4000 * scale, 4000 * scale));
+            ret.push(frame(ts, 3, 10 * scale, 2500 * scale, 3000 * scale));
+            ret.push(frame(ts, 6, 70 * scale, 3500 * scale, 4500 * scale));
+            ret.push(frame(ts, 7, 5 * scale, 2000 * scale, 2800 * scale));
+            ret.push(frame(ts, 8, 85 * scale, 3800 * scale, 4200 * scale));
+        }
+        ret
+    }
+
+    fn kill(timestamp: i64, killer_id: i32, assists: Vec<i32>) -> LolTimelineEventRow {
+        LolTimelineEventRow{
+            timestamp,
+            real_type: String::from("CHAMPION_KILL"),
+            killer_id: Some(killer_id),
+            assisting_participant_ids: Some(assists),
+            ..LolTimelineEventRow::default()
+        }
+    }
+
+    #[test]
+    fn test_team_diffs() {
+        let diffs = compute_lol_team_diffs(&participants(), &frames());
+        assert_eq!(diffs.len(), 3);
+        assert_eq!(diffs[0].gold_diff, 0);
+        assert_eq!(diffs[1].timestamp, 600020);
+        assert_eq!(diffs[1].gold_diff, 10500 - 9300);
+        assert_eq!(diffs[2].xp_diff, 2 * (12000 - 11500));
+    }
+
+    #[test]
+    fn test_objectives() {
+        let events = vec![
+            LolTimelineEventRow{
+                timestamp: 900000,
+                real_type: String::from("BUILDING_KILL"),
+                building_type: Some(String::from("TOWER_BUILDING")),
+                tower_type: Some(String::from("OUTER_TURRET")),
+                lane_type: Some(String::from("MID_LANE")),
+                team_id: Some(200),
+                killer_id: Some(0),
+                ..LolTimelineEventRow::default()
+            },
+            LolTimelineEventRow{
+                timestamp: 300000,
+                real_type: String::from("ELITE_MONSTER_KILL"),
+                monster_type: Some(String::from("DRAGON")),
+                monster_sub_type: Some(String::from("FIRE_DRAGON")),
+                killer_id: Some(7),
+                ..LolTimelineEventRow::default()
+            },
+            kill(100000, 1, vec![]),
+        ];
+
+        let objectives = compute_lol_objective_timeline(&participants(), &events);
+        assert_eq!(objectives.len(), 2);
+        assert_eq!(objectives[0].objective, LolObjectiveType::Dragon);
+        assert_eq!(objectives[0].sub_type.as_deref(), Some("FIRE_DRAGON"));
+        assert_eq!(objectives[0].team_id, Some(200));
+        assert_eq!(objectives[1].objective, LolObjectiveType::Tower);
+        assert_eq!(objectives[1].team_id, Some(100));
+        assert_eq!(objectives[1].killer_id, None);
+    }
+
+    #[test]
+    fn test_lane_phase() {
+        let lanes = compute_lol_lane_phase(&participants(), &frames());
+        assert_eq!(lanes.len(), 6);
+
+        let mid = lanes.iter().find(|x| { x.participant_id == 1 }).unwrap();
+        assert_eq!(mid.opponent_id, 6);
+        assert_eq!(mid.at10, Some(LolLaneDiff{ cs_diff: 10, gold_diff: 500, xp_diff: 500 }));
+        assert_eq!(mid.at15, Some(LolLaneDiff{ cs_diff: 20, gold_diff: 1000, xp_diff: 1000 }));
+
+        // Carries go against carries and supports against supports.
+        assert_eq!(lanes.iter().find(|x| { x.participant_id == 2 }).unwrap().opponent_id, 8);
+        assert_eq!(lanes.iter().find(|x| { x.participant_id == 3 }).unwrap().opponent_id, 7);
+    }
+
+    #[test]
+    fn test_lane_phase_short_game() {
+        let short: Vec<LolTimelineFrameRow> = frames().into_iter().filter(|x| { x.timestamp < 900000 }).collect();
+        let lanes = compute_lol_lane_phase(&participants(), &short);
+        assert!(lanes.iter().all(|x| { x.at10.is_some() && x.at15.is_none() }));
+    }
+
+    #[test]
+    fn test_kill_participation() {
+        let events = vec![
+            kill(100000, 1, vec![2]),
+            kill(700000, 3, vec![]),
+            kill(800000, 6, vec![]),
+            // Executed.
+            kill(850000, 0, vec![]),
+        ];
+
+        let kp = compute_lol_kill_participation(&participants(), &frames(), &events);
+        assert_eq!(kp.timestamps, vec![0, 600020, 900031]);
+        assert_eq!(kp.participants.get(&1).unwrap(), &vec![None, Some(1.0), Some(0.5)]);
+        assert_eq!(kp.participants.get(&3).unwrap(), &vec![None, Some(0.0), Some(0.5)]);
+        assert_eq!(kp.participants.get(&6).unwrap(), &vec![None, None, Some(1.0)]);
+        assert_eq!(kp.participants.get(&7).unwrap(), &vec![None, None, Some(0.0)]);
+    }
+
+    #[test]
+    fn test_ward_heatmap() {
+        let events = vec![
+            LolTimelineEventRow{
+                timestamp: 590000,
+                real_type: String::from("WARD_PLACED"),
+                creator_id: Some(2),
+                ward_type: Some(String::from("YELLOW_TRINKET")),
+                ..LolTimelineEventRow::default()
+            },
+            LolTimelineEventRow{
+                timestamp: 610000,
+                real_type: String::from("WARD_KILL"),
+                killer_id: Some(2),
+                ward_type: Some(String::from("CONTROL_WARD")),
+                ..LolTimelineEventRow::default()
+            },
+            LolTimelineEventRow{
+                timestamp: 610000,
+                real_type: String::from("WARD_PLACED"),
+                creator_id: Some(0),
+                ..LolTimelineEventRow::default()
+            },
+        ];
+
+        let heatmap = compute_lol_ward_heatmap(&frames(), &events, 10);
+        assert_eq!(heatmap.participants.len(), 1);
+
+        // Participant 2 sits at (2000, 14000) which is column 1 of the top row.
+        let grid = heatmap.participants.get(&2).unwrap();
+        assert_eq!(grid.placed.len(), 100);
+        assert_eq!(grid.placed[91], 1);
+        assert_eq!(grid.killed[91], 1);
+        assert_eq!(grid.placed.iter().sum::<i32>(), 1);
+    }
+}
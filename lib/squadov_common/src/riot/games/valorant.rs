@@ -1,3 +1,5 @@
+pub mod analytics;
+
 use serde::{Serialize,Deserialize};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
@@ -142,6 +144,7 @@ impl ValorantMatchRoundResultDto {
                 puuid: x.puuid.clone(),
                 score: x.score,
                 round_num,
+                ability: x.ability.clone(),
             }
         }).collect();
 
@@ -180,6 +183,7 @@ pub struct FlatValorantMatchPlayerRoundStatsDto {
     pub puuid: String,
     pub score: i32,
     pub round_num: i32,
+    pub ability: Option<ValorantMatchPlayerRoundAbilityDto>,
 }
 
 pub struct FlatValorantMatchKillDto {
@@ -207,6 +211,48 @@ pub struct ValorantMatchPlayerRoundStatsDto {
     pub damage: Vec<ValorantMatchDamageDto>,
     pub economy: ValorantMatchEconomyDto,
     pub score: i32,
+    #[serde(default)]
+    pub ability: Option<ValorantMatchPlayerRoundAbilityDto>,
+}
+
+// Riot only tells us which of the player's abilities had an effect in the round so each ability
+// counts as being cast at most once per round.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ValorantMatchPlayerRoundAbilityDto {
+    #[serde(rename="grenadeEffects", default)]
+    pub grenade_effects: Option<String>,
+    #[serde(rename="ability1Effects", default)]
+    pub ability1_effects: Option<String>,
+    #[serde(rename="ability2Effects", default)]
+    pub ability2_effects: Option<String>,
+    #[serde(rename="ultimateEffects", default)]
+    pub ultimate_effects: Option<String>,
+}
+
+fn ability_effect_casts(effects: &Option<String>) -> i32 {
+    if effects.as_ref().map(|x| { !x.is_empty() }).unwrap_or(false) {
+        1
+    } else {
+        0
+    }
+}
+
+impl ValorantMatchPlayerRoundAbilityDto {
+    pub fn grenade_casts(&self) -> i32 {
+        ability_effect_casts(&self.grenade_effects)
+    }
+
+    pub fn ability1_casts(&self) -> i32 {
+        ability_effect_casts(&self.ability1_effects)
+    }
+
+    pub fn ability2_casts(&self) -> i32 {
+        ability_effect_casts(&self.ability2_effects)
+    }
+
+    pub fn ultimate_casts(&self) -> i32 {
+        ability_effect_casts(&self.ultimate_effects)
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -0,0 +1,441 @@
+use serde::{Serialize, Deserialize};
+use serde_repr::Serialize_repr;
+use uuid::Uuid;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ValorantEconomyConfig {
+    // Round numbers start at 0 so by default these are the first round of each half. Overtime rounds aren't pistol rounds.
+    pub pistol_rounds: Vec<i32>,
+    // All thresholds are per player averages of the loadout value across the team.
+    pub eco_max_loadout_value: i32,
+    pub semi_eco_max_loadout_value: i32,
+    pub full_buy_min_loadout_value: i32,
+}
+
+impl Default for ValorantEconomyConfig {
+    fn default() -> Self {
+        Self {
+            pistol_rounds: vec![0, 12],
+            eco_max_loadout_value: 1000,
+            semi_eco_max_loadout_value: 2000,
+            full_buy_min_loadout_value: 3900,
+        }
+    }
+}
+
+#[derive(Serialize_repr, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(i32)]
+pub enum ValorantBuyType {
+    Pistol,
+    Eco,
+    SemiEco,
+    SemiBuy,
+    FullBuy,
+}
+
+// One player's economy for one round along with who won the round.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct ValorantEconomyPlayerRound {
+    pub match_uuid: Uuid,
+    pub round_num: i32,
+    pub puuid: String,
+    pub team_id: String,
+    pub character_id: String,
+    pub loadout_value: i32,
+    pub spent_money: i32,
+    pub remaining_money: i32,
+    pub team_round_winner: String,
+}
+
+// The first kill of the round. The killer is missing when the player died to something other than another player.
+#[derive(sqlx::FromRow, Serialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct ValorantRoundFirstKill {
+    #[serde(skip)]
+    pub match_uuid: Uuid,
+    pub round_num: i32,
+    pub killer_puuid: Option<String>,
+    pub victim_puuid: String,
+    pub time_since_round_start_millis: i32,
+}
+
+// Summed up from the player's stats in each round. Only rounds where we know the ability usage count towards rounds_played.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct ValorantPlayerAbilityCasts {
+    pub match_uuid: Uuid,
+    pub puuid: String,
+    pub character_id: String,
+    pub rounds_played: i32,
+    pub grenade_casts: i32,
+    pub ability1_casts: i32,
+    pub ability2_casts: i32,
+    pub ultimate_casts: i32,
+}
+
+fn ratio(num: i64, den: i64) -> Option<f64> {
+    if den > 0 {
+        Some(num as f64 / den as f64)
+    } else {
+        None
+    }
+}
+
+pub fn classify_valorant_buy(config: &ValorantEconomyConfig, round_num: i32, players: i32, loadout_value: i32) -> ValorantBuyType {
+    if config.pistol_rounds.contains(&round_num) {
+        return ValorantBuyType::Pistol;
+    }
+
+    if players <= 0 {
+        return ValorantBuyType::Eco;
+    }
+
+    let avg_loadout = loadout_value / players;
+    if avg_loadout >= config.full_buy_min_loadout_value {
+        ValorantBuyType::FullBuy
+    } else if avg_loadout <= config.eco_max_loadout_value {
+        ValorantBuyType::Eco
+    } else if avg_loadout <= config.semi_eco_max_loadout_value {
+        ValorantBuyType::SemiEco
+    } else {
+        ValorantBuyType::SemiBuy
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct ValorantTeamRoundEconomy {
+    pub round_num: i32,
+    pub team_id: String,
+    pub buy_type: ValorantBuyType,
+    pub players: i32,
+    // Totals across the players on the team.
+    pub loadout_value: i32,
+    pub spent_money: i32,
+    pub remaining_money: i32,
+    pub won: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct ValorantBuyTypeStats {
+    pub buy_type: ValorantBuyType,
+    pub rounds: i64,
+    pub wins: i64,
+    pub win_rate: Option<f64>,
+}
+
+#[derive(Default, Debug)]
+struct ValorantBuyTypeAccumulator {
+    stats: BTreeMap<ValorantBuyType, (i64, i64)>,
+}
+
+impl ValorantBuyTypeAccumulator {
+    fn add(&mut self, buy_type: ValorantBuyType, won: bool) {
+        let entry = self.stats.entry(buy_type).or_insert((0, 0));
+        entry.0 += 1;
+        if won {
+            entry.1 += 1;
+        }
+    }
+
+    fn finish(&self) -> Vec<ValorantBuyTypeStats> {
+        self.stats.iter().map(|(buy_type, (rounds, wins))| {
+            ValorantBuyTypeStats{
+                buy_type: *buy_type,
+                rounds: *rounds,
+                wins: *wins,
+                win_rate: ratio(*wins, *rounds),
+            }
+        }).collect()
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct ValorantPlayerAbilityUsage {
+    pub puuid: String,
+    pub character_id: String,
+    pub rounds_played: i32,
+    pub grenade_casts: i32,
+    pub ability1_casts: i32,
+    pub ability2_casts: i32,
+    pub ultimate_casts: i32,
+    pub casts_per_round: Option<f64>,
+}
+
+impl From<&ValorantPlayerAbilityCasts> for ValorantPlayerAbilityUsage {
+    fn from(x: &ValorantPlayerAbilityCasts) -> Self {
+        Self {
+            puuid: x.puuid.clone(),
+            character_id: x.character_id.clone(),
+            rounds_played: x.rounds_played,
+            grenade_casts: x.grenade_casts,
+            ability1_casts: x.ability1_casts,
+            ability2_casts: x.ability2_casts,
+            ultimate_casts: x.ultimate_casts,
+            casts_per_round: ratio((x.grenade_casts + x.ability1_casts + x.ability2_casts + x.ultimate_casts) as i64, x.rounds_played as i64),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct ValorantMatchRoundAnalytics {
+    // Sorted by round and then team.
+    pub team_rounds: Vec<ValorantTeamRoundEconomy>,
+    // Across both teams.
+    pub buy_types: Vec<ValorantBuyTypeStats>,
+    pub first_kills: Vec<ValorantRoundFirstKill>,
+    // Empty for matches stored before we started keeping track of ability casts until they get backfilled.
+    pub abilities: Vec<ValorantPlayerAbilityUsage>,
+}
+
+impl ValorantMatchRoundAnalytics {
+    // All the rows should be from the same match.
+    pub fn from_rows(economy: &[ValorantEconomyPlayerRound], mut first_kills: Vec<ValorantRoundFirstKill>, abilities: &[ValorantPlayerAbilityCasts], config: &ValorantEconomyConfig) -> Self {
+        let mut acc = ValorantBuyTypeAccumulator::default();
+        let team_rounds: Vec<ValorantTeamRoundEconomy> = group_team_rounds(economy).into_iter().map(|((round_num, team_id), players)| {
+            let team = team_round_economy(round_num, team_id, &players, config);
+            acc.add(team.buy_type, team.won);
+            team
+        }).collect();
+
+        first_kills.sort_by_key(|x| { x.round_num });
+
+        Self {
+            team_rounds,
+            buy_types: acc.finish(),
+            first_kills,
+            abilities: abilities.iter().map(|x| { x.into() }).collect(),
+        }
+    }
+}
+
+fn group_team_rounds(economy: &[ValorantEconomyPlayerRound]) -> BTreeMap<(i32, String), Vec<&ValorantEconomyPlayerRound>> {
+    let mut team_rounds: BTreeMap<(i32, String), Vec<&ValorantEconomyPlayerRound>> = BTreeMap::new();
+    for r in economy {
+        team_rounds.entry((r.round_num, r.team_id.clone())).or_insert(vec![]).push(r);
+    }
+    team_rounds
+}
+
+fn team_round_economy(round_num: i32, team_id: String, players: &[&ValorantEconomyPlayerRound], config: &ValorantEconomyConfig) -> ValorantTeamRoundEconomy {
+    let loadout_value = players.iter().map(|x| { x.loadout_value }).sum();
+    ValorantTeamRoundEconomy{
+        round_num,
+        buy_type: classify_valorant_buy(config, round_num, players.len() as i32, loadout_value),
+        players: players.len() as i32,
+        loadout_value,
+        spent_money: players.iter().map(|x| { x.spent_money }).sum(),
+        remaining_money: players.iter().map(|x| { x.remaining_money }).sum(),
+        won: players.first().map(|x| { x.team_round_winner == team_id }).unwrap_or(false),
+        team_id,
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all="camelCase")]
+pub struct ValorantAgentRoundStats {
+    pub rounds: i64,
+    pub first_bloods: i64,
+    pub first_blood_rate: Option<f64>,
+    pub first_deaths: i64,
+    pub first_death_rate: Option<f64>,
+    // Only counts matches where we know the ability casts.
+    pub ability_rounds: i64,
+    pub grenade_casts_per_round: Option<f64>,
+    pub ability1_casts_per_round: Option<f64>,
+    pub ability2_casts_per_round: Option<f64>,
+    pub ultimate_casts_per_round: Option<f64>,
+    #[serde(skip)]
+    grenade_casts: i64,
+    #[serde(skip)]
+    ability1_casts: i64,
+    #[serde(skip)]
+    ability2_casts: i64,
+    #[serde(skip)]
+    ultimate_casts: i64,
+}
+
+impl ValorantAgentRoundStats {
+    fn add_abilities(&mut self, casts: &ValorantPlayerAbilityCasts) {
+        self.ability_rounds += casts.rounds_played as i64;
+        self.grenade_casts += casts.grenade_casts as i64;
+        self.ability1_casts += casts.ability1_casts as i64;
+        self.ability2_casts += casts.ability2_casts as i64;
+        self.ultimate_casts += casts.ultimate_casts as i64;
+    }
+
+    fn finish(&mut self) {
+        self.first_blood_rate = ratio(self.first_bloods, self.rounds);
+        self.first_death_rate = ratio(self.first_deaths, self.rounds);
+        self.grenade_casts_per_round = ratio(self.grenade_casts, self.ability_rounds);
+        self.ability1_casts_per_round = ratio(self.ability1_casts, self.ability_rounds);
+        self.ability2_casts_per_round = ratio(self.ability2_casts, self.ability_rounds);
+        self.ultimate_casts_per_round = ratio(self.ultimate_casts, self.ability_rounds);
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct ValorantPlayerRoundAnalytics {
+    pub matches: i64,
+    // Only the rounds played by the player's team.
+    pub buy_types: Vec<ValorantBuyTypeStats>,
+    pub by_agent: BTreeMap<String, ValorantAgentRoundStats>,
+}
+
+impl ValorantPlayerRoundAnalytics {
+    // The economy rows need to include every player in the player's matches so we can figure out the team's buy.
+    // The ability casts should only be for the player.
+    pub fn from_rows(puuid: &str, economy: &[ValorantEconomyPlayerRound], first_kills: &[ValorantRoundFirstKill], abilities: &[ValorantPlayerAbilityCasts], config: &ValorantEconomyConfig) -> Self {
+        let mut matches: HashMap<Uuid, Vec<ValorantEconomyPlayerRound>> = HashMap::new();
+        for r in economy {
+            matches.entry(r.match_uuid.clone()).or_insert(vec![]).push(r.clone());
+        }
+
+        let first_kills: HashMap<(Uuid, i32), &ValorantRoundFirstKill> = first_kills.iter().map(|x| {
+            ((x.match_uuid.clone(), x.round_num), x)
+        }).collect();
+
+        let mut acc = ValorantBuyTypeAccumulator::default();
+        let mut by_agent: BTreeMap<String, ValorantAgentRoundStats> = BTreeMap::new();
+        for (match_uuid, rows) in &matches {
+            for ((round_num, team_id), players) in group_team_rounds(rows) {
+                let player = match players.iter().find(|x| { x.puuid == puuid }) {
+                    Some(x) => *x,
+                    None => continue,
+                };
+
+                let team = team_round_economy(round_num, team_id, &players, config);
+                acc.add(team.buy_type, team.won);
+
+                let agent = by_agent.entry(player.character_id.clone()).or_default();
+                agent.rounds += 1;
+                if let Some(kill) = first_kills.get(&(match_uuid.clone(), round_num)) {
+                    if kill.killer_puuid.as_deref() == Some(puuid) {
+                        agent.first_bloods += 1;
+                    }
+
+                    if kill.victim_puuid == puuid {
+                        agent.first_deaths += 1;
+                    }
+                }
+            }
+        }
+
+        for casts in abilities {
+            if casts.puuid == puuid {
+                by_agent.entry(casts.character_id.clone()).or_default().add_abilities(casts);
+            }
+        }
+
+        for (_, agent) in by_agent.iter_mut() {
+            agent.finish();
+        }
+
+        Self {
+            matches: matches.len() as i64,
+            buy_types: acc.finish(),
+            by_agent,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn econ(match_uuid: &Uuid, round_num: i32, puuid: &str, team_id: &str, winner: &str, loadout_value: i32) -> ValorantEconomyPlayerRound {
+        ValorantEconomyPlayerRound{
+            match_uuid: match_uuid.clone(),
+            round_num,
+            puuid: puuid.to_string(),
+            team_id: team_id.to_string(),
+            character_id: format!("{}-agent", puuid),
+            loadout_value,
+            spent_money: loadout_value,
+            remaining_money: 100,
+            team_round_winner: winner.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_classify_buy() {
+        let config = ValorantEconomyConfig::default();
+        assert_eq!(classify_valorant_buy(&config, 0, 5, 20000), ValorantBuyType::Pistol);
+        assert_eq!(classify_valorant_buy(&config, 12, 5, 4000), ValorantBuyType::Pistol);
+        assert_eq!(classify_valorant_buy(&config, 1, 5, 2500), ValorantBuyType::Eco);
+        assert_eq!(classify_valorant_buy(&config, 2, 5, 9000), ValorantBuyType::SemiEco);
+        assert_eq!(classify_valorant_buy(&config, 3, 5, 15000), ValorantBuyType::SemiBuy);
+        assert_eq!(classify_valorant_buy(&config, 4, 5, 19500), ValorantBuyType::FullBuy);
+        // Overtime rounds are never pistol rounds.
+        assert_eq!(classify_valorant_buy(&config, 24, 5, 25000), ValorantBuyType::FullBuy);
+    }
+
+    #[test]
+    fn test_match_and_player_analytics() {
+        let match_uuid = Uuid::new_v4();
+        let economy = vec![
+            econ(&match_uuid, 0, "a", "Red", "Red", 800),
+            econ(&match_uuid, 0, "b", "Blue", "Red", 800),
+            econ(&match_uuid, 1, "a", "Red", "Blue", 4500),
+            econ(&match_uuid, 1, "b", "Blue", "Blue", 500),
+            econ(&match_uuid, 2, "a", "Red", "Red", 4000),
+            econ(&match_uuid, 2, "b", "Blue", "Red", 3000),
+        ];
+
+        let first_kills = vec![
+            ValorantRoundFirstKill{
+                match_uuid: match_uuid.clone(),
+                round_num: 1,
+                killer_puuid: Some(String::from("b")),
+                victim_puuid: String::from("a"),
+                time_since_round_start_millis: 20000,
+            },
+            ValorantRoundFirstKill{
+                match_uuid: match_uuid.clone(),
+                round_num: 0,
+                killer_puuid: Some(String::from("a")),
+                victim_puuid: String::from("b"),
+                time_since_round_start_millis: 15000,
+            },
+        ];
+
+        let abilities = vec![
+            ValorantPlayerAbilityCasts{
+                match_uuid: match_uuid.clone(),
+                puuid: String::from("a"),
+                character_id: String::from("a-agent"),
+                rounds_played: 3,
+                grenade_casts: 3,
+                ability1_casts: 6,
+                ability2_casts: 0,
+                ultimate_casts: 1,
+            },
+        ];
+
+        let config = ValorantEconomyConfig::default();
+        let analytics = ValorantMatchRoundAnalytics::from_rows(&economy, first_kills.clone(), &abilities, &config);
+        assert_eq!(analytics.team_rounds.len(), 6);
+        assert_eq!(analytics.team_rounds[2].team_id, "Blue");
+        assert_eq!(analytics.team_rounds[2].buy_type, ValorantBuyType::Eco);
+        assert!(analytics.team_rounds[2].won);
+        assert_eq!(analytics.first_kills[0].round_num, 0);
+        assert_eq!(analytics.abilities[0].casts_per_round, Some(10.0 / 3.0));
+
+        let player = ValorantPlayerRoundAnalytics::from_rows("a", &economy, &first_kills, &abilities, &config);
+        assert_eq!(player.matches, 1);
+        let full_buy = player.buy_types.iter().find(|x| { x.buy_type == ValorantBuyType::FullBuy }).unwrap();
+        assert_eq!((full_buy.rounds, full_buy.wins), (2, 1));
+
+        let agent = player.by_agent.get("a-agent").unwrap();
+        assert_eq!(agent.rounds, 3);
+        assert_eq!(agent.first_bloods, 1);
+        assert_eq!(agent.first_deaths, 1);
+        assert_eq!(agent.first_blood_rate, Some(1.0 / 3.0));
+        assert_eq!(agent.ability1_casts_per_round, Some(2.0));
+    }
+}
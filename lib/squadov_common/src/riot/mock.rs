@@ -0,0 +1,148 @@
+// A small stand-in for the Riot API that serves recorded JSON so that everything built on top of RiotApiHandler
+// can be run without a real API key. Point RiotApiEndpointConfig::default_base_url at RiotMockServer::base_url.
+use crate::SquadOvError;
+use actix_web::{
+    web,
+    App,
+    HttpRequest,
+    HttpResponse,
+    HttpServer,
+    http::StatusCode,
+    dev::ServerHandle,
+};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct RiotMockManifest {
+    // Request path (no leading slash or query string) to the fixture file relative to the manifest.
+    pub routes: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct RiotMockFault {
+    // Matches any request whose path starts with this.
+    pub path_prefix: String,
+    // Generally 429 or 503.
+    pub status: u16,
+    // In seconds.
+    pub retry_after: Option<u64>,
+    // Number of requests that fail before the fault goes away.
+    pub count: u32,
+}
+
+struct RiotMockState {
+    fixture_dir: PathBuf,
+    routes: HashMap<String, String>,
+    faults: Mutex<Vec<RiotMockFault>>,
+    requests: Mutex<HashMap<String, usize>>,
+}
+
+impl RiotMockState {
+    fn take_fault(&self, path: &str) -> Option<RiotMockFault> {
+        let mut faults = self.faults.lock().unwrap();
+        let idx = faults.iter().position(|x| { path.starts_with(&x.path_prefix) })?;
+        let ret = faults[idx].clone();
+        faults[idx].count -= 1;
+        if faults[idx].count == 0 {
+            faults.remove(idx);
+        }
+        Some(ret)
+    }
+}
+
+async fn serve_fixture(req: HttpRequest, state: web::Data<Arc<RiotMockState>>) -> HttpResponse {
+    let path = req.path().trim_start_matches('/').to_string();
+    *state.requests.lock().unwrap().entry(path.clone()).or_insert(0) += 1;
+
+    if let Some(fault) = state.take_fault(&path) {
+        let mut resp = HttpResponse::build(StatusCode::from_u16(fault.status).unwrap_or(StatusCode::SERVICE_UNAVAILABLE));
+        if let Some(retry_after) = fault.retry_after {
+            resp.insert_header(("Retry-After", retry_after.to_string()));
+        }
+        return resp.finish();
+    }
+
+    let file = match state.routes.get(&path) {
+        Some(x) => state.fixture_dir.join(x),
+        None => return HttpResponse::NotFound().finish(),
+    };
+
+    match std::fs::read(&file) {
+        Ok(body) => HttpResponse::Ok().content_type("application/json").body(body),
+        Err(err) => {
+            log::warn!("Failed to read Riot mock fixture {}: {:?}", file.display(), err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn add_fault(fault: web::Json<RiotMockFault>, state: web::Data<Arc<RiotMockState>>) -> HttpResponse {
+    if fault.count > 0 {
+        state.faults.lock().unwrap().push(fault.into_inner());
+    }
+    HttpResponse::NoContent().finish()
+}
+
+pub struct RiotMockServer {
+    addr: SocketAddr,
+    handle: ServerHandle,
+    state: Arc<RiotMockState>,
+}
+
+impl RiotMockServer {
+    // Use port 0 in the bind address to get a random port.
+    pub async fn start(manifest: &Path, bind: &str) -> Result<Self, SquadOvError> {
+        let parsed: RiotMockManifest = serde_json::from_slice(&std::fs::read(manifest)?)?;
+        let state = Arc::new(RiotMockState{
+            fixture_dir: manifest.parent().map(|x| { x.to_path_buf() }).unwrap_or_default(),
+            routes: parsed.routes,
+            faults: Mutex::new(vec![]),
+            requests: Mutex::new(HashMap::new()),
+        });
+
+        let server_state = state.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(web::Data::new(server_state.clone()))
+                .route("/__mock/faults", web::post().to(add_fault))
+                .default_service(web::to(serve_fixture))
+        })
+            .workers(1)
+            .bind(bind)?;
+
+        let addr = server.addrs().first().cloned().ok_or_else(|| { SquadOvError::InternalError(String::from("Riot mock server has no address")) })?;
+        let server = server.run();
+        let handle = server.handle();
+        tokio::task::spawn(server);
+
+        Ok(Self {
+            addr,
+            handle,
+            state,
+        })
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn add_fault(&self, fault: RiotMockFault) {
+        if fault.count > 0 {
+            self.state.faults.lock().unwrap().push(fault);
+        }
+    }
+
+    // Number of requests the server has received for the given path (no leading slash).
+    pub fn request_count(&self, path: &str) -> usize {
+        self.state.requests.lock().unwrap().get(path).cloned().unwrap_or(0)
+    }
+
+    pub async fn stop(&self) {
+        self.handle.stop(true).await;
+    }
+}
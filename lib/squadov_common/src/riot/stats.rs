@@ -0,0 +1,10 @@
+pub mod lol;
+pub mod tft;
+
+fn ratio(num: i64, den: i64) -> Option<f64> {
+    if den > 0 {
+        Some(num as f64 / den as f64)
+    } else {
+        None
+    }
+}
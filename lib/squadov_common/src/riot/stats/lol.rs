@@ -0,0 +1,202 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use std::collections::BTreeMap;
+use super::ratio;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all="camelCase")]
+pub struct LolStatsFilters {
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub queues: Option<Vec<i32>>,
+    // Patches are the first two parts of the game version (e.g. "12.14").
+    pub patches: Option<Vec<String>>,
+}
+
+pub fn lol_patch_from_game_version(game_version: &str) -> String {
+    game_version.split('.').take(2).collect::<Vec<&str>>().join(".")
+}
+
+// One player's stats out of a single match along with their team's total damage to champions.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct LolPlayerMatchStatsRow {
+    pub match_uuid: Uuid,
+    pub game_creation: DateTime<Utc>,
+    // In seconds.
+    pub game_duration: i64,
+    pub queue_id: i32,
+    pub game_version: String,
+    pub champion_id: i32,
+    pub lane: String,
+    pub win: bool,
+    pub kills: i32,
+    pub deaths: i32,
+    pub assists: i32,
+    pub total_minions_killed: i32,
+    pub neutral_minions_kills: i32,
+    pub vision_score: i64,
+    pub total_damage_dealt_to_champions: i64,
+    pub team_damage_dealt_to_champions: i64,
+}
+
+impl LolPlayerMatchStatsRow {
+    fn damage_share(&self) -> Option<f64> {
+        ratio(self.total_damage_dealt_to_champions, self.team_damage_dealt_to_champions)
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all="camelCase")]
+pub struct LolStatsBreakdown {
+    pub games: i64,
+    pub wins: i64,
+    pub win_rate: Option<f64>,
+    pub kills: i64,
+    pub deaths: i64,
+    pub assists: i64,
+    // Deaths are treated as 1 when the player didn't die.
+    pub kda: Option<f64>,
+    pub cs_per_min: Option<f64>,
+    pub avg_vision_score: Option<f64>,
+    pub avg_damage_share: Option<f64>,
+    #[serde(skip)]
+    cs: i64,
+    #[serde(skip)]
+    seconds: i64,
+    #[serde(skip)]
+    vision_score: i64,
+    #[serde(skip)]
+    damage_share: f64,
+    #[serde(skip)]
+    damage_share_games: i64,
+}
+
+impl LolStatsBreakdown {
+    fn add(&mut self, row: &LolPlayerMatchStatsRow) {
+        self.games += 1;
+        if row.win {
+            self.wins += 1;
+        }
+
+        self.kills += row.kills as i64;
+        self.deaths += row.deaths as i64;
+        self.assists += row.assists as i64;
+        self.cs += (row.total_minions_killed + row.neutral_minions_kills) as i64;
+        self.seconds += row.game_duration;
+        self.vision_score += row.vision_score;
+
+        if let Some(share) = row.damage_share() {
+            self.damage_share += share;
+            self.damage_share_games += 1;
+        }
+
+        self.win_rate = ratio(self.wins, self.games);
+        self.kda = ratio(self.kills + self.assists, std::cmp::max(self.deaths, 1));
+        self.cs_per_min = ratio(self.cs * 60, self.seconds);
+        self.avg_vision_score = ratio(self.vision_score, self.games);
+        self.avg_damage_share = if self.damage_share_games > 0 {
+            Some(self.damage_share / self.damage_share_games as f64)
+        } else {
+            None
+        };
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct LolDamageSharePoint {
+    pub match_uuid: Uuid,
+    pub game_creation: DateTime<Utc>,
+    pub patch: String,
+    pub champion_id: i32,
+    pub damage_share: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all="camelCase")]
+pub struct LolStatsSummary {
+    pub overall: LolStatsBreakdown,
+    pub by_champion: BTreeMap<i32, LolStatsBreakdown>,
+    // Keyed by the lane Riot assigned the player.
+    pub by_role: BTreeMap<String, LolStatsBreakdown>,
+    // Oldest match first.
+    pub damage_share: Vec<LolDamageSharePoint>,
+}
+
+impl LolStatsSummary {
+    pub fn from_rows(rows: &[LolPlayerMatchStatsRow]) -> Self {
+        let mut ret = Self::default();
+        for row in rows {
+            ret.overall.add(row);
+            ret.by_champion.entry(row.champion_id).or_default().add(row);
+            ret.by_role.entry(row.lane.clone()).or_default().add(row);
+            ret.damage_share.push(LolDamageSharePoint{
+                match_uuid: row.match_uuid.clone(),
+                game_creation: row.game_creation.clone(),
+                patch: lol_patch_from_game_version(&row.game_version),
+                champion_id: row.champion_id,
+                damage_share: row.damage_share(),
+            });
+        }
+        ret.damage_share.sort_by_key(|x| { x.game_creation });
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, TimeZone};
+
+    fn row(champion_id: i32, lane: &str, win: bool, kills: i32, deaths: i32, assists: i32, day: u32) -> LolPlayerMatchStatsRow {
+        LolPlayerMatchStatsRow{
+            match_uuid: Uuid::new_v4(),
+            game_creation: Utc.ymd(2022, 7, day).and_hms(0, 0, 0),
+            game_duration: 1800,
+            queue_id: 420,
+            game_version: String::from("12.14.456.5556"),
+            champion_id,
+            lane: lane.to_string(),
+            win,
+            kills,
+            deaths,
+            assists,
+            total_minions_killed: 200,
+            neutral_minions_kills: 10,
+            vision_score: 20,
+            total_damage_dealt_to_champions: 10000,
+            team_damage_dealt_to_champions: 40000,
+        }
+    }
+
+    #[test]
+    fn test_patch_from_game_version() {
+        assert_eq!(lol_patch_from_game_version("12.14.456.5556"), "12.14");
+        assert_eq!(lol_patch_from_game_version("12"), "12");
+    }
+
+    #[test]
+    fn test_summary() {
+        let rows = vec![
+            row(1, "MIDDLE", true, 10, 0, 5, 3),
+            row(1, "MIDDLE", false, 2, 4, 2, 1),
+            row(2, "TOP", true, 3, 2, 7, 2),
+        ];
+
+        let summary = LolStatsSummary::from_rows(&rows);
+        assert_eq!(summary.overall.games, 3);
+        assert_eq!(summary.overall.win_rate, Some(2.0 / 3.0));
+        assert_eq!(summary.overall.kda, Some(29.0 / 6.0));
+        assert_eq!(summary.overall.cs_per_min, Some(7.0));
+        assert_eq!(summary.overall.avg_damage_share, Some(0.25));
+
+        let mid = summary.by_champion.get(&1).unwrap();
+        assert_eq!(mid.games, 2);
+        assert_eq!(mid.kda, Some(19.0 / 4.0));
+        assert_eq!(summary.by_role.get("TOP").unwrap().wins, 1);
+
+        let days: Vec<u32> = summary.damage_share.iter().map(|x| { x.game_creation.day() }).collect();
+        assert_eq!(days, vec![1, 2, 3]);
+    }
+}
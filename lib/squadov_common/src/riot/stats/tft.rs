@@ -0,0 +1,206 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use super::ratio;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all="camelCase")]
+pub struct TftStatsFilters {
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub queues: Option<Vec<i32>>,
+}
+
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct TftPlayerPlacementRow {
+    pub match_uuid: Uuid,
+    pub game_datetime: DateTime<Utc>,
+    pub placement: i32,
+    pub companion_species: String,
+}
+
+// Only traits that were active (i.e. at least the first tier) at the end of the game.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct TftPlayerTraitRow {
+    pub match_uuid: Uuid,
+    pub name: String,
+}
+
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct TftPlayerUnitRow {
+    pub match_uuid: Uuid,
+    pub character_id: Option<String>,
+    pub name: String,
+    pub items: Vec<i32>,
+}
+
+impl TftPlayerUnitRow {
+    // Older sets don't have a character ID.
+    fn unit_id(&self) -> String {
+        self.character_id.clone().unwrap_or_else(|| { self.name.clone() })
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all="camelCase")]
+pub struct TftPlacementStats {
+    pub games: i64,
+    pub wins: i64,
+    pub avg_placement: Option<f64>,
+    pub top4_rate: Option<f64>,
+    #[serde(skip)]
+    total_placement: i64,
+    #[serde(skip)]
+    top4: i64,
+}
+
+impl TftPlacementStats {
+    fn add(&mut self, placement: i32) {
+        self.games += 1;
+        self.total_placement += placement as i64;
+        if placement <= 4 {
+            self.top4 += 1;
+        }
+
+        if placement == 1 {
+            self.wins += 1;
+        }
+
+        self.avg_placement = ratio(self.total_placement, self.games);
+        self.top4_rate = ratio(self.top4, self.games);
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct TftUnitUsage {
+    pub unit_id: String,
+    pub stats: TftPlacementStats,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct TftItemUsage {
+    pub item_id: i32,
+    // Total number of copies of the item across all the player's units.
+    pub count: i64,
+    pub stats: TftPlacementStats,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all="camelCase")]
+pub struct TftStatsSummary {
+    pub overall: TftPlacementStats,
+    pub by_trait: BTreeMap<String, TftPlacementStats>,
+    pub by_companion: BTreeMap<String, TftPlacementStats>,
+    // Both sorted by the number of games they showed up in (most played first).
+    pub units: Vec<TftUnitUsage>,
+    pub items: Vec<TftItemUsage>,
+}
+
+impl TftStatsSummary {
+    pub fn from_rows(placements: &[TftPlayerPlacementRow], traits: &[TftPlayerTraitRow], units: &[TftPlayerUnitRow]) -> Self {
+        let mut ret = Self::default();
+        let mut match_placements: HashMap<Uuid, i32> = HashMap::new();
+        for p in placements {
+            ret.overall.add(p.placement);
+            ret.by_companion.entry(p.companion_species.clone()).or_default().add(p.placement);
+            match_placements.insert(p.match_uuid.clone(), p.placement);
+        }
+
+        // Everything else only gets counted once per match.
+        let unique_traits: BTreeSet<(Uuid, String)> = traits.iter().map(|x| { (x.match_uuid.clone(), x.name.clone()) }).collect();
+        for (match_uuid, name) in unique_traits {
+            if let Some(placement) = match_placements.get(&match_uuid) {
+                ret.by_trait.entry(name).or_default().add(*placement);
+            }
+        }
+
+        let mut unique_units: BTreeSet<(Uuid, String)> = BTreeSet::new();
+        let mut unique_items: BTreeSet<(Uuid, i32)> = BTreeSet::new();
+        let mut item_counts: HashMap<i32, i64> = HashMap::new();
+        for u in units {
+            unique_units.insert((u.match_uuid.clone(), u.unit_id()));
+            for item in &u.items {
+                unique_items.insert((u.match_uuid.clone(), *item));
+                *item_counts.entry(*item).or_insert(0) += 1;
+            }
+        }
+
+        let mut unit_stats: BTreeMap<String, TftPlacementStats> = BTreeMap::new();
+        for (match_uuid, unit_id) in unique_units {
+            if let Some(placement) = match_placements.get(&match_uuid) {
+                unit_stats.entry(unit_id).or_default().add(*placement);
+            }
+        }
+
+        let mut item_stats: BTreeMap<i32, TftPlacementStats> = BTreeMap::new();
+        for (match_uuid, item_id) in unique_items {
+            if let Some(placement) = match_placements.get(&match_uuid) {
+                item_stats.entry(item_id).or_default().add(*placement);
+            }
+        }
+
+        ret.units = unit_stats.into_iter().map(|(unit_id, stats)| {
+            TftUnitUsage{
+                unit_id,
+                stats,
+            }
+        }).collect();
+        ret.units.sort_by(|a, b| { b.stats.games.cmp(&a.stats.games) });
+
+        ret.items = item_stats.into_iter().map(|(item_id, stats)| {
+            TftItemUsage{
+                item_id,
+                count: item_counts.get(&item_id).cloned().unwrap_or(0),
+                stats,
+            }
+        }).collect();
+        ret.items.sort_by(|a, b| { b.stats.games.cmp(&a.stats.games).then(b.count.cmp(&a.count)) });
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let m1 = Uuid::new_v4();
+        let m2 = Uuid::new_v4();
+        let placements = vec![
+            TftPlayerPlacementRow{ match_uuid: m1.clone(), game_datetime: Utc::now(), placement: 1, companion_species: String::from("PetTFTAvatar") },
+            TftPlayerPlacementRow{ match_uuid: m2.clone(), game_datetime: Utc::now(), placement: 6, companion_species: String::from("PetTFTAvatar") },
+        ];
+
+        let traits = vec![
+            TftPlayerTraitRow{ match_uuid: m1.clone(), name: String::from("Set7_Dragonmancer") },
+            TftPlayerTraitRow{ match_uuid: m2.clone(), name: String::from("Set7_Dragonmancer") },
+            TftPlayerTraitRow{ match_uuid: m2.clone(), name: String::from("Set7_Mage") },
+        ];
+
+        let units = vec![
+            TftPlayerUnitRow{ match_uuid: m1.clone(), character_id: Some(String::from("TFT7_Sona")), name: String::new(), items: vec![44, 44] },
+            TftPlayerUnitRow{ match_uuid: m1.clone(), character_id: Some(String::from("TFT7_Sona")), name: String::new(), items: vec![] },
+            TftPlayerUnitRow{ match_uuid: m2.clone(), character_id: Some(String::from("TFT7_Sona")), name: String::new(), items: vec![] },
+            TftPlayerUnitRow{ match_uuid: m2.clone(), character_id: None, name: String::from("TFT7_Nami"), items: vec![12] },
+        ];
+
+        let summary = TftStatsSummary::from_rows(&placements, &traits, &units);
+        assert_eq!(summary.overall.avg_placement, Some(3.5));
+        assert_eq!(summary.overall.top4_rate, Some(0.5));
+        assert_eq!(summary.by_companion.get("PetTFTAvatar").unwrap().games, 2);
+        assert_eq!(summary.by_trait.get("Set7_Dragonmancer").unwrap().avg_placement, Some(3.5));
+        assert_eq!(summary.by_trait.get("Set7_Mage").unwrap().wins, 0);
+
+        assert_eq!(summary.units[0].unit_id, "TFT7_Sona");
+        assert_eq!(summary.units[0].stats.games, 2);
+        assert_eq!(summary.units[1].unit_id, "TFT7_Nami");
+
+        let item = summary.items.iter().find(|x| { x.item_id == 44 }).unwrap();
+        assert_eq!(item.count, 2);
+        assert_eq!(item.stats.games, 1);
+    }
+}
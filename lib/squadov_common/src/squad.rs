@@ -1,5 +1,6 @@
 pub mod status;
 pub mod links;
+pub mod events;
 
 use crate::{SquadOvError, SquadOvGames, SquadOvWowRelease};
 use chrono::{DateTime, Utc};
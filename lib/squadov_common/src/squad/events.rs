@@ -0,0 +1,96 @@
+use uuid::Uuid;
+use crate::{
+    SquadOvError,
+    SquadOvGames,
+    redis::RedisConfig,
+};
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+// Anything that gets relayed through a RedisEventHub. The channel is the Redis pubsub channel.
+pub trait RedisEvent: Clone + Serialize + DeserializeOwned + Send + Sync + 'static {
+    const CHANNEL: &'static str;
+}
+
+#[derive(Clone,Serialize,Deserialize,Debug)]
+#[serde(rename_all="camelCase")]
+pub struct NewMatchEvent {
+    pub match_uuid: Uuid,
+    pub video_uuid: Uuid,
+    pub game: SquadOvGames,
+    pub user_id: i64,
+}
+
+impl RedisEvent for NewMatchEvent {
+    const CHANNEL: &'static str = "new-match";
+}
+
+pub type NewMatchEventHub = RedisEventHub<NewMatchEvent>;
+
+// Relays events across servers using Redis pubsub. Each server will then
+// forward the event to any local listeners (e.g. GraphQL subscriptions).
+pub struct RedisEventHub<T: RedisEvent> {
+    rconfig: RedisConfig,
+    redis: Arc<deadpool_redis::Pool>,
+    events: broadcast::Sender<T>,
+}
+
+impl<T: RedisEvent> RedisEventHub<T> {
+    pub async fn new(redis_config: &RedisConfig, redis: Arc<deadpool_redis::Pool>) -> Arc<Self> {
+        let hub = Arc::new(RedisEventHub{
+            rconfig: redis_config.clone(),
+            redis,
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+        });
+
+        {
+            let ps_hub = hub.clone();
+            tokio::task::spawn(async move {
+                loop {
+                    let inner_hub = ps_hub.clone();
+                    let t1 = tokio::task::spawn(async move {
+                        let client = redis::Client::open(inner_hub.rconfig.url.as_str())?;
+                        let mut conn = client.get_connection()?;
+                        let mut pubsub = conn.as_pubsub();
+                        pubsub.subscribe(T::CHANNEL)?;
+
+                        loop {
+                            let msg = pubsub.get_message()?;
+                            let event = serde_json::from_str::<T>(&msg.get_payload::<String>()?)?;
+                            // Sending only fails when there's no one listening which is fine.
+                            let _ = inner_hub.events.send(event);
+                        }
+
+                        #[allow(unreachable_code)]
+                        Ok::<(), SquadOvError>(())
+                    });
+
+                    match t1.await {
+                        Ok(_) => (),
+                        Err(err) => log::warn!("{} Pubsub Thread failed...restarting {:?}", T::CHANNEL, err),
+                    };
+
+                    async_std::task::sleep(std::time::Duration::from_millis(16)).await;
+                }
+            });
+        }
+
+        hub
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<T> {
+        self.events.subscribe()
+    }
+
+    pub async fn publish(&self, event: &T) -> Result<(), SquadOvError> {
+        let mut conn = self.redis.get().await?;
+        deadpool_redis::redis::cmd("PUBLISH")
+            .arg(&[T::CHANNEL, &serde_json::to_string(event)?])
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
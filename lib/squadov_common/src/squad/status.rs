@@ -13,9 +13,11 @@ use serde::{Serialize, Deserialize};
 use serde_repr::{Serialize_repr, Deserialize_repr};
 use std::sync::Arc;
 use async_std::sync::RwLock;
+use tokio::sync::broadcast;
 
 const HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
 const HEARTBEAT_TIMEOUT_SECONDS: i64 = 30;
+const STATE_CHANGE_CHANNEL_CAPACITY: usize = 1024;
 
 #[derive(Clone,Debug,Serialize_repr, Deserialize_repr, PartialEq)]
 #[repr(i32)]
@@ -27,9 +29,9 @@ pub enum Activity {
 }
 
 #[derive(Clone,Serialize,Deserialize,Debug)]
-struct UserActivityState {
-    activity: Activity,
-    game: Vec<FullSupportedGame>,
+pub struct UserActivityState {
+    pub activity: Activity,
+    pub game: Vec<FullSupportedGame>,
 }
 
 impl Default for UserActivityState {
@@ -69,6 +71,8 @@ pub struct UserActivityStatusTracker {
     sessions: RwLock<HashMap<Uuid, Recipient<UserActivityChange>>>,
     // For each user, sessions that are listening to the user.
     per_user_sessions: RwLock<HashMap<i64, HashSet<Uuid>>>,
+    // Every state change that this server is made aware of for listeners that aren't websocket sessions (e.g. GraphQL subscriptions).
+    changes: broadcast::Sender<(i64, UserActivityState)>,
 }
 
 impl UserActivityStatusTracker {
@@ -78,6 +82,7 @@ impl UserActivityStatusTracker {
             redis,
             sessions: RwLock::new(HashMap::new()),
             per_user_sessions: RwLock::new(HashMap::new()),
+            changes: broadcast::channel(STATE_CHANGE_CHANNEL_CAPACITY).0,
         });
 
         {
@@ -116,6 +121,10 @@ impl UserActivityStatusTracker {
         tracker
     }
 
+    pub fn subscribe_changes(&self) -> broadcast::Receiver<(i64, UserActivityState)> {
+        self.changes.subscribe()
+    }
+
     async fn get_connection(&self) -> Result<deadpool_redis::Connection, SquadOvError> {
         let conn = self.redis.get().await?;
         Ok(conn)
@@ -125,7 +134,7 @@ impl UserActivityStatusTracker {
         format!("state-cache-{}", user_id)
     }
 
-    async fn get_user_state(&self, user_id: i64) -> Result<UserActivityState, SquadOvError> {
+    pub async fn get_user_state(&self, user_id: i64) -> Result<UserActivityState, SquadOvError> {
         let mut conn = self.get_connection().await?;
         let raw: Option<String> = deadpool_redis::redis::cmd("GET")
             .arg(&[&self.get_user_cache_key(user_id)])
@@ -139,7 +148,7 @@ impl UserActivityStatusTracker {
         })
     }
 
-    async fn batch_get_multiple_user_states(&self, user_ids: &[i64]) -> Result<Vec<UserActivityState>, SquadOvError> {
+    pub async fn batch_get_multiple_user_states(&self, user_ids: &[i64]) -> Result<Vec<UserActivityState>, SquadOvError> {
         // Break the input user ids into batches. Running a ton of keys on MGET at a single time is a YIKES.
         let mut result: Vec<UserActivityState> = vec![];
         for ch in user_ids.chunks(10) {
@@ -190,6 +199,9 @@ impl UserActivityStatusTracker {
             self.get_user_state(user_id).await?
         };
 
+        // Sending only fails when there's no one listening which is fine.
+        let _ = self.changes.send((user_id, final_state.clone()));
+
         let per_user_sessions = self.per_user_sessions.read().await;
 
         if let Some(pu_sessions) = per_user_sessions.get(&user_id) {
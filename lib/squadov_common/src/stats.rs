@@ -1,6 +1,14 @@
 mod aimlab;
+mod valorant;
+mod csgo;
+mod lol;
+mod wow;
 
 pub use aimlab::*;
+pub use valorant::*;
+pub use csgo::*;
+pub use lol::*;
+pub use wow::*;
 
 use serde_repr::{Serialize_repr, Deserialize_repr};
 
@@ -23,4 +31,9 @@ pub enum StatPermission {
     AimlabLinetrace,
     AimlabMultilinetrace,
     AimlabPentakill,
+    ValorantAgents,
+    ValorantMaps,
+    CsgoMaps,
+    LolChampions,
+    WowEncounters,
 }
\ No newline at end of file
@@ -0,0 +1,15 @@
+#[derive(sqlx::FromRow)]
+#[derive(juniper::GraphQLObject)]
+pub struct CsgoStatMapData {
+    map: String,
+    games: i32,
+    rounds: i32,
+    round_wins: i32,
+    round_win_rate: f64,
+    kills: i32,
+    deaths: i32,
+    assists: i32,
+    kda: f64,
+    headshot_pct: f64,
+    adr: f64,
+}
@@ -0,0 +1,14 @@
+#[derive(sqlx::FromRow)]
+#[derive(juniper::GraphQLObject)]
+pub struct LolStatChampionData {
+    champion_id: i32,
+    games: i32,
+    wins: i32,
+    kills: i32,
+    deaths: i32,
+    assists: i32,
+    kda: f64,
+    cs_per_min: f64,
+    damage_per_min: f64,
+    avg_vision_score: f64,
+}
@@ -0,0 +1,16 @@
+#[derive(sqlx::FromRow)]
+#[derive(juniper::GraphQLObject)]
+pub struct ValorantStatAggregateData {
+    // Either the agent (character) ID or the map ID depending on how the stats were grouped.
+    key: String,
+    games: i32,
+    wins: i32,
+    rounds: i32,
+    kills: i32,
+    deaths: i32,
+    assists: i32,
+    kda: f64,
+    headshot_pct: f64,
+    avg_combat_score: f64,
+    avg_damage_per_round: f64,
+}
@@ -0,0 +1,18 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(sqlx::FromRow)]
+#[derive(juniper::GraphQLObject)]
+pub struct WowStatEncounterData {
+    match_uuid: Uuid,
+    start_tm: DateTime<Utc>,
+    encounter_id: i32,
+    encounter_name: String,
+    difficulty: i32,
+    success: bool,
+    duration_seconds: f64,
+    damage_dealt: f64,
+    heals: f64,
+    dps: f64,
+    hps: f64,
+}
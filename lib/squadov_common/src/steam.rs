@@ -1,6 +1,9 @@
 pub mod rabbitmq;
 pub mod api;
 pub mod db;
+pub mod openid;
+pub mod friends;
+pub mod mock;
 
 use crate::SquadOvError;
 use serde::Serialize;
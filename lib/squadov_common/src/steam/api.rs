@@ -5,12 +5,19 @@ use crate::{
 };
 use url::Url;
 use reqwest::{StatusCode};
+use chrono::{DateTime, Utc, TimeZone};
 
 #[derive(Clone, Deserialize, Debug)]
 pub struct SteamApiConfig {
     pub api_key: String,
     pub requests: usize,
     pub seconds: u64,
+    #[serde(default="default_steam_api_url")]
+    pub base_url: String,
+}
+
+fn default_steam_api_url() -> String {
+    String::from("http://api.steampowered.com")
 }
 
 pub struct SteamApiClient {
@@ -35,6 +42,36 @@ pub struct SteamPlayerSummary {
     pub avatarfull: String
 }
 
+// GetFriendList isn't wrapped in "response" like the other endpoints.
+#[derive(Deserialize)]
+pub struct SteamFriendListResponse {
+    friendslist: SteamFriendList,
+}
+
+#[derive(Deserialize)]
+pub struct SteamFriendList {
+    #[serde(default)]
+    friends: Vec<SteamFriend>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SteamFriend {
+    pub steamid: String,
+    pub relationship: String,
+    // Unix timestamp.
+    pub friend_since: i64,
+}
+
+impl SteamFriend {
+    pub fn friend_since_tm(&self) -> Option<DateTime<Utc>> {
+        if self.friend_since > 0 {
+            Some(Utc.timestamp(self.friend_since, 0))
+        } else {
+            None
+        }
+    }
+}
+
 impl SteamApiClient {
     pub fn new(config: &SteamApiConfig) -> SteamApiClient {
         Self {
@@ -59,7 +96,7 @@ impl SteamApiClient {
     pub async fn get_player_summaries(&self, steam_ids: &[i64]) -> Result<Vec<SteamPlayerSummary>, SquadOvError> {
         self.limiter.consume().await?;
 
-        let mut url = self.build_url("http://api.steampowered.com/ISteamUser/GetPlayerSummaries/v0002")?;
+        let mut url = self.build_url(&format!("{}/ISteamUser/GetPlayerSummaries/v0002", &self.config.base_url))?;
         url.query_pairs_mut().append_pair("steamids", &steam_ids.iter().map(|x| {
             format!("{}", x)
         }).collect::<Vec<String>>().join(","));
@@ -76,4 +113,52 @@ impl SteamApiClient {
         let data = resp.json::<GenericSteamApiResponse<SteamPlayerSummaryResponse>>().await?;
         Ok(data.response.players)
     }
-}
\ No newline at end of file
+
+    // Returns None if the profile is private (Steam won't give out the friend list).
+    pub async fn get_friend_list(&self, steam_id: i64) -> Result<Option<Vec<SteamFriend>>, SquadOvError> {
+        self.limiter.consume().await?;
+
+        let mut url = self.build_url(&format!("{}/ISteamUser/GetFriendList/v0001", &self.config.base_url))?;
+        url.query_pairs_mut()
+            .append_pair("steamid", &format!("{}", steam_id))
+            .append_pair("relationship", "friend");
+
+        let client = self.create_http_client()?;
+        let resp = client.get(url.as_str())
+            .send()
+            .await?;
+
+        if resp.status() == StatusCode::UNAUTHORIZED {
+            return Ok(None);
+        } else if resp.status() != StatusCode::OK {
+            return Err(SquadOvError::InternalError(format!("Failed to get Steam friend list {} - {}", resp.status().as_u16(), resp.text().await?)));
+        }
+
+        let data = resp.json::<SteamFriendListResponse>().await?;
+        Ok(Some(data.friendslist.friends))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_friend_list() {
+        let data: SteamFriendListResponse = serde_json::from_str(r#"{
+            "friendslist": {
+                "friends": [
+                    {"steamid": "76561197960265731", "relationship": "friend", "friend_since": 1596397200},
+                    {"steamid": "76561197960265732", "relationship": "friend", "friend_since": 0}
+                ]
+            }
+        }"#).unwrap();
+        assert_eq!(data.friendslist.friends.len(), 2);
+        assert_eq!(data.friendslist.friends[0].friend_since_tm(), Some(Utc.ymd(2020, 8, 2).and_hms(19, 40, 0)));
+        assert_eq!(data.friendslist.friends[1].friend_since_tm(), None);
+
+        // Steam leaves out the array entirely when there are no friends.
+        let data: SteamFriendListResponse = serde_json::from_str(r#"{"friendslist": {}}"#).unwrap();
+        assert!(data.friendslist.friends.is_empty());
+    }
+}
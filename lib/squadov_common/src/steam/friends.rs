@@ -0,0 +1,359 @@
+use crate::{
+    SquadOvError,
+    steam::api::SteamFriend,
+};
+use serde::{Serialize, Deserialize};
+use sqlx::{Executor, Transaction, Postgres, postgres::PgPool};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+// How many CS:GO matches two users need to have been in together before they get suggested to each other.
+pub const MIN_MATCHES_PLAYED_TOGETHER: i64 = 3;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all="camelCase")]
+pub struct FriendSuggestionSettings {
+    // Whether the user can be suggested to their Steam friends.
+    pub suggest_to_steam_friends: bool,
+    // Whether the user can be suggested to people they've played (CS:GO) with.
+    pub suggest_to_played_with: bool,
+}
+
+// Suggestions are opt-in.
+impl Default for FriendSuggestionSettings {
+    fn default() -> Self {
+        Self {
+            suggest_to_steam_friends: false,
+            suggest_to_played_with: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FriendSuggestionCandidate {
+    pub user_id: i64,
+    pub steam_friend: bool,
+    pub matches_played_together: i64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct FriendSuggestion {
+    pub user_id: i64,
+    pub username: String,
+    pub steam_name: Option<String>,
+    pub profile_image_url: Option<String>,
+    pub steam_friend: bool,
+    pub matches_played_together: i64,
+}
+
+// Steam friends come first and then whoever the user has played with the most.
+pub fn merge_friend_suggestion_candidates(steam_friends: &[i64], played_with: &[(i64, i64)], min_matches: i64) -> Vec<FriendSuggestionCandidate> {
+    let mut candidates: HashMap<i64, FriendSuggestionCandidate> = HashMap::new();
+    for user_id in steam_friends {
+        candidates.insert(*user_id, FriendSuggestionCandidate{
+            user_id: *user_id,
+            steam_friend: true,
+            matches_played_together: 0,
+        });
+    }
+
+    for (user_id, count) in played_with {
+        if let Some(c) = candidates.get_mut(user_id) {
+            c.matches_played_together = *count;
+        } else if *count >= min_matches {
+            candidates.insert(*user_id, FriendSuggestionCandidate{
+                user_id: *user_id,
+                steam_friend: false,
+                matches_played_together: *count,
+            });
+        }
+    }
+
+    let mut ret: Vec<FriendSuggestionCandidate> = candidates.into_iter().map(|(_, x)| { x }).collect();
+    ret.sort_by(|a, b| {
+        b.steam_friend.cmp(&a.steam_friend)
+            .then(b.matches_played_together.cmp(&a.matches_played_together))
+            .then(a.user_id.cmp(&b.user_id))
+    });
+    ret
+}
+
+pub async fn get_steam_friend_lists_that_need_sync<'a, T>(ex: T, steam_ids: &[i64]) -> Result<Vec<i64>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar::<_, i64>(
+            "
+            SELECT s.steam_id
+            FROM UNNEST($1::BIGINT[]) AS s(steam_id)
+            LEFT JOIN squadov.steam_friend_list_syncs AS sfls
+                ON sfls.steam_id = s.steam_id
+            WHERE sfls.last_sync_time IS NULL
+                OR sfls.last_sync_time < NOW() - INTERVAL '1 day'
+            "
+        )
+            .bind(steam_ids)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+// Replaces the stored friend list. Private profiles (no friend list) keep no friends on our end either.
+pub async fn store_steam_friend_list(ex: &mut Transaction<'_, Postgres>, steam_id: i64, friends: Option<&[SteamFriend]>) -> Result<(), SquadOvError> {
+    sqlx::query(
+        "
+        DELETE FROM squadov.steam_friends
+        WHERE steam_id = $1
+        "
+    )
+        .bind(steam_id)
+        .execute(&mut *ex)
+        .await?;
+
+    let mut friend_ids: Vec<i64> = vec![];
+    let mut friend_since: Vec<Option<DateTime<Utc>>> = vec![];
+    for f in friends.unwrap_or(&[]) {
+        friend_ids.push(f.steamid.parse::<i64>()?);
+        friend_since.push(f.friend_since_tm());
+    }
+
+    if !friend_ids.is_empty() {
+        sqlx::query(
+            "
+            INSERT INTO squadov.steam_friends (
+                steam_id,
+                friend_steam_id,
+                friend_since
+            )
+            SELECT $1, f.friend_steam_id, f.friend_since
+            FROM UNNEST($2::BIGINT[], $3::TIMESTAMPTZ[]) AS f(friend_steam_id, friend_since)
+            ON CONFLICT DO NOTHING
+            "
+        )
+            .bind(steam_id)
+            .bind(&friend_ids)
+            .bind(&friend_since)
+            .execute(&mut *ex)
+            .await?;
+    }
+
+    sqlx::query(
+        "
+        INSERT INTO squadov.steam_friend_list_syncs (
+            steam_id,
+            last_sync_time,
+            is_private
+        ) VALUES (
+            $1,
+            NOW(),
+            $2
+        )
+        ON CONFLICT (steam_id) DO UPDATE SET
+            last_sync_time = EXCLUDED.last_sync_time,
+            is_private = EXCLUDED.is_private
+        "
+    )
+        .bind(steam_id)
+        .bind(friends.is_none())
+        .execute(&mut *ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_friend_suggestion_settings<'a, T>(ex: T, user_id: i64) -> Result<FriendSuggestionSettings, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, (bool, bool)>(
+            "
+            SELECT suggest_to_steam_friends, suggest_to_played_with
+            FROM squadov.user_friend_suggestion_settings
+            WHERE user_id = $1
+            "
+        )
+            .bind(user_id)
+            .fetch_optional(ex)
+            .await?
+            .map(|x| {
+                FriendSuggestionSettings{
+                    suggest_to_steam_friends: x.0,
+                    suggest_to_played_with: x.1,
+                }
+            })
+            .unwrap_or_default()
+    )
+}
+
+pub async fn update_friend_suggestion_settings<'a, T>(ex: T, user_id: i64, settings: &FriendSuggestionSettings) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        INSERT INTO squadov.user_friend_suggestion_settings (
+            user_id,
+            suggest_to_steam_friends,
+            suggest_to_played_with
+        ) VALUES (
+            $1,
+            $2,
+            $3
+        )
+        ON CONFLICT (user_id) DO UPDATE SET
+            suggest_to_steam_friends = EXCLUDED.suggest_to_steam_friends,
+            suggest_to_played_with = EXCLUDED.suggest_to_played_with
+        "
+    )
+        .bind(user_id)
+        .bind(settings.suggest_to_steam_friends)
+        .bind(settings.suggest_to_played_with)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+// Friendship is mutual on Steam so the user's own friend list gets filled in with the other side of public friend lists.
+// That way users with private profiles still get matched up with friends whose profiles are public. Only Steam accounts
+// the users have verified count since anyone can report a Steam account as theirs.
+async fn get_steam_friend_user_ids(ex: &PgPool, user_id: i64) -> Result<Vec<i64>, SquadOvError> {
+    Ok(
+        sqlx::query_scalar::<_, i64>(
+            "
+            WITH me(steam_id) AS (
+                SELECT steam_id
+                FROM squadov.view_verified_steam_user_links
+                WHERE user_id = $1
+            ), friends(steam_id) AS (
+                SELECT sf.friend_steam_id
+                FROM me
+                INNER JOIN squadov.steam_friends AS sf
+                    ON sf.steam_id = me.steam_id
+                UNION
+                SELECT sf.steam_id
+                FROM me
+                INNER JOIN squadov.steam_friends AS sf
+                    ON sf.friend_steam_id = me.steam_id
+                INNER JOIN squadov.steam_friend_list_syncs AS sfls
+                    ON sfls.steam_id = sf.steam_id
+                WHERE NOT sfls.is_private
+            )
+            SELECT DISTINCT vsul.user_id
+            FROM friends AS f
+            INNER JOIN squadov.view_verified_steam_user_links AS vsul
+                ON vsul.steam_id = f.steam_id
+            INNER JOIN squadov.user_friend_suggestion_settings AS ufss
+                ON ufss.user_id = vsul.user_id
+            WHERE vsul.user_id != $1
+                AND ufss.suggest_to_steam_friends
+            "
+        )
+            .bind(user_id)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+// Users with a verified Steam account that show up in the player list of the user's CS:GO matches along with the
+// number of matches they were in together.
+async fn get_csgo_played_with_user_ids(ex: &PgPool, user_id: i64) -> Result<Vec<(i64, i64)>, SquadOvError> {
+    Ok(
+        sqlx::query_as::<_, (i64, i64)>(
+            "
+            SELECT vsul.user_id, COUNT(DISTINCT cmv.match_uuid)
+            FROM squadov.csgo_match_views AS cmv
+            INNER JOIN squadov.csgo_event_container AS cec
+                ON cec.view_uuid = cmv.view_uuid
+            INNER JOIN squadov.csgo_event_container_players AS ccp
+                ON ccp.container_id = cec.id
+            INNER JOIN squadov.view_verified_steam_user_links AS vsul
+                ON vsul.steam_id = ccp.steam_id
+            INNER JOIN squadov.user_friend_suggestion_settings AS ufss
+                ON ufss.user_id = vsul.user_id
+            WHERE cmv.user_id = $1
+                AND cmv.match_uuid IS NOT NULL
+                AND vsul.user_id != $1
+                AND ufss.suggest_to_played_with
+            GROUP BY vsul.user_id
+            "
+        )
+            .bind(user_id)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+// People that the user isn't already in a squad with.
+pub async fn get_friend_suggestions(ex: &PgPool, user_id: i64, limit: usize) -> Result<Vec<FriendSuggestion>, SquadOvError> {
+    let steam_friends = get_steam_friend_user_ids(ex, user_id).await?;
+    let played_with = get_csgo_played_with_user_ids(ex, user_id).await?;
+    let candidates = merge_friend_suggestion_candidates(&steam_friends, &played_with, MIN_MATCHES_PLAYED_TOGETHER);
+    if candidates.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let candidate_ids: Vec<i64> = candidates.iter().map(|x| { x.user_id }).collect();
+    let mut details: HashMap<i64, (String, Option<String>, Option<String>)> = sqlx::query_as::<_, (i64, String, Option<String>, Option<String>)>(
+        "
+        SELECT DISTINCT ON (u.id) u.id, u.username, suc.steam_name, suc.profile_image_url
+        FROM UNNEST($2::BIGINT[]) AS c(user_id)
+        INNER JOIN squadov.users AS u
+            ON u.id = c.user_id
+        LEFT JOIN squadov.view_verified_steam_user_links AS vsul
+            ON vsul.user_id = u.id
+        LEFT JOIN squadov.steam_users_cache AS suc
+            ON suc.steam_id = vsul.steam_id
+        WHERE NOT EXISTS (
+            SELECT 1
+            FROM squadov.squad_role_assignments AS me
+            INNER JOIN squadov.squad_role_assignments AS them
+                ON them.squad_id = me.squad_id
+            WHERE me.user_id = $1
+                AND them.user_id = u.id
+        )
+        ORDER BY u.id, suc.last_sync_time DESC NULLS LAST
+        "
+    )
+        .bind(user_id)
+        .bind(&candidate_ids)
+        .fetch_all(ex)
+        .await?
+        .into_iter()
+        .map(|x| { (x.0, (x.1, x.2, x.3)) })
+        .collect();
+
+    Ok(
+        candidates.into_iter()
+            .filter_map(|c| {
+                let (username, steam_name, profile_image_url) = details.remove(&c.user_id)?;
+                Some(FriendSuggestion{
+                    user_id: c.user_id,
+                    username,
+                    steam_name,
+                    profile_image_url,
+                    steam_friend: c.steam_friend,
+                    matches_played_together: c.matches_played_together,
+                })
+            })
+            .take(limit)
+            .collect()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_friend_suggestion_candidates() {
+        let candidates = merge_friend_suggestion_candidates(&[5, 2], &[(2, 1), (7, 3), (8, 10), (9, 2)], 3);
+        assert_eq!(candidates, vec![
+            FriendSuggestionCandidate{user_id: 2, steam_friend: true, matches_played_together: 1},
+            FriendSuggestionCandidate{user_id: 5, steam_friend: true, matches_played_together: 0},
+            FriendSuggestionCandidate{user_id: 8, steam_friend: false, matches_played_together: 10},
+            FriendSuggestionCandidate{user_id: 7, steam_friend: false, matches_played_together: 3},
+        ]);
+    }
+}
@@ -0,0 +1,311 @@
+// A small in-memory stand-in for the parts of the Steam Web API that we use (GetPlayerSummaries and GetFriendList)
+// so that the Steam integration can be tested without real Steam accounts. Point SteamApiConfig::base_url at
+// SteamMockServer::base_url.
+//
+// Friendships are mutual like they are on Steam. Players with private profiles get a 401 from GetFriendList
+// and requests without the right API key get a 403.
+use crate::SquadOvError;
+use actix_web::{
+    web,
+    App,
+    HttpRequest,
+    HttpResponse,
+    HttpServer,
+    dev::ServerHandle,
+};
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SteamMockPlayer {
+    pub steamid: String,
+    pub personaname: String,
+    #[serde(default)]
+    pub avatarfull: String,
+    #[serde(default)]
+    pub private: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SteamMockFriendship {
+    pub a: String,
+    pub b: String,
+    // Unix timestamp.
+    #[serde(default)]
+    pub friend_since: i64,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SteamMockFixtures {
+    // Any key is accepted if not set.
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub players: Vec<SteamMockPlayer>,
+    #[serde(default)]
+    pub friendships: Vec<SteamMockFriendship>,
+}
+
+#[derive(Deserialize)]
+struct SteamMockPrivacyRequest {
+    private: bool,
+}
+
+#[derive(Default)]
+struct SteamMockState {
+    api_key: Option<String>,
+    players: Mutex<HashMap<String, SteamMockPlayer>>,
+    friendships: Mutex<Vec<SteamMockFriendship>>,
+    requests: Mutex<HashMap<String, usize>>,
+}
+
+impl SteamMockState {
+    fn check_key(&self, query: &[(String, String)]) -> bool {
+        match (&self.api_key, query_value(query, "key")) {
+            (Some(expected), Some(key)) => expected == key,
+            (None, Some(_)) => true,
+            _ => false,
+        }
+    }
+
+    fn player_summaries(&self, steam_ids: &str) -> HttpResponse {
+        let players = self.players.lock().unwrap();
+        let summaries: Vec<serde_json::Value> = steam_ids.split(',')
+            .filter_map(|id| { players.get(id.trim()) })
+            .map(|p| {
+                json!({
+                    "steamid": &p.steamid,
+                    "communityvisibilitystate": if p.private { 1 } else { 3 },
+                    "personaname": &p.personaname,
+                    "avatarfull": &p.avatarfull,
+                })
+            })
+            .collect();
+
+        HttpResponse::Ok().json(json!({
+            "response": {
+                "players": summaries,
+            }
+        }))
+    }
+
+    fn friend_list(&self, steam_id: &str) -> HttpResponse {
+        if self.players.lock().unwrap().get(steam_id).map(|x| { x.private }).unwrap_or(false) {
+            return HttpResponse::Unauthorized().finish();
+        }
+
+        let friends: Vec<serde_json::Value> = self.friendships.lock().unwrap().iter()
+            .filter_map(|f| {
+                let other = if f.a == steam_id {
+                    &f.b
+                } else if f.b == steam_id {
+                    &f.a
+                } else {
+                    return None;
+                };
+
+                Some(json!({
+                    "steamid": other,
+                    "relationship": "friend",
+                    "friend_since": f.friend_since,
+                }))
+            })
+            .collect();
+
+        // Steam leaves out the array when there's nothing in it.
+        if friends.is_empty() {
+            HttpResponse::Ok().json(json!({
+                "friendslist": {}
+            }))
+        } else {
+            HttpResponse::Ok().json(json!({
+                "friendslist": {
+                    "friends": friends,
+                }
+            }))
+        }
+    }
+
+    fn add_friendship(&self, friendship: SteamMockFriendship) {
+        let mut friendships = self.friendships.lock().unwrap();
+        let exists = friendships.iter().any(|f| {
+            (f.a == friendship.a && f.b == friendship.b) || (f.a == friendship.b && f.b == friendship.a)
+        });
+
+        if !exists {
+            friendships.push(friendship);
+        }
+    }
+
+    fn remove_friendship(&self, a: &str, b: &str) {
+        self.friendships.lock().unwrap().retain(|f| {
+            !((f.a == a && f.b == b) || (f.a == b && f.b == a))
+        });
+    }
+
+    fn set_private(&self, steam_id: &str, private: bool) -> bool {
+        match self.players.lock().unwrap().get_mut(steam_id) {
+            Some(p) => {
+                p.private = private;
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+fn query_value<'a>(query: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    query.iter().find(|(k, _)| { k == key }).map(|(_, v)| { v.as_str() })
+}
+
+async fn handle_steam_api(state: &SteamMockState, req: &HttpRequest, path: &str) -> HttpResponse {
+    let query: Vec<(String, String)> = url::form_urlencoded::parse(req.query_string().as_bytes()).into_owned().collect();
+    if !state.check_key(&query) {
+        return HttpResponse::Forbidden().finish();
+    }
+
+    match path {
+        "ISteamUser/GetPlayerSummaries/v0002" => match query_value(&query, "steamids") {
+            Some(ids) => state.player_summaries(ids),
+            None => HttpResponse::BadRequest().finish(),
+        },
+        "ISteamUser/GetFriendList/v0001" => match query_value(&query, "steamid") {
+            Some(id) => state.friend_list(id),
+            None => HttpResponse::BadRequest().finish(),
+        },
+        _ => HttpResponse::NotFound().finish(),
+    }
+}
+
+// Things that happen on Steam's side (making friends, changing privacy settings) rather than through the API.
+async fn handle_mock_action(state: &SteamMockState, req: &HttpRequest, path: &str, body: &[u8]) -> HttpResponse {
+    let parts: Vec<&str> = path.split('/').collect();
+    match (req.method().as_str(), parts.as_slice()) {
+        ("GET", ["players"]) => HttpResponse::Ok().json(state.players.lock().unwrap().values().cloned().collect::<Vec<SteamMockPlayer>>()),
+        ("POST", ["players"]) => match serde_json::from_slice::<SteamMockPlayer>(body) {
+            Ok(player) => {
+                state.players.lock().unwrap().insert(player.steamid.clone(), player);
+                HttpResponse::NoContent().finish()
+            },
+            Err(_) => HttpResponse::BadRequest().finish(),
+        },
+        ("POST", ["players", steam_id, "privacy"]) => match serde_json::from_slice::<SteamMockPrivacyRequest>(body) {
+            Ok(req) => if state.set_private(steam_id, req.private) {
+                HttpResponse::NoContent().finish()
+            } else {
+                HttpResponse::NotFound().finish()
+            },
+            Err(_) => HttpResponse::BadRequest().finish(),
+        },
+        ("GET", ["friendships"]) => HttpResponse::Ok().json(state.friendships.lock().unwrap().clone()),
+        ("POST", ["friendships"]) => match serde_json::from_slice::<SteamMockFriendship>(body) {
+            Ok(friendship) => {
+                state.add_friendship(friendship);
+                HttpResponse::NoContent().finish()
+            },
+            Err(_) => HttpResponse::BadRequest().finish(),
+        },
+        ("DELETE", ["friendships", a, b]) => {
+            state.remove_friendship(a, b);
+            HttpResponse::NoContent().finish()
+        },
+        _ => HttpResponse::NotFound().finish(),
+    }
+}
+
+async fn handle_request(req: HttpRequest, body: web::Bytes, state: web::Data<Arc<SteamMockState>>) -> HttpResponse {
+    let path = req.path().trim_start_matches('/').to_string();
+    *state.requests.lock().unwrap().entry(format!("{} {}", req.method(), &path)).or_insert(0) += 1;
+
+    if let Some(action) = path.strip_prefix("__mock/") {
+        handle_mock_action(&state, &req, action, &body).await
+    } else {
+        handle_steam_api(&state, &req, &path).await
+    }
+}
+
+pub struct SteamMockServer {
+    addr: SocketAddr,
+    handle: ServerHandle,
+    state: Arc<SteamMockState>,
+}
+
+impl SteamMockServer {
+    // Use port 0 in the bind address to get a random port.
+    pub async fn start(fixtures: Option<&Path>, bind: &str) -> Result<Self, SquadOvError> {
+        let fixtures: SteamMockFixtures = match fixtures {
+            Some(x) => serde_json::from_slice(&std::fs::read(x)?)?,
+            None => SteamMockFixtures::default(),
+        };
+
+        let state = Arc::new(SteamMockState{
+            api_key: fixtures.api_key,
+            ..SteamMockState::default()
+        });
+
+        for p in fixtures.players {
+            state.players.lock().unwrap().insert(p.steamid.clone(), p);
+        }
+
+        for f in fixtures.friendships {
+            state.add_friendship(f);
+        }
+
+        let server_state = state.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(web::Data::new(server_state.clone()))
+                .default_service(web::to(handle_request))
+        })
+            .workers(1)
+            .bind(bind)?;
+
+        let addr = server.addrs().first().cloned().ok_or_else(|| { SquadOvError::InternalError(String::from("Steam mock server has no address")) })?;
+        let server = server.run();
+        let handle = server.handle();
+        tokio::task::spawn(server);
+
+        Ok(Self {
+            addr,
+            handle,
+            state,
+        })
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    // Number of requests received for the method and path, e.g. "GET ISteamUser/GetFriendList/v0001".
+    pub fn request_count(&self, method_path: &str) -> usize {
+        self.state.requests.lock().unwrap().get(method_path).cloned().unwrap_or(0)
+    }
+
+    pub fn add_player(&self, player: SteamMockPlayer) {
+        self.state.players.lock().unwrap().insert(player.steamid.clone(), player);
+    }
+
+    pub fn add_friendship(&self, a: &str, b: &str, friend_since: i64) {
+        self.state.add_friendship(SteamMockFriendship{
+            a: a.to_string(),
+            b: b.to_string(),
+            friend_since,
+        });
+    }
+
+    pub fn remove_friendship(&self, a: &str, b: &str) {
+        self.state.remove_friendship(a, b);
+    }
+
+    // Returns false if the player doesn't exist.
+    pub fn set_private(&self, steam_id: &str, private: bool) -> bool {
+        self.state.set_private(steam_id, private)
+    }
+
+    pub async fn stop(&self) {
+        self.handle.stop(true).await;
+    }
+}
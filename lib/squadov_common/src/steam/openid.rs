@@ -0,0 +1,94 @@
+use crate::SquadOvError;
+use url::Url;
+use std::collections::HashMap;
+
+const STEAM_OPENID_URL: &'static str = "https://steamcommunity.com/openid/login";
+const STEAM_OPENID_NS: &'static str = "http://specs.openid.net/auth/2.0";
+const STEAM_OPENID_IDENTIFIER_SELECT: &'static str = "http://specs.openid.net/auth/2.0/identifier_select";
+const STEAM_CLAIMED_ID_PREFIX: &'static str = "https://steamcommunity.com/openid/id/";
+const STEAM_OPENID_REQUIRED_SIGNED_FIELDS: &'static [&'static str] = &["op_endpoint", "claimed_id", "identity", "return_to", "response_nonce"];
+
+/// Builds the URL to send the user to so that they can sign in with Steam. Steam doesn't support OAuth
+/// so we have to go through OpenID 2.0 instead. Note that Steam will send the user back to the return_to
+/// URL as-is (with the OpenID parameters appended) so any state needs to be embedded in the return URL.
+pub fn build_steam_openid_login_url(return_to: &str, realm: &str) -> Result<String, SquadOvError> {
+    let mut url = Url::parse(STEAM_OPENID_URL)?;
+    url.query_pairs_mut()
+        .append_pair("openid.ns", STEAM_OPENID_NS)
+        .append_pair("openid.mode", "checkid_setup")
+        .append_pair("openid.return_to", return_to)
+        .append_pair("openid.realm", realm)
+        .append_pair("openid.identity", STEAM_OPENID_IDENTIFIER_SELECT)
+        .append_pair("openid.claimed_id", STEAM_OPENID_IDENTIFIER_SELECT);
+    Ok(url.to_string())
+}
+
+fn extract_steam_id_from_claimed_id(claimed_id: &str) -> Result<i64, SquadOvError> {
+    if !claimed_id.starts_with(STEAM_CLAIMED_ID_PREFIX) {
+        return Err(SquadOvError::BadRequest);
+    }
+    Ok(claimed_id[STEAM_CLAIMED_ID_PREFIX.len()..].parse::<i64>()?)
+}
+
+/// Checks everything about the OpenID parameters that Steam passed back to the return URL that we can check
+/// without asking Steam. Returns the Steam ID that's being claimed. This does NOT verify the signature.
+pub fn check_steam_openid_response_params(params: &HashMap<String, String>, expected_return_to: &str) -> Result<i64, SquadOvError> {
+    if params.get("openid.mode").map(|x| { x.as_str() }) != Some("id_res") {
+        return Err(SquadOvError::BadRequest);
+    }
+
+    // Steam only vouches for the response if it actually came from Steam's endpoint.
+    if params.get("openid.op_endpoint").map(|x| { x.as_str() }) != Some(STEAM_OPENID_URL) {
+        return Err(SquadOvError::Unauthorized);
+    }
+
+    // Make sure someone isn't trying to replay a response that was meant for some other site or some other login attempt.
+    // The expected return URL contains the state we generated when starting the login so this needs to be an exact match.
+    let return_to = params.get("openid.return_to").ok_or(SquadOvError::BadRequest)?;
+    if return_to != expected_return_to {
+        return Err(SquadOvError::Unauthorized);
+    }
+
+    // The signature only covers the fields listed in openid.signed so anything we rely on has to be in there.
+    // Otherwise the claimed ID could be swapped out on a validly signed response.
+    let signed: Vec<&str> = params.get("openid.signed").ok_or(SquadOvError::BadRequest)?.split(',').collect();
+    if STEAM_OPENID_REQUIRED_SIGNED_FIELDS.iter().any(|x| { !signed.contains(x) }) {
+        return Err(SquadOvError::Unauthorized);
+    }
+
+    let claimed_id = params.get("openid.claimed_id").ok_or(SquadOvError::BadRequest)?;
+    if params.get("openid.identity") != Some(claimed_id) {
+        return Err(SquadOvError::Unauthorized);
+    }
+    extract_steam_id_from_claimed_id(claimed_id)
+}
+
+/// Verifies the OpenID parameters that Steam passed back to the return URL by asking Steam to
+/// check the signature for us. Returns the user's Steam ID if the response is valid.
+pub async fn verify_steam_openid_response(params: &HashMap<String, String>, expected_return_to: &str) -> Result<i64, SquadOvError> {
+    let steam_id = check_steam_openid_response_params(params, expected_return_to)?;
+
+    let mut body = params.clone();
+    body.insert(String::from("openid.mode"), String::from("check_authentication"));
+
+    let client = reqwest::ClientBuilder::new().build()?;
+    let result = client
+        .post(STEAM_OPENID_URL)
+        .form(&body)
+        .send()
+        .await?;
+
+    let status = result.status().as_u16();
+    if status != 200 {
+        return Err(SquadOvError::InternalError(format!("Failed to verify Steam OpenID response [{}]: {}", status, result.text().await?)));
+    }
+
+    // The response is in the key-value form encoding: one "key:value" per line.
+    let text = result.text().await?;
+    let is_valid = text.lines().any(|x| { x.trim() == "is_valid:true" });
+    if !is_valid {
+        return Err(SquadOvError::Unauthorized);
+    }
+
+    Ok(steam_id)
+}
@@ -6,6 +6,7 @@ use crate::{
     steam::{
         api::SteamApiClient,
         db,
+        friends,
     },
 };
 use sqlx::postgres::{PgPool};
@@ -19,6 +20,9 @@ pub enum SteamTask {
     ProfileSync {
         steam_ids: Vec<i64>,
     },
+    FriendSync {
+        steam_ids: Vec<i64>,
+    },
 }
 
 pub struct SteamApiRabbitmqInterface {
@@ -44,6 +48,13 @@ impl SteamApiRabbitmqInterface {
         })?, RABBITMQ_DEFAULT_PRIORITY, STEAM_MAX_AGE_SECONDS).await;
         Ok(())
     }
+
+    pub async fn request_sync_steam_friends(&self, ids: &[i64]) -> Result<(), SquadOvError> {
+        self.rmq.publish(&self.mqconfig.steam_queue, serde_json::to_vec(&SteamTask::FriendSync{
+            steam_ids: ids.to_vec(),
+        })?, RABBITMQ_DEFAULT_PRIORITY, STEAM_MAX_AGE_SECONDS).await;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -64,6 +75,22 @@ impl RabbitMqListener for SteamApiRabbitmqInterface {
                     tx.commit().await?;
                 }
             },
+            SteamTask::FriendSync{steam_ids} => {
+                // Friend lists don't change often enough to be worth pulling more than once a day.
+                // One bad account shouldn't keep the rest of the friend lists from syncing.
+                for steam_id in friends::get_steam_friend_lists_that_need_sync(&*self.db, &steam_ids).await? {
+                    let friend_list = match self.client.get_friend_list(steam_id).await {
+                        Ok(x) => x,
+                        Err(err) => {
+                            log::warn!("Failed to get Steam friend list for {}: {:?}", steam_id, err);
+                            continue;
+                        }
+                    };
+                    let mut tx = self.db.begin().await?;
+                    friends::store_steam_friend_list(&mut tx, steam_id, friend_list.as_deref()).await?;
+                    tx.commit().await?;
+                }
+            },
         };
         Ok(())
     }
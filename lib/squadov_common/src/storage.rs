@@ -1,3 +1,6 @@
+pub mod usage;
+pub use usage::*;
+
 use serde::Deserialize;
 use std::collections::HashMap;
 use async_std::sync::RwLock;
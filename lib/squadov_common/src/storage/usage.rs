@@ -0,0 +1,757 @@
+// Keeps track of how many bytes each user (and by extension, each squad) has in cloud storage. Sizes get recorded when
+// the object gets stored (or processed) and are periodically re-measured against the storage backend since that's the
+// only source of truth. VODs that are shared to a squad count towards that squad's usage on top of the owner's.
+use crate::{
+    SquadOvError,
+    SquadOvGames,
+    VodSegmentId,
+    subscriptions::{self, SquadOvSubTiers},
+};
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::{Serialize, Deserialize};
+use sqlx::{Executor, Postgres};
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
+use uuid::Uuid;
+
+const GIGABYTE: i64 = 1024 * 1024 * 1024;
+// Number of cleanup suggestions to send back with a quota error.
+pub const STORAGE_CLEANUP_SUGGESTION_COUNT: i64 = 10;
+
+#[derive(Display, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all="snake_case")]
+pub enum StorageResourceType {
+    #[display(fmt="vod")]
+    Vod,
+    #[display(fmt="clip")]
+    Clip,
+    #[display(fmt="thumbnail")]
+    Thumbnail,
+    #[display(fmt="combat_log")]
+    CombatLog,
+}
+
+impl FromStr for StorageResourceType {
+    type Err = SquadOvError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "vod" => StorageResourceType::Vod,
+            "clip" => StorageResourceType::Clip,
+            "thumbnail" => StorageResourceType::Thumbnail,
+            "combat_log" => StorageResourceType::CombatLog,
+            _ => return Err(SquadOvError::BadRequest),
+        })
+    }
+}
+
+// Squads get the quota of their best active team subscription (or the basic quota if they don't have one).
+pub fn get_storage_quota_bytes(tier: &SquadOvSubTiers) -> i64 {
+    match tier {
+        SquadOvSubTiers::Basic => 10 * GIGABYTE,
+        SquadOvSubTiers::Silver => 100 * GIGABYTE,
+        SquadOvSubTiers::Gold => 500 * GIGABYTE,
+        SquadOvSubTiers::Diamond => 2048 * GIGABYTE,
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all="camelCase")]
+pub struct StorageUsageBreakdown {
+    pub game: Option<SquadOvGames>,
+    pub resource_type: StorageResourceType,
+    pub bytes: i64,
+    pub count: i64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct StorageUsageSummary {
+    pub used_bytes: i64,
+    pub quota_bytes: i64,
+    pub breakdown: Vec<StorageUsageBreakdown>,
+}
+
+impl StorageUsageSummary {
+    pub fn new(breakdown: Vec<StorageUsageBreakdown>, quota_bytes: i64) -> Self {
+        Self {
+            used_bytes: breakdown.iter().map(|x| { x.bytes }).sum(),
+            quota_bytes,
+            breakdown,
+        }
+    }
+
+    pub fn is_over_quota(&self) -> bool {
+        self.used_bytes >= self.quota_bytes
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all="camelCase")]
+pub struct StorageCleanupSuggestion {
+    pub video_uuid: Uuid,
+    pub is_clip: bool,
+    pub game: Option<SquadOvGames>,
+    pub bytes: i64,
+    pub tm: Option<DateTime<Utc>>,
+}
+
+// Sent back to the client (as part of the error) when an upload gets rejected.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all="camelCase")]
+pub struct StorageQuotaExceeded {
+    // Set when it's the squad that's out of space rather than the user.
+    pub squad_id: Option<i64>,
+    pub used_bytes: i64,
+    pub quota_bytes: i64,
+    pub suggestions: Vec<StorageCleanupSuggestion>,
+}
+
+#[derive(Clone, Debug)]
+pub struct StorageResource {
+    pub resource_type: StorageResourceType,
+    pub resource_key: String,
+    pub video_uuid: Option<Uuid>,
+    pub partition_id: Option<String>,
+    pub bucket: Option<String>,
+    pub bytes: i64,
+}
+
+impl StorageResource {
+    // VOD keys are the segment's path in the bucket (see VodSegmentId::get_fname).
+    pub fn vod_segment(&self) -> Option<VodSegmentId> {
+        if self.resource_type == StorageResourceType::CombatLog {
+            return None;
+        }
+
+        let parts: Vec<&str> = self.resource_key.split('/').collect();
+        if parts.len() != 3 {
+            return None;
+        }
+
+        Some(VodSegmentId{
+            video_uuid: Uuid::parse_str(parts[0]).ok()?,
+            quality: parts[1].to_string(),
+            segment_name: parts[2].to_string(),
+        })
+    }
+
+    // None for VODs and for the row that tracks the rest of the combat log's partition.
+    pub fn combat_log_object_key(&self) -> Option<&str> {
+        if self.resource_type != StorageResourceType::CombatLog || self.partition_id.as_deref() == Some(self.resource_key.as_str()) {
+            return None;
+        }
+        Some(&self.resource_key)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct StorageResourceRow {
+    resource_type: String,
+    resource_key: String,
+    video_uuid: Option<Uuid>,
+    partition_id: Option<String>,
+    bucket: Option<String>,
+    bytes: i64,
+}
+
+impl TryFrom<StorageResourceRow> for StorageResource {
+    type Error = SquadOvError;
+
+    fn try_from(x: StorageResourceRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            resource_type: StorageResourceType::from_str(&x.resource_type)?,
+            resource_key: x.resource_key,
+            video_uuid: x.video_uuid,
+            partition_id: x.partition_id,
+            bucket: x.bucket,
+            bytes: x.bytes,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct StorageUsageBreakdownRow {
+    game: Option<i32>,
+    resource_type: String,
+    bytes: i64,
+    count: i64,
+}
+
+fn game_from_db(game: Option<i32>) -> Option<SquadOvGames> {
+    game.map(|x| { SquadOvGames::try_from(x).unwrap_or(SquadOvGames::Unknown) })
+}
+
+fn breakdown_from_rows(rows: Vec<StorageUsageBreakdownRow>) -> Result<Vec<StorageUsageBreakdown>, SquadOvError> {
+    rows.into_iter().map(|x| {
+        Ok(StorageUsageBreakdown{
+            game: game_from_db(x.game),
+            resource_type: StorageResourceType::from_str(&x.resource_type)?,
+            bytes: x.bytes,
+            count: x.count,
+        })
+    }).collect()
+}
+
+// Records the size of a segment (the VOD itself, its preview, or its thumbnail) that's been stored for the VOD.
+// The owner, game, and whether this is a clip all come from the VOD.
+pub async fn track_vod_segment_storage_usage<'a, T>(ex: T, segment: &VodSegmentId, bucket: &str, bytes: i64) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        INSERT INTO squadov.storage_usage (
+            resource_type,
+            resource_key,
+            user_id,
+            video_uuid,
+            game,
+            bucket,
+            bytes,
+            tracked_tm
+        )
+        SELECT
+            CASE
+                WHEN $4 THEN 'thumbnail'
+                WHEN v.is_clip THEN 'clip'
+                ELSE 'vod'
+            END,
+            $2,
+            u.id,
+            v.video_uuid,
+            m.game,
+            $3,
+            $5,
+            NOW()
+        FROM squadov.vods AS v
+        INNER JOIN squadov.users AS u
+            ON u.uuid = v.user_uuid
+        LEFT JOIN squadov.matches AS m
+            ON m.uuid = v.match_uuid
+        WHERE v.video_uuid = $1
+        ON CONFLICT (resource_type, resource_key) DO UPDATE
+            SET bytes = EXCLUDED.bytes,
+                bucket = EXCLUDED.bucket,
+                tracked_tm = EXCLUDED.tracked_tm
+        "
+    )
+        .bind(&segment.video_uuid)
+        .bind(segment.get_fname())
+        .bind(bucket)
+        .bind(segment.segment_name == "thumbnail.jpg")
+        .bind(bytes)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+// Combat logs get written out in many small pieces so the size keeps growing as the log gets processed. We don't
+// know which game a combat log is for at this point so they only get broken down by type.
+// The combat log parser tracks each object it writes (resource_key is the object key) so that retries don't count
+// an object twice. Everything else in the partition (e.g. reports) gets tracked in a row keyed by the partition ID.
+pub async fn track_combat_log_storage_usage<'a, T>(ex: T, partition_id: &str, resource_key: &str, bytes: i64) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        INSERT INTO squadov.storage_usage (
+            resource_type,
+            resource_key,
+            user_id,
+            partition_id,
+            bytes,
+            tracked_tm
+        )
+        SELECT 'combat_log', $2, cl.owner_id, cl.partition_id, $3, NOW()
+        FROM squadov.combat_logs AS cl
+        WHERE cl.partition_id = $1
+        ON CONFLICT (resource_type, resource_key) DO UPDATE
+            SET bytes = EXCLUDED.bytes,
+                tracked_tm = EXCLUDED.tracked_tm
+        "
+    )
+        .bind(partition_id)
+        .bind(resource_key)
+        .bind(bytes)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+// Bytes in the partition that are already tracked object by object.
+pub async fn get_tracked_combat_log_object_bytes<'a, T>(ex: T, partition_id: &str) -> Result<i64, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar(
+            "
+            SELECT COALESCE(SUM(bytes), 0)::BIGINT
+            FROM squadov.storage_usage
+            WHERE resource_type = 'combat_log'
+                AND partition_id = $1
+                AND resource_key <> partition_id
+            "
+        )
+            .bind(partition_id)
+            .fetch_one(ex)
+            .await?
+    )
+}
+
+pub async fn untrack_storage_resource<'a, T>(ex: T, resource_type: StorageResourceType, resource_key: &str) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        DELETE FROM squadov.storage_usage
+        WHERE resource_type = $1
+            AND resource_key = $2
+        "
+    )
+        .bind(format!("{}", resource_type))
+        .bind(resource_key)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+// For when the VOD gets removed from cloud storage (the VOD itself might stick around as a local VOD).
+pub async fn untrack_vod_storage_usage<'a, T>(ex: T, video_uuid: &Uuid) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        DELETE FROM squadov.storage_usage
+        WHERE video_uuid = $1
+        "
+    )
+        .bind(video_uuid)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_user_storage_usage_breakdown<'a, T>(ex: T, user_id: i64) -> Result<Vec<StorageUsageBreakdown>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    breakdown_from_rows(
+        sqlx::query_as::<_, StorageUsageBreakdownRow>(
+            "
+            SELECT game, resource_type, SUM(bytes)::BIGINT AS bytes, COUNT(*) AS count
+            FROM squadov.storage_usage
+            WHERE user_id = $1
+            GROUP BY game, resource_type
+            ORDER BY bytes DESC
+            "
+        )
+            .bind(user_id)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+// Everything that's been shared to the squad, no matter who owns it.
+pub async fn get_squad_storage_usage_breakdown<'a, T>(ex: T, squad_id: i64) -> Result<Vec<StorageUsageBreakdown>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    breakdown_from_rows(
+        sqlx::query_as::<_, StorageUsageBreakdownRow>(
+            "
+            SELECT su.game, su.resource_type, SUM(su.bytes)::BIGINT AS bytes, COUNT(*) AS count
+            FROM squadov.storage_usage AS su
+            WHERE su.video_uuid IN (
+                SELECT DISTINCT smvc.video_uuid
+                FROM squadov.share_match_vod_connections AS smvc
+                WHERE smvc.dest_squad_id = $1
+                    AND smvc.video_uuid IS NOT NULL
+            )
+            GROUP BY su.game, su.resource_type
+            ORDER BY bytes DESC
+            "
+        )
+            .bind(squad_id)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+// Oldest VODs/clips first, skipping anything the user favorited (either the VOD itself or its match).
+pub async fn get_user_storage_cleanup_suggestions<'a, T>(ex: T, user_id: i64, limit: i64) -> Result<Vec<StorageCleanupSuggestion>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    #[derive(sqlx::FromRow)]
+    struct Row {
+        video_uuid: Uuid,
+        is_clip: bool,
+        game: Option<i32>,
+        bytes: i64,
+        tm: Option<DateTime<Utc>>,
+    }
+
+    Ok(
+        sqlx::query_as::<_, Row>(
+            "
+            SELECT
+                v.video_uuid,
+                v.is_clip,
+                MAX(su.game) AS game,
+                SUM(su.bytes)::BIGINT AS bytes,
+                COALESCE(v.end_time, v.start_time) AS tm
+            FROM squadov.storage_usage AS su
+            INNER JOIN squadov.vods AS v
+                ON v.video_uuid = su.video_uuid
+            LEFT JOIN squadov.user_favorite_vods AS ufv
+                ON ufv.video_uuid = v.video_uuid
+                    AND ufv.user_id = su.user_id
+            LEFT JOIN squadov.user_favorite_matches AS ufm
+                ON ufm.match_uuid = v.match_uuid
+                    AND ufm.user_id = su.user_id
+            WHERE su.user_id = $1
+                AND ufv.video_uuid IS NULL
+                AND ufm.match_uuid IS NULL
+            GROUP BY v.video_uuid, v.is_clip, v.start_time, v.end_time
+            ORDER BY tm ASC NULLS FIRST
+            LIMIT $2
+            "
+        )
+            .bind(user_id)
+            .bind(limit)
+            .fetch_all(ex)
+            .await?
+            .into_iter()
+            .map(|x| {
+                StorageCleanupSuggestion{
+                    video_uuid: x.video_uuid,
+                    is_clip: x.is_clip,
+                    game: game_from_db(x.game),
+                    bytes: x.bytes,
+                    tm: x.tm,
+                }
+            })
+            .collect()
+    )
+}
+
+pub async fn get_user_storage_usage_summary<'a, T>(ex: T, user_id: i64) -> Result<StorageUsageSummary, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres> + Copy
+{
+    let tier = subscriptions::get_user_sub_tier(ex, user_id).await?;
+    Ok(StorageUsageSummary::new(
+        get_user_storage_usage_breakdown(ex, user_id).await?,
+        get_storage_quota_bytes(&tier),
+    ))
+}
+
+// The best tier out of the squad's active team subscriptions.
+async fn get_squad_team_sub_tier<'a, T>(ex: T, squad_id: i64) -> Result<Option<SquadOvSubTiers>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        subscriptions::get_team_subscriptions_for_squad(ex, squad_id).await?
+            .into_iter()
+            .filter(|x| { x.is_active() })
+            .map(|x| { x.tier })
+            .fold(None, |acc, x| { if acc.as_ref().map(|y| { x > *y }).unwrap_or(true) { Some(x) } else { acc } })
+    )
+}
+
+pub async fn get_squad_storage_usage_summary<'a, T>(ex: T, squad_id: i64) -> Result<StorageUsageSummary, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres> + Copy
+{
+    let tier = get_squad_team_sub_tier(ex, squad_id).await?.unwrap_or(SquadOvSubTiers::Basic);
+    Ok(StorageUsageSummary::new(
+        get_squad_storage_usage_breakdown(ex, squad_id).await?,
+        get_storage_quota_bytes(&tier),
+    ))
+}
+
+// Returns an error with the user's usage and what they could delete to free up space if they're over their quota.
+pub async fn check_user_storage_quota<'a, T>(ex: T, user_id: i64) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres> + Copy
+{
+    let summary = get_user_storage_usage_summary(ex, user_id).await?;
+    if summary.is_over_quota() {
+        return Err(SquadOvError::QuotaExceeded(Box::new(StorageQuotaExceeded{
+            squad_id: None,
+            used_bytes: summary.used_bytes,
+            quota_bytes: summary.quota_bytes,
+            suggestions: get_user_storage_cleanup_suggestions(ex, user_id, STORAGE_CLEANUP_SUGGESTION_COUNT).await?,
+        })));
+    }
+    Ok(())
+}
+
+// Squads don't own anything themselves so there's nothing to suggest. Squads without a team subscription were sharing
+// before squads had a quota so they're grandfathered in for now and only get a warning in the logs.
+pub async fn check_squad_storage_quota<'a, T>(ex: T, squad_id: i64) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres> + Copy
+{
+    let summary = get_squad_storage_usage_summary(ex, squad_id).await?;
+    if get_squad_team_sub_tier(ex, squad_id).await?.is_none() {
+        if summary.is_over_quota() {
+            log::warn!("Squad {} is over the basic storage quota ({} / {} bytes) without a team subscription", squad_id, summary.used_bytes, summary.quota_bytes);
+        }
+        return Ok(());
+    }
+
+    if summary.is_over_quota() {
+        return Err(SquadOvError::QuotaExceeded(Box::new(StorageQuotaExceeded{
+            squad_id: Some(squad_id),
+            used_bytes: summary.used_bytes,
+            quota_bytes: summary.quota_bytes,
+            suggestions: vec![],
+        })));
+    }
+    Ok(())
+}
+
+// Resources that have gone the longest without being checked against the storage backend.
+pub async fn get_storage_resources_to_reconcile<'a, T>(ex: T, limit: i64) -> Result<Vec<StorageResource>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, StorageResourceRow>(
+        "
+        SELECT resource_type, resource_key, video_uuid, partition_id, bucket, bytes
+        FROM squadov.storage_usage
+        ORDER BY reconciled_tm ASC NULLS FIRST, tracked_tm ASC
+        LIMIT $1
+        "
+    )
+        .bind(limit)
+        .fetch_all(ex)
+        .await?
+        .into_iter()
+        .map(|x| { x.try_into() })
+        .collect()
+}
+
+pub async fn mark_storage_resource_reconciled<'a, T>(ex: T, resource: &StorageResource, bytes: i64) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        UPDATE squadov.storage_usage
+        SET bytes = $3,
+            reconciled_tm = NOW()
+        WHERE resource_type = $1
+            AND resource_key = $2
+        "
+    )
+        .bind(format!("{}", resource.resource_type))
+        .bind(&resource.resource_key)
+        .bind(bytes)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+#[derive(Clone, Debug)]
+pub struct UntrackedCloudVod {
+    pub video_uuid: Uuid,
+    pub bucket: String,
+    pub raw_container_format: String,
+    pub has_fastify: bool,
+    pub has_preview: bool,
+    pub has_thumbnail: bool,
+}
+
+// Cloud VODs that have nothing tracked at all (e.g. they were uploaded before we started keeping track). The ones we
+// haven't tried to measure yet come first, then whichever ones we tried the longest time ago.
+pub async fn get_untracked_cloud_vods<'a, T>(ex: T, limit: i64) -> Result<Vec<UntrackedCloudVod>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    #[derive(sqlx::FromRow)]
+    struct Row {
+        video_uuid: Uuid,
+        bucket: String,
+        raw_container_format: String,
+        has_fastify: bool,
+        has_preview: bool,
+        has_thumbnail: bool,
+    }
+
+    Ok(
+        sqlx::query_as::<_, Row>(
+            "
+            SELECT
+                v.video_uuid,
+                vsc.spec AS bucket,
+                v.raw_container_format,
+                vm.has_fastify,
+                vm.has_preview,
+                vt.video_uuid IS NOT NULL AS has_thumbnail
+            FROM squadov.vod_storage_copies AS vsc
+            INNER JOIN squadov.vods AS v
+                ON v.video_uuid = vsc.video_uuid
+            INNER JOIN squadov.vod_metadata AS vm
+                ON vm.video_uuid = v.video_uuid
+                    AND vm.id = 'source'
+            LEFT JOIN squadov.vod_thumbnails AS vt
+                ON vt.video_uuid = v.video_uuid
+            LEFT JOIN squadov.storage_usage_reconcile_attempts AS sura
+                ON sura.video_uuid = v.video_uuid
+            WHERE vsc.loc = 0
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM squadov.storage_usage AS su
+                    WHERE su.video_uuid = v.video_uuid
+                )
+            ORDER BY sura.last_reconcile_attempt_tm ASC NULLS FIRST
+            LIMIT $1
+            "
+        )
+            .bind(limit)
+            .fetch_all(ex)
+            .await?
+            .into_iter()
+            .map(|x| {
+                UntrackedCloudVod{
+                    video_uuid: x.video_uuid,
+                    bucket: x.bucket,
+                    raw_container_format: x.raw_container_format,
+                    has_fastify: x.has_fastify,
+                    has_preview: x.has_preview,
+                    has_thumbnail: x.has_thumbnail,
+                }
+            })
+            .collect()
+    )
+}
+
+pub async fn mark_cloud_vod_reconcile_attempts<'a, T>(ex: T, video_uuids: &[Uuid]) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        INSERT INTO squadov.storage_usage_reconcile_attempts (
+            video_uuid,
+            last_reconcile_attempt_tm
+        )
+        SELECT inp.video_uuid, NOW()
+        FROM UNNEST($1::UUID[]) AS inp(video_uuid)
+        ON CONFLICT (video_uuid) DO UPDATE
+            SET last_reconcile_attempt_tm = EXCLUDED.last_reconcile_attempt_tm
+        "
+    )
+        .bind(video_uuids)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+// Combat logs without a row for the rest of the partition that are old enough that the parser should be done writing them.
+pub async fn get_untracked_combat_logs<'a, T>(ex: T, older_than: &DateTime<Utc>, limit: i64) -> Result<Vec<String>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar(
+            "
+            SELECT cl.partition_id
+            FROM squadov.combat_logs AS cl
+            WHERE cl.start_time < $1
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM squadov.storage_usage AS su
+                    WHERE su.resource_type = 'combat_log'
+                        AND su.resource_key = cl.partition_id
+                )
+            LIMIT $2
+            "
+        )
+            .bind(older_than)
+            .bind(limit)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resource_type_round_trip() {
+        for t in &[StorageResourceType::Vod, StorageResourceType::Clip, StorageResourceType::Thumbnail, StorageResourceType::CombatLog] {
+            assert_eq!(StorageResourceType::from_str(&format!("{}", t)).unwrap(), *t);
+            assert_eq!(serde_json::to_value(t).unwrap(), serde_json::Value::String(format!("{}", t)));
+        }
+    }
+
+    #[test]
+    fn test_resource_vod_segment() {
+        let segment = VodSegmentId{
+            video_uuid: Uuid::new_v4(),
+            quality: String::from("source"),
+            segment_name: String::from("fastify.mp4"),
+        };
+
+        let mut resource = StorageResource{
+            resource_type: StorageResourceType::Vod,
+            resource_key: segment.get_fname(),
+            video_uuid: Some(segment.video_uuid.clone()),
+            partition_id: None,
+            bucket: None,
+            bytes: 0,
+        };
+        let parsed = resource.vod_segment().unwrap();
+        assert_eq!(parsed.video_uuid, segment.video_uuid);
+        assert_eq!(parsed.quality, segment.quality);
+        assert_eq!(parsed.segment_name, segment.segment_name);
+
+        resource.resource_type = StorageResourceType::CombatLog;
+        assert!(resource.vod_segment().is_none());
+    }
+
+    #[test]
+    fn test_resource_combat_log_object_key() {
+        let mut resource = StorageResource{
+            resource_type: StorageResourceType::CombatLog,
+            resource_key: String::from("form=Raw/partition=abc/combatlog_1_def.gz"),
+            video_uuid: None,
+            partition_id: Some(String::from("abc")),
+            bucket: None,
+            bytes: 0,
+        };
+        assert_eq!(resource.combat_log_object_key(), Some("form=Raw/partition=abc/combatlog_1_def.gz"));
+
+        resource.resource_key = String::from("abc");
+        assert!(resource.combat_log_object_key().is_none());
+    }
+
+    #[test]
+    fn test_quota_grows_with_tier() {
+        assert!(get_storage_quota_bytes(&SquadOvSubTiers::Basic) < get_storage_quota_bytes(&SquadOvSubTiers::Silver));
+        assert!(get_storage_quota_bytes(&SquadOvSubTiers::Silver) < get_storage_quota_bytes(&SquadOvSubTiers::Gold));
+        assert!(get_storage_quota_bytes(&SquadOvSubTiers::Gold) < get_storage_quota_bytes(&SquadOvSubTiers::Diamond));
+    }
+
+    #[test]
+    fn test_summary_over_quota() {
+        let summary = StorageUsageSummary::new(vec![
+            StorageUsageBreakdown{game: Some(SquadOvGames::Valorant), resource_type: StorageResourceType::Vod, bytes: 6 * GIGABYTE, count: 3},
+            StorageUsageBreakdown{game: None, resource_type: StorageResourceType::CombatLog, bytes: 4 * GIGABYTE, count: 1},
+        ], 10 * GIGABYTE);
+        assert_eq!(summary.used_bytes, 10 * GIGABYTE);
+        assert!(summary.is_over_quota());
+        assert!(!StorageUsageSummary::new(vec![], 10 * GIGABYTE).is_over_quota());
+    }
+}
@@ -8,6 +8,8 @@ pub mod webhook;
 pub mod subscription;
 pub mod currency;
 pub mod customer;
+pub mod mock;
+pub mod events;
 
 use crate::{
     SquadOvError,
@@ -60,6 +62,13 @@ pub struct StripeApiConfig {
     secret_api_key: String,
     api_version: StripeApiVersion,
     pub webhook_secret: String,
+    // Only needs to be changed to point at a mock Stripe server.
+    #[serde(default="default_stripe_base_url")]
+    pub base_url: String,
+}
+
+fn default_stripe_base_url() -> String {
+    String::from("https://api.stripe.com")
 }
 
 impl StripeApiClient {
@@ -79,8 +88,8 @@ impl StripeApiClient {
         }
     }
 
-    pub fn build_url(path: &str) -> String {
-        format!("https://api.stripe.com/{}", path)
+    pub fn build_url(&self, path: &str) -> String {
+        format!("{}/{}", &self.config.base_url, path)
     }
 
     pub async fn send_request(&self, request: Request) -> Result<Response, SquadOvError> {
@@ -62,9 +62,11 @@ pub struct StripeCheckoutDiscount {
     pub promotion_code: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Default)]
 pub struct StripeCheckoutSubscriptionData {
     pub trial_period_days: Option<i64>,
+    // Copied onto the subscription so that later subscription events have it too.
+    pub metadata: HashMap<String, String>,
 }
 
 #[derive(Serialize)]
@@ -79,6 +81,7 @@ pub struct StripeCreateSessionRequest {
     pub discounts: Vec<StripeCheckoutDiscount>,
     pub subscription_data: Option<StripeCheckoutSubscriptionData>,
     pub allow_promotion_codes: bool,
+    pub metadata: HashMap<String, String>,
 }
 
 impl StripeCreateSessionRequest {
@@ -138,6 +141,18 @@ impl StripeCreateSessionRequest {
                     ("subscription_data[trial_period_days]".to_string(), format!("{}", td)),
                 );
             }
+
+            for (k, v) in &s.metadata {
+                tuples.push(
+                    (format!("subscription_data[metadata][{}]", k), v.clone()),
+                );
+            }
+        }
+
+        for (k, v) in &self.metadata {
+            tuples.push(
+                (format!("metadata[{}]", k), v.clone()),
+            );
         }
 
         HashMap::from_iter(tuples)
@@ -146,6 +161,11 @@ impl StripeCreateSessionRequest {
 
 #[derive(Deserialize)]
 pub struct StripeCheckoutSession {
+    pub id: String,
+    pub mode: Option<StripeCheckoutSessionMode>,
+    pub payment_status: Option<String>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
     pub client_reference_id: Option<String>,
     pub customer: Option<String>,
     pub url: Option<String>,
@@ -156,7 +176,7 @@ impl StripeApiClient {
     pub async fn create_a_session(&self, request: StripeCreateSessionRequest) -> Result<StripeCheckoutSession, SquadOvError> {
         Ok(
             self.send_request(
-                self.client.post(&Self::build_url("v1/checkout/sessions"))
+                self.client.post(&self.build_url("v1/checkout/sessions"))
                     .form(&request.to_map())
                     .build()?
             )
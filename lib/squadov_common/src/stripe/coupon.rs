@@ -14,7 +14,7 @@ impl StripeApiClient {
     pub async fn retrieve_a_coupon(&self, request: &str) -> Result<StripeCoupon, SquadOvError> {
         Ok(
             self.send_request(
-                self.client.get(&Self::build_url(&format!("v1/coupons/{}", request)))
+                self.client.get(&self.build_url(&format!("v1/coupons/{}", request)))
                     .build()?
             )
                 .await?
@@ -16,7 +16,7 @@ impl StripeApiClient {
     pub async fn retrieve_a_customer(&self, id: &str) -> Result<StripeCustomer, SquadOvError> {
         Ok(
             self.send_request(
-                self.client.get(&Self::build_url(&format!("v1/customers/{}", id)))
+                self.client.get(&self.build_url(&format!("v1/customers/{}", id)))
                     .build()?
             )
                 .await?
@@ -27,7 +27,7 @@ impl StripeApiClient {
     pub async fn create_a_customer(&self, email: &str, name: &str) -> Result<StripeCustomer, SquadOvError> {
         Ok(
             self.send_request(
-                self.client.post(&Self::build_url("v1/customers"))
+                self.client.post(&self.build_url("v1/customers"))
                     .form(&HashMap::<String, String>::from_iter(vec![
                         ("email".to_string(), email.to_string()),
                         ("name".to_string(), name.to_string())
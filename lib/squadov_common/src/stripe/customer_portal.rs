@@ -38,7 +38,7 @@ impl StripeApiClient {
     pub async fn create_a_portal_session(&self, request: StripeCreatePortalSessionRequest) -> Result<StripePortalSession, SquadOvError> {
         Ok(
             self.send_request(
-                self.client.post(&Self::build_url("v1/billing_portal/sessions"))
+                self.client.post(&self.build_url("v1/billing_portal/sessions"))
                     .form(&request.to_map())
                     .build()?
             )
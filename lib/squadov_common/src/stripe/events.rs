@@ -0,0 +1,356 @@
+// Every Stripe webhook event gets stored with its raw payload before it gets handled so nothing is lost if handling
+// fails. Handling happens off of a RabbitMQ queue and is idempotent by event ID: an event can only be claimed for
+// processing while it's pending or failed (or stuck in processing for too long).
+use crate::{
+    SquadOvError,
+    rabbitmq::{RABBITMQ_DEFAULT_PRIORITY, RabbitMqInterface, RabbitMqConfig},
+};
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::{Serialize, Deserialize};
+use sqlx::{Executor, Postgres};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::convert::{TryFrom, TryInto};
+
+// Attempts (including the first) before an event is left as failed for an admin to replay.
+pub const STRIPE_WEBHOOK_MAX_ATTEMPTS: i32 = 8;
+// Anything still marked as processing after this long is assumed to have died with its worker.
+pub const STRIPE_WEBHOOK_PROCESSING_TIMEOUT_SECONDS: i64 = 600;
+const STRIPE_WEBHOOK_MAX_AGE_SECONDS: i64 = 86400 * 3;
+
+#[derive(Display, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all="lowercase")]
+pub enum StripeWebhookEventStatus {
+    #[display(fmt="pending")]
+    Pending,
+    #[display(fmt="processing")]
+    Processing,
+    #[display(fmt="succeeded")]
+    Succeeded,
+    #[display(fmt="failed")]
+    Failed,
+    // Event types we don't do anything with.
+    #[display(fmt="ignored")]
+    Ignored,
+}
+
+impl FromStr for StripeWebhookEventStatus {
+    type Err = SquadOvError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "pending" => StripeWebhookEventStatus::Pending,
+            "processing" => StripeWebhookEventStatus::Processing,
+            "succeeded" => StripeWebhookEventStatus::Succeeded,
+            "failed" => StripeWebhookEventStatus::Failed,
+            "ignored" => StripeWebhookEventStatus::Ignored,
+            _ => return Err(SquadOvError::BadRequest),
+        })
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct StripeWebhookEventRecord {
+    pub event_id: String,
+    pub event_type: String,
+    pub status: StripeWebhookEventStatus,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub received_tm: DateTime<Utc>,
+    pub last_attempt_tm: Option<DateTime<Utc>>,
+    pub processed_tm: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub payload: Option<serde_json::Value>,
+}
+
+#[derive(sqlx::FromRow)]
+struct StripeWebhookEventRow {
+    event_id: String,
+    event_type: String,
+    status: String,
+    attempts: i32,
+    last_error: Option<String>,
+    received_tm: DateTime<Utc>,
+    last_attempt_tm: Option<DateTime<Utc>>,
+    processed_tm: Option<DateTime<Utc>>,
+    payload: Option<serde_json::Value>,
+}
+
+impl TryFrom<StripeWebhookEventRow> for StripeWebhookEventRecord {
+    type Error = SquadOvError;
+
+    fn try_from(x: StripeWebhookEventRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            event_id: x.event_id,
+            event_type: x.event_type,
+            status: StripeWebhookEventStatus::from_str(&x.status)?,
+            attempts: x.attempts,
+            last_error: x.last_error,
+            received_tm: x.received_tm,
+            last_attempt_tm: x.last_attempt_tm,
+            processed_tm: x.processed_tm,
+            payload: x.payload,
+        })
+    }
+}
+
+impl StripeWebhookEventStatus {
+    pub fn can_retry(&self, attempts: i32) -> bool {
+        *self == StripeWebhookEventStatus::Failed && attempts < STRIPE_WEBHOOK_MAX_ATTEMPTS
+    }
+}
+
+// Returns false if the event was already stored (i.e. Stripe sent it again).
+pub async fn store_stripe_webhook_event<'a, T>(ex: T, event_id: &str, event_type: &str, payload: &serde_json::Value) -> Result<bool, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query(
+            "
+            INSERT INTO squadov.stripe_webhook_events (
+                event_id,
+                event_type,
+                payload,
+                status,
+                attempts,
+                received_tm
+            ) VALUES (
+                $1,
+                $2,
+                $3,
+                'pending',
+                0,
+                NOW()
+            ) ON CONFLICT DO NOTHING
+            "
+        )
+            .bind(event_id)
+            .bind(event_type)
+            .bind(payload)
+            .execute(ex)
+            .await?
+            .rows_affected() > 0
+    )
+}
+
+// Marks the event as processing and returns it if it can be processed right now. None means some other worker has it,
+// it's already been handled, or it's out of attempts.
+pub async fn claim_stripe_webhook_event<'a, T>(ex: T, event_id: &str) -> Result<Option<StripeWebhookEventRecord>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, StripeWebhookEventRow>(
+        "
+        UPDATE squadov.stripe_webhook_events
+        SET status = 'processing',
+            attempts = attempts + 1,
+            last_attempt_tm = NOW()
+        WHERE event_id = $1
+            AND (
+                status = 'pending'
+                OR (status = 'failed' AND attempts < $2)
+                OR (status = 'processing' AND last_attempt_tm < NOW() - $3 * INTERVAL '1 second')
+            )
+        RETURNING event_id, event_type, status, attempts, last_error, received_tm, last_attempt_tm, processed_tm, payload
+        "
+    )
+        .bind(event_id)
+        .bind(STRIPE_WEBHOOK_MAX_ATTEMPTS)
+        .bind(STRIPE_WEBHOOK_PROCESSING_TIMEOUT_SECONDS as f64)
+        .fetch_optional(ex)
+        .await?
+        .map(|x| { x.try_into() })
+        .transpose()
+}
+
+pub async fn finish_stripe_webhook_event<'a, T>(ex: T, event_id: &str, status: StripeWebhookEventStatus, error: Option<&str>) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        UPDATE squadov.stripe_webhook_events
+        SET status = $2,
+            last_error = $3,
+            processed_tm = CASE WHEN $2 IN ('succeeded', 'ignored') THEN NOW() ELSE processed_tm END
+        WHERE event_id = $1
+        "
+    )
+        .bind(event_id)
+        .bind(format!("{}", status))
+        .bind(error)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+// Puts the events back into the pending state with a fresh set of attempts. Returns the events that exist.
+pub async fn reset_stripe_webhook_events<'a, T>(ex: T, event_ids: &[String]) -> Result<Vec<String>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar(
+            "
+            UPDATE squadov.stripe_webhook_events
+            SET status = 'pending',
+                attempts = 0,
+                last_error = NULL
+            WHERE event_id = ANY($1)
+            RETURNING event_id
+            "
+        )
+            .bind(event_ids)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub async fn get_stripe_webhook_event<'a, T>(ex: T, event_id: &str) -> Result<StripeWebhookEventRecord, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, StripeWebhookEventRow>(
+        "
+        SELECT event_id, event_type, status, attempts, last_error, received_tm, last_attempt_tm, processed_tm, payload
+        FROM squadov.stripe_webhook_events
+        WHERE event_id = $1
+        "
+    )
+        .bind(event_id)
+        .fetch_optional(ex)
+        .await?
+        .ok_or(SquadOvError::NotFound)?
+        .try_into()
+}
+
+// Newest first and without the payload.
+pub async fn list_stripe_webhook_events<'a, T>(ex: T, status: Option<StripeWebhookEventStatus>, event_type: Option<&str>, start: i64, end: i64) -> Result<Vec<StripeWebhookEventRecord>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, StripeWebhookEventRow>(
+        "
+        SELECT event_id, event_type, status, attempts, last_error, received_tm, last_attempt_tm, processed_tm, NULL::JSONB AS payload
+        FROM squadov.stripe_webhook_events
+        WHERE ($1::VARCHAR IS NULL OR status = $1)
+            AND ($2::VARCHAR IS NULL OR event_type = $2)
+        ORDER BY received_tm DESC
+        LIMIT $3 OFFSET $4
+        "
+    )
+        .bind(status.map(|x| { format!("{}", x) }))
+        .bind(event_type)
+        .bind(end - start)
+        .bind(start)
+        .fetch_all(ex)
+        .await?
+        .into_iter()
+        .map(|x| { x.try_into() })
+        .collect()
+}
+
+// Events that should have been handled by now but weren't (e.g. the queue message got lost or the worker died).
+pub async fn get_stale_stripe_webhook_event_ids<'a, T>(ex: T, older_than: &DateTime<Utc>) -> Result<Vec<String>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar(
+            "
+            SELECT event_id
+            FROM squadov.stripe_webhook_events
+            WHERE (
+                (status = 'pending' AND received_tm < $1)
+                OR (status = 'failed' AND attempts < $2 AND last_attempt_tm < $1)
+                OR (status = 'processing' AND last_attempt_tm < NOW() - $3 * INTERVAL '1 second')
+            )
+            ORDER BY received_tm ASC
+            "
+        )
+            .bind(older_than)
+            .bind(STRIPE_WEBHOOK_MAX_ATTEMPTS)
+            .bind(STRIPE_WEBHOOK_PROCESSING_TIMEOUT_SECONDS as f64)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+// Events that failed on every attempt and won't be retried without an admin replaying them.
+pub async fn get_exhausted_stripe_webhook_event_ids<'a, T>(ex: T) -> Result<Vec<String>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar(
+            "
+            SELECT event_id
+            FROM squadov.stripe_webhook_events
+            WHERE status = 'failed'
+                AND attempts >= $1
+            ORDER BY received_tm ASC
+            "
+        )
+            .bind(STRIPE_WEBHOOK_MAX_ATTEMPTS)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum StripeWebhookTask {
+    Process {
+        event_id: String,
+    },
+}
+
+pub struct StripeWebhookEventQueue {
+    mqconfig: RabbitMqConfig,
+    rmq: Arc<RabbitMqInterface>,
+}
+
+impl StripeWebhookEventQueue {
+    pub fn new(mqconfig: RabbitMqConfig, rmq: Arc<RabbitMqInterface>) -> Self {
+        Self {
+            mqconfig,
+            rmq,
+        }
+    }
+
+    pub async fn request_process_event(&self, event_id: &str) -> Result<(), SquadOvError> {
+        self.rmq.publish(&self.mqconfig.stripe_queue, serde_json::to_vec(&StripeWebhookTask::Process{
+            event_id: String::from(event_id),
+        })?, RABBITMQ_DEFAULT_PRIORITY, STRIPE_WEBHOOK_MAX_AGE_SECONDS).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_round_trip() {
+        for s in &[
+            StripeWebhookEventStatus::Pending,
+            StripeWebhookEventStatus::Processing,
+            StripeWebhookEventStatus::Succeeded,
+            StripeWebhookEventStatus::Failed,
+            StripeWebhookEventStatus::Ignored,
+        ] {
+            assert_eq!(StripeWebhookEventStatus::from_str(&format!("{}", s)).unwrap(), *s);
+        }
+        assert!(StripeWebhookEventStatus::from_str("unknown").is_err());
+    }
+
+    #[test]
+    fn test_can_retry() {
+        assert!(StripeWebhookEventStatus::Failed.can_retry(1));
+        assert!(!StripeWebhookEventStatus::Failed.can_retry(STRIPE_WEBHOOK_MAX_ATTEMPTS));
+        assert!(!StripeWebhookEventStatus::Succeeded.can_retry(1));
+    }
+}
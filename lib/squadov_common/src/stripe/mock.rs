@@ -0,0 +1,702 @@
+// A small in-memory stand-in for the parts of the Stripe API that we use so that billing can be tested without
+// a Stripe account. Point StripeApiConfig::base_url at StripeMockServer::base_url. Objects are stored as raw JSON
+// so tests can put whatever state they need (e.g. a subscription going past_due) in with StripeMockServer::upsert.
+//
+// When a webhook target is set, the mock also plays the part of Stripe's webhook delivery: completing a checkout
+// session, renewing/updating/canceling a subscription all send the same signed events that Stripe would. These can
+// be triggered over HTTP too (the /__mock endpoints) so the mock can be driven by hand.
+use crate::{
+    SquadOvError,
+    stripe::webhook::StripeSignature,
+};
+use actix_web::{
+    web,
+    App,
+    HttpRequest,
+    HttpResponse,
+    HttpServer,
+    dev::ServerHandle,
+};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+const MOCK_PERIOD_SECONDS: i64 = 30 * 86400;
+
+// Collections are named after their path in the Stripe API (e.g. products or checkout/sessions).
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct StripeMockFixtures {
+    #[serde(flatten)]
+    pub collections: HashMap<String, Vec<Value>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct StripeMockWebhookTarget {
+    pub url: String,
+    pub secret: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct StripeMockDelivery {
+    pub event_id: String,
+    pub event_type: String,
+    // None if the request didn't make it to the target at all.
+    pub status: Option<u16>,
+}
+
+#[derive(Default)]
+struct StripeMockState {
+    base_url: Mutex<String>,
+    objects: Mutex<HashMap<String, BTreeMap<String, Value>>>,
+    next_id: Mutex<u64>,
+    requests: Mutex<HashMap<String, usize>>,
+    webhook: Mutex<Option<StripeMockWebhookTarget>>,
+    deliveries: Mutex<Vec<StripeMockDelivery>>,
+}
+
+impl StripeMockState {
+    fn base_url(&self) -> String {
+        self.base_url.lock().unwrap().clone()
+    }
+
+    fn generate_id(&self, prefix: &str) -> String {
+        let mut next = self.next_id.lock().unwrap();
+        *next += 1;
+        format!("{}_mock{}", prefix, *next)
+    }
+
+    fn insert(&self, collection: &str, obj: Value) {
+        if let Some(id) = obj.get("id").and_then(|x| { x.as_str() }) {
+            self.objects.lock().unwrap().entry(collection.to_string()).or_default().insert(id.to_string(), obj.clone());
+        }
+    }
+
+    fn get(&self, collection: &str, id: &str) -> Option<Value> {
+        self.objects.lock().unwrap().get(collection).and_then(|x| { x.get(id).cloned() })
+    }
+
+    fn list(&self, collection: &str) -> Vec<Value> {
+        self.objects.lock().unwrap().get(collection).map(|x| { x.values().cloned().collect() }).unwrap_or_default()
+    }
+
+    // Shallow merge of the top level fields like Stripe's update endpoints.
+    fn update(&self, collection: &str, id: &str, changes: serde_json::Map<String, Value>) -> Option<Value> {
+        let mut objects = self.objects.lock().unwrap();
+        let obj = objects.get_mut(collection)?.get_mut(id)?;
+        if let Value::Object(fields) = obj {
+            for (k, v) in changes {
+                fields.insert(k, v);
+            }
+        }
+        Some(obj.clone())
+    }
+
+    fn create_event(&self, event_type: &str, object: Value) -> Value {
+        let event = json!({
+            "id": self.generate_id("evt"),
+            "object": "event",
+            "type": event_type,
+            "created": Utc::now().timestamp(),
+            "data": {
+                "object": object,
+            },
+        });
+        self.insert("events", event.clone());
+        event
+    }
+
+    fn subscription_item(&self, subscription: &str, price: &str, quantity: i64) -> Result<Value, SquadOvError> {
+        Ok(json!({
+            "price": self.get("prices", price).ok_or(SquadOvError::NotFound)?,
+            "quantity": quantity,
+            "subscription": subscription,
+        }))
+    }
+
+    fn create_subscription(&self, customer: &str, items: &[(String, i64)], metadata: serde_json::Map<String, Value>) -> Result<Value, SquadOvError> {
+        let id = self.generate_id("sub");
+        let data = items.iter()
+            .map(|(price, quantity)| { self.subscription_item(&id, price, *quantity) })
+            .collect::<Result<Vec<Value>, SquadOvError>>()?;
+
+        let sub = json!({
+            "id": &id,
+            "object": "subscription",
+            "customer": customer,
+            "status": "active",
+            "current_period_end": Utc::now().timestamp() + MOCK_PERIOD_SECONDS,
+            "metadata": metadata,
+            "items": {
+                "object": "list",
+                "data": data,
+            },
+        });
+        self.insert("subscriptions", sub.clone());
+        Ok(sub)
+    }
+
+    fn create_invoice(&self, subscription: &Value, paid: bool) -> Value {
+        let invoice = json!({
+            "id": self.generate_id("in"),
+            "object": "invoice",
+            "customer": subscription["customer"],
+            "subscription": subscription["id"],
+            "status": if paid { "paid" } else { "open" },
+            "lines": {
+                "object": "list",
+                "data": subscription["items"]["data"],
+            },
+        });
+        self.insert("invoices", invoice.clone());
+        invoice
+    }
+
+    // Pays for the checkout session and returns the events that Stripe would send in the order it'd (usually) send them.
+    fn complete_checkout_session(&self, id: &str) -> Result<Vec<Value>, SquadOvError> {
+        let session = self.get("checkout/sessions", id).ok_or(SquadOvError::NotFound)?;
+        if json_str(&session, &["status"]) == Some("complete") {
+            return Err(SquadOvError::BadRequest);
+        }
+
+        let mut events: Vec<Value> = vec![];
+        let mut changes = serde_json::Map::new();
+        changes.insert(String::from("status"), json!("complete"));
+        changes.insert(String::from("payment_status"), json!("paid"));
+
+        if json_str(&session, &["mode"]) == Some("subscription") {
+            let customer = json_str(&session, &["customer"]).ok_or(SquadOvError::BadRequest)?;
+            let items: Vec<(String, i64)> = session["mock_line_items"].as_array().cloned().unwrap_or_default().iter().filter_map(|x| {
+                Some((x["price"].as_str()?.to_string(), x["quantity"].as_i64().unwrap_or(1)))
+            }).collect();
+            let metadata = session["mock_subscription_metadata"].as_object().cloned().unwrap_or_default();
+
+            let sub = self.create_subscription(customer, &items, metadata)?;
+            let invoice = self.create_invoice(&sub, true);
+            changes.insert(String::from("subscription"), sub["id"].clone());
+            changes.insert(String::from("invoice"), invoice["id"].clone());
+            events.push(self.create_event("customer.subscription.created", sub));
+            events.push(self.create_event("invoice.paid", invoice));
+        }
+
+        let session = self.update("checkout/sessions", id, changes).ok_or(SquadOvError::NotFound)?;
+        events.push(self.create_event("checkout.session.completed", session));
+        Ok(events)
+    }
+
+    // Moves the subscription into the next billing period.
+    fn renew_subscription(&self, id: &str, paid: bool) -> Result<Vec<Value>, SquadOvError> {
+        let sub = self.get("subscriptions", id).ok_or(SquadOvError::NotFound)?;
+        let mut changes = serde_json::Map::new();
+        if paid {
+            let end = sub["current_period_end"].as_i64().unwrap_or_else(|| { Utc::now().timestamp() });
+            changes.insert(String::from("current_period_end"), json!(end + MOCK_PERIOD_SECONDS));
+            changes.insert(String::from("status"), json!("active"));
+        } else {
+            changes.insert(String::from("status"), json!("past_due"));
+        }
+
+        let sub = self.update("subscriptions", id, changes).ok_or(SquadOvError::NotFound)?;
+        let invoice = self.create_invoice(&sub, paid);
+        Ok(vec![
+            self.create_event(if paid { "invoice.paid" } else { "invoice.payment_failed" }, invoice),
+            self.create_event("customer.subscription.updated", sub),
+        ])
+    }
+
+    fn update_subscription(&self, id: &str, form: &[(String, String)]) -> Result<Vec<Value>, SquadOvError> {
+        let sub = self.get("subscriptions", id).ok_or(SquadOvError::NotFound)?;
+        let mut changes = serde_json::Map::new();
+        if let Some(status) = form_value(form, "status") {
+            changes.insert(String::from("status"), json!(status));
+        }
+
+        let metadata = form_map(form, "metadata");
+        if !metadata.is_empty() {
+            let mut merged = sub["metadata"].as_object().cloned().unwrap_or_default();
+            merged.extend(metadata);
+            changes.insert(String::from("metadata"), Value::Object(merged));
+        }
+
+        // Only the quantity of the first item can be changed (e.g. adding seats to a team subscription).
+        if let Some(quantity) = form_value(form, "items[0][quantity]").and_then(|x| { x.parse::<i64>().ok() }) {
+            let mut items = sub["items"].clone();
+            if let Some(first) = items["data"].get_mut(0) {
+                first["quantity"] = json!(quantity);
+            }
+            changes.insert(String::from("items"), items);
+        }
+
+        let sub = self.update("subscriptions", id, changes).ok_or(SquadOvError::NotFound)?;
+        Ok(vec![self.create_event("customer.subscription.updated", sub)])
+    }
+
+    fn cancel_subscription(&self, id: &str) -> Result<Vec<Value>, SquadOvError> {
+        let mut changes = serde_json::Map::new();
+        changes.insert(String::from("status"), json!("canceled"));
+        let sub = self.update("subscriptions", id, changes).ok_or(SquadOvError::NotFound)?;
+        Ok(vec![self.create_event("customer.subscription.deleted", sub)])
+    }
+
+    async fn deliver(&self, events: &[Value]) -> Result<Vec<StripeMockDelivery>, SquadOvError> {
+        let target = self.webhook.lock().unwrap().clone();
+        let target = match target {
+            Some(x) => x,
+            None => return Ok(vec![]),
+        };
+
+        let client = reqwest::Client::new();
+        let mut ret: Vec<StripeMockDelivery> = vec![];
+        for e in events {
+            let (payload, sig) = sign_stripe_webhook_event(e, &target.secret, Utc::now())?;
+            let status = match client.post(&target.url)
+                .header("Stripe-Signature", sig)
+                .header("Content-Type", "application/json")
+                .body(payload)
+                .send()
+                .await {
+                Ok(resp) => Some(resp.status().as_u16()),
+                Err(err) => {
+                    log::warn!("Failed to deliver mock Stripe webhook: {:?}", err);
+                    None
+                }
+            };
+
+            let delivery = StripeMockDelivery{
+                event_id: e["id"].as_str().unwrap_or("").to_string(),
+                event_type: e["type"].as_str().unwrap_or("").to_string(),
+                status,
+            };
+            self.deliveries.lock().unwrap().push(delivery.clone());
+            ret.push(delivery);
+        }
+        Ok(ret)
+    }
+}
+
+fn parse_form(data: &[u8]) -> Vec<(String, String)> {
+    url::form_urlencoded::parse(data).into_owned().collect()
+}
+
+fn form_value<'a>(form: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    form.iter().find(|(k, _)| { k == key }).map(|(_, v)| { v.as_str() })
+}
+
+// Pulls out prefix[key] entries (e.g. metadata[squad_id]) into a map.
+fn form_map(form: &[(String, String)], prefix: &str) -> serde_json::Map<String, Value> {
+    let prefix = format!("{}[", prefix);
+    form.iter()
+        .filter_map(|(k, v)| {
+            let key = k.strip_prefix(&prefix)?.strip_suffix(']')?;
+            if key.contains('[') {
+                None
+            } else {
+                Some((key.to_string(), Value::String(v.clone())))
+            }
+        })
+        .collect()
+}
+
+// Pulls out prefix[i][field] entries (e.g. line_items[0][price]) in order.
+fn form_list(form: &[(String, String)], prefix: &str, field: &str) -> Vec<String> {
+    let mut ret: Vec<String> = vec![];
+    loop {
+        match form_value(form, &format!("{}[{}][{}]", prefix, ret.len(), field)) {
+            Some(x) => ret.push(x.to_string()),
+            None => break,
+        }
+    }
+    ret
+}
+
+fn json_str<'a>(obj: &'a Value, path: &[&str]) -> Option<&'a str> {
+    let mut cur = obj;
+    for p in path {
+        cur = cur.get(p)?;
+    }
+    cur.as_str()
+}
+
+// Supports the subset of the search query language that StripeSearchProductsRequest generates.
+fn matches_search(obj: &Value, query: &str) -> bool {
+    query.split(" AND ").filter(|x| { !x.trim().is_empty() }).all(|clause| {
+        let (field, value) = match clause.split_once(':') {
+            Some(x) => x,
+            None => return false,
+        };
+        let value = value.trim().trim_matches('\'');
+        let field = field.trim();
+        if let Some(key) = field.strip_prefix("metadata['").and_then(|x| { x.strip_suffix("']") }) {
+            json_str(obj, &["metadata", key]) == Some(value)
+        } else {
+            match obj.get(field) {
+                Some(Value::Bool(b)) => b.to_string() == value,
+                Some(Value::String(s)) => s == value,
+                _ => false,
+            }
+        }
+    })
+}
+
+fn list_response(data: Vec<Value>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "object": "list",
+        "has_more": false,
+        "data": data,
+    }))
+}
+
+fn not_found(path: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(json!({
+        "error": {
+            "type": "invalid_request_error",
+            "message": format!("No such resource: {}", path),
+        }
+    }))
+}
+
+fn error_response(path: &str, err: SquadOvError) -> HttpResponse {
+    match err {
+        SquadOvError::NotFound => not_found(path),
+        _ => HttpResponse::BadRequest().json(json!({
+            "error": {
+                "type": "invalid_request_error",
+                "message": format!("{:?}", err),
+            }
+        })),
+    }
+}
+
+fn handle_get(state: &StripeMockState, path: &str, query: &[(String, String)]) -> HttpResponse {
+    match path {
+        "products" => {
+            let active = form_value(query, "active");
+            list_response(state.list("products").into_iter().filter(|x| {
+                active.map(|a| { x.get("active").and_then(|x| { x.as_bool() }).unwrap_or(true).to_string() == a }).unwrap_or(true)
+            }).collect())
+        },
+        "products/search" => {
+            let search = form_value(query, "query").unwrap_or("");
+            list_response(state.list("products").into_iter().filter(|x| { matches_search(x, search) }).collect())
+        },
+        "prices" => list_response(state.list("prices").into_iter().filter(|x| {
+            form_value(query, "product").map(|p| { json_str(x, &["product"]) == Some(p) }).unwrap_or(true) &&
+                form_value(query, "currency").map(|c| { json_str(x, &["currency"]).map(|y| { y.eq_ignore_ascii_case(c) }).unwrap_or(true) }).unwrap_or(true) &&
+                form_value(query, "recurring[interval]").map(|i| { json_str(x, &["recurring", "interval"]) == Some(i) }).unwrap_or(true)
+        }).collect()),
+        "subscriptions" => {
+            // Like Stripe, canceled subscriptions aren't listed by default.
+            let customer = form_value(query, "customer");
+            list_response(state.list("subscriptions").into_iter().filter(|x| {
+                customer.map(|c| { json_str(x, &["customer"]) == Some(c) }).unwrap_or(true) &&
+                    json_str(x, &["status"]) != Some("canceled")
+            }).collect())
+        },
+        "invoices" => list_response(state.list("invoices").into_iter().filter(|x| {
+            form_value(query, "customer").map(|c| { json_str(x, &["customer"]) == Some(c) }).unwrap_or(true) &&
+                form_value(query, "subscription").map(|s| { json_str(x, &["subscription"]) == Some(s) }).unwrap_or(true)
+        }).collect()),
+        _ => {
+            let (collection, id) = match path.rsplit_once('/') {
+                Some(x) => x,
+                None => return not_found(path),
+            };
+
+            match state.get(collection, id) {
+                Some(x) => HttpResponse::Ok().json(x),
+                None => not_found(path),
+            }
+        }
+    }
+}
+
+// Stripe API calls that cause webhooks return the object right away and the events get delivered afterwards.
+async fn handle_post(state: &StripeMockState, path: &str, form: &[(String, String)]) -> HttpResponse {
+    match path {
+        "customers" => {
+            let customer = json!({
+                "id": state.generate_id("cus"),
+                "object": "customer",
+                "email": form_value(form, "email").unwrap_or(""),
+                "name": form_value(form, "name"),
+            });
+            state.insert("customers", customer.clone());
+            HttpResponse::Ok().json(customer)
+        },
+        "checkout/sessions" => {
+            let id = state.generate_id("cs");
+            let prices = form_list(form, "line_items", "price");
+            let line_items: Vec<Value> = prices.iter().enumerate().map(|(i, price)| {
+                json!({
+                    "price": price,
+                    "quantity": form_value(form, &format!("line_items[{}][quantity]", i)).and_then(|x| { x.parse::<i64>().ok() }),
+                })
+            }).collect();
+
+            let session = json!({
+                "id": &id,
+                "object": "checkout.session",
+                "url": format!("{}/checkout/{}", state.base_url(), &id),
+                "mode": form_value(form, "mode"),
+                "status": "open",
+                "customer": form_value(form, "customer"),
+                "client_reference_id": form_value(form, "client_reference_id"),
+                "payment_status": "unpaid",
+                "metadata": form_map(form, "metadata"),
+                "success_url": form_value(form, "success_url"),
+                "cancel_url": form_value(form, "cancel_url"),
+                // Not part of Stripe's response, just so tests can check what was requested.
+                "mock_line_items": line_items,
+                "mock_subscription_metadata": form_map(form, "subscription_data[metadata]"),
+                "mock_trial_period_days": form_value(form, "subscription_data[trial_period_days]"),
+            });
+            state.insert("checkout/sessions", session.clone());
+            HttpResponse::Ok().json(session)
+        },
+        "billing_portal/sessions" => HttpResponse::Ok().json(json!({
+            "id": state.generate_id("bps"),
+            "object": "billing_portal.session",
+            "customer": form_value(form, "customer"),
+            "url": format!("{}/portal", state.base_url()),
+        })),
+        "subscriptions" => {
+            let customer = match form_value(form, "customer") {
+                Some(x) => x,
+                None => return error_response(path, SquadOvError::BadRequest),
+            };
+
+            let quantities = form_list(form, "items", "quantity");
+            let items: Vec<(String, i64)> = form_list(form, "items", "price").into_iter().enumerate().map(|(i, price)| {
+                (price, quantities.get(i).and_then(|x| { x.parse::<i64>().ok() }).unwrap_or(1))
+            }).collect();
+
+            let sub = match state.create_subscription(customer, &items, form_map(form, "metadata")) {
+                Ok(x) => x,
+                Err(err) => return error_response(path, err),
+            };
+            let invoice = state.create_invoice(&sub, true);
+            let events = vec![
+                state.create_event("customer.subscription.created", sub.clone()),
+                state.create_event("invoice.paid", invoice),
+            ];
+
+            match state.deliver(&events).await {
+                Ok(_) => HttpResponse::Ok().json(sub),
+                Err(err) => error_response(path, err),
+            }
+        },
+        _ => {
+            if let Some(id) = path.strip_prefix("subscriptions/") {
+                let result = state.update_subscription(id, form);
+                deliver_and_respond(state, path, result, "subscriptions", id).await
+            } else {
+                not_found(path)
+            }
+        }
+    }
+}
+
+async fn deliver_and_respond(state: &StripeMockState, path: &str, events: Result<Vec<Value>, SquadOvError>, collection: &str, id: &str) -> HttpResponse {
+    let events = match events {
+        Ok(x) => x,
+        Err(err) => return error_response(path, err),
+    };
+
+    if let Err(err) = state.deliver(&events).await {
+        return error_response(path, err);
+    }
+
+    match state.get(collection, id) {
+        Some(x) => HttpResponse::Ok().json(x),
+        None => not_found(path),
+    }
+}
+
+async fn handle_delete(state: &StripeMockState, path: &str) -> HttpResponse {
+    if let Some(id) = path.strip_prefix("subscriptions/") {
+        let result = state.cancel_subscription(id);
+        deliver_and_respond(state, path, result, "subscriptions", id).await
+    } else {
+        not_found(path)
+    }
+}
+
+// Things that happen on Stripe's side (paying for checkout, renewals) rather than through the API.
+async fn handle_mock_action(state: &StripeMockState, path: &str, body: &[u8]) -> HttpResponse {
+    let parts: Vec<&str> = path.split('/').collect();
+    let events = match parts.as_slice() {
+        ["webhook"] => {
+            return match serde_json::from_slice::<StripeMockWebhookTarget>(body) {
+                Ok(target) => {
+                    *state.webhook.lock().unwrap() = Some(target);
+                    HttpResponse::NoContent().finish()
+                },
+                Err(_) => HttpResponse::BadRequest().finish(),
+            };
+        },
+        ["deliveries"] => return HttpResponse::Ok().json(state.deliveries.lock().unwrap().clone()),
+        ["checkout", id, "complete"] => state.complete_checkout_session(id),
+        ["subscriptions", id, "renew"] => state.renew_subscription(id, true),
+        ["subscriptions", id, "fail"] => state.renew_subscription(id, false),
+        ["events", id, "resend"] => state.get("events", id).map(|x| { vec![x] }).ok_or(SquadOvError::NotFound),
+        _ => return not_found(path),
+    };
+
+    let events = match events {
+        Ok(x) => x,
+        Err(err) => return error_response(path, err),
+    };
+
+    match state.deliver(&events).await {
+        Ok(x) => HttpResponse::Ok().json(x),
+        Err(err) => error_response(path, err),
+    }
+}
+
+async fn handle_request(req: HttpRequest, body: web::Bytes, state: web::Data<Arc<StripeMockState>>) -> HttpResponse {
+    let path = req.path().trim_start_matches('/').to_string();
+    *state.requests.lock().unwrap().entry(format!("{} {}", req.method(), &path)).or_insert(0) += 1;
+
+    if let Some(action) = path.strip_prefix("__mock/") {
+        return if req.method() == actix_web::http::Method::POST || req.method() == actix_web::http::Method::GET {
+            handle_mock_action(&state, action, &body).await
+        } else {
+            HttpResponse::MethodNotAllowed().finish()
+        };
+    }
+
+    let path = match path.strip_prefix("v1/") {
+        Some(x) => x,
+        None => return not_found(&path),
+    };
+
+    if req.method() == actix_web::http::Method::GET {
+        handle_get(&state, path, &parse_form(req.query_string().as_bytes()))
+    } else if req.method() == actix_web::http::Method::POST {
+        handle_post(&state, path, &parse_form(&body)).await
+    } else if req.method() == actix_web::http::Method::DELETE {
+        handle_delete(&state, path).await
+    } else {
+        HttpResponse::MethodNotAllowed().finish()
+    }
+}
+
+pub struct StripeMockServer {
+    addr: SocketAddr,
+    handle: ServerHandle,
+    state: Arc<StripeMockState>,
+}
+
+impl StripeMockServer {
+    // Use port 0 in the bind address to get a random port.
+    pub async fn start(fixtures: Option<&Path>, bind: &str) -> Result<Self, SquadOvError> {
+        let fixtures: StripeMockFixtures = match fixtures {
+            Some(x) => serde_json::from_slice(&std::fs::read(x)?)?,
+            None => StripeMockFixtures::default(),
+        };
+
+        let state = Arc::new(StripeMockState::default());
+        for (collection, objects) in fixtures.collections {
+            for obj in objects {
+                state.insert(&collection, obj);
+            }
+        }
+
+        let server_state = state.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(web::Data::new(server_state.clone()))
+                .default_service(web::to(handle_request))
+        })
+            .workers(1)
+            .bind(bind)?;
+
+        let addr = server.addrs().first().cloned().ok_or_else(|| { SquadOvError::InternalError(String::from("Stripe mock server has no address")) })?;
+        *state.base_url.lock().unwrap() = format!("http://{}", addr);
+
+        let server = server.run();
+        let handle = server.handle();
+        tokio::task::spawn(server);
+
+        Ok(Self {
+            addr,
+            handle,
+            state,
+        })
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    // Adds or replaces the object with the same ID.
+    pub fn upsert(&self, collection: &str, obj: Value) {
+        self.state.insert(collection, obj);
+    }
+
+    pub fn get(&self, collection: &str, id: &str) -> Option<Value> {
+        self.state.get(collection, id)
+    }
+
+    pub fn list(&self, collection: &str) -> Vec<Value> {
+        self.state.list(collection)
+    }
+
+    // Number of requests received for the method and path, e.g. "GET v1/products/prod_123".
+    pub fn request_count(&self, method_path: &str) -> usize {
+        self.state.requests.lock().unwrap().get(method_path).cloned().unwrap_or(0)
+    }
+
+    // Wraps the object up the way Stripe sends it to our webhook.
+    pub fn create_webhook_event(&self, event_type: &str, object: Value) -> Value {
+        self.state.create_event(event_type, object)
+    }
+
+    // Where events get delivered to. Without a target, events are still created but never sent.
+    pub fn set_webhook_target(&self, url: &str, secret: &str) {
+        *self.state.webhook.lock().unwrap() = Some(StripeMockWebhookTarget{
+            url: url.to_string(),
+            secret: secret.to_string(),
+        });
+    }
+
+    pub fn deliveries(&self) -> Vec<StripeMockDelivery> {
+        self.state.deliveries.lock().unwrap().clone()
+    }
+
+    pub async fn deliver_events(&self, events: &[Value]) -> Result<Vec<StripeMockDelivery>, SquadOvError> {
+        self.state.deliver(events).await
+    }
+
+    pub async fn complete_checkout_session(&self, id: &str) -> Result<Vec<StripeMockDelivery>, SquadOvError> {
+        let events = self.state.complete_checkout_session(id)?;
+        self.state.deliver(&events).await
+    }
+
+    // A successful (paid) or failed renewal of the subscription.
+    pub async fn renew_subscription(&self, id: &str, paid: bool) -> Result<Vec<StripeMockDelivery>, SquadOvError> {
+        let events = self.state.renew_subscription(id, paid)?;
+        self.state.deliver(&events).await
+    }
+
+    pub async fn cancel_subscription(&self, id: &str) -> Result<Vec<StripeMockDelivery>, SquadOvError> {
+        let events = self.state.cancel_subscription(id)?;
+        self.state.deliver(&events).await
+    }
+
+    pub async fn stop(&self) {
+        self.handle.stop(true).await;
+    }
+}
+
+// Returns the body and Stripe-Signature header value to send to our webhook endpoint.
+pub fn sign_stripe_webhook_event(event: &Value, webhook_secret: &str, t: DateTime<Utc>) -> Result<(String, String), SquadOvError> {
+    let payload = serde_json::to_string(event)?;
+    let sig = StripeSignature::sign(&payload, webhook_secret, t)?;
+    Ok((payload, sig.to_header_value()))
+}
@@ -78,7 +78,7 @@ impl StripeApiClient {
     pub async fn list_all_prices(&self, request: ListAllPricesRequest) -> Result<ListAllPricesResponse, SquadOvError> {
         Ok(
             self.send_request(
-                self.client.get(&Self::build_url("v1/prices"))
+                self.client.get(&self.build_url("v1/prices"))
                     .query(&if let Some(product) = request.product {
                         vec![("product", product)]
                     } else {
@@ -108,7 +108,7 @@ impl StripeApiClient {
     pub async fn retrieve_a_price(&self, price: &str) -> Result<StripePrice, SquadOvError> {
         Ok(
             self.send_request(
-                self.client.get(&Self::build_url(&format!("v1/prices/{}", price)))
+                self.client.get(&self.build_url(&format!("v1/prices/{}", price)))
                     .build()?
             )
                 .await?
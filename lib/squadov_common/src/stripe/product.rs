@@ -48,7 +48,7 @@ impl StripeApiClient {
     pub async fn list_all_products(&self, request: StripeListAllProductRequest) -> Result<StripeListAllProductResponse, SquadOvError> {
         Ok(
             self.send_request(
-                self.client.get(&Self::build_url("v1/products"))
+                self.client.get(&self.build_url("v1/products"))
                     .query(&[("active", request.active)])
                     .build()?
             )
@@ -60,7 +60,7 @@ impl StripeApiClient {
     pub async fn search_products(&self, request: StripeSearchProductsRequest) -> Result<StripeListAllProductResponse, SquadOvError> {
         Ok(
             self.send_request(
-                self.client.get(&Self::build_url("v1/products/search"))
+                self.client.get(&self.build_url("v1/products/search"))
                     .query(&[("query", request.build_query())])
                     .build()?
             )
@@ -72,7 +72,7 @@ impl StripeApiClient {
     pub async fn retrieve_a_product(&self, product: &str) -> Result<StripeProduct, SquadOvError> {
         Ok(
             self.send_request(
-                self.client.get(&Self::build_url(&format!("v1/products/{}", product)))
+                self.client.get(&self.build_url(&format!("v1/products/{}", product)))
                     .build()?
             )
                 .await?
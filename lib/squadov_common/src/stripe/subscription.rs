@@ -14,6 +14,7 @@ use crate::{
     },
 };
 use chrono::{DateTime, Utc, serde::ts_seconds};
+use std::collections::HashMap;
 
 #[derive(Display)]
 pub enum StripeSubscriptionStatus {
@@ -78,12 +79,15 @@ impl StripeSubscriptionStatus {
 
 #[derive(Deserialize)]
 pub struct StripeSubscription {
+    pub id: String,
     pub customer: String,
     pub items: StripeInvoiceLineContainer,
     pub status: StripeSubscriptionStatus,
 
     #[serde(with="ts_seconds")]
     pub current_period_end: DateTime<Utc>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
 }
 
 #[derive(Serialize)]
@@ -100,7 +104,7 @@ impl StripeApiClient {
     pub async fn retrieve_a_subscription(&self, subscription: &str) -> Result<StripeSubscription, SquadOvError> {
         Ok(
             self.send_request(
-                self.client.get(&Self::build_url(&format!("v1/subscriptions/{}", subscription)))
+                self.client.get(&self.build_url(&format!("v1/subscriptions/{}", subscription)))
                     .build()?
             )
                 .await?
@@ -111,7 +115,7 @@ impl StripeApiClient {
     pub async fn list_subscriptions(&self, request: StripeListSubscriptionsRequest) -> Result<Vec<StripeSubscription>, SquadOvError> {
         Ok(
             self.send_request(
-                self.client.get(&Self::build_url("v1/subscriptions"))
+                self.client.get(&self.build_url("v1/subscriptions"))
                     .query(&if let Some(customer) = request.customer {
                         vec![("customer", customer)]
                     } else {
@@ -66,13 +66,30 @@ pub struct StripeSignature {
 }
 
 impl StripeSignature {
-    pub fn is_valid(&self, payload: &str, endpoint_secret: &str) -> Result<bool, SquadOvError> {
-        let signed_payload = format!("{}.{}", self.t.timestamp(), payload);
+    fn compute_signature(t: &DateTime<Utc>, payload: &str, endpoint_secret: &str) -> Result<String, SquadOvError> {
+        let signed_payload = format!("{}.{}", t.timestamp(), payload);
 
         let mut mac = Hmac::<Sha256>::new_from_slice(endpoint_secret.as_bytes())?;
         mac.update(signed_payload.as_bytes());
+        Ok(hex::encode(mac.finalize().into_bytes()))
+    }
+
+    // Signs the payload the same way Stripe does. Only useful for sending our own webhooks (e.g. from the mock).
+    pub fn sign(payload: &str, endpoint_secret: &str, t: DateTime<Utc>) -> Result<Self, SquadOvError> {
+        let sig = Self::compute_signature(&t, payload, endpoint_secret)?;
+        Ok(Self {
+            t,
+            v: HashMap::from([(String::from("v1"), sig)]),
+        })
+    }
 
-        let test_sig = hex::encode(mac.finalize().into_bytes());
+    // Value for the Stripe-Signature header.
+    pub fn to_header_value(&self) -> String {
+        format!("t={},v1={}", self.t.timestamp(), self.v.get("v1").map(|x| { x.as_str() }).unwrap_or(""))
+    }
+
+    pub fn is_valid(&self, payload: &str, endpoint_secret: &str) -> Result<bool, SquadOvError> {
+        let test_sig = Self::compute_signature(&self.t, payload, endpoint_secret)?;
         Ok(
             if let Some(ref_sig) = self.v.get("v1") {
                 ref_sig.as_str() == test_sig.as_str()
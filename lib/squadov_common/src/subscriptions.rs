@@ -1,3 +1,9 @@
+mod team;
+mod gift;
+
+pub use team::*;
+pub use gift::*;
+
 use serde::{Serializer, Serialize, Deserialize, Deserializer, de::Error};
 use chrono::{DateTime, Utc};
 use sqlx::{Executor, Postgres};
@@ -92,7 +98,8 @@ impl SquadOvSubTiers {
     }
 }
 
-pub async fn get_user_sub_tier<'a, T>(ex: T, user_id: i64) -> Result<SquadOvSubTiers, SquadOvError>
+// Only the tier the user is paying for themselves. Use get_user_sub_tier to also take into account team seats and gifts.
+pub async fn get_user_personal_sub_tier<'a, T>(ex: T, user_id: i64) -> Result<SquadOvSubTiers, SquadOvError>
 where
     T: Executor<'a, Database = Postgres>
 {
@@ -115,6 +122,131 @@ where
     )
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all="lowercase")]
+pub enum SquadOvSubSource {
+    Personal,
+    Team,
+    Gift,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct SquadOvSubEntitlement {
+    pub source: SquadOvSubSource,
+    pub tier: SquadOvSubTiers,
+    pub end_tm: Option<DateTime<Utc>>,
+    // The team subscription or gift that this entitlement comes from.
+    pub source_id: Option<i64>,
+    pub squad_id: Option<i64>,
+}
+
+#[derive(sqlx::FromRow)]
+struct SquadOvSubEntitlementRow {
+    source: String,
+    tier: String,
+    end_tm: Option<DateTime<Utc>>,
+    source_id: Option<i64>,
+    squad_id: Option<i64>,
+}
+
+// Every subscription that's currently giving the user a tier. Team seats only count while the user is still in the squad.
+pub async fn get_user_sub_entitlements<'a, T>(ex: T, user_id: i64) -> Result<Vec<SquadOvSubEntitlement>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, SquadOvSubEntitlementRow>(
+            "
+            SELECT 'personal' AS source, ust.tier, ust.end_tm, NULL::BIGINT AS source_id, NULL::BIGINT AS squad_id
+            FROM squadov.user_subscription_tier AS ust
+            WHERE ust.user_id = $1
+                AND ust.end_tm >= NOW()
+            UNION ALL
+            SELECT 'team', ts.tier, ts.end_tm, ts.id, ts.squad_id
+            FROM squadov.team_subscription_seats AS tss
+            INNER JOIN squadov.team_subscriptions AS ts
+                ON ts.id = tss.team_subscription_id
+            INNER JOIN squadov.squad_role_assignments AS sra
+                ON sra.squad_id = ts.squad_id
+                    AND sra.user_id = tss.user_id
+            WHERE tss.user_id = $1
+                AND ts.end_tm >= NOW()
+            UNION ALL
+            SELECT 'gift', gs.tier, gs.end_tm, gs.id, NULL::BIGINT
+            FROM squadov.gift_subscriptions AS gs
+            WHERE gs.dest_user_id = $1
+                AND gs.start_tm <= NOW()
+                AND gs.end_tm >= NOW()
+            "
+        )
+            .bind(user_id)
+            .fetch_all(ex)
+            .await?
+            .into_iter()
+            .map(|x| {
+                Ok(SquadOvSubEntitlement{
+                    source: match x.source.as_str() {
+                        "team" => SquadOvSubSource::Team,
+                        "gift" => SquadOvSubSource::Gift,
+                        _ => SquadOvSubSource::Personal,
+                    },
+                    tier: SquadOvSubTiers::from_str(&x.tier)?,
+                    end_tm: x.end_tm,
+                    source_id: x.source_id,
+                    squad_id: x.squad_id,
+                })
+            })
+            .collect::<Result<Vec<SquadOvSubEntitlement>, SquadOvError>>()?
+    )
+}
+
+pub fn pick_best_sub_tier(entitlements: &[SquadOvSubEntitlement]) -> SquadOvSubTiers {
+    let mut best = SquadOvSubTiers::Basic;
+    for e in entitlements {
+        if e.tier > best {
+            best = e.tier.clone();
+        }
+    }
+    best
+}
+
+// The best tier out of the user's personal subscription, team seats and gifts.
+pub async fn get_user_sub_tier<'a, T>(ex: T, user_id: i64) -> Result<SquadOvSubTiers, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(pick_best_sub_tier(&get_user_sub_entitlements(ex, user_id).await?))
+}
+
+// Users that had a team seat or gift run out since the given time. Their feature flags need to be recomputed since
+// Stripe won't tell us about these.
+pub async fn get_users_with_expired_entitlements<'a, T>(ex: T, since: &DateTime<Utc>) -> Result<Vec<i64>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar(
+            "
+            SELECT tss.user_id
+            FROM squadov.team_subscription_seats AS tss
+            INNER JOIN squadov.team_subscriptions AS ts
+                ON ts.id = tss.team_subscription_id
+            WHERE ts.end_tm >= $1
+                AND ts.end_tm < NOW()
+            UNION
+            SELECT gs.dest_user_id
+            FROM squadov.gift_subscriptions AS gs
+            WHERE gs.end_tm >= $1
+                AND gs.end_tm < NOW()
+            "
+        )
+            .bind(since)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
 pub async fn set_user_sub_tier<'a, T>(ex: T, user_id: i64, tier: SquadOvSubTiers, end_tm: Option<DateTime<Utc>>) -> Result<(), SquadOvError>
 where
     T: Executor<'a, Database = Postgres>
@@ -168,6 +300,40 @@ impl<'de> Deserialize<'de> for SquadOvSubTiers {
     }
 }
 
+// What a Stripe product gives the user is determined by its metadata:
+//  - tier: a personal subscription.
+//  - team_tier: a team subscription where the quantity is the number of seats.
+//  - gift_tier + gift_days: a one time purchase that gives another user the tier for that many days.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SquadOvStripeProduct {
+    Personal(SquadOvSubTiers),
+    Team(SquadOvSubTiers),
+    Gift{
+        tier: SquadOvSubTiers,
+        days: i64,
+    },
+}
+
+impl SquadOvStripeProduct {
+    pub fn from_metadata(metadata: &HashMap<String, String>) -> Result<Option<Self>, SquadOvError> {
+        Ok(
+            if let Some(tier) = metadata.get("tier") {
+                Some(SquadOvStripeProduct::Personal(SquadOvSubTiers::from_str(tier)?))
+            } else if let Some(tier) = metadata.get("team_tier") {
+                Some(SquadOvStripeProduct::Team(SquadOvSubTiers::from_str(tier)?))
+            } else if let Some(tier) = metadata.get("gift_tier") {
+                let days = metadata.get("gift_days").ok_or(SquadOvError::BadRequest)?.parse::<i64>()?;
+                Some(SquadOvStripeProduct::Gift{
+                    tier: SquadOvSubTiers::from_str(tier)?,
+                    days,
+                })
+            } else {
+                None
+            }
+        )
+    }
+}
+
 #[derive(Serialize, Clone, Debug)]
 pub struct SquadOvDiscount {
     #[serde(skip_serializing)]
@@ -226,6 +392,23 @@ where
     )
 }
 
+pub async fn get_all_stripe_customer_ids<'a, T>(ex: T) -> Result<Vec<String>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar(
+            "
+            SELECT customer
+            FROM squadov.stripe_customers
+            ORDER BY user_id ASC
+            "
+        )
+            .fetch_all(ex)
+            .await?
+    )
+}
+
 pub async fn associate_user_id_with_customer_id<'a, T>(ex: T, user_id: i64, customer_id: &str) -> Result<(), SquadOvError>
 where
     T: Executor<'a, Database = Postgres>
@@ -242,4 +425,42 @@ where
         .execute(ex)
         .await?;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entitlement(source: SquadOvSubSource, tier: SquadOvSubTiers) -> SquadOvSubEntitlement {
+        SquadOvSubEntitlement{
+            source,
+            tier,
+            end_tm: None,
+            source_id: None,
+            squad_id: None,
+        }
+    }
+
+    #[test]
+    fn test_pick_best_sub_tier() {
+        assert_eq!(pick_best_sub_tier(&[]), SquadOvSubTiers::Basic);
+        assert_eq!(pick_best_sub_tier(&[
+            entitlement(SquadOvSubSource::Personal, SquadOvSubTiers::Silver),
+            entitlement(SquadOvSubSource::Team, SquadOvSubTiers::Diamond),
+            entitlement(SquadOvSubSource::Gift, SquadOvSubTiers::Gold),
+        ]), SquadOvSubTiers::Diamond);
+    }
+
+    #[test]
+    fn test_stripe_product_from_metadata() {
+        let metadata = |x: &[(&str, &str)]| -> HashMap<String, String> {
+            x.iter().map(|(k, v)| { (k.to_string(), v.to_string()) }).collect()
+        };
+
+        assert_eq!(SquadOvStripeProduct::from_metadata(&metadata(&[("tier", "GOLD")])).unwrap(), Some(SquadOvStripeProduct::Personal(SquadOvSubTiers::Gold)));
+        assert_eq!(SquadOvStripeProduct::from_metadata(&metadata(&[("team_tier", "SILVER")])).unwrap(), Some(SquadOvStripeProduct::Team(SquadOvSubTiers::Silver)));
+        assert_eq!(SquadOvStripeProduct::from_metadata(&metadata(&[("gift_tier", "DIAMOND"), ("gift_days", "30")])).unwrap(), Some(SquadOvStripeProduct::Gift{tier: SquadOvSubTiers::Diamond, days: 30}));
+        assert!(SquadOvStripeProduct::from_metadata(&metadata(&[("gift_tier", "DIAMOND")])).is_err());
+        assert_eq!(SquadOvStripeProduct::from_metadata(&metadata(&[])).unwrap(), None);
+    }
+}
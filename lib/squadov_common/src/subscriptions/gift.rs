@@ -0,0 +1,108 @@
+use serde::Serialize;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres};
+use crate::SquadOvError;
+use super::SquadOvSubTiers;
+use std::str::FromStr;
+use std::convert::{TryFrom, TryInto};
+
+// A fixed length subscription that one user bought for another.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct GiftSubscription {
+    pub id: i64,
+    pub source_user_id: Option<i64>,
+    pub dest_user_id: i64,
+    pub tier: SquadOvSubTiers,
+    pub created_tm: DateTime<Utc>,
+    pub start_tm: DateTime<Utc>,
+    pub end_tm: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+struct GiftSubscriptionRow {
+    id: i64,
+    source_user_id: Option<i64>,
+    dest_user_id: i64,
+    tier: String,
+    created_tm: DateTime<Utc>,
+    start_tm: DateTime<Utc>,
+    end_tm: DateTime<Utc>,
+}
+
+impl TryFrom<GiftSubscriptionRow> for GiftSubscription {
+    type Error = SquadOvError;
+
+    fn try_from(x: GiftSubscriptionRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: x.id,
+            source_user_id: x.source_user_id,
+            dest_user_id: x.dest_user_id,
+            tier: SquadOvSubTiers::from_str(&x.tier)?,
+            created_tm: x.created_tm,
+            start_tm: x.start_tm,
+            end_tm: x.end_tm,
+        })
+    }
+}
+
+// Gifts stack: a new gift starts when the recipient's last gift runs out. The Stripe checkout session makes this idempotent
+// so a webhook that gets delivered twice doesn't give out two gifts; None is returned for the duplicate.
+pub async fn create_gift_subscription<'a, T>(ex: T, source_user_id: Option<i64>, dest_user_id: i64, tier: &SquadOvSubTiers, days: i64, stripe_checkout_session: Option<&str>) -> Result<Option<GiftSubscription>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, GiftSubscriptionRow>(
+        "
+        WITH start(tm) AS (
+            SELECT GREATEST(NOW(), MAX(end_tm))
+            FROM squadov.gift_subscriptions
+            WHERE dest_user_id = $2
+        )
+        INSERT INTO squadov.gift_subscriptions (
+            source_user_id,
+            dest_user_id,
+            tier,
+            stripe_checkout_session,
+            created_tm,
+            start_tm,
+            end_tm
+        )
+        SELECT $1, $2, $3, $4, NOW(), start.tm, start.tm + $5 * INTERVAL '1 day'
+        FROM start
+        ON CONFLICT (stripe_checkout_session) DO NOTHING
+        RETURNING id, source_user_id, dest_user_id, tier, created_tm, start_tm, end_tm
+        "
+    )
+        .bind(source_user_id)
+        .bind(dest_user_id)
+        .bind(format!("{}", tier))
+        .bind(stripe_checkout_session)
+        .bind(days as f64)
+        .fetch_optional(ex)
+        .await?
+        .map(|x| { x.try_into() })
+        .transpose()
+}
+
+// Gifts the user has sent or received.
+pub async fn get_gift_subscriptions_for_user<'a, T>(ex: T, user_id: i64) -> Result<Vec<GiftSubscription>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, GiftSubscriptionRow>(
+        "
+        SELECT id, source_user_id, dest_user_id, tier, created_tm, start_tm, end_tm
+        FROM squadov.gift_subscriptions
+        WHERE source_user_id = $1
+            OR dest_user_id = $1
+        ORDER BY created_tm DESC
+        "
+    )
+        .bind(user_id)
+        .fetch_all(ex)
+        .await?
+        .into_iter()
+        .map(|x| { x.try_into() })
+        .collect()
+}
@@ -0,0 +1,443 @@
+use serde::Serialize;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres, Transaction, postgres::PgPool};
+use crate::{
+    SquadOvError,
+    stripe::{
+        StripeApiClient,
+        invoice::StripeInvoice,
+        subscription::StripeSubscription,
+    },
+};
+use super::{SquadOvSubTiers, SquadOvStripeProduct};
+use std::str::FromStr;
+use std::convert::{TryFrom, TryInto};
+
+// A subscription bought by a squad owner with a fixed number of seats. Each seat gives a squad member the
+// subscription's tier.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct TeamSubscription {
+    pub id: i64,
+    #[serde(skip_serializing)]
+    pub stripe_subscription: String,
+    pub squad_id: Option<i64>,
+    pub owner_user_id: i64,
+    pub tier: SquadOvSubTiers,
+    pub seats: i32,
+    pub start_tm: DateTime<Utc>,
+    pub end_tm: Option<DateTime<Utc>>,
+}
+
+#[derive(sqlx::FromRow)]
+struct TeamSubscriptionRow {
+    id: i64,
+    stripe_subscription: String,
+    squad_id: Option<i64>,
+    owner_user_id: i64,
+    tier: String,
+    seats: i32,
+    start_tm: DateTime<Utc>,
+    end_tm: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<TeamSubscriptionRow> for TeamSubscription {
+    type Error = SquadOvError;
+
+    fn try_from(x: TeamSubscriptionRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: x.id,
+            stripe_subscription: x.stripe_subscription,
+            squad_id: x.squad_id,
+            owner_user_id: x.owner_user_id,
+            tier: SquadOvSubTiers::from_str(&x.tier)?,
+            seats: x.seats,
+            start_tm: x.start_tm,
+            end_tm: x.end_tm,
+        })
+    }
+}
+
+impl TeamSubscription {
+    pub fn is_active(&self) -> bool {
+        self.end_tm.map(|x| { x >= Utc::now() }).unwrap_or(false)
+    }
+}
+
+#[derive(Serialize, Clone, Debug, sqlx::FromRow)]
+#[serde(rename_all="camelCase")]
+pub struct TeamSubscriptionSeat {
+    pub user_id: i64,
+    pub username: String,
+    pub assigned_tm: DateTime<Utc>,
+}
+
+// Seats are kept in the order they were assigned so when a team drops seats, the most recently assigned seats go first.
+pub fn team_seats_to_remove(seats: &[TeamSubscriptionSeat], max_seats: i32) -> Vec<i64> {
+    let mut ordered: Vec<&TeamSubscriptionSeat> = seats.iter().collect();
+    ordered.sort_by(|a, b| { a.assigned_tm.cmp(&b.assigned_tm).then(a.user_id.cmp(&b.user_id)) });
+    ordered.into_iter()
+        .skip(max_seats.max(0) as usize)
+        .map(|x| { x.user_id })
+        .collect()
+}
+
+const TEAM_SUBSCRIPTION_COLUMNS: &str = "id, stripe_subscription, squad_id, owner_user_id, tier, seats, start_tm, end_tm";
+
+pub async fn upsert_team_subscription<'a, T>(ex: T, stripe_subscription: &str, squad_id: Option<i64>, owner_user_id: i64, tier: &SquadOvSubTiers, seats: i32, end_tm: Option<DateTime<Utc>>) -> Result<i64, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar(
+            "
+            INSERT INTO squadov.team_subscriptions (
+                stripe_subscription,
+                squad_id,
+                owner_user_id,
+                tier,
+                seats,
+                start_tm,
+                end_tm
+            ) VALUES (
+                $1,
+                $2,
+                $3,
+                $4,
+                $5,
+                NOW(),
+                $6
+            ) ON CONFLICT (stripe_subscription) DO UPDATE
+                SET squad_id = COALESCE(EXCLUDED.squad_id, team_subscriptions.squad_id),
+                    tier = EXCLUDED.tier,
+                    seats = EXCLUDED.seats,
+                    end_tm = EXCLUDED.end_tm
+            RETURNING id
+            "
+        )
+            .bind(stripe_subscription)
+            .bind(squad_id)
+            .bind(owner_user_id)
+            .bind(format!("{}", tier))
+            .bind(seats)
+            .bind(end_tm)
+            .fetch_one(ex)
+            .await?
+    )
+}
+
+// Ends the team subscription right away (e.g. it got canceled or stopped being paid for). Seats are kept around
+// so they come back if the subscription gets reactivated.
+pub async fn expire_team_subscription<'a, T>(ex: T, stripe_subscription: &str) -> Result<Option<i64>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar(
+            "
+            UPDATE squadov.team_subscriptions
+            SET end_tm = LEAST(COALESCE(end_tm, NOW()), NOW())
+            WHERE stripe_subscription = $1
+            RETURNING id
+            "
+        )
+            .bind(stripe_subscription)
+            .fetch_optional(ex)
+            .await?
+    )
+}
+
+pub async fn get_team_subscription<'a, T>(ex: T, id: i64) -> Result<TeamSubscription, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, TeamSubscriptionRow>(&format!(
+        "
+        SELECT {}
+        FROM squadov.team_subscriptions
+        WHERE id = $1
+        ",
+        TEAM_SUBSCRIPTION_COLUMNS,
+    ))
+        .bind(id)
+        .fetch_optional(ex)
+        .await?
+        .ok_or(SquadOvError::NotFound)?
+        .try_into()
+}
+
+pub async fn get_team_subscription_from_stripe<'a, T>(ex: T, stripe_subscription: &str) -> Result<Option<TeamSubscription>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, TeamSubscriptionRow>(&format!(
+        "
+        SELECT {}
+        FROM squadov.team_subscriptions
+        WHERE stripe_subscription = $1
+        ",
+        TEAM_SUBSCRIPTION_COLUMNS,
+    ))
+        .bind(stripe_subscription)
+        .fetch_optional(ex)
+        .await?
+        .map(|x| { x.try_into() })
+        .transpose()
+}
+
+pub async fn get_team_subscriptions_for_squad<'a, T>(ex: T, squad_id: i64) -> Result<Vec<TeamSubscription>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query_as::<_, TeamSubscriptionRow>(&format!(
+        "
+        SELECT {}
+        FROM squadov.team_subscriptions
+        WHERE squad_id = $1
+        ORDER BY start_tm DESC
+        ",
+        TEAM_SUBSCRIPTION_COLUMNS,
+    ))
+        .bind(squad_id)
+        .fetch_all(ex)
+        .await?
+        .into_iter()
+        .map(|x| { x.try_into() })
+        .collect()
+}
+
+pub async fn get_team_subscription_seats<'a, T>(ex: T, team_subscription_id: i64) -> Result<Vec<TeamSubscriptionSeat>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, TeamSubscriptionSeat>(
+            "
+            SELECT tss.user_id, u.username, tss.assigned_tm
+            FROM squadov.team_subscription_seats AS tss
+            INNER JOIN squadov.users AS u
+                ON u.id = tss.user_id
+            WHERE tss.team_subscription_id = $1
+            ORDER BY tss.assigned_tm ASC, tss.user_id ASC
+            "
+        )
+            .bind(team_subscription_id)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+// Locks the team subscription for the rest of the transaction so seat changes can't race each other.
+pub async fn lock_team_subscription(tx: &mut Transaction<'_, Postgres>, team_subscription_id: i64) -> Result<TeamSubscription, SquadOvError> {
+    sqlx::query_as::<_, TeamSubscriptionRow>(&format!(
+        "
+        SELECT {}
+        FROM squadov.team_subscriptions
+        WHERE id = $1
+        FOR UPDATE
+        ",
+        TEAM_SUBSCRIPTION_COLUMNS,
+    ))
+        .bind(team_subscription_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(SquadOvError::NotFound)?
+        .try_into()
+}
+
+pub async fn assign_team_subscription_seat(tx: &mut Transaction<'_, Postgres>, team_subscription_id: i64, user_id: i64) -> Result<(), SquadOvError> {
+    let sub = lock_team_subscription(tx, team_subscription_id).await?;
+    if !sub.is_active() {
+        return Err(SquadOvError::BadRequest);
+    }
+
+    let used: i64 = sqlx::query_scalar(
+        "
+        SELECT COUNT(*)
+        FROM squadov.team_subscription_seats
+        WHERE team_subscription_id = $1
+        "
+    )
+        .bind(team_subscription_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    if used >= sub.seats as i64 {
+        return Err(SquadOvError::BadRequest);
+    }
+
+    let inserted = sqlx::query(
+        "
+        INSERT INTO squadov.team_subscription_seats (
+            team_subscription_id,
+            user_id,
+            assigned_tm
+        ) VALUES (
+            $1,
+            $2,
+            NOW()
+        ) ON CONFLICT DO NOTHING
+        "
+    )
+        .bind(team_subscription_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    if inserted == 0 {
+        return Err(SquadOvError::Duplicate);
+    }
+    Ok(())
+}
+
+pub async fn remove_team_subscription_seats<'a, T>(ex: T, team_subscription_id: i64, user_ids: &[i64]) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        DELETE FROM squadov.team_subscription_seats
+        WHERE team_subscription_id = $1
+            AND user_id = ANY($2)
+        "
+    )
+        .bind(team_subscription_id)
+        .bind(user_ids)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+// Drops seats until the team subscription has at most max_seats assigned. Returns the users that lost their seat.
+pub async fn trim_team_subscription_seats(tx: &mut Transaction<'_, Postgres>, team_subscription_id: i64, max_seats: i32) -> Result<Vec<i64>, SquadOvError> {
+    let seats = get_team_subscription_seats(&mut *tx, team_subscription_id).await?;
+    let removed = team_seats_to_remove(&seats, max_seats);
+    if !removed.is_empty() {
+        remove_team_subscription_seats(&mut *tx, team_subscription_id, &removed).await?;
+    }
+    Ok(removed)
+}
+
+// Removes any seats the user has on the squad's team subscriptions (e.g. when they leave the squad).
+pub async fn remove_user_team_seats_for_squad<'a, T>(ex: T, squad_id: i64, user_id: i64) -> Result<Vec<i64>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar(
+            "
+            DELETE FROM squadov.team_subscription_seats AS tss
+            USING squadov.team_subscriptions AS ts
+            WHERE ts.id = tss.team_subscription_id
+                AND ts.squad_id = $1
+                AND tss.user_id = $2
+            RETURNING tss.team_subscription_id
+            "
+        )
+            .bind(squad_id)
+            .bind(user_id)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub async fn get_team_seat_user_ids_for_squad<'a, T>(ex: T, squad_id: i64) -> Result<Vec<i64>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar(
+            "
+            SELECT DISTINCT tss.user_id
+            FROM squadov.team_subscription_seats AS tss
+            INNER JOIN squadov.team_subscriptions AS ts
+                ON ts.id = tss.team_subscription_id
+            WHERE ts.squad_id = $1
+            "
+        )
+            .bind(squad_id)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_team_seats_to_remove() {
+        let seat = |user_id: i64, secs: i64| {
+            TeamSubscriptionSeat{
+                user_id,
+                username: String::new(),
+                assigned_tm: Utc.timestamp(secs, 0),
+            }
+        };
+
+        let seats = vec![seat(3, 300), seat(1, 100), seat(2, 200), seat(4, 200)];
+        assert_eq!(team_seats_to_remove(&seats, 4), Vec::<i64>::new());
+        assert_eq!(team_seats_to_remove(&seats, 2), vec![4, 3]);
+        assert_eq!(team_seats_to_remove(&seats, 0), vec![1, 2, 4, 3]);
+        assert_eq!(team_seats_to_remove(&seats, -1).len(), 4);
+    }
+}
+
+// Brings our copy of the team subscription in line with Stripe's (renewals, seat changes, cancellations). Returns None if the
+// subscription isn't for a team and otherwise the users whose tier may have changed.
+pub async fn sync_team_subscription_from_stripe(stripe: &StripeApiClient, pool: &PgPool, owner_user_id: i64, sub: &StripeSubscription) -> Result<Option<Vec<i64>>, SquadOvError> {
+    let mut team: Option<(SquadOvSubTiers, i32)> = None;
+    for d in &sub.items.data {
+        let price = stripe.retrieve_a_price(&d.price.id).await?;
+        let product = stripe.retrieve_a_product(&price.product).await?;
+        if let Some(SquadOvStripeProduct::Team(tier)) = SquadOvStripeProduct::from_metadata(&product.metadata)? {
+            team = Some((tier, d.quantity.unwrap_or(1)));
+            break;
+        }
+    }
+
+    let (tier, seats) = if let Some(x) = team {
+        x
+    } else {
+        return Ok(None);
+    };
+
+    let squad_id = sub.metadata.get("squad_id").map(|x| { x.parse::<i64>() }).transpose()?;
+    let mut tx = pool.begin().await?;
+    let affected_users: Vec<i64> = if sub.status.is_valid() {
+        let id = upsert_team_subscription(&mut tx, &sub.id, squad_id, owner_user_id, &tier, seats, Some(sub.current_period_end + chrono::Duration::days(2))).await?;
+        lock_team_subscription(&mut tx, id).await?;
+
+        // Seats that no longer fit get dropped and everyone else may have had their tier changed.
+        let mut users = trim_team_subscription_seats(&mut tx, id, seats).await?;
+        users.extend(get_team_subscription_seats(&mut tx, id).await?.into_iter().map(|x| { x.user_id }));
+        users
+    } else if let Some(id) = expire_team_subscription(&mut tx, &sub.id).await? {
+        get_team_subscription_seats(&mut tx, id).await?.into_iter().map(|x| { x.user_id }).collect()
+    } else {
+        vec![]
+    };
+    tx.commit().await?;
+    Ok(Some(affected_users))
+}
+
+// A renewal of a team subscription shows up as a paid invoice. Returns the IDs of the team subscriptions on the invoice along
+// with the users whose tier may have changed.
+pub async fn sync_team_subscriptions_from_stripe_invoice(stripe: &StripeApiClient, pool: &PgPool, owner_user_id: i64, invoice: &StripeInvoice) -> Result<(Vec<String>, Vec<i64>), SquadOvError> {
+    let mut team_subscriptions: Vec<String> = vec![];
+    let mut affected_users: Vec<i64> = vec![];
+    for d in &invoice.lines.data {
+        if let Some(sub) = d.subscription.as_ref() {
+            if !team_subscriptions.contains(sub) {
+                let subscription = stripe.retrieve_a_subscription(sub).await?;
+                if let Some(users) = sync_team_subscription_from_stripe(stripe, pool, owner_user_id, &subscription).await? {
+                    team_subscriptions.push(sub.clone());
+                    affected_users.extend(users);
+                }
+            }
+        }
+    }
+    Ok((team_subscriptions, affected_users))
+}
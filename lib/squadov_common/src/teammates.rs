@@ -0,0 +1,551 @@
+// Who users play with (and against) across games. Every time a VOD's match data gets synced we store the roster
+// of the match from the point of view of the VOD's owner: the game identities of everyone else in the match, whether
+// they were on the user's team and whether the user won. Analytics are aggregated on request from those rows so they
+// can be filtered by game and time. Game identities that belong to SquadOV users are merged together so that the same
+// person shows up once across games.
+pub mod db;
+pub mod roster;
+
+use crate::SquadOvGames;
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, BTreeSet};
+use uuid::Uuid;
+
+pub const TEAMMATE_IDENTITY_RIOT: &str = "riot";
+pub const TEAMMATE_IDENTITY_STEAM: &str = "steam";
+pub const TEAMMATE_IDENTITY_WOW: &str = "wow";
+pub const TEAMMATE_IDENTITY_HEARTHSTONE: &str = "hearthstone";
+
+// Duos/trios need to have played this many matches together to show up.
+pub const MIN_SYNERGY_MATCHES: i64 = 3;
+// Opponents need to have been played this many times to count as a rivalry.
+pub const MIN_RIVALRY_MATCHES: i64 = 3;
+// Only the most recent matches (within the filter) go into the analytics so heavy players don't pull in their whole history.
+// The analytics say when matches were left out (see TeammateAnalytics::truncated).
+pub const MAX_TEAMMATE_ANALYTICS_MATCHES: i64 = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchRosterPlayer {
+    pub identity_type: String,
+    pub identity_id: String,
+    pub display_name: Option<String>,
+    // Only used to compare against the user's team.
+    pub team: String,
+    pub is_pov: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchRoster {
+    pub match_uuid: Uuid,
+    pub user_id: i64,
+    pub game: SquadOvGames,
+    pub match_time: DateTime<Utc>,
+    pub won: Option<bool>,
+    pub players: Vec<MatchRosterPlayer>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchRosterRelation {
+    pub identity_type: String,
+    pub identity_id: String,
+    pub display_name: Option<String>,
+    pub is_teammate: bool,
+}
+
+impl MatchRoster {
+    // Everyone other than the user relative to the user's team. Empty if we can't find the user in the roster.
+    pub fn relations(&self) -> Vec<MatchRosterRelation> {
+        let pov_team = match self.players.iter().find(|x| { x.is_pov }) {
+            Some(x) => x.team.clone(),
+            None => return vec![],
+        };
+
+        let mut seen: HashSet<(&str, &str)> = HashSet::new();
+        self.players.iter()
+            .filter(|x| { !x.is_pov })
+            .filter(|x| { seen.insert((x.identity_type.as_str(), x.identity_id.as_str())) })
+            .map(|x| {
+                MatchRosterRelation{
+                    identity_type: x.identity_type.clone(),
+                    identity_id: x.identity_id.clone(),
+                    display_name: x.display_name.clone(),
+                    is_teammate: x.team == pov_team,
+                }
+            })
+            .collect()
+    }
+}
+
+// Users are anonymous in other users' teammate analytics unless they say otherwise.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all="camelCase")]
+pub struct TeammateAnalyticsSettings {
+    pub show_in_teammate_analytics: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all="camelCase")]
+pub struct TeammateAnalyticsFilter {
+    pub games: Option<Vec<SquadOvGames>>,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TeammateMatchOutcome {
+    pub match_uuid: Uuid,
+    pub game: SquadOvGames,
+    pub won: Option<bool>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TeammateMatchPlayer {
+    pub match_uuid: Uuid,
+    pub identity_type: String,
+    pub identity_id: String,
+    pub display_name: Option<String>,
+    pub is_teammate: bool,
+    // Only set if the SquadOV user is fine with being shown to people they've played with.
+    pub linked_user_id: Option<i64>,
+    pub linked_username: Option<String>,
+}
+
+impl TeammateMatchPlayer {
+    fn key(&self) -> String {
+        match self.linked_user_id {
+            Some(user_id) => format!("user:{}", user_id),
+            None => format!("{}:{}", &self.identity_type, &self.identity_id),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all="camelCase")]
+pub struct TeammateRecord {
+    pub matches: i64,
+    pub wins: i64,
+    pub losses: i64,
+    // Only counts matches where we know who won.
+    pub win_rate: Option<f64>,
+}
+
+impl TeammateRecord {
+    fn new(matches: i64, wins: i64, losses: i64) -> Self {
+        Self {
+            matches,
+            wins,
+            losses,
+            win_rate: if wins + losses > 0 {
+                Some(wins as f64 / (wins + losses) as f64)
+            } else {
+                None
+            },
+        }
+    }
+
+    fn add(&mut self, won: Option<bool>) {
+        *self = Self::new(
+            self.matches + 1,
+            self.wins + if won == Some(true) { 1 } else { 0 },
+            self.losses + if won == Some(false) { 1 } else { 0 },
+        );
+    }
+
+    fn combine(&self, other: &TeammateRecord) -> TeammateRecord {
+        Self::new(self.matches + other.matches, self.wins + other.wins, self.losses + other.losses)
+    }
+
+    fn minus(&self, other: &TeammateRecord) -> TeammateRecord {
+        Self::new(self.matches - other.matches, self.wins - other.wins, self.losses - other.losses)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all="camelCase")]
+pub struct TeammateIdentity {
+    pub identity_type: String,
+    pub identity_id: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct TeammateSummary {
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub display_name: Option<String>,
+    pub identities: Vec<TeammateIdentity>,
+    pub games: Vec<SquadOvGames>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct TeammateStats {
+    #[serde(flatten)]
+    pub teammate: TeammateSummary,
+    pub with: TeammateRecord,
+    // Only counts matches in the games played together.
+    pub without: TeammateRecord,
+    // Win rate with minus win rate without.
+    pub synergy: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct TeammateGroupStats {
+    pub members: Vec<TeammateSummary>,
+    pub with: TeammateRecord,
+    pub without: TeammateRecord,
+    pub synergy: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct OpponentStats {
+    #[serde(flatten)]
+    pub opponent: TeammateSummary,
+    // From the user's point of view.
+    pub record: TeammateRecord,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct TeammateAnalytics {
+    // Only covers the matches that went into the analytics.
+    pub overall: TeammateRecord,
+    // Every match within the filter, including the ones left out by MAX_TEAMMATE_ANALYTICS_MATCHES.
+    pub total_matches: i64,
+    pub truncated: bool,
+    pub teammates: Vec<TeammateStats>,
+    pub duos: Vec<TeammateGroupStats>,
+    pub trios: Vec<TeammateGroupStats>,
+    pub opponents: Vec<OpponentStats>,
+    pub rivalries: Vec<OpponentStats>,
+}
+
+#[derive(Default)]
+struct GroupAccumulator {
+    by_game: HashMap<SquadOvGames, TeammateRecord>,
+}
+
+impl GroupAccumulator {
+    fn add(&mut self, game: SquadOvGames, won: Option<bool>) {
+        self.by_game.entry(game).or_default().add(won);
+    }
+
+    // (with, without, synergy) where without only looks at the games that the group played in.
+    fn finish(&self, overall_by_game: &HashMap<SquadOvGames, TeammateRecord>) -> (TeammateRecord, TeammateRecord, Option<f64>) {
+        let mut with = TeammateRecord::default();
+        let mut without = TeammateRecord::default();
+        for (game, record) in &self.by_game {
+            with = with.combine(record);
+            without = without.combine(&overall_by_game.get(game).cloned().unwrap_or_default().minus(record));
+        }
+
+        let synergy = match (with.win_rate, without.win_rate) {
+            (Some(a), Some(b)) => Some(a - b),
+            _ => None,
+        };
+        (with, without, synergy)
+    }
+}
+
+fn cmp_f64_desc(a: Option<f64>, b: Option<f64>) -> Ordering {
+    b.unwrap_or(f64::MIN).partial_cmp(&a.unwrap_or(f64::MIN)).unwrap_or(Ordering::Equal)
+}
+
+pub fn compute_teammate_analytics(outcomes: &[TeammateMatchOutcome], players: &[TeammateMatchPlayer], limit: usize) -> TeammateAnalytics {
+    let mut overall = TeammateRecord::default();
+    let mut overall_by_game: HashMap<SquadOvGames, TeammateRecord> = HashMap::new();
+    let matches: HashMap<Uuid, &TeammateMatchOutcome> = outcomes.iter().map(|x| { (x.match_uuid.clone(), x) }).collect();
+    for o in outcomes {
+        overall.add(o.won);
+        overall_by_game.entry(o.game).or_default().add(o.won);
+    }
+
+    let mut summaries: HashMap<String, TeammateSummary> = HashMap::new();
+    // Match to the (sorted) keys of the teammates/opponents in that match.
+    let mut match_teammates: HashMap<Uuid, BTreeSet<String>> = HashMap::new();
+    let mut match_opponents: HashMap<Uuid, BTreeSet<String>> = HashMap::new();
+    for p in players {
+        let outcome = match matches.get(&p.match_uuid) {
+            Some(x) => x,
+            None => continue,
+        };
+
+        let key = p.key();
+        let summary = summaries.entry(key.clone()).or_insert_with(|| {
+            TeammateSummary{
+                user_id: p.linked_user_id,
+                username: p.linked_username.clone(),
+                display_name: None,
+                identities: vec![],
+                games: vec![],
+            }
+        });
+
+        if summary.display_name.is_none() {
+            summary.display_name = p.display_name.clone();
+        }
+
+        let identity = TeammateIdentity{
+            identity_type: p.identity_type.clone(),
+            identity_id: p.identity_id.clone(),
+        };
+        if !summary.identities.contains(&identity) {
+            summary.identities.push(identity);
+            summary.identities.sort();
+        }
+
+        if !summary.games.contains(&outcome.game) {
+            summary.games.push(outcome.game);
+        }
+
+        if p.is_teammate {
+            match_teammates.entry(p.match_uuid.clone()).or_default().insert(key);
+        } else {
+            match_opponents.entry(p.match_uuid.clone()).or_default().insert(key);
+        }
+    }
+
+    let mut singles: HashMap<String, GroupAccumulator> = HashMap::new();
+    let mut pairs: HashMap<(String, String), GroupAccumulator> = HashMap::new();
+    for (match_uuid, keys) in &match_teammates {
+        let outcome = matches.get(match_uuid).unwrap();
+        let keys: Vec<&String> = keys.iter().collect();
+        for (i, a) in keys.iter().enumerate() {
+            singles.entry((*a).clone()).or_default().add(outcome.game, outcome.won);
+            for b in keys.iter().skip(i + 1) {
+                pairs.entry(((*a).clone(), (*b).clone())).or_default().add(outcome.game, outcome.won);
+            }
+        }
+    }
+
+    let mut opponent_records: HashMap<String, TeammateRecord> = HashMap::new();
+    for (match_uuid, keys) in &match_opponents {
+        let outcome = matches.get(match_uuid).unwrap();
+        for k in keys {
+            opponent_records.entry(k.clone()).or_default().add(outcome.won);
+        }
+    }
+
+    let mut teammates: Vec<(String, TeammateStats)> = singles.iter().map(|(key, acc)| {
+        let (with, without, synergy) = acc.finish(&overall_by_game);
+        (key.clone(), TeammateStats{
+            teammate: summaries.get(key).cloned().unwrap(),
+            with,
+            without,
+            synergy,
+        })
+    }).collect();
+    teammates.sort_by(|a, b| {
+        b.1.with.matches.cmp(&a.1.with.matches).then(a.0.cmp(&b.0))
+    });
+
+    let mut duos: Vec<(String, TeammateGroupStats)> = teammates.iter()
+        .filter(|(_, x)| { x.with.matches >= MIN_SYNERGY_MATCHES && x.synergy.is_some() })
+        .map(|(key, x)| {
+            (key.clone(), TeammateGroupStats{
+                members: vec![x.teammate.clone()],
+                with: x.with,
+                without: x.without,
+                synergy: x.synergy,
+            })
+        })
+        .collect();
+    duos.sort_by(|a, b| {
+        cmp_f64_desc(a.1.synergy, b.1.synergy).then(a.0.cmp(&b.0))
+    });
+
+    let mut trios: Vec<(String, TeammateGroupStats)> = pairs.iter()
+        .filter_map(|((a, b), acc)| {
+            let (with, without, synergy) = acc.finish(&overall_by_game);
+            if with.matches < MIN_SYNERGY_MATCHES || synergy.is_none() {
+                return None;
+            }
+
+            Some((format!("{}/{}", a, b), TeammateGroupStats{
+                members: vec![summaries.get(a).cloned().unwrap(), summaries.get(b).cloned().unwrap()],
+                with,
+                without,
+                synergy,
+            }))
+        })
+        .collect();
+    trios.sort_by(|a, b| {
+        cmp_f64_desc(a.1.synergy, b.1.synergy).then(a.0.cmp(&b.0))
+    });
+
+    let mut opponents: Vec<(String, OpponentStats)> = opponent_records.into_iter().map(|(key, record)| {
+        (key.clone(), OpponentStats{
+            opponent: summaries.get(&key).cloned().unwrap(),
+            record,
+        })
+    }).collect();
+    opponents.sort_by(|a, b| {
+        b.1.record.matches.cmp(&a.1.record.matches).then(a.0.cmp(&b.0))
+    });
+
+    // The closer the record is to even the bigger the rivalry.
+    let mut rivalries: Vec<(String, OpponentStats)> = opponents.iter()
+        .filter(|(_, x)| { x.record.matches >= MIN_RIVALRY_MATCHES && x.record.win_rate.is_some() })
+        .cloned()
+        .collect();
+    rivalries.sort_by(|a, b| {
+        let a_diff = (a.1.record.win_rate.unwrap() - 0.5).abs();
+        let b_diff = (b.1.record.win_rate.unwrap() - 0.5).abs();
+        a_diff.partial_cmp(&b_diff).unwrap_or(Ordering::Equal)
+            .then(b.1.record.matches.cmp(&a.1.record.matches))
+            .then(a.0.cmp(&b.0))
+    });
+
+    TeammateAnalytics{
+        overall,
+        total_matches: outcomes.len() as i64,
+        truncated: false,
+        teammates: teammates.into_iter().take(limit).map(|x| { x.1 }).collect(),
+        duos: duos.into_iter().take(limit).map(|x| { x.1 }).collect(),
+        trios: trios.into_iter().take(limit).map(|x| { x.1 }).collect(),
+        opponents: opponents.into_iter().take(limit).map(|x| { x.1 }).collect(),
+        rivalries: rivalries.into_iter().take(limit).map(|x| { x.1 }).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn player(match_uuid: u128, identity_type: &str, identity_id: &str, is_teammate: bool, linked_user_id: Option<i64>) -> TeammateMatchPlayer {
+        TeammateMatchPlayer{
+            match_uuid: Uuid::from_u128(match_uuid),
+            identity_type: identity_type.to_string(),
+            identity_id: identity_id.to_string(),
+            display_name: Some(identity_id.to_string()),
+            is_teammate,
+            linked_user_id,
+            linked_username: linked_user_id.map(|x| { format!("user{}", x) }),
+        }
+    }
+
+    #[test]
+    fn test_roster_relations() {
+        let roster = MatchRoster{
+            match_uuid: Uuid::from_u128(1),
+            user_id: 1,
+            game: SquadOvGames::Valorant,
+            match_time: Utc.ymd(2022, 8, 1).and_hms(0, 0, 0),
+            won: Some(true),
+            players: vec![
+                ("me", "Blue", true),
+                ("friend", "Blue", false),
+                ("friend", "Blue", false),
+                ("enemy", "Red", false),
+            ].into_iter().map(|(id, team, is_pov)| {
+                MatchRosterPlayer{
+                    identity_type: TEAMMATE_IDENTITY_RIOT.to_string(),
+                    identity_id: id.to_string(),
+                    display_name: None,
+                    team: team.to_string(),
+                    is_pov,
+                }
+            }).collect(),
+        };
+
+        let relations = roster.relations();
+        assert_eq!(relations.iter().map(|x| { (x.identity_id.as_str(), x.is_teammate) }).collect::<Vec<(&str, bool)>>(), vec![("friend", true), ("enemy", false)]);
+
+        let no_pov = MatchRoster{
+            players: roster.players.iter().filter(|x| { !x.is_pov }).cloned().collect(),
+            ..roster
+        };
+        assert!(no_pov.relations().is_empty());
+    }
+
+    #[test]
+    fn test_compute_teammate_analytics() {
+        // Valorant: W W L L with "a" (a SquadOV user) in the first three; "b" joins for the first two.
+        // CS:GO: a W with "a"'s Steam account which should get merged with the Riot account.
+        let outcomes: Vec<TeammateMatchOutcome> = vec![
+            (1, SquadOvGames::Valorant, Some(true)),
+            (2, SquadOvGames::Valorant, Some(true)),
+            (3, SquadOvGames::Valorant, Some(false)),
+            (4, SquadOvGames::Valorant, Some(false)),
+            (5, SquadOvGames::Csgo, Some(true)),
+        ].into_iter().map(|(id, game, won)| {
+            TeammateMatchOutcome{
+                match_uuid: Uuid::from_u128(id),
+                game,
+                won,
+            }
+        }).collect();
+
+        let players = vec![
+            player(1, TEAMMATE_IDENTITY_RIOT, "a-riot", true, Some(2)),
+            player(2, TEAMMATE_IDENTITY_RIOT, "a-riot", true, Some(2)),
+            player(3, TEAMMATE_IDENTITY_RIOT, "a-riot", true, Some(2)),
+            player(5, TEAMMATE_IDENTITY_STEAM, "a-steam", true, Some(2)),
+            player(1, TEAMMATE_IDENTITY_RIOT, "b", true, None),
+            player(2, TEAMMATE_IDENTITY_RIOT, "b", true, None),
+            player(5, TEAMMATE_IDENTITY_STEAM, "b-steam", true, None),
+            player(1, TEAMMATE_IDENTITY_RIOT, "rival", false, None),
+            player(3, TEAMMATE_IDENTITY_RIOT, "rival", false, None),
+            player(4, TEAMMATE_IDENTITY_RIOT, "rival", false, None),
+            player(4, TEAMMATE_IDENTITY_RIOT, "rival", false, None),
+            // Not one of the user's matches.
+            player(9, TEAMMATE_IDENTITY_RIOT, "b", true, None),
+        ];
+
+        let analytics = compute_teammate_analytics(&outcomes, &players, 10);
+        assert_eq!(analytics.overall, TeammateRecord::new(5, 3, 2));
+        assert_eq!(analytics.total_matches, 5);
+        assert!(!analytics.truncated);
+
+        let a = &analytics.teammates[0];
+        assert_eq!(a.teammate.user_id, Some(2));
+        assert_eq!(a.teammate.identities.len(), 2);
+        assert_eq!(a.teammate.games.len(), 2);
+        assert_eq!(a.with, TeammateRecord::new(4, 3, 1));
+        // The only match without them is the last Valorant loss.
+        assert_eq!(a.without, TeammateRecord::new(1, 0, 1));
+        assert_eq!(a.synergy, Some(0.75));
+        assert_eq!(analytics.teammates.len(), 3);
+
+        // Only "a" played enough matches with the user.
+        assert_eq!(analytics.duos.len(), 1);
+        assert!(analytics.trios.is_empty());
+
+        let rival = &analytics.opponents[0];
+        assert_eq!(rival.opponent.display_name.as_deref(), Some("rival"));
+        assert_eq!(rival.record, TeammateRecord::new(3, 1, 2));
+        assert_eq!(analytics.rivalries.len(), 1);
+    }
+
+    #[test]
+    fn test_trio_synergy() {
+        let outcomes: Vec<TeammateMatchOutcome> = (1..=6).map(|id| {
+            TeammateMatchOutcome{
+                match_uuid: Uuid::from_u128(id),
+                game: SquadOvGames::LeagueOfLegends,
+                won: Some(id <= 3),
+            }
+        }).collect();
+
+        let mut players = vec![];
+        for id in 1..=3 {
+            players.push(player(id, TEAMMATE_IDENTITY_RIOT, "x", true, None));
+            players.push(player(id, TEAMMATE_IDENTITY_RIOT, "y", true, None));
+        }
+        players.push(player(4, TEAMMATE_IDENTITY_RIOT, "x", true, None));
+
+        let analytics = compute_teammate_analytics(&outcomes, &players, 10);
+        assert_eq!(analytics.trios.len(), 1);
+        assert_eq!(analytics.trios[0].with, TeammateRecord::new(3, 3, 0));
+        assert_eq!(analytics.trios[0].without, TeammateRecord::new(3, 0, 3));
+        assert_eq!(analytics.trios[0].synergy, Some(1.0));
+
+        // The user never lost with "y".
+        assert_eq!(analytics.duos.iter().map(|x| { x.members[0].display_name.clone().unwrap() }).collect::<Vec<String>>(), vec!["y", "x"]);
+    }
+}
@@ -0,0 +1,310 @@
+use crate::{
+    SquadOvError,
+    SquadOvGames,
+    teammates::{
+        MatchRoster,
+        TeammateAnalytics,
+        TeammateAnalyticsFilter,
+        TeammateAnalyticsSettings,
+        TeammateMatchOutcome,
+        TeammateMatchPlayer,
+        MAX_TEAMMATE_ANALYTICS_MATCHES,
+        compute_teammate_analytics,
+    },
+};
+use sqlx::{Executor, Transaction, Postgres, postgres::PgPool};
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+pub async fn store_match_roster(ex: &mut Transaction<'_, Postgres>, roster: &MatchRoster) -> Result<(), SquadOvError> {
+    sqlx::query(
+        "
+        INSERT INTO squadov.teammate_match_outcomes (
+            match_uuid,
+            user_id,
+            game,
+            match_time,
+            won
+        )
+        VALUES (
+            $1,
+            $2,
+            $3,
+            $4,
+            $5
+        )
+        ON CONFLICT (match_uuid, user_id) DO UPDATE SET
+            game = EXCLUDED.game,
+            match_time = EXCLUDED.match_time,
+            won = EXCLUDED.won
+        "
+    )
+        .bind(&roster.match_uuid)
+        .bind(roster.user_id)
+        .bind(roster.game as i32)
+        .bind(&roster.match_time)
+        .bind(roster.won)
+        .execute(&mut *ex)
+        .await?;
+
+    sqlx::query(
+        "
+        DELETE FROM squadov.teammate_match_players
+        WHERE match_uuid = $1
+            AND user_id = $2
+        "
+    )
+        .bind(&roster.match_uuid)
+        .bind(roster.user_id)
+        .execute(&mut *ex)
+        .await?;
+
+    let relations = roster.relations();
+    if relations.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query(
+        "
+        INSERT INTO squadov.teammate_match_players (
+            match_uuid,
+            user_id,
+            identity_type,
+            identity_id,
+            display_name,
+            is_teammate
+        )
+        SELECT $1, $2, r.identity_type, r.identity_id, r.display_name, r.is_teammate
+        FROM UNNEST($3::VARCHAR[], $4::VARCHAR[], $5::VARCHAR[], $6::BOOLEAN[]) AS r(identity_type, identity_id, display_name, is_teammate)
+        "
+    )
+        .bind(&roster.match_uuid)
+        .bind(roster.user_id)
+        .bind(relations.iter().map(|x| { x.identity_type.clone() }).collect::<Vec<String>>())
+        .bind(relations.iter().map(|x| { x.identity_id.clone() }).collect::<Vec<String>>())
+        .bind(relations.iter().map(|x| { x.display_name.clone() }).collect::<Vec<Option<String>>>())
+        .bind(relations.iter().map(|x| { x.is_teammate }).collect::<Vec<bool>>())
+        .execute(&mut *ex)
+        .await?;
+    Ok(())
+}
+
+// Called wherever match data gets stored so the roster gets rebuilt for every VOD of the match.
+pub async fn queue_teammate_roster_sync<'a, T>(ex: T, match_uuid: &Uuid) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        INSERT INTO squadov.teammate_roster_sync_queue (video_uuid)
+        SELECT v.video_uuid
+        FROM squadov.vods AS v
+        WHERE v.match_uuid = $1
+            AND v.user_uuid IS NOT NULL
+            AND NOT v.is_clip
+        ON CONFLICT (video_uuid) DO UPDATE SET
+            queued_tm = NULL
+        "
+    )
+        .bind(match_uuid)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_vods_for_teammate_roster_sync<'a, T>(ex: T, limit: i64) -> Result<Vec<Uuid>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar::<_, Uuid>(
+            "
+            UPDATE squadov.teammate_roster_sync_queue AS trsq
+            SET queued_tm = NOW()
+            FROM (
+                SELECT video_uuid
+                FROM squadov.teammate_roster_sync_queue
+                WHERE queued_tm IS NULL OR queued_tm < (NOW() - INTERVAL '1 day')
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            ) AS sub
+            WHERE sub.video_uuid = trsq.video_uuid
+            RETURNING trsq.video_uuid
+            "
+        )
+            .bind(limit)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub async fn finish_teammate_roster_sync<'a, T>(ex: T, video_uuid: &Uuid) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        DELETE FROM squadov.teammate_roster_sync_queue
+        WHERE video_uuid = $1
+        "
+    )
+        .bind(video_uuid)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_teammate_analytics_settings<'a, T>(ex: T, user_id: i64) -> Result<TeammateAnalyticsSettings, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar::<_, bool>(
+            "
+            SELECT show_in_teammate_analytics
+            FROM squadov.user_teammate_analytics_settings
+            WHERE user_id = $1
+            "
+        )
+            .bind(user_id)
+            .fetch_optional(ex)
+            .await?
+            .map(|x| {
+                TeammateAnalyticsSettings{
+                    show_in_teammate_analytics: x,
+                }
+            })
+            .unwrap_or_default()
+    )
+}
+
+pub async fn update_teammate_analytics_settings<'a, T>(ex: T, user_id: i64, settings: &TeammateAnalyticsSettings) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        INSERT INTO squadov.user_teammate_analytics_settings (
+            user_id,
+            show_in_teammate_analytics
+        ) VALUES (
+            $1,
+            $2
+        )
+        ON CONFLICT (user_id) DO UPDATE SET
+            show_in_teammate_analytics = EXCLUDED.show_in_teammate_analytics
+        "
+    )
+        .bind(user_id)
+        .bind(settings.show_in_teammate_analytics)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_teammate_analytics(ex: &PgPool, user_id: i64, filter: &TeammateAnalyticsFilter, limit: usize) -> Result<TeammateAnalytics, SquadOvError> {
+    let games: Option<Vec<i32>> = filter.games.as_ref().map(|x| { x.iter().map(|g| { *g as i32 }).collect() });
+
+    // The window count happens before the LIMIT so we know how many matches got left out.
+    let rows = sqlx::query_as::<_, (Uuid, i32, Option<bool>, i64)>(
+        "
+        SELECT tmo.match_uuid, tmo.game, tmo.won, COUNT(*) OVER ()
+        FROM squadov.teammate_match_outcomes AS tmo
+        WHERE tmo.user_id = $1
+            AND ($2::INTEGER[] IS NULL OR tmo.game = ANY($2))
+            AND ($3::TIMESTAMPTZ IS NULL OR tmo.match_time >= $3)
+            AND ($4::TIMESTAMPTZ IS NULL OR tmo.match_time <= $4)
+        ORDER BY tmo.match_time DESC
+        LIMIT $5
+        "
+    )
+        .bind(user_id)
+        .bind(&games)
+        .bind(&filter.start)
+        .bind(&filter.end)
+        .bind(MAX_TEAMMATE_ANALYTICS_MATCHES)
+        .fetch_all(ex)
+        .await?;
+
+    let total_matches = rows.first().map(|x| { x.3 }).unwrap_or(0);
+    let outcomes: Vec<TeammateMatchOutcome> = rows
+        .into_iter()
+        .map(|(match_uuid, game, won, _)| {
+            TeammateMatchOutcome{
+                match_uuid,
+                game: SquadOvGames::try_from(game).unwrap_or(SquadOvGames::Unknown),
+                won,
+            }
+        })
+        .collect();
+
+    // Game identities get matched up with SquadOV users here rather than when the match is stored so that accounts
+    // linked after the fact get picked up too. Only Steam accounts the user verified count and users stay anonymous
+    // unless they've opted into showing up in other users' teammate analytics.
+    let players: Vec<TeammateMatchPlayer> = sqlx::query_as::<_, (Uuid, String, String, Option<String>, bool, Option<i64>, Option<String>)>(
+        "
+        SELECT
+            tmp.match_uuid,
+            tmp.identity_type,
+            tmp.identity_id,
+            COALESCE(tmp.display_name, ra.game_name || '#' || ra.tag_line),
+            tmp.is_teammate,
+            lu.id,
+            lu.username
+        FROM squadov.teammate_match_players AS tmp
+        LEFT JOIN squadov.riot_accounts AS ra
+            ON tmp.identity_type = 'riot'
+                AND ra.puuid = tmp.identity_id
+        LEFT JOIN LATERAL (
+            SELECT u.id, u.username
+            FROM squadov.users AS u
+            INNER JOIN squadov.user_teammate_analytics_settings AS utas
+                ON utas.user_id = u.id
+            WHERE u.id IN (
+                SELECT ral.user_id
+                FROM squadov.riot_account_links AS ral
+                WHERE tmp.identity_type = 'riot'
+                    AND ral.puuid = tmp.identity_id
+                UNION ALL
+                SELECT vsul.user_id
+                FROM squadov.view_verified_steam_user_links AS vsul
+                WHERE tmp.identity_type = 'steam'
+                    AND vsul.steam_id = CASE WHEN tmp.identity_type = 'steam' THEN tmp.identity_id::BIGINT END
+                UNION ALL
+                SELECT wucc.user_id
+                FROM squadov.wow_user_character_cache AS wucc
+                WHERE tmp.identity_type = 'wow'
+                    AND wucc.unit_guid = tmp.identity_id
+            )
+                AND u.id != tmp.user_id
+                AND utas.show_in_teammate_analytics
+            ORDER BY u.id
+            LIMIT 1
+        ) AS lu ON TRUE
+        WHERE tmp.user_id = $1
+            AND tmp.match_uuid = ANY($2::UUID[])
+        "
+    )
+        .bind(user_id)
+        .bind(outcomes.iter().map(|x| { x.match_uuid.clone() }).collect::<Vec<Uuid>>())
+        .fetch_all(ex)
+        .await?
+        .into_iter()
+        .map(|(match_uuid, identity_type, identity_id, display_name, is_teammate, linked_user_id, linked_username)| {
+            TeammateMatchPlayer{
+                match_uuid,
+                identity_type,
+                identity_id,
+                display_name,
+                is_teammate,
+                linked_user_id,
+                linked_username,
+            }
+        })
+        .collect();
+
+    let mut analytics = compute_teammate_analytics(&outcomes, &players, limit);
+    analytics.total_matches = total_matches;
+    analytics.truncated = total_matches > outcomes.len() as i64;
+    Ok(analytics)
+}
@@ -0,0 +1,190 @@
+use crate::{
+    SquadOvError,
+    SquadOvGames,
+    csgo::db as csgo_db,
+    elastic::vod::{
+        ESVodDocument,
+        ESVodCachedValorant,
+        ESVodCachedLol,
+        ESVodCachedHearthstone,
+        ESVodCachedWow,
+    },
+    teammates::{
+        MatchRoster,
+        MatchRosterPlayer,
+        TEAMMATE_IDENTITY_RIOT,
+        TEAMMATE_IDENTITY_STEAM,
+        TEAMMATE_IDENTITY_WOW,
+        TEAMMATE_IDENTITY_HEARTHSTONE,
+    },
+};
+use sqlx::{Executor, Postgres};
+
+fn valorant_roster(data: &ESVodCachedValorant) -> (Vec<MatchRosterPlayer>, Option<bool>) {
+    let mut won = None;
+    let mut players = vec![];
+    for t in &data.teams {
+        for p in &t.players {
+            if p.is_pov {
+                won = Some(t.team.won);
+            }
+
+            players.push(MatchRosterPlayer{
+                identity_type: String::from(TEAMMATE_IDENTITY_RIOT),
+                identity_id: p.info.puuid.clone(),
+                // Valorant names get pulled from riot_accounts when we need them.
+                display_name: None,
+                team: t.team.team_id.clone(),
+                is_pov: p.is_pov,
+            });
+        }
+    }
+    (players, won)
+}
+
+fn lol_roster(data: &ESVodCachedLol) -> (Vec<MatchRosterPlayer>, Option<bool>) {
+    let mut won = None;
+    let mut players = vec![];
+    for t in &data.teams {
+        for p in &t.players {
+            if p.is_pov {
+                won = Some(t.team.win);
+            }
+
+            let display_name = if !p.info.riot_id_name.is_empty() {
+                Some(format!("{}#{}", &p.info.riot_id_name, &p.info.riot_id_tagline))
+            } else if !p.info.summoner_name.is_empty() {
+                Some(p.info.summoner_name.clone())
+            } else {
+                None
+            };
+
+            players.push(MatchRosterPlayer{
+                identity_type: String::from(TEAMMATE_IDENTITY_RIOT),
+                identity_id: p.info.puuid.clone(),
+                display_name,
+                team: t.team.team_id.to_string(),
+                is_pov: p.is_pov,
+            });
+        }
+    }
+    (players, won)
+}
+
+// Hearthstone is always 1v1 so everyone is on their own team.
+fn hearthstone_roster(data: &ESVodCachedHearthstone) -> (Vec<MatchRosterPlayer>, Option<bool>) {
+    let metadata = &data.packet.metadata;
+    let local_id = metadata.players.iter().find(|(_, p)| { p.local }).map(|(id, _)| { *id });
+    let won = match (local_id, data.packet.latest_snapshot.as_ref().and_then(|x| { x.get_match_winner_player_id() })) {
+        (Some(local), Some(winner)) => Some(local == winner),
+        _ => None,
+    };
+
+    let players = metadata.players.iter()
+        .filter(|(_, p)| { !p.name.is_empty() })
+        .map(|(id, p)| {
+            MatchRosterPlayer{
+                identity_type: String::from(TEAMMATE_IDENTITY_HEARTHSTONE),
+                identity_id: p.name.clone(),
+                display_name: Some(p.name.clone()),
+                team: id.to_string(),
+                is_pov: p.local,
+            }
+        })
+        .collect();
+    (players, won)
+}
+
+fn wow_roster(data: &ESVodCachedWow) -> (Vec<MatchRosterPlayer>, Option<bool>) {
+    let mut won = None;
+    let mut players = vec![];
+    for t in &data.teams {
+        for p in &t.players {
+            if p.is_pov {
+                won = Some(t.team.won);
+            }
+
+            players.push(MatchRosterPlayer{
+                identity_type: String::from(TEAMMATE_IDENTITY_WOW),
+                identity_id: p.info.data.guid.clone(),
+                display_name: if p.info.data.name.is_empty() { None } else { Some(p.info.data.name.clone()) },
+                team: t.team.id.to_string(),
+                is_pov: p.is_pov,
+            });
+        }
+    }
+    (players, won)
+}
+
+// The roster of the VOD's match from the point of view of the VOD's owner. None if the VOD isn't for a match
+// or the game isn't one where people play with or against others.
+pub async fn build_match_roster<'a, T>(ex: T, doc: &ESVodDocument) -> Result<Option<MatchRoster>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    // Clips would just repeat the match of the VOD they were clipped from.
+    if doc.vod.is_clip {
+        return Ok(None);
+    }
+
+    let match_uuid = match doc.data.match_uuid.or(doc.vod.match_uuid) {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+
+    let (players, won) = match doc.data.game {
+        SquadOvGames::Valorant => match &doc.data.valorant {
+            Some(x) => valorant_roster(x),
+            None => return Ok(None),
+        },
+        SquadOvGames::LeagueOfLegends => match &doc.data.lol {
+            Some(x) => lol_roster(x),
+            None => return Ok(None),
+        },
+        SquadOvGames::Hearthstone => match &doc.data.hearthstone {
+            Some(x) => hearthstone_roster(x),
+            None => return Ok(None),
+        },
+        SquadOvGames::WorldOfWarcraft => match &doc.data.wow {
+            Some(x) => wow_roster(x),
+            None => return Ok(None),
+        },
+        SquadOvGames::Csgo => match &doc.data.csgo {
+            Some(x) => {
+                let players = csgo_db::get_csgo_match_player_teams(ex, &match_uuid, doc.owner.user_id).await?
+                    .into_iter()
+                    .map(|(steam_id, steam_name, team)| {
+                        MatchRosterPlayer{
+                            identity_type: String::from(TEAMMATE_IDENTITY_STEAM),
+                            identity_id: steam_id.to_string(),
+                            display_name: steam_name,
+                            team: team.to_string(),
+                            is_pov: steam_id == x.pov.steam_id,
+                        }
+                    })
+                    .collect();
+                (players, Some(x.pov.winner))
+            },
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+
+    let match_time = match doc.data.hearthstone.as_ref().map(|x| { x.packet.metadata.match_time }).or(doc.vod.start_time).or(doc.vod.end_time) {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+
+    if players.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(MatchRoster{
+        match_uuid,
+        user_id: doc.owner.user_id,
+        game: doc.data.game,
+        match_time,
+        won,
+        players,
+    }))
+}
@@ -2,6 +2,8 @@ pub mod oauth;
 pub mod api;
 pub mod rabbitmq;
 pub mod eventsub;
+pub mod streams;
+pub mod mock;
 
 use serde::Deserialize;
 
@@ -11,4 +13,17 @@ pub struct TwitchConfig {
     pub client_id: String,
     pub client_secret: String,
     pub eventsub_hostname: String,
-}
\ No newline at end of file
+    // Both of these only need to be changed to point at a fake Twitch server (see twitch::mock).
+    #[serde(default="default_twitch_helix_url")]
+    pub helix_url: String,
+    #[serde(default="default_twitch_id_url")]
+    pub id_url: String,
+}
+
+fn default_twitch_helix_url() -> String {
+    String::from("https://api.twitch.tv/helix")
+}
+
+fn default_twitch_id_url() -> String {
+    String::from("https://id.twitch.tv/oauth2")
+}
@@ -17,8 +17,7 @@ use reqwest::{StatusCode, header};
 use std::sync::Arc;
 use async_std::sync::RwLock;
 use sqlx::PgPool;
-
-const TWITCH_HELIX_URL : &'static str = "https://api.twitch.tv/helix";
+use chrono::{DateTime, Utc, SecondsFormat};
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum TwitchTokenType {
@@ -69,6 +68,65 @@ impl TwitchSubscriptionAlt {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TwitchVideo {
+    pub id: String,
+    // Only set for archives (past broadcasts).
+    pub stream_id: Option<String>,
+    pub user_id: String,
+    pub url: String,
+    pub created_at: DateTime<Utc>,
+    // Twitch gives this as a string like 3h8m33s.
+    pub duration: String,
+    #[serde(rename="type")]
+    pub video_type: String,
+}
+
+impl TwitchVideo {
+    pub fn duration_seconds(&self) -> Option<i64> {
+        parse_twitch_duration(&self.duration)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TwitchClip {
+    pub id: String,
+    pub url: String,
+    pub broadcaster_id: String,
+    // Empty if the clip's VOD isn't available.
+    #[serde(default)]
+    pub video_id: String,
+    pub created_at: DateTime<Utc>,
+    pub duration: f64,
+    // Seconds into the VOD where the clip starts.
+    pub vod_offset: Option<i64>,
+}
+
+pub fn parse_twitch_duration(duration: &str) -> Option<i64> {
+    let mut total: i64 = 0;
+    let mut current = String::new();
+    for c in duration.chars() {
+        if c.is_ascii_digit() {
+            current.push(c);
+            continue;
+        }
+
+        let value = current.parse::<i64>().ok()?;
+        current.clear();
+        total += value * match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+    }
+
+    if current.is_empty() {
+        Some(total)
+    } else {
+        None
+    }
+}
 
 impl TwitchApiClient {
     pub fn new(config: TwitchConfig, token: TwitchOAuthToken, typ: TwitchTokenType, pool: Arc<PgPool>) -> Self {
@@ -87,12 +145,12 @@ impl TwitchApiClient {
             if self.token_type == TwitchTokenType::App {
                 let is_valid = {
                     let tk = self.token.read().await;
-                    oauth::validate_access_token(&tk.access_token).await?
+                    oauth::validate_access_token(&self.config.id_url, &tk.access_token).await?
                 };
 
                 if !is_valid {
                     let mut tk = self.token.write().await;
-                    let new_token = oauth::get_oauth_client_credentials_token(&self.config.client_id, &self.config.client_secret).await?;
+                    let new_token = oauth::get_oauth_client_credentials_token(&self.config.id_url, &self.config.client_id, &self.config.client_secret).await?;
                     tk.copy_from(&new_token);
                 }
             }
@@ -110,7 +168,7 @@ impl TwitchApiClient {
     }
 
     pub async fn refresh_user_access_token(&self) -> Result<(), SquadOvError> {
-        let new_token = oauth::refresh_oauth_token(&self.config.client_id, &self.config.client_secret, &{
+        let new_token = oauth::refresh_oauth_token(&self.config.id_url, &self.config.client_id, &self.config.client_secret, &{
             let tk = self.token.read().await;
             tk.refresh_token.clone()
         }).await?;
@@ -151,11 +209,12 @@ impl TwitchApiClient {
     pub async fn get_basic_account_info(&self, broadcaster_id: &str) -> Result<TwitchAccount, SquadOvError> {
         let resp = {
             let bid = broadcaster_id.to_string();
+            let helix = self.config.helix_url.clone();
             self.execute(Arc::new(move |client: &reqwest::Client| {
                 client.get(
                     &format!(
                         "{}/channels?broadcaster_id={}",
-                        TWITCH_HELIX_URL,
+                        &helix,
                         bid.clone(),
                     )
                 )
@@ -201,12 +260,13 @@ impl TwitchApiClient {
             let sub = sub.to_string();
             let condition = condition.clone();
             let transport = transport.clone();
+            let helix = self.config.helix_url.clone();
 
             self.execute(Arc::new(move |client: &reqwest::Client| {
                 client.post(
                     &format!(
                         "{}/eventsub/subscriptions",
-                        TWITCH_HELIX_URL,
+                        &helix,
                     )
                 )
                 .json(&Request{
@@ -218,6 +278,11 @@ impl TwitchApiClient {
             })).await?
         };
 
+        // Twitch won't let us create the same subscription twice.
+        if resp.status() == StatusCode::CONFLICT {
+            return Err(SquadOvError::Duplicate);
+        }
+
         if resp.status() != StatusCode::ACCEPTED {
             return Err(SquadOvError::InternalError(format!("Register Eventsub Twitch: {} - {}", resp.status().as_u16(), resp.text().await?)));
         }
@@ -229,10 +294,11 @@ impl TwitchApiClient {
         let resp = {
             let bid = broadcaster_id.to_string();
             let cursor = cursor.clone();
+            let helix = self.config.helix_url.clone();
             self.execute(Arc::new(move |client: &reqwest::Client| {
                 let base = format!(
                     "{}/subscriptions?broadcaster_id={}",
-                    TWITCH_HELIX_URL,
+                    &helix,
                     bid.clone(),
                 );
 
@@ -266,4 +332,89 @@ impl TwitchApiClient {
             None
         }))
     }
-}
\ No newline at end of file
+
+    // Past broadcasts of the user, most recent first. We only ever need the ones for streams that just ended so there's no pagination.
+    pub async fn get_archive_videos(&self, user_id: &str) -> Result<Vec<TwitchVideo>, SquadOvError> {
+        let resp = {
+            let uid = user_id.to_string();
+            let helix = self.config.helix_url.clone();
+            self.execute(Arc::new(move |client: &reqwest::Client| {
+                client.get(&format!("{}/videos", &helix))
+                    .query(&[
+                        ("user_id", uid.as_str()),
+                        ("type", "archive"),
+                        ("first", "20"),
+                    ])
+            })).await?
+        };
+
+        if resp.status() != StatusCode::OK {
+            return Err(SquadOvError::InternalError(format!("Get Videos Twitch: {} - {}", resp.status().as_u16(), resp.text().await?)));
+        }
+
+        #[derive(Deserialize)]
+        struct Response {
+            data: Vec<TwitchVideo>,
+        }
+
+        Ok(resp.json::<Response>().await?.data)
+    }
+
+    pub async fn get_clips(&self, broadcaster_id: &str, start: &DateTime<Utc>, end: &DateTime<Utc>, cursor: Option<String>) -> Result<(Vec<TwitchClip>, Option<String>), SquadOvError> {
+        let resp = {
+            let bid = broadcaster_id.to_string();
+            let start = start.to_rfc3339_opts(SecondsFormat::Secs, true);
+            let end = end.to_rfc3339_opts(SecondsFormat::Secs, true);
+            let cursor = cursor.clone();
+            let helix = self.config.helix_url.clone();
+            self.execute(Arc::new(move |client: &reqwest::Client| {
+                let req = client.get(&format!("{}/clips", &helix))
+                    .query(&[
+                        ("broadcaster_id", bid.as_str()),
+                        ("started_at", start.as_str()),
+                        ("ended_at", end.as_str()),
+                        ("first", "100"),
+                    ]);
+
+                if let Some(cursor) = cursor.as_ref() {
+                    req.query(&[("after", cursor.as_str())])
+                } else {
+                    req
+                }
+            })).await?
+        };
+
+        if resp.status() != StatusCode::OK {
+            return Err(SquadOvError::InternalError(format!("Get Clips Twitch: {} - {}", resp.status().as_u16(), resp.text().await?)));
+        }
+
+        #[derive(Deserialize)]
+        struct Pagination {
+            cursor: Option<String>,
+        }
+
+        #[derive(Deserialize)]
+        struct Response {
+            data: Vec<TwitchClip>,
+            pagination: Option<Pagination>,
+        }
+
+        let data = resp.json::<Response>().await?;
+        Ok((data.data, data.pagination.and_then(|x| { x.cursor })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_twitch_duration() {
+        assert_eq!(parse_twitch_duration("3h8m33s"), Some(11313));
+        assert_eq!(parse_twitch_duration("45m"), Some(2700));
+        assert_eq!(parse_twitch_duration("12s"), Some(12));
+        assert_eq!(parse_twitch_duration(""), Some(0));
+        assert_eq!(parse_twitch_duration("12"), None);
+        assert_eq!(parse_twitch_duration("1d2h"), None);
+    }
+}
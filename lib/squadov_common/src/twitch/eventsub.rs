@@ -4,9 +4,63 @@ use crate::{
     sql
 };
 use sqlx::{Executor, Postgres};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use sha2::Sha256;
+use hmac::{Hmac, Mac};
 
 pub const TWITCH_CHANNEL_SUBSCRIBE: &'static str = "channel.subscribe";
 pub const TWITCH_CHANNEL_UNSUB: &'static str = "channel.subscription.end";
+pub const TWITCH_STREAM_ONLINE: &'static str = "stream.online";
+pub const TWITCH_STREAM_OFFLINE: &'static str = "stream.offline";
+
+// Twitch recommends rejecting messages older than this so old messages can't be replayed.
+const TWITCH_EVENTSUB_MAX_MESSAGE_AGE_SECONDS: i64 = 600;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TwitchStreamOnlineEvent {
+    // The stream ID which is also what the archived video references once the stream is over.
+    pub id: String,
+    pub broadcaster_user_id: String,
+    pub broadcaster_user_login: String,
+    pub broadcaster_user_name: String,
+    #[serde(rename="type")]
+    pub stream_type: String,
+    pub started_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TwitchStreamOfflineEvent {
+    pub broadcaster_user_id: String,
+    pub broadcaster_user_login: String,
+    pub broadcaster_user_name: String,
+}
+
+// Value for the Twitch-Eventsub-Message-Signature header.
+pub fn sign_twitch_eventsub_message(secret: &str, message_id: &str, timestamp: &str, body: &[u8]) -> Result<String, SquadOvError> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(message_id.as_bytes());
+    mac.update(timestamp.as_bytes());
+    mac.update(body);
+    Ok(format!("sha256={}", hex::encode(mac.finalize().into_bytes())))
+}
+
+pub fn verify_twitch_eventsub_message(secret: &str, message_id: &str, timestamp: &str, body: &[u8], signature: &str, now: DateTime<Utc>) -> Result<(), SquadOvError> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(message_id.as_bytes());
+    mac.update(timestamp.as_bytes());
+    mac.update(body);
+
+    let signature = signature.strip_prefix("sha256=").ok_or(SquadOvError::Forbidden)?;
+    let signature = hex::decode(signature).map_err(|_| { SquadOvError::Forbidden })?;
+    mac.verify_slice(&signature).map_err(|_| { SquadOvError::Forbidden })?;
+
+    let sent = DateTime::parse_from_rfc3339(timestamp).map_err(|_| { SquadOvError::Forbidden })?;
+    if (now - sent.with_timezone(&Utc)).num_seconds().abs() > TWITCH_EVENTSUB_MAX_MESSAGE_AGE_SECONDS {
+        return Err(SquadOvError::Forbidden);
+    }
+    Ok(())
+}
 
 pub async fn insert_twitch_eventsub<'a, T>(ex: T, id: &str, sub: &str, raw: serde_json::Value) -> Result<(), SquadOvError>
 where
@@ -92,4 +146,25 @@ where
         .execute(ex)
         .await?;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_eventsub_signature() {
+        let now = Utc::now();
+        let timestamp = now.to_rfc3339();
+        let body = br#"{"subscription":{"id":"abc","type":"stream.online"}}"#;
+        let sig = sign_twitch_eventsub_message("secret", "msg-1", &timestamp, body).unwrap();
+
+        assert!(verify_twitch_eventsub_message("secret", "msg-1", &timestamp, body, &sig, now).is_ok());
+        assert!(verify_twitch_eventsub_message("other", "msg-1", &timestamp, body, &sig, now).is_err());
+        assert!(verify_twitch_eventsub_message("secret", "msg-2", &timestamp, body, &sig, now).is_err());
+        assert!(verify_twitch_eventsub_message("secret", "msg-1", &timestamp, b"{}", &sig, now).is_err());
+        assert!(verify_twitch_eventsub_message("secret", "msg-1", &timestamp, body, "garbage", now).is_err());
+        assert!(verify_twitch_eventsub_message("secret", "msg-1", &timestamp, body, &sig, now + Duration::minutes(11)).is_err());
+    }
+}
@@ -0,0 +1,548 @@
+// A small in-memory stand-in for the parts of Twitch that we use (OAuth tokens, Helix and EventSub) so that
+// the Twitch integration can be tested without real Twitch accounts. Point TwitchConfig::id_url and
+// TwitchConfig::helix_url at TwitchMockServer::id_url and TwitchMockServer::helix_url.
+//
+// EventSub subscriptions created through Helix go through the same callback verification (challenge) as on
+// Twitch. Streams can then be started/stopped (TwitchMockServer::go_online/go_offline or the /__mock endpoints)
+// which sends signed stream.online/stream.offline notifications to every enabled subscription that matches.
+// Ending a stream also creates its archive video like Twitch does for broadcasters with past broadcasts enabled.
+use crate::{
+    SquadOvError,
+    twitch::{
+        api::{TwitchVideo, TwitchClip},
+        eventsub::{
+            self,
+            TwitchStreamOnlineEvent,
+            TwitchStreamOfflineEvent,
+            TWITCH_STREAM_ONLINE,
+            TWITCH_STREAM_OFFLINE,
+        },
+    },
+};
+use actix_web::{
+    web,
+    App,
+    HttpRequest,
+    HttpResponse,
+    HttpServer,
+    dev::ServerHandle,
+};
+use chrono::{DateTime, Utc, SecondsFormat};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct TwitchMockFixtures {
+    // Objects in the same format as GET /helix/channels.
+    #[serde(default)]
+    pub channels: Vec<Value>,
+    #[serde(default)]
+    pub videos: Vec<TwitchVideo>,
+    #[serde(default)]
+    pub clips: Vec<TwitchClip>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct TwitchMockSubscription {
+    pub id: String,
+    pub sub_type: String,
+    pub condition: Value,
+    pub callback: String,
+    pub status: String,
+    #[serde(skip)]
+    secret: String,
+}
+
+impl TwitchMockSubscription {
+    fn to_json(&self) -> Value {
+        json!({
+            "id": &self.id,
+            "status": &self.status,
+            "type": &self.sub_type,
+            "version": "1",
+            "cost": 0,
+            "condition": &self.condition,
+            "transport": {
+                "method": "webhook",
+                "callback": &self.callback,
+            },
+            "created_at": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        })
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all="camelCase")]
+pub struct TwitchMockDelivery {
+    pub message_id: String,
+    pub message_type: String,
+    pub subscription_type: String,
+    // None if the request didn't make it to the callback at all.
+    pub status: Option<u16>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all="camelCase")]
+struct TwitchMockOnlineRequest {
+    id: Option<String>,
+    started_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+struct TwitchMockOfflineRequest {
+    ended_at: Option<DateTime<Utc>>,
+    #[serde(default="default_create_archive")]
+    archive: bool,
+}
+
+impl Default for TwitchMockOfflineRequest {
+    fn default() -> Self {
+        Self {
+            ended_at: None,
+            archive: default_create_archive(),
+        }
+    }
+}
+
+fn default_create_archive() -> bool {
+    true
+}
+
+#[derive(Default)]
+struct TwitchMockState {
+    channels: Mutex<HashMap<String, Value>>,
+    videos: Mutex<Vec<TwitchVideo>>,
+    clips: Mutex<Vec<TwitchClip>>,
+    subscriptions: Mutex<Vec<TwitchMockSubscription>>,
+    // Broadcaster ID to the stream that's currently live.
+    streams: Mutex<HashMap<String, TwitchStreamOnlineEvent>>,
+    next_id: Mutex<u64>,
+    requests: Mutex<HashMap<String, usize>>,
+    deliveries: Mutex<Vec<TwitchMockDelivery>>,
+}
+
+fn format_twitch_duration(seconds: i64) -> String {
+    format!("{}h{}m{}s", seconds / 3600, (seconds % 3600) / 60, seconds % 60)
+}
+
+impl TwitchMockState {
+    fn generate_id(&self) -> String {
+        let mut next = self.next_id.lock().unwrap();
+        *next += 1;
+        format!("{}", 1000 + *next)
+    }
+
+    fn channel_names(&self, broadcaster_id: &str) -> (String, String) {
+        let channels = self.channels.lock().unwrap();
+        let name = channels.get(broadcaster_id)
+            .and_then(|x| { x.get("broadcaster_name") })
+            .and_then(|x| { x.as_str() })
+            .map(|x| { x.to_string() })
+            .unwrap_or(format!("mock{}", broadcaster_id));
+        (name.to_lowercase(), name)
+    }
+
+    async fn send_message(&self, sub: &TwitchMockSubscription, message_type: &str, body: Value) -> (TwitchMockDelivery, Option<String>) {
+        let message_id = uuid::Uuid::new_v4().to_string();
+        let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true);
+        let payload = body.to_string();
+
+        let mut delivery = TwitchMockDelivery{
+            message_id: message_id.clone(),
+            message_type: message_type.to_string(),
+            subscription_type: sub.sub_type.clone(),
+            status: None,
+        };
+
+        let signature = match eventsub::sign_twitch_eventsub_message(&sub.secret, &message_id, &timestamp, payload.as_bytes()) {
+            Ok(x) => x,
+            Err(err) => {
+                log::warn!("Failed to sign mock Twitch message: {:?}", err);
+                return (delivery, None);
+            }
+        };
+
+        let result = reqwest::Client::new().post(&sub.callback)
+            .header("Twitch-Eventsub-Message-Id", &message_id)
+            .header("Twitch-Eventsub-Message-Retry", "0")
+            .header("Twitch-Eventsub-Message-Type", message_type)
+            .header("Twitch-Eventsub-Message-Signature", signature)
+            .header("Twitch-Eventsub-Message-Timestamp", &timestamp)
+            .header("Twitch-Eventsub-Subscription-Type", &sub.sub_type)
+            .header("Twitch-Eventsub-Subscription-Version", "1")
+            .header("Content-Type", "application/json")
+            .body(payload)
+            .send()
+            .await;
+
+        let text = match result {
+            Ok(resp) => {
+                delivery.status = Some(resp.status().as_u16());
+                resp.text().await.ok()
+            },
+            Err(err) => {
+                log::warn!("Failed to deliver mock Twitch message: {:?}", err);
+                None
+            }
+        };
+
+        self.deliveries.lock().unwrap().push(delivery.clone());
+        (delivery, text)
+    }
+
+    // Twitch responds to the create request right away and verifies the callback afterwards. Here the verification
+    // happens first so the subscription is usable as soon as the create request returns.
+    async fn create_subscription(&self, body: &Value) -> Result<TwitchMockSubscription, HttpResponse> {
+        let sub_type = body["type"].as_str().ok_or(HttpResponse::BadRequest().finish())?.to_string();
+        let condition = body["condition"].clone();
+        let transport = &body["transport"];
+        if transport["method"].as_str() != Some("webhook") {
+            return Err(HttpResponse::BadRequest().finish());
+        }
+
+        let callback = transport["callback"].as_str().ok_or(HttpResponse::BadRequest().finish())?.to_string();
+        let secret = transport["secret"].as_str().ok_or(HttpResponse::BadRequest().finish())?.to_string();
+
+        if self.subscriptions.lock().unwrap().iter().any(|x| { x.sub_type == sub_type && x.condition == condition && x.status == "enabled" }) {
+            return Err(HttpResponse::Conflict().json(json!({
+                "error": "Conflict",
+                "status": 409,
+                "message": "subscription already exists",
+            })));
+        }
+
+        let mut sub = TwitchMockSubscription{
+            id: uuid::Uuid::new_v4().to_string(),
+            sub_type,
+            condition,
+            callback,
+            status: String::from("webhook_callback_verification_pending"),
+            secret,
+        };
+
+        let challenge = uuid::Uuid::new_v4().to_string();
+        let (delivery, response) = self.send_message(&sub, "webhook_callback_verification", json!({
+            "challenge": &challenge,
+            "subscription": sub.to_json(),
+        })).await;
+
+        sub.status = if delivery.status == Some(200) && response.as_deref() == Some(challenge.as_str()) {
+            String::from("enabled")
+        } else {
+            String::from("webhook_callback_verification_failed")
+        };
+
+        self.subscriptions.lock().unwrap().push(sub.clone());
+        Ok(sub)
+    }
+
+    async fn notify(&self, sub_type: &str, broadcaster_id: &str, event: Value) -> Vec<TwitchMockDelivery> {
+        let subs: Vec<TwitchMockSubscription> = self.subscriptions.lock().unwrap().iter().filter(|x| {
+            x.status == "enabled" && x.sub_type == sub_type && x.condition["broadcaster_user_id"].as_str() == Some(broadcaster_id)
+        }).cloned().collect();
+
+        let mut ret = vec![];
+        for s in subs {
+            let (delivery, _) = self.send_message(&s, "notification", json!({
+                "subscription": s.to_json(),
+                "event": &event,
+            })).await;
+            ret.push(delivery);
+        }
+        ret
+    }
+
+    async fn go_online(&self, broadcaster_id: &str, req: TwitchMockOnlineRequest) -> Result<Vec<TwitchMockDelivery>, SquadOvError> {
+        let (login, name) = self.channel_names(broadcaster_id);
+        let event = TwitchStreamOnlineEvent{
+            id: req.id.unwrap_or_else(|| { self.generate_id() }),
+            broadcaster_user_id: broadcaster_id.to_string(),
+            broadcaster_user_login: login,
+            broadcaster_user_name: name,
+            stream_type: String::from("live"),
+            started_at: req.started_at.unwrap_or_else(Utc::now),
+        };
+
+        self.streams.lock().unwrap().insert(broadcaster_id.to_string(), event.clone());
+        Ok(self.notify(TWITCH_STREAM_ONLINE, broadcaster_id, serde_json::to_value(&event)?).await)
+    }
+
+    async fn go_offline(&self, broadcaster_id: &str, req: TwitchMockOfflineRequest) -> Result<Vec<TwitchMockDelivery>, SquadOvError> {
+        let stream = self.streams.lock().unwrap().remove(broadcaster_id).ok_or(SquadOvError::NotFound)?;
+        if req.archive {
+            let ended_at = req.ended_at.unwrap_or_else(Utc::now);
+            let id = self.generate_id();
+            self.videos.lock().unwrap().push(TwitchVideo{
+                url: format!("https://www.twitch.tv/videos/{}", &id),
+                id,
+                stream_id: Some(stream.id.clone()),
+                user_id: broadcaster_id.to_string(),
+                created_at: stream.started_at.clone(),
+                duration: format_twitch_duration((ended_at - stream.started_at).num_seconds().max(0)),
+                video_type: String::from("archive"),
+            });
+        }
+
+        let event = TwitchStreamOfflineEvent{
+            broadcaster_user_id: stream.broadcaster_user_id,
+            broadcaster_user_login: stream.broadcaster_user_login,
+            broadcaster_user_name: stream.broadcaster_user_name,
+        };
+        Ok(self.notify(TWITCH_STREAM_OFFLINE, broadcaster_id, serde_json::to_value(&event)?).await)
+    }
+}
+
+fn query_value<'a>(query: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    query.iter().find(|(k, _)| { k == key }).map(|(_, v)| { v.as_str() })
+}
+
+fn query_time(query: &[(String, String)], key: &str) -> Option<DateTime<Utc>> {
+    query_value(query, key).and_then(|x| { DateTime::parse_from_rfc3339(x).ok() }).map(|x| { x.with_timezone(&Utc) })
+}
+
+fn data_response(data: Value) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "data": data,
+        "pagination": {},
+    }))
+}
+
+fn error_response(err: SquadOvError) -> HttpResponse {
+    match err {
+        SquadOvError::NotFound => HttpResponse::NotFound().finish(),
+        _ => HttpResponse::BadRequest().body(format!("{:?}", err)),
+    }
+}
+
+fn handle_oauth(path: &str) -> HttpResponse {
+    match path {
+        "token" => HttpResponse::Ok().json(json!({
+            "access_token": "mock_access_token",
+            "refresh_token": "mock_refresh_token",
+            "expires_in": 14400,
+            "token_type": "bearer",
+        })),
+        "validate" => HttpResponse::Ok().json(json!({
+            "client_id": "mock",
+            "expires_in": 14400,
+        })),
+        _ => HttpResponse::NotFound().finish(),
+    }
+}
+
+async fn handle_helix(state: &TwitchMockState, req: &HttpRequest, path: &str, body: &[u8]) -> HttpResponse {
+    let query: Vec<(String, String)> = url::form_urlencoded::parse(req.query_string().as_bytes()).into_owned().collect();
+    let method = req.method().clone();
+
+    match (method.as_str(), path) {
+        ("GET", "channels") => {
+            let channels = state.channels.lock().unwrap();
+            data_response(json!(
+                query_value(&query, "broadcaster_id").and_then(|x| { channels.get(x) }).cloned().into_iter().collect::<Vec<Value>>()
+            ))
+        },
+        ("GET", "subscriptions") => data_response(json!([])),
+        ("GET", "videos") => {
+            let user_id = query_value(&query, "user_id");
+            let video_type = query_value(&query, "type").filter(|x| { *x != "all" });
+            let mut videos: Vec<TwitchVideo> = state.videos.lock().unwrap().iter().filter(|x| {
+                user_id.map(|u| { x.user_id == u }).unwrap_or(true) &&
+                    video_type.map(|t| { x.video_type == t }).unwrap_or(true)
+            }).cloned().collect();
+            videos.sort_by(|a, b| { b.created_at.cmp(&a.created_at) });
+            data_response(json!(videos))
+        },
+        ("GET", "clips") => {
+            let broadcaster_id = query_value(&query, "broadcaster_id");
+            let start = query_time(&query, "started_at");
+            let end = query_time(&query, "ended_at");
+            data_response(json!(state.clips.lock().unwrap().iter().filter(|x| {
+                broadcaster_id.map(|b| { x.broadcaster_id == b }).unwrap_or(true) &&
+                    start.map(|s| { x.created_at >= s }).unwrap_or(true) &&
+                    end.map(|e| { x.created_at <= e }).unwrap_or(true)
+            }).cloned().collect::<Vec<TwitchClip>>()))
+        },
+        ("GET", "eventsub/subscriptions") => {
+            let subs: Vec<Value> = state.subscriptions.lock().unwrap().iter().map(|x| { x.to_json() }).collect();
+            HttpResponse::Ok().json(json!({
+                "total": subs.len(),
+                "data": subs,
+                "pagination": {},
+            }))
+        },
+        ("POST", "eventsub/subscriptions") => {
+            let body: Value = match serde_json::from_slice(body) {
+                Ok(x) => x,
+                Err(_) => return HttpResponse::BadRequest().finish(),
+            };
+
+            match state.create_subscription(&body).await {
+                Ok(sub) => HttpResponse::Accepted().json(json!({
+                    "data": [sub.to_json()],
+                    "total": state.subscriptions.lock().unwrap().len(),
+                })),
+                Err(resp) => resp,
+            }
+        },
+        ("DELETE", "eventsub/subscriptions") => {
+            let id = query_value(&query, "id").unwrap_or("");
+            let mut subs = state.subscriptions.lock().unwrap();
+            let before = subs.len();
+            subs.retain(|x| { x.id != id });
+            if subs.len() == before {
+                HttpResponse::NotFound().finish()
+            } else {
+                HttpResponse::NoContent().finish()
+            }
+        },
+        _ => HttpResponse::NotFound().finish(),
+    }
+}
+
+// Things that happen on Twitch's side (going live, making clips) rather than through the API.
+async fn handle_mock_action(state: &TwitchMockState, path: &str, body: &[u8]) -> HttpResponse {
+    let parts: Vec<&str> = path.split('/').collect();
+    let result = match parts.as_slice() {
+        ["deliveries"] => return HttpResponse::Ok().json(state.deliveries.lock().unwrap().clone()),
+        ["subscriptions"] => return HttpResponse::Ok().json(state.subscriptions.lock().unwrap().clone()),
+        ["clips"] => return match serde_json::from_slice::<TwitchClip>(body) {
+            Ok(clip) => {
+                state.clips.lock().unwrap().push(clip);
+                HttpResponse::NoContent().finish()
+            },
+            Err(_) => HttpResponse::BadRequest().finish(),
+        },
+        ["streams", user_id, "online"] => state.go_online(user_id, serde_json::from_slice(body).unwrap_or_default()).await,
+        ["streams", user_id, "offline"] => state.go_offline(user_id, serde_json::from_slice(body).unwrap_or_default()).await,
+        _ => return HttpResponse::NotFound().finish(),
+    };
+
+    match result {
+        Ok(x) => HttpResponse::Ok().json(x),
+        Err(err) => error_response(err),
+    }
+}
+
+async fn handle_request(req: HttpRequest, body: web::Bytes, state: web::Data<Arc<TwitchMockState>>) -> HttpResponse {
+    let path = req.path().trim_start_matches('/').to_string();
+    *state.requests.lock().unwrap().entry(format!("{} {}", req.method(), &path)).or_insert(0) += 1;
+
+    if let Some(action) = path.strip_prefix("__mock/") {
+        handle_mock_action(&state, action, &body).await
+    } else if let Some(oauth) = path.strip_prefix("oauth2/") {
+        handle_oauth(oauth)
+    } else if let Some(helix) = path.strip_prefix("helix/") {
+        handle_helix(&state, &req, helix, &body).await
+    } else {
+        HttpResponse::NotFound().finish()
+    }
+}
+
+pub struct TwitchMockServer {
+    addr: SocketAddr,
+    handle: ServerHandle,
+    state: Arc<TwitchMockState>,
+}
+
+impl TwitchMockServer {
+    // Use port 0 in the bind address to get a random port.
+    pub async fn start(fixtures: Option<&Path>, bind: &str) -> Result<Self, SquadOvError> {
+        let fixtures: TwitchMockFixtures = match fixtures {
+            Some(x) => serde_json::from_slice(&std::fs::read(x)?)?,
+            None => TwitchMockFixtures::default(),
+        };
+
+        let state = Arc::new(TwitchMockState::default());
+        for c in fixtures.channels {
+            if let Some(id) = c.get("broadcaster_id").and_then(|x| { x.as_str() }) {
+                state.channels.lock().unwrap().insert(id.to_string(), c.clone());
+            }
+        }
+        *state.videos.lock().unwrap() = fixtures.videos;
+        *state.clips.lock().unwrap() = fixtures.clips;
+
+        let server_state = state.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(web::Data::new(server_state.clone()))
+                .default_service(web::to(handle_request))
+        })
+            // More than one worker since creating a subscription waits on the callback which may end up calling back into us.
+            .workers(2)
+            .bind(bind)?;
+
+        let addr = server.addrs().first().cloned().ok_or_else(|| { SquadOvError::InternalError(String::from("Twitch mock server has no address")) })?;
+        let server = server.run();
+        let handle = server.handle();
+        tokio::task::spawn(server);
+
+        Ok(Self {
+            addr,
+            handle,
+            state,
+        })
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn helix_url(&self) -> String {
+        format!("{}/helix", self.base_url())
+    }
+
+    pub fn id_url(&self) -> String {
+        format!("{}/oauth2", self.base_url())
+    }
+
+    // Number of requests received for the method and path, e.g. "GET helix/videos".
+    pub fn request_count(&self, method_path: &str) -> usize {
+        self.state.requests.lock().unwrap().get(method_path).cloned().unwrap_or(0)
+    }
+
+    pub fn add_video(&self, video: TwitchVideo) {
+        self.state.videos.lock().unwrap().push(video);
+    }
+
+    pub fn add_clip(&self, clip: TwitchClip) {
+        self.state.clips.lock().unwrap().push(clip);
+    }
+
+    pub fn videos(&self) -> Vec<TwitchVideo> {
+        self.state.videos.lock().unwrap().clone()
+    }
+
+    pub fn subscriptions(&self) -> Vec<TwitchMockSubscription> {
+        self.state.subscriptions.lock().unwrap().clone()
+    }
+
+    pub fn deliveries(&self) -> Vec<TwitchMockDelivery> {
+        self.state.deliveries.lock().unwrap().clone()
+    }
+
+    // The stream ID is generated if not given.
+    pub async fn go_online(&self, broadcaster_id: &str, stream_id: Option<&str>, started_at: DateTime<Utc>) -> Result<Vec<TwitchMockDelivery>, SquadOvError> {
+        self.state.go_online(broadcaster_id, TwitchMockOnlineRequest{
+            id: stream_id.map(|x| { x.to_string() }),
+            started_at: Some(started_at),
+        }).await
+    }
+
+    // The archive video is only created if archive is set (i.e. the broadcaster has past broadcasts turned on).
+    pub async fn go_offline(&self, broadcaster_id: &str, ended_at: DateTime<Utc>, archive: bool) -> Result<Vec<TwitchMockDelivery>, SquadOvError> {
+        self.state.go_offline(broadcaster_id, TwitchMockOfflineRequest{
+            ended_at: Some(ended_at),
+            archive,
+        }).await
+    }
+
+    pub async fn stop(&self) {
+        self.handle.stop(true).await;
+    }
+}
@@ -33,18 +33,20 @@ impl TwitchOAuthToken {
 
 #[derive(Deserialize)]
 pub struct TwitchIdToken {
-    sub: String,
+    pub sub: String,
+    // These claims are only present if they were explicitly requested in the authorization URL.
+    pub preferred_username: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
 }
 
-const TOKEN_URL: &'static str = "https://id.twitch.tv/oauth2/token";
-
-pub async fn exchange_authorization_code_for_access_token(client_id: &str, client_secret: &str, redirect_url: &str, code: &str) -> Result<TwitchOAuthToken, SquadOvError> {
+pub async fn exchange_authorization_code_for_access_token(id_url: &str, client_id: &str, client_secret: &str, redirect_url: &str, code: &str) -> Result<TwitchOAuthToken, SquadOvError> {
     let client = reqwest::ClientBuilder::new().build()?;
     let result = client
         .post(
             &format!(
                 "{base}?client_id={client_id}&client_secret={client_secret}&code={code}&grant_type=authorization_code&redirect_uri={redirect}",
-                base=TOKEN_URL,
+                base=format!("{}/token", id_url),
                 client_id=client_id,
                 client_secret=client_secret,
                 code=code,
@@ -62,7 +64,7 @@ pub async fn exchange_authorization_code_for_access_token(client_id: &str, clien
     Ok(result.json::<TwitchOAuthToken>().await?)
 }
 
-pub async fn refresh_oauth_token(client_id: &str, client_secret: &str, refresh_token: &str) -> Result<TwitchOAuthToken, SquadOvError> {        
+pub async fn refresh_oauth_token(id_url: &str, client_id: &str, client_secret: &str, refresh_token: &str) -> Result<TwitchOAuthToken, SquadOvError> {        
     #[derive(Serialize)]
     pub struct Body<'a> {
         grant_type: &'a str,
@@ -73,7 +75,7 @@ pub async fn refresh_oauth_token(client_id: &str, client_secret: &str, refresh_t
 
     let client = reqwest::ClientBuilder::new().build()?;
     let result = client
-        .post(TOKEN_URL)
+        .post(&format!("{}/token", id_url))
         .form(&Body{
             grant_type: "refresh_token",
             refresh_token,
@@ -91,13 +93,13 @@ pub async fn refresh_oauth_token(client_id: &str, client_secret: &str, refresh_t
     Ok(result.json::<TwitchOAuthToken>().await?)
 }
 
-pub async fn get_oauth_client_credentials_token(client_id: &str, client_secret: &str) -> Result<TwitchOAuthToken, SquadOvError> {
+pub async fn get_oauth_client_credentials_token(id_url: &str, client_id: &str, client_secret: &str) -> Result<TwitchOAuthToken, SquadOvError> {
     let client = reqwest::ClientBuilder::new().build()?;
     let result = client
         .post(
             &format!(
                 "{base}?client_id={client_id}&client_secret={client_secret}&grant_type=client_credentials",
-                base=TOKEN_URL,
+                base=format!("{}/token", id_url),
                 client_id=client_id,
                 client_secret=client_secret,
             )
@@ -113,11 +115,11 @@ pub async fn get_oauth_client_credentials_token(client_id: &str, client_secret:
     Ok(result.json::<TwitchOAuthToken>().await?)
 }
 
-pub async fn validate_access_token(access_token: &str) -> Result<bool, SquadOvError> {
+pub async fn validate_access_token(id_url: &str, access_token: &str) -> Result<bool, SquadOvError> {
     let access_token = format!("Bearer {}", access_token);
     let client = reqwest::ClientBuilder::new().build()?;
     let result = client
-        .get("https://id.twitch.tv/oauth2/validate")
+        .get(&format!("{}/validate", id_url))
         .header(header::AUTHORIZATION, header::HeaderValue::from_str(&access_token)?)
         .send()
         .await?;
@@ -132,7 +134,11 @@ pub async fn validate_access_token(access_token: &str) -> Result<bool, SquadOvEr
     Ok(success)
 }
 
-pub fn extract_twitch_user_id_from_id_token(token: &str) -> Result<String, SquadOvError> {
+pub fn decode_twitch_id_token(token: &str) -> Result<TwitchIdToken, SquadOvError> {
     let jwt = jsonwebtoken::dangerous_insecure_decode::<TwitchIdToken>(token)?;
-    Ok(jwt.claims.sub)
+    Ok(jwt.claims)
+}
+
+pub fn extract_twitch_user_id_from_id_token(token: &str) -> Result<String, SquadOvError> {
+    Ok(decode_twitch_id_token(token)?.sub)
 }
\ No newline at end of file
@@ -10,6 +10,7 @@ use crate::{
         },
         TwitchConfig,
         eventsub,
+        streams,
     },
     accounts::twitch,
 };
@@ -23,6 +24,10 @@ pub enum TwitchTask {
         twitch_user_id: String,
         cursor: Option<String>,
     },
+    // Links the stream's archive and clips to the matches that were played during it.
+    LinkStreamVods {
+        stream_id: String,
+    },
 }
 
 pub struct TwitchApiRabbitmqInterface {
@@ -51,6 +56,60 @@ impl TwitchApiRabbitmqInterface {
         })?, RABBITMQ_DEFAULT_PRIORITY, TWITCH_MAX_AGE_SECONDS).await;
         Ok(())
     }
+
+    pub async fn request_link_stream_vods(&self, stream_id: &str) -> Result<(), SquadOvError> {
+        self.rmq.publish(&self.mqconfig.twitch_queue, serde_json::to_vec(&TwitchTask::LinkStreamVods{
+            stream_id: String::from(stream_id),
+        })?, RABBITMQ_DEFAULT_PRIORITY, TWITCH_MAX_AGE_SECONDS).await;
+        Ok(())
+    }
+
+    async fn link_stream_vods(&self, stream_id: &str) -> Result<(), SquadOvError> {
+        let stream = streams::get_twitch_stream(&*self.db, stream_id).await?;
+        let ended_tm = match stream.ended_tm {
+            Some(x) => x,
+            None => {
+                log::warn!("Trying to link Twitch VODs for a stream that hasn't ended: {}", stream_id);
+                return Ok(());
+            }
+        };
+
+        let token = twitch::get_twitch_oauth_token(&*self.db, &stream.twitch_user_id).await?;
+        let client = TwitchApiClient::new(self.tvconfig.clone(), token, TwitchTokenType::User, self.db.clone());
+
+        // There won't be an archive if the broadcaster doesn't have past broadcasts turned on but there can still be clips.
+        let archive = client.get_archive_videos(&stream.twitch_user_id).await?.into_iter().find(|x| {
+            x.stream_id.as_deref() == Some(stream.id.as_str())
+        });
+
+        let mut clips = vec![];
+        let mut cursor: Option<String> = None;
+        loop {
+            let (data, next) = client.get_clips(&stream.twitch_user_id, &stream.started_tm, &ended_tm, cursor).await?;
+            clips.extend(data);
+            if next.is_none() {
+                break;
+            }
+            cursor = next;
+        }
+
+        let matches = streams::find_matches_during_twitch_stream(&*self.db, &stream.id).await?;
+        let mut links = vec![];
+        if let Some(archive) = archive.as_ref() {
+            links.extend(streams::link_twitch_archive_to_matches(&stream.id, archive, &matches));
+        }
+
+        for c in &clips {
+            links.extend(streams::link_twitch_clip_to_matches(&stream.id, c, archive.as_ref(), &matches));
+        }
+
+        log::info!("Linking {} Twitch videos to {} matches for stream {}", links.len(), matches.len(), &stream.id);
+        let mut tx = self.db.begin().await?;
+        streams::store_twitch_match_links(&mut tx, &links).await?;
+        streams::mark_twitch_stream_linked(&mut tx, &stream.id).await?;
+        tx.commit().await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -74,6 +133,7 @@ impl RabbitMqListener for TwitchApiRabbitmqInterface {
                     self.request_sync_subscriber(&twitch_user_id, Some(cursor)).await?;
                 }
             },
+            TwitchTask::LinkStreamVods{stream_id} => self.link_stream_vods(&stream_id).await?,
         };
         Ok(())
     }
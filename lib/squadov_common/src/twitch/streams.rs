@@ -0,0 +1,507 @@
+use crate::{
+    SquadOvError,
+    squad::events::{RedisEvent, RedisEventHub},
+    twitch::{
+        api::{TwitchVideo, TwitchClip},
+        eventsub::TwitchStreamOnlineEvent,
+    },
+};
+use chrono::{DateTime, Utc, Duration};
+use serde::{Serialize, Deserialize};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+pub const TWITCH_LINK_ARCHIVE: &'static str = "archive";
+pub const TWITCH_LINK_CLIP: &'static str = "clip";
+
+#[derive(sqlx::FromRow, Serialize, Clone, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct TwitchStream {
+    pub id: String,
+    pub twitch_user_id: String,
+    pub started_tm: DateTime<Utc>,
+    pub ended_tm: Option<DateTime<Utc>>,
+}
+
+// A stream that's currently live along with the SquadOV user whose Twitch account it is.
+#[derive(sqlx::FromRow, Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct LiveTwitchStream {
+    pub user_id: i64,
+    pub username: String,
+    pub twitch_user_id: String,
+    pub twitch_name: String,
+    pub stream_id: String,
+    pub started_tm: DateTime<Utc>,
+}
+
+// Lets squadmates know when someone starts or stops streaming.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct TwitchStreamEvent {
+    #[serde(flatten)]
+    pub stream: LiveTwitchStream,
+    pub live: bool,
+}
+
+impl RedisEvent for TwitchStreamEvent {
+    const CHANNEL: &'static str = "twitch-stream";
+}
+
+pub type TwitchStreamEventHub = RedisEventHub<TwitchStreamEvent>;
+
+// A match that a user with a linked Twitch account played (and recorded) while streaming.
+#[derive(sqlx::FromRow, Clone, Debug)]
+pub struct TwitchStreamMatch {
+    pub match_uuid: Uuid,
+    pub user_id: i64,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow, Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all="camelCase")]
+pub struct TwitchMatchLink {
+    pub stream_id: String,
+    pub match_uuid: Uuid,
+    pub user_id: i64,
+    pub twitch_video_id: String,
+    pub video_type: String,
+    pub url: String,
+    pub start_offset_seconds: i64,
+    pub end_offset_seconds: i64,
+}
+
+impl TwitchMatchLink {
+    // Archives can be opened at a specific time; clips are short enough that they always start from the beginning.
+    pub fn jump_url(&self) -> String {
+        if self.video_type == TWITCH_LINK_ARCHIVE {
+            let t = self.start_offset_seconds;
+            format!("{}?t={}h{}m{}s", &self.url, t / 3600, (t % 3600) / 60, t % 60)
+        } else {
+            self.url.clone()
+        }
+    }
+}
+
+// Seconds into the video where the match starts and ends (clamped to the video) if they overlap at all.
+fn overlap_offsets(video_start: &DateTime<Utc>, video_duration_seconds: i64, m: &TwitchStreamMatch) -> Option<(i64, i64)> {
+    let video_end = *video_start + Duration::seconds(video_duration_seconds);
+    if m.end_time <= *video_start || m.start_time >= video_end {
+        return None;
+    }
+
+    let start = (m.start_time - *video_start).num_seconds().max(0);
+    let end = (m.end_time - *video_start).num_seconds().min(video_duration_seconds);
+    if end > start {
+        Some((start, end))
+    } else {
+        None
+    }
+}
+
+pub fn link_twitch_archive_to_matches(stream_id: &str, video: &TwitchVideo, matches: &[TwitchStreamMatch]) -> Vec<TwitchMatchLink> {
+    let duration = match video.duration_seconds() {
+        Some(x) => x,
+        None => {
+            log::warn!("Unknown Twitch video duration: {} [{}]", &video.duration, &video.id);
+            return vec![];
+        }
+    };
+
+    matches.iter().filter_map(|m| {
+        let (start, end) = overlap_offsets(&video.created_at, duration, m)?;
+        Some(TwitchMatchLink{
+            stream_id: stream_id.to_string(),
+            match_uuid: m.match_uuid.clone(),
+            user_id: m.user_id,
+            twitch_video_id: video.id.clone(),
+            video_type: String::from(TWITCH_LINK_ARCHIVE),
+            url: video.url.clone(),
+            start_offset_seconds: start,
+            end_offset_seconds: end,
+        })
+    }).collect()
+}
+
+pub fn link_twitch_clip_to_matches(stream_id: &str, clip: &TwitchClip, archive: Option<&TwitchVideo>, matches: &[TwitchStreamMatch]) -> Vec<TwitchMatchLink> {
+    let duration = clip.duration.ceil() as i64;
+
+    // The offset into the archive is exact. Otherwise the best we can do is assume the clip covers the time right before it was made.
+    let clip_start = match (archive, clip.vod_offset) {
+        (Some(archive), Some(offset)) if archive.id == clip.video_id => archive.created_at + Duration::seconds(offset),
+        _ => clip.created_at - Duration::seconds(duration),
+    };
+
+    matches.iter().filter_map(|m| {
+        let (start, end) = overlap_offsets(&clip_start, duration, m)?;
+        Some(TwitchMatchLink{
+            stream_id: stream_id.to_string(),
+            match_uuid: m.match_uuid.clone(),
+            user_id: m.user_id,
+            twitch_video_id: clip.id.clone(),
+            video_type: String::from(TWITCH_LINK_CLIP),
+            url: clip.url.clone(),
+            start_offset_seconds: start,
+            end_offset_seconds: end,
+        })
+    }).collect()
+}
+
+// Returns false if we already knew about the stream (Twitch can send the same notification more than once).
+pub async fn start_twitch_stream<'a, T>(ex: T, event: &TwitchStreamOnlineEvent) -> Result<bool, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query(
+            "
+            INSERT INTO squadov.twitch_streams (
+                id,
+                twitch_user_id,
+                started_tm
+            )
+            SELECT $1, ta.twitch_user_id, $3
+            FROM squadov.twitch_accounts AS ta
+            WHERE ta.twitch_user_id = $2
+            ON CONFLICT DO NOTHING
+            "
+        )
+            .bind(&event.id)
+            .bind(&event.broadcaster_user_id)
+            .bind(&event.started_at)
+            .execute(ex)
+            .await?
+            .rows_affected() > 0
+    )
+}
+
+// Ends every stream of the user that's still live in case we missed a previous offline notification.
+pub async fn end_twitch_streams<'a, T>(ex: T, twitch_user_id: &str, tm: &DateTime<Utc>) -> Result<Vec<TwitchStream>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, TwitchStream>(
+            "
+            UPDATE squadov.twitch_streams
+            SET ended_tm = $2
+            WHERE twitch_user_id = $1
+                AND ended_tm IS NULL
+            RETURNING id, twitch_user_id, started_tm, ended_tm
+            "
+        )
+            .bind(twitch_user_id)
+            .bind(tm)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub async fn get_twitch_stream<'a, T>(ex: T, stream_id: &str) -> Result<TwitchStream, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, TwitchStream>(
+            "
+            SELECT id, twitch_user_id, started_tm, ended_tm
+            FROM squadov.twitch_streams
+            WHERE id = $1
+            "
+        )
+            .bind(stream_id)
+            .fetch_optional(ex)
+            .await?
+            .ok_or(SquadOvError::NotFound)?
+    )
+}
+
+pub async fn mark_twitch_stream_linked<'a, T>(ex: T, stream_id: &str) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query(
+        "
+        UPDATE squadov.twitch_streams
+        SET linked_tm = NOW()
+        WHERE id = $1
+        "
+    )
+        .bind(stream_id)
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_live_twitch_streams_for_twitch_user<'a, T>(ex: T, twitch_user_id: &str) -> Result<Vec<LiveTwitchStream>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, LiveTwitchStream>(
+            "
+            SELECT
+                u.id AS user_id,
+                u.username,
+                ta.twitch_user_id,
+                ta.twitch_name,
+                ts.id AS stream_id,
+                ts.started_tm
+            FROM squadov.twitch_streams AS ts
+            INNER JOIN squadov.twitch_accounts AS ta
+                ON ta.twitch_user_id = ts.twitch_user_id
+            INNER JOIN squadov.linked_twitch_accounts AS lta
+                ON lta.twitch_user_id = ta.twitch_user_id
+            INNER JOIN squadov.users AS u
+                ON u.id = lta.user_id
+            WHERE ts.twitch_user_id = $1
+                AND ts.ended_tm IS NULL
+            "
+        )
+            .bind(twitch_user_id)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub async fn get_live_twitch_streams_for_users<'a, T>(ex: T, user_ids: &[i64]) -> Result<Vec<LiveTwitchStream>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, LiveTwitchStream>(
+            "
+            SELECT
+                u.id AS user_id,
+                u.username,
+                ta.twitch_user_id,
+                ta.twitch_name,
+                ts.id AS stream_id,
+                ts.started_tm
+            FROM squadov.users AS u
+            INNER JOIN squadov.linked_twitch_accounts AS lta
+                ON lta.user_id = u.id
+            INNER JOIN squadov.twitch_accounts AS ta
+                ON ta.twitch_user_id = lta.twitch_user_id
+            INNER JOIN squadov.twitch_streams AS ts
+                ON ts.twitch_user_id = ta.twitch_user_id
+            WHERE u.id = ANY($1)
+                AND ts.ended_tm IS NULL
+            ORDER BY ts.started_tm DESC
+            "
+        )
+            .bind(user_ids)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+// Recorded matches of every user linked to the streaming Twitch account that overlap with the stream.
+pub async fn find_matches_during_twitch_stream<'a, T>(ex: T, stream_id: &str) -> Result<Vec<TwitchStreamMatch>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, TwitchStreamMatch>(
+            "
+            SELECT
+                v.match_uuid,
+                u.id AS user_id,
+                v.start_time,
+                v.end_time
+            FROM squadov.twitch_streams AS ts
+            INNER JOIN squadov.linked_twitch_accounts AS lta
+                ON lta.twitch_user_id = ts.twitch_user_id
+            INNER JOIN squadov.users AS u
+                ON u.id = lta.user_id
+            INNER JOIN squadov.vods AS v
+                ON v.user_uuid = u.uuid
+            WHERE ts.id = $1
+                AND v.match_uuid IS NOT NULL
+                AND NOT v.is_clip
+                AND v.start_time IS NOT NULL
+                AND v.end_time IS NOT NULL
+                AND v.start_time < COALESCE(ts.ended_tm, NOW())
+                AND v.end_time > ts.started_tm
+            "
+        )
+            .bind(stream_id)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub async fn store_twitch_match_links<'a, T>(ex: T, links: &[TwitchMatchLink]) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    if links.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query(
+        "
+        INSERT INTO squadov.twitch_match_links (
+            stream_id,
+            match_uuid,
+            user_id,
+            twitch_video_id,
+            video_type,
+            url,
+            start_offset_seconds,
+            end_offset_seconds
+        )
+        SELECT *
+        FROM UNNEST($1::VARCHAR[], $2::UUID[], $3::BIGINT[], $4::VARCHAR[], $5::VARCHAR[], $6::VARCHAR[], $7::BIGINT[], $8::BIGINT[])
+        ON CONFLICT (twitch_video_id, match_uuid, user_id) DO UPDATE SET
+            start_offset_seconds = EXCLUDED.start_offset_seconds,
+            end_offset_seconds = EXCLUDED.end_offset_seconds
+        "
+    )
+        .bind(links.iter().map(|x| { x.stream_id.clone() }).collect::<Vec<String>>())
+        .bind(links.iter().map(|x| { x.match_uuid.clone() }).collect::<Vec<Uuid>>())
+        .bind(links.iter().map(|x| { x.user_id }).collect::<Vec<i64>>())
+        .bind(links.iter().map(|x| { x.twitch_video_id.clone() }).collect::<Vec<String>>())
+        .bind(links.iter().map(|x| { x.video_type.clone() }).collect::<Vec<String>>())
+        .bind(links.iter().map(|x| { x.url.clone() }).collect::<Vec<String>>())
+        .bind(links.iter().map(|x| { x.start_offset_seconds }).collect::<Vec<i64>>())
+        .bind(links.iter().map(|x| { x.end_offset_seconds }).collect::<Vec<i64>>())
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+// Only links for the given users (i.e. the ones whose VODs the viewer has access to) are returned.
+pub async fn get_twitch_match_links<'a, T>(ex: T, match_uuid: &Uuid, user_uuids: &[Uuid]) -> Result<Vec<TwitchMatchLink>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as::<_, TwitchMatchLink>(
+            "
+            SELECT tml.stream_id, tml.match_uuid, tml.user_id, tml.twitch_video_id, tml.video_type, tml.url, tml.start_offset_seconds, tml.end_offset_seconds
+            FROM squadov.twitch_match_links AS tml
+            INNER JOIN squadov.users AS u
+                ON u.id = tml.user_id
+            WHERE tml.match_uuid = $1
+                AND u.uuid = ANY($2)
+            ORDER BY tml.video_type, tml.start_offset_seconds
+            "
+        )
+            .bind(match_uuid)
+            .bind(user_uuids)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+// Twitch accounts that are missing one of the given EventSub subscriptions (e.g. accounts linked before we started listening for them).
+pub async fn get_twitch_accounts_missing_eventsub<'a, T>(ex: T, sub: &str) -> Result<Vec<String>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar::<_, String>(
+            "
+            SELECT ta.twitch_user_id
+            FROM squadov.twitch_accounts AS ta
+            WHERE NOT EXISTS (
+                SELECT 1
+                FROM squadov.twitch_event_subscriptions AS tes
+                WHERE tes.sub = $1
+                    AND tes.raw_data->'subscription'->'condition'->>'broadcaster_user_id' = ta.twitch_user_id
+            )
+            "
+        )
+            .bind(sub)
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn test_match(n: u128, user_id: i64, start: i64, end: i64) -> TwitchStreamMatch {
+        let base = Utc.ymd(2022, 8, 1).and_hms(12, 0, 0);
+        TwitchStreamMatch{
+            match_uuid: Uuid::from_u128(n),
+            user_id,
+            start_time: base + Duration::seconds(start),
+            end_time: base + Duration::seconds(end),
+        }
+    }
+
+    fn test_archive() -> TwitchVideo {
+        TwitchVideo{
+            id: String::from("v1"),
+            stream_id: Some(String::from("s1")),
+            user_id: String::from("1234"),
+            url: String::from("https://www.twitch.tv/videos/v1"),
+            created_at: Utc.ymd(2022, 8, 1).and_hms(12, 0, 0),
+            duration: String::from("1h0m0s"),
+            video_type: String::from("archive"),
+        }
+    }
+
+    #[test]
+    fn test_link_archive() {
+        let matches = vec![
+            // Started before the stream.
+            test_match(1, 1, -300, 600),
+            // Entirely inside the stream.
+            test_match(2, 1, 1200, 3000),
+            // Goes past the end of the stream.
+            test_match(3, 2, 3300, 4000),
+            // Not during the stream at all.
+            test_match(4, 1, 3600, 4000),
+            test_match(5, 1, -600, 0),
+        ];
+
+        let links = link_twitch_archive_to_matches("s1", &test_archive(), &matches);
+        assert_eq!(links.len(), 3);
+        assert_eq!((links[0].match_uuid, links[0].start_offset_seconds, links[0].end_offset_seconds), (Uuid::from_u128(1), 0, 600));
+        assert_eq!((links[1].match_uuid, links[1].start_offset_seconds, links[1].end_offset_seconds), (Uuid::from_u128(2), 1200, 3000));
+        assert_eq!((links[2].match_uuid, links[2].user_id, links[2].start_offset_seconds, links[2].end_offset_seconds), (Uuid::from_u128(3), 2, 3300, 3600));
+        assert!(links.iter().all(|x| { x.video_type == TWITCH_LINK_ARCHIVE && x.twitch_video_id == "v1" }));
+        assert_eq!(links[1].jump_url(), "https://www.twitch.tv/videos/v1?t=0h20m0s");
+    }
+
+    #[test]
+    fn test_link_clip() {
+        let archive = test_archive();
+        let matches = vec![
+            test_match(1, 1, 1000, 1500),
+            test_match(2, 1, 2000, 2500),
+        ];
+
+        // Uses the offset into the archive when there is one.
+        let clip = TwitchClip{
+            id: String::from("c1"),
+            url: String::from("https://clips.twitch.tv/c1"),
+            broadcaster_id: String::from("1234"),
+            video_id: String::from("v1"),
+            created_at: Utc.ymd(2022, 8, 1).and_hms(14, 0, 0),
+            duration: 29.6,
+            vod_offset: Some(1490),
+        };
+        let links = link_twitch_clip_to_matches("s1", &clip, Some(&archive), &matches);
+        assert_eq!(links.len(), 1);
+        assert_eq!((links[0].match_uuid, links[0].start_offset_seconds, links[0].end_offset_seconds), (Uuid::from_u128(1), 0, 10));
+        assert_eq!(links[0].jump_url(), "https://clips.twitch.tv/c1");
+
+        // Otherwise the clip is assumed to end when it was created.
+        let clip = TwitchClip{
+            video_id: String::new(),
+            vod_offset: None,
+            created_at: archive.created_at + Duration::seconds(2020),
+            ..clip
+        };
+        let links = link_twitch_clip_to_matches("s1", &clip, Some(&archive), &matches);
+        assert_eq!(links.len(), 1);
+        assert_eq!((links[0].match_uuid, links[0].start_offset_seconds, links[0].end_offset_seconds), (Uuid::from_u128(2), 10, 30));
+    }
+}
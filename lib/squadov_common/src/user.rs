@@ -145,4 +145,21 @@ where
             .fetch_one(ex)
             .await?
     )
-}
\ No newline at end of file
+}
+pub async fn get_squadov_user_id_from_username<'a, T>(ex: T, username: &str) -> Result<Option<i64>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_scalar!(
+            "
+            SELECT id
+            FROM squadov.users
+            WHERE username = $1
+            ",
+            username,
+        )
+            .fetch_optional(ex)
+            .await?
+    )
+}
@@ -18,13 +18,18 @@ use crate::{
         RabbitMqInterface,
         RabbitMqListener,
     },
-    storage::StorageManager,
+    storage::{
+        self,
+        StorageManager,
+        StorageResourceType,
+    },
     elastic::{
         vod::ESVodDocument,
         rabbitmq::ElasticSearchJobInterface,
     },
     matches,
     user,
+    features,
 };
 use std::sync::{Arc};
 use std::io::{self, BufReader};
@@ -148,7 +153,7 @@ impl VodSegmentId {
         vec![self.video_uuid.to_string(), self.quality.clone(), self.segment_name.clone()]
     }
 
-    fn get_fname(&self) -> String {
+    pub fn get_fname(&self) -> String {
         self.get_path_parts().join("/")
     }
 }
@@ -398,17 +403,21 @@ impl VodProcessingInterface {
 
         log::info!("[Preview] Upload Preview VOD - {}", vod_uuid);
         let manager = self.vod.get_bucket(&metadata.bucket).await.ok_or(SquadOvError::InternalError(format!("Invalid bucket: {}", &metadata.bucket)))?;
-        manager.upload_vod_from_file(&VodSegmentId{
+        let preview_id = VodSegmentId{
             video_uuid: vod_uuid.clone(),
             quality: String::from("source"),
             segment_name: String::from("preview.mp4"),
-        }, &preview_filename, manager::StorageType::Hot).await?;
+        };
+        manager.upload_vod_from_file(&preview_id, &preview_filename, manager::StorageType::Hot).await?;
 
         log::info!("[Preview] Process VOD TX (Begin) - {}", vod_uuid);
         let mut tx = self.db.begin().await?;
 
         log::info!("[Preview] Mark DB Preview (Query) - {}", vod_uuid);
         db::mark_vod_with_preview(&mut tx, vod_uuid).await?;
+
+        log::info!("[Preview] Track Storage Usage - {}", vod_uuid);
+        storage::track_vod_segment_storage_usage(&mut tx, &preview_id, &metadata.bucket, std::fs::metadata(&preview_filename)?.len() as i64).await?;
         log::info!("[Preview] Process VOD TX (Commit) - {}", vod_uuid);
         tx.commit().await?;
 
@@ -488,7 +497,7 @@ impl VodProcessingInterface {
             request_sync_elasticsearch: None,
             expiration_time: None,
             request_expiration_time: None,
-        }).await?;
+        }, features::get_cached_evaluated_feature_flags(&*self.db, request.user_id).await?.flags.vod_retention).await?;
 
         log::info!("[Clip] Add Video Metadata - {}", request.id);
         db::bulk_add_video_metadata(&mut tx, &clip_uuid, &[clip_metadata]).await?;
@@ -509,6 +518,9 @@ impl VodProcessingInterface {
         log::info!("[Clip] Mark VOD Copy - {}", request.id);
         db::bulk_sync_vod_copies(&mut tx, &[clip_uuid.clone()], VodCopyLocation::Cloud, &metadata.bucket).await?;
 
+        log::info!("[Clip] Track Storage Usage - {}", request.id);
+        storage::track_vod_segment_storage_usage(&mut tx, &clip_id, &metadata.bucket, std::fs::metadata(&clip_filename)?.len() as i64).await?;
+
         log::info!("[Clip] TX (Commit) - {}", request.id);
         tx.commit().await?;
 
@@ -547,6 +559,9 @@ impl VodProcessingInterface {
             db::add_vod_thumbnail(&mut tx, vod_uuid, &metadata.bucket, &thumbnail_id, thumbnail_dims.0 as i32, thumbnail_dims.1 as i32).await?;
         }
 
+        log::info!("[Thumbnail] Track Storage Usage - {}", vod_uuid);
+        storage::track_vod_segment_storage_usage(&mut tx, &thumbnail_id, &metadata.bucket, std::fs::metadata(&thumbnail_filename)?.len() as i64).await?;
+
         log::info!("[Thumbnail] Check if VOD is Public - {}", vod_uuid);
         if db::check_if_vod_public(&*self.db, vod_uuid).await? {
             log::info!("[Thumbnail] Setting Thumbnail as Public - {}", vod_uuid);
@@ -638,12 +653,15 @@ impl VodProcessingInterface {
         log::info!("[Fastify] Store VOD MD5 - {}", vod_uuid);
         db::store_vod_md5(&mut tx, vod_uuid, &md5_hash).await?;
 
+        log::info!("[Fastify] Track Storage Usage - {}", vod_uuid);
+        storage::track_vod_segment_storage_usage(&mut tx, &fastify_segment, &metadata.bucket, std::fs::metadata(&fastify_filename)?.len() as i64).await?;
+
         log::info!("[Fastify] Process VOD TX (Commit) - {}", vod_uuid);
         tx.commit().await?;
 
         log::info!("[Fastify] Delete Source VOD - {}", vod_uuid);
         match manager.delete_vod(&source_segment_id).await {
-            Ok(()) => (),
+            Ok(()) => storage::untrack_storage_resource(&*self.db, if vod.is_clip { StorageResourceType::Clip } else { StorageResourceType::Vod }, &source_segment_id.get_fname()).await?,
             Err(err) => log::warn!("Failed to delete source VOD: {}", err),
         };
 
@@ -704,6 +722,9 @@ impl VodProcessingInterface {
         log::info!("[Delete] DB Delete Storage - {}", vod_uuid);
         db::bulk_delete_vod_copies(&mut tx, &[vod_uuid.clone()], VodCopyLocation::Cloud, &metadata.bucket).await?;
 
+        log::info!("[Delete] DB Delete Storage Usage - {}", vod_uuid);
+        storage::untrack_vod_storage_usage(&mut tx, vod_uuid).await?;
+
         log::info!("[Delete] VOD Delete - {}", vod_uuid);
         manager.delete_vod(&VodSegmentId{
             video_uuid: vod_uuid.clone(),
@@ -296,33 +296,23 @@ where
     Ok(())
 }
 
-pub async fn associate_vod<'a, T>(ex: T, assoc: &VodAssociation) -> Result<(), SquadOvError>
+pub async fn associate_vod<'a, T>(ex: T, assoc: &VodAssociation, vod_retention: Option<i64>) -> Result<(), SquadOvError>
 where
     T: Executor<'a, Database = Postgres>
 {
     sqlx::query!(
         "
-        WITH vod_expiration(video_uuid, tm) AS (
-            SELECT v.video_uuid, CASE 
-                        WHEN (NOT v.is_clip AND uf.vod_retention IS NOT NULL) THEN (v.start_time + uf.vod_retention * INTERVAL '1 sec')
-                        ELSE NULL
-                   END 
-            FROM squadov.vods AS v
-            INNER JOIN squadov.users AS u
-                ON u.uuid = v.user_uuid
-            INNER JOIN squadov.user_feature_flags AS uf
-                ON uf.user_id = u.id
-            WHERE video_uuid = $5
-        )
         UPDATE squadov.vods AS v
         SET match_uuid = $1,
             user_uuid = $2,
             start_time = $3,
             end_time = $4,
-            expiration_time = ve.tm,
+            expiration_time = CASE 
+                WHEN (NOT v.is_clip AND $7::BIGINT IS NOT NULL) THEN (v.start_time + $7 * INTERVAL '1 sec')
+                ELSE NULL
+            END,
             raw_container_format = $6
-        FROM vod_expiration AS ve
-        WHERE ve.video_uuid = v.video_uuid
+        WHERE v.video_uuid = $5
         ",
         assoc.match_uuid,
         assoc.user_uuid,
@@ -330,6 +320,7 @@ where
         assoc.end_time,
         assoc.video_uuid,
         &assoc.raw_container_format,
+        vod_retention,
     )
         .execute(ex)
         .await?;
@@ -990,7 +981,7 @@ where
     )
 }
 
-pub async fn update_user_vods_expiration_from_feature_flags<'a, T>(ex: T, user_id: i64) -> Result<(), SquadOvError>
+pub async fn update_user_vods_expiration<'a, T>(ex: T, user_id: i64, vod_retention: Option<i64>) -> Result<(), SquadOvError>
 where
     T: Executor<'a, Database = Postgres>
 {
@@ -998,22 +989,18 @@ where
         "
         UPDATE squadov.vods AS v
         SET expiration_time = CASE
-            WHEN sub.vod_retention IS NOT NULL THEN GREATEST(v.end_time + sub.vod_retention * INTERVAL '1 second', NOW() + INTERVAL '1 day')
+            WHEN $2::BIGINT IS NOT NULL THEN GREATEST(v.end_time + $2 * INTERVAL '1 second', NOW() + INTERVAL '1 day')
             ELSE NULL
         END
-        FROM (
-            SELECT u.uuid, uf.vod_retention
-            FROM squadov.users AS u
-            INNER JOIN squadov.user_feature_flags AS uf
-                ON uf.user_id = u.id
-            WHERE u.id = $1
-        ) AS sub(uuid, vod_retention)
-        WHERE sub.uuid = v.user_uuid
+        FROM squadov.users AS u
+        WHERE u.id = $1
+            AND u.uuid = v.user_uuid
             AND NOT v.is_clip
             AND v.match_uuid IS NOT NULL
             AND v.end_time IS NOT NULL
         ",
         user_id,
+        vod_retention,
     )
         .execute(ex)
         .await?;
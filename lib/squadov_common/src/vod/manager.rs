@@ -69,4 +69,6 @@ pub trait VodManager {
     async fn make_segment_public(&self, segment: &VodSegmentId) -> Result<(), SquadOvError>;
     async fn check_vod_segment_is_public(&self, segment: &VodSegmentId) -> Result<bool, SquadOvError>;
     async fn delete_vod(&self, segment: &VodSegmentId) -> Result<(), SquadOvError>;
+    // Size of the stored segment as reported by the storage backend (not what the client claims to have uploaded).
+    async fn get_segment_size_bytes(&self, segment: &VodSegmentId) -> Result<i64, SquadOvError>;
 }
\ No newline at end of file
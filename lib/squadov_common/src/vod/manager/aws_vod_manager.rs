@@ -23,6 +23,7 @@ use rusoto_s3::{
     PutObjectAclRequest,
     PutObjectTaggingRequest,
     DeleteObjectRequest,
+    HeadObjectRequest,
     CreateMultipartUploadRequest,
     UploadPartRequest,
     CompleteMultipartUploadRequest, CompletedMultipartUpload, CompletedPart,
@@ -308,6 +309,17 @@ impl VodManager for S3VodManager {
         Ok(())
     }
 
+    async fn get_segment_size_bytes(&self, segment: &VodSegmentId) -> Result<i64, SquadOvError> {
+        let req = HeadObjectRequest{
+            bucket: self.bucket.clone(),
+            key: segment.get_fname(),
+            ..HeadObjectRequest::default()
+        };
+
+        let resp = self.client().s3.head_object(req).await?;
+        resp.content_length.ok_or(SquadOvError::NotFound)
+    }
+
     async fn get_public_segment_redirect_uri(&self, segment: &VodSegmentId) -> Result<String, SquadOvError> {
         Ok(
             format!(
@@ -88,6 +88,15 @@ impl VodManager for FilesystemVodManager {
         Ok(fs::remove_file(fname)?)
     }
 
+    async fn get_segment_size_bytes(&self, segment: &VodSegmentId) -> Result<i64, SquadOvError> {
+        let fname = self.segment_id_to_path(segment);
+        if !fname.exists() {
+            return Err(SquadOvError::NotFound);
+        }
+
+        Ok(fs::metadata(fname)?.len() as i64)
+    }
+
     async fn get_public_segment_redirect_uri(&self, segment: &VodSegmentId) -> Result<String, SquadOvError> {
         Ok(self.get_segment_redirect_uri(segment, true).await?.0)
     }
@@ -209,6 +209,12 @@ impl VodManager for GCSVodManager {
         Ok(client.delete_object(&self.bucket, &fname).await?)
     }
 
+    async fn get_segment_size_bytes(&self, segment: &VodSegmentId) -> Result<i64, SquadOvError> {
+        let fname = self.get_fname_from_segment_id(segment);
+        let client = self.get_gcp_client().gcs();
+        Ok(client.get_object_size(&self.bucket, &fname).await?)
+    }
+
     async fn get_public_segment_redirect_uri(&self, segment: &VodSegmentId) -> Result<String, SquadOvError> {
         let fname = self.get_fname_from_segment_id(segment);
         Ok(
@@ -97,7 +97,7 @@ impl<'a> CombatLogReportIO for WowReportsGenerator<'a> {
         }
 
         {
-            let mut gen = stats::WowStatReportGenerator::new(self.parent_cl.start_time.clone());
+            let mut gen = stats::WowStatReportGenerator::new(self.parent_cl.partition_id.clone(), self.parent_cl.start_time.clone());
             gen.initialize_work_dir(dir)?;
             self.stat_gen = Some(gen);
         }
@@ -17,6 +17,7 @@ use crate::{
             },
         },
         RawStaticCombatLogReport,
+        CombatLogReportType,
     },
     wow::{
         reports::WowReportTypes,
@@ -37,6 +38,10 @@ use avro_rs::{
     Schema,
 };
 use async_std::sync::{RwLock};
+use async_trait::async_trait;
+use sqlx::{Executor, Transaction, Postgres};
+use uuid::Uuid;
+use rusoto_s3::S3Client;
 
 pub struct WowStatTimelineGenerator<'a> {
     writer: CombatLogAvroFileIO<'a>,
@@ -65,7 +70,7 @@ const TIMELINE_SCHEMA_RAW: &'static str = r#"
     }
 "#;
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all="camelCase")]
 pub struct WowUnitStatSummary {
     pub guid: String,
@@ -87,6 +92,116 @@ const SUMMARY_SCHEMA_RAW: &'static str = r#"
     }
 "#;
 
+// Stores the per-player summary in the database so that it can be aggregated across matches.
+pub struct WowMatchStatSummaryReport {
+    pub partition_id: String,
+    pub summaries: Vec<WowUnitStatSummary>,
+}
+
+#[async_trait]
+impl CombatLogReport for WowMatchStatSummaryReport {
+    fn report_type(&self) -> CombatLogReportType {
+        CombatLogReportType::Dynamic
+    }
+
+    async fn store_static_report(&self, _bucket: String, _partition: String, _s3: Arc<S3Client>) -> Result<(), SquadOvError> {
+        Err(SquadOvError::BadRequest)
+    }
+
+    async fn store_dynamic_report(&self, tx: &mut Transaction<'_, Postgres>) -> Result<(), SquadOvError> {
+        store_wow_stat_summaries_for_partition(tx, &self.partition_id, &self.summaries).await
+    }
+}
+
+// Every match view that uses the combat log partition gets the same summary.
+pub async fn store_wow_stat_summaries_for_partition<'a, T>(ex: T, partition_id: &str, summaries: &[WowUnitStatSummary]) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    if summaries.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        "
+        INSERT INTO squadov.wow_match_view_stat_summaries (
+            view_id,
+            unit_guid,
+            damage_dealt,
+            damage_received,
+            heals
+        )
+        SELECT wmv.id, x.guid, x.damage_dealt, x.damage_received, x.heals
+        FROM UNNEST($2::VARCHAR[], $3::BIGINT[], $4::BIGINT[], $5::BIGINT[]) AS x(guid, damage_dealt, damage_received, heals)
+        CROSS JOIN squadov.wow_match_view AS wmv
+        WHERE wmv.combat_log_partition_id = $1
+        ON CONFLICT (view_id, unit_guid) DO UPDATE SET
+            damage_dealt = EXCLUDED.damage_dealt,
+            damage_received = EXCLUDED.damage_received,
+            heals = EXCLUDED.heals
+        ",
+        partition_id,
+        &summaries.iter().map(|x| { x.guid.clone() }).collect::<Vec<String>>(),
+        &summaries.iter().map(|x| { x.damage_dealt }).collect::<Vec<i64>>(),
+        &summaries.iter().map(|x| { x.damage_received }).collect::<Vec<i64>>(),
+        &summaries.iter().map(|x| { x.heals }).collect::<Vec<i64>>(),
+    )
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+pub struct WowStatSummaryBackfillView {
+    pub view_id: Uuid,
+    pub combat_log_partition_id: Option<String>,
+}
+
+// Marks the returned match views as queued so they don't get picked up again while their backfill is still pending. Views
+// that have been queued for a day without finishing (e.g. the report couldn't be read) are picked up again.
+pub async fn get_wow_match_views_for_stat_summary_backfill<'a, T>(ex: T, limit: i64) -> Result<Vec<WowStatSummaryBackfillView>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as!(
+            WowStatSummaryBackfillView,
+            "
+            UPDATE squadov.wow_match_view_stat_summary_backfill AS wssb
+            SET queued_tm = NOW()
+            FROM (
+                SELECT view_id
+                FROM squadov.wow_match_view_stat_summary_backfill
+                WHERE queued_tm IS NULL OR queued_tm < (NOW() - INTERVAL '1 day')
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            ) AS sub, squadov.wow_match_view AS wmv
+            WHERE sub.view_id = wssb.view_id
+                AND wmv.id = wssb.view_id
+            RETURNING wssb.view_id, wmv.combat_log_partition_id AS "combat_log_partition_id?"
+            ",
+            limit,
+        )
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+pub async fn finish_wow_match_view_stat_summary_backfill<'a, T>(ex: T, view_id: &Uuid) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query!(
+        "
+        DELETE FROM squadov.wow_match_view_stat_summary_backfill
+        WHERE view_id = $1
+        ",
+        view_id,
+    )
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
 lazy_static! {
     pub static ref TIMELINE_SCHEMA: Schema = Schema::parse_str(TIMELINE_SCHEMA_RAW).unwrap();
     pub static ref SUMMARY_SCHEMA: Schema = Schema::parse_str(SUMMARY_SCHEMA_RAW).unwrap();
@@ -141,6 +256,7 @@ impl<'a> WowStatTimelineGenerator<'a> {
 }
 
 pub struct WowStatReportGenerator<'a> {
+    partition_id: String,
     start_tm: DateTime<Utc>,
     work_dir: Option<String>,
     dps_timeline: Option<WowStatTimelineGenerator<'a>>,
@@ -189,8 +305,9 @@ impl<'a> CombatLogReportHandler for WowStatReportGenerator<'a> {
 }
 
 impl<'a> WowStatReportGenerator<'a> {
-    pub fn new(start_tm: DateTime<Utc>) -> Self {
+    pub fn new(partition_id: String, start_tm: DateTime<Utc>) -> Self {
         Self {
+            partition_id,
             start_tm,
             work_dir: None,
             dps_timeline: None,
@@ -207,6 +324,11 @@ impl<'a> WowStatReportGenerator<'a> {
         }
     }
 
+    // The per-player totals so far. These are what end up in summary.avro and the database.
+    pub fn unit_summary(&self, guid: &str) -> Option<&WowUnitStatSummary> {
+        self.summary.get(guid)
+    }
+
     fn get_player_user_from_guid(&self, guid: &str) -> Option<String> {
         if let Some(owner) = self.unit_ownership.get(guid) {
             Some(owner.clone())
@@ -346,10 +468,17 @@ impl<'a> CombatLogReportIO for WowStatReportGenerator<'a> {
 
         if let Some(work_dir) = self.work_dir.as_ref() {
             let mut w = CombatLogAvroFileIO::new(work_dir, &SUMMARY_SCHEMA)?;
+            let mut db_summaries: Vec<WowUnitStatSummary> = vec![];
             for (_, summary) in self.summary.drain() {
+                db_summaries.push(summary.clone());
                 w.handle(summary)?;
             }
 
+            ret.push(Arc::new(WowMatchStatSummaryReport{
+                partition_id: self.partition_id.clone(),
+                summaries: db_summaries,
+            }));
+
             ret.push(
                 Arc::new(RawStaticCombatLogReport{
                     key_name: String::from("summary.avro"),
@@ -0,0 +1,428 @@
+#!/usr/bin/env python3
+# Generates two_rounds.dem: a tiny CS:GO demo with a data table, two players, two rounds, kills, damage,
+# a grenade, and player movement so that the parser's round, kill, and position sampling paths all get exercised.
+#
+# Usage: python3 generate_two_rounds_demo.py [output path]
+import struct
+import sys
+
+TICK_RATE = 64
+PLAYBACK_TICKS = 448
+
+# Demo commands.
+DEM_SIGNON = 1
+DEM_PACKET = 2
+DEM_DATATABLES = 6
+DEM_STOP = 7
+
+# Net messages.
+SVC_SEND_TABLE = 9
+SVC_CREATE_STRING_TABLE = 12
+SVC_GAME_EVENT = 25
+SVC_PACKET_ENTITIES = 26
+SVC_GAME_EVENT_LIST = 30
+
+# Send prop types and flags.
+DPT_INT = 0
+DPT_FLOAT = 1
+DPT_VECTOR = 2
+DPT_STRING = 4
+SPROP_UNSIGNED = 1 << 0
+SPROP_COORD = 1 << 1
+SPROP_NOSCALE = 1 << 2
+
+# Game event key types.
+KEY_STRING = 1
+KEY_FLOAT = 2
+KEY_LONG = 3
+KEY_SHORT = 4
+KEY_BYTE = 5
+KEY_BOOL = 6
+
+def varint(v):
+    out = bytearray()
+    while True:
+        b = v & 0x7F
+        v >>= 7
+        if v:
+            out.append(b | 0x80)
+        else:
+            out.append(b)
+            return bytes(out)
+
+class Proto:
+    def __init__(self):
+        self.data = bytearray()
+
+    def int(self, field, v):
+        self.data += varint(field << 3) + varint(v)
+        return self
+
+    def float(self, field, v):
+        self.data += varint((field << 3) | 5) + struct.pack('<f', v)
+        return self
+
+    def bytes(self, field, v):
+        if isinstance(v, str):
+            v = v.encode('utf-8')
+        elif isinstance(v, Proto):
+            v = bytes(v.data)
+        self.data += varint((field << 3) | 2) + varint(len(v)) + v
+        return self
+
+class BitWriter:
+    def __init__(self):
+        self.bits = []
+
+    def bit(self, v):
+        self.bits.append(1 if v else 0)
+
+    def multibit(self, v, n):
+        for i in range(n):
+            self.bit((v >> i) & 1)
+
+    def raw(self, data):
+        for b in data:
+            self.multibit(b, 8)
+
+    def cstr(self, s):
+        self.raw(s.encode('utf-8') + b'\x00')
+
+    def var_ubits(self, v):
+        if v < 16:
+            self.multibit(v, 6)
+        elif v < 256:
+            self.multibit((v & 15) | 16, 6)
+            self.multibit(v >> 4, 4)
+        elif v < 4096:
+            self.multibit((v & 15) | 32, 6)
+            self.multibit(v >> 4, 8)
+        else:
+            self.multibit((v & 15) | 48, 6)
+            self.multibit(v >> 4, 28)
+
+    def coord(self, v):
+        # Only integral coordinates so that the values round trip exactly.
+        v = int(v)
+        if v == 0:
+            self.bit(0)
+            self.bit(0)
+            return
+        self.bit(1)
+        self.bit(0)
+        self.bit(v < 0)
+        self.multibit(abs(v) - 1, 14)
+
+    def to_bytes(self):
+        out = bytearray()
+        for i in range(0, len(self.bits), 8):
+            chunk = self.bits[i:i+8]
+            out.append(sum(b << j for j, b in enumerate(chunk)))
+        return bytes(out)
+
+# (name, type, flags, num_bits)
+TABLES = {
+    'DT_CSPlayer': [
+        ('m_vecOrigin', DPT_VECTOR, SPROP_COORD, 0),
+        ('m_angEyeAngles[0]', DPT_FLOAT, SPROP_NOSCALE, 32),
+        ('m_angEyeAngles[1]', DPT_FLOAT, SPROP_NOSCALE, 32),
+        ('m_iTeamNum', DPT_INT, SPROP_UNSIGNED, 6),
+        ('m_lifeState', DPT_INT, SPROP_UNSIGNED, 3),
+        ('m_iAccount', DPT_INT, SPROP_UNSIGNED, 16),
+    ],
+    'DT_CSTeam': [
+        ('m_iTeamNum', DPT_INT, SPROP_UNSIGNED, 6),
+        ('m_szTeamname', DPT_STRING, 0, 0),
+    ],
+    'DT_WeaponAK47': [
+        ('m_vecOrigin', DPT_VECTOR, SPROP_COORD, 0),
+        ('m_hOwnerEntity', DPT_INT, SPROP_UNSIGNED, 21),
+    ],
+}
+
+# (class id, class name, table name)
+CLASSES = [
+    (0, 'CCSPlayer', 'DT_CSPlayer'),
+    (1, 'CCSTeam', 'DT_CSTeam'),
+    (2, 'CAK47', 'DT_WeaponAK47'),
+]
+SERVER_CLASS_BITS = 2
+
+# (event id, name, [(key name, key type)])
+EVENTS = [
+    (1, 'round_start', [('timelimit', KEY_LONG), ('fraglimit', KEY_LONG), ('objective', KEY_STRING)]),
+    (2, 'round_announce_match_start', []),
+    (3, 'round_freeze_end', []),
+    (4, 'player_death', [
+        ('userid', KEY_SHORT), ('attacker', KEY_SHORT), ('assister', KEY_SHORT), ('assistedflash', KEY_BOOL),
+        ('weapon', KEY_STRING), ('headshot', KEY_BOOL), ('penetrated', KEY_SHORT), ('noscope', KEY_BOOL),
+        ('thrusmoke', KEY_BOOL), ('attackerblind', KEY_BOOL),
+    ]),
+    (5, 'player_hurt', [
+        ('userid', KEY_SHORT), ('attacker', KEY_SHORT), ('health', KEY_BYTE), ('armor', KEY_BYTE), ('weapon', KEY_STRING),
+        ('dmg_health', KEY_SHORT), ('dmg_armor', KEY_BYTE), ('hitgroup', KEY_BYTE),
+    ]),
+    (6, 'round_end', [('winner', KEY_BYTE), ('reason', KEY_BYTE), ('message', KEY_STRING)]),
+    (7, 'round_mvp', [('userid', KEY_SHORT), ('reason', KEY_SHORT)]),
+    (8, 'hegrenade_detonate', [('userid', KEY_SHORT), ('entityid', KEY_SHORT), ('x', KEY_FLOAT), ('y', KEY_FLOAT), ('z', KEY_FLOAT)]),
+]
+
+# (user id, steam id, name)
+PLAYERS = [
+    (2, 76561198000000001, 'squadov_t'),
+    (3, 76561198000000002, 'squadov_ct'),
+]
+
+TEAM_T = 2
+TEAM_CT = 3
+NO_OWNER = (1 << 21) - 1
+
+def send_table(name, props, is_end=False):
+    msg = Proto()
+    if is_end:
+        return msg.int(1, 1)
+    msg.int(1, 0).bytes(2, name).int(3, 0)
+    for (var_name, typ, flags, num_bits) in props:
+        prop = Proto().int(1, typ).bytes(2, var_name).int(3, flags).int(4, 128).int(6, 0).float(7, 0.0).float(8, 0.0).int(9, num_bits)
+        msg.bytes(4, prop)
+    return msg
+
+def data_tables_payload():
+    out = bytearray()
+    tables = [send_table(name, props) for name, props in TABLES.items()] + [send_table('', [], is_end=True)]
+    for t in tables:
+        out += varint(SVC_SEND_TABLE) + varint(len(t.data)) + t.data
+    out += struct.pack('<h', len(CLASSES))
+    for (class_id, name, dt_name) in CLASSES:
+        out += struct.pack('<h', class_id) + name.encode('utf-8') + b'\x00' + dt_name.encode('utf-8') + b'\x00'
+    return bytes(out)
+
+def player_info(user_id, xuid, name):
+    def padded(s, n):
+        return s.encode('utf-8').ljust(n, b'\x00')
+    return (
+        struct.pack('<Q', 0) +
+        struct.pack('>Q', xuid) +
+        padded(name, 128) +
+        struct.pack('>i', user_id) +
+        padded('STEAM_1:1:%d' % (xuid - 76561197960265728), 33) +
+        b'\x00' * 3 +
+        struct.pack('>I', 0) +
+        padded('', 128) +
+        b'\x00' + b'\x00' + b'\x00' * 2 +
+        struct.pack('<IIII', 0, 0, 0, 0) +
+        b'\x00' + b'\x00' * 3
+    )
+
+def userinfo_string_table():
+    w = BitWriter()
+    # Not dictionary encoded.
+    w.bit(0)
+    for idx, (user_id, xuid, name) in enumerate(PLAYERS):
+        # Sequential entry index, with an entry string but no substring.
+        w.bit(1)
+        w.bit(1)
+        w.bit(0)
+        w.cstr(str(idx))
+        data = player_info(user_id, xuid, name)
+        w.bit(1)
+        w.multibit(len(data), 14)
+        w.raw(data)
+    return Proto().bytes(1, 'userinfo').int(2, 256).int(3, len(PLAYERS)).int(4, 0).int(5, 0).int(6, 0).int(7, 0).bytes(8, w.to_bytes())
+
+def game_event_list():
+    msg = Proto()
+    for (event_id, name, keys) in EVENTS:
+        desc = Proto().int(1, event_id).bytes(2, name)
+        for (key_name, key_type) in keys:
+            desc.bytes(3, Proto().int(1, key_type).bytes(2, key_name))
+        msg.bytes(1, desc)
+    return msg
+
+def game_event(name, **values):
+    (event_id, _, keys) = next(x for x in EVENTS if x[1] == name)
+    msg = Proto().int(2, event_id)
+    for (key_name, key_type) in keys:
+        v = values.get(key_name)
+        key = Proto().int(1, key_type)
+        if key_type == KEY_STRING:
+            key.bytes(2, v or '')
+        elif key_type == KEY_FLOAT:
+            key.float(3, v or 0.0)
+        elif key_type == KEY_LONG:
+            key.int(4, v or 0)
+        elif key_type == KEY_SHORT:
+            key.int(5, v or 0)
+        elif key_type == KEY_BYTE:
+            key.int(6, v or 0)
+        elif key_type == KEY_BOOL:
+            key.int(7, 1 if v else 0)
+        msg.bytes(3, key)
+    return msg
+
+def write_prop(w, prop, v):
+    (_, typ, flags, num_bits) = prop
+    if typ == DPT_VECTOR:
+        for c in v:
+            w.coord(c)
+    elif typ == DPT_FLOAT:
+        w.raw(struct.pack('<f', v))
+    elif typ == DPT_INT:
+        w.multibit(v, num_bits)
+    elif typ == DPT_STRING:
+        data = v.encode('utf-8')
+        w.multibit(len(data), 9)
+        w.raw(data)
+
+def write_entity_props(w, table, values):
+    props = TABLES[table]
+    indices = sorted(i for i, p in enumerate(props) if p[0] in values)
+    # Use the "new way" of encoding field indices.
+    w.bit(1)
+    last = -1
+    for idx in indices:
+        if idx == last + 1:
+            w.bit(1)
+        else:
+            w.bit(0)
+            w.bit(1)
+            w.multibit(idx - last - 1, 3)
+        last = idx
+    # 0xFFF terminates the field index list.
+    w.bit(0)
+    w.bit(0)
+    w.multibit(127, 7)
+    w.multibit(127, 7)
+    for idx in indices:
+        write_prop(w, props[idx], values[props[idx][0]])
+
+# updates: [(entity id, class id or None for a delta, values)]
+def packet_entities(updates, is_delta):
+    w = BitWriter()
+    last = -1
+    for (entity_id, class_id, values) in sorted(updates, key=lambda x: x[0]):
+        w.var_ubits(entity_id - last - 1)
+        last = entity_id
+        # Not leaving the PVS.
+        w.bit(0)
+        if class_id is not None:
+            w.bit(1)
+            w.multibit(class_id, SERVER_CLASS_BITS)
+            w.multibit(0, 10)
+        else:
+            w.bit(0)
+        table = next(x[2] for x in CLASSES if x[0] == class_id) if class_id is not None else ENTITY_TABLES[entity_id]
+        write_entity_props(w, table, values)
+    return Proto().int(1, 1024).int(2, len(updates)).int(3, 1 if is_delta else 0).bytes(7, w.to_bytes())
+
+# Entity 1 and 2 are the players (userinfo index + 1).
+ENTITY_TABLES = {
+    1: 'DT_CSPlayer',
+    2: 'DT_CSPlayer',
+    3: 'DT_CSTeam',
+    4: 'DT_CSTeam',
+    5: 'DT_WeaponAK47',
+}
+
+def header():
+    def padded(s, n):
+        return s.encode('utf-8').ljust(n, b'\x00')
+    return (
+        padded('HL2DEMO', 8) +
+        struct.pack('<ii', 4, 13808) +
+        padded('SquadOV Test Server', 260) +
+        padded('GOTV Demo', 260) +
+        padded('de_dust2', 260) +
+        padded('csgo', 260) +
+        struct.pack('<fiii', PLAYBACK_TICKS / TICK_RATE, PLAYBACK_TICKS, PLAYBACK_TICKS, 0)
+    )
+
+def command(cmd, tick):
+    return struct.pack('<BiB', cmd, tick, 0)
+
+def packet(cmd, tick, messages):
+    payload = bytearray()
+    for (msg_type, msg) in messages:
+        payload += varint(msg_type) + varint(len(msg.data)) + msg.data
+    return command(cmd, tick) + b'\x00' * 152 + b'\x00' * 8 + struct.pack('<i', len(payload)) + bytes(payload)
+
+def entities(tick, updates, is_delta=True):
+    return packet(DEM_PACKET, tick, [(SVC_PACKET_ENTITIES, packet_entities(updates, is_delta))])
+
+def events(tick, *evts):
+    return packet(DEM_PACKET, tick, [(SVC_GAME_EVENT, e) for e in evts])
+
+def build():
+    out = bytearray(header())
+
+    dt = data_tables_payload()
+    out += command(DEM_DATATABLES, 0) + struct.pack('<i', len(dt)) + dt
+    out += packet(DEM_SIGNON, 0, [
+        (SVC_GAME_EVENT_LIST, game_event_list()),
+        (SVC_CREATE_STRING_TABLE, userinfo_string_table()),
+    ])
+
+    out += entities(1, [
+        (1, 0, {'m_vecOrigin': (-400, 800, 64), 'm_angEyeAngles[0]': 0.0, 'm_angEyeAngles[1]': 90.0, 'm_iTeamNum': TEAM_T, 'm_lifeState': 0, 'm_iAccount': 800}),
+        (2, 0, {'m_vecOrigin': (1200, -300, 0), 'm_angEyeAngles[0]': 0.0, 'm_angEyeAngles[1]': 270.0, 'm_iTeamNum': TEAM_CT, 'm_lifeState': 0, 'm_iAccount': 800}),
+        (3, 1, {'m_iTeamNum': TEAM_T, 'm_szTeamname': 'TERRORIST'}),
+        (4, 1, {'m_iTeamNum': TEAM_CT, 'm_szTeamname': 'CT'}),
+        (5, 2, {'m_vecOrigin': (50, 60, 0), 'm_hOwnerEntity': NO_OWNER}),
+    ], is_delta=False)
+
+    # Round 1: the T gets a headshot with a glock.
+    out += events(64,
+        game_event('round_start', timelimit=115, objective='BOMB TARGET'),
+        game_event('round_announce_match_start'),
+    )
+    out += entities(72, [(1, None, {'m_vecOrigin': (-380, 820, 64)})])
+    out += events(128, game_event('round_freeze_end'))
+    out += entities(136, [
+        (1, None, {'m_vecOrigin': (-300, 900, 64)}),
+        (2, None, {'m_vecOrigin': (1100, -200, 0)}),
+    ])
+    out += entities(140, [(1, None, {'m_vecOrigin': (-290, 905, 64)})])
+    out += entities(144, [(2, None, {'m_vecOrigin': (1050, -150, 0)})])
+    out += events(150,
+        game_event('player_hurt', userid=3, attacker=2, health=0, armor=0, weapon='glock', dmg_health=100, dmg_armor=0, hitgroup=1),
+        game_event('player_death', userid=3, attacker=2, weapon='glock', headshot=True),
+    )
+    out += entities(152, [(2, None, {'m_lifeState': 1})])
+    out += events(200,
+        game_event('round_end', winner=TEAM_T, reason=9, message='#SFUI_Notice_Terrorists_Win'),
+        game_event('round_mvp', userid=2, reason=1),
+    )
+    out += entities(208, [
+        (1, None, {'m_vecOrigin': (-400, 800, 64), 'm_iAccount': 3250}),
+        (2, None, {'m_vecOrigin': (1200, -300, 0), 'm_lifeState': 0, 'm_iAccount': 1900}),
+    ])
+
+    # Round 2: the CT nades the T and then finishes them off through a wall.
+    out += events(256, game_event('round_start', timelimit=115, objective='BOMB TARGET'))
+    out += entities(264, [(1, None, {'m_vecOrigin': (-350, 850, 64)})])
+    out += events(320, game_event('round_freeze_end'))
+    out += events(330,
+        game_event('hegrenade_detonate', userid=3, entityid=6, x=-320.0, y=880.0, z=64.0),
+        game_event('player_hurt', userid=2, attacker=3, health=60, armor=0, weapon='hegrenade', dmg_health=40, dmg_armor=0, hitgroup=0),
+    )
+    out += entities(336, [
+        (1, None, {'m_vecOrigin': (-200, 1000, 64)}),
+        (2, None, {'m_vecOrigin': (1000, 0, 0)}),
+    ])
+    out += events(340,
+        game_event('player_hurt', userid=2, attacker=3, health=0, armor=0, weapon='m4a1_silencer', dmg_health=60, dmg_armor=0, hitgroup=1),
+        game_event('player_death', userid=2, attacker=3, weapon='m4a1_silencer', headshot=True, penetrated=1),
+    )
+    out += entities(344, [(1, None, {'m_lifeState': 1})])
+    out += events(400,
+        game_event('round_end', winner=TEAM_CT, reason=8, message='#SFUI_Notice_CTs_Win'),
+        game_event('round_mvp', userid=3, reason=1),
+    )
+
+    out += command(DEM_STOP, PLAYBACK_TICKS)
+    return bytes(out)
+
+if __name__ == '__main__':
+    path = sys.argv[1] if len(sys.argv) > 1 else 'two_rounds.dem'
+    with open(path, 'wb') as f:
+        f.write(build())
@@ -0,0 +1,25 @@
+// Helpers shared between the integration tests. Not every test binary uses all of them.
+#![allow(dead_code)]
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use std::path::PathBuf;
+
+pub fn test_data(parts: &[&str]) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("test_data");
+    for p in parts {
+        path.push(p);
+    }
+    path
+}
+
+// Fixtures that get loaded into one of the mock API servers (e.g. test_data/stripe/mock/fixtures.json).
+pub fn mock_fixtures(service: &str) -> PathBuf {
+    test_data(&[service, "mock", "fixtures.json"])
+}
+
+// Tests that need a database are marked #[ignore] and get run with `cargo test -- --ignored`. At that point
+// SQUADOV_TEST_DATABASE_URL has to point at a database with all the migrations applied so fail loudly if it doesn't.
+pub async fn test_pool(max_connections: u32) -> PgPool {
+    let url = std::env::var("SQUADOV_TEST_DATABASE_URL").expect("SQUADOV_TEST_DATABASE_URL must be set to run the database tests");
+    PgPoolOptions::new().max_connections(max_connections).connect(&url).await.unwrap()
+}
@@ -0,0 +1,198 @@
+// Runs the Riot API handlers against the mock Riot server. The backfill test needs a database with all the migrations
+// applied so it's ignored by default (see common::test_pool). Unlike the stats tests the backfills commit
+// so we clean up the fixture matches/accounts before and after.
+mod common;
+
+use async_trait::async_trait;
+use squadov_common::{
+    SquadOvError,
+    rabbitmq::{RabbitMqConfig, RabbitMqInterface},
+    elastic::rabbitmq::ElasticSearchJobInterface,
+    riot::{
+        db,
+        api::{
+            ApiKeyLimit,
+            RiotApiKeyConfig,
+            RiotApiEndpointConfig,
+            RiotApiCache,
+            RiotApiCacheConfig,
+            RiotApiHandler,
+            RiotRateLimiterConfig,
+            LocalRiotRateLimitStore,
+            RiotApiApplicationInterface,
+            RiotConfig,
+        },
+        mock::{RiotMockServer, RiotMockFault},
+    },
+};
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+fn manifest() -> PathBuf {
+    common::test_data(&["riot", "mock", "manifest.json"])
+}
+
+fn key_config() -> RiotApiKeyConfig {
+    let limit = ApiKeyLimit{
+        requests: 100,
+        seconds: 1,
+        enabled: false,
+    };
+
+    RiotApiKeyConfig{
+        key: String::from("fixture-key"),
+        burst_limit: limit.clone(),
+        bulk_limit: limit,
+    }
+}
+
+fn riot_config(server: &RiotMockServer) -> RiotConfig {
+    RiotConfig{
+        rso_url: String::new(),
+        rso_client_id: String::new(),
+        rso_client_secret: String::new(),
+        rso_api_key: key_config(),
+        valorant_api_key: key_config(),
+        lol_api_key: key_config(),
+        tft_api_key: key_config(),
+        endpoints: RiotApiEndpointConfig{
+            default_base_url: server.base_url(),
+            auth_url: server.base_url(),
+            ..RiotApiEndpointConfig::default()
+        },
+        cache: RiotApiCacheConfig::default(),
+        rate_limit: RiotRateLimiterConfig::default(),
+    }
+}
+
+#[derive(Default)]
+struct MemoryCache {
+    data: Mutex<HashMap<String, String>>,
+}
+
+#[async_trait]
+impl RiotApiCache for MemoryCache {
+    async fn get(&self, key: &str) -> Result<Option<String>, SquadOvError> {
+        Ok(self.data.lock().unwrap().get(key).cloned())
+    }
+
+    async fn set(&self, key: &str, value: &str, _ttl_seconds: i64) -> Result<(), SquadOvError> {
+        self.data.lock().unwrap().insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_mock_cache_and_faults() {
+    let server = RiotMockServer::start(&manifest(), "127.0.0.1:0").await.unwrap();
+    // None of the requests in this test touch the database.
+    let pool = Arc::new(PgPoolOptions::new().connect_lazy("postgres://localhost/unused").unwrap());
+    let api = RiotApiHandler::new(key_config(), &riot_config(&server), Some(Arc::new(MemoryCache::default())), Arc::new(LocalRiotRateLimitStore::default()), pool);
+
+    let lol_match = api.get_lol_match("NA1", 4000000001).await.unwrap();
+    assert_eq!(lol_match.info.game_id, 4000000001);
+    api.get_lol_match("NA1", 4000000001).await.unwrap();
+    assert_eq!(server.request_count("lol/match/v5/matches/NA1_4000000001"), 1);
+
+    // Missing fixtures are 404s and those never get cached.
+    assert!(matches!(api.get_lol_match_timeline("NA1", 4000000001).await, Err(SquadOvError::NotFound)));
+    assert!(matches!(api.get_lol_match_timeline("NA1", 4000000001).await, Err(SquadOvError::NotFound)));
+    assert_eq!(server.request_count("lol/match/v5/matches/NA1_4000000001/timeline"), 2);
+
+    server.add_fault(RiotMockFault{
+        path_prefix: String::from("tft/match/v1/matches/"),
+        status: 429,
+        retry_after: Some(1),
+        count: 1,
+    });
+    assert!(matches!(api.get_tft_match("NA1", "NA1_4100000001").await, Err(SquadOvError::RateLimit)));
+    // The retry has to wait out the Retry-After before going back to the server.
+    let start = std::time::Instant::now();
+    assert_eq!(api.get_tft_match("NA1", "NA1_4100000001").await.unwrap().info.participants.len(), 2);
+    assert!(start.elapsed() >= std::time::Duration::from_millis(900));
+    let counters = api.rate_limit_snapshot().counters;
+    let tft_counters = counters.get("americas:tft-match-v1.getMatch").unwrap();
+    assert_eq!(tft_counters.rate_limited, 1);
+    assert_eq!(tft_counters.delayed, 1);
+
+    server.add_fault(RiotMockFault{
+        path_prefix: String::from("riot/account/"),
+        status: 503,
+        retry_after: None,
+        count: 1,
+    });
+    assert!(matches!(api.get_account_by_puuid("fixture-val-puuid").await, Err(SquadOvError::Defer(_))));
+    assert_eq!(api.get_account_by_puuid("fixture-val-puuid").await.unwrap().game_name.as_deref(), Some("Fixture"));
+    assert_eq!(api.get_active_shard_by_game_for_puuid("val", "fixture-val-puuid").await.unwrap(), "na");
+    server.stop().await;
+}
+
+async fn cleanup(pool: &PgPool) {
+    sqlx::query(
+        "
+        DELETE FROM squadov.matches
+        WHERE uuid IN (
+            SELECT match_uuid FROM squadov.lol_matches WHERE platform = 'NA1' AND match_id = 4000000001
+            UNION
+            SELECT match_uuid FROM squadov.tft_matches WHERE platform = 'NA1' AND match_id = 4100000001
+        )
+        "
+    )
+        .execute(pool)
+        .await
+        .unwrap();
+
+    let puuids = vec!["fixture-val-puuid", "fixture-tft-puuid", "fixture-tft-enemy"];
+    sqlx::query("DELETE FROM squadov.riot_account_game_shards WHERE puuid = ANY($1)")
+        .bind(&puuids)
+        .execute(pool)
+        .await
+        .unwrap();
+
+    sqlx::query("DELETE FROM squadov.riot_accounts WHERE puuid = ANY($1)")
+        .bind(&puuids)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_mock_backfill() {
+    let pool = Arc::new(common::test_pool(2).await);
+    cleanup(&*pool).await;
+
+    let server = RiotMockServer::start(&manifest(), "127.0.0.1:0").await.unwrap();
+    let config = riot_config(&server);
+
+    // Nothing gets sent to RabbitMQ, the match requests just pile up in the publish queue.
+    let mqconfig = RabbitMqConfig::default();
+    let rmq = RabbitMqInterface::new(&mqconfig, Some(pool.clone()), false).await.unwrap();
+    let es_itf = Arc::new(ElasticSearchJobInterface::new_producer_only(&mqconfig, rmq.clone(), pool.clone()));
+    let api = Arc::new(RiotApiHandler::new(key_config(), &config, None, Arc::new(LocalRiotRateLimitStore::default()), pool.clone()));
+    let itf = RiotApiApplicationInterface::new(config, &mqconfig, api, rmq, pool.clone(), es_itf);
+
+    itf.backfill_user_lol_matches("fixture-lol-puuid", "NA1").await.unwrap();
+    assert_eq!(server.request_count("lol/match/v5/matches/by-puuid/fixture-lol-puuid/ids"), 1);
+    itf.obtain_lol_match_info("NA1", 4000000001).await.unwrap();
+    assert!(db::check_lol_match_details_exist(&*pool, "NA1", 4000000001).await.unwrap());
+
+    itf.backfill_user_tft_matches("fixture-tft-puuid", "NA1").await.unwrap();
+    assert_eq!(server.request_count("tft/match/v1/matches/by-puuid/fixture-tft-puuid/ids"), 1);
+    itf.obtain_tft_match_info("NA1", "NA1", 4100000001).await.unwrap();
+    assert_eq!(db::get_tft_matches_that_require_backfill(&*pool, &vec![String::from("NA1_4100000001")]).await.unwrap().len(), 0);
+    assert_eq!(server.request_count("tft/summoner/v1/summoners/by-puuid/fixture-tft-enemy"), 1);
+
+    itf.obtain_riot_account_from_puuid("fixture-val-puuid").await.unwrap();
+    itf.backfill_user_valorant_matches("fixture-val-puuid").await.unwrap();
+    let shard: String = sqlx::query_scalar("SELECT shard FROM squadov.riot_account_game_shards WHERE puuid = 'fixture-val-puuid' AND game = 'val'")
+        .fetch_one(&*pool)
+        .await
+        .unwrap();
+    assert_eq!(shard, "na");
+
+    server.stop().await;
+    cleanup(&*pool).await;
+}
@@ -0,0 +1,79 @@
+// These tests push the fixture matches through the same code paths we use to store matches pulled from Riot's API
+// and then read the stats back out. They need a database with all the migrations applied so they're ignored by default
+// (see common::test_pool). Everything happens inside of a transaction that gets rolled back.
+mod common;
+
+use squadov_common::riot::{
+    db,
+    games::{LolMatchDto, TftMatchDto},
+    stats::{
+        lol::{LolStatsFilters, LolStatsSummary},
+        tft::{TftStatsFilters, TftStatsSummary},
+    },
+};
+use uuid::Uuid;
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(common::test_data(&["riot", name])).unwrap()
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_lol_stats_from_fixture() {
+    let pool = common::test_pool(1).await;
+
+    let lol_match: LolMatchDto = serde_json::from_str(&fixture("lol_match.json")).unwrap();
+    let mut tx = pool.begin().await.unwrap();
+    let match_uuid = db::create_or_get_match_uuid_for_lol_match(&mut tx, "NA1", lol_match.info.game_id, lol_match.info.game_creation.clone()).await.unwrap();
+    db::store_lol_match_info(&mut tx, &match_uuid, &lol_match).await.unwrap();
+
+    let rows = db::get_lol_player_match_stats_rows(&mut tx, "fixture-lol-puuid", 0, 20, &LolStatsFilters::default()).await.unwrap();
+    let summary = LolStatsSummary::from_rows(&rows);
+    assert_eq!(summary.overall.games, 1);
+    assert_eq!(summary.overall.wins, 1);
+    assert_eq!(summary.overall.kda, Some(7.0));
+    assert_eq!(summary.overall.cs_per_min, Some(6.4));
+    assert_eq!(summary.overall.avg_vision_score, Some(25.0));
+    assert_eq!(summary.overall.avg_damage_share, Some(0.4));
+    assert!(summary.by_champion.contains_key(&103));
+    assert!(summary.by_role.contains_key("MIDDLE"));
+    assert_eq!(summary.damage_share[0].patch, "12.14");
+
+    let filters = LolStatsFilters{
+        queues: Some(vec![420]),
+        patches: Some(vec![String::from("12.13")]),
+        ..LolStatsFilters::default()
+    };
+    assert!(db::get_lol_player_match_stats_rows(&mut tx, "fixture-lol-puuid", 0, 20, &filters).await.unwrap().is_empty());
+
+    // Past the first page.
+    assert!(db::get_lol_player_match_stats_rows(&mut tx, "fixture-lol-puuid", 1, 20, &LolStatsFilters::default()).await.unwrap().is_empty());
+    tx.rollback().await.unwrap();
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_tft_stats_from_fixture() {
+    let pool = common::test_pool(1).await;
+
+    let tft_match: TftMatchDto = serde_json::from_str(&fixture("tft_match.json")).unwrap();
+    let mut tx = pool.begin().await.unwrap();
+    let match_uuid = db::create_or_get_match_uuid_for_tft_match(&mut tx, "NA1", "americas", 4100000001, tft_match.info.game_datetime.clone()).await.unwrap();
+    db::store_tft_match_info(&mut tx, &match_uuid, &tft_match).await.unwrap();
+
+    let placements = db::get_tft_player_placement_rows(&mut tx, "fixture-tft-puuid", 0, 20, &TftStatsFilters::default()).await.unwrap();
+    let match_uuids: Vec<Uuid> = placements.iter().map(|x| { x.match_uuid.clone() }).collect();
+    let traits = db::get_tft_player_active_trait_rows(&mut tx, "fixture-tft-puuid", &match_uuids).await.unwrap();
+    let units = db::get_tft_player_unit_rows(&mut tx, "fixture-tft-puuid", &match_uuids).await.unwrap();
+    let summary = TftStatsSummary::from_rows(&placements, &traits, &units);
+
+    assert_eq!(summary.overall.games, 1);
+    assert_eq!(summary.overall.avg_placement, Some(2.0));
+    assert_eq!(summary.overall.top4_rate, Some(1.0));
+    assert!(summary.by_companion.contains_key("PetTFTAvatar"));
+    // Inactive traits don't count.
+    assert_eq!(summary.by_trait.keys().collect::<Vec<&String>>(), vec!["Set7_Dragonmancer"]);
+    assert_eq!(summary.units.len(), 3);
+    assert_eq!(summary.items.iter().find(|x| { x.item_id == 44 }).map(|x| { x.count }), Some(2));
+    tx.rollback().await.unwrap();
+}
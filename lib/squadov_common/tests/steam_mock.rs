@@ -0,0 +1,260 @@
+// Runs the Steam client against the mock Steam Web API server and checks the Steam OpenID responses we accept. The
+// friend suggestion test needs a database with all the migrations applied so it's ignored by default (see common::test_pool).
+mod common;
+
+use squadov_common::{
+    steam::{
+        api::{SteamApiClient, SteamApiConfig},
+        friends::{self, FriendSuggestionSettings},
+        mock::SteamMockServer,
+        openid::check_steam_openid_response_params,
+    },
+    SquadOvError,
+};
+use serde_json::json;
+use sqlx::postgres::PgPool;
+use std::collections::{HashMap, HashSet};
+
+const LEADER: &str = "76561198000000001";
+const TEAMMATE: &str = "76561198000000002";
+const PRIVATE_FRIEND: &str = "76561198000000003";
+const LONER: &str = "76561198000000004";
+// Only exist on our end for the friend suggestion test.
+const OPTED_OUT: &str = "76561198000000005";
+const UNVERIFIED: &str = "76561198000000006";
+const NO_SETTINGS: &str = "76561198000000007";
+
+fn steam_client(server: &SteamMockServer, api_key: &str) -> SteamApiClient {
+    let config: SteamApiConfig = serde_json::from_value(json!({
+        "api_key": api_key,
+        "requests": 100,
+        "seconds": 1,
+        "base_url": server.base_url(),
+    })).unwrap();
+    SteamApiClient::new(&config)
+}
+
+fn id(steam_id: &str) -> i64 {
+    steam_id.parse().unwrap()
+}
+
+#[tokio::test]
+async fn test_mock_player_summaries() {
+    let server = SteamMockServer::start(Some(&common::mock_fixtures("steam")), "127.0.0.1:0").await.unwrap();
+    let steam = steam_client(&server, "fixture_key");
+
+    let mut summaries = steam.get_player_summaries(&[id(LEADER), id(TEAMMATE), 1]).await.unwrap();
+    summaries.sort_by(|a, b| { a.steamid.cmp(&b.steamid) });
+    assert_eq!(summaries.iter().map(|x| { x.personaname.as_str() }).collect::<Vec<&str>>(), vec!["SquadLeader", "Teammate"]);
+
+    assert!(steam_client(&server, "wrong_key").get_player_summaries(&[id(LEADER)]).await.is_err());
+    assert_eq!(server.request_count("GET ISteamUser/GetPlayerSummaries/v0002"), 2);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_mock_friend_list() {
+    let server = SteamMockServer::start(Some(&common::mock_fixtures("steam")), "127.0.0.1:0").await.unwrap();
+    let steam = steam_client(&server, "fixture_key");
+
+    let mut friends = steam.get_friend_list(id(LEADER)).await.unwrap().unwrap();
+    friends.sort_by(|a, b| { a.steamid.cmp(&b.steamid) });
+    assert_eq!(friends.iter().map(|x| { x.steamid.as_str() }).collect::<Vec<&str>>(), vec![TEAMMATE, PRIVATE_FRIEND]);
+    assert!(friends.iter().all(|x| { x.friend_since_tm().is_some() }));
+
+    // Private profiles don't have a friend list but still show up in their friends' lists.
+    assert!(steam.get_friend_list(id(PRIVATE_FRIEND)).await.unwrap().is_none());
+    assert!(steam.get_friend_list(id(LONER)).await.unwrap().unwrap().is_empty());
+
+    server.add_friendship(LONER, TEAMMATE, 0);
+    server.remove_friendship(TEAMMATE, LEADER);
+    assert!(server.set_private(LEADER, true));
+    assert!(steam.get_friend_list(id(LEADER)).await.unwrap().is_none());
+
+    let friends = steam.get_friend_list(id(TEAMMATE)).await.unwrap().unwrap();
+    assert_eq!(friends.len(), 1);
+    assert_eq!(friends[0].steamid, LONER);
+    assert!(friends[0].friend_since_tm().is_none());
+
+    assert!(steam_client(&server, "wrong_key").get_friend_list(id(LONER)).await.is_err());
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_mock_friend_suggestion_candidates() {
+    let server = SteamMockServer::start(Some(&common::mock_fixtures("steam")), "127.0.0.1:0").await.unwrap();
+    let steam = steam_client(&server, "fixture_key");
+
+    // SquadOV users linked to the Steam accounts.
+    let users: HashMap<&str, i64> = vec![(LEADER, 1), (TEAMMATE, 2), (PRIVATE_FRIEND, 3), (LONER, 4)].into_iter().collect();
+    let steam_friends: Vec<i64> = steam.get_friend_list(id(LEADER)).await.unwrap().unwrap().into_iter().filter_map(|x| {
+        users.get(x.steamid.as_str()).cloned()
+    }).collect();
+
+    // The loner isn't a friend but has been in enough CS:GO matches with the leader.
+    let candidates = friends::merge_friend_suggestion_candidates(&steam_friends, &[(2, 5), (4, friends::MIN_MATCHES_PLAYED_TOGETHER)], friends::MIN_MATCHES_PLAYED_TOGETHER);
+    assert_eq!(candidates.iter().map(|x| { (x.user_id, x.steam_friend, x.matches_played_together) }).collect::<Vec<(i64, bool, i64)>>(), vec![
+        (2, true, 5),
+        (3, true, 0),
+        (4, false, friends::MIN_MATCHES_PLAYED_TOGETHER),
+    ]);
+    server.stop().await;
+}
+
+// Creates a user with the given Steam account. Verified accounts get linked by signing in through Steam while
+// unverified ones only get reported by the client.
+async fn create_steam_user(pool: &PgPool, name: &str, steam_id: &str, verified: bool, settings: Option<FriendSuggestionSettings>) -> i64 {
+    let user_id: i64 = sqlx::query_scalar(
+        "
+        INSERT INTO squadov.users (email, username, verified, uuid, local_encryption_key)
+        VALUES ($1, $2, TRUE, gen_random_uuid(), 'fixture')
+        RETURNING id
+        "
+    )
+        .bind(format!("{}@squadov.gg", name))
+        .bind(name)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+    sqlx::query("INSERT INTO squadov.steam_users_cache (steam_id, steam_name) VALUES ($1, $2)")
+        .bind(id(steam_id))
+        .bind(name)
+        .execute(pool)
+        .await
+        .unwrap();
+
+    if verified {
+        sqlx::query("INSERT INTO squadov.user_login_identities (user_id, provider, provider_user_id, display_name, create_tm) VALUES ($1, 'steam', $2, $3, NOW())")
+            .bind(user_id)
+            .bind(steam_id)
+            .bind(name)
+            .execute(pool)
+            .await
+            .unwrap();
+    } else {
+        sqlx::query("INSERT INTO squadov.steam_user_links (steam_id, user_id) VALUES ($1, $2)")
+            .bind(id(steam_id))
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    if let Some(settings) = settings {
+        friends::update_friend_suggestion_settings(pool, user_id, &settings).await.unwrap();
+    }
+    user_id
+}
+
+async fn sync_friend_list(pool: &PgPool, steam: &SteamApiClient, steam_id: &str) {
+    let friend_list = steam.get_friend_list(id(steam_id)).await.unwrap();
+    let mut tx = pool.begin().await.unwrap();
+    friends::store_steam_friend_list(&mut tx, id(steam_id), friend_list.as_deref()).await.unwrap();
+    tx.commit().await.unwrap();
+}
+
+async fn cleanup(pool: &PgPool) {
+    sqlx::query("DELETE FROM squadov.users WHERE username LIKE 'steam-fixture-%'")
+        .execute(pool)
+        .await
+        .unwrap();
+
+    sqlx::query("DELETE FROM squadov.steam_users_cache WHERE steam_id = ANY($1)")
+        .bind(vec![id(LEADER), id(TEAMMATE), id(PRIVATE_FRIEND), id(LONER), id(OPTED_OUT), id(UNVERIFIED), id(NO_SETTINGS)])
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_friend_suggestions_respect_privacy() {
+    let pool = common::test_pool(2).await;
+    cleanup(&pool).await;
+
+    let server = SteamMockServer::start(Some(&common::mock_fixtures("steam")), "127.0.0.1:0").await.unwrap();
+    let steam = steam_client(&server, "fixture_key");
+
+    let opted_in = Some(FriendSuggestionSettings{
+        suggest_to_steam_friends: true,
+        suggest_to_played_with: true,
+    });
+    let loner = create_steam_user(&pool, "steam-fixture-loner", LONER, true, None).await;
+    create_steam_user(&pool, "steam-fixture-leader", LEADER, true, opted_in.clone()).await;
+    create_steam_user(&pool, "steam-fixture-teammate", TEAMMATE, true, opted_in.clone()).await;
+    create_steam_user(&pool, "steam-fixture-private", PRIVATE_FRIEND, true, opted_in.clone()).await;
+    create_steam_user(&pool, "steam-fixture-opted-out", OPTED_OUT, true, Some(FriendSuggestionSettings::default())).await;
+    create_steam_user(&pool, "steam-fixture-unverified", UNVERIFIED, false, opted_in.clone()).await;
+    create_steam_user(&pool, "steam-fixture-no-settings", NO_SETTINGS, true, None).await;
+
+    for friend in &[TEAMMATE, OPTED_OUT, UNVERIFIED, NO_SETTINGS] {
+        server.add_friendship(LONER, friend, 0);
+    }
+    sync_friend_list(&pool, &steam, LONER).await;
+
+    // These only show up on the other side's friend list since the loner's list was already synced.
+    server.add_friendship(LEADER, LONER, 0);
+    server.add_friendship(PRIVATE_FRIEND, LONER, 0);
+    sync_friend_list(&pool, &steam, LEADER).await;
+    sync_friend_list(&pool, &steam, PRIVATE_FRIEND).await;
+
+    let suggested: HashSet<String> = friends::get_friend_suggestions(&pool, loner, usize::MAX).await.unwrap().into_iter().map(|x| {
+        assert!(x.steam_friend);
+        x.username
+    }).collect();
+    assert_eq!(suggested, vec!["steam-fixture-teammate", "steam-fixture-leader"].into_iter().map(String::from).collect());
+
+    // Going private hides the leader's side of the friendship too.
+    assert!(server.set_private(LEADER, true));
+    sync_friend_list(&pool, &steam, LEADER).await;
+    let suggested: Vec<String> = friends::get_friend_suggestions(&pool, loner, usize::MAX).await.unwrap().into_iter().map(|x| { x.username }).collect();
+    assert_eq!(suggested, vec!["steam-fixture-teammate"]);
+
+    cleanup(&pool).await;
+    server.stop().await;
+}
+
+const OPENID_RETURN_TO: &str = "https://app.squadov.gg/login/steam?state=fixture";
+
+fn openid_params() -> HashMap<String, String> {
+    let claimed_id = format!("https://steamcommunity.com/openid/id/{}", LEADER);
+    vec![
+        ("openid.ns", "http://specs.openid.net/auth/2.0"),
+        ("openid.mode", "id_res"),
+        ("openid.op_endpoint", "https://steamcommunity.com/openid/login"),
+        ("openid.claimed_id", claimed_id.as_str()),
+        ("openid.identity", claimed_id.as_str()),
+        ("openid.return_to", OPENID_RETURN_TO),
+        ("openid.response_nonce", "2022-08-01T00:00:00Zfixture"),
+        ("openid.assoc_handle", "1234567890"),
+        ("openid.signed", "signed,op_endpoint,claimed_id,identity,return_to,response_nonce,assoc_handle"),
+        ("openid.sig", "fixture"),
+    ].into_iter().map(|(k, v)| { (k.to_string(), v.to_string()) }).collect()
+}
+
+#[test]
+fn test_steam_openid_response_params() {
+    assert_eq!(check_steam_openid_response_params(&openid_params(), OPENID_RETURN_TO).unwrap(), id(LEADER));
+    assert!(matches!(check_steam_openid_response_params(&openid_params(), "https://app.squadov.gg/login/steam?state=other"), Err(SquadOvError::Unauthorized)));
+
+    let mut params = openid_params();
+    params.insert(String::from("openid.op_endpoint"), String::from("https://evil.example.com/openid/login"));
+    assert!(matches!(check_steam_openid_response_params(&params, OPENID_RETURN_TO), Err(SquadOvError::Unauthorized)));
+
+    // A claimed ID that isn't covered by the signature could've been swapped in by anyone.
+    for field in &["claimed_id", "identity", "op_endpoint"] {
+        let mut params = openid_params();
+        let signed = params["openid.signed"].split(',').filter(|x| { x != field }).collect::<Vec<&str>>().join(",");
+        params.insert(String::from("openid.signed"), signed);
+        assert!(matches!(check_steam_openid_response_params(&params, OPENID_RETURN_TO), Err(SquadOvError::Unauthorized)));
+    }
+
+    let mut params = openid_params();
+    params.insert(String::from("openid.identity"), format!("https://steamcommunity.com/openid/id/{}", TEAMMATE));
+    assert!(matches!(check_steam_openid_response_params(&params, OPENID_RETURN_TO), Err(SquadOvError::Unauthorized)));
+
+    let mut params = openid_params();
+    params.insert(String::from("openid.mode"), String::from("cancel"));
+    assert!(matches!(check_steam_openid_response_params(&params, OPENID_RETURN_TO), Err(SquadOvError::BadRequest)));
+}
@@ -0,0 +1,87 @@
+// Storage usage backfill tests. These need a database with all the migrations applied so they're ignored by
+// default (see common::test_pool).
+mod common;
+
+use squadov_common::{
+    storage,
+    vod::{
+        VodCopyLocation,
+        VodMetadata,
+        VodSegmentId,
+        db as vdb,
+    },
+};
+use sqlx::postgres::PgPool;
+use uuid::Uuid;
+
+const BUCKET: &str = "gs://storage-usage-fixture";
+
+async fn create_user(pool: &PgPool, name: &str) -> i64 {
+    sqlx::query_scalar(
+        "
+        INSERT INTO squadov.users (email, username, verified, uuid, local_encryption_key)
+        VALUES ($1, $2, TRUE, gen_random_uuid(), 'fixture')
+        RETURNING id
+        "
+    )
+        .bind(format!("{}@squadov.gg", name))
+        .bind(name)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+// A VOD that made it to the cloud but has nothing in storage_usage.
+async fn create_untracked_cloud_vod(pool: &PgPool, user_id: i64) -> Uuid {
+    let video_uuid = Uuid::new_v4();
+    vdb::reserve_vod_uuid(pool, &video_uuid, "mp4", user_id, false).await.unwrap();
+    vdb::bulk_add_video_metadata(pool, &video_uuid, &[VodMetadata{
+        video_uuid: video_uuid.clone(),
+        id: String::from("source"),
+        bucket: String::from(BUCKET),
+        ..VodMetadata::default()
+    }]).await.unwrap();
+    vdb::bulk_sync_vod_copies(pool, &[video_uuid.clone()], VodCopyLocation::Cloud, BUCKET).await.unwrap();
+    video_uuid
+}
+
+async fn untracked_position(pool: &PgPool, video_uuid: &Uuid) -> usize {
+    storage::get_untracked_cloud_vods(pool, i64::MAX).await.unwrap().iter().position(|x| { &x.video_uuid == video_uuid }).unwrap()
+}
+
+async fn cleanup(pool: &PgPool) {
+    sqlx::query("DELETE FROM squadov.users WHERE username LIKE 'storage-fixture-%'")
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_unmeasurable_vod_does_not_block_backfill() {
+    let pool = common::test_pool(2).await;
+    cleanup(&pool).await;
+
+    let user_id = create_user(&pool, "storage-fixture-user").await;
+    let missing = create_untracked_cloud_vod(&pool, user_id).await;
+    let measurable = create_untracked_cloud_vod(&pool, user_id).await;
+
+    // Nothing ever gets tracked for the missing VOD (its files aren't in the bucket) so it stays untracked but has to
+    // end up behind every VOD we haven't tried yet.
+    storage::mark_cloud_vod_reconcile_attempts(&pool, &[missing.clone()]).await.unwrap();
+    assert!(untracked_position(&pool, &measurable).await < untracked_position(&pool, &missing).await);
+
+    // Once the other one has been tried too the missing VOD is the oldest attempt again and gets retried first.
+    storage::mark_cloud_vod_reconcile_attempts(&pool, &[measurable.clone()]).await.unwrap();
+    assert!(untracked_position(&pool, &missing).await < untracked_position(&pool, &measurable).await);
+
+    // Tracking the VOD takes it out of the backfill entirely.
+    storage::track_vod_segment_storage_usage(&pool, &VodSegmentId{
+        video_uuid: measurable.clone(),
+        quality: String::from("source"),
+        segment_name: String::from("video.mp4"),
+    }, BUCKET, 1024).await.unwrap();
+    assert!(storage::get_untracked_cloud_vods(&pool, i64::MAX).await.unwrap().iter().all(|x| { x.video_uuid != measurable }));
+
+    cleanup(&pool).await;
+}
@@ -0,0 +1,449 @@
+// Runs the Stripe client against the mock Stripe server. The subscription tests need a database with all the migrations
+// applied so they're ignored by default (see common::test_pool).
+mod common;
+
+use actix_web::{
+    web,
+    App,
+    FromRequest,
+    HttpResponse,
+    HttpServer,
+    dev::Payload,
+    test::TestRequest,
+};
+use chrono::Utc;
+use squadov_common::{
+    SquadOvError,
+    stripe::{
+        StripeApiClient,
+        StripeApiConfig,
+        checkout::{
+            StripeCreateSessionRequest,
+            StripeCheckoutSessionMode,
+            StripeCheckoutLineItem,
+            StripeCheckoutSubscriptionData,
+        },
+        price::{
+            ListAllPricesRequest,
+            StripeRecurring,
+            StripeRecurringInterval,
+        },
+        product::{
+            StripeListAllProductRequest,
+            StripeSearchProductsRequest,
+        },
+        invoice::StripeInvoice,
+        subscription::{
+            StripeSubscription,
+            StripeListSubscriptionsRequest,
+        },
+        webhook::{
+            StripeGenericWebhookEvent,
+            StripeSignature,
+            StripeTypedWebhookEvent,
+        },
+        mock::{StripeMockServer, sign_stripe_webhook_event},
+    },
+    subscriptions::{self, SquadOvSubTiers, SquadOvSubSource},
+};
+use serde_json::{json, Value};
+use sqlx::postgres::PgPool;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+
+const WEBHOOK_SECRET: &str = "whsec_fixture";
+
+fn stripe_client(server: &StripeMockServer) -> StripeApiClient {
+    let config: StripeApiConfig = serde_json::from_value(json!({
+        "secret_api_key": "sk_test_fixture",
+        "api_version": "2020-08-27",
+        "webhook_secret": WEBHOOK_SECRET,
+        "base_url": server.base_url(),
+    })).unwrap();
+    StripeApiClient::new(&config)
+}
+
+#[tokio::test]
+async fn test_mock_products_and_checkout() {
+    let server = StripeMockServer::start(Some(&common::mock_fixtures("stripe")), "127.0.0.1:0").await.unwrap();
+    let stripe = stripe_client(&server);
+
+    assert_eq!(stripe.list_all_products(StripeListAllProductRequest{active: Some(true)}).await.unwrap().data.len(), 3);
+    let team = stripe.search_products(StripeSearchProductsRequest{
+        active: Some(true),
+        metadata: Some(HashMap::from([(String::from("team_tier"), String::from("GOLD"))])),
+    }).await.unwrap();
+    assert_eq!(team.data.len(), 1);
+    assert_eq!(team.data[0].id, "prod_fixture_team_gold");
+
+    let prices = stripe.list_all_prices(ListAllPricesRequest{
+        product: Some(String::from("prod_fixture_gold")),
+        recurring: Some(StripeRecurring{interval: Some(StripeRecurringInterval::Year)}),
+        currency: None,
+    }).await.unwrap();
+    assert_eq!(prices.data.len(), 1);
+    assert_eq!(prices.data[0].id, "price_fixture_gold_year");
+    assert!(matches!(stripe.retrieve_a_product("prod_missing").await, Err(SquadOvError::InternalError(_))));
+
+    // Canceled subscriptions aren't listed.
+    let subs = stripe.list_subscriptions(StripeListSubscriptionsRequest{customer: Some(String::from("cus_fixture_owner"))}).await.unwrap();
+    assert_eq!(subs.len(), 1);
+    assert_eq!(subs[0].metadata.get("squad_id").map(|x| { x.as_str() }), Some("1"));
+    assert_eq!(subs[0].items.data[0].quantity, Some(3));
+
+    let customer = stripe.create_a_customer("stripe-fixture-new@squadov.gg", "stripe-fixture-new").await.unwrap();
+    assert_eq!(stripe.retrieve_a_customer(&customer.id).await.unwrap().email, "stripe-fixture-new@squadov.gg");
+
+    let session = stripe.create_a_session(StripeCreateSessionRequest{
+        cancel_url: String::from("http://localhost/cancel"),
+        success_url: String::from("http://localhost/success"),
+        mode: StripeCheckoutSessionMode::Subscription,
+        line_items: vec![StripeCheckoutLineItem{
+            price: String::from("price_fixture_team_gold_month"),
+            quantity: Some(5),
+            subscription: None,
+        }],
+        discounts: vec![],
+        client_reference_id: None,
+        customer: Some(customer.id.clone()),
+        customer_email: None,
+        subscription_data: Some(StripeCheckoutSubscriptionData{
+            trial_period_days: None,
+            metadata: HashMap::from([(String::from("squad_id"), String::from("12"))]),
+        }),
+        allow_promotion_codes: true,
+        metadata: HashMap::from([(String::from("gift_recipient_id"), String::from("34"))]),
+    }).await.unwrap();
+    assert!(session.url.unwrap().starts_with(&server.base_url()));
+    assert_eq!(session.metadata.get("gift_recipient_id").map(|x| { x.as_str() }), Some("34"));
+
+    let stored = server.get("checkout/sessions", &session.id).unwrap();
+    assert_eq!(stored["mock_line_items"][0]["quantity"], 5);
+    assert_eq!(stored["mock_subscription_metadata"]["squad_id"], "12");
+    assert_eq!(server.request_count("POST v1/checkout/sessions"), 1);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_mock_webhook_signature() {
+    let server = StripeMockServer::start(Some(&common::mock_fixtures("stripe")), "127.0.0.1:0").await.unwrap();
+    let event = server.create_webhook_event("customer.subscription.updated", server.get("subscriptions", "sub_fixture_team").unwrap());
+    let (payload, header) = sign_stripe_webhook_event(&event, WEBHOOK_SECRET, Utc::now()).unwrap();
+
+    let req = TestRequest::default()
+        .insert_header(("Stripe-Signature", header))
+        .to_http_request();
+    let sig = StripeSignature::from_request(&req, &mut Payload::None).await.unwrap();
+    assert!(sig.is_valid(&payload, WEBHOOK_SECRET).unwrap());
+    assert!(!sig.is_valid(&payload, "whsec_wrong").unwrap());
+
+    let generic: StripeGenericWebhookEvent = serde_json::from_str(&payload).unwrap();
+    let typed = StripeTypedWebhookEvent::<StripeSubscription>::try_from(generic).unwrap();
+    assert_eq!(typed.data.object.id, "sub_fixture_team");
+    assert!(typed.data.object.status.is_valid());
+    server.stop().await;
+}
+
+// Stands in for our webhook endpoint and keeps track of the event types that pass the signature check.
+async fn receive_webhook(received: web::Data<Arc<Mutex<Vec<String>>>>, payload: web::Bytes, sig: StripeSignature) -> HttpResponse {
+    let payload = String::from_utf8(payload.to_vec()).unwrap();
+    if !sig.is_valid(&payload, WEBHOOK_SECRET).unwrap() {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let event: StripeGenericWebhookEvent = serde_json::from_str(&payload).unwrap();
+    received.lock().unwrap().push(event.event_type);
+    HttpResponse::NoContent().finish()
+}
+
+#[tokio::test]
+async fn test_mock_webhook_delivery() {
+    let received: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+    let receiver_state = received.clone();
+    let receiver = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(receiver_state.clone()))
+            .route("/webhooks/stripe", web::post().to(receive_webhook))
+    })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+    let receiver_addr = receiver.addrs()[0];
+    let receiver = receiver.run();
+    let receiver_handle = receiver.handle();
+    tokio::task::spawn(receiver);
+
+    let server = StripeMockServer::start(Some(&common::mock_fixtures("stripe")), "127.0.0.1:0").await.unwrap();
+    server.set_webhook_target(&format!("http://{}/webhooks/stripe", receiver_addr), WEBHOOK_SECRET);
+    let stripe = stripe_client(&server);
+
+    let customer = stripe.create_a_customer("stripe-fixture-delivery@squadov.gg", "stripe-fixture-delivery").await.unwrap();
+    let session = stripe.create_a_session(StripeCreateSessionRequest{
+        cancel_url: String::from("http://localhost/cancel"),
+        success_url: String::from("http://localhost/success"),
+        mode: StripeCheckoutSessionMode::Subscription,
+        line_items: vec![StripeCheckoutLineItem{
+            price: String::from("price_fixture_gold_month"),
+            quantity: Some(1),
+            subscription: None,
+        }],
+        discounts: vec![],
+        client_reference_id: None,
+        customer: Some(customer.id.clone()),
+        customer_email: None,
+        subscription_data: Some(StripeCheckoutSubscriptionData::default()),
+        allow_promotion_codes: true,
+        metadata: HashMap::new(),
+    }).await.unwrap();
+
+    let deliveries = server.complete_checkout_session(&session.id).await.unwrap();
+    assert!(deliveries.iter().all(|x| { x.status == Some(204) }));
+    assert_eq!(*received.lock().unwrap(), vec!["customer.subscription.created", "invoice.paid", "checkout.session.completed"]);
+    assert!(server.complete_checkout_session(&session.id).await.is_err());
+
+    let subs = stripe.list_subscriptions(StripeListSubscriptionsRequest{customer: Some(customer.id.clone())}).await.unwrap();
+    assert_eq!(subs.len(), 1);
+    assert!(subs[0].status.is_valid());
+    assert_eq!(subs[0].items.data[0].price.id, "price_fixture_gold_month");
+
+    // A failed renewal leaves the subscription past due and canceling it hides it from the list.
+    received.lock().unwrap().clear();
+    server.renew_subscription(&subs[0].id, false).await.unwrap();
+    assert_eq!(*received.lock().unwrap(), vec!["invoice.payment_failed", "customer.subscription.updated"]);
+    assert!(!stripe.retrieve_a_subscription(&subs[0].id).await.unwrap().status.is_valid());
+    assert_eq!(server.list("invoices").iter().filter(|x| { x["subscription"] == subs[0].id.as_str() }).count(), 2);
+
+    server.cancel_subscription(&subs[0].id).await.unwrap();
+    assert!(stripe.list_subscriptions(StripeListSubscriptionsRequest{customer: Some(customer.id.clone())}).await.unwrap().is_empty());
+
+    // A receiver with a different secret rejects everything.
+    server.set_webhook_target(&format!("http://{}/webhooks/stripe", receiver_addr), "whsec_wrong");
+    let event = server.create_webhook_event("customer.subscription.updated", server.get("subscriptions", &subs[0].id).unwrap());
+    assert_eq!(server.deliver_events(&[event]).await.unwrap()[0].status, Some(401));
+    assert_eq!(server.deliveries().len(), 7);
+
+    server.stop().await;
+    receiver_handle.stop(true).await;
+}
+
+async fn create_user(pool: &PgPool, name: &str) -> i64 {
+    sqlx::query_scalar(
+        "
+        INSERT INTO squadov.users (email, username, verified, uuid, local_encryption_key)
+        VALUES ($1, $2, TRUE, gen_random_uuid(), 'fixture')
+        RETURNING id
+        "
+    )
+        .bind(format!("{}@squadov.gg", name))
+        .bind(name)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn create_squad(pool: &PgPool, owner: i64, members: &[i64]) -> i64 {
+    let squad_id: i64 = sqlx::query_scalar(
+        "
+        INSERT INTO squadov.squads (squad_name, creation_time, is_default, max_members)
+        VALUES ('stripe-fixture-squad', NOW(), FALSE, NULL)
+        RETURNING id
+        "
+    )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+    let mut roles = vec![(owner, "Owner")];
+    roles.extend(members.iter().map(|x| { (*x, "Member") }));
+    for (user_id, role) in &roles {
+        sqlx::query("INSERT INTO squadov.squad_role_assignments (squad_id, user_id, squad_role) VALUES ($1, $2, $3::squadov.squad_role)")
+            .bind(squad_id)
+            .bind(user_id)
+            .bind(role)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+    squad_id
+}
+
+async fn cleanup(pool: &PgPool) {
+    sqlx::query("DELETE FROM squadov.squads WHERE squad_name = 'stripe-fixture-squad'")
+        .execute(pool)
+        .await
+        .unwrap();
+
+    sqlx::query("DELETE FROM squadov.users WHERE username LIKE 'stripe-fixture-%'")
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_team_and_gift_entitlements() {
+    let pool = common::test_pool(2).await;
+    cleanup(&pool).await;
+
+    let owner = create_user(&pool, "stripe-fixture-owner").await;
+    let member = create_user(&pool, "stripe-fixture-member").await;
+    let other = create_user(&pool, "stripe-fixture-other").await;
+    let squad_id = create_squad(&pool, owner, &[member, other]).await;
+
+    let team_id = subscriptions::upsert_team_subscription(&pool, "sub_fixture_db_team", Some(squad_id), owner, &SquadOvSubTiers::Gold, 2, Some(Utc::now() + chrono::Duration::days(30))).await.unwrap();
+    let mut tx = pool.begin().await.unwrap();
+    subscriptions::assign_team_subscription_seat(&mut tx, team_id, member).await.unwrap();
+    assert!(matches!(subscriptions::assign_team_subscription_seat(&mut tx, team_id, member).await, Err(SquadOvError::Duplicate)));
+    tx.commit().await.unwrap();
+
+    let mut tx = pool.begin().await.unwrap();
+    subscriptions::assign_team_subscription_seat(&mut tx, team_id, other).await.unwrap();
+    assert!(matches!(subscriptions::assign_team_subscription_seat(&mut tx, team_id, owner).await, Err(SquadOvError::BadRequest)));
+    tx.commit().await.unwrap();
+
+    assert_eq!(subscriptions::get_user_sub_tier(&pool, member).await.unwrap(), SquadOvSubTiers::Gold);
+    assert_eq!(subscriptions::get_user_personal_sub_tier(&pool, member).await.unwrap(), SquadOvSubTiers::Basic);
+
+    // Dropping down to one seat removes the most recently assigned seat.
+    let mut tx = pool.begin().await.unwrap();
+    assert_eq!(subscriptions::trim_team_subscription_seats(&mut tx, team_id, 1).await.unwrap(), vec![other]);
+    tx.commit().await.unwrap();
+    assert_eq!(subscriptions::get_user_sub_tier(&pool, other).await.unwrap(), SquadOvSubTiers::Basic);
+
+    // Seats stop counting once the user leaves the squad.
+    sqlx::query("DELETE FROM squadov.squad_role_assignments WHERE squad_id = $1 AND user_id = $2")
+        .bind(squad_id)
+        .bind(member)
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriptions::get_user_sub_tier(&pool, member).await.unwrap(), SquadOvSubTiers::Basic);
+
+    // Gifts stack and the same checkout session can't give out a gift twice.
+    let first = subscriptions::create_gift_subscription(&pool, Some(owner), member, &SquadOvSubTiers::Diamond, 30, Some("cs_fixture_gift_1")).await.unwrap().unwrap();
+    assert!(subscriptions::create_gift_subscription(&pool, Some(owner), member, &SquadOvSubTiers::Diamond, 30, Some("cs_fixture_gift_1")).await.unwrap().is_none());
+    let second = subscriptions::create_gift_subscription(&pool, Some(owner), member, &SquadOvSubTiers::Silver, 7, Some("cs_fixture_gift_2")).await.unwrap().unwrap();
+    assert_eq!(second.start_tm, first.end_tm);
+
+    let entitlements = subscriptions::get_user_sub_entitlements(&pool, member).await.unwrap();
+    assert_eq!(entitlements.len(), 1);
+    assert_eq!(entitlements[0].source, SquadOvSubSource::Gift);
+    assert_eq!(subscriptions::get_user_sub_tier(&pool, member).await.unwrap(), SquadOvSubTiers::Diamond);
+    assert_eq!(subscriptions::get_gift_subscriptions_for_user(&pool, owner).await.unwrap().len(), 2);
+
+    assert_eq!(subscriptions::expire_team_subscription(&pool, "sub_fixture_db_team").await.unwrap(), Some(team_id));
+    assert!(!subscriptions::get_team_subscription(&pool, team_id).await.unwrap().is_active());
+
+    cleanup(&pool).await;
+}
+
+async fn receive_webhook_event(received: web::Data<Arc<Mutex<Vec<Value>>>>, payload: web::Bytes) -> HttpResponse {
+    received.lock().unwrap().push(serde_json::from_slice(&payload).unwrap());
+    HttpResponse::NoContent().finish()
+}
+
+// Does the same thing for team subscriptions as the invoice.paid and customer.subscription.* webhook handlers. Returns the
+// users whose tier may have changed.
+async fn handle_team_webhook_events(stripe: &StripeApiClient, pool: &PgPool, owner: i64, received: &Mutex<Vec<Value>>) -> Vec<i64> {
+    let events: Vec<Value> = received.lock().unwrap().drain(..).collect();
+    let mut affected: Vec<i64> = vec![];
+    for e in events {
+        let event: StripeGenericWebhookEvent = serde_json::from_value(e).unwrap();
+        match event.event_type.as_str() {
+            "invoice.paid" => {
+                let event = StripeTypedWebhookEvent::<StripeInvoice>::try_from(event).unwrap();
+                affected.extend(subscriptions::sync_team_subscriptions_from_stripe_invoice(stripe, pool, owner, &event.data.object).await.unwrap().1);
+            },
+            "customer.subscription.updated" | "customer.subscription.deleted" => {
+                let event = StripeTypedWebhookEvent::<StripeSubscription>::try_from(event).unwrap();
+                affected.extend(subscriptions::sync_team_subscription_from_stripe(stripe, pool, owner, &event.data.object).await.unwrap().unwrap());
+            },
+            _ => (),
+        }
+    }
+    affected
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_team_subscription_webhooks() {
+    let pool = common::test_pool(2).await;
+    cleanup(&pool).await;
+
+    let owner = create_user(&pool, "stripe-fixture-owner").await;
+    let member = create_user(&pool, "stripe-fixture-member").await;
+    let other = create_user(&pool, "stripe-fixture-other").await;
+    let squad_id = create_squad(&pool, owner, &[member, other]).await;
+
+    let received: Arc<Mutex<Vec<Value>>> = Arc::new(Mutex::new(vec![]));
+    let receiver_state = received.clone();
+    let receiver = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(receiver_state.clone()))
+            .route("/webhooks/stripe", web::post().to(receive_webhook_event))
+    })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+    let receiver_addr = receiver.addrs()[0];
+    let receiver = receiver.run();
+    let receiver_handle = receiver.handle();
+    tokio::task::spawn(receiver);
+
+    let server = StripeMockServer::start(Some(&common::mock_fixtures("stripe")), "127.0.0.1:0").await.unwrap();
+    server.set_webhook_target(&format!("http://{}/webhooks/stripe", receiver_addr), WEBHOOK_SECRET);
+    let stripe = stripe_client(&server);
+
+    let mut sub = server.get("subscriptions", "sub_fixture_team").unwrap();
+    sub["id"] = json!("sub_fixture_webhook_team");
+    sub["current_period_end"] = json!(Utc::now().timestamp());
+    sub["metadata"]["squad_id"] = json!(squad_id.to_string());
+    sub["items"]["data"][0]["quantity"] = json!(2);
+    sub["items"]["data"][0]["subscription"] = json!("sub_fixture_webhook_team");
+    server.upsert("subscriptions", sub);
+
+    // The first payment creates the team subscription.
+    server.renew_subscription("sub_fixture_webhook_team", true).await.unwrap();
+    handle_team_webhook_events(&stripe, &pool, owner, &received).await;
+    let team = subscriptions::get_team_subscription_from_stripe(&pool, "sub_fixture_webhook_team").await.unwrap().unwrap();
+    assert_eq!(team.squad_id, Some(squad_id));
+    assert_eq!(team.seats, 2);
+    assert_eq!(team.tier, SquadOvSubTiers::Gold);
+
+    let mut tx = pool.begin().await.unwrap();
+    subscriptions::assign_team_subscription_seat(&mut tx, team.id, member).await.unwrap();
+    subscriptions::assign_team_subscription_seat(&mut tx, team.id, other).await.unwrap();
+    tx.commit().await.unwrap();
+    assert_eq!(subscriptions::get_user_sub_tier(&pool, other).await.unwrap(), SquadOvSubTiers::Gold);
+
+    // Renewing pushes back when everyone's seat ends.
+    server.renew_subscription("sub_fixture_webhook_team", true).await.unwrap();
+    let affected = handle_team_webhook_events(&stripe, &pool, owner, &received).await;
+    assert!(affected.contains(&member) && affected.contains(&other));
+    let renewed = subscriptions::get_team_subscription(&pool, team.id).await.unwrap();
+    assert!(renewed.end_tm.unwrap() > team.end_tm.unwrap() + chrono::Duration::days(29));
+
+    // Dropping a seat in Stripe takes the seat away from whoever got it last.
+    let mut sub = server.get("subscriptions", "sub_fixture_webhook_team").unwrap();
+    sub["items"]["data"][0]["quantity"] = json!(1);
+    server.upsert("subscriptions", sub.clone());
+    server.deliver_events(&[server.create_webhook_event("customer.subscription.updated", sub)]).await.unwrap();
+    let affected = handle_team_webhook_events(&stripe, &pool, owner, &received).await;
+    assert!(affected.contains(&other));
+    assert_eq!(subscriptions::get_team_subscription(&pool, team.id).await.unwrap().seats, 1);
+    assert_eq!(subscriptions::get_team_subscription_seats(&pool, team.id).await.unwrap().into_iter().map(|x| { x.user_id }).collect::<Vec<i64>>(), vec![member]);
+    assert_eq!(subscriptions::get_user_sub_tier(&pool, other).await.unwrap(), SquadOvSubTiers::Basic);
+    assert_eq!(subscriptions::get_user_sub_tier(&pool, member).await.unwrap(), SquadOvSubTiers::Gold);
+
+    // A failed renewal ends the team subscription for everyone still holding a seat.
+    server.renew_subscription("sub_fixture_webhook_team", false).await.unwrap();
+    let affected = handle_team_webhook_events(&stripe, &pool, owner, &received).await;
+    assert_eq!(affected, vec![member]);
+    assert!(!subscriptions::get_team_subscription(&pool, team.id).await.unwrap().is_active());
+    assert_eq!(subscriptions::get_user_sub_tier(&pool, member).await.unwrap(), SquadOvSubTiers::Basic);
+
+    server.stop().await;
+    receiver_handle.stop(true).await;
+    cleanup(&pool).await;
+}
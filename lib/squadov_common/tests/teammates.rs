@@ -0,0 +1,101 @@
+// Teammate analytics tests. These need a database with all the migrations applied so they're ignored by
+// default (see common::test_pool).
+mod common;
+
+use squadov_common::{
+    SquadOvGames,
+    teammates::{
+        MAX_TEAMMATE_ANALYTICS_MATCHES,
+        TeammateAnalyticsFilter,
+        db as tdb,
+    },
+};
+use sqlx::postgres::PgPool;
+
+async fn create_user(pool: &PgPool, name: &str) -> i64 {
+    sqlx::query_scalar(
+        "
+        INSERT INTO squadov.users (email, username, verified, uuid, local_encryption_key)
+        VALUES ($1, $2, TRUE, gen_random_uuid(), 'fixture')
+        RETURNING id
+        "
+    )
+        .bind(format!("{}@squadov.gg", name))
+        .bind(name)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+// One win per day going back from now so the oldest match is the one that gets left out.
+async fn create_outcomes(pool: &PgPool, user_id: i64, count: i64) {
+    sqlx::query(
+        "
+        WITH new_matches AS (
+            INSERT INTO squadov.matches (uuid, game)
+            SELECT gen_random_uuid(), $3
+            FROM generate_series(1, $2)
+            RETURNING uuid
+        )
+        INSERT INTO squadov.teammate_match_outcomes (match_uuid, user_id, game, match_time, won)
+        SELECT nm.uuid, $1, $3, NOW() - ROW_NUMBER() OVER () * INTERVAL '1 day', TRUE
+        FROM new_matches AS nm
+        "
+    )
+        .bind(user_id)
+        .bind(count)
+        .bind(SquadOvGames::Valorant as i32)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+async fn cleanup(pool: &PgPool) {
+    sqlx::query(
+        "
+        DELETE FROM squadov.matches
+        WHERE uuid IN (
+            SELECT tmo.match_uuid
+            FROM squadov.teammate_match_outcomes AS tmo
+            INNER JOIN squadov.users AS u
+                ON u.id = tmo.user_id
+            WHERE u.username LIKE 'teammates-fixture-%'
+        )
+        "
+    )
+        .execute(pool)
+        .await
+        .unwrap();
+
+    sqlx::query("DELETE FROM squadov.users WHERE username LIKE 'teammates-fixture-%'")
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_teammate_analytics_reports_truncation() {
+    let pool = common::test_pool(2).await;
+    cleanup(&pool).await;
+
+    let user_id = create_user(&pool, "teammates-fixture-user").await;
+    create_outcomes(&pool, user_id, MAX_TEAMMATE_ANALYTICS_MATCHES + 1).await;
+
+    let analytics = tdb::get_teammate_analytics(&pool, user_id, &TeammateAnalyticsFilter::default(), 10).await.unwrap();
+    assert_eq!(analytics.overall.matches, MAX_TEAMMATE_ANALYTICS_MATCHES);
+    assert_eq!(analytics.total_matches, MAX_TEAMMATE_ANALYTICS_MATCHES + 1);
+    assert!(analytics.truncated);
+
+    // Filtering the oldest match out brings everything back under the cap.
+    let recent = TeammateAnalyticsFilter{
+        start: Some(chrono::Utc::now() - chrono::Duration::days(MAX_TEAMMATE_ANALYTICS_MATCHES) - chrono::Duration::hours(12)),
+        ..TeammateAnalyticsFilter::default()
+    };
+    let analytics = tdb::get_teammate_analytics(&pool, user_id, &recent, 10).await.unwrap();
+    assert_eq!(analytics.overall.matches, MAX_TEAMMATE_ANALYTICS_MATCHES);
+    assert_eq!(analytics.total_matches, MAX_TEAMMATE_ANALYTICS_MATCHES);
+    assert!(!analytics.truncated);
+
+    cleanup(&pool).await;
+}
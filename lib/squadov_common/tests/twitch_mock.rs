@@ -0,0 +1,268 @@
+// Runs the Twitch client and EventSub handling against the mock Twitch server.
+mod common;
+
+use actix_web::{
+    web,
+    App,
+    HttpRequest,
+    HttpResponse,
+    HttpServer,
+    dev::ServerHandle,
+};
+use chrono::{Duration, TimeZone, Utc};
+use squadov_common::{
+    SquadOvError,
+    twitch::{
+        TwitchConfig,
+        api::{
+            EventSubCondition,
+            EventSubTransport,
+            TwitchApiClient,
+            TwitchClip,
+            TwitchTokenType,
+        },
+        eventsub::{
+            self,
+            TwitchStreamOnlineEvent,
+            TwitchStreamOfflineEvent,
+            TWITCH_STREAM_ONLINE,
+            TWITCH_STREAM_OFFLINE,
+        },
+        mock::TwitchMockServer,
+        oauth::TwitchOAuthToken,
+        streams::{self, TwitchStreamMatch, TWITCH_LINK_ARCHIVE, TWITCH_LINK_CLIP},
+    },
+};
+use serde_json::{json, Value};
+use sqlx::postgres::PgPoolOptions;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+const EVENTSUB_SECRET: &str = "eventsub_fixture_secret";
+const BROADCASTER_ID: &str = "141981764";
+
+fn twitch_client(server: &TwitchMockServer) -> TwitchApiClient {
+    let config: TwitchConfig = serde_json::from_value(json!({
+        "base_url": format!("{}/oauth2/authorize", server.base_url()),
+        "client_id": "fixture",
+        "client_secret": "fixture",
+        "eventsub_hostname": "http://127.0.0.1",
+        "helix_url": server.helix_url(),
+        "id_url": server.id_url(),
+    })).unwrap();
+
+    let token: TwitchOAuthToken = serde_json::from_value(json!({
+        "access_token": "fixture_token",
+        "expires_in": 3600,
+    })).unwrap();
+
+    // The pool is only used when refreshing user tokens.
+    let pool = Arc::new(PgPoolOptions::new().connect_lazy("postgres://localhost/unused").unwrap());
+    TwitchApiClient::new(config, token, TwitchTokenType::App, pool)
+}
+
+struct Receiver {
+    secret: String,
+    // Subscription type and event of every notification that passed the signature check.
+    notifications: Mutex<Vec<(String, Value)>>,
+}
+
+// Stands in for our /twitch/eventsub endpoint.
+async fn receive_eventsub(receiver: web::Data<Arc<Receiver>>, body: web::Bytes, req: HttpRequest) -> HttpResponse {
+    let header = |key: &str| { req.headers().get(key).and_then(|x| { x.to_str().ok() }).unwrap_or("").to_string() };
+    if eventsub::verify_twitch_eventsub_message(
+        &receiver.secret,
+        &header("Twitch-Eventsub-Message-Id"),
+        &header("Twitch-Eventsub-Message-Timestamp"),
+        &body,
+        &header("Twitch-Eventsub-Message-Signature"),
+        Utc::now(),
+    ).is_err() {
+        return HttpResponse::Forbidden().finish();
+    }
+
+    let message: Value = serde_json::from_slice(&body).unwrap();
+    match header("Twitch-Eventsub-Message-Type").as_str() {
+        "webhook_callback_verification" => HttpResponse::Ok().body(message["challenge"].as_str().unwrap().to_string()),
+        "notification" => {
+            receiver.notifications.lock().unwrap().push((
+                message["subscription"]["type"].as_str().unwrap().to_string(),
+                message["event"].clone(),
+            ));
+            HttpResponse::NoContent().finish()
+        },
+        _ => HttpResponse::BadRequest().finish(),
+    }
+}
+
+fn start_receiver(secret: &str) -> (Arc<Receiver>, SocketAddr, ServerHandle) {
+    let receiver = Arc::new(Receiver{
+        secret: secret.to_string(),
+        notifications: Mutex::new(vec![]),
+    });
+
+    let state = receiver.clone();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(state.clone()))
+            .route("/twitch/eventsub", web::post().to(receive_eventsub))
+    })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+    let addr = server.addrs()[0];
+    let server = server.run();
+    let handle = server.handle();
+    tokio::task::spawn(server);
+    (receiver, addr, handle)
+}
+
+fn transport(addr: &SocketAddr) -> EventSubTransport {
+    EventSubTransport{
+        method: String::from("webhook"),
+        callback: format!("http://{}/twitch/eventsub", addr),
+        secret: String::from(EVENTSUB_SECRET),
+    }
+}
+
+fn condition() -> EventSubCondition {
+    EventSubCondition{
+        broadcaster_user_id: String::from(BROADCASTER_ID),
+    }
+}
+
+#[tokio::test]
+async fn test_mock_helix() {
+    let server = TwitchMockServer::start(Some(&common::mock_fixtures("twitch")), "127.0.0.1:0").await.unwrap();
+    let twitch = twitch_client(&server);
+
+    let account = twitch.get_basic_account_info(BROADCASTER_ID).await.unwrap();
+    assert_eq!(account.twitch_name, "SquadOVStreamer");
+    assert!(matches!(twitch.get_basic_account_info("1").await, Err(SquadOvError::NotFound)));
+
+    // Uploads aren't archives.
+    let videos = twitch.get_archive_videos(BROADCASTER_ID).await.unwrap();
+    assert_eq!(videos.len(), 1);
+    assert_eq!(videos[0].stream_id.as_deref(), Some("39000000001"));
+    assert_eq!(videos[0].duration_seconds(), Some(8130));
+
+    let start = Utc.ymd(2022, 7, 30).and_hms(18, 0, 0);
+    for (id, offset) in &[("ClipA", 600), ("ClipB", 7000), ("ClipC", 9000)] {
+        server.add_clip(TwitchClip{
+            id: id.to_string(),
+            url: format!("https://clips.twitch.tv/{}", id),
+            broadcaster_id: String::from(BROADCASTER_ID),
+            video_id: String::from("1500000001"),
+            created_at: start + Duration::seconds(*offset + 30),
+            duration: 30.0,
+            vod_offset: Some(*offset),
+        });
+    }
+
+    // Clips are filtered on when they were made.
+    let (clips, cursor) = twitch.get_clips(BROADCASTER_ID, &start, &(start + Duration::seconds(8130)), None).await.unwrap();
+    assert!(cursor.is_none());
+    assert_eq!(clips.iter().map(|x| { x.id.as_str() }).collect::<Vec<&str>>(), vec!["ClipA", "ClipB"]);
+
+    // The client validates the app token before every request.
+    assert_eq!(server.request_count("GET oauth2/validate"), 4);
+    assert_eq!(server.request_count("GET helix/clips"), 1);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_mock_stream_eventsub() {
+    let (receiver, addr, receiver_handle) = start_receiver(EVENTSUB_SECRET);
+    let server = TwitchMockServer::start(Some(&common::mock_fixtures("twitch")), "127.0.0.1:0").await.unwrap();
+    let twitch = twitch_client(&server);
+
+    twitch.register_eventsub_subscription(TWITCH_STREAM_ONLINE, condition(), transport(&addr)).await.unwrap();
+    twitch.register_eventsub_subscription(TWITCH_STREAM_OFFLINE, condition(), transport(&addr)).await.unwrap();
+    assert!(matches!(twitch.register_eventsub_subscription(TWITCH_STREAM_ONLINE, condition(), transport(&addr)).await, Err(SquadOvError::Duplicate)));
+    assert!(server.subscriptions().iter().all(|x| { x.status == "enabled" }));
+
+    let started = Utc::now() - Duration::hours(1);
+    let deliveries = server.go_online(BROADCASTER_ID, Some("40000000001"), started).await.unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].status, Some(204));
+
+    let ended = Utc::now();
+    server.go_offline(BROADCASTER_ID, ended, true).await.unwrap();
+
+    let notifications = receiver.notifications.lock().unwrap().clone();
+    assert_eq!(notifications.iter().map(|x| { x.0.as_str() }).collect::<Vec<&str>>(), vec![TWITCH_STREAM_ONLINE, TWITCH_STREAM_OFFLINE]);
+
+    let online: TwitchStreamOnlineEvent = serde_json::from_value(notifications[0].1.clone()).unwrap();
+    assert_eq!(online.id, "40000000001");
+    assert_eq!(online.broadcaster_user_name, "SquadOVStreamer");
+    assert_eq!(online.started_at.timestamp(), started.timestamp());
+
+    let offline: TwitchStreamOfflineEvent = serde_json::from_value(notifications[1].1.clone()).unwrap();
+    assert_eq!(offline.broadcaster_user_id, BROADCASTER_ID);
+
+    // Ending the stream leaves an archive behind which is what gets linked to the matches played during the stream.
+    let archive = twitch.get_archive_videos(BROADCASTER_ID).await.unwrap().into_iter().find(|x| {
+        x.stream_id.as_deref() == Some("40000000001")
+    }).unwrap();
+    assert_eq!(archive.duration_seconds(), Some(3600));
+
+    server.add_clip(TwitchClip{
+        id: String::from("LiveClip"),
+        url: String::from("https://clips.twitch.tv/LiveClip"),
+        broadcaster_id: String::from(BROADCASTER_ID),
+        video_id: archive.id.clone(),
+        created_at: started + Duration::seconds(1830),
+        duration: 30.0,
+        vod_offset: Some(1800),
+    });
+    let (clips, _) = twitch.get_clips(BROADCASTER_ID, &started, &ended, None).await.unwrap();
+    assert_eq!(clips.len(), 1);
+
+    let matches = vec![
+        TwitchStreamMatch{
+            match_uuid: Uuid::from_u128(1),
+            user_id: 7,
+            start_time: started + Duration::minutes(20),
+            end_time: started + Duration::minutes(50),
+        },
+        TwitchStreamMatch{
+            match_uuid: Uuid::from_u128(2),
+            user_id: 7,
+            start_time: started - Duration::minutes(30),
+            end_time: started - Duration::minutes(5),
+        },
+    ];
+
+    let links = streams::link_twitch_archive_to_matches("40000000001", &archive, &matches);
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].video_type, TWITCH_LINK_ARCHIVE);
+    assert_eq!((links[0].start_offset_seconds, links[0].end_offset_seconds), (1200, 3000));
+    assert_eq!(links[0].jump_url(), format!("{}?t=0h20m0s", &archive.url));
+
+    let links = streams::link_twitch_clip_to_matches("40000000001", &clips[0], Some(&archive), &matches);
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].video_type, TWITCH_LINK_CLIP);
+    assert_eq!((links[0].start_offset_seconds, links[0].end_offset_seconds), (0, 30));
+
+    server.stop().await;
+    receiver_handle.stop(true).await;
+}
+
+#[tokio::test]
+async fn test_mock_eventsub_wrong_secret() {
+    let (receiver, addr, receiver_handle) = start_receiver("some_other_secret");
+    let server = TwitchMockServer::start(Some(&common::mock_fixtures("twitch")), "127.0.0.1:0").await.unwrap();
+    let twitch = twitch_client(&server);
+
+    // The receiver can't verify the challenge so the subscription never gets enabled and nothing gets sent to it.
+    twitch.register_eventsub_subscription(TWITCH_STREAM_ONLINE, condition(), transport(&addr)).await.unwrap();
+    assert_eq!(server.subscriptions()[0].status, "webhook_callback_verification_failed");
+    assert_eq!(server.deliveries()[0].status, Some(403));
+
+    assert!(server.go_online(BROADCASTER_ID, None, Utc::now()).await.unwrap().is_empty());
+    assert!(receiver.notifications.lock().unwrap().is_empty());
+
+    server.stop().await;
+    receiver_handle.stop(true).await;
+}
@@ -0,0 +1,100 @@
+// Feeds parsed combat log events through the WoW stat report generator and checks the per-player summary
+// that gets stored for the GraphQL encounter stats.
+use squadov_common::{
+    combatlog::CombatLogReportHandler,
+    wow::{
+        WowCombatLogPacket,
+        reports::stats::WowStatReportGenerator,
+    },
+};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+const PARTITION: &str = "wow-stats-fixture";
+const PLAYER: &str = "Player-1234-00000001";
+const HEALER: &str = "Player-1234-00000002";
+const PET: &str = "Pet-0-1234-0-0-000000001";
+const BOSS: &str = "Creature-0-1234-0-0-000000001";
+
+fn start_tm() -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339("2022-08-01T00:00:00Z").unwrap().with_timezone(&Utc)
+}
+
+fn unit(guid: &str) -> Value {
+    json!({
+        "guid": guid,
+        "name": guid,
+        "flags": 0,
+        "raid_flags": 0,
+    })
+}
+
+fn packet(offset_seconds: i64, source: &str, dest: &str, event: Value) -> WowCombatLogPacket {
+    let tm = start_tm() + chrono::Duration::seconds(offset_seconds);
+    serde_json::from_value(json!({
+        "partition_id": PARTITION,
+        "time": tm,
+        "data": {
+            "form": "Parsed",
+            "inner": {
+                "timestamp": tm,
+                "source": unit(source),
+                "dest": unit(dest),
+                "advanced": null,
+                "event": event,
+            },
+        },
+    })).unwrap()
+}
+
+fn damage(amount: i64) -> Value {
+    json!({
+        "type": "DamageDone",
+        "damage": {"type": "SwingDamage"},
+        "amount": amount,
+        "overkill": 0,
+    })
+}
+
+fn heal(amount: i64, overheal: i64) -> Value {
+    json!({
+        "type": "Healing",
+        "spell": {"id": 2061, "name": "Flash Heal", "school": 2},
+        "amount": amount,
+        "overheal": overheal,
+        "absorbed": 0,
+    })
+}
+
+#[test]
+fn test_wow_stat_summary_aggregation() {
+    let mut gen = WowStatReportGenerator::new(String::from(PARTITION), start_tm());
+    gen.update_ownership(&vec![(String::from(PET), String::from(PLAYER))].into_iter().collect::<HashMap<String, String>>());
+
+    for p in &[
+        packet(0, PLAYER, BOSS, damage(1000)),
+        packet(1, PLAYER, BOSS, damage(500)),
+        // Pets count towards their owner.
+        packet(2, PET, BOSS, damage(250)),
+        packet(3, BOSS, PLAYER, damage(800)),
+        // Overhealing doesn't count and can never make the heals negative.
+        packet(4, HEALER, PLAYER, heal(600, 200)),
+        packet(5, HEALER, PLAYER, heal(100, 300)),
+    ] {
+        gen.handle(p).unwrap();
+    }
+
+    let player = gen.unit_summary(PLAYER).unwrap();
+    assert_eq!(player.damage_dealt, 1750);
+    assert_eq!(player.damage_received, 800);
+    assert_eq!(player.heals, 0);
+
+    let healer = gen.unit_summary(HEALER).unwrap();
+    assert_eq!(healer.damage_dealt, 0);
+    assert_eq!(healer.heals, 400);
+
+    // Only players get a summary.
+    assert!(gen.unit_summary(PET).is_none());
+    assert!(gen.unit_summary(BOSS).is_none());
+}
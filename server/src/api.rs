@@ -1,6 +1,7 @@
 pub mod api_service;
 pub mod auth;
 pub mod fusionauth;
+pub mod identity;
 pub mod access;
 pub mod v1;
 pub mod graphql;
@@ -20,7 +21,7 @@ use squadov_common::{
     HalResponse,
     KafkaCredentialKeyPair,
     riot::{
-        api::{RiotApiHandler, RiotApiApplicationInterface, RiotConfig},
+        api::{RiotApiHandler, RiotApiApplicationInterface, RiotConfig, create_riot_api_cache, create_riot_rate_limit_store},
     },
     rabbitmq::{RabbitMqInterface, RabbitMqConfig},
     EmailConfig,
@@ -87,7 +88,7 @@ use squadov_common::{
         rabbitmq::ElasticSearchJobInterface,
     },
     combatlog::interface::CombatLogInterface,
-    stripe::{StripeApiClient, StripeApiConfig},
+    stripe::{StripeApiClient, StripeApiConfig, events::StripeWebhookEventQueue},
     discord::rabbitmq::DiscordTaskProducer,
 };
 use url::Url;
@@ -246,7 +247,9 @@ pub struct CombatLogConfig {
 
 #[derive(Deserialize,Debug,Clone)]
 pub struct ApiConfig {
-    fusionauth: fusionauth::FusionAuthConfig,
+    fusionauth: Option<fusionauth::FusionAuthConfig>,
+    #[serde(default)]
+    pub identity: identity::IdentityConfig,
     pub gcp: squadov_common::GCPConfig,
     pub aws: AWSConfig,
     pub database: DatabaseConfig,
@@ -271,6 +274,11 @@ pub struct ApiConfig {
     pub combatlog: CombatLogConfig,
     pub elasticsearch: ElasticSearchConfig,
     pub stripe: StripeApiConfig,
+    pub social_login: Option<auth::SocialLoginConfig>,
+    #[serde(default)]
+    pub csgo_economy: squadov_common::csgo::economy::CsgoEconomyConfig,
+    #[serde(default)]
+    pub valorant_economy: squadov_common::riot::games::valorant::analytics::ValorantEconomyConfig,
 }
 
 impl CommonConfig for DatabaseConfig {
@@ -291,13 +299,16 @@ impl CommonConfig for DatabaseConfig {
 
 impl CommonConfig for ApiConfig {
     fn read_from_env(&mut self) {
-        self.fusionauth.read_from_env();
+        if let Some(fusionauth) = self.fusionauth.as_mut() {
+            fusionauth.read_from_env();
+        }
+        self.identity.read_from_env();
         self.database.read_from_env();
     }
 }
 
 pub struct ApiClients {
-    pub fusionauth: fusionauth::FusionAuthClient,
+    pub identity: Arc<dyn identity::IdentityProvider + Send + Sync>,
 }
 
 pub struct ApiApplication {
@@ -309,7 +320,7 @@ pub struct ApiApplication {
     speed_check: Arc<StorageManager<Arc<dyn SpeedCheckManager + Send + Sync>>>,
     pub pool: Arc<PgPool>,
     pub heavy_pool: Arc<PgPool>,
-    schema: Arc<graphql::GraphqlSchema>,
+    pub schema: Arc<graphql::GraphqlSchema>,
     pub blob: Arc<StorageManager<Arc<BlobManagementClient>>>,
     pub rso_itf: Arc<RiotApiApplicationInterface>,
     pub valorant_itf: Arc<RiotApiApplicationInterface>,
@@ -333,6 +344,7 @@ pub struct ApiApplication {
     pub cl_itf: Arc<CombatLogInterface>,
     pub rabbitmq: Arc<RabbitMqInterface>,
     pub stripe: Arc<StripeApiClient>,
+    pub stripe_webhooks: Arc<StripeWebhookEventQueue>,
     pub discord: Arc<DiscordTaskProducer>,
 }
 
@@ -487,10 +499,12 @@ impl ApiApplication {
         );
 
         let cl_itf = Arc::new(CombatLogInterface::new(&config.combatlog.bucket, aws.clone()));
-        let rso_api = Arc::new(RiotApiHandler::new(config.riot.rso_api_key.clone(), pool.clone()));
-        let valorant_api = Arc::new(RiotApiHandler::new(config.riot.valorant_api_key.clone(), pool.clone()));
-        let lol_api = Arc::new(RiotApiHandler::new(config.riot.lol_api_key.clone(), pool.clone()));
-        let tft_api = Arc::new(RiotApiHandler::new(config.riot.tft_api_key.clone(), pool.clone()));
+        let riot_cache = create_riot_api_cache(&config.riot.cache, &config.redis, pool.clone()).unwrap();
+        let riot_rate_limits = create_riot_rate_limit_store(&config.riot.rate_limit, &config.redis).unwrap();
+        let rso_api = Arc::new(RiotApiHandler::new(config.riot.rso_api_key.clone(), &config.riot, riot_cache.clone(), riot_rate_limits.clone(), pool.clone()));
+        let valorant_api = Arc::new(RiotApiHandler::new(config.riot.valorant_api_key.clone(), &config.riot, riot_cache.clone(), riot_rate_limits.clone(), pool.clone()));
+        let lol_api = Arc::new(RiotApiHandler::new(config.riot.lol_api_key.clone(), &config.riot, riot_cache.clone(), riot_rate_limits.clone(), pool.clone()));
+        let tft_api = Arc::new(RiotApiHandler::new(config.riot.tft_api_key.clone(), &config.riot, riot_cache.clone(), riot_rate_limits.clone(), pool.clone()));
         let steam_api = Arc::new(SteamApiClient::new(&config.steam));
         let es_api = Arc::new(ElasticSearchClient::new(config.elasticsearch.clone()));
 
@@ -578,10 +592,23 @@ impl ApiApplication {
             }
         }
 
+        let email = Arc::new(EmailClient::new(&config.email));
+        let identity: Arc<dyn identity::IdentityProvider + Send + Sync> = match config.identity.provider {
+            identity::IdentityProviderType::FusionAuth => Arc::new(fusionauth::FusionAuthClient::new(
+                config.fusionauth.clone().expect("FusionAuth config is required when using the FusionAuth identity provider.")
+            )),
+            identity::IdentityProviderType::Local => Arc::new(identity::LocalIdentityProvider::new(
+                config.identity.local.clone().expect("Local identity config is required when using the local identity provider."),
+                &config.squadov.app_url,
+                pool.clone(),
+                email.clone(),
+            )),
+        };
+
         let mut app = ApiApplication{
             config: config.clone(),
             clients: ApiClients{
-                fusionauth: fusionauth::FusionAuthClient::new(config.fusionauth.clone()),
+                identity,
             },
             users: auth::UserManager{},
             session: auth::SessionManager::new(),
@@ -595,7 +622,7 @@ impl ApiApplication {
             valorant_itf,
             lol_itf,
             tft_itf,
-            email: Arc::new(EmailClient::new(&config.email)),
+            email,
             vod_itf,
             csgo_itf,
             steam_itf,
@@ -609,7 +636,7 @@ impl ApiApplication {
             segment: Arc::new(SegmentClient::new(config.segment.clone())),
             twitch_api: Arc::new(TwitchApiClient::new(
                 config.twitch.clone(),
-                oauth::get_oauth_client_credentials_token(&config.twitch.client_id, &config.twitch.client_secret).await.unwrap(),
+                oauth::get_oauth_client_credentials_token(&config.twitch.id_url, &config.twitch.client_id, &config.twitch.client_secret).await.unwrap(),
                 TwitchTokenType::App,
                 pool.clone(),
             )),
@@ -618,6 +645,7 @@ impl ApiApplication {
             cl_itf,
             rabbitmq: rabbitmq.clone(),
             stripe: Arc::new(StripeApiClient::new(&config.stripe)),
+            stripe_webhooks: Arc::new(StripeWebhookEventQueue::new(config.rabbitmq.clone(), rabbitmq.clone())),
             discord: Arc::new(DiscordTaskProducer::new(rabbitmq.clone(), config.rabbitmq.clone())),
         };
 
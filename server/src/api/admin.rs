@@ -1,3 +1,13 @@
 pub mod analytics;
+pub mod features;
+pub mod riot;
+pub mod stripe;
+pub mod storage;
+pub mod twitch;
 
-pub use analytics::*;
\ No newline at end of file
+pub use analytics::*;
+pub use features::*;
+pub use riot::*;
+pub use stripe::*;
+pub use storage::*;
+pub use twitch::*;
@@ -0,0 +1,229 @@
+use actix_web::{web, HttpResponse, HttpRequest};
+use crate::api::{
+    self,
+    v1,
+    auth::SquadOVSession,
+};
+use squadov_common::{
+    SquadOvError,
+    features::{
+        self,
+        db as fdb,
+        FeatureFlagCondition,
+        FeatureFlagDefinition,
+        FeatureFlagRule,
+        FeatureFlagType,
+    },
+};
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+
+#[derive(Deserialize)]
+pub struct FeatureFlagPath {
+    pub flag_key: String,
+}
+
+#[derive(Deserialize)]
+pub struct FeatureFlagRulePath {
+    pub flag_key: String,
+    pub rule_id: i64,
+}
+
+#[derive(Deserialize)]
+pub struct FeatureFlagOverridePath {
+    pub flag_key: String,
+    pub user_id: i64,
+}
+
+#[derive(Deserialize)]
+pub struct FeatureFlagUserPath {
+    pub user_id: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct FeatureFlagDefinitionInput {
+    pub flag_key: String,
+    pub flag_type: FeatureFlagType,
+    pub default_value: serde_json::Value,
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct FeatureFlagDefinitionUpdateInput {
+    pub default_value: serde_json::Value,
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct FeatureFlagRuleInput {
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub conditions: Vec<FeatureFlagCondition>,
+    pub value: serde_json::Value,
+    #[serde(default="default_rule_enabled")]
+    pub enabled: bool,
+}
+
+fn default_rule_enabled() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+pub struct FeatureFlagOverrideInput {
+    pub value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct FeatureFlagAuditQuery {
+    pub flag_key: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct FeatureFlagWithRules {
+    #[serde(flatten)]
+    pub definition: FeatureFlagDefinition,
+    pub rules: Vec<FeatureFlagRule>,
+}
+
+impl FeatureFlagRuleInput {
+    fn validate(&self, definition: &FeatureFlagDefinition) -> Result<(), SquadOvError> {
+        definition.flag_type.validate(&self.value)?;
+        for c in &self.conditions {
+            c.validate()?;
+        }
+        Ok(())
+    }
+}
+
+pub async fn list_feature_flags_handler(app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, SquadOvError> {
+    let rules = fdb::list_feature_flag_rules(&*app.pool, None).await?;
+    Ok(HttpResponse::Ok().json(
+        fdb::list_feature_flag_definitions(&*app.pool).await?.into_iter().map(|definition| {
+            FeatureFlagWithRules{
+                rules: rules.iter().filter(|x| { x.flag_key == definition.flag_key }).cloned().collect(),
+                definition,
+            }
+        }).collect::<Vec<FeatureFlagWithRules>>()
+    ))
+}
+
+pub async fn get_feature_flag_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<FeatureFlagPath>) -> Result<HttpResponse, SquadOvError> {
+    Ok(HttpResponse::Ok().json(FeatureFlagWithRules{
+        definition: fdb::get_feature_flag_definition(&*app.pool, &path.flag_key).await?,
+        rules: fdb::list_feature_flag_rules(&*app.pool, Some(&path.flag_key)).await?,
+    }))
+}
+
+pub async fn create_feature_flag_handler(app : web::Data<Arc<api::ApiApplication>>, data: web::Json<FeatureFlagDefinitionInput>, session: SquadOVSession) -> Result<HttpResponse, SquadOvError> {
+    features::validate_feature_flag_key(&data.flag_key)?;
+    data.flag_type.validate(&data.default_value)?;
+
+    let mut tx = app.pool.begin().await?;
+    let definition = fdb::create_feature_flag_definition(&mut tx, &data.flag_key, data.flag_type, &data.default_value, data.description.as_deref()).await?;
+    fdb::add_feature_flag_audit_entry(&mut tx, &definition.flag_key, session.user.id, "create", None, Some(serde_json::to_value(&definition)?)).await?;
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(definition))
+}
+
+pub async fn update_feature_flag_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<FeatureFlagPath>, data: web::Json<FeatureFlagDefinitionUpdateInput>, session: SquadOVSession) -> Result<HttpResponse, SquadOvError> {
+    let mut tx = app.pool.begin().await?;
+    let old = fdb::get_feature_flag_definition(&mut tx, &path.flag_key).await?;
+    old.flag_type.validate(&data.default_value)?;
+
+    let new = fdb::update_feature_flag_definition(&mut tx, &path.flag_key, &data.default_value, data.description.as_deref()).await?;
+    fdb::add_feature_flag_audit_entry(&mut tx, &path.flag_key, session.user.id, "update", Some(serde_json::to_value(&old)?), Some(serde_json::to_value(&new)?)).await?;
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(new))
+}
+
+pub async fn delete_feature_flag_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<FeatureFlagPath>, session: SquadOVSession) -> Result<HttpResponse, SquadOvError> {
+    let mut tx = app.pool.begin().await?;
+    let old = fdb::get_feature_flag_definition(&mut tx, &path.flag_key).await?;
+    fdb::delete_feature_flag_definition(&mut tx, &path.flag_key).await?;
+    fdb::add_feature_flag_audit_entry(&mut tx, &path.flag_key, session.user.id, "delete", Some(serde_json::to_value(&old)?), None).await?;
+    tx.commit().await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn create_feature_flag_rule_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<FeatureFlagPath>, data: web::Json<FeatureFlagRuleInput>, session: SquadOVSession) -> Result<HttpResponse, SquadOvError> {
+    let mut tx = app.pool.begin().await?;
+    let definition = fdb::get_feature_flag_definition(&mut tx, &path.flag_key).await?;
+    data.validate(&definition)?;
+
+    let rule = fdb::create_feature_flag_rule(&mut tx, &path.flag_key, data.priority, &data.conditions, &data.value, data.enabled).await?;
+    fdb::add_feature_flag_audit_entry(&mut tx, &path.flag_key, session.user.id, "create_rule", None, Some(serde_json::to_value(&rule)?)).await?;
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+pub async fn update_feature_flag_rule_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<FeatureFlagRulePath>, data: web::Json<FeatureFlagRuleInput>, session: SquadOVSession) -> Result<HttpResponse, SquadOvError> {
+    let mut tx = app.pool.begin().await?;
+    let definition = fdb::get_feature_flag_definition(&mut tx, &path.flag_key).await?;
+    data.validate(&definition)?;
+
+    let old = fdb::get_feature_flag_rule(&mut tx, &path.flag_key, path.rule_id).await?;
+    let new = fdb::update_feature_flag_rule(&mut tx, &path.flag_key, path.rule_id, data.priority, &data.conditions, &data.value, data.enabled).await?;
+    fdb::add_feature_flag_audit_entry(&mut tx, &path.flag_key, session.user.id, "update_rule", Some(serde_json::to_value(&old)?), Some(serde_json::to_value(&new)?)).await?;
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(new))
+}
+
+pub async fn delete_feature_flag_rule_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<FeatureFlagRulePath>, session: SquadOVSession) -> Result<HttpResponse, SquadOvError> {
+    let mut tx = app.pool.begin().await?;
+    let old = fdb::get_feature_flag_rule(&mut tx, &path.flag_key, path.rule_id).await?;
+    fdb::delete_feature_flag_rule(&mut tx, &path.flag_key, path.rule_id).await?;
+    fdb::add_feature_flag_audit_entry(&mut tx, &path.flag_key, session.user.id, "delete_rule", Some(serde_json::to_value(&old)?), None).await?;
+    tx.commit().await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn list_feature_flag_overrides_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<FeatureFlagPath>) -> Result<HttpResponse, SquadOvError> {
+    Ok(HttpResponse::Ok().json(fdb::list_feature_flag_overrides(&*app.pool, &path.flag_key).await?))
+}
+
+pub async fn set_feature_flag_override_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<FeatureFlagOverridePath>, data: web::Json<FeatureFlagOverrideInput>, session: SquadOVSession) -> Result<HttpResponse, SquadOvError> {
+    let mut tx = app.pool.begin().await?;
+    let definition = fdb::get_feature_flag_definition(&mut tx, &path.flag_key).await?;
+    definition.flag_type.validate(&data.value)?;
+
+    let old = fdb::get_feature_flag_override(&mut tx, &path.flag_key, path.user_id).await?;
+    fdb::set_feature_flag_override(&mut tx, &path.flag_key, path.user_id, &data.value).await?;
+    fdb::add_feature_flag_audit_entry(
+        &mut tx,
+        &path.flag_key,
+        session.user.id,
+        "set_override",
+        old.map(|x| { serde_json::json!({"userId": path.user_id, "value": x}) }),
+        Some(serde_json::json!({"userId": path.user_id, "value": &data.value})),
+    ).await?;
+    tx.commit().await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn delete_feature_flag_override_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<FeatureFlagOverridePath>, session: SquadOVSession) -> Result<HttpResponse, SquadOvError> {
+    let mut tx = app.pool.begin().await?;
+    let old = fdb::get_feature_flag_override(&mut tx, &path.flag_key, path.user_id).await?.ok_or(SquadOvError::NotFound)?;
+    fdb::delete_feature_flag_override(&mut tx, &path.flag_key, path.user_id).await?;
+    fdb::add_feature_flag_audit_entry(&mut tx, &path.flag_key, session.user.id, "delete_override", Some(serde_json::json!({"userId": path.user_id, "value": old})), None).await?;
+    tx.commit().await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn list_feature_flag_audit_handler(app : web::Data<Arc<api::ApiApplication>>, page: web::Query<api::PaginationParameters>, query: web::Query<FeatureFlagAuditQuery>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let data = fdb::list_feature_flag_audit_entries(&*app.pool, query.flag_key.as_deref(), page.start, page.end).await?;
+    let expected_total = page.end - page.start;
+    let got_total = data.len() as i64;
+    Ok(HttpResponse::Ok().json(api::construct_hal_pagination_response(data, &req, &page, expected_total == got_total)?))
+}
+
+// Shows where each of the user's flags came from (default, rule, or override) to help debug targeting.
+pub async fn evaluate_user_feature_flags_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<FeatureFlagUserPath>, query: web::Query<v1::FeatureFlagQuery>) -> Result<HttpResponse, SquadOvError> {
+    let base = v1::get_feature_flags(&*app.pool, path.user_id).await?;
+    Ok(HttpResponse::Ok().json(v1::evaluate_feature_flags_for_user(&*app.pool, path.user_id, base.early_access, query.client_version.as_deref()).await?))
+}
@@ -0,0 +1,27 @@
+use actix_web::{web, HttpResponse};
+use crate::api;
+use squadov_common::{
+    SquadOvError,
+    riot::api::RiotRateLimitSnapshot,
+};
+use std::sync::Arc;
+use serde::Serialize;
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct RiotRateLimitStatus {
+    rso: RiotRateLimitSnapshot,
+    valorant: RiotRateLimitSnapshot,
+    lol: RiotRateLimitSnapshot,
+    tft: RiotRateLimitSnapshot,
+}
+
+// Limits learned from Riot and limiter counters for this server only.
+pub async fn get_riot_rate_limits_handler(app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, SquadOvError> {
+    Ok(HttpResponse::Ok().json(RiotRateLimitStatus{
+        rso: app.rso_itf.rate_limit_snapshot(),
+        valorant: app.valorant_itf.rate_limit_snapshot(),
+        lol: app.lol_itf.rate_limit_snapshot(),
+        tft: app.tft_itf.rate_limit_snapshot(),
+    }))
+}
@@ -0,0 +1,8 @@
+use actix_web::{web, HttpResponse};
+use crate::api::{self, v1};
+use squadov_common::SquadOvError;
+use std::sync::Arc;
+
+pub async fn reconcile_storage_handler(app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, SquadOvError> {
+    Ok(HttpResponse::Ok().json(v1::reconcile_storage_usage(app.as_ref().clone()).await?))
+}
@@ -0,0 +1,70 @@
+use actix_web::{web, HttpResponse, HttpRequest};
+use crate::api::{self, v1};
+use squadov_common::{
+    SquadOvError,
+    stripe::events::{
+        self,
+        StripeWebhookEventStatus,
+    },
+};
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct StripeEventsQuery {
+    pub status: Option<StripeWebhookEventStatus>,
+    pub event_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct StripeEventInput {
+    pub event_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct StripeReplayInput {
+    #[serde(default)]
+    pub event_ids: Vec<String>,
+    // Also replay every event that ran out of attempts.
+    #[serde(default)]
+    pub failed: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct StripeReplayResponse {
+    pub replayed: Vec<String>,
+}
+
+pub async fn list_stripe_webhook_events_handler(app : web::Data<Arc<api::ApiApplication>>, page: web::Query<api::PaginationParameters>, query: web::Query<StripeEventsQuery>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let data = events::list_stripe_webhook_events(&*app.pool, query.status, query.event_type.as_deref(), page.start, page.end).await?;
+    let expected_total = page.end - page.start;
+    let got_total = data.len() as i64;
+    Ok(HttpResponse::Ok().json(api::construct_hal_pagination_response(data, &req, &page, expected_total == got_total)?))
+}
+
+pub async fn get_stripe_webhook_event_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<StripeEventInput>) -> Result<HttpResponse, SquadOvError> {
+    Ok(HttpResponse::Ok().json(events::get_stripe_webhook_event(&*app.pool, &path.event_id).await?))
+}
+
+pub async fn replay_stripe_webhook_events_handler(app : web::Data<Arc<api::ApiApplication>>, data: web::Json<StripeReplayInput>) -> Result<HttpResponse, SquadOvError> {
+    let mut event_ids = data.event_ids.clone();
+    if data.failed {
+        event_ids.extend(events::get_exhausted_stripe_webhook_event_ids(&*app.pool).await?);
+    }
+
+    // Replaying an event that already succeeded is allowed on purpose (e.g. after fixing a bug in how it was handled).
+    let replayed = events::reset_stripe_webhook_events(&*app.pool, &event_ids).await?;
+    for id in &replayed {
+        app.stripe_webhooks.request_process_event(id).await?;
+    }
+    Ok(HttpResponse::Ok().json(StripeReplayResponse{
+        replayed,
+    }))
+}
+
+pub async fn reconcile_stripe_handler(app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, SquadOvError> {
+    Ok(HttpResponse::Ok().json(v1::reconcile_stripe_customers(app.as_ref().clone()).await?))
+}
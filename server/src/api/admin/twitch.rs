@@ -0,0 +1,36 @@
+use actix_web::{web, HttpResponse};
+use crate::api;
+use squadov_common::{
+    SquadOvError,
+    twitch::streams,
+};
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+
+#[derive(Deserialize)]
+pub struct TwitchStreamPath {
+    pub stream_id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct TwitchEventSubRegistration {
+    pub registered: i64,
+}
+
+pub async fn register_twitch_stream_eventsubs_handler(app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, SquadOvError> {
+    Ok(HttpResponse::Ok().json(TwitchEventSubRegistration{
+        registered: app.register_missing_twitch_stream_eventsubs().await?,
+    }))
+}
+
+// Links the stream's VOD/clips to matches again (e.g. to pick up clips that were made after the stream ended).
+pub async fn relink_twitch_stream_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<TwitchStreamPath>) -> Result<HttpResponse, SquadOvError> {
+    let stream = streams::get_twitch_stream(&*app.pool, &path.stream_id).await?;
+    if stream.ended_tm.is_none() {
+        return Err(SquadOvError::BadRequest);
+    }
+
+    app.twitch_itf.request_link_stream_vods(&stream.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -42,6 +42,51 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                         .route("/sync/user/{user_id}", web::post().to(v1::sync_user_subscription_handler))
                         .route("/sync/customer/{customer_id}", web::post().to(v1::sync_customer_subscription_handler))
                 )
+                .service(
+                    web::scope("/riot")
+                        .route("/ratelimits", web::get().to(admin::get_riot_rate_limits_handler))
+                )
+                .service(
+                    web::scope("/stripe")
+                        .route("/events", web::get().to(admin::list_stripe_webhook_events_handler))
+                        .route("/events/replay", web::post().to(admin::replay_stripe_webhook_events_handler))
+                        .route("/events/{event_id}", web::get().to(admin::get_stripe_webhook_event_handler))
+                        .route("/reconcile", web::post().to(admin::reconcile_stripe_handler))
+                )
+                .service(
+                    web::scope("/storage")
+                        .route("/reconcile", web::post().to(admin::reconcile_storage_handler))
+                )
+                .service(
+                    web::scope("/twitch")
+                        .route("/eventsub/streams", web::post().to(admin::register_twitch_stream_eventsubs_handler))
+                        .route("/streams/{stream_id}/link", web::post().to(admin::relink_twitch_stream_handler))
+                )
+                .service(
+                    web::scope("/features")
+                        .route("", web::get().to(admin::list_feature_flags_handler))
+                        .route("", web::post().to(admin::create_feature_flag_handler))
+                        .route("/audit", web::get().to(admin::list_feature_flag_audit_handler))
+                        .route("/evaluate/{user_id}", web::get().to(admin::evaluate_user_feature_flags_handler))
+                        .service(
+                            web::scope("/flag/{flag_key}")
+                                .route("", web::get().to(admin::get_feature_flag_handler))
+                                .route("", web::put().to(admin::update_feature_flag_handler))
+                                .route("", web::delete().to(admin::delete_feature_flag_handler))
+                                .route("/rules", web::post().to(admin::create_feature_flag_rule_handler))
+                                .service(
+                                    web::resource("/rules/{rule_id}")
+                                        .route(web::put().to(admin::update_feature_flag_rule_handler))
+                                        .route(web::delete().to(admin::delete_feature_flag_rule_handler))
+                                )
+                                .route("/overrides", web::get().to(admin::list_feature_flag_overrides_handler))
+                                .service(
+                                    web::resource("/overrides/{user_id}")
+                                        .route(web::put().to(admin::set_feature_flag_override_handler))
+                                        .route(web::delete().to(admin::delete_feature_flag_override_handler))
+                                )
+                        )
+                )
         )
         .service(
             web::scope("/webhooks")
@@ -62,6 +107,13 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                     web::resource("/session/heartbeat")
                         .route(web::post().to(v1::refresh_user_session_handler))
                 )
+                .service(
+                    web::scope("/social")
+                        .route("/register", web::post().to(auth::social_registration_handler))
+                        .route("/register/verify", web::post().to(auth::verify_social_registration_handler))
+                        .route("/{provider}", web::get().to(auth::get_social_login_url_handler))
+                        .route("/{provider}", web::post().to(auth::handle_social_login_callback_handler))
+                )
                 .service(
                     web::scope("/oauth")
                         .route("/riot", web::post().to(v1::handle_riot_oauth_callback_handler))
@@ -82,6 +134,7 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
         .service(
             web::scope("/ws")
                 .route("/status/{user_id}", web::get().to(v1::get_user_status_handler))
+                .route("/graphql", web::get().to(graphql::graphql_ws_handler))
         )
         .service(
             // TODO: More generic signature verification here?
@@ -249,6 +302,7 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                             web::scope("/events")
                                 .route("", web::get().to(v1::get_accessible_match_custom_events_handler))
                         )
+                        .route("/twitch", web::get().to(v1::get_match_twitch_links_handler))
                         .service(
                             web::scope("")
                                 .wrap(access::ApiAccess::new(
@@ -306,12 +360,25 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                         .route("", web::delete().to(auth::remove_2fa_handler))
                                         .route("", web::post().to(auth::enable_2fa_handler))
                                 )
+                                .service(
+                                    web::scope("/logins")
+                                        .route("", web::get().to(auth::get_my_login_identities_handler))
+                                        .route("/link/{provider}", web::get().to(auth::get_link_login_identity_url_handler))
+                                        .route("/{identity_id}", web::delete().to(auth::delete_login_identity_handler))
+                                )
+                                .service(
+                                    web::scope("/sessions")
+                                        .route("", web::get().to(auth::list_my_login_sessions_handler))
+                                        .route("", web::delete().to(auth::revoke_other_login_sessions_handler))
+                                        .route("/{login_session_id}", web::delete().to(auth::revoke_my_login_session_handler))
+                                )
                                 .service(
                                     web::scope("/accounts")
                                         .route("", web::get().to(v1::get_all_my_linked_accounts_handler))
                                         .service(
                                             web::scope("/twitch")
                                                 .route("", web::get().to(v1::get_my_linked_twitch_account_handler))
+                                                .route("/live", web::get().to(v1::get_live_squadmate_streams_handler))
                                         )
                                         .service(
                                             web::scope("/discord")
@@ -329,11 +396,23 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                 .service(
                                     web::scope("/discover")
                                         .route("/squads", web::get().to(v1::get_user_discover_squads_handler))
+                                        .route("/friends", web::get().to(v1::get_user_friend_suggestions_handler))
+                                        .service(
+                                            web::resource("/friends/settings")
+                                                .route(web::get().to(v1::get_user_friend_suggestion_settings_handler))
+                                                .route(web::post().to(v1::edit_user_friend_suggestion_settings_handler))
+                                        )
                                 )
                                 .service(
                                     web::scope("/analytics")
                                         .route("/event", web::post().to(v1::mark_user_analytics_event_handler))
                                         .route("/vod/{video_uuid}", web::post().to(v1::create_user_vod_watch_analytics_handler))
+                                        .route("/teammates", web::post().to(v1::get_user_teammate_analytics_handler))
+                                        .service(
+                                            web::resource("/teammates/settings")
+                                                .route(web::get().to(v1::get_user_teammate_analytics_settings_handler))
+                                                .route(web::post().to(v1::edit_user_teammate_analytics_settings_handler))
+                                        )
                                 )
                                 .service(
                                     web::scope("/events")
@@ -353,7 +432,14 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                         .route("/checkout", web::get().to(v1::start_subscription_checkout_handler))
                                         .route("/manage", web::get().to(v1::start_subscription_manage_handler))
                                         .route("/tier", web::get().to(v1::get_user_tier_handler))
+                                        .route("/entitlements", web::get().to(v1::get_user_sub_entitlements_handler))
+                                        .service(
+                                            web::scope("/gift")
+                                                .route("", web::get().to(v1::list_gift_subscriptions_handler))
+                                                .route("/checkout", web::get().to(v1::start_gift_subscription_checkout_handler))
+                                        )
                                 )
+                                .route("/storage", web::get().to(v1::get_user_storage_usage_handler))
                         )
                         .service(
                             web::scope("/{user_id}")
@@ -481,6 +567,14 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                             }),
                                         ))
                                         .route("", web::get().to(v1::get_lol_match_handler))
+                                        .service(
+                                            web::scope("/timeline")
+                                                .route("/diffs", web::get().to(v1::get_lol_match_team_diffs_handler))
+                                                .route("/objectives", web::get().to(v1::get_lol_match_objective_timeline_handler))
+                                                .route("/lanes", web::get().to(v1::get_lol_match_lane_phase_handler))
+                                                .route("/kp", web::get().to(v1::get_lol_match_kill_participation_handler))
+                                                .route("/wards", web::get().to(v1::get_lol_match_ward_heatmap_handler))
+                                        )
                                 )
                         )
                         .service(
@@ -507,6 +601,7 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                             }),
                                         ))
                                         .route("/matches", web::post().to(v1::list_lol_matches_for_user_handler))
+                                        .route("/stats", web::post().to(v1::get_lol_player_stats_summary_handler))
                                 )
                         )
                 )
@@ -553,6 +648,7 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                             }),
                                         ))
                                         .route("/matches", web::post().to(v1::list_tft_matches_for_user_handler))
+                                        .route("/stats", web::post().to(v1::get_tft_player_stats_summary_handler))
                                 )
                         )
                 )
@@ -591,6 +687,10 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                             web::resource("/stats")
                                                 .route(web::get().to(v1::get_player_stats_summary_handler))
                                         )
+                                        .service(
+                                            web::resource("/rounds")
+                                                .route(web::get().to(v1::get_valorant_player_round_analytics_handler))
+                                        )
                                 )
                                 .route("/backfill", web::post().to(v1::request_valorant_match_backfill_handler))
                         )
@@ -611,6 +711,10 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                     web::resource("/metadata/{puuid}")
                                         .route(web::get().to(v1::get_valorant_player_match_metadata_handler))
                                 )
+                                .service(
+                                    web::resource("/rounds")
+                                        .route(web::get().to(v1::get_valorant_match_round_analytics_handler))
+                                )
                                 .route("/vods", web::get().to(v1::get_valorant_match_user_accessible_vod_handler))
                         )
                 )
@@ -670,6 +774,11 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                     web::scope("/match")
                                         .route("", web::post().to(v1::create_hearthstone_match_handler))
                                         .route("", web::post().to(v1::list_hearthstone_matches_for_user_handler))
+                                        .service(
+                                            web::scope("/import")
+                                                .app_data(web::PayloadConfig::new(5 * 1024 * 1024))
+                                                .route("", web::post().to(v1::import_hearthstone_match_hsreplay_handler))
+                                        )
                                         .service(
                                             web::scope("/{match_uuid}")
                                                 .route("", web::post().to(v1::upload_hearthstone_logs_handler))
@@ -695,6 +804,8 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                                         ))
                                                         .route("", web::get().to(v1::get_hearthstone_match_handler))
                                                         .route("/logs", web::get().to(v1::get_hearthstone_match_logs_handler))
+                                                        .route("/hsreplay", web::get().to(v1::export_hearthstone_match_hsreplay_handler))
+                                                        .route("/battlegrounds", web::get().to(v1::get_hearthstone_battlegrounds_match_handler))
                                                 )
                                         )
                                 )
@@ -710,6 +821,31 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                                 .route("/deck", web::post().to(v1::create_finished_arena_draft_deck_handler))
                                         )
                                 )
+                                .service(
+                                    web::scope("/decks/{deck_id}")
+                                        // Deck stats are aggregated over all of the user's matches (including ones that aren't shared)
+                                        // so only the deck's owner gets to see them.
+                                        .wrap(access::ApiAccess::new(
+                                            Box::new(access::UserSpecificAccessChecker{
+                                                obtainer: access::UserIdPathSetObtainer{
+                                                    key: "user_id"
+                                                },
+                                            })
+                                        ))
+                                        .route("/analytics", web::get().to(v1::get_hearthstone_deck_analytics_handler))
+                                )
+                                .service(
+                                    web::scope("/battlegrounds")
+                                        // Same as the deck stats, these are aggregated over all of the user's matches.
+                                        .wrap(access::ApiAccess::new(
+                                            Box::new(access::UserSpecificAccessChecker{
+                                                obtainer: access::UserIdPathSetObtainer{
+                                                    key: "user_id"
+                                                },
+                                            })
+                                        ))
+                                        .route("/analytics", web::get().to(v1::get_hearthstone_battlegrounds_analytics_handler))
+                                )
                                 .service(
                                     web::scope("/duels")
                                         .route("", web::get().to(v1::list_duel_runs_for_user_handler))
@@ -908,8 +1044,33 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                                     })
                                                 ))
                                                 .route("", web::get().to(v1::get_csgo_match_handler))
+                                                .route("/rounds/{round_num}/track", web::get().to(v1::get_csgo_round_track_handler))
+                                                .route("/utility", web::get().to(v1::get_csgo_match_utility_handler))
+                                                .route("/economy", web::get().to(v1::get_csgo_match_economy_handler))
                                         )
                                 )
+                                .service(
+                                    web::resource("/heatmap")
+                                        .wrap(access::ApiAccess::new(
+                                            Box::new(access::UserSpecificAccessChecker{
+                                                obtainer: access::UserIdPathSetObtainer{
+                                                    key: "user_id"
+                                                },
+                                            }),
+                                        ))
+                                        .route(web::get().to(v1::get_csgo_heatmap_handler))
+                                )
+                                .service(
+                                    web::resource("/economy")
+                                        .wrap(access::ApiAccess::new(
+                                            Box::new(access::UserSpecificAccessChecker{
+                                                obtainer: access::UserIdPathSetObtainer{
+                                                    key: "user_id"
+                                                },
+                                            }),
+                                        ))
+                                        .route(web::get().to(v1::get_csgo_user_economy_handler))
+                                )
                         )
                 )
                 .service(
@@ -1148,6 +1309,15 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                         )
                                         .route("/share", web::post().to(v1::update_squad_share_settings_handler))
                                         .route("/content/{video_uuid}", web::delete().to(v1::remove_content_from_squad_handler))
+                                        .service(
+                                            web::scope("/subscription")
+                                                .route("/checkout", web::get().to(v1::start_team_subscription_checkout_handler))
+                                                .service(
+                                                    web::resource("/{team_subscription_id}/seats/{user_id}")
+                                                        .route(web::post().to(v1::assign_team_subscription_seat_handler))
+                                                        .route(web::delete().to(v1::remove_team_subscription_seat_handler))
+                                                )
+                                        )
                                 )
                                 .service(
                                     web::scope("/invite/{invite_uuid}")
@@ -1181,6 +1351,7 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                             web::scope("/invite")
                                                 .route("", web::post().to(v1::create_squad_invite_handler))
                                                 .route("", web::get().to(v1::get_all_squad_invites_handler))
+                                                .route("/suggestions", web::post().to(v1::create_squad_invite_from_suggestions_handler))
                                         )
                                         .service(
                                             web::scope("/membership")
@@ -1188,6 +1359,8 @@ pub fn create_service(graphql_debug: bool) -> impl HttpServiceFactory {
                                                 .route("", web::get().to(v1::get_all_squad_user_memberships_handler))
                                         )
                                         .route("/share", web::get().to(v1::get_squad_share_settings_handler))
+                                        .route("/subscription", web::get().to(v1::list_team_subscriptions_handler))
+                                        .route("/storage", web::get().to(v1::get_squad_storage_usage_handler))
                                 )
                         )
                 )
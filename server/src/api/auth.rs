@@ -6,6 +6,8 @@ mod verify_email;
 mod logout;
 mod session;
 mod mfa;
+mod social;
+mod active_sessions;
 
 pub use user::*;
 pub use login::*;
@@ -15,6 +17,8 @@ pub use verify_email::*;
 pub use logout::*;
 pub use session::*;
 pub use mfa::*;
+pub use social::*;
+pub use active_sessions::*;
 
 use squadov_common::SquadOvError;
 use std::sync::Arc;
@@ -27,7 +31,7 @@ use actix_web::{dev::ServiceRequest, dev::ServiceResponse, Error, web, HttpMessa
 use futures::future::{ok, Ready};
 use futures::Future;
 use chrono::Utc;
-use crate::api::v1::get_feature_flags;
+use crate::api::v1::get_evaluated_feature_flags;
 
 /// Session validation middleware.
 /// 
@@ -108,7 +112,7 @@ where
                     let user = app.users.get_stored_user_from_id(access_token.user_id.unwrap_or(-1), &*app.pool).await?.ok_or(SquadOvError::NotFound)?;
                     Some(SquadOVSession{
                         session_id: String::new(),
-                        features: Some(get_feature_flags(&*app.pool, user.id).await?),
+                        features: Some(get_evaluated_feature_flags(&*app.pool, user.id, None).await?.flags),
                         // We do want this to fail if the access token's user id is not set. It's legacy behavior what can you do.
                         user,
                         access_token: String::new(),
@@ -124,7 +128,7 @@ where
                 let user = app.users.get_stored_user_from_uuid(&share_token.user_uuid, &*app.pool).await?.ok_or(SquadOvError::NotFound)?;
                 Some(SquadOVSession{
                     session_id: String::new(),
-                    features: Some(get_feature_flags(&*app.pool, user.id).await?),
+                    features: Some(get_evaluated_feature_flags(&*app.pool, user.id, None).await?.flags),
                     user,
                     access_token: String::new(),
                     refresh_token: String::new(),
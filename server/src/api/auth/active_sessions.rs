@@ -0,0 +1,576 @@
+use actix_web::{web, HttpRequest, HttpResponse, HttpMessage};
+use actix_web::http::header::Header;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres, Transaction};
+use crate::api::{
+    self,
+    auth::{SquadOVSession, SquadOVUser, SquadOvMachineId},
+};
+use squadov_common::{
+    SquadOvError,
+    EmailTemplate,
+    EmailUser,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Information about the device a user is logging in from.
+#[derive(Debug, Clone, Default)]
+pub struct SessionDeviceInfo {
+    pub machine_id: Option<String>,
+    pub ip_addr: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl SessionDeviceInfo {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let conn = req.connection_info();
+        Self {
+            machine_id: SquadOvMachineId::parse(req).ok().map(|x| { x.id }),
+            ip_addr: conn.realip_remote_addr().map(|x| {
+                // The remote address may include the port.
+                x.parse::<std::net::SocketAddr>().map(|y| { y.ip().to_string() }).unwrap_or(x.to_string())
+            }),
+            user_agent: req.headers().get("user-agent").map(|x| { x.to_str().ok() }).flatten().map(|x| { x.to_string() }),
+        }
+    }
+}
+
+/// A login session persists across session refreshes (each refresh creates a new session ID) and is what
+/// the user sees and can revoke.
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct UserLoginSession {
+    pub id: Uuid,
+    pub machine_id: Option<String>,
+    pub ip_addr: Option<String>,
+    pub user_agent: Option<String>,
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub create_tm: DateTime<Utc>,
+    pub last_used_tm: DateTime<Utc>,
+    pub current: bool,
+}
+
+async fn is_known_device_for_user<'a, T>(ex: T, user_id: i64, device: &SessionDeviceInfo) -> Result<bool, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    // The desktop client always sends us a machine ID. The web app doesn't so the best we can do is check the IP and user agent.
+    Ok(
+        sqlx::query!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM squadov.user_login_sessions
+                WHERE user_id = $1
+                    AND (
+                        ($2::VARCHAR IS NOT NULL AND machine_id = $2)
+                        OR ($2::VARCHAR IS NULL AND ip_addr = $3 AND user_agent = $4)
+                    )
+            ) AS "exists!"
+            "#,
+            user_id,
+            device.machine_id,
+            device.ip_addr,
+            device.user_agent,
+        )
+            .fetch_one(ex)
+            .await?
+            .exists
+    )
+}
+
+async fn user_has_login_sessions<'a, T>(ex: T, user_id: i64) -> Result<bool, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM squadov.user_login_sessions
+                WHERE user_id = $1
+            ) AS "exists!"
+            "#,
+            user_id,
+        )
+            .fetch_one(ex)
+            .await?
+            .exists
+    )
+}
+
+pub async fn get_login_session_id_for_session<'a, T>(ex: T, session_id: &str) -> Result<Option<Uuid>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query!(
+            "
+            SELECT login_session_id
+            FROM squadov.user_sessions
+            WHERE id = $1
+            ",
+            session_id,
+        )
+            .fetch_optional(ex)
+            .await?
+            .map(|x| { x.login_session_id })
+            .flatten()
+    )
+}
+
+pub async fn list_login_sessions_for_user<'a, T>(ex: T, user_id: i64, current: Option<&Uuid>) -> Result<Vec<UserLoginSession>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query!(
+            "
+            SELECT
+                id,
+                machine_id,
+                ip_addr,
+                user_agent,
+                city,
+                country,
+                create_tm,
+                last_used_tm
+            FROM squadov.user_login_sessions AS uls
+            WHERE uls.user_id = $1
+                AND EXISTS (
+                    SELECT 1
+                    FROM squadov.user_sessions AS us
+                    WHERE us.login_session_id = uls.id
+                        AND (us.expiration_tm IS NULL OR us.expiration_tm > NOW())
+                )
+            ORDER BY last_used_tm DESC
+            ",
+            user_id,
+        )
+            .fetch_all(ex)
+            .await?
+            .into_iter()
+            .map(|x| {
+                UserLoginSession{
+                    current: Some(&x.id) == current,
+                    id: x.id,
+                    machine_id: x.machine_id,
+                    ip_addr: x.ip_addr,
+                    user_agent: x.user_agent,
+                    city: x.city,
+                    country: x.country,
+                    create_tm: x.create_tm,
+                    last_used_tm: x.last_used_tm,
+                }
+            })
+            .collect()
+    )
+}
+
+/// Creates a new login session for the given (newly created) session.
+pub async fn attach_new_login_session(ex: &mut Transaction<'_, Postgres>, user_id: i64, session_id: &str, device: &SessionDeviceInfo, is_new_device: bool) -> Result<Uuid, SquadOvError> {
+    let login_session_id = Uuid::new_v4();
+    sqlx::query!(
+        "
+        INSERT INTO squadov.user_login_sessions (
+            id,
+            user_id,
+            machine_id,
+            ip_addr,
+            user_agent,
+            create_tm,
+            last_used_tm,
+            location_resolved,
+            notify_new_device
+        ) VALUES (
+            $1,
+            $2,
+            $3,
+            $4,
+            $5,
+            NOW(),
+            NOW(),
+            FALSE,
+            $6
+        )
+        ",
+        &login_session_id,
+        user_id,
+        device.machine_id,
+        device.ip_addr,
+        device.user_agent,
+        is_new_device,
+    )
+        .execute(&mut *ex)
+        .await?;
+
+    sqlx::query!(
+        "
+        UPDATE squadov.user_sessions
+        SET login_session_id = $2
+        WHERE id = $1
+        ",
+        session_id,
+        &login_session_id,
+    )
+        .execute(&mut *ex)
+        .await?;
+    Ok(login_session_id)
+}
+
+/// Deletes the given sessions along with the login sessions they belong to. If login_session_ids is None, all of the
+/// user's login sessions except for keep_login_session_id are deleted.
+pub async fn delete_login_sessions(ex: &mut Transaction<'_, Postgres>, user_id: i64, session_ids: &[String], login_session_ids: Option<&[Uuid]>, keep_login_session_id: Option<&Uuid>) -> Result<(), SquadOvError> {
+    sqlx::query!(
+        "
+        DELETE FROM squadov.user_sessions
+        WHERE id = ANY($1)
+            OR transition_id = ANY($1)
+        ",
+        session_ids,
+    )
+        .execute(&mut *ex)
+        .await?;
+
+    // Deleting the login session will cascade to any remaining sessions in the refresh chain.
+    sqlx::query!(
+        "
+        DELETE FROM squadov.user_login_sessions
+        WHERE user_id = $1
+            AND ($2::UUID[] IS NULL OR id = ANY($2))
+            AND ($3::UUID IS NULL OR id != $3)
+        ",
+        user_id,
+        login_session_ids.map(|x| { x.to_vec() }),
+        keep_login_session_id,
+    )
+        .execute(&mut *ex)
+        .await?;
+    Ok(())
+}
+
+impl api::ApiApplication {
+    /// Creates a new login session and attaches it to the given (newly created) session.
+    /// The location lookup and the security email for new devices are handled later by resolve_pending_login_sessions
+    /// so that logging in doesn't have to wait on any external services.
+    pub async fn create_login_session(&self, user: &SquadOVUser, session_id: &str, device: &SessionDeviceInfo) -> Result<(), SquadOvError> {
+        // Don't bother users with an email on their very first login.
+        let is_new_device = user_has_login_sessions(&*self.pool, user.id).await? && !is_known_device_for_user(&*self.pool, user.id, device).await?;
+
+        let mut tx = self.pool.begin().await?;
+        attach_new_login_session(&mut tx, user.id, session_id, device, is_new_device).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Fills in the location of login sessions that haven't been resolved yet and sends the security email
+    /// for logins from new devices. Returns the number of login sessions that were handled.
+    pub async fn resolve_pending_login_sessions(&self, limit: i64) -> Result<usize, SquadOvError> {
+        let pending = sqlx::query!(
+            "
+            SELECT id, user_id, machine_id, ip_addr, user_agent, notify_new_device
+            FROM squadov.user_login_sessions
+            WHERE NOT location_resolved
+            ORDER BY create_tm ASC
+            LIMIT $1
+            ",
+            limit,
+        )
+            .fetch_all(&*self.pool)
+            .await?;
+
+        for p in &pending {
+            let device = SessionDeviceInfo{
+                machine_id: p.machine_id.clone(),
+                ip_addr: p.ip_addr.clone(),
+                user_agent: p.user_agent.clone(),
+            };
+
+            // Failing to lookup the location shouldn't prevent us from marking the session as resolved. The lookup
+            // goes through the per-IP location cache so we only hit ipstack for addresses we haven't seen recently.
+            let location = match device.ip_addr.as_ref() {
+                Some(ip) => match self.get_ip_location_data(ip).await {
+                    Ok(x) => x,
+                    Err(err) => {
+                        log::warn!("Failed to get location data for login session: {:?}", err);
+                        None
+                    }
+                },
+                None => None,
+            };
+
+            sqlx::query!(
+                "
+                UPDATE squadov.user_login_sessions
+                SET city = $2,
+                    country = $3,
+                    location_resolved = TRUE
+                WHERE id = $1
+                ",
+                &p.id,
+                location.as_ref().map(|x| { x.city.clone() }).flatten(),
+                location.as_ref().map(|x| { x.country.clone() }).flatten(),
+            )
+                .execute(&*self.pool)
+                .await?;
+
+            if p.notify_new_device {
+                let user = match self.users.get_stored_user_from_id(p.user_id, &*self.pool).await? {
+                    Some(x) => x,
+                    None => continue,
+                };
+
+                let location_str = location.map(|x| {
+                    vec![x.city, x.country].into_iter().filter_map(|y| { y }).collect::<Vec<String>>().join(", ")
+                }).unwrap_or(String::from("Unknown"));
+
+                // This is purely informational so it shouldn't stop us from processing the other sessions.
+                if let Err(err) = self.send_new_device_login_email(&user, &device, &location_str).await {
+                    log::warn!("Failed to send new device login email: {:?}", err);
+                }
+            }
+        }
+
+        Ok(pending.len())
+    }
+
+    async fn send_new_device_login_email(&self, user: &SquadOVUser, device: &SessionDeviceInfo, location: &str) -> Result<(), SquadOvError> {
+        let template = match self.config.email.new_device_template.as_ref() {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        let mut params: HashMap<String, String> = HashMap::new();
+        params.insert(String::from("username"), user.username.clone());
+        params.insert(String::from("device"), if device.machine_id.is_some() {
+            String::from("SquadOV Desktop")
+        } else {
+            device.user_agent.clone().unwrap_or(String::from("Unknown"))
+        });
+        params.insert(String::from("ip"), device.ip_addr.clone().unwrap_or(String::from("Unknown")));
+        params.insert(String::from("location"), location.to_string());
+        params.insert(String::from("time"), Utc::now().to_rfc2822());
+        params.insert(String::from("sessions_url"), format!("{}/settings/security", &self.config.squadov.app_url));
+
+        self.email.send_bulk_templated_email(template, vec![
+            EmailTemplate{
+                params,
+                to: EmailUser{
+                    email: user.email.clone(),
+                    name: Some(user.username.clone()),
+                },
+            }
+        ]).await
+    }
+
+    // Revokes the tokens with whoever issued them. This is best effort since we're going to delete the sessions regardless.
+    async fn revoke_session_tokens(&self, refresh_token: &str) {
+        if let Err(err) = self.clients.identity.logout(refresh_token).await {
+            log::warn!("Failed to revoke session tokens: {:?}", err);
+        }
+    }
+
+    /// Revokes the given login sessions for the user. If login_session_ids is None, all sessions except
+    /// for the one specified by keep_session_id are revoked (including legacy sessions without a login session).
+    pub async fn revoke_login_sessions(&self, user_id: i64, login_session_ids: Option<&[Uuid]>, keep_session_id: &str) -> Result<(), SquadOvError> {
+        let keep_login_session_id = get_login_session_id_for_session(&*self.pool, keep_session_id).await?;
+
+        let sessions = sqlx::query!(
+            "
+            SELECT id, refresh_token, login_session_id
+            FROM squadov.user_sessions
+            WHERE user_id = $1
+                AND is_temp = FALSE
+                AND transition_id IS NULL
+                AND id != $2
+                AND ($3::UUID[] IS NULL OR login_session_id = ANY($3))
+                AND ($4::UUID IS NULL OR login_session_id IS NULL OR login_session_id != $4)
+            ",
+            user_id,
+            keep_session_id,
+            login_session_ids.map(|x| { x.to_vec() }),
+            keep_login_session_id,
+        )
+            .fetch_all(&*self.pool)
+            .await?;
+
+        for s in &sessions {
+            self.revoke_session_tokens(&s.refresh_token).await;
+        }
+
+        let mut tx = self.pool.begin().await?;
+        delete_login_sessions(&mut tx, user_id, &sessions.iter().map(|x| { x.id.clone() }).collect::<Vec<String>>(), login_session_ids, keep_login_session_id.as_ref()).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoginSessionPath {
+    login_session_id: Uuid,
+}
+
+pub async fn list_my_login_sessions_handler(app : web::Data<Arc<api::ApiApplication>>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+
+    let current = get_login_session_id_for_session(&*app.pool, &session.session_id).await?;
+    Ok(HttpResponse::Ok().json(
+        list_login_sessions_for_user(&*app.pool, session.user.id, current.as_ref()).await?
+    ))
+}
+
+pub async fn revoke_my_login_session_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<LoginSessionPath>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+
+    // The user should use the regular logout for their current session.
+    if get_login_session_id_for_session(&*app.pool, &session.session_id).await? == Some(path.login_session_id) {
+        return Err(SquadOvError::BadRequest);
+    }
+
+    app.revoke_login_sessions(session.user.id, Some(&[path.login_session_id]), &session.session_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn revoke_other_login_sessions_handler(app : web::Data<Arc<api::ApiApplication>>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+
+    app.revoke_login_sessions(session.user.id, None, &session.session_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    // These need a database with all the migrations applied. Run them with
+    // SQUADOV_TEST_DATABASE_URL set and `cargo test -- --ignored`.
+    use super::*;
+    use crate::api::auth::SessionManager;
+    use sqlx::postgres::{PgPool, PgPoolOptions};
+
+    async fn test_pool() -> PgPool {
+        let url = std::env::var("SQUADOV_TEST_DATABASE_URL").expect("SQUADOV_TEST_DATABASE_URL must be set to run the active session tests");
+        PgPoolOptions::new().max_connections(1).connect(&url).await.unwrap()
+    }
+
+    async fn create_test_user(pool: &PgPool) -> i64 {
+        let name = format!("session-test-{}", Uuid::new_v4().to_simple());
+        sqlx::query_scalar(
+            "
+            INSERT INTO squadov.users (email, username, verified, uuid, local_encryption_key)
+            VALUES ($1, $2, TRUE, gen_random_uuid(), 'fixture')
+            RETURNING id
+            "
+        )
+            .bind(format!("{}@squadov.gg", &name))
+            .bind(&name)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    // Returns the session ID and the ID of the login session it belongs to.
+    async fn create_test_login(pool: &PgPool, user_id: i64) -> (String, Uuid) {
+        let session_id = Uuid::new_v4().to_string();
+        let mut tx = pool.begin().await.unwrap();
+        sqlx::query(
+            "
+            INSERT INTO squadov.user_sessions (id, access_token, refresh_token, user_id, is_temp, issue_tm)
+            VALUES ($1, 'access', 'refresh', $2, FALSE, NOW())
+            "
+        )
+            .bind(&session_id)
+            .bind(user_id)
+            .execute(&mut tx)
+            .await
+            .unwrap();
+
+        let login_session_id = attach_new_login_session(&mut tx, user_id, &session_id, &SessionDeviceInfo::default(), false).await.unwrap();
+        tx.commit().await.unwrap();
+        (session_id, login_session_id)
+    }
+
+    async fn listed_login_sessions(pool: &PgPool, user_id: i64) -> Vec<Uuid> {
+        list_login_sessions_for_user(pool, user_id, None).await.unwrap().into_iter().map(|x| { x.id }).collect()
+    }
+
+    async fn cleanup_test_user(pool: &PgPool, user_id: i64) {
+        sqlx::query("DELETE FROM squadov.users WHERE id = $1")
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_list_login_sessions_after_logout() {
+        let pool = test_pool().await;
+        let user_id = create_test_user(&pool).await;
+        let (session_id, _) = create_test_login(&pool, user_id).await;
+        let (_, other_login_session_id) = create_test_login(&pool, user_id).await;
+
+        let mut tx = pool.begin().await.unwrap();
+        SessionManager::new().delete_session_and_login_session(&mut tx, &session_id).await.unwrap();
+        tx.commit().await.unwrap();
+
+        assert_eq!(listed_login_sessions(&pool, user_id).await, vec![other_login_session_id]);
+        cleanup_test_user(&pool, user_id).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_list_login_sessions_after_expiry() {
+        let pool = test_pool().await;
+        let user_id = create_test_user(&pool).await;
+        let (session_id, login_session_id) = create_test_login(&pool, user_id).await;
+        let (_, other_login_session_id) = create_test_login(&pool, user_id).await;
+
+        sqlx::query("UPDATE squadov.user_sessions SET expiration_tm = NOW() - INTERVAL '1 hour' WHERE id = $1")
+            .bind(&session_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Expired sessions don't count even before they get cleaned up.
+        assert_eq!(listed_login_sessions(&pool, user_id).await, vec![other_login_session_id]);
+
+        let mut tx = pool.begin().await.unwrap();
+        SessionManager::new().clean_expired_sessions_for_user(&mut tx, user_id).await.unwrap();
+        tx.commit().await.unwrap();
+
+        let remaining: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM squadov.user_login_sessions WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert!(!remaining.contains(&login_session_id));
+        assert_eq!(listed_login_sessions(&pool, user_id).await, vec![other_login_session_id]);
+        cleanup_test_user(&pool, user_id).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_list_login_sessions_after_revoke() {
+        let pool = test_pool().await;
+        let user_id = create_test_user(&pool).await;
+        let (current_session_id, current_login_session_id) = create_test_login(&pool, user_id).await;
+        let (session_id, login_session_id) = create_test_login(&pool, user_id).await;
+
+        let mut tx = pool.begin().await.unwrap();
+        delete_login_sessions(&mut tx, user_id, &[session_id], Some(&[login_session_id]), Some(&current_login_session_id)).await.unwrap();
+        tx.commit().await.unwrap();
+
+        let sessions = list_login_sessions_for_user(&pool, user_id, get_login_session_id_for_session(&pool, &current_session_id).await.unwrap().as_ref()).await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, current_login_session_id);
+        assert!(sessions[0].current);
+        cleanup_test_user(&pool, user_id).await;
+    }
+}
@@ -2,18 +2,20 @@ use actix_web::{web, HttpRequest, HttpResponse, HttpMessage};
 use serde::{Serialize, Deserialize};
 use crate::api::{
     self,
-    fusionauth,
-    v1::get_feature_flags,
+    identity::{
+        IdentityLoginResult,
+        IdentityTokens,
+    },
+    v1::get_evaluated_feature_flags,
 };
 use squadov_common::{
     SquadOvError,
     profile,
 };
-use crate::api::auth::SquadOVSession;
+use crate::api::auth::{SquadOVSession, SquadOVUser, SessionDeviceInfo};
 use crate::logged_error;
 use uuid::Uuid;
 use std::sync::Arc;
-use chrono::{DateTime, Utc, NaiveDateTime};
 use convert_case::{Case, Casing};
 
 #[derive(Deserialize, Clone)]
@@ -42,117 +44,100 @@ struct LoginResponse {
 }
 
 impl api::ApiApplication {
-    async fn generic_login_from_fusionauth(&self, result: fusionauth::FusionAuthLoginResult) -> Result<super::SquadOVSession, SquadOvError> {
-        let reg = self.clients.fusionauth.find_auth_registration(&result.user);
-        let mut session = match reg {
-            Some(x) => super::SquadOVSession{
-                session_id: Uuid::new_v4().to_string(),
-                user: super::SquadOVUser{
-                    id: -1, // Invalid ID is fine here - we'll grab it later.
-                    username: match &x.username {
-                        Some(y) => y.clone(),
-                        None => String::from(""),
-                    },
-                    email: result.user.email.clone(),
-                    verified: result.user.verified,
-                    uuid: Uuid::nil(), // We'll pull this later along with the id.
-                    is_test: false,
-                    is_admin: false,
-                    welcome_sent: false,
-                    registration_time: Some(DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(x.insert_instant / 1000, 0), Utc)),
-                    support_priority: String::from("normal"),
-                    last_trial_usage: None,
-                },
-                access_token: result.token,
-                refresh_token: result.refresh_token,
-                is_temp: false,
-                share_token: None,
-                sqv_access_token: None,
-                features: None,
-            },
-            None => return Err(SquadOvError::InternalError(String::from("Could not find user auth registration with the current app."))),
+    /// Creates a brand new SquadOV user along with everything a new user is expected to have
+    /// (default squad, profile, pending invites).
+    pub async fn create_new_squadov_user(&self, user: &SquadOVUser) -> Result<SquadOVUser, SquadOvError> {
+        let user = match self.users.create_user(user, &self.pool).await {
+            Ok(z) => z,
+            Err(err) => return Err(SquadOvError::InternalError(format!("Create User {}", err))),
         };
 
-        // Ensure that the user is also being tracked by our own database.
-        // If not, create a new user.
-        let mut did_create_user = false;
-        let stored_user = match self.users.get_stored_user_from_email(&session.user.email, &self.pool).await {
-            Ok(x) => match x {
-                Some(y) => y,
-                None => {
-                    did_create_user = true;
-                    let user = match self.users.create_user(&session.user, &self.pool).await {
-                        Ok(z) => z,
-                        Err(err) => return Err(SquadOvError::InternalError(format!("Create User {}", err))),
-                    };
-
-                    // Check for any pending squad invites and apply them.
-                    self.associate_pending_invites_to_user(&user.email, user.id).await?;
-
-                    // Create a default squad for this user and create their default profile using a slug created from their username.
-                    let mut tx = self.pool.begin().await?;
-                    self.create_default_squad(&mut tx, &user).await?;
-                    profile::create_user_profile_for_user_id(
-                        &mut tx,
-                        user.id,
-                        &format!(
-                            "{}-{}",
-                            petname::Petnames::large().generate_one(3, "-").to_case(Case::Pascal),
-                            user.uuid.to_hyphenated().to_string().split("-").collect::<Vec<&str>>()[0],
-                        ),
-                    ).await?;
-                    tx.commit().await?;
-
-                    user
-                },
-            },
-            Err(err) => return Err(SquadOvError::InternalError(format!("Get User {}", err))),
-        };
+        // Check for any pending squad invites and apply them.
+        self.associate_pending_invites_to_user(&user.email, user.id).await?;
+
+        // Create a default squad for this user and create their default profile using a slug created from their username.
+        let mut tx = self.pool.begin().await?;
+        self.create_default_squad(&mut tx, &user).await?;
+        profile::create_user_profile_for_user_id(
+            &mut tx,
+            user.id,
+            &format!(
+                "{}-{}",
+                petname::Petnames::large().generate_one(3, "-").to_case(Case::Pascal),
+                user.uuid.to_hyphenated().to_string().split("-").collect::<Vec<&str>>()[0],
+            ),
+        ).await?;
+        tx.commit().await?;
+        Ok(user)
+    }
 
+    /// Creates and stores a new session for a user that has already been authenticated.
+    pub async fn start_user_session(&self, user: SquadOVUser, tokens: IdentityTokens, did_create_user: bool, device: &SessionDeviceInfo) -> Result<SquadOVSession, SquadOvError> {
         // Need to do a preliminary identify on login. Empty IP/Anonymous ID so that we only
         // fill out some basic information (email, primarily, for Vero).
-        self.analytics_identify_user(&stored_user, "", "").await?;
+        self.analytics_identify_user(&user, "", "").await?;
 
         // If we just created the user, then we also need to mark the user as just having done the "register" event.
         if did_create_user {
-            self.segment.track(&stored_user.uuid.to_string(), "registered").await?;
+            self.segment.track(&user.uuid.to_string(), "registered").await?;
         }
 
-        session.features = Some(get_feature_flags(&*self.pool, stored_user.id).await?);
-        session.user = stored_user;
+        let session = SquadOVSession{
+            session_id: Uuid::new_v4().to_string(),
+            features: Some(get_evaluated_feature_flags(&*self.pool, user.id, None).await?.flags),
+            user,
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            is_temp: false,
+            share_token: None,
+            sqv_access_token: None,
+        };
+
         // Store this session in our database and ensure the user is made aware of which session they should
         // be echoing back to us so we can verify their session. It's the client's responsibility to store
         // the session ID and echo it back to us (since we're kinda assuming the lack of cookies because of Electron).
         self.session.store_session(&*self.pool, &session).await?;
+        self.create_login_session(&session.user, &session.session_id, device).await?;
         Ok(session)
     }
-}
 
-/// Authenticates the user with our backend and returns a session.
-async fn login(fa: &fusionauth::FusionAuthClient, data: LoginData, ip: Option<&str>) -> Result<fusionauth::FusionAuthLoginResult, SquadOvError> {
-    let res = fa.login(fa.build_login_input(
-        data.username,
-        data.password,
-        ip,
-    )).await;
-    match res {
-        Ok(result) => Ok(result),
-        // TODO: Handle two factor errors/change password errors/email verification errors.
-        Err(err) => match err {
-            fusionauth::FusionAuthLoginError::Auth => Err(SquadOvError::Credentials),
-            fusionauth::FusionAuthLoginError::Generic{code, message} => Err(SquadOvError::InternalError(format!("Code: {} Message: {}", code, message))),
-            fusionauth::FusionAuthLoginError::TwoFactor(two_factor_id) => Err(SquadOvError::TwoFactor(two_factor_id)),
-            _ => Err(SquadOvError::InternalError(String::from("Unhandled error."))),
-        }
+    async fn generic_login_from_identity(&self, result: IdentityLoginResult, device: &SessionDeviceInfo) -> Result<SquadOVSession, SquadOvError> {
+        // Ensure that the user is also being tracked by our own database.
+        // If not, create a new user.
+        let mut did_create_user = false;
+        let stored_user = match self.users.get_stored_user_from_email(&result.user.email, &self.pool).await {
+            Ok(x) => match x {
+                Some(y) => y,
+                None => {
+                    did_create_user = true;
+                    self.create_new_squadov_user(&SquadOVUser{
+                        id: -1, // Invalid ID is fine here - we'll grab it later.
+                        username: result.user.username.unwrap_or(String::from("")),
+                        email: result.user.email.clone(),
+                        verified: result.user.verified,
+                        uuid: Uuid::nil(), // We'll pull this later along with the id.
+                        is_test: false,
+                        is_admin: false,
+                        welcome_sent: false,
+                        registration_time: result.user.registration_time,
+                        support_priority: String::from("normal"),
+                        last_trial_usage: None,
+                    }).await?
+                },
+            },
+            Err(err) => return Err(SquadOvError::InternalError(format!("Get User {}", err))),
+        };
+
+        self.start_user_session(stored_user, result.tokens, did_create_user, device).await
     }
 }
 
-/// Handles taking the user's login request, passing it to FusionAuth and returning a response.
+/// Handles taking the user's login request, passing it to the identity provider and returning a response.
 /// 
 /// We expect only two parameters to be passed via the POST body: 
 /// * Username
 /// * Password
-/// This function will login the user with FusionAuth. If that's successful, it'll also login the user
+/// This function will login the user with the identity provider. If that's successful, it'll also login the user
 /// with SquadOV for session tracking.
 ///
 /// Possible Responses:
@@ -167,7 +152,7 @@ pub async fn login_handler(data : web::Json<LoginData>, app : web::Data<Arc<api:
 
     // First authenticate with our backend and obtain a valid session.
     let conn = req.connection_info();
-    let login_result = match login(&app.clients.fusionauth, data.clone(), conn.realip_remote_addr()).await {
+    let login_result = match app.clients.identity.login(&data.username, &data.password, conn.realip_remote_addr()).await {
         Ok(x) => x,
         Err(err) => match err {
             SquadOvError::TwoFactor(two_factor_id) => return Ok(HttpResponse::Ok().json(LoginResponse{
@@ -179,7 +164,7 @@ pub async fn login_handler(data : web::Json<LoginData>, app : web::Data<Arc<api:
             _ => return Err(err),
         }
     };
-    let session = app.generic_login_from_fusionauth(login_result).await?;
+    let session = app.generic_login_from_identity(login_result, &SessionDeviceInfo::from_request(&req)).await?;
     app.record_user_event(&[session.user.id], "login", data.platform.as_ref().map(|x| { x.as_str() })).await?;
 
     Ok(HttpResponse::Ok().json(LoginResponse{
@@ -195,8 +180,8 @@ pub async fn mfa_login_handler(data : web::Json<MfaLoginData>, app : web::Data<A
         return Err(SquadOvError::BadRequest);
     }
 
-    let login_result = app.clients.fusionauth.mfa_login(&data.id, &data.code).await?;
-    let session = app.generic_login_from_fusionauth(login_result).await?;
+    let login_result = app.clients.identity.mfa_login(&data.id, &data.code).await?;
+    let session = app.generic_login_from_identity(login_result, &SessionDeviceInfo::from_request(&req)).await?;
     app.record_user_event(&[session.user.id], "mfa_login", data.platform.as_ref().map(|x| { x.as_str() })).await?;
 
     Ok(HttpResponse::Ok().json(LoginResponse{
@@ -218,11 +203,7 @@ pub async fn verify_pw_handler(app : web::Data<Arc<api::ApiApplication>>, data :
     let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
 
     let conn = req.connection_info();
-    let _ = login(&app.clients.fusionauth, LoginData{
-        username: session.user.email.clone(),
-        password: data.password.clone(),
-        platform: None,
-    }, conn.realip_remote_addr()).await?;
+    let _ = app.clients.identity.login(&session.user.email, &data.password, conn.realip_remote_addr()).await?;
 
     Ok(HttpResponse::NoContent().finish())
 }
\ No newline at end of file
@@ -2,7 +2,6 @@ use actix_web::{web, HttpRequest, HttpResponse};
 use serde::{Deserialize};
 use sqlx::{Transaction, Postgres};
 use crate::api;
-use crate::api::fusionauth;
 use squadov_common::SquadOvError;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -23,16 +22,6 @@ pub struct RegisterParams {
     sig: Option<String>
 }
 
-async fn register(fa: &fusionauth::FusionAuthClient, data: RegisterData) -> Result<String, SquadOvError> {
-    let output = fa.register(fa.build_register_input(
-        data.username,
-        data.email,
-        data.password,
-    )).await?;
-
-    Ok(output.user.email)
-}
-
 impl api::ApiApplication {
     async fn associate_user_to_referral_code(&self, tx: &mut Transaction<'_, Postgres>, email: &str, referral_code: &str) -> Result<(), SquadOvError> {
         sqlx::query!(
@@ -99,8 +88,14 @@ pub async fn register_handler(data : web::Json<RegisterData>, app : web::Data<Ar
     let mut data = data.into_inner();
     data.email = data.email.to_lowercase().trim().to_string();
 
+    // Users who signed up via a third party before we started registering them with the identity provider
+    // won't exist there yet so we need to make sure no one can register a password login for their email.
+    if app.users.get_stored_user_from_email(&data.email, &*app.pool).await?.is_some() {
+        return Err(SquadOvError::Duplicate);
+    }
+
     let referral = data.r#ref.clone();
-    let email = register(&app.clients.fusionauth, data).await?;
+    let email = app.clients.identity.register(&data.username, &data.email, &data.password).await?;
 
     let mut tx = app.pool.begin().await?;
     if let Some(referral_code) = &referral {
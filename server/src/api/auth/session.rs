@@ -1,6 +1,6 @@
 use sqlx;
 use sqlx::postgres::PgPool;
-use sqlx::{Executor, Postgres};
+use sqlx::{Executor, Postgres, Transaction};
 use actix_web::{
     HttpRequest,
     FromRequest,
@@ -29,10 +29,9 @@ use squadov_common::{
     },
 };
 use crate::api::{
-    fusionauth,
     v1::{
         FeatureFlags,
-        get_feature_flags,
+        get_evaluated_feature_flags,
     },
 };
 use uuid::Uuid;
@@ -129,6 +128,25 @@ impl SessionManager {
         return Ok(())
     }
 
+    // Logging out ends the whole login session (the device the user sees in their list of sessions) and not
+    // just the latest session in the refresh chain. Deleting the login session cascades to the rest of the chain.
+    pub async fn delete_session_and_login_session(&self, ex: &mut Transaction<'_, Postgres>, id: &str) -> Result<(), SquadOvError> {
+        if let Some(login_session_id) = super::get_login_session_id_for_session(&mut *ex, id).await? {
+            sqlx::query!(
+                "
+                DELETE FROM squadov.user_login_sessions
+                WHERE id = $1
+                ",
+                &login_session_id,
+            )
+                .execute(&mut *ex)
+                .await?;
+        }
+
+        self.delete_session(id, &mut *ex).await?;
+        Ok(())
+    }
+
     pub async fn get_session_from_id(&self, id : &str, pool: &PgPool) -> Result<Option<SquadOVSession>, SquadOvError> {
         let ret = sqlx::query!(
             "
@@ -177,7 +195,7 @@ impl SessionManager {
                 is_temp: x.is_temp,
                 share_token: None,
                 sqv_access_token: None,
-                features: Some(get_feature_flags(pool, x.user_id).await?),
+                features: Some(get_evaluated_feature_flags(pool, x.user_id, None).await?.flags),
             })),
             None => Ok(None),
         }
@@ -272,12 +290,23 @@ impl SessionManager {
     where
         T: Executor<'a, Database = Postgres>
     {
+        // The new session belongs to the same login session as the old one.
         sqlx::query!(
             "
-            UPDATE squadov.user_sessions
-            SET transition_id = $2,
-                expiration_tm = NOW() + INTERVAL '3 hour'
-            WHERE id = $1
+            WITH old_session AS (
+                UPDATE squadov.user_sessions
+                SET transition_id = $2,
+                    expiration_tm = NOW() + INTERVAL '3 hour'
+                WHERE id = $1
+                RETURNING login_session_id
+            ), new_session AS (
+                UPDATE squadov.user_sessions
+                SET login_session_id = (SELECT login_session_id FROM old_session)
+                WHERE id = $2
+            )
+            UPDATE squadov.user_login_sessions
+            SET last_used_tm = NOW()
+            WHERE id = (SELECT login_session_id FROM old_session)
             ",
             old_id,
             new_id,
@@ -287,10 +316,7 @@ impl SessionManager {
         return Ok(())
     }
 
-    pub async fn clean_expired_sessions_for_user<'a, T>(&self, ex: T, user_id: i64) -> Result<(), SquadOvError>
-    where
-        T: Executor<'a, Database = Postgres>
-    {
+    pub async fn clean_expired_sessions_for_user(&self, ex: &mut Transaction<'_, Postgres>, user_id: i64) -> Result<(), SquadOvError> {
         sqlx::query!(
             "
             DELETE FROM squadov.user_sessions
@@ -301,7 +327,23 @@ impl SessionManager {
             ",
             user_id
         )
-            .execute(ex)
+            .execute(&mut *ex)
+            .await?;
+
+        // Login sessions without any sessions left can't be used anymore.
+        sqlx::query!(
+            "
+            DELETE FROM squadov.user_login_sessions AS uls
+            WHERE uls.user_id = $1
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM squadov.user_sessions AS us
+                    WHERE us.login_session_id = uls.id
+                )
+            ",
+            user_id
+        )
+            .execute(&mut *ex)
             .await?;
         Ok(())
     }
@@ -309,22 +351,16 @@ impl SessionManager {
 
 impl crate::api::ApiApplication {
     pub async fn is_session_valid(&self, session: &SquadOVSession) -> Result<bool, squadov_common::SquadOvError> {   
-        // Temp sessions are generated by us and don't have a fusionauth access token to verify.
+        // Temp sessions are generated by us and don't have an identity provider access token to verify.
         if !session.is_temp {
-            match self.clients.fusionauth.validate_jwt(&session.access_token).await {
-                Ok(_) => Ok(true),
-                Err(err) => match err {
-                    fusionauth::FusionAuthValidateJwtError::Invalid => Ok(false),
-                    _ => Err(squadov_common::SquadOvError::InternalError(format!("Validate JWT {}", err)))
-                }
-            }
+            self.clients.identity.validate_access_token(&session.access_token).await
         } else {
             Ok(true)
         }   
     }
 
     pub async fn refresh_session_if_necessary(&self, session: SquadOVSession, force: bool) -> Result<SquadOVSession, squadov_common::SquadOvError> {
-        // Check if the session is expired (as determined by FusionAuth).
+        // Check if the session is expired (as determined by the identity provider).
         // If it is expired (or close to it), generate a new session ID and use the refresh token to get a new access token.
         // If it isn't expired, return the session as is.
         let mut session = session;
@@ -335,7 +371,7 @@ impl crate::api::ApiApplication {
                 return Ok(self.session.get_session_from_id(&transition_id, &*self.pool).await?.ok_or(SquadOvError::NotFound)?);
             }
 
-            let new_token = match self.clients.fusionauth.refresh_jwt(&session.refresh_token).await {
+            let new_token = match self.clients.identity.refresh_tokens(&session.refresh_token).await {
                 Ok(t) => t,
                 Err(err) => {
                     log::warn!("Failed to Refresh client JWT: {}", err);
@@ -345,7 +381,7 @@ impl crate::api::ApiApplication {
 
             let old_id = session.session_id;
             session.session_id = Uuid::new_v4().to_string();
-            session.access_token = new_token.token;
+            session.access_token = new_token.access_token;
             session.refresh_token = new_token.refresh_token;
 
             let mut tx = self.pool.begin().await?;
@@ -393,14 +429,19 @@ impl crate::api::ApiApplication {
     }
 
     pub async fn logout(&self, session: &SquadOVSession) -> Result<(),  squadov_common::SquadOvError> {
-        // Logout from FusionAuth AND delete the session from our database.
+        // Logout from the identity provider AND delete the session from our database.
         // Both operations should be done regardless of whether the other one is successful.
-        let fa_result = self.clients.fusionauth.logout(&session.refresh_token).await;
-        let db_result = self.session.delete_session(&session.session_id, &*self.pool).await;
+        let idp_result = self.clients.identity.logout(&session.refresh_token).await;
+        let db_result = async {
+            let mut tx = self.pool.begin().await?;
+            self.session.delete_session_and_login_session(&mut tx, &session.session_id).await?;
+            tx.commit().await?;
+            Ok::<(), SquadOvError>(())
+        }.await;
 
-        match fa_result {
+        match idp_result {
             Ok(_) => (),
-            Err(err) => return Err(squadov_common::SquadOvError::InternalError(format!("Failed to logout (FA): {}", err)))
+            Err(err) => return Err(squadov_common::SquadOvError::InternalError(format!("Failed to logout (IDP): {}", err)))
         };
 
         match db_result {
@@ -0,0 +1,864 @@
+use actix_web::{web, HttpRequest, HttpResponse, HttpMessage};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc, Duration};
+use sqlx::{Executor, Postgres};
+use crate::api::{
+    self,
+    auth::{SquadOVSession, SquadOVUser, SessionDeviceInfo},
+    identity::IdentityLoginResult,
+};
+use crate::logged_error;
+use squadov_common::{
+    SquadOvError,
+    EmailTemplate,
+    EmailUser,
+    crypto::password::{
+        generate_secure_token,
+        hash_token,
+    },
+    discord::api::DiscordApiClient,
+    steam::openid::{
+        build_steam_openid_login_url,
+        verify_steam_openid_response,
+    },
+    twitch::oauth::decode_twitch_id_token,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use url::Url;
+use uuid::Uuid;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SocialLoginConfig {
+    // Full authorization URLs (minus the state). These are separate from the account linking URLs since they
+    // need to redirect back to the login page and request the scopes necessary to identify the user (e.g. email).
+    pub discord_url: String,
+    pub twitch_url: String,
+    // Steam only supports OpenID 2.0 - Steam redirects back to the return URL with the state we append to it.
+    // The return URL Steam hands back to us must match this URL (plus our state) exactly.
+    pub steam_return_url: String,
+    pub steam_realm: String,
+    // Postmark template alias for verifying the email of users who signed up without one (e.g. Steam).
+    pub verify_email_template: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all="lowercase")]
+pub enum SocialLoginProvider {
+    Discord,
+    Twitch,
+    Steam,
+}
+
+impl SocialLoginProvider {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SocialLoginProvider::Discord => "discord",
+            SocialLoginProvider::Twitch => "twitch",
+            SocialLoginProvider::Steam => "steam",
+        }
+    }
+}
+
+impl std::str::FromStr for SocialLoginProvider {
+    type Err = SquadOvError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "discord" => Ok(SocialLoginProvider::Discord),
+            "twitch" => Ok(SocialLoginProvider::Twitch),
+            "steam" => Ok(SocialLoginProvider::Steam),
+            _ => Err(SquadOvError::BadRequest),
+        }
+    }
+}
+
+// What we know about the user from the third party after they've authenticated with them.
+struct SocialIdentity {
+    provider: SocialLoginProvider,
+    provider_user_id: String,
+    display_name: String,
+    // Only set if the third party has verified the email.
+    email: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct UserLoginIdentity {
+    pub id: i64,
+    pub provider: String,
+    pub display_name: String,
+    pub create_tm: DateTime<Utc>,
+    pub last_login_tm: Option<DateTime<Utc>>,
+}
+
+async fn find_user_id_for_login_identity<'a, T>(ex: T, provider: SocialLoginProvider, provider_user_id: &str) -> Result<Option<i64>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query!(
+            "
+            SELECT user_id
+            FROM squadov.user_login_identities
+            WHERE provider = $1
+                AND provider_user_id = $2
+            ",
+            provider.as_str(),
+            provider_user_id,
+        )
+            .fetch_optional(ex)
+            .await?
+            .map(|x| { x.user_id })
+    )
+}
+
+// Linking an identity that's already linked to the same user is a no-op. Identities that are linked to some other
+// user fail with SquadOvError::Duplicate - the user has to unlink it from the other account first.
+async fn link_login_identity_to_user<'a, T>(ex: T, user_id: i64, identity: &SocialIdentity) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    let linked_user_id = sqlx::query!(
+        "
+        INSERT INTO squadov.user_login_identities (
+            user_id,
+            provider,
+            provider_user_id,
+            display_name,
+            create_tm
+        ) VALUES (
+            $1,
+            $2,
+            $3,
+            $4,
+            NOW()
+        )
+        ON CONFLICT (provider, provider_user_id) DO UPDATE
+            SET display_name = user_login_identities.display_name
+        RETURNING user_id
+        ",
+        user_id,
+        identity.provider.as_str(),
+        &identity.provider_user_id,
+        &identity.display_name,
+    )
+        .fetch_one(ex)
+        .await?
+        .user_id;
+
+    if linked_user_id != user_id {
+        return Err(SquadOvError::Duplicate);
+    }
+    Ok(())
+}
+
+async fn mark_login_identity_used<'a, T>(ex: T, identity: &SocialIdentity) -> Result<(), SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    sqlx::query!(
+        "
+        UPDATE squadov.user_login_identities
+        SET last_login_tm = NOW(),
+            display_name = $3
+        WHERE provider = $1
+            AND provider_user_id = $2
+        ",
+        identity.provider.as_str(),
+        &identity.provider_user_id,
+        &identity.display_name,
+    )
+        .execute(ex)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_login_identities_for_user<'a, T>(ex: T, user_id: i64) -> Result<Vec<UserLoginIdentity>, SquadOvError>
+where
+    T: Executor<'a, Database = Postgres>
+{
+    Ok(
+        sqlx::query_as!(
+            UserLoginIdentity,
+            "
+            SELECT
+                id,
+                provider,
+                display_name,
+                create_tm,
+                last_login_tm
+            FROM squadov.user_login_identities
+            WHERE user_id = $1
+            ORDER BY create_tm ASC
+            ",
+            user_id,
+        )
+            .fetch_all(ex)
+            .await?
+    )
+}
+
+impl api::ApiApplication {
+    fn social_login_config(&self) -> Result<&SocialLoginConfig, SquadOvError> {
+        self.config.social_login.as_ref().ok_or(SquadOvError::NotFound)
+    }
+
+    // The state is embedded in the return URL since that's the only thing Steam passes back to us as-is.
+    fn steam_return_to_for_state(&self, state: &str) -> Result<String, SquadOvError> {
+        let mut return_to = Url::parse(&self.social_login_config()?.steam_return_url)?;
+        return_to.query_pairs_mut().append_pair("state", state);
+        Ok(return_to.to_string())
+    }
+
+    async fn create_social_login_state(&self, provider: SocialLoginProvider, link_user_id: Option<i64>) -> Result<String, SquadOvError> {
+        // Most login attempts that get abandoned never come back to consume their state so clean those up here.
+        sqlx::query!(
+            "
+            DELETE FROM squadov.social_login_states
+            WHERE expiration_tm <= NOW()
+            "
+        )
+            .execute(&*self.pool)
+            .await?;
+
+        let state = generate_secure_token(32)?;
+        sqlx::query!(
+            "
+            INSERT INTO squadov.social_login_states (
+                state_hash,
+                provider,
+                link_user_id,
+                expiration_tm
+            ) VALUES (
+                $1,
+                $2,
+                $3,
+                $4
+            )
+            ",
+            hash_token(&state),
+            provider.as_str(),
+            link_user_id,
+            Utc::now() + Duration::minutes(30),
+        )
+            .execute(&*self.pool)
+            .await?;
+        Ok(state)
+    }
+
+    // Returns the user ID that the identity should be linked to if this OAuth flow was started by a user that's already logged in.
+    async fn consume_social_login_state(&self, provider: SocialLoginProvider, state: &str) -> Result<Option<i64>, SquadOvError> {
+        Ok(
+            sqlx::query!(
+                "
+                DELETE FROM squadov.social_login_states
+                WHERE state_hash = $1
+                    AND provider = $2
+                    AND expiration_tm > NOW()
+                RETURNING link_user_id
+                ",
+                hash_token(state),
+                provider.as_str(),
+            )
+                .fetch_optional(&*self.pool)
+                .await?
+                .ok_or(SquadOvError::Unauthorized)?
+                .link_user_id
+        )
+    }
+
+    async fn generate_social_login_url(&self, provider: SocialLoginProvider, link_user_id: Option<i64>) -> Result<String, SquadOvError> {
+        let config = self.social_login_config()?;
+        let state = self.create_social_login_state(provider, link_user_id).await?;
+        Ok(match provider {
+            SocialLoginProvider::Discord => format!("{base}&state={state}", base=&config.discord_url, state=&state),
+            SocialLoginProvider::Twitch => format!("{base}&state={state}", base=&config.twitch_url, state=&state),
+            SocialLoginProvider::Steam => build_steam_openid_login_url(&self.steam_return_to_for_state(&state)?, &config.steam_realm)?,
+        })
+    }
+
+    // Only call this after the state has been consumed.
+    async fn resolve_social_identity(&self, provider: SocialLoginProvider, data: &SocialLoginCallbackData) -> Result<SocialIdentity, SquadOvError> {
+        match provider {
+            SocialLoginProvider::Discord => {
+                let mut redirect_url = Url::parse(&data.redirect_url)?;
+                redirect_url.set_query(None);
+
+                let token = squadov_common::discord::oauth::exchange_authorization_code_for_access_token(
+                    &self.config.discord.client_id,
+                    &self.config.discord.client_secret,
+                    &redirect_url.as_str(),
+                    data.code.as_ref().ok_or(SquadOvError::BadRequest)?,
+                ).await?;
+
+                // We don't know who the SquadOV user is yet and we don't store this token so there's no user to pass along here.
+                let api = DiscordApiClient::new(self.config.discord.clone(), token, self.pool.clone(), -1);
+                let discord_user = api.get_current_user().await?;
+                Ok(SocialIdentity{
+                    provider,
+                    display_name: format!("{}#{}", &discord_user.username, &discord_user.discriminator),
+                    provider_user_id: discord_user.id,
+                    email: if discord_user.verified.unwrap_or(false) {
+                        discord_user.email
+                    } else {
+                        None
+                    },
+                })
+            },
+            SocialLoginProvider::Twitch => {
+                let mut redirect_url = Url::parse(&data.redirect_url)?;
+                redirect_url.set_query(None);
+
+                let token = squadov_common::twitch::oauth::exchange_authorization_code_for_access_token(
+                    &self.config.twitch.id_url,
+                    &self.config.twitch.client_id,
+                    &self.config.twitch.client_secret,
+                    &redirect_url.as_str(),
+                    data.code.as_ref().ok_or(SquadOvError::BadRequest)?,
+                ).await?;
+
+                let id_token = decode_twitch_id_token(token.id_token.as_ref().ok_or(SquadOvError::BadRequest)?)?;
+                Ok(SocialIdentity{
+                    provider,
+                    display_name: id_token.preferred_username.clone().unwrap_or(id_token.sub.clone()),
+                    provider_user_id: id_token.sub,
+                    email: if id_token.email_verified.unwrap_or(false) {
+                        id_token.email
+                    } else {
+                        None
+                    },
+                })
+            },
+            SocialLoginProvider::Steam => {
+                // Steam has to have sent the user back to the exact URL we generated for this state.
+                let steam_id = verify_steam_openid_response(&data.openid, &self.steam_return_to_for_state(&data.state)?).await?;
+                self.steam_itf.request_sync_steam_accounts(&[steam_id]).await?;
+
+                let display_name = sqlx::query!(
+                    "
+                    SELECT steam_name
+                    FROM squadov.steam_users_cache
+                    WHERE steam_id = $1
+                    ",
+                    steam_id,
+                )
+                    .fetch_optional(&*self.pool)
+                    .await?
+                    .map(|x| { x.steam_name })
+                    .unwrap_or(steam_id.to_string());
+
+                Ok(SocialIdentity{
+                    provider,
+                    provider_user_id: steam_id.to_string(),
+                    display_name,
+                    // Steam never gives us an email.
+                    email: None,
+                })
+            },
+        }
+    }
+
+    // Usernames need to be unique so we try the third party's display name first and fall back to adding a random suffix.
+    async fn generate_available_username(&self, display_name: &str) -> Result<String, SquadOvError> {
+        let base: String = display_name
+            .split('#')
+            .next()
+            .unwrap_or("")
+            .chars()
+            .filter(|x| { x.is_ascii_alphanumeric() || *x == '_' || *x == '-' })
+            .collect();
+        let base = if base.is_empty() {
+            String::from("player")
+        } else {
+            base
+        };
+
+        for i in 0..5 {
+            let candidate = if i == 0 {
+                base.clone()
+            } else {
+                format!("{}{}", &base, rand::random::<u16>() % 10000)
+            };
+
+            let exists = sqlx::query!(
+                "
+                SELECT EXISTS (
+                    SELECT 1
+                    FROM squadov.users
+                    WHERE username = $1
+                ) AS \"exists!\"
+                ",
+                &candidate,
+            )
+                .fetch_one(&*self.pool)
+                .await?
+                .exists;
+
+            if !exists {
+                return Ok(candidate);
+            }
+        }
+
+        Err(SquadOvError::Duplicate)
+    }
+
+    async fn create_user_from_social_identity(&self, identity: &SocialIdentity, username: &str, email: &str) -> Result<SquadOVUser, SquadOvError> {
+        // We purposefully don't automatically link to an existing user with the same email. The user
+        // should login and link the account from their settings instead.
+        if self.users.get_stored_user_from_email(email, &*self.pool).await?.is_some() {
+            return Err(SquadOvError::Duplicate);
+        }
+
+        // Every user needs to exist in the identity provider so that sessions, MFA, etc. all work the same way
+        // regardless of how the user logs in. This will also fail if the email is already registered there.
+        self.clients.identity.register_external(username, email).await?;
+
+        let user = self.create_new_squadov_user(&SquadOVUser{
+            id: -1,
+            username: username.to_string(),
+            email: email.to_string(),
+            verified: true,
+            uuid: Uuid::nil(),
+            is_test: false,
+            is_admin: false,
+            welcome_sent: false,
+            registration_time: None,
+            support_priority: String::from("normal"),
+            last_trial_usage: None,
+        }).await?;
+
+        // We create the SquadOV user right away rather than on the first login so the registration time needs to be filled in here.
+        self.update_user_registration_time(user.id, &Utc::now()).await?;
+        link_login_identity_to_user(&*self.pool, user.id, identity).await?;
+        Ok(self.users.get_stored_user_from_id(user.id, &*self.pool).await?.ok_or(SquadOvError::NotFound)?)
+    }
+
+    // Logs the user in through the identity provider so the session gets the same tokens and goes through the same
+    // MFA flow as a password login. Users created via social login before they were registered with the identity
+    // provider get registered here.
+    async fn social_identity_login(&self, user: &SquadOVUser, ip: Option<&str>) -> Result<IdentityLoginResult, SquadOvError> {
+        match self.clients.identity.external_login(&user.email, ip).await {
+            Err(SquadOvError::NotFound) => {
+                self.clients.identity.register_external(&user.username, &user.email).await?;
+                self.clients.identity.external_login(&user.email, ip).await
+            },
+            x => x,
+        }
+    }
+
+    async fn finish_social_login(&self, user: SquadOVUser, identity: &SocialIdentity, did_create_user: bool, req: &HttpRequest) -> Result<SocialLoginResponse, SquadOvError> {
+        let ip = req.connection_info().realip_remote_addr().map(|x| { x.to_string() });
+        let login_result = match self.social_identity_login(&user, ip.as_ref().map(|x| { x.as_str() })).await {
+            Ok(x) => x,
+            // The user needs to finish logging in via the usual MFA login.
+            Err(SquadOvError::TwoFactor(two_factor_id)) => return Ok(SocialLoginResponse{
+                user_id: 0,
+                session_id: String::new(),
+                verified: false,
+                pending_registration: None,
+                two_factor: Some(two_factor_id),
+                linked: false,
+            }),
+            Err(err) => return Err(err),
+        };
+
+        mark_login_identity_used(&*self.pool, identity).await?;
+        let session = self.start_user_session(user, login_result.tokens, did_create_user, &SessionDeviceInfo::from_request(req)).await?;
+        Ok(SocialLoginResponse::from_session(&session))
+    }
+
+    async fn create_pending_social_registration(&self, identity: &SocialIdentity) -> Result<String, SquadOvError> {
+        // Same as with the login states, registrations that never got verified would otherwise stick around forever.
+        sqlx::query!(
+            "
+            DELETE FROM squadov.social_login_pending_registrations
+            WHERE expiration_tm <= NOW()
+            "
+        )
+            .execute(&*self.pool)
+            .await?;
+
+        let pending_id = generate_secure_token(32)?;
+        sqlx::query!(
+            "
+            INSERT INTO squadov.social_login_pending_registrations (
+                id_hash,
+                provider,
+                provider_user_id,
+                display_name,
+                expiration_tm
+            ) VALUES (
+                $1,
+                $2,
+                $3,
+                $4,
+                $5
+            )
+            ",
+            hash_token(&pending_id),
+            identity.provider.as_str(),
+            &identity.provider_user_id,
+            &identity.display_name,
+            Utc::now() + Duration::days(1),
+        )
+            .execute(&*self.pool)
+            .await?;
+        Ok(pending_id)
+    }
+
+    async fn send_social_registration_verification_email(&self, username: &str, email: &str, verification_id: &str) -> Result<(), SquadOvError> {
+        let mut params: HashMap<String, String> = HashMap::new();
+        params.insert(String::from("username"), username.to_string());
+        params.insert(String::from("verification_url"), format!("{}/verify/social/{}", &self.config.squadov.app_url, verification_id));
+        params.insert(String::from("verification_id"), verification_id.to_string());
+
+        if let Some(template) = self.social_login_config()?.verify_email_template.as_ref() {
+            self.email.send_bulk_templated_email(template, vec![
+                EmailTemplate{
+                    params,
+                    to: EmailUser{
+                        email: email.to_string(),
+                        name: Some(username.to_string()),
+                    },
+                }
+            ]).await?;
+        } else {
+            log::info!("No email template for social login verification email to {} - {:?}", email, params);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SocialLoginProviderPath {
+    provider: SocialLoginProvider,
+}
+
+#[derive(Deserialize)]
+pub struct LoginIdentityPath {
+    identity_id: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct SocialLoginCallbackData {
+    state: String,
+    // OAuth (Discord/Twitch).
+    code: Option<String>,
+    #[serde(default)]
+    redirect_url: String,
+    // OpenID (Steam). All the openid.* query parameters that were passed to the return URL.
+    #[serde(default)]
+    openid: HashMap<String, String>,
+    platform: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+struct SocialLoginResponse {
+    user_id: i64,
+    session_id: String,
+    verified: bool,
+    // Set if we need the user to give us an email before we can create their account.
+    pending_registration: Option<String>,
+    // Set if the user has MFA enabled. This should be passed to the MFA login endpoint along with the code.
+    two_factor: Option<String>,
+    // Set if this request linked the identity to an existing logged in user instead of logging in.
+    linked: bool,
+}
+
+impl SocialLoginResponse {
+    fn from_session(session: &SquadOVSession) -> Self {
+        Self {
+            user_id: session.user.id,
+            session_id: session.session_id.clone(),
+            verified: session.user.verified,
+            pending_registration: None,
+            two_factor: None,
+            linked: false,
+        }
+    }
+}
+
+/// Returns the URL the user should be sent to in order to login with the third party.
+pub async fn get_social_login_url_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<SocialLoginProviderPath>) -> Result<HttpResponse, SquadOvError> {
+    Ok(HttpResponse::Ok().json(&app.generate_social_login_url(path.provider, None).await?))
+}
+
+/// Handles the redirect back from the third party. Depending on how the flow was started this will either
+/// 1) Link the third party identity to the user who started the flow.
+/// 2) Login the user that this identity is linked to.
+/// 3) Create a new user if the third party gave us a verified email.
+/// 4) Return a pending registration ID that needs to be completed with an email.
+/// We only ever login users through identities that were linked via this flow. Users with MFA enabled
+/// get a two factor ID back and need to finish logging in with the MFA login endpoint.
+pub async fn handle_social_login_callback_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<SocialLoginProviderPath>, data: web::Json<SocialLoginCallbackData>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let link_user_id = app.consume_social_login_state(path.provider, &data.state).await?;
+    let identity = app.resolve_social_identity(path.provider, &data).await?;
+
+    if let Some(link_user_id) = link_user_id {
+        link_login_identity_to_user(&*app.pool, link_user_id, &identity).await?;
+        return Ok(HttpResponse::Ok().json(SocialLoginResponse{
+            user_id: link_user_id,
+            session_id: String::new(),
+            verified: true,
+            pending_registration: None,
+            two_factor: None,
+            linked: true,
+        }));
+    }
+
+    let mut did_create_user = false;
+    let user_id = if let Some(user_id) = find_user_id_for_login_identity(&*app.pool, identity.provider, &identity.provider_user_id).await? {
+        Some(user_id)
+    } else if let Some(email) = identity.email.as_ref() {
+        let username = app.generate_available_username(&identity.display_name).await?;
+        did_create_user = true;
+        Some(app.create_user_from_social_identity(&identity, &username, email).await?.id)
+    } else {
+        None
+    };
+
+    let user_id = match user_id {
+        Some(x) => x,
+        None => return Ok(HttpResponse::Ok().json(SocialLoginResponse{
+            user_id: 0,
+            session_id: String::new(),
+            verified: false,
+            pending_registration: Some(app.create_pending_social_registration(&identity).await?),
+            two_factor: None,
+            linked: false,
+        })),
+    };
+
+    let user = app.users.get_stored_user_from_id(user_id, &*app.pool).await?.ok_or(SquadOvError::NotFound)?;
+    let response = app.finish_social_login(user, &identity, did_create_user, &req).await?;
+    if response.two_factor.is_none() {
+        app.record_user_event(&[response.user_id], "social_login", data.platform.as_ref().map(|x| { x.as_str() })).await?;
+    }
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct SocialRegistrationData {
+    pending_id: String,
+    username: String,
+    email: String,
+}
+
+/// Provides the username/email for a pending social registration. The account isn't created
+/// until the user verifies the email so calling this again is how the verification email is resent.
+pub async fn social_registration_handler(app : web::Data<Arc<api::ApiApplication>>, data: web::Json<SocialRegistrationData>) -> Result<HttpResponse, SquadOvError> {
+    let email = data.email.to_lowercase().trim().to_string();
+    if app.users.get_stored_user_from_email(&email, &*app.pool).await?.is_some() {
+        return Err(SquadOvError::Duplicate);
+    }
+
+    let username_taken = sqlx::query!(
+        "
+        SELECT EXISTS (
+            SELECT 1
+            FROM squadov.users
+            WHERE username = $1
+        ) AS \"exists!\"
+        ",
+        &data.username,
+    )
+        .fetch_one(&*app.pool)
+        .await?
+        .exists;
+
+    if username_taken {
+        return Err(SquadOvError::Duplicate);
+    }
+
+    let verification_id = generate_secure_token(32)?;
+    let result = sqlx::query!(
+        "
+        UPDATE squadov.social_login_pending_registrations
+        SET email = $2,
+            username = $3,
+            verification_hash = $4
+        WHERE id_hash = $1
+            AND expiration_tm > NOW()
+        ",
+        hash_token(&data.pending_id),
+        &email,
+        &data.username,
+        hash_token(&verification_id),
+    )
+        .execute(&*app.pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(SquadOvError::NotFound);
+    }
+
+    app.send_social_registration_verification_email(&data.username, &email, &verification_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct SocialRegistrationVerifyData {
+    verification_id: String,
+}
+
+/// Finishes a pending social registration once the user has clicked the link in the verification email.
+pub async fn verify_social_registration_handler(app : web::Data<Arc<api::ApiApplication>>, data: web::Json<SocialRegistrationVerifyData>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let pending = sqlx::query!(
+        "
+        DELETE FROM squadov.social_login_pending_registrations
+        WHERE verification_hash = $1
+            AND expiration_tm > NOW()
+        RETURNING
+            provider,
+            provider_user_id,
+            display_name,
+            email AS \"email!\",
+            username AS \"username!\"
+        ",
+        hash_token(&data.verification_id),
+    )
+        .fetch_optional(&*app.pool)
+        .await?
+        .ok_or(SquadOvError::NotFound)?;
+
+    let identity = SocialIdentity{
+        provider: pending.provider.parse()?,
+        provider_user_id: pending.provider_user_id,
+        display_name: pending.display_name,
+        email: Some(pending.email.clone()),
+    };
+
+    let user = app.create_user_from_social_identity(&identity, &pending.username, &pending.email).await?;
+    Ok(HttpResponse::Ok().json(app.finish_social_login(user, &identity, true, &req).await?))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+struct UserLoginIdentitiesResponse {
+    has_password: bool,
+    identities: Vec<UserLoginIdentity>,
+}
+
+// Users created through a social login also exist in the identity provider but they don't know their password
+// unless they've gone through forgot password since.
+async fn user_has_password_login(app: &api::ApiApplication, email: &str) -> Result<bool, SquadOvError> {
+    match app.clients.identity.find_user_from_email(email).await {
+        Ok(x) => Ok(x.has_password),
+        Err(err) => match err {
+            SquadOvError::NotFound => Ok(false),
+            _ => Err(err),
+        }
+    }
+}
+
+pub async fn get_my_login_identities_handler(app : web::Data<Arc<api::ApiApplication>>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+
+    Ok(HttpResponse::Ok().json(UserLoginIdentitiesResponse{
+        has_password: user_has_password_login(&app, &session.user.email).await?,
+        identities: get_login_identities_for_user(&*app.pool, session.user.id).await?,
+    }))
+}
+
+/// Returns the URL to start linking a third party identity to the currently logged in user.
+pub async fn get_link_login_identity_url_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<SocialLoginProviderPath>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+    Ok(HttpResponse::Ok().json(&app.generate_social_login_url(path.provider, Some(session.user.id)).await?))
+}
+
+pub async fn delete_login_identity_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<LoginIdentityPath>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+
+    // Don't let the user lock themselves out of their account.
+    let identities = get_login_identities_for_user(&*app.pool, session.user.id).await?;
+    if !identities.iter().any(|x| { x.id == path.identity_id }) {
+        return Err(SquadOvError::NotFound);
+    }
+
+    if identities.len() == 1 && !user_has_password_login(&app, &session.user.email).await? {
+        return logged_error!(SquadOvError::BadRequest);
+    }
+
+    sqlx::query!(
+        "
+        DELETE FROM squadov.user_login_identities
+        WHERE id = $1
+            AND user_id = $2
+        ",
+        path.identity_id,
+        session.user.id,
+    )
+        .execute(&*app.pool)
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    // These need a database with all the migrations applied. Run them with
+    // SQUADOV_TEST_DATABASE_URL set and `cargo test -- --ignored`.
+    use super::*;
+    use sqlx::postgres::{PgPool, PgPoolOptions};
+
+    async fn test_pool() -> PgPool {
+        let url = std::env::var("SQUADOV_TEST_DATABASE_URL").expect("SQUADOV_TEST_DATABASE_URL must be set to run the social login tests");
+        PgPoolOptions::new().max_connections(1).connect(&url).await.unwrap()
+    }
+
+    async fn create_test_user(pool: &PgPool) -> i64 {
+        let suffix = Uuid::new_v4().to_simple().to_string();
+        sqlx::query_scalar(
+            "
+            INSERT INTO squadov.users (email, username, verified, uuid, local_encryption_key)
+            VALUES ($1, $2, TRUE, gen_random_uuid(), 'fixture')
+            RETURNING id
+            "
+        )
+            .bind(format!("social-test-{}@squadov.gg", &suffix))
+            .bind(format!("social-test-{}", &suffix))
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn delete_test_user(pool: &PgPool, user_id: i64) {
+        sqlx::query("DELETE FROM squadov.users WHERE id = $1")
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_link_login_identity_already_linked() {
+        let pool = test_pool().await;
+        let owner = create_test_user(&pool).await;
+        let other = create_test_user(&pool).await;
+        let identity = SocialIdentity{
+            provider: SocialLoginProvider::Discord,
+            provider_user_id: Uuid::new_v4().to_string(),
+            display_name: String::from("social-test#0001"),
+            email: None,
+        };
+
+        link_login_identity_to_user(&pool, owner, &identity).await.unwrap();
+        // Linking it again to the same user is fine but it can't be stolen by someone else.
+        link_login_identity_to_user(&pool, owner, &identity).await.unwrap();
+        assert!(matches!(link_login_identity_to_user(&pool, other, &identity).await, Err(SquadOvError::Duplicate)));
+
+        assert_eq!(find_user_id_for_login_identity(&pool, identity.provider, &identity.provider_user_id).await.unwrap(), Some(owner));
+        assert_eq!(get_login_identities_for_user(&pool, owner).await.unwrap().len(), 1);
+        assert!(get_login_identities_for_user(&pool, other).await.unwrap().is_empty());
+
+        delete_test_user(&pool, owner).await;
+        delete_test_user(&pool, other).await;
+    }
+}
@@ -1,7 +1,6 @@
 use actix_web::{HttpResponse, HttpRequest, web, HttpMessage};
 use serde::{Serialize,Deserialize};
 use crate::api;
-use crate::api::fusionauth;
 use crate::logged_error;
 use squadov_common;
 use super::SquadOVSession;
@@ -18,40 +17,24 @@ pub struct CheckEmailVerifiedResponse {
     verified: bool
 }
 
-async fn verify_email(fa: &fusionauth::FusionAuthClient, verification_id: &str) -> Result<(), squadov_common::SquadOvError> {
-    match fa.verify_email(verification_id).await {
-        Ok(_) => Ok(()),
-        Err(err) => Err(squadov_common::SquadOvError::InternalError(format!("Failed to verify email: {}", err))),
-    }
-}
-
-async fn resend_verify_email(fa: &fusionauth::FusionAuthClient, email: &str) -> Result<(), squadov_common::SquadOvError> {
-    match fa.resend_verify_email(email).await {
-        Ok(_) => Ok(()),
-        Err(err) => Err(squadov_common::SquadOvError::InternalError(format!("Failed to resend verification email: {}", err))),
-    }
-}
-
 /// Verifies the user's email. This needs to
-///  1) Communicate with the backend FusionAuth service to verify the email there.
+///  1) Communicate with the identity provider to verify the email there.
 ///  2) Mark the user's email as being verified in the database.
 /// 
 /// Possible Responses:
 /// * 200 - Email verification succeded.
 /// * 500 - Email verification failed.
 pub async fn verify_email_handler(data : web::Json<VerifyEmailData>, app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, squadov_common::SquadOvError> {
-    // Get the user for this verification ID.
-    // Note that we can't assume that the user is logged in here.
-    let user = match app.clients.fusionauth.find_user_from_email_verification_id(&data.verification_id).await {
-        Ok(u) => u,
-        Err(err) => return logged_error!(squadov_common::SquadOvError::InternalError(format!("Failed to get user from verification ID: {}", err))),
+    // Note that we can't assume that the user is logged in here so we rely on the
+    // identity provider to tell us which user was verified.
+    let email = match app.clients.identity.verify_email(&data.verification_id).await {
+        Ok(e) => e,
+        Err(err) => return logged_error!(err),
     };
 
-    verify_email(&app.clients.fusionauth, &data.verification_id).await?;
-
     // If we get to this point it means the verification was successful!
     // Make the user with the given email as being verified.
-    match app.users.mark_user_email_verified_from_email(&user.email, &app.pool).await {
+    match app.users.mark_user_email_verified_from_email(&email, &app.pool).await {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
         Err(err) => logged_error!(squadov_common::SquadOvError::InternalError(format!("Mark User Email Verified {}", err))),
     }
@@ -88,7 +71,7 @@ pub async fn resend_verify_email_handler(app : web::Data<Arc<api::ApiApplication
         None => return logged_error!(squadov_common::SquadOvError::Unauthorized),
     };
 
-    match resend_verify_email(&app.clients.fusionauth, &session.user.email).await {
+    match app.clients.identity.resend_verify_email(&session.user.email).await {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
         Err(err) => logged_error!(err),
     }
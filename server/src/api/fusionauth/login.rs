@@ -56,6 +56,26 @@ pub struct FusionAuthMfaLoginInput {
     application_id: String,
 }
 
+#[derive(Serialize,Debug,Clone)]
+#[serde(rename_all="camelCase")]
+struct FusionAuthPasswordlessStartInput {
+    login_id: String,
+    application_id: String,
+}
+
+#[derive(Deserialize,Debug)]
+struct FusionAuthPasswordlessStartResult {
+    code: String,
+}
+
+#[derive(Serialize,Debug,Clone)]
+#[serde(rename_all="camelCase")]
+struct FusionAuthPasswordlessLoginInput {
+    code: String,
+    application_id: String,
+    ip_address: String,
+}
+
 impl super::FusionAuthClient {
     pub fn build_login_input(&self, username : String, password: String, ip : Option<&str>) -> FusionAuthLoginInput {
         return FusionAuthLoginInput{
@@ -114,7 +134,56 @@ impl super::FusionAuthClient {
             .json(&input)
             .send()
             .await;
+        self.handle_login_response(res).await
+    }
+
+    /// Logs in a user without their password. This is only for users that we've already authenticated some other way
+    /// (e.g. via a third party). Users with MFA enabled will still get a FusionAuthLoginError::TwoFactor.
+    pub async fn passwordless_login(&self, login_id: &str, ip : Option<&str>) -> Result<FusionAuthLoginResult, FusionAuthLoginError> {
+        let res = self.client.post(self.build_url("/api/passwordless/start").as_str())
+            .json(&FusionAuthPasswordlessStartInput{
+                login_id: login_id.to_string(),
+                application_id: self.cfg.application_id.clone(),
+            })
+            .send()
+            .await;
+
+        let code = match res {
+            Ok(resp) => {
+                let status = resp.status();
+                match status.as_u16() {
+                    200 => match resp.json::<FusionAuthPasswordlessStartResult>().await {
+                        Ok(j) => j.code,
+                        Err(err) => return Err(FusionAuthLoginError::Generic{
+                            code: 0,
+                            message: format!("{}", err)
+                        }),
+                    },
+                    404 => return Err(FusionAuthLoginError::Auth),
+                    _ => return Err(FusionAuthLoginError::Generic{
+                        code: status.as_u16(),
+                        message: format!("Fusion Auth Error: {}", resp.text().await.unwrap()),
+                    }),
+                }
+            },
+            Err(err) => return Err(FusionAuthLoginError::Generic{
+                code: 0,
+                message: format!("{}", err)
+            }),
+        };
+
+        let res = self.client.post(self.build_url("/api/passwordless/login").as_str())
+            .json(&FusionAuthPasswordlessLoginInput{
+                code,
+                application_id: self.cfg.application_id.clone(),
+                ip_address: ip.unwrap_or("").to_string(),
+            })
+            .send()
+            .await;
+        self.handle_login_response(res).await
+    }
 
+    async fn handle_login_response(&self, res: Result<reqwest::Response, reqwest::Error>) -> Result<FusionAuthLoginResult, FusionAuthLoginError> {
         match res {
             Ok(resp) => {
                 let status = resp.status();
@@ -6,6 +6,8 @@ use std::collections::HashMap;
 pub struct FusionAuthRegisterInput {
     registration: super::FusionAuthRegistration,
     user: super::FusionSingleAppAuthUser,
+    #[serde(rename = "skipVerification")]
+    skip_verification: bool,
 }
 
 #[derive(Deserialize)]
@@ -60,9 +62,17 @@ impl super::FusionAuthClient {
                 password: Some(password),
                 username: username.clone(),
             },
+            skip_verification: false,
         }
     }
 
+    // For users whose email was already verified by a third party so there's no need to send a verification email.
+    pub fn build_verified_register_input(&self, username : String, email : String, password: String) -> FusionAuthRegisterInput {
+        let mut input = self.build_register_input(username, email, password);
+        input.skip_verification = true;
+        input
+    }
+
     pub async fn register(&self, input : FusionAuthRegisterInput) -> Result<FusionAuthRegisterResult, SquadOvError> {
         let res = self.client.post(self.build_url("/api/user/registration").as_str())
             .json(&input)
@@ -1,7 +1,21 @@
 mod stats;
+mod matches;
+mod mutations;
+mod subscriptions;
+mod ws;
+
+pub use ws::*;
 
 use crate::api;
-use squadov_common::stats::StatPermission;
+use squadov_common::{
+    SquadOvError,
+    stats::StatPermission,
+    squad::{
+        status::UserActivityStatusTracker,
+        events::NewMatchEventHub,
+    },
+    twitch::streams::TwitchStreamEventHub,
+};
 use actix_web::{web, HttpResponse, HttpRequest, HttpMessage};
 use juniper::http::GraphQLRequest;
 use juniper::http::graphiql::graphiql_source;
@@ -10,10 +24,22 @@ use juniper::FieldResult;
 
 pub struct GraphqlContext {
     app: Arc<api::ApiApplication>,
-    session: Option<api::auth::SquadOVSession>
+    session: Option<api::auth::SquadOVSession>,
+    status_tracker: Arc<UserActivityStatusTracker>,
+    match_events: Arc<NewMatchEventHub>,
+    stream_events: Arc<TwitchStreamEventHub>,
 }
 
 impl GraphqlContext {
+    // Anything that isn't a stat (matches, clips, mutations, subscriptions) is only available to the user themselves and never to share tokens.
+    pub fn user_session(&self) -> FieldResult<&api::auth::SquadOVSession> {
+        let session = self.session.as_ref().ok_or(SquadOvError::Unauthorized)?;
+        if session.share_token.is_some() {
+            return Err(juniper::FieldError::new("Share tokens can not be used for this operation.", juniper::Value::Null));
+        }
+        Ok(session)
+    }
+
     pub fn has_access_to_stat(&self, stats: &[StatPermission]) -> FieldResult<bool> {
         Ok(stats.iter().all(|x| {
             if self.session.is_none() {
@@ -65,14 +91,24 @@ impl GraphqlRootQuery {
             user_id, 
         })
     }
+
+    async fn recent_matches(context: &GraphqlContext, params: matches::GraphqlRecentMatchParams) -> FieldResult<Vec<matches::GraphqlRecentMatch>> {
+        let session = context.user_session()?;
+        Ok(context.app.get_graphql_recent_matches(session.user.id, &params).await?)
+    }
+
+    async fn recent_clips(context: &GraphqlContext, params: matches::GraphqlRecentMatchParams) -> FieldResult<Vec<matches::GraphqlClip>> {
+        let session = context.user_session()?;
+        Ok(context.app.get_graphql_recent_clips(session.user.id, &params).await?)
+    }
 }
 
-pub type GraphqlSchema = juniper::RootNode<'static, GraphqlRootQuery, juniper::EmptyMutation<GraphqlContext>, juniper::EmptySubscription<GraphqlContext>>;
+pub type GraphqlSchema = juniper::RootNode<'static, GraphqlRootQuery, mutations::GraphqlRootMutation, subscriptions::GraphqlRootSubscription>;
 pub fn create_schema() -> GraphqlSchema {
-    GraphqlSchema::new(GraphqlRootQuery{}, juniper::EmptyMutation::new(), juniper::EmptySubscription::new())
+    GraphqlSchema::new(GraphqlRootQuery{}, mutations::GraphqlRootMutation{}, subscriptions::GraphqlRootSubscription{})
 }
 
-pub async fn graphql_handler(app : web::Data<Arc<api::ApiApplication>>, data: web::Json<GraphQLRequest>, req: HttpRequest) -> Result<HttpResponse, squadov_common::SquadOvError> {
+pub async fn graphql_handler(app : web::Data<Arc<api::ApiApplication>>, status_tracker: web::Data<Arc<UserActivityStatusTracker>>, match_events: web::Data<Arc<NewMatchEventHub>>, stream_events: web::Data<Arc<TwitchStreamEventHub>>, data: web::Json<GraphQLRequest>, req: HttpRequest) -> Result<HttpResponse, squadov_common::SquadOvError> {
     let context = Arc::new(GraphqlContext{
         app: app.get_ref().clone(),
         session: {
@@ -82,6 +118,9 @@ pub async fn graphql_handler(app : web::Data<Arc<api::ApiApplication>>, data: we
                 None => return Err(squadov_common::SquadOvError::Unauthorized),
             }
         },
+        status_tracker: status_tracker.get_ref().clone(),
+        match_events: match_events.get_ref().clone(),
+        stream_events: stream_events.get_ref().clone(),
     });
     let resp = data.execute(&app.schema, &context).await;
     Ok(HttpResponse::Ok().json(&resp))
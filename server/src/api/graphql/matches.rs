@@ -0,0 +1,246 @@
+use crate::api;
+use crate::api::v1::RecentMatchQuery;
+use squadov_common::{
+    SquadOvError,
+    SquadOvGames,
+    elastic::vod::ESVodDocument,
+    matches::{self, RecentMatchPov},
+    vod::{VodClip, VodTag},
+};
+use elasticsearch_dsl::{Sort, SortOrder};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+
+const DEFAULT_RECENT_LIMIT: i32 = 20;
+const MAX_RECENT_LIMIT: i32 = 100;
+
+#[derive(juniper::GraphQLInputObject)]
+pub(crate) struct GraphqlRecentMatchParams {
+    games: Option<Vec<i32>>,
+    tags: Option<Vec<String>>,
+    squads: Option<Vec<String>>,
+    users: Option<Vec<String>>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    only_favorite: Option<bool>,
+    only_watchlist: Option<bool>,
+    offset: Option<i32>,
+    limit: Option<i32>,
+}
+
+impl GraphqlRecentMatchParams {
+    fn range(&self) -> (i64, i64) {
+        let start = self.offset.unwrap_or(0).max(0) as i64;
+        (start, start + self.limit.unwrap_or(DEFAULT_RECENT_LIMIT).clamp(1, MAX_RECENT_LIMIT) as i64)
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+pub(crate) struct GraphqlVodTag {
+    tag_id: String,
+    tag: String,
+    count: i32,
+    is_self: bool,
+}
+
+impl From<VodTag> for GraphqlVodTag {
+    fn from(t: VodTag) -> Self {
+        Self {
+            tag_id: t.tag_id.to_string(),
+            tag: t.tag,
+            count: t.count as i32,
+            is_self: t.is_self,
+        }
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+pub(crate) struct GraphqlMatchPov {
+    video_uuid: Uuid,
+    user_id: String,
+    username: String,
+    tm: DateTime<Utc>,
+    favorite_reason: Option<String>,
+    is_watchlist: bool,
+    is_local: bool,
+    tags: Vec<GraphqlVodTag>,
+    // Game specific summary of the match from this user's perspective as JSON (same format as the REST API).
+    details: Option<String>,
+}
+
+impl TryFrom<RecentMatchPov> for GraphqlMatchPov {
+    type Error = SquadOvError;
+    fn try_from(p: RecentMatchPov) -> Result<Self, Self::Error> {
+        let details = if let Some(x) = p.aimlab_task.as_ref() {
+            Some(serde_json::to_string(x)?)
+        } else if let Some(x) = p.lol_match.as_ref() {
+            Some(serde_json::to_string(x)?)
+        } else if let Some(x) = p.tft_match.as_ref() {
+            Some(serde_json::to_string(x)?)
+        } else if let Some(x) = p.valorant_match.as_ref() {
+            Some(serde_json::to_string(x)?)
+        } else if let Some(x) = p.wow_challenge.as_ref() {
+            Some(serde_json::to_string(x)?)
+        } else if let Some(x) = p.wow_encounter.as_ref() {
+            Some(serde_json::to_string(x)?)
+        } else if let Some(x) = p.wow_arena.as_ref() {
+            Some(serde_json::to_string(x)?)
+        } else if let Some(x) = p.wow_instance.as_ref() {
+            Some(serde_json::to_string(x)?)
+        } else if let Some(x) = p.csgo_match.as_ref() {
+            Some(serde_json::to_string(x)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            video_uuid: p.vod.video_tracks.first().ok_or(SquadOvError::NotFound)?.metadata.video_uuid.clone(),
+            user_id: p.user_id.to_string(),
+            username: p.username,
+            tm: p.tm,
+            favorite_reason: p.favorite_reason,
+            is_watchlist: p.is_watchlist,
+            is_local: p.is_local,
+            tags: p.tags.into_iter().map(GraphqlVodTag::from).collect(),
+            details,
+        })
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+pub(crate) struct GraphqlRecentMatch {
+    match_uuid: Uuid,
+    game: i32,
+    povs: Vec<GraphqlMatchPov>,
+}
+
+#[derive(juniper::GraphQLObject)]
+pub(crate) struct GraphqlClip {
+    video_uuid: Uuid,
+    match_uuid: Option<Uuid>,
+    title: String,
+    description: String,
+    clipper: String,
+    game: i32,
+    tm: DateTime<Utc>,
+    views: i32,
+    reacts: i32,
+    comments: i32,
+    favorite_reason: Option<String>,
+    is_watchlist: bool,
+    tags: Vec<GraphqlVodTag>,
+    published: bool,
+}
+
+impl From<VodClip> for GraphqlClip {
+    fn from(c: VodClip) -> Self {
+        Self {
+            video_uuid: c.clip.video_uuid,
+            match_uuid: c.clip.match_uuid,
+            title: c.title,
+            description: c.description,
+            clipper: c.clipper,
+            game: c.game as i32,
+            tm: c.tm,
+            views: c.views as i32,
+            reacts: c.reacts as i32,
+            comments: c.comments as i32,
+            favorite_reason: c.favorite_reason,
+            is_watchlist: c.is_watchlist,
+            tags: c.tags.into_iter().map(GraphqlVodTag::from).collect(),
+            published: c.published,
+        }
+    }
+}
+
+impl api::ApiApplication {
+    async fn graphql_params_to_recent_match_query(&self, user_id: i64, params: &GraphqlRecentMatchParams) -> Result<RecentMatchQuery, SquadOvError> {
+        // Same squad restrictions as the REST API: users can only filter on squads they're actually a part of.
+        let available_user_squads: HashSet<i64> = self.get_user_squads(user_id).await?.into_iter().map(|x| { x.squad.id }).collect();
+        let (squads, must_match_squads) = if let Some(squads) = params.squads.as_ref() {
+            (
+                squads.iter()
+                    .map(|x| { x.parse::<i64>() })
+                    .collect::<Result<Vec<i64>, _>>()?
+                    .into_iter()
+                    .filter(|x| { available_user_squads.contains(x) })
+                    .collect::<Vec<_>>(),
+                true,
+            )
+        } else {
+            (available_user_squads.into_iter().collect::<Vec<_>>(), false)
+        };
+
+        Ok(RecentMatchQuery{
+            games: if let Some(games) = params.games.as_ref() {
+                Some(games.iter().map(|x| {
+                    SquadOvGames::try_from(*x).map_err(|_| { SquadOvError::BadRequest })
+                }).collect::<Result<Vec<_>, SquadOvError>>()?)
+            } else {
+                None
+            },
+            tags: params.tags.clone(),
+            squads: Some(squads),
+            must_match_squads,
+            users: if let Some(users) = params.users.as_ref() {
+                Some(users.iter().map(|x| { x.parse::<i64>() }).collect::<Result<Vec<_>, _>>()?)
+            } else {
+                None
+            },
+            time_start: params.start.map(|x| { x.timestamp_millis() }),
+            time_end: params.end.map(|x| { x.timestamp_millis() }),
+            only_favorite: params.only_favorite.unwrap_or(false),
+            only_watchlist: params.only_watchlist.unwrap_or(false),
+            ..RecentMatchQuery::default()
+        })
+    }
+
+    pub(crate) async fn get_graphql_recent_matches(&self, user_id: i64, params: &GraphqlRecentMatchParams) -> Result<Vec<GraphqlRecentMatch>, SquadOvError> {
+        if !self.is_user_allowed_to_es_search(user_id).await? {
+            return Ok(vec![]);
+        }
+
+        let (start, end) = params.range();
+        let filter = self.graphql_params_to_recent_match_query(user_id, params).await?;
+
+        // Unlike the REST API, pagination here is over VODs rather than matches so a single search is sufficient.
+        let es_search = filter.to_es_search(user_id, None, false)
+            .from(start)
+            .size(end - start)
+            .sort(vec![
+                Sort::new("vod.endTime")
+                    .order(SortOrder::Desc)
+            ]);
+
+        let documents: Vec<ESVodDocument> = self.es_api.search_documents(&self.config.elasticsearch.vod_index_read, serde_json::to_value(es_search)?).await?;
+        Ok(
+            matches::vod_documents_to_recent_matches(documents, user_id, "")
+                .into_iter()
+                .map(|m| {
+                    Ok(GraphqlRecentMatch{
+                        match_uuid: m.match_uuid,
+                        game: m.game as i32,
+                        povs: m.povs.into_iter().map(GraphqlMatchPov::try_from).collect::<Result<Vec<_>, SquadOvError>>()?,
+                    })
+                })
+                .collect::<Result<Vec<_>, SquadOvError>>()?
+        )
+    }
+
+    pub(crate) async fn get_graphql_recent_clips(&self, user_id: i64, params: &GraphqlRecentMatchParams) -> Result<Vec<GraphqlClip>, SquadOvError> {
+        if !self.is_user_allowed_to_es_search(user_id).await? {
+            return Ok(vec![]);
+        }
+
+        let (start, end) = params.range();
+        let filter = self.graphql_params_to_recent_match_query(user_id, params).await?;
+        Ok(
+            self.list_user_accessible_clips(user_id, start, end - start, &filter, "").await?
+                .into_iter()
+                .map(GraphqlClip::from)
+                .collect()
+        )
+    }
+}
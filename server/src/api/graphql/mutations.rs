@@ -0,0 +1,91 @@
+use crate::api::access::{self, AccessChecker};
+use super::{GraphqlContext, matches::GraphqlVodTag};
+use squadov_common::SquadOvError;
+use juniper::FieldResult;
+use uuid::Uuid;
+
+impl GraphqlContext {
+    async fn check_vod_access(&self, video_uuid: &Uuid) -> FieldResult<()> {
+        let session = self.user_session()?;
+        let checker = access::VodAccessChecker{
+            must_be_vod_owner: false,
+            obtainer: access::VodPathObtainer{
+                video_uuid_key: "video_uuid",
+            },
+        };
+
+        if !checker.check(self.app.clone(), Some(session), access::VodAccessBasicData{video_uuid: video_uuid.clone()}).await? {
+            return Err(SquadOvError::Unauthorized.into());
+        }
+        Ok(())
+    }
+}
+
+pub struct GraphqlRootMutation {
+}
+
+#[juniper::graphql_object(
+    Context = GraphqlContext,
+)]
+impl GraphqlRootMutation {
+    async fn add_vod_tags(context: &GraphqlContext, video_uuid: Uuid, tags: Vec<String>) -> FieldResult<Vec<GraphqlVodTag>> {
+        context.check_vod_access(&video_uuid).await?;
+        let session = context.user_session()?;
+
+        let mut tx = context.app.pool.begin().await.map_err(SquadOvError::from)?;
+        context.app.create_tags(&mut tx, &tags).await?;
+        let ret_tags = context.app.add_tags_to_video(&mut tx, &video_uuid, &tags, session.user.id).await?;
+        tx.commit().await.map_err(SquadOvError::from)?;
+
+        context.app.es_itf.request_update_vod_tags(video_uuid).await?;
+        Ok(ret_tags.into_iter().map(GraphqlVodTag::from).collect())
+    }
+
+    async fn remove_vod_tag(context: &GraphqlContext, video_uuid: Uuid, tag_id: String) -> FieldResult<bool> {
+        context.check_vod_access(&video_uuid).await?;
+        let session = context.user_session()?;
+        context.app.remove_tag_from_video_for_user(tag_id.parse::<i64>()?, &video_uuid, session.user.id).await?;
+        context.app.es_itf.request_update_vod_tags(video_uuid).await?;
+        Ok(true)
+    }
+
+    async fn favorite_match(context: &GraphqlContext, match_uuid: Uuid, reason: String) -> FieldResult<bool> {
+        let session = context.user_session()?;
+        context.app.add_match_favorite_for_user(&match_uuid, session.user.id, &reason).await?;
+        Ok(true)
+    }
+
+    async fn unfavorite_match(context: &GraphqlContext, match_uuid: Uuid) -> FieldResult<bool> {
+        let session = context.user_session()?;
+        context.app.remove_match_favorite_for_user(&match_uuid, session.user.id).await?;
+        Ok(true)
+    }
+
+    async fn favorite_vod(context: &GraphqlContext, video_uuid: Uuid, reason: String) -> FieldResult<bool> {
+        context.check_vod_access(&video_uuid).await?;
+        let session = context.user_session()?;
+        context.app.add_vod_favorite_for_user(&video_uuid, session.user.id, &reason).await?;
+        Ok(true)
+    }
+
+    async fn unfavorite_vod(context: &GraphqlContext, video_uuid: Uuid) -> FieldResult<bool> {
+        context.check_vod_access(&video_uuid).await?;
+        let session = context.user_session()?;
+        context.app.remove_vod_favorite_for_user(&video_uuid, session.user.id).await?;
+        Ok(true)
+    }
+
+    async fn add_to_watchlist(context: &GraphqlContext, video_uuid: Uuid) -> FieldResult<bool> {
+        context.check_vod_access(&video_uuid).await?;
+        let session = context.user_session()?;
+        context.app.add_vod_watchlist_for_user(&video_uuid, session.user.id).await?;
+        Ok(true)
+    }
+
+    async fn remove_from_watchlist(context: &GraphqlContext, video_uuid: Uuid) -> FieldResult<bool> {
+        context.check_vod_access(&video_uuid).await?;
+        let session = context.user_session()?;
+        context.app.remove_vod_watchlist_for_user(&video_uuid, session.user.id).await?;
+        Ok(true)
+    }
+}
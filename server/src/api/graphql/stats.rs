@@ -1,5 +1,10 @@
 mod aimlab;
+mod valorant;
+mod csgo;
+mod lol;
+mod wow;
 use squadov_common;
+use chrono::{DateTime, Utc};
 
 #[derive(juniper::GraphQLEnum)]
 pub(crate) enum GraphqlStatGroupFunction {
@@ -38,6 +43,12 @@ pub(crate) fn stat_sort_direction_to_sql(d: &GraphqlSortDirection) -> squadov_co
     }
 }
 
+#[derive(juniper::GraphQLInputObject)]
+pub(crate) struct GraphqlGameStatsParams {
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+}
+
 pub struct GraphqlAllStats {
     pub user_id: i64
 }
@@ -51,4 +62,28 @@ impl GraphqlAllStats {
             user_id: self.user_id,
         }
     }
+
+    fn valorant(&self) -> valorant::GraphqlValorantStats {
+        valorant::GraphqlValorantStats{
+            user_id: self.user_id,
+        }
+    }
+
+    fn csgo(&self) -> csgo::GraphqlCsgoStats {
+        csgo::GraphqlCsgoStats{
+            user_id: self.user_id,
+        }
+    }
+
+    fn lol(&self) -> lol::GraphqlLolStats {
+        lol::GraphqlLolStats{
+            user_id: self.user_id,
+        }
+    }
+
+    fn wow(&self) -> wow::GraphqlWowStats {
+        wow::GraphqlWowStats{
+            user_id: self.user_id,
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,72 @@
+use crate::api;
+use squadov_common::stats::{self, StatPermission};
+use squadov_common::SquadOvError;
+use juniper::FieldResult;
+
+impl api::ApiApplication {
+    async fn get_csgo_map_stats(&self, user_id: i64, params: &super::GraphqlGameStatsParams) -> Result<Vec<stats::CsgoStatMapData>, SquadOvError> {
+        // Each view may have multiple event containers (e.g. GSI and demo) so only use the latest one to avoid double counting rounds.
+        Ok(sqlx::query_as::<_, stats::CsgoStatMapData>(
+            "
+            SELECT
+                cmv.map AS \"map\",
+                COUNT(DISTINCT cmv.view_uuid)::INTEGER AS \"games\",
+                COUNT(cecrps.round_num)::INTEGER AS \"rounds\",
+                COUNT(cecrps.round_num) FILTER (WHERE cecr.winning_team = cecrps.team)::INTEGER AS \"round_wins\",
+                COUNT(cecrps.round_num) FILTER (WHERE cecr.winning_team = cecrps.team)::DOUBLE PRECISION / GREATEST(COUNT(cecrps.round_num), 1) AS \"round_win_rate\",
+                SUM(cecrps.kills)::INTEGER AS \"kills\",
+                SUM(cecrps.deaths)::INTEGER AS \"deaths\",
+                SUM(cecrps.assists)::INTEGER AS \"assists\",
+                (SUM(cecrps.kills) + SUM(cecrps.assists))::DOUBLE PRECISION / GREATEST(SUM(cecrps.deaths), 1) AS \"kda\",
+                COALESCE(SUM(cecrps.headshot_kills), 0)::DOUBLE PRECISION / GREATEST(SUM(cecrps.kills), 1) AS \"headshot_pct\",
+                COALESCE(SUM(cecrps.damage), 0)::DOUBLE PRECISION / GREATEST(COUNT(cecrps.round_num), 1) AS \"adr\"
+            FROM squadov.csgo_match_views AS cmv
+            INNER JOIN squadov.csgo_event_container AS cec
+                ON cec.id = (
+                    SELECT MAX(id)
+                    FROM squadov.csgo_event_container
+                    WHERE view_uuid = cmv.view_uuid
+                )
+            INNER JOIN squadov.csgo_event_container_players AS cecp
+                ON cecp.container_id = cec.id
+            INNER JOIN squadov.steam_user_links AS sul
+                ON sul.steam_id = cecp.steam_id
+                    AND sul.user_id = cmv.user_id
+            INNER JOIN squadov.csgo_event_container_round_player_stats AS cecrps
+                ON cecrps.container_id = cec.id
+                    AND cecrps.user_id = cecp.user_id
+            INNER JOIN squadov.csgo_event_container_rounds AS cecr
+                ON cecr.container_id = cecrps.container_id
+                    AND cecr.round_num = cecrps.round_num
+            WHERE cmv.user_id = $1
+                AND cmv.match_uuid IS NOT NULL
+                AND ($2::TIMESTAMPTZ IS NULL OR cmv.start_time >= $2)
+                AND ($3::TIMESTAMPTZ IS NULL OR cmv.start_time <= $3)
+            GROUP BY cmv.map
+            ORDER BY \"games\" DESC
+            "
+        )
+            .bind(user_id)
+            .bind(params.start)
+            .bind(params.end)
+            .fetch_all(&*self.pool)
+            .await?)
+    }
+}
+
+pub struct GraphqlCsgoStats {
+    pub user_id: i64
+}
+
+#[juniper::graphql_object(
+    Context = api::graphql::GraphqlContext,
+)]
+impl GraphqlCsgoStats {
+    async fn maps(&self, context: &api::graphql::GraphqlContext, params: super::GraphqlGameStatsParams) -> FieldResult<Vec<stats::CsgoStatMapData>> {
+        if !context.has_access_to_stat(&[StatPermission::CsgoMaps])? {
+            return Err(juniper::FieldError::new("No CS:GO map access.", juniper::Value::Null));
+        }
+
+        Ok(context.app.get_csgo_map_stats(self.user_id, &params).await?)
+    }
+}
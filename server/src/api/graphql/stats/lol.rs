@@ -0,0 +1,59 @@
+use crate::api;
+use squadov_common::stats::{self, StatPermission};
+use squadov_common::SquadOvError;
+use juniper::FieldResult;
+
+impl api::ApiApplication {
+    async fn get_lol_champion_stats(&self, user_id: i64, params: &super::GraphqlGameStatsParams) -> Result<Vec<stats::LolStatChampionData>, SquadOvError> {
+        Ok(sqlx::query_as::<_, stats::LolStatChampionData>(
+            "
+            SELECT
+                lmp.champion_id AS \"champion_id\",
+                COUNT(lmp.match_uuid)::INTEGER AS \"games\",
+                COUNT(lmp.match_uuid) FILTER (WHERE lmp.win)::INTEGER AS \"wins\",
+                SUM(lmp.kills)::INTEGER AS \"kills\",
+                SUM(lmp.deaths)::INTEGER AS \"deaths\",
+                SUM(lmp.assists)::INTEGER AS \"assists\",
+                (SUM(lmp.kills) + SUM(lmp.assists))::DOUBLE PRECISION / GREATEST(SUM(lmp.deaths), 1) AS \"kda\",
+                SUM(lmp.total_minions_killed + lmp.neutral_minions_killed)::DOUBLE PRECISION / GREATEST(SUM(lmi.game_duration) / 60.0, 1.0) AS \"cs_per_min\",
+                SUM(lmp.total_damage_dealt_to_champions)::DOUBLE PRECISION / GREATEST(SUM(lmi.game_duration) / 60.0, 1.0) AS \"damage_per_min\",
+                AVG(lmp.vision_score)::DOUBLE PRECISION AS \"avg_vision_score\"
+            FROM squadov.lol_match_participants AS lmp
+            INNER JOIN squadov.lol_match_participant_identities AS lmpi
+                ON lmpi.match_uuid = lmp.match_uuid
+                    AND lmpi.participant_id = lmp.participant_id
+            INNER JOIN squadov.lol_match_info AS lmi
+                ON lmi.match_uuid = lmp.match_uuid
+            INNER JOIN squadov.riot_account_links AS ral
+                ON ral.puuid = lmpi.puuid
+            WHERE ral.user_id = $1
+                AND ($2::TIMESTAMPTZ IS NULL OR lmi.game_creation >= $2)
+                AND ($3::TIMESTAMPTZ IS NULL OR lmi.game_creation <= $3)
+            GROUP BY lmp.champion_id
+            ORDER BY \"games\" DESC
+            "
+        )
+            .bind(user_id)
+            .bind(params.start)
+            .bind(params.end)
+            .fetch_all(&*self.pool)
+            .await?)
+    }
+}
+
+pub struct GraphqlLolStats {
+    pub user_id: i64
+}
+
+#[juniper::graphql_object(
+    Context = api::graphql::GraphqlContext,
+)]
+impl GraphqlLolStats {
+    async fn champions(&self, context: &api::graphql::GraphqlContext, params: super::GraphqlGameStatsParams) -> FieldResult<Vec<stats::LolStatChampionData>> {
+        if !context.has_access_to_stat(&[StatPermission::LolChampions])? {
+            return Err(juniper::FieldError::new("No LoL champion access.", juniper::Value::Null));
+        }
+
+        Ok(context.app.get_lol_champion_stats(self.user_id, &params).await?)
+    }
+}
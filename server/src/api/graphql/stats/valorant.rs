@@ -0,0 +1,83 @@
+use crate::api;
+use squadov_common::stats::{self, StatPermission};
+use squadov_common::SquadOvError;
+use juniper::FieldResult;
+
+enum ValorantStatGrouping {
+    Agent,
+    Map,
+}
+
+fn valorant_grouping_to_sql(group: &ValorantStatGrouping) -> &'static str {
+    match group {
+        ValorantStatGrouping::Agent => "vmp.character_id",
+        ValorantStatGrouping::Map => "vm.map_id",
+    }
+}
+
+impl api::ApiApplication {
+    async fn get_valorant_aggregate_stats(&self, user_id: i64, group: &ValorantStatGrouping, params: &super::GraphqlGameStatsParams) -> Result<Vec<stats::ValorantStatAggregateData>, SquadOvError> {
+        Ok(sqlx::query_as::<_, stats::ValorantStatAggregateData>(
+            &format!(
+                "
+                SELECT
+                    COALESCE({group}, '') AS \"key\",
+                    COUNT(vvpms.match_uuid)::INTEGER AS \"games\",
+                    COUNT(vvpms.match_uuid) FILTER (WHERE vvpms.won)::INTEGER AS \"wins\",
+                    SUM(vvpms.rounds_played)::INTEGER AS \"rounds\",
+                    SUM(vvpms.kills)::INTEGER AS \"kills\",
+                    SUM(vvpms.deaths)::INTEGER AS \"deaths\",
+                    SUM(vvpms.assists)::INTEGER AS \"assists\",
+                    (SUM(vvpms.kills) + SUM(vvpms.assists))::DOUBLE PRECISION / GREATEST(SUM(vvpms.deaths), 1) AS \"kda\",
+                    SUM(vvpms.headshots)::DOUBLE PRECISION / GREATEST(SUM(vvpms.headshots + vvpms.bodyshots + vvpms.legshots), 1) AS \"headshot_pct\",
+                    SUM(vvpms.total_combat_score)::DOUBLE PRECISION / GREATEST(SUM(vvpms.rounds_played), 1) AS \"avg_combat_score\",
+                    SUM(vvpms.total_damage)::DOUBLE PRECISION / GREATEST(SUM(vvpms.rounds_played), 1) AS \"avg_damage_per_round\"
+                FROM squadov.view_valorant_player_match_stats AS vvpms
+                INNER JOIN squadov.valorant_match_players AS vmp
+                    ON vmp.match_uuid = vvpms.match_uuid
+                        AND vmp.puuid = vvpms.puuid
+                INNER JOIN squadov.valorant_matches AS vm
+                    ON vm.match_uuid = vvpms.match_uuid
+                INNER JOIN squadov.riot_account_links AS ral
+                    ON ral.puuid = vvpms.puuid
+                WHERE ral.user_id = $1
+                    AND ($2::TIMESTAMPTZ IS NULL OR vm.server_start_time_utc >= $2)
+                    AND ($3::TIMESTAMPTZ IS NULL OR vm.server_start_time_utc <= $3)
+                GROUP BY {group}
+                ORDER BY \"games\" DESC
+                ",
+                group=valorant_grouping_to_sql(group),
+            )
+        )
+            .bind(user_id)
+            .bind(params.start)
+            .bind(params.end)
+            .fetch_all(&*self.pool)
+            .await?)
+    }
+}
+
+pub struct GraphqlValorantStats {
+    pub user_id: i64
+}
+
+#[juniper::graphql_object(
+    Context = api::graphql::GraphqlContext,
+)]
+impl GraphqlValorantStats {
+    async fn agents(&self, context: &api::graphql::GraphqlContext, params: super::GraphqlGameStatsParams) -> FieldResult<Vec<stats::ValorantStatAggregateData>> {
+        if !context.has_access_to_stat(&[StatPermission::ValorantAgents])? {
+            return Err(juniper::FieldError::new("No Valorant agent access.", juniper::Value::Null));
+        }
+
+        Ok(context.app.get_valorant_aggregate_stats(self.user_id, &ValorantStatGrouping::Agent, &params).await?)
+    }
+
+    async fn maps(&self, context: &api::graphql::GraphqlContext, params: super::GraphqlGameStatsParams) -> FieldResult<Vec<stats::ValorantStatAggregateData>> {
+        if !context.has_access_to_stat(&[StatPermission::ValorantMaps])? {
+            return Err(juniper::FieldError::new("No Valorant map access.", juniper::Value::Null));
+        }
+
+        Ok(context.app.get_valorant_aggregate_stats(self.user_id, &ValorantStatGrouping::Map, &params).await?)
+    }
+}
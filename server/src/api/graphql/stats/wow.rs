@@ -0,0 +1,81 @@
+use crate::api;
+use squadov_common::stats::{self, StatPermission};
+use squadov_common::SquadOvError;
+use juniper::FieldResult;
+use chrono::{DateTime, Utc};
+
+const DEFAULT_ENCOUNTER_LIMIT: i32 = 50;
+const MAX_ENCOUNTER_LIMIT: i32 = 200;
+
+#[derive(juniper::GraphQLInputObject)]
+pub(crate) struct GraphqlWowEncounterStatsParams {
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    encounter_id: Option<i32>,
+    difficulty: Option<i32>,
+    limit: Option<i32>,
+}
+
+impl api::ApiApplication {
+    async fn get_wow_encounter_stats(&self, user_id: i64, params: &GraphqlWowEncounterStatsParams) -> Result<Vec<stats::WowStatEncounterData>, SquadOvError> {
+        // The stat summary is stored per character so we need to find the character(s) that belong to the user.
+        Ok(sqlx::query_as::<_, stats::WowStatEncounterData>(
+            "
+            SELECT
+                wmv.match_uuid AS \"match_uuid\",
+                wmv.start_tm AS \"start_tm\",
+                wev.encounter_id AS \"encounter_id\",
+                wev.encounter_name AS \"encounter_name\",
+                wev.difficulty AS \"difficulty\",
+                COALESCE(wev.success, FALSE) AS \"success\",
+                EXTRACT(EPOCH FROM (wmv.end_tm - wmv.start_tm))::DOUBLE PRECISION AS \"duration_seconds\",
+                wss.damage_dealt::DOUBLE PRECISION AS \"damage_dealt\",
+                wss.heals::DOUBLE PRECISION AS \"heals\",
+                wss.damage_dealt::DOUBLE PRECISION / GREATEST(EXTRACT(EPOCH FROM (wmv.end_tm - wmv.start_tm)), 1.0) AS \"dps\",
+                wss.heals::DOUBLE PRECISION / GREATEST(EXTRACT(EPOCH FROM (wmv.end_tm - wmv.start_tm)), 1.0) AS \"hps\"
+            FROM squadov.wow_match_view AS wmv
+            INNER JOIN squadov.wow_encounter_view AS wev
+                ON wev.view_id = wmv.id
+            INNER JOIN squadov.wow_match_view_stat_summaries AS wss
+                ON wss.view_id = wmv.id
+            INNER JOIN squadov.wow_user_character_cache AS wucc
+                ON wucc.unit_guid = wss.unit_guid
+                    AND wucc.user_id = wmv.user_id
+            WHERE wmv.user_id = $1
+                AND wmv.match_uuid IS NOT NULL
+                AND wmv.end_tm IS NOT NULL
+                AND ($2::TIMESTAMPTZ IS NULL OR wmv.start_tm >= $2)
+                AND ($3::TIMESTAMPTZ IS NULL OR wmv.start_tm <= $3)
+                AND ($4::INTEGER IS NULL OR wev.encounter_id = $4)
+                AND ($5::INTEGER IS NULL OR wev.difficulty = $5)
+            ORDER BY wmv.start_tm DESC
+            LIMIT $6
+            "
+        )
+            .bind(user_id)
+            .bind(params.start)
+            .bind(params.end)
+            .bind(params.encounter_id)
+            .bind(params.difficulty)
+            .bind(params.limit.unwrap_or(DEFAULT_ENCOUNTER_LIMIT).clamp(1, MAX_ENCOUNTER_LIMIT) as i64)
+            .fetch_all(&*self.pool)
+            .await?)
+    }
+}
+
+pub struct GraphqlWowStats {
+    pub user_id: i64
+}
+
+#[juniper::graphql_object(
+    Context = api::graphql::GraphqlContext,
+)]
+impl GraphqlWowStats {
+    async fn encounters(&self, context: &api::graphql::GraphqlContext, params: GraphqlWowEncounterStatsParams) -> FieldResult<Vec<stats::WowStatEncounterData>> {
+        if !context.has_access_to_stat(&[StatPermission::WowEncounters])? {
+            return Err(juniper::FieldError::new("No WoW encounter access.", juniper::Value::Null));
+        }
+
+        Ok(context.app.get_wow_encounter_stats(self.user_id, &params).await?)
+    }
+}
@@ -0,0 +1,239 @@
+use super::GraphqlContext;
+use squadov_common::{
+    SquadOvError,
+    squad::events::NewMatchEvent,
+    twitch::streams::TwitchStreamEvent,
+};
+use juniper::{FieldError, FieldResult};
+use futures::{Stream, StreamExt};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::pin::Pin;
+
+type GraphqlStream<T> = Pin<Box<dyn Stream<Item = Result<T, FieldError>> + Send>>;
+
+// Receivers that fall behind just skip the messages they missed - these are only notifications so the
+// client can always refetch to get the full state.
+fn broadcast_to_stream<T: Clone + Send + 'static>(rx: broadcast::Receiver<T>) -> impl Stream<Item = T> + Send {
+    futures::stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(x) => return Some((x, rx)),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+// Users can always see their own new matches on top of their squadmates'.
+fn new_match_audience(self_id: i64, squadmates: Vec<i64>) -> HashSet<i64> {
+    let mut users: HashSet<i64> = squadmates.into_iter().collect();
+    users.insert(self_id);
+    users
+}
+
+fn filter_new_matches(rx: broadcast::Receiver<NewMatchEvent>, users: HashSet<i64>) -> impl Stream<Item = NewMatchEvent> + Send {
+    broadcast_to_stream(rx).filter(move |x| futures::future::ready(users.contains(&x.user_id)))
+}
+
+// Users don't need to be told about their own stream.
+fn squadmate_stream_audience(self_id: i64, squadmates: Vec<i64>) -> HashSet<i64> {
+    squadmates.into_iter().filter(|x| { *x != self_id }).collect()
+}
+
+fn filter_squadmate_streams(rx: broadcast::Receiver<TwitchStreamEvent>, users: HashSet<i64>) -> impl Stream<Item = TwitchStreamEvent> + Send {
+    broadcast_to_stream(rx).filter(move |x| futures::future::ready(users.contains(&x.stream.user_id)))
+}
+
+#[derive(juniper::GraphQLObject)]
+pub(crate) struct GraphqlUserStatus {
+    user_id: String,
+    activity: i32,
+    games: Vec<i32>,
+}
+
+#[derive(juniper::GraphQLObject)]
+pub(crate) struct GraphqlNewMatch {
+    match_uuid: Uuid,
+    video_uuid: Uuid,
+    game: i32,
+    user_id: String,
+}
+
+#[derive(juniper::GraphQLObject)]
+pub(crate) struct GraphqlTwitchStream {
+    user_id: String,
+    username: String,
+    twitch_user_id: String,
+    twitch_name: String,
+    stream_id: String,
+    started_tm: DateTime<Utc>,
+    live: bool,
+}
+
+pub struct GraphqlRootSubscription {
+}
+
+#[juniper::graphql_subscription(
+    Context = GraphqlContext,
+)]
+impl GraphqlRootSubscription {
+    // Note that squad membership is determined when the subscription starts.
+    async fn squad_status(context: &GraphqlContext, squad_id: String) -> FieldResult<GraphqlStream<GraphqlUserStatus>> {
+        let session = context.user_session()?;
+        let squad_id = squad_id.parse::<i64>()?;
+        if context.app.get_squad_user_role(squad_id, session.user.id).await?.is_none() {
+            return Err(SquadOvError::Unauthorized.into());
+        }
+
+        let members: HashSet<i64> = context.app.get_user_ids_in_same_squad_as_users(&[session.user.id], Some(&vec![squad_id])).await?.into_iter().collect();
+        Ok(Box::pin(
+            broadcast_to_stream(context.status_tracker.subscribe_changes())
+                .filter(move |(user_id, _)| futures::future::ready(members.contains(user_id)))
+                .map(|(user_id, state)| {
+                    Ok(GraphqlUserStatus{
+                        user_id: user_id.to_string(),
+                        activity: state.activity as i32,
+                        games: state.game.iter().map(|x| { x.game as i32 }).collect(),
+                    })
+                })
+        ))
+    }
+
+    // New matches recorded by the current user or by anyone in the same squad(s) as the user.
+    async fn new_matches(context: &GraphqlContext, squad_id: Option<String>) -> FieldResult<GraphqlStream<GraphqlNewMatch>> {
+        let session = context.user_session()?;
+        let squad_filter = if let Some(squad_id) = squad_id {
+            let squad_id = squad_id.parse::<i64>()?;
+            if context.app.get_squad_user_role(squad_id, session.user.id).await?.is_none() {
+                return Err(SquadOvError::Unauthorized.into());
+            }
+            Some(vec![squad_id])
+        } else {
+            None
+        };
+
+        let users = new_match_audience(session.user.id, context.app.get_user_ids_in_same_squad_as_users(&[session.user.id], squad_filter.as_ref()).await?);
+
+        Ok(Box::pin(
+            filter_new_matches(context.match_events.subscribe(), users)
+                .map(|x| {
+                    Ok(GraphqlNewMatch{
+                        match_uuid: x.match_uuid,
+                        video_uuid: x.video_uuid,
+                        game: x.game as i32,
+                        user_id: x.user_id.to_string(),
+                    })
+                })
+        ))
+    }
+
+    // Squadmates going live (or offline) on Twitch.
+    async fn squadmate_streams(context: &GraphqlContext, squad_id: Option<String>) -> FieldResult<GraphqlStream<GraphqlTwitchStream>> {
+        let session = context.user_session()?;
+        let squad_filter = if let Some(squad_id) = squad_id {
+            let squad_id = squad_id.parse::<i64>()?;
+            if context.app.get_squad_user_role(squad_id, session.user.id).await?.is_none() {
+                return Err(SquadOvError::Unauthorized.into());
+            }
+            Some(vec![squad_id])
+        } else {
+            None
+        };
+
+        let users = squadmate_stream_audience(session.user.id, context.app.get_user_ids_in_same_squad_as_users(&[session.user.id], squad_filter.as_ref()).await?);
+
+        Ok(Box::pin(
+            filter_squadmate_streams(context.stream_events.subscribe(), users)
+                .map(|x| {
+                    Ok(GraphqlTwitchStream{
+                        user_id: x.stream.user_id.to_string(),
+                        username: x.stream.username,
+                        twitch_user_id: x.stream.twitch_user_id,
+                        twitch_name: x.stream.twitch_name,
+                        stream_id: x.stream.stream_id,
+                        started_tm: x.stream.started_tm,
+                        live: x.live,
+                    })
+                })
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squadov_common::{
+        SquadOvGames,
+        twitch::streams::LiveTwitchStream,
+    };
+
+    const SELF_ID: i64 = 1;
+    const SQUADMATE_ID: i64 = 2;
+    const OUTSIDER_ID: i64 = 3;
+
+    fn new_match(user_id: i64) -> NewMatchEvent {
+        NewMatchEvent{
+            match_uuid: Uuid::new_v4(),
+            video_uuid: Uuid::new_v4(),
+            game: SquadOvGames::Valorant,
+            user_id,
+        }
+    }
+
+    fn stream_event(user_id: i64) -> TwitchStreamEvent {
+        TwitchStreamEvent{
+            stream: LiveTwitchStream{
+                user_id,
+                username: format!("user-{}", user_id),
+                twitch_user_id: format!("twitch-{}", user_id),
+                twitch_name: format!("twitch-name-{}", user_id),
+                stream_id: format!("stream-{}", user_id),
+                started_tm: Utc::now(),
+            },
+            live: true,
+        }
+    }
+
+    // Sends every event then closes the channel so the filtered stream ends and can be collected.
+    async fn received<T: Clone + Send + 'static, S: Stream<Item = T>>(events: Vec<T>, filter: impl FnOnce(broadcast::Receiver<T>) -> S) -> Vec<T> {
+        let (tx, rx) = broadcast::channel(16);
+        let stream = filter(rx);
+        for e in events {
+            tx.send(e).unwrap();
+        }
+        drop(tx);
+        stream.collect().await
+    }
+
+    #[tokio::test]
+    async fn test_new_matches_only_from_self_and_squadmates() {
+        let users = new_match_audience(SELF_ID, vec![SQUADMATE_ID]);
+        let got = received(vec![new_match(OUTSIDER_ID), new_match(SELF_ID), new_match(SQUADMATE_ID), new_match(OUTSIDER_ID)], |rx| {
+            filter_new_matches(rx, users)
+        }).await;
+        assert_eq!(got.iter().map(|x| { x.user_id }).collect::<Vec<_>>(), vec![SELF_ID, SQUADMATE_ID]);
+    }
+
+    #[tokio::test]
+    async fn test_new_matches_without_squadmates() {
+        let users = new_match_audience(SELF_ID, vec![]);
+        let got = received(vec![new_match(SQUADMATE_ID), new_match(SELF_ID)], |rx| {
+            filter_new_matches(rx, users)
+        }).await;
+        assert_eq!(got.iter().map(|x| { x.user_id }).collect::<Vec<_>>(), vec![SELF_ID]);
+    }
+
+    #[tokio::test]
+    async fn test_squadmate_streams_only_from_squadmates() {
+        // The squadmate query includes the user themselves.
+        let users = squadmate_stream_audience(SELF_ID, vec![SELF_ID, SQUADMATE_ID]);
+        let got = received(vec![stream_event(SELF_ID), stream_event(OUTSIDER_ID), stream_event(SQUADMATE_ID)], |rx| {
+            filter_squadmate_streams(rx, users)
+        }).await;
+        assert_eq!(got.iter().map(|x| { x.stream.user_id }).collect::<Vec<_>>(), vec![SQUADMATE_ID]);
+    }
+}
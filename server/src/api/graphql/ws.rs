@@ -0,0 +1,117 @@
+use crate::api;
+use super::GraphqlContext;
+use actix::prelude::*;
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use juniper::DefaultScalarValue;
+use juniper_graphql_ws::{ClientMessage, Connection, ConnectionConfig, ServerMessage};
+use futures::{Stream, StreamExt, channel::mpsc};
+use squadov_common::{
+    SquadOvError,
+    squad::{
+        status::UserActivityStatusTracker,
+        events::NewMatchEventHub,
+    },
+    twitch::streams::TwitchStreamEventHub,
+};
+use std::sync::Arc;
+use std::pin::Pin;
+
+const KEEP_ALIVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+type GraphqlServerStream = Pin<Box<dyn Stream<Item = ServerMessage<DefaultScalarValue>>>>;
+
+// Bridges the websocket to a graphql-ws connection. Client messages get forwarded to the connection's sink
+// and everything the connection emits gets written back out to the websocket.
+struct GraphqlWebsocketSession {
+    client: mpsc::UnboundedSender<ClientMessage<DefaultScalarValue>>,
+    server: Option<GraphqlServerStream>,
+}
+
+impl Actor for GraphqlWebsocketSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if let Some(server) = self.server.take() {
+            ctx.add_stream(server);
+        }
+    }
+}
+
+impl StreamHandler<ServerMessage<DefaultScalarValue>> for GraphqlWebsocketSession {
+    fn handle(&mut self, msg: ServerMessage<DefaultScalarValue>, ctx: &mut Self::Context) {
+        match serde_json::to_string(&msg) {
+            Ok(x) => ctx.text(x),
+            Err(err) => log::warn!("Failed to serialize GraphQL websocket message: {:?}", err),
+        };
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for GraphqlWebsocketSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Text(text)) => {
+                let msg = match serde_json::from_str::<ClientMessage<DefaultScalarValue>>(&text) {
+                    Ok(x) => x,
+                    Err(err) => {
+                        log::warn!("Invalid GraphQL websocket message: {:?}", err);
+                        ctx.stop();
+                        return;
+                    }
+                };
+
+                if self.client.unbounded_send(msg).is_err() {
+                    ctx.stop();
+                }
+            },
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            },
+            Err(err) => {
+                log::warn!("GraphQL websocket protocol error: {:?}", err);
+                ctx.stop();
+            },
+            _ => (),
+        }
+    }
+}
+
+// The websocket can't be authenticated using headers so the client must pass its session ID in the connection_init payload.
+pub async fn graphql_ws_handler(req: HttpRequest, stream: web::Payload, app : web::Data<Arc<api::ApiApplication>>, status_tracker: web::Data<Arc<UserActivityStatusTracker>>, match_events: web::Data<Arc<NewMatchEventHub>>, stream_events: web::Data<Arc<TwitchStreamEventHub>>) -> Result<HttpResponse, SquadOvError> {
+    let app = app.get_ref().clone();
+    let status_tracker = status_tracker.get_ref().clone();
+    let match_events = match_events.get_ref().clone();
+    let stream_events = stream_events.get_ref().clone();
+    let schema = app.schema.clone();
+
+    let connection = Connection::new(schema, move |params: juniper::Variables<DefaultScalarValue>| async move {
+        let session_id = params.get("sessionId").and_then(|x| x.as_string_value()).ok_or(SquadOvError::Unauthorized)?.to_string();
+        let session = app.session.get_session_from_id(&session_id, &*app.pool).await?.ok_or(SquadOvError::Unauthorized)?;
+        if !app.is_session_valid(&session).await? {
+            return Err(SquadOvError::Unauthorized);
+        }
+
+        Ok::<_, SquadOvError>(ConnectionConfig::new(GraphqlContext{
+            app,
+            session: Some(session),
+            status_tracker,
+            match_events,
+            stream_events,
+        }).with_keep_alive_interval(KEEP_ALIVE_INTERVAL))
+    });
+
+    let (sink, server) = connection.split();
+    let (client, rx) = mpsc::unbounded();
+    // This finishes once the websocket actor goes away and drops its sender.
+    actix_web::rt::spawn(rx.map(Ok).forward(sink));
+
+    let resp = ws::WsResponseBuilder::new(GraphqlWebsocketSession{
+        client,
+        server: Some(Box::pin(server)),
+    }, &req, stream)
+        .protocols(&["graphql-ws"])
+        .start()?;
+    Ok(resp)
+}
@@ -0,0 +1,126 @@
+mod fusionauth;
+mod local;
+
+pub use local::*;
+
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use squadov_common::{
+    SquadOvError,
+    config::CommonConfig,
+};
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub enum IdentityProviderType {
+    #[serde(rename="fusionauth")]
+    FusionAuth,
+    #[serde(rename="local")]
+    Local,
+}
+
+impl Default for IdentityProviderType {
+    fn default() -> Self {
+        IdentityProviderType::FusionAuth
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct IdentityConfig {
+    #[serde(default)]
+    pub provider: IdentityProviderType,
+    pub local: Option<LocalIdentityConfig>,
+}
+
+impl CommonConfig for IdentityConfig {
+    fn read_from_env(&mut self) {
+        if let Ok(provider) = std::env::var("SQUADOV_IDENTITY_PROVIDER") {
+            match provider.to_lowercase().as_str() {
+                "fusionauth" => self.provider = IdentityProviderType::FusionAuth,
+                "local" => self.provider = IdentityProviderType::Local,
+                _ => log::warn!("Unknown identity provider: {}", provider),
+            }
+        }
+
+        if let Some(local) = self.local.as_mut() {
+            local.read_from_env();
+        }
+    }
+}
+
+/// A user as known by the identity provider. Note that this is *not* the same as
+/// a SquadOVUser - the identity provider's user is created at registration while
+/// the SquadOVUser is created upon first login.
+#[derive(Debug, Clone)]
+pub struct IdentityUser {
+    // An ID that is only meaningful to the identity provider.
+    pub id: String,
+    pub email: String,
+    pub username: Option<String>,
+    pub verified: bool,
+    pub registration_time: Option<DateTime<Utc>>,
+    pub has_mfa: bool,
+    // Whether the user ever chose a password (as opposed to only logging in through a third party).
+    pub has_password: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct IdentityTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct IdentityLoginResult {
+    pub user: IdentityUser,
+    pub tokens: IdentityTokens,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct IdentityMfaSecret {
+    pub secret: String,
+    pub secret_base32_encoded: String,
+}
+
+/// Everything the server needs from whatever is actually storing user credentials.
+/// Errors follow the same conventions throughout:
+/// * SquadOvError::Credentials - Bad username/password combination.
+/// * SquadOvError::TwoFactor(id) - The operation needs an MFA code. For logins, the ID is what should be passed to mfa_login.
+/// * SquadOvError::Duplicate - Registration with an existing username/email.
+/// * SquadOvError::NotFound - The user (or token) does not exist.
+#[async_trait]
+pub trait IdentityProvider {
+    async fn login(&self, login_id: &str, password: &str, ip: Option<&str>) -> Result<IdentityLoginResult, SquadOvError>;
+    async fn mfa_login(&self, two_factor_id: &str, code: &str) -> Result<IdentityLoginResult, SquadOvError>;
+    async fn logout(&self, refresh_token: &str) -> Result<(), SquadOvError>;
+    async fn register(&self, username: &str, email: &str, password: &str) -> Result<String, SquadOvError>;
+
+    // Logs in a user that was already authenticated by a third party (e.g. social login). This goes through the
+    // same MFA flow as a password login so it'll return SquadOvError::TwoFactor if the user has MFA enabled.
+    async fn external_login(&self, email: &str, ip: Option<&str>) -> Result<IdentityLoginResult, SquadOvError>;
+    // Registers a user whose email was already verified by a third party. The user gets a random password
+    // that they can replace using the forgot password flow.
+    async fn register_external(&self, username: &str, email: &str) -> Result<(), SquadOvError>;
+
+    // Returns false if the access token is invalid or expired.
+    async fn validate_access_token(&self, access_token: &str) -> Result<bool, SquadOvError>;
+    async fn refresh_tokens(&self, refresh_token: &str) -> Result<IdentityTokens, SquadOvError>;
+
+    async fn find_user_from_email(&self, email: &str) -> Result<IdentityUser, SquadOvError>;
+    async fn update_user(&self, current_email: &str, username: &str, email: &str) -> Result<(), SquadOvError>;
+
+    // Returns the email of the user who was verified.
+    async fn verify_email(&self, verification_id: &str) -> Result<String, SquadOvError>;
+    async fn resend_verify_email(&self, email: &str) -> Result<(), SquadOvError>;
+
+    // This should succeed even if the user doesn't exist so that we don't leak which emails are registered.
+    async fn start_forgot_password(&self, login_id: &str) -> Result<(), SquadOvError>;
+    async fn change_forgotten_password(&self, change_password_id: &str, user_id: &str, password: &str, mfa_code: Option<&str>) -> Result<(), SquadOvError>;
+    async fn change_password(&self, login_id: &str, current_password: &str, new_password: &str, mfa_code: Option<&str>) -> Result<(), SquadOvError>;
+
+    async fn generate_mfa_secret(&self) -> Result<IdentityMfaSecret, SquadOvError>;
+    // Returns a list of recovery codes that the user can use in place of an MFA code.
+    async fn enable_mfa(&self, email: &str, code: &str, secret: &str) -> Result<Vec<String>, SquadOvError>;
+    async fn disable_mfa(&self, email: &str, code: &str) -> Result<(), SquadOvError>;
+}
@@ -0,0 +1,245 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc, NaiveDateTime};
+use squadov_common::{
+    SquadOvError,
+    crypto::password::generate_secure_token,
+};
+use crate::api::fusionauth::{
+    FusionAuthClient,
+    FusionAuthUser,
+    FusionAuthLoginResult,
+    FusionAuthLoginError,
+    FusionAuthValidateJwtError,
+    FusionAuthUserError,
+    FusionAuthResendVerificationEmailError,
+};
+use super::{
+    IdentityProvider,
+    IdentityUser,
+    IdentityTokens,
+    IdentityLoginResult,
+    IdentityMfaSecret,
+};
+
+fn fa_user_error(err: FusionAuthUserError) -> SquadOvError {
+    match err {
+        FusionAuthUserError::DoesNotExist => SquadOvError::NotFound,
+        _ => SquadOvError::InternalError(format!("Failed to find user from email address: {:?}", err)),
+    }
+}
+
+impl FusionAuthClient {
+    fn to_identity_user(&self, user: &FusionAuthUser) -> IdentityUser {
+        let reg = self.find_auth_registration(user);
+        IdentityUser{
+            id: user.id.to_string(),
+            email: user.email.clone(),
+            username: reg.map(|x| { x.username.clone() }).flatten(),
+            verified: user.verified,
+            registration_time: reg.map(|x| {
+                DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(x.insert_instant / 1000, 0), Utc)
+            }),
+            has_mfa: if let Some(methods) = user.two_factor.methods.as_ref() {
+                !methods.is_empty()
+            } else {
+                false
+            },
+            // FusionAuth doesn't tell us whether the password is one we generated in register_external.
+            has_password: true,
+        }
+    }
+
+    fn to_identity_login_result(&self, result: FusionAuthLoginResult) -> Result<IdentityLoginResult, SquadOvError> {
+        if self.find_auth_registration(&result.user).is_none() {
+            return Err(SquadOvError::InternalError(String::from("Could not find user auth registration with the current app.")));
+        }
+
+        Ok(IdentityLoginResult{
+            user: self.to_identity_user(&result.user),
+            tokens: IdentityTokens{
+                access_token: result.token,
+                refresh_token: result.refresh_token,
+            },
+        })
+    }
+
+    async fn get_trust_token(&self, challenge: &str, user_id: Option<&str>, login_id: Option<&str>, mfa_code: &str) -> Result<String, SquadOvError> {
+        let two_factor_id = self.start_mfa(challenge, user_id, login_id).await?;
+        Ok(self.complete_mfa(mfa_code, &two_factor_id).await?)
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for FusionAuthClient {
+    async fn login(&self, login_id: &str, password: &str, ip: Option<&str>) -> Result<IdentityLoginResult, SquadOvError> {
+        let result = match FusionAuthClient::login(self, self.build_login_input(
+            login_id.to_string(),
+            password.to_string(),
+            ip,
+        )).await {
+            Ok(x) => x,
+            // TODO: Handle change password/email verification errors.
+            Err(err) => return match err {
+                FusionAuthLoginError::Auth => Err(SquadOvError::Credentials),
+                FusionAuthLoginError::Generic{code, message} => Err(SquadOvError::InternalError(format!("Code: {} Message: {}", code, message))),
+                FusionAuthLoginError::TwoFactor(two_factor_id) => Err(SquadOvError::TwoFactor(two_factor_id)),
+                _ => Err(SquadOvError::InternalError(String::from("Unhandled error."))),
+            }
+        };
+        self.to_identity_login_result(result)
+    }
+
+    async fn mfa_login(&self, two_factor_id: &str, code: &str) -> Result<IdentityLoginResult, SquadOvError> {
+        let result = FusionAuthClient::mfa_login(self, two_factor_id, code).await?;
+        self.to_identity_login_result(result)
+    }
+
+    async fn logout(&self, refresh_token: &str) -> Result<(), SquadOvError> {
+        FusionAuthClient::logout(self, refresh_token).await.map_err(|err| {
+            SquadOvError::InternalError(format!("FA Logout: {}", err))
+        })
+    }
+
+    async fn register(&self, username: &str, email: &str, password: &str) -> Result<String, SquadOvError> {
+        let output = FusionAuthClient::register(self, self.build_register_input(
+            username.to_string(),
+            email.to_string(),
+            password.to_string(),
+        )).await?;
+        Ok(output.user.email)
+    }
+
+    async fn external_login(&self, email: &str, ip: Option<&str>) -> Result<IdentityLoginResult, SquadOvError> {
+        let result = match self.passwordless_login(email, ip).await {
+            Ok(x) => x,
+            Err(err) => return match err {
+                FusionAuthLoginError::Auth => Err(SquadOvError::NotFound),
+                FusionAuthLoginError::Generic{code, message} => Err(SquadOvError::InternalError(format!("Code: {} Message: {}", code, message))),
+                FusionAuthLoginError::TwoFactor(two_factor_id) => Err(SquadOvError::TwoFactor(two_factor_id)),
+                _ => Err(SquadOvError::InternalError(String::from("Unhandled error."))),
+            }
+        };
+        self.to_identity_login_result(result)
+    }
+
+    async fn register_external(&self, username: &str, email: &str) -> Result<(), SquadOvError> {
+        FusionAuthClient::register(self, self.build_verified_register_input(
+            username.to_string(),
+            email.to_string(),
+            generate_secure_token(32)?,
+        )).await?;
+        Ok(())
+    }
+
+    async fn validate_access_token(&self, access_token: &str) -> Result<bool, SquadOvError> {
+        match self.validate_jwt(access_token).await {
+            Ok(_) => Ok(true),
+            Err(err) => match err {
+                FusionAuthValidateJwtError::Invalid => Ok(false),
+                _ => Err(SquadOvError::InternalError(format!("Validate JWT {}", err))),
+            }
+        }
+    }
+
+    async fn refresh_tokens(&self, refresh_token: &str) -> Result<IdentityTokens, SquadOvError> {
+        let new_token = self.refresh_jwt(refresh_token).await.map_err(|err| {
+            SquadOvError::InternalError(format!("Refresh JWT {}", err))
+        })?;
+
+        Ok(IdentityTokens{
+            access_token: new_token.token,
+            refresh_token: new_token.refresh_token,
+        })
+    }
+
+    async fn find_user_from_email(&self, email: &str) -> Result<IdentityUser, SquadOvError> {
+        let user = self.find_user_from_email_address(email).await.map_err(fa_user_error)?;
+        Ok(self.to_identity_user(&user))
+    }
+
+    async fn update_user(&self, current_email: &str, username: &str, email: &str) -> Result<(), SquadOvError> {
+        let user = self.find_user_from_email_address(current_email).await.map_err(fa_user_error)?;
+        self.update_user_id(&user.id, username, email).await
+    }
+
+    async fn verify_email(&self, verification_id: &str) -> Result<String, SquadOvError> {
+        // Get the user for this verification ID before the verification ID gets consumed.
+        let user = self.find_user_from_email_verification_id(verification_id).await.map_err(|err| {
+            SquadOvError::InternalError(format!("Failed to get user from verification ID: {}", err))
+        })?;
+
+        FusionAuthClient::verify_email(self, verification_id).await.map_err(|err| {
+            SquadOvError::InternalError(format!("Failed to verify email: {}", err))
+        })?;
+        Ok(user.email)
+    }
+
+    async fn resend_verify_email(&self, email: &str) -> Result<(), SquadOvError> {
+        FusionAuthClient::resend_verify_email(self, email).await.map_err(|err| {
+            SquadOvError::InternalError(format!("Failed to resend verification email: {}", err))
+        })
+    }
+
+    async fn start_forgot_password(&self, login_id: &str) -> Result<(), SquadOvError> {
+        match self.start_forgot_password_workflow(login_id).await {
+            Ok(_) => Ok(()),
+            Err(err) => match err {
+                // Handle this case especially since we don't want to present to the caller data about whether or not that particular
+                // user exists.
+                FusionAuthResendVerificationEmailError::DoesNotExist => Ok(()),
+                _ => Err(SquadOvError::InternalError(format!("Start Forgot Password Workflow: {}", err)))
+            }
+        }
+    }
+
+    async fn change_forgotten_password(&self, change_password_id: &str, user_id: &str, password: &str, mfa_code: Option<&str>) -> Result<(), SquadOvError> {
+        let (trust_challenge, trust_token) = if let Some(mfa) = mfa_code {
+            (Some(String::from("FORGOT_PW")), Some(self.get_trust_token("FORGOT_PW", Some(user_id), None, mfa).await?))
+        } else {
+            (None, None)
+        };
+
+        match self.change_user_password(change_password_id, password, trust_challenge, trust_token).await {
+            Ok(_) => Ok(()),
+            Err(err) => match err {
+                FusionAuthUserError::InvalidRequest(_j) => Err(SquadOvError::TwoFactor(String::new())),
+                _ => Err(SquadOvError::InternalError(format!("Change Password: {}", err))),
+            }
+        }
+    }
+
+    async fn change_password(&self, login_id: &str, current_password: &str, new_password: &str, mfa_code: Option<&str>) -> Result<(), SquadOvError> {
+        let (trust_challenge, trust_token) = if let Some(mfa) = mfa_code {
+            (Some(String::from("CHANGE_PW")), Some(self.get_trust_token("CHANGE_PW", None, Some(login_id), mfa).await?))
+        } else {
+            (None, None)
+        };
+
+        self.change_user_password_with_id(current_password, new_password, login_id, trust_challenge, trust_token).await
+    }
+
+    async fn generate_mfa_secret(&self) -> Result<IdentityMfaSecret, SquadOvError> {
+        let secret = FusionAuthClient::generate_mfa_secret(self).await?;
+        Ok(IdentityMfaSecret{
+            secret: secret.secret,
+            secret_base32_encoded: secret.secret_base32_encoded,
+        })
+    }
+
+    async fn enable_mfa(&self, email: &str, code: &str, secret: &str) -> Result<Vec<String>, SquadOvError> {
+        let user = self.find_user_from_email_address(email).await.map_err(fa_user_error)?;
+        FusionAuthClient::enable_mfa(self, &user.id, code, secret).await
+    }
+
+    async fn disable_mfa(&self, email: &str, code: &str) -> Result<(), SquadOvError> {
+        let user = self.find_user_from_email_address(email).await.map_err(fa_user_error)?;
+        if let Some(methods) = user.two_factor.methods.as_ref() {
+            for m in methods {
+                if m.method == "authenticator" {
+                    FusionAuthClient::disable_mfa(self, &user.id, code, &m.id).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1138 @@
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc, Duration};
+use sqlx::postgres::PgPool;
+use squadov_common::{
+    SquadOvError,
+    EmailClient,
+    EmailTemplate,
+    EmailUser,
+    config::CommonConfig,
+    crypto::{
+        password::{
+            hash_password,
+            verify_password,
+            generate_secure_token,
+            hash_token,
+        },
+        totp::{
+            generate_totp_secret,
+            verify_totp_code,
+        },
+    },
+    encode::{base32_encode, base32_decode},
+};
+use super::{
+    IdentityProvider,
+    IdentityUser,
+    IdentityTokens,
+    IdentityLoginResult,
+    IdentityMfaSecret,
+};
+use jsonwebtoken::{EncodingKey, DecodingKey, Header, Validation};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+const TOKEN_PURPOSE_REFRESH: &str = "refresh";
+const TOKEN_PURPOSE_TWO_FACTOR: &str = "two_factor";
+const TOKEN_PURPOSE_VERIFY_EMAIL: &str = "verify_email";
+const TOKEN_PURPOSE_FORGOT_PASSWORD: &str = "forgot_password";
+
+const NUM_RECOVERY_CODES: usize = 10;
+// Number of wrong MFA codes we accept for a single two factor token before throwing the token away.
+// The user has to log in with their password again to get a new one.
+const MAX_TWO_FACTOR_ATTEMPTS: i32 = 5;
+// Number of wrong passwords in a row before we stop checking passwords for the identity for a little while.
+const MAX_PASSWORD_ATTEMPTS: i32 = 10;
+const PASSWORD_LOCKOUT_MINUTES: i64 = 15;
+
+fn default_access_token_lifetime_seconds() -> i64 {
+    3600
+}
+
+fn default_refresh_token_lifetime_days() -> i64 {
+    30
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LocalIdentityConfig {
+    // Secret used to sign the access token JWTs.
+    pub jwt_secret: String,
+    #[serde(default="default_access_token_lifetime_seconds")]
+    pub access_token_lifetime_seconds: i64,
+    #[serde(default="default_refresh_token_lifetime_days")]
+    pub refresh_token_lifetime_days: i64,
+    // Postmark template aliases. If these aren't set, the links are logged instead
+    // which is useful when self-hosting or running tests offline.
+    pub verify_email_template: Option<String>,
+    pub forgot_password_template: Option<String>,
+}
+
+impl CommonConfig for LocalIdentityConfig {
+    fn read_from_env(&mut self) {
+        if let Ok(secret) = std::env::var("SQUADOV_LOCAL_IDENTITY_JWT_SECRET") {
+            self.jwt_secret = secret;
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct LocalIdentityClaims {
+    sub: String,
+    email: String,
+    iat: i64,
+    exp: i64,
+}
+
+struct LocalIdentity {
+    id: Uuid,
+    email: String,
+    username: String,
+    password_hash: String,
+    // Identities created for social logins get a random password that nobody knows until they go through forgot password.
+    password_set: bool,
+    verified: bool,
+    mfa_secret: Option<String>,
+    registration_time: DateTime<Utc>,
+}
+
+impl LocalIdentity {
+    fn to_identity_user(&self) -> IdentityUser {
+        IdentityUser{
+            id: self.id.to_string(),
+            email: self.email.clone(),
+            username: Some(self.username.clone()),
+            verified: self.verified,
+            registration_time: Some(self.registration_time.clone()),
+            has_mfa: self.mfa_secret.is_some(),
+            has_password: self.password_set,
+        }
+    }
+}
+
+/// An identity provider that stores everything in our own database. Passwords are hashed with
+/// PBKDF2, MFA uses standard TOTP and all single-use tokens (refresh, email verification, etc.)
+/// are only ever stored hashed.
+pub struct LocalIdentityProvider {
+    config: LocalIdentityConfig,
+    app_url: String,
+    pool: Arc<PgPool>,
+    email: Arc<EmailClient>,
+}
+
+impl LocalIdentityProvider {
+    pub fn new(config: LocalIdentityConfig, app_url: &str, pool: Arc<PgPool>, email: Arc<EmailClient>) -> Self {
+        Self {
+            config,
+            app_url: app_url.to_string(),
+            pool,
+            email,
+        }
+    }
+
+    // Login IDs with an @ are always treated as emails and everything else as a username. We don't allow
+    // usernames with an @ in them so a login ID can never match more than one identity.
+    async fn find_identity_from_login_id(&self, login_id: &str) -> Result<Option<LocalIdentity>, SquadOvError> {
+        if login_id.contains('@') {
+            self.find_identity_from_email(login_id).await
+        } else {
+            self.find_identity_from_username(login_id).await
+        }
+    }
+
+    async fn find_identity_from_email(&self, email: &str) -> Result<Option<LocalIdentity>, SquadOvError> {
+        Ok(
+            sqlx::query_as!(
+                LocalIdentity,
+                "
+                SELECT
+                    id,
+                    email,
+                    username,
+                    password_hash,
+                    password_set,
+                    verified,
+                    mfa_secret,
+                    registration_time
+                FROM squadov.local_identities
+                WHERE email = LOWER($1)
+                ",
+                email,
+            )
+                .fetch_optional(&*self.pool)
+                .await?
+        )
+    }
+
+    async fn find_identity_from_username(&self, username: &str) -> Result<Option<LocalIdentity>, SquadOvError> {
+        Ok(
+            sqlx::query_as!(
+                LocalIdentity,
+                "
+                SELECT
+                    id,
+                    email,
+                    username,
+                    password_hash,
+                    password_set,
+                    verified,
+                    mfa_secret,
+                    registration_time
+                FROM squadov.local_identities
+                WHERE username = $1
+                ",
+                username,
+            )
+                .fetch_optional(&*self.pool)
+                .await?
+        )
+    }
+
+    async fn find_identity_from_id(&self, id: &Uuid) -> Result<LocalIdentity, SquadOvError> {
+        Ok(
+            sqlx::query_as!(
+                LocalIdentity,
+                "
+                SELECT
+                    id,
+                    email,
+                    username,
+                    password_hash,
+                    password_set,
+                    verified,
+                    mfa_secret,
+                    registration_time
+                FROM squadov.local_identities
+                WHERE id = $1
+                ",
+                id,
+            )
+                .fetch_optional(&*self.pool)
+                .await?
+                .ok_or(SquadOvError::NotFound)?
+        )
+    }
+
+    async fn create_token(&self, identity_id: &Uuid, purpose: &str, lifetime: Duration) -> Result<String, SquadOvError> {
+        let token = generate_secure_token(32)?;
+        sqlx::query!(
+            "
+            INSERT INTO squadov.local_identity_tokens (
+                token_hash,
+                identity_id,
+                purpose,
+                issue_tm,
+                expiration_tm
+            )
+            VALUES (
+                $1,
+                $2,
+                $3,
+                NOW(),
+                $4
+            )
+            ",
+            hash_token(&token),
+            identity_id,
+            purpose,
+            Utc::now() + lifetime,
+        )
+            .execute(&*self.pool)
+            .await?;
+        Ok(token)
+    }
+
+    async fn find_token(&self, token: &str, purpose: &str) -> Result<Option<Uuid>, SquadOvError> {
+        Ok(
+            sqlx::query!(
+                "
+                SELECT identity_id
+                FROM squadov.local_identity_tokens
+                WHERE token_hash = $1
+                    AND purpose = $2
+                    AND expiration_tm > NOW()
+                ",
+                hash_token(token),
+                purpose,
+            )
+                .fetch_optional(&*self.pool)
+                .await?
+                .map(|x| { x.identity_id })
+        )
+    }
+
+    async fn consume_token(&self, token: &str, purpose: &str) -> Result<Option<Uuid>, SquadOvError> {
+        Ok(
+            sqlx::query!(
+                "
+                DELETE FROM squadov.local_identity_tokens
+                WHERE token_hash = $1
+                    AND purpose = $2
+                    AND expiration_tm > NOW()
+                RETURNING identity_id
+                ",
+                hash_token(token),
+                purpose,
+            )
+                .fetch_optional(&*self.pool)
+                .await?
+                .map(|x| { x.identity_id })
+        )
+    }
+
+    // Records a failed attempt at using the token and deletes the token once it's been used too many times.
+    async fn record_failed_token_attempt(&self, token: &str, purpose: &str, max_attempts: i32) -> Result<(), SquadOvError> {
+        sqlx::query!(
+            "
+            UPDATE squadov.local_identity_tokens
+            SET attempts = attempts + 1
+            WHERE token_hash = $1
+                AND purpose = $2
+            ",
+            hash_token(token),
+            purpose,
+        )
+            .execute(&*self.pool)
+            .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM squadov.local_identity_tokens
+            WHERE token_hash = $1
+                AND purpose = $2
+                AND attempts >= $3
+            ",
+            hash_token(token),
+            purpose,
+            max_attempts,
+        )
+            .execute(&*self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_tokens_for_identity(&self, identity_id: &Uuid, purpose: &str) -> Result<(), SquadOvError> {
+        sqlx::query!(
+            "
+            DELETE FROM squadov.local_identity_tokens
+            WHERE identity_id = $1
+                AND (purpose = $2 OR expiration_tm <= NOW())
+            ",
+            identity_id,
+            purpose,
+        )
+            .execute(&*self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn issue_tokens(&self, identity: &LocalIdentity) -> Result<IdentityTokens, SquadOvError> {
+        let now = Utc::now();
+        let access_token = jsonwebtoken::encode(
+            &Header::default(),
+            &LocalIdentityClaims{
+                sub: identity.id.to_string(),
+                email: identity.email.clone(),
+                iat: now.timestamp(),
+                exp: (now + Duration::seconds(self.config.access_token_lifetime_seconds)).timestamp(),
+            },
+            &EncodingKey::from_secret(self.config.jwt_secret.as_bytes()),
+        )?;
+
+        Ok(IdentityTokens{
+            access_token,
+            refresh_token: self.create_token(&identity.id, TOKEN_PURPOSE_REFRESH, Duration::days(self.config.refresh_token_lifetime_days)).await?,
+        })
+    }
+
+    // Checks the code against the user's TOTP secret first and falls back to checking (and consuming) a recovery code.
+    async fn check_mfa_code(&self, identity: &LocalIdentity, code: &str) -> Result<bool, SquadOvError> {
+        let secret = match identity.mfa_secret.as_ref() {
+            Some(x) => x,
+            None => return Ok(false),
+        };
+
+        if verify_totp_code(secret, code, Utc::now().timestamp())? {
+            return Ok(true);
+        }
+
+        let result = sqlx::query!(
+            "
+            UPDATE squadov.local_identity_recovery_codes
+            SET used_tm = NOW()
+            WHERE identity_id = $1
+                AND code_hash = $2
+                AND used_tm IS NULL
+            ",
+            identity.id,
+            hash_token(&code.trim().to_uppercase()),
+        )
+            .execute(&*self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn send_identity_email(&self, template: Option<&String>, identity: &LocalIdentity, params: HashMap<String, String>) -> Result<(), SquadOvError> {
+        if let Some(template) = template {
+            self.email.send_bulk_templated_email(template, vec![
+                EmailTemplate{
+                    params,
+                    to: EmailUser{
+                        email: identity.email.clone(),
+                        name: Some(identity.username.clone()),
+                    },
+                }
+            ]).await?;
+        } else {
+            log::info!("No email template for local identity email to {} - {:?}", &identity.email, params);
+        }
+        Ok(())
+    }
+
+    async fn send_verification_email(&self, identity: &LocalIdentity) -> Result<(), SquadOvError> {
+        let token = self.create_token(&identity.id, TOKEN_PURPOSE_VERIFY_EMAIL, Duration::days(7)).await?;
+        let mut params: HashMap<String, String> = HashMap::new();
+        params.insert(String::from("username"), identity.username.clone());
+        params.insert(String::from("verification_url"), format!("{}/verify/{}", &self.app_url, &token));
+        params.insert(String::from("verification_id"), token);
+        self.send_identity_email(self.config.verify_email_template.as_ref(), identity, params).await
+    }
+
+    async fn set_password(&self, identity_id: &Uuid, password: &str) -> Result<(), SquadOvError> {
+        sqlx::query!(
+            "
+            UPDATE squadov.local_identities
+            SET password_hash = $2,
+                password_set = TRUE
+            WHERE id = $1
+            ",
+            identity_id,
+            hash_password(password)?,
+        )
+            .execute(&*self.pool)
+            .await?;
+
+        // Changing the password should sign the user out everywhere else.
+        self.delete_tokens_for_identity(identity_id, TOKEN_PURPOSE_REFRESH).await?;
+        Ok(())
+    }
+
+    async fn require_mfa_if_enabled(&self, identity: &LocalIdentity, mfa_code: Option<&str>) -> Result<(), SquadOvError> {
+        if identity.mfa_secret.is_none() {
+            return Ok(());
+        }
+
+        match mfa_code {
+            Some(code) => if self.check_mfa_code(identity, code).await? {
+                Ok(())
+            } else {
+                Err(SquadOvError::Unauthorized)
+            },
+            None => Err(SquadOvError::TwoFactor(String::new())),
+        }
+    }
+
+    // Called once the user has proven who they are (password, third party, etc.). Users with MFA enabled
+    // still need to go through mfa_login before they get any tokens.
+    async fn finish_login(&self, identity: LocalIdentity) -> Result<IdentityLoginResult, SquadOvError> {
+        if identity.mfa_secret.is_some() {
+            let two_factor_id = self.create_token(&identity.id, TOKEN_PURPOSE_TWO_FACTOR, Duration::minutes(5)).await?;
+            return Err(SquadOvError::TwoFactor(two_factor_id));
+        }
+
+        Ok(IdentityLoginResult{
+            tokens: self.issue_tokens(&identity).await?,
+            user: identity.to_identity_user(),
+        })
+    }
+
+    // Usernames with an @ would be ambiguous with emails when logging in.
+    fn validate_username(username: &str) -> Result<(), SquadOvError> {
+        if username.contains('@') {
+            return Err(SquadOvError::BadRequest);
+        }
+        Ok(())
+    }
+
+    // Checks the password and locks the identity out of password logins for a while after too many wrong ones in a row.
+    // The lockout is checked first so that guesses made while locked out don't tell you anything.
+    async fn check_password(&self, identity: &LocalIdentity, password: &str) -> Result<(), SquadOvError> {
+        let locked = sqlx::query!(
+            "
+            SELECT COALESCE(login_locked_until_tm > NOW(), FALSE) AS \"locked!\"
+            FROM squadov.local_identities
+            WHERE id = $1
+            ",
+            identity.id,
+        )
+            .fetch_one(&*self.pool)
+            .await?
+            .locked;
+
+        if locked {
+            return Err(SquadOvError::RateLimit);
+        }
+
+        if !verify_password(password, &identity.password_hash)? {
+            self.record_failed_login_attempt(&identity.id).await?;
+            return Err(SquadOvError::Credentials);
+        }
+
+        sqlx::query!(
+            "
+            UPDATE squadov.local_identities
+            SET failed_login_attempts = 0,
+                login_locked_until_tm = NULL
+            WHERE id = $1
+            ",
+            identity.id,
+        )
+            .execute(&*self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn record_failed_login_attempt(&self, identity_id: &Uuid) -> Result<(), SquadOvError> {
+        sqlx::query!(
+            "
+            UPDATE squadov.local_identities
+            SET failed_login_attempts = failed_login_attempts + 1
+            WHERE id = $1
+            ",
+            identity_id,
+        )
+            .execute(&*self.pool)
+            .await?;
+
+        sqlx::query!(
+            "
+            UPDATE squadov.local_identities
+            SET failed_login_attempts = 0,
+                login_locked_until_tm = $3
+            WHERE id = $1
+                AND failed_login_attempts >= $2
+            ",
+            identity_id,
+            MAX_PASSWORD_ATTEMPTS,
+            Utc::now() + Duration::minutes(PASSWORD_LOCKOUT_MINUTES),
+        )
+            .execute(&*self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // Identities without a password get a random one so that the password hash is never empty.
+    async fn create_identity(&self, username: &str, email: &str, password: Option<&str>, verified: bool) -> Result<LocalIdentity, SquadOvError> {
+        Self::validate_username(username)?;
+        let password_hash = match password {
+            Some(x) => hash_password(x)?,
+            None => hash_password(&generate_secure_token(32)?)?,
+        };
+
+        Ok(
+            sqlx::query_as!(
+                LocalIdentity,
+                "
+                INSERT INTO squadov.local_identities (
+                    id,
+                    email,
+                    username,
+                    password_hash,
+                    password_set,
+                    verified,
+                    registration_time
+                )
+                VALUES (
+                    $1,
+                    LOWER($2),
+                    $3,
+                    $4,
+                    $5,
+                    $6,
+                    NOW()
+                )
+                RETURNING
+                    id,
+                    email,
+                    username,
+                    password_hash,
+                    password_set,
+                    verified,
+                    mfa_secret,
+                    registration_time
+                ",
+                Uuid::new_v4(),
+                email,
+                username,
+                password_hash,
+                password.is_some(),
+                verified,
+            )
+                .fetch_one(&*self.pool)
+                .await?
+        )
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for LocalIdentityProvider {
+    async fn login(&self, login_id: &str, password: &str, _ip: Option<&str>) -> Result<IdentityLoginResult, SquadOvError> {
+        let identity = self.find_identity_from_login_id(login_id).await?.ok_or(SquadOvError::Credentials)?;
+        self.check_password(&identity, password).await?;
+        self.finish_login(identity).await
+    }
+
+    async fn mfa_login(&self, two_factor_id: &str, code: &str) -> Result<IdentityLoginResult, SquadOvError> {
+        let identity_id = self.find_token(two_factor_id, TOKEN_PURPOSE_TWO_FACTOR).await?.ok_or(SquadOvError::Unauthorized)?;
+        let identity = self.find_identity_from_id(&identity_id).await?;
+        if !self.check_mfa_code(&identity, code).await? {
+            self.record_failed_token_attempt(two_factor_id, TOKEN_PURPOSE_TWO_FACTOR, MAX_TWO_FACTOR_ATTEMPTS).await?;
+            return Err(SquadOvError::Credentials);
+        }
+
+        // Someone else may have used the same token in the meantime - only one of them gets to log in.
+        if self.consume_token(two_factor_id, TOKEN_PURPOSE_TWO_FACTOR).await?.is_none() {
+            return Err(SquadOvError::Unauthorized);
+        }
+
+        Ok(IdentityLoginResult{
+            tokens: self.issue_tokens(&identity).await?,
+            user: identity.to_identity_user(),
+        })
+    }
+
+    async fn logout(&self, refresh_token: &str) -> Result<(), SquadOvError> {
+        self.consume_token(refresh_token, TOKEN_PURPOSE_REFRESH).await?.ok_or(SquadOvError::Unauthorized)?;
+        Ok(())
+    }
+
+    async fn register(&self, username: &str, email: &str, password: &str) -> Result<String, SquadOvError> {
+        let identity = self.create_identity(username, email, Some(password), false).await?;
+        self.send_verification_email(&identity).await?;
+        Ok(identity.email)
+    }
+
+    async fn external_login(&self, email: &str, _ip: Option<&str>) -> Result<IdentityLoginResult, SquadOvError> {
+        let identity = self.find_identity_from_email(email).await?.ok_or(SquadOvError::NotFound)?;
+        self.finish_login(identity).await
+    }
+
+    async fn register_external(&self, username: &str, email: &str) -> Result<(), SquadOvError> {
+        self.create_identity(username, email, None, true).await?;
+        Ok(())
+    }
+
+    async fn validate_access_token(&self, access_token: &str) -> Result<bool, SquadOvError> {
+        Ok(jsonwebtoken::decode::<LocalIdentityClaims>(
+            access_token,
+            &DecodingKey::from_secret(self.config.jwt_secret.as_bytes()),
+            &Validation::default(),
+        ).is_ok())
+    }
+
+    async fn refresh_tokens(&self, refresh_token: &str) -> Result<IdentityTokens, SquadOvError> {
+        // Refresh tokens are single use - we hand out a new one every time.
+        let identity_id = self.consume_token(refresh_token, TOKEN_PURPOSE_REFRESH).await?.ok_or(SquadOvError::Unauthorized)?;
+        let identity = self.find_identity_from_id(&identity_id).await?;
+        self.issue_tokens(&identity).await
+    }
+
+    async fn find_user_from_email(&self, email: &str) -> Result<IdentityUser, SquadOvError> {
+        Ok(self.find_identity_from_email(email).await?.ok_or(SquadOvError::NotFound)?.to_identity_user())
+    }
+
+    async fn update_user(&self, current_email: &str, username: &str, email: &str) -> Result<(), SquadOvError> {
+        Self::validate_username(username)?;
+        let result = sqlx::query!(
+            "
+            UPDATE squadov.local_identities
+            SET username = $2,
+                email = LOWER($3)
+            WHERE email = LOWER($1)
+            ",
+            current_email,
+            username,
+            email,
+        )
+            .execute(&*self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(SquadOvError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn verify_email(&self, verification_id: &str) -> Result<String, SquadOvError> {
+        let identity_id = self.consume_token(verification_id, TOKEN_PURPOSE_VERIFY_EMAIL).await?.ok_or(SquadOvError::NotFound)?;
+        Ok(
+            sqlx::query!(
+                "
+                UPDATE squadov.local_identities
+                SET verified = TRUE
+                WHERE id = $1
+                RETURNING email
+                ",
+                identity_id,
+            )
+                .fetch_one(&*self.pool)
+                .await?
+                .email
+        )
+    }
+
+    async fn resend_verify_email(&self, email: &str) -> Result<(), SquadOvError> {
+        let identity = self.find_identity_from_email(email).await?.ok_or(SquadOvError::NotFound)?;
+        if identity.verified {
+            return Ok(());
+        }
+
+        self.delete_tokens_for_identity(&identity.id, TOKEN_PURPOSE_VERIFY_EMAIL).await?;
+        self.send_verification_email(&identity).await
+    }
+
+    async fn start_forgot_password(&self, login_id: &str) -> Result<(), SquadOvError> {
+        let identity = match self.find_identity_from_login_id(login_id).await? {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        self.delete_tokens_for_identity(&identity.id, TOKEN_PURPOSE_FORGOT_PASSWORD).await?;
+        let token = self.create_token(&identity.id, TOKEN_PURPOSE_FORGOT_PASSWORD, Duration::hours(1)).await?;
+
+        let mut params: HashMap<String, String> = HashMap::new();
+        params.insert(String::from("username"), identity.username.clone());
+        params.insert(String::from("change_password_url"), format!("{}/forgotpw/{}?userId={}", &self.app_url, &token, &identity.id));
+        params.insert(String::from("change_password_id"), token);
+        params.insert(String::from("user_id"), identity.id.to_string());
+        self.send_identity_email(self.config.forgot_password_template.as_ref(), &identity, params).await
+    }
+
+    async fn change_forgotten_password(&self, change_password_id: &str, user_id: &str, password: &str, mfa_code: Option<&str>) -> Result<(), SquadOvError> {
+        let identity_id = self.find_token(change_password_id, TOKEN_PURPOSE_FORGOT_PASSWORD).await?.ok_or(SquadOvError::Unauthorized)?;
+        if identity_id != Uuid::parse_str(user_id)? {
+            return Err(SquadOvError::Unauthorized);
+        }
+
+        let identity = self.find_identity_from_id(&identity_id).await?;
+        if let Err(err) = self.require_mfa_if_enabled(&identity, mfa_code).await {
+            if let SquadOvError::Unauthorized = err {
+                self.record_failed_token_attempt(change_password_id, TOKEN_PURPOSE_FORGOT_PASSWORD, MAX_TWO_FACTOR_ATTEMPTS).await?;
+            }
+            return Err(err);
+        }
+
+        // Someone else may have used the same token in the meantime - only one of them gets to change the password.
+        self.consume_token(change_password_id, TOKEN_PURPOSE_FORGOT_PASSWORD).await?.ok_or(SquadOvError::Unauthorized)?;
+        self.set_password(&identity.id, password).await
+    }
+
+    async fn change_password(&self, login_id: &str, current_password: &str, new_password: &str, mfa_code: Option<&str>) -> Result<(), SquadOvError> {
+        let identity = self.find_identity_from_login_id(login_id).await?.ok_or(SquadOvError::NotFound)?;
+        self.check_password(&identity, current_password).await?;
+
+        self.require_mfa_if_enabled(&identity, mfa_code).await?;
+        self.set_password(&identity.id, new_password).await
+    }
+
+    async fn generate_mfa_secret(&self) -> Result<IdentityMfaSecret, SquadOvError> {
+        let secret = generate_totp_secret()?;
+        Ok(IdentityMfaSecret{
+            secret: base64::encode(&base32_decode(&secret)?),
+            secret_base32_encoded: secret,
+        })
+    }
+
+    async fn enable_mfa(&self, email: &str, code: &str, secret: &str) -> Result<Vec<String>, SquadOvError> {
+        let identity = self.find_identity_from_email(email).await?.ok_or(SquadOvError::NotFound)?;
+
+        // Switching to a new authenticator app has to go through disable_mfa first so it needs a code from the old one.
+        if identity.mfa_secret.is_some() {
+            return Err(SquadOvError::BadRequest);
+        }
+
+        // Make sure the user actually set up their authenticator app properly before turning MFA on.
+        if !verify_totp_code(secret, code, Utc::now().timestamp())? {
+            return Err(SquadOvError::BadRequest);
+        }
+
+        let mut recovery_codes: Vec<String> = Vec::new();
+        for _i in 0..NUM_RECOVERY_CODES {
+            let mut buf = [0u8; 5];
+            openssl::rand::rand_bytes(&mut buf)?;
+            recovery_codes.push(base32_encode(&buf));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query!(
+            "
+            UPDATE squadov.local_identities
+            SET mfa_secret = $2
+            WHERE id = $1
+                AND mfa_secret IS NULL
+            ",
+            identity.id,
+            secret.to_uppercase(),
+        )
+            .execute(&mut tx)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(SquadOvError::BadRequest);
+        }
+
+        sqlx::query!(
+            "
+            DELETE FROM squadov.local_identity_recovery_codes
+            WHERE identity_id = $1
+            ",
+            identity.id,
+        )
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query!(
+            "
+            INSERT INTO squadov.local_identity_recovery_codes (
+                identity_id,
+                code_hash
+            )
+            SELECT $1, inp.code_hash
+            FROM UNNEST($2::VARCHAR[]) AS inp(code_hash)
+            ",
+            identity.id,
+            &recovery_codes.iter().map(|x| { hash_token(x) }).collect::<Vec<String>>(),
+        )
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(recovery_codes)
+    }
+
+    async fn disable_mfa(&self, email: &str, code: &str) -> Result<(), SquadOvError> {
+        let identity = self.find_identity_from_email(email).await?.ok_or(SquadOvError::NotFound)?;
+        if !self.check_mfa_code(&identity, code).await? {
+            return Err(SquadOvError::BadRequest);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "
+            UPDATE squadov.local_identities
+            SET mfa_secret = NULL
+            WHERE id = $1
+            ",
+            identity.id,
+        )
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM squadov.local_identity_recovery_codes
+            WHERE identity_id = $1
+            ",
+            identity.id,
+        )
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // These need a database with all the migrations applied. Run them with
+    // SQUADOV_TEST_DATABASE_URL set and `cargo test -- --ignored`.
+    use super::*;
+    use squadov_common::{
+        EmailConfig,
+        crypto::totp::generate_totp_code,
+    };
+    use sqlx::postgres::PgPoolOptions;
+
+    struct TestIdentity {
+        username: String,
+        email: String,
+        password: String,
+    }
+
+    async fn test_provider() -> LocalIdentityProvider {
+        let url = std::env::var("SQUADOV_TEST_DATABASE_URL").expect("SQUADOV_TEST_DATABASE_URL must be set to run the local identity tests");
+        let pool = PgPoolOptions::new().max_connections(1).connect(&url).await.unwrap();
+        LocalIdentityProvider::new(
+            LocalIdentityConfig{
+                jwt_secret: String::from("local-identity-test-secret"),
+                access_token_lifetime_seconds: default_access_token_lifetime_seconds(),
+                refresh_token_lifetime_days: default_refresh_token_lifetime_days(),
+                verify_email_template: None,
+                forgot_password_template: None,
+            },
+            "http://localhost",
+            Arc::new(pool),
+            Arc::new(EmailClient::new(&EmailConfig{
+                postmark_api_key: String::new(),
+                invite_template: String::new(),
+                welcome_template: String::new(),
+                new_device_template: None,
+            })),
+        )
+    }
+
+    async fn register_test_identity(provider: &LocalIdentityProvider) -> TestIdentity {
+        let suffix = Uuid::new_v4().to_simple().to_string();
+        let identity = TestIdentity{
+            username: format!("local-test-{}", &suffix),
+            email: format!("local-test-{}@squadov.gg", &suffix),
+            password: String::from("hunter22"),
+        };
+        provider.register(&identity.username, &identity.email, &identity.password).await.unwrap();
+        identity
+    }
+
+    async fn enable_test_mfa(provider: &LocalIdentityProvider, identity: &TestIdentity) -> String {
+        let secret = provider.generate_mfa_secret().await.unwrap().secret_base32_encoded;
+        let code = generate_totp_code(&secret, Utc::now().timestamp()).unwrap();
+        provider.enable_mfa(&identity.email, &code, &secret).await.unwrap();
+        secret
+    }
+
+    async fn cleanup_test_identity(provider: &LocalIdentityProvider, identity: &TestIdentity) {
+        sqlx::query!(
+            "
+            DELETE FROM squadov.local_identities
+            WHERE email = $1
+            ",
+            &identity.email,
+        )
+            .execute(&*provider.pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_login_with_email_or_username() {
+        let provider = test_provider().await;
+        let identity = register_test_identity(&provider).await;
+
+        let by_email = provider.login(&identity.email.to_uppercase(), &identity.password, None).await.unwrap();
+        assert_eq!(by_email.user.email, identity.email);
+        assert!(provider.validate_access_token(&by_email.tokens.access_token).await.unwrap());
+
+        let by_username = provider.login(&identity.username, &identity.password, None).await.unwrap();
+        assert_eq!(by_username.user.id, by_email.user.id);
+
+        assert!(matches!(provider.login(&identity.username, "wrong-password", None).await, Err(SquadOvError::Credentials)));
+        assert!(matches!(provider.login("local-test-missing", &identity.password, None).await, Err(SquadOvError::Credentials)));
+
+        // Usernames that look like emails would make login IDs ambiguous.
+        assert!(matches!(provider.register("someone@squadov.gg", "someone-else@squadov.gg", "hunter22").await, Err(SquadOvError::BadRequest)));
+
+        cleanup_test_identity(&provider, &identity).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_mfa_login() {
+        let provider = test_provider().await;
+        let identity = register_test_identity(&provider).await;
+        let secret = enable_test_mfa(&provider, &identity).await;
+
+        let two_factor_id = match provider.login(&identity.email, &identity.password, None).await {
+            Err(SquadOvError::TwoFactor(id)) => id,
+            _ => panic!("Expected a two factor challenge."),
+        };
+
+        let code = generate_totp_code(&secret, Utc::now().timestamp()).unwrap();
+        let result = provider.mfa_login(&two_factor_id, &code).await.unwrap();
+        assert_eq!(result.user.email, identity.email);
+        assert!(result.user.has_mfa);
+
+        // The two factor token is single use.
+        assert!(matches!(provider.mfa_login(&two_factor_id, &code).await, Err(SquadOvError::Unauthorized)));
+
+        cleanup_test_identity(&provider, &identity).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_external_login_requires_mfa() {
+        let provider = test_provider().await;
+        let identity = register_test_identity(&provider).await;
+        provider.external_login(&identity.email, None).await.unwrap();
+
+        let secret = enable_test_mfa(&provider, &identity).await;
+        let two_factor_id = match provider.external_login(&identity.email, None).await {
+            Err(SquadOvError::TwoFactor(id)) => id,
+            _ => panic!("Expected a two factor challenge."),
+        };
+        provider.mfa_login(&two_factor_id, &generate_totp_code(&secret, Utc::now().timestamp()).unwrap()).await.unwrap();
+
+        assert!(matches!(provider.external_login("local-test-missing@squadov.gg", None).await, Err(SquadOvError::NotFound)));
+        cleanup_test_identity(&provider, &identity).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_mfa_login_recovery_code() {
+        let provider = test_provider().await;
+        let identity = register_test_identity(&provider).await;
+        let secret = provider.generate_mfa_secret().await.unwrap().secret_base32_encoded;
+        let recovery_codes = provider.enable_mfa(&identity.email, &generate_totp_code(&secret, Utc::now().timestamp()).unwrap(), &secret).await.unwrap();
+        assert_eq!(recovery_codes.len(), NUM_RECOVERY_CODES);
+
+        let two_factor_id = match provider.login(&identity.username, &identity.password, None).await {
+            Err(SquadOvError::TwoFactor(id)) => id,
+            _ => panic!("Expected a two factor challenge."),
+        };
+        provider.mfa_login(&two_factor_id, &recovery_codes[0].to_lowercase()).await.unwrap();
+
+        // Recovery codes can only be used once.
+        let two_factor_id = match provider.login(&identity.username, &identity.password, None).await {
+            Err(SquadOvError::TwoFactor(id)) => id,
+            _ => panic!("Expected a two factor challenge."),
+        };
+        assert!(matches!(provider.mfa_login(&two_factor_id, &recovery_codes[0]).await, Err(SquadOvError::Credentials)));
+
+        cleanup_test_identity(&provider, &identity).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_mfa_login_attempt_limit() {
+        let provider = test_provider().await;
+        let identity = register_test_identity(&provider).await;
+        let secret = enable_test_mfa(&provider, &identity).await;
+
+        let two_factor_id = match provider.login(&identity.email, &identity.password, None).await {
+            Err(SquadOvError::TwoFactor(id)) => id,
+            _ => panic!("Expected a two factor challenge."),
+        };
+
+        for _i in 0..MAX_TWO_FACTOR_ATTEMPTS {
+            assert!(matches!(provider.mfa_login(&two_factor_id, "abcdef").await, Err(SquadOvError::Credentials)));
+        }
+
+        // The token should be gone now so even the right code doesn't work.
+        let code = generate_totp_code(&secret, Utc::now().timestamp()).unwrap();
+        assert!(matches!(provider.mfa_login(&two_factor_id, &code).await, Err(SquadOvError::Unauthorized)));
+
+        cleanup_test_identity(&provider, &identity).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_password_lockout() {
+        let provider = test_provider().await;
+        let identity = register_test_identity(&provider).await;
+
+        // Logging in successfully resets the count.
+        for _i in 0..(MAX_PASSWORD_ATTEMPTS - 1) {
+            assert!(matches!(provider.login(&identity.username, "wrong-password", None).await, Err(SquadOvError::Credentials)));
+        }
+        provider.login(&identity.username, &identity.password, None).await.unwrap();
+
+        for _i in 0..MAX_PASSWORD_ATTEMPTS {
+            assert!(matches!(provider.login(&identity.username, "wrong-password", None).await, Err(SquadOvError::Credentials)));
+        }
+
+        // Even the right password doesn't work until the lockout is over.
+        assert!(matches!(provider.login(&identity.email, &identity.password, None).await, Err(SquadOvError::RateLimit)));
+        assert!(matches!(provider.change_password(&identity.email, &identity.password, "hunter23", None).await, Err(SquadOvError::RateLimit)));
+
+        cleanup_test_identity(&provider, &identity).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_logout_requires_valid_refresh_token() {
+        let provider = test_provider().await;
+        let identity = register_test_identity(&provider).await;
+        let result = provider.login(&identity.email, &identity.password, None).await.unwrap();
+
+        provider.logout(&result.tokens.refresh_token).await.unwrap();
+        assert!(matches!(provider.logout(&result.tokens.refresh_token).await, Err(SquadOvError::Unauthorized)));
+        assert!(matches!(provider.logout("not-a-refresh-token").await, Err(SquadOvError::Unauthorized)));
+
+        cleanup_test_identity(&provider, &identity).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_change_forgotten_password_is_single_use() {
+        let provider = test_provider().await;
+        let identity = register_test_identity(&provider).await;
+        let identity_id = provider.find_identity_from_email(&identity.email).await.unwrap().unwrap().id;
+
+        let token = provider.create_token(&identity_id, TOKEN_PURPOSE_FORGOT_PASSWORD, Duration::hours(1)).await.unwrap();
+        provider.change_forgotten_password(&token, &identity_id.to_string(), "hunter23", None).await.unwrap();
+        assert!(matches!(provider.change_forgotten_password(&token, &identity_id.to_string(), "hunter24", None).await, Err(SquadOvError::Unauthorized)));
+        provider.login(&identity.email, "hunter23", None).await.unwrap();
+
+        cleanup_test_identity(&provider, &identity).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_update_user() {
+        let provider = test_provider().await;
+        let identity = register_test_identity(&provider).await;
+
+        assert!(matches!(provider.update_user(&identity.email, "someone@squadov.gg", &identity.email).await, Err(SquadOvError::BadRequest)));
+        assert!(matches!(provider.update_user("local-test-missing@squadov.gg", &identity.username, "local-test-missing@squadov.gg").await, Err(SquadOvError::NotFound)));
+
+        let username = format!("{}-renamed", &identity.username);
+        provider.update_user(&identity.email, &username, &identity.email).await.unwrap();
+        provider.login(&username, &identity.password, None).await.unwrap();
+
+        cleanup_test_identity(&provider, &identity).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_enable_mfa_requires_disabling_first() {
+        let provider = test_provider().await;
+        let identity = register_test_identity(&provider).await;
+        let secret = enable_test_mfa(&provider, &identity).await;
+
+        // Turning MFA on again would swap out the secret without ever checking a code from the current one.
+        let new_secret = provider.generate_mfa_secret().await.unwrap().secret_base32_encoded;
+        let new_code = generate_totp_code(&new_secret, Utc::now().timestamp()).unwrap();
+        assert!(matches!(provider.enable_mfa(&identity.email, &new_code, &new_secret).await, Err(SquadOvError::BadRequest)));
+
+        provider.disable_mfa(&identity.email, &generate_totp_code(&secret, Utc::now().timestamp()).unwrap()).await.unwrap();
+        provider.enable_mfa(&identity.email, &new_code, &new_secret).await.unwrap();
+
+        cleanup_test_identity(&provider, &identity).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_external_identity_has_no_password() {
+        let provider = test_provider().await;
+        let suffix = Uuid::new_v4().to_simple().to_string();
+        let identity = TestIdentity{
+            username: format!("local-test-{}", &suffix),
+            email: format!("local-test-{}@squadov.gg", &suffix),
+            password: String::from("hunter22"),
+        };
+        provider.register_external(&identity.username, &identity.email).await.unwrap();
+        assert!(!provider.find_user_from_email(&identity.email).await.unwrap().has_password);
+
+        // Going through forgot password is how these users get a password they can log in with.
+        let identity_id = provider.find_identity_from_email(&identity.email).await.unwrap().unwrap().id;
+        let token = provider.create_token(&identity_id, TOKEN_PURPOSE_FORGOT_PASSWORD, Duration::hours(1)).await.unwrap();
+        provider.change_forgotten_password(&token, &identity_id.to_string(), &identity.password, None).await.unwrap();
+        assert!(provider.find_user_from_email(&identity.email).await.unwrap().has_password);
+
+        let registered = register_test_identity(&provider).await;
+        assert!(provider.find_user_from_email(&registered.email).await.unwrap().has_password);
+
+        cleanup_test_identity(&provider, &identity).await;
+        cleanup_test_identity(&provider, &registered).await;
+    }
+}
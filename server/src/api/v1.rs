@@ -24,7 +24,10 @@ mod discord;
 mod combat_log;
 mod aws;
 mod subscription;
+mod team_subscription;
+mod gift_subscription;
 mod stripe;
+mod storage;
 mod util;
 
 pub use user::*;
@@ -53,7 +56,10 @@ pub use discord::*;
 pub use combat_log::*;
 pub use aws::*;
 pub use subscription::*;
+pub use team_subscription::*;
+pub use gift_subscription::*;
 pub use stripe::*;
+pub use storage::*;
 pub use util::*;
 
 use serde::Serialize;
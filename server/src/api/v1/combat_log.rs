@@ -2,6 +2,7 @@ use actix_web::{web, HttpResponse, HttpRequest, HttpMessage};
 use squadov_common::{
     SquadOvError,
     combatlog::db,
+    storage,
 };
 use crate::{
     api::{
@@ -35,6 +36,12 @@ pub async fn create_update_combat_log_handler(app : web::Data<Arc<ApiApplication
     let extensions = req.extensions();
     let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
     let data = data.into_inner();
+
+    // Only new combat logs count against the quota - we don't want to cut off a log that's already in progress.
+    if !db::does_combat_log_exist(&*app.pool, &path.partition_key).await? {
+        storage::check_user_storage_quota(&*app.pool, session.user.id).await?;
+    }
+
     db::create_combat_log(&*app.pool, &path.partition_key, session.user.id, data.start_time, data.cl_state).await?;
     Ok(HttpResponse::NoContent().finish())
 }
\ No newline at end of file
@@ -1,7 +1,13 @@
 pub mod views;
 pub mod list;
 pub mod get;
+pub mod positions;
+pub mod utility;
+pub mod economy;
 
 pub use views::*;
 pub use list::*;
-pub use get::*;
\ No newline at end of file
+pub use get::*;
+pub use positions::*;
+pub use utility::*;
+pub use economy::*;
\ No newline at end of file
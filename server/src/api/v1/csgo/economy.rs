@@ -0,0 +1,33 @@
+use actix_web::{web, HttpResponse};
+use crate::api;
+use crate::api::v1::CsgoUserMatchInput;
+use squadov_common::{
+    SquadOvError,
+    csgo::{
+        db,
+        economy::{
+            CsgoMatchEconomy,
+            CsgoUserEconomySummary,
+        },
+    },
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct CsgoUserEconomyPath {
+    user_id: i64,
+}
+
+pub async fn get_csgo_match_economy_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<CsgoUserMatchInput>) -> Result<HttpResponse, SquadOvError> {
+    let view = db::find_csgo_view_from_match_user(&*app.pool, &path.match_uuid, path.user_id).await?;
+    let rows = db::get_csgo_economy_rows_for_view(&*app.pool, &view.view_uuid).await?;
+    let steam_ids = db::get_steam_ids_for_user(&*app.pool, path.user_id).await?;
+    Ok(HttpResponse::Ok().json(CsgoMatchEconomy::from_rows(rows, &steam_ids, &app.config.csgo_economy)))
+}
+
+pub async fn get_csgo_user_economy_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<CsgoUserEconomyPath>) -> Result<HttpResponse, SquadOvError> {
+    let rows = db::get_csgo_economy_rows_for_user(&*app.pool, path.user_id).await?;
+    let steam_ids = db::get_steam_ids_for_user(&*app.pool, path.user_id).await?;
+    Ok(HttpResponse::Ok().json(CsgoUserEconomySummary::from_rows(rows, &steam_ids, &app.config.csgo_economy)))
+}
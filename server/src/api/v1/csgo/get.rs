@@ -23,8 +23,8 @@ use std::collections::HashMap;
 
 #[derive(Deserialize)]
 pub struct CsgoUserMatchInput {
-    user_id: i64,
-    match_uuid: Uuid,
+    pub user_id: i64,
+    pub match_uuid: Uuid,
 }
 
 #[derive(Serialize)]
@@ -0,0 +1,82 @@
+use actix_web::{web, HttpResponse};
+use crate::api;
+use squadov_common::{
+    SquadOvError,
+    csgo::{
+        db,
+        positions::{
+            CsgoHeatmapKind,
+            CSGO_HEATMAP_CELL_SIZE,
+        },
+    },
+};
+use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct CsgoRoundTrackInput {
+    user_id: i64,
+    match_uuid: Uuid,
+    round_num: i32,
+}
+
+pub async fn get_csgo_round_track_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<CsgoRoundTrackInput>) -> Result<HttpResponse, SquadOvError> {
+    let view = db::find_csgo_view_from_match_user(&*app.pool, &path.match_uuid, path.user_id).await?;
+    Ok(HttpResponse::Ok().json(
+        db::get_csgo_round_track_for_view(&*app.pool, &view.view_uuid, path.round_num).await?
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct CsgoHeatmapPath {
+    user_id: i64,
+}
+
+#[derive(Deserialize)]
+pub struct CsgoHeatmapQuery {
+    map: String,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoHeatmapCell {
+    x: i32,
+    y: i32,
+    positions: i64,
+    kills: i64,
+    deaths: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoHeatmapResponse {
+    map: String,
+    // World units covered by each cell - the cell at (x, y) covers [x * cellSize, (x + 1) * cellSize).
+    cell_size: f32,
+    cells: Vec<CsgoHeatmapCell>,
+}
+
+pub async fn get_csgo_heatmap_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<CsgoHeatmapPath>, query: web::Query<CsgoHeatmapQuery>) -> Result<HttpResponse, SquadOvError> {
+    let mut cells: BTreeMap<(i32, i32), CsgoHeatmapCell> = BTreeMap::new();
+    for (kind, x, y, count) in db::get_csgo_user_heatmap_cells(&*app.pool, path.user_id, &query.map).await? {
+        let cell = cells.entry((x, y)).or_insert(CsgoHeatmapCell{
+            x,
+            y,
+            ..CsgoHeatmapCell::default()
+        });
+
+        match kind {
+            CsgoHeatmapKind::Position => cell.positions += count,
+            CsgoHeatmapKind::Kill => cell.kills += count,
+            CsgoHeatmapKind::Death => cell.deaths += count,
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(CsgoHeatmapResponse{
+        map: query.map.clone(),
+        cell_size: CSGO_HEATMAP_CELL_SIZE,
+        cells: cells.into_iter().map(|(_, v)| { v }).collect(),
+    }))
+}
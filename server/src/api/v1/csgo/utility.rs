@@ -0,0 +1,17 @@
+use actix_web::{web, HttpResponse};
+use crate::api;
+use crate::api::v1::CsgoUserMatchInput;
+use squadov_common::{
+    SquadOvError,
+    csgo::{
+        db,
+        utility::CsgoUtilityReport,
+    },
+};
+use std::sync::Arc;
+
+pub async fn get_csgo_match_utility_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<CsgoUserMatchInput>) -> Result<HttpResponse, SquadOvError> {
+    let view = db::find_csgo_view_from_match_user(&*app.pool, &path.match_uuid, path.user_id).await?;
+    let container = db::get_csgo_event_container_from_view(&*app.pool, &view.view_uuid).await?;
+    Ok(HttpResponse::Ok().json(CsgoUtilityReport::from_container(&container)))
+}
@@ -0,0 +1,96 @@
+use actix_web::{
+    web,
+    HttpResponse,
+};
+use crate::api::{
+    self,
+    auth::SquadOVSession,
+    v1,
+};
+use std::sync::Arc;
+use std::collections::HashMap;
+use squadov_common::{
+    SquadOvError,
+    stripe::{
+        checkout::{
+            StripeCreateSessionRequest,
+            StripeCheckoutSessionMode,
+            StripeCheckoutLineItem,
+        },
+        currency::StripeCurrency,
+    },
+    subscriptions::{
+        self,
+        SquadOvSubTiers,
+    },
+    user,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct GiftCheckoutQuery {
+    pub tier: SquadOvSubTiers,
+    pub days: i64,
+    // Username of the user receiving the gift.
+    pub recipient: String,
+    #[serde(default="StripeCurrency::default")]
+    pub currency: StripeCurrency,
+}
+
+pub async fn start_gift_subscription_checkout_handler(app : web::Data<Arc<api::ApiApplication>>, session: SquadOVSession, query: web::Query<GiftCheckoutQuery>) -> Result<HttpResponse, SquadOvError> {
+    if !query.tier.has_subscription() || query.days <= 0 {
+        return Err(SquadOvError::BadRequest);
+    }
+
+    let recipient_id = user::get_squadov_user_id_from_username(&*app.pool, &query.recipient).await?.ok_or(SquadOvError::NotFound)?;
+
+    if recipient_id == session.user.id {
+        return Err(SquadOvError::BadRequest);
+    }
+
+    // Each gift length is its own product (e.g. 1 month or 1 year of Gold) with a one time price.
+    let price = v1::find_stripe_price(
+        &app,
+        HashMap::from([
+            ("gift_tier".to_string(), format!("{}", query.tier)),
+            ("gift_days".to_string(), query.days.to_string()),
+        ]),
+        None,
+        &query.currency,
+    ).await?;
+    let customer = v1::get_or_create_stripe_customer(&app, &session.user).await?;
+
+    // The gift gets created by the checkout.session.completed webhook using this metadata.
+    let checkout = app.stripe.create_a_session(StripeCreateSessionRequest{
+        cancel_url: format!("{}/subscription?gift=0", &app.config.squadov.app_url),
+        success_url: format!("{}/subscription?gift=1", &app.config.squadov.app_url),
+        mode: StripeCheckoutSessionMode::Payment,
+        line_items: vec![
+            StripeCheckoutLineItem{
+                price: price.id,
+                quantity: Some(1),
+                subscription: None,
+            }
+        ],
+        discounts: vec![],
+        client_reference_id: Some(session.user.uuid.to_string()),
+        customer: Some(customer),
+        customer_email: None,
+        subscription_data: None,
+        allow_promotion_codes: true,
+        metadata: HashMap::from([
+            ("gift_recipient_id".to_string(), recipient_id.to_string()),
+            ("gift_tier".to_string(), format!("{}", query.tier)),
+            ("gift_days".to_string(), query.days.to_string()),
+        ]),
+    }).await?;
+
+    Ok(HttpResponse::Ok().json(checkout.url))
+}
+
+pub async fn list_gift_subscriptions_handler(app : web::Data<Arc<api::ApiApplication>>, session: SquadOVSession) -> Result<HttpResponse, SquadOvError> {
+    Ok(HttpResponse::Ok().json(
+        subscriptions::get_gift_subscriptions_for_user(&*app.pool, session.user.id).await?
+    ))
+}
@@ -1,10 +1,13 @@
+mod analytics;
 mod arena;
+mod battlegrounds;
 mod cards;
 mod create;
 mod deck;
 mod list;
 mod get;
 mod duels;
+mod hsreplay;
 
 use serde::{Deserialize};
 use uuid::Uuid;
@@ -43,10 +46,13 @@ impl Default for HearthstoneListQuery {
     }
 }
 
+pub use analytics::*;
 pub use arena::*;
+pub use battlegrounds::*;
 pub use cards::*;
 pub use create::*;
 pub use deck::*;
 pub use list::*;
 pub use get::*;
-pub use duels::*;
\ No newline at end of file
+pub use duels::*;
+pub use hsreplay::*;
\ No newline at end of file
@@ -0,0 +1,88 @@
+use squadov_common::SquadOvError;
+use squadov_common::blob;
+use squadov_common::hearthstone::{
+    db as hdb,
+    game_state::HearthstoneGameLog,
+    power_parser::HearthstonePowerLogParser,
+    deck_analytics::{
+        HearthstoneMatchDeckAnalysis,
+        HearthstoneDeckAnalytics,
+    },
+};
+use crate::api;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use sqlx::{Transaction, Postgres};
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct HearthstoneDeckAnalyticsPath {
+    user_id: i64,
+    deck_id: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct HearthstoneDeckAnalyticsQuery {
+    // Only return stats for this version of the deck.
+    version_id: Option<i64>,
+}
+
+impl api::ApiApplication {
+    // Called once the match's logs have been parsed so that the deck stats are always pre-aggregated by the time
+    // someone asks for them. Matches without a deck (e.g. Battlegrounds) are ignored.
+    pub async fn update_hearthstone_deck_analytics(&self, tx: &mut Transaction<'_, Postgres>, logs: Arc<RwLock<HearthstoneGameLog>>, winner_player_id: Option<i32>, match_uuid: &Uuid, user_id: i64) -> Result<(), SquadOvError> {
+        let deck_info = match hdb::get_hearthstone_match_deck_info(&*self.pool, match_uuid, user_id).await? {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        let deck_card_ids: HashSet<String> = hdb::get_hearthstone_deck_slots_for_version(&*self.pool, deck_info.deck_version_id).await?
+            .into_iter()
+            .map(|x| { x.card_id })
+            .collect();
+
+        let analysis = HearthstoneMatchDeckAnalysis::from_game_log(&*logs.read()?, deck_info.local_player_id, winner_player_id, &deck_card_ids);
+        hdb::store_hearthstone_deck_analytics_for_match(tx, match_uuid, user_id, deck_info.deck_version_id, &analysis).await?;
+        Ok(())
+    }
+
+    // Re-parses the raw power logs of a match that was stored before we kept track of what each match contributed
+    // to the deck stats.
+    pub async fn backfill_hearthstone_deck_analytics(&self, match_uuid: &Uuid, user_id: i64) -> Result<(), SquadOvError> {
+        let blob_uuid = sqlx::query!(
+            "
+            SELECT raw_logs_blob_uuid
+            FROM squadov.hearthstone_raw_power_logs
+            WHERE match_uuid = $1
+                AND user_id = $2
+            ",
+            match_uuid,
+            user_id,
+        )
+            .fetch_optional(&*self.pool)
+            .await?
+            .map(|x| { x.raw_logs_blob_uuid });
+
+        let mut tx = self.pool.begin().await?;
+        if let Some(blob_uuid) = blob_uuid {
+            let manager = self.get_blob_manager(&blob::get_blob_bucket(&*self.pool, &blob_uuid).await?).await?;
+            let data = super::decode_hearthstone_power_logs(&manager.get_blob(&blob_uuid, true).await?)?;
+
+            let mut parser = HearthstonePowerLogParser::new(false);
+            parser.parse(&data)?;
+            self.update_hearthstone_deck_analytics(&mut tx, parser.fsm.game.clone(), parser.state.match_winner_player_id, match_uuid, user_id).await?;
+        }
+        hdb::finish_hearthstone_deck_analytics_backfill(&mut tx, match_uuid, user_id).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+pub async fn get_hearthstone_deck_analytics_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<HearthstoneDeckAnalyticsPath>, query: web::Query<HearthstoneDeckAnalyticsQuery>) -> Result<HttpResponse, SquadOvError> {
+    let matchups = hdb::get_hearthstone_deck_matchup_rows(&*app.pool, path.deck_id, path.user_id, query.version_id).await?;
+    let cards = hdb::get_hearthstone_deck_card_rows(&*app.pool, path.deck_id, path.user_id, query.version_id).await?;
+    Ok(HttpResponse::Ok().json(HearthstoneDeckAnalytics::from_rows(path.deck_id, &matchups, &cards)))
+}
@@ -0,0 +1,39 @@
+use squadov_common::SquadOvError;
+use squadov_common::hearthstone::{
+    db as hdb,
+    game_state::HearthstoneGameLog,
+    battlegrounds::{
+        HearthstoneBattlegroundsMatchAnalysis,
+        HearthstoneBattlegroundsAnalytics,
+    },
+};
+use crate::api;
+use actix_web::{web, HttpResponse};
+use sqlx::{Transaction, Postgres};
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+impl api::ApiApplication {
+    // Called once the match's logs have been parsed for Battlegrounds matches. The rating we get from the client is the
+    // rating going into the match so the rating change gets filled in once the next match comes in.
+    pub async fn update_hearthstone_battlegrounds_analytics(&self, tx: &mut Transaction<'_, Postgres>, logs: Arc<RwLock<HearthstoneGameLog>>, match_uuid: &Uuid, user_id: i64) -> Result<(), SquadOvError> {
+        let local_player = match hdb::get_hearthstone_match_local_player(&mut *tx, match_uuid, user_id).await? {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        let analysis = HearthstoneBattlegroundsMatchAnalysis::from_game_log(&*logs.read()?, local_player.player_id);
+        hdb::store_hearthstone_battlegrounds_match(tx, match_uuid, user_id, local_player.battlegrounds_rating, &analysis).await?;
+        Ok(())
+    }
+}
+
+pub async fn get_hearthstone_battlegrounds_match_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<super::HearthstoneMatchGetInput>) -> Result<HttpResponse, SquadOvError> {
+    let history = hdb::get_hearthstone_battlegrounds_match_history(&*app.pool, &path.match_uuid, path.user_id).await?.ok_or(SquadOvError::NotFound)?;
+    Ok(HttpResponse::Ok().json(&history))
+}
+
+pub async fn get_hearthstone_battlegrounds_analytics_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<super::HearthstoneUserMatchInput>) -> Result<HttpResponse, SquadOvError> {
+    let rows = hdb::get_hearthstone_battlegrounds_match_rows(&*app.pool, path.user_id).await?;
+    Ok(HttpResponse::Ok().json(HearthstoneBattlegroundsAnalytics::from_rows(&rows)))
+}
@@ -1,6 +1,7 @@
 use squadov_common::{
     SquadOvError,
     storage::CloudStorageLocation,
+    teammates::db as tdb,
 };
 use squadov_common::SquadOvGames;
 use squadov_common::hearthstone;
@@ -597,15 +598,7 @@ impl api::ApiApplication {
     }
 
     async fn parse_hearthstone_power_logs(&self, data: &[u8], match_uuid: &Uuid, user_id: i64) -> Result<(), SquadOvError> {
-        // Need to try to uncompress using GZIP. If that fails we'll assume that the input data is raw JSON data.
-        // TODO: Use the HTTP headers instead?
-        let mut gz = flate2::read::GzDecoder::new(data);
-        let mut uncompressed_data: Vec<u8> = Vec::new();
-        gz.read_to_end(&mut uncompressed_data)?;
-        let data: Vec<HearthstoneRawLog> = match gz.read_to_end(&mut uncompressed_data) {
-            Ok(_) => serde_json::from_slice(&uncompressed_data)?,
-            Err(_) => serde_json::from_slice(&data)? 
-        };
+        let data = decode_hearthstone_power_logs(data)?;
 
         let mut tx = self.pool.begin().await?;
         let parser = Arc::new(RwLock::new(HearthstonePowerLogParser::new(false)));
@@ -642,11 +635,40 @@ impl api::ApiApplication {
             let game = parser.read()?.fsm.game.clone();
             self.store_hearthstone_match_game_log(&mut tx, game, match_uuid, user_id).await?;
         }
+
+        {
+            log::info!("Store Deck Analytics");
+            let winner = parser.read()?.state.match_winner_player_id;
+            let game = parser.read()?.fsm.game.clone();
+            self.update_hearthstone_deck_analytics(&mut tx, game, winner, match_uuid, user_id).await?;
+        }
+
+        {
+            let game_type = parser.read()?.state.game_type;
+            if game_type == GameType::Battlegrounds || game_type == GameType::BattlegroundsFriendly {
+                log::info!("Store Battlegrounds Analytics");
+                let game = parser.read()?.fsm.game.clone();
+                self.update_hearthstone_battlegrounds_analytics(&mut tx, game, match_uuid, user_id).await?;
+            }
+        }
         tx.commit().await?;
         Ok(())
     }
 }
 
+// The raw power logs as uploaded by the client (also what we keep in hearthstone_raw_power_logs).
+pub fn decode_hearthstone_power_logs(data: &[u8]) -> Result<Vec<HearthstoneRawLog>, SquadOvError> {
+    // Need to try to uncompress using GZIP. If that fails we'll assume that the input data is raw JSON data.
+    // TODO: Use the HTTP headers instead?
+    let mut gz = flate2::read::GzDecoder::new(data);
+    let mut uncompressed_data: Vec<u8> = Vec::new();
+    gz.read_to_end(&mut uncompressed_data)?;
+    Ok(match gz.read_to_end(&mut uncompressed_data) {
+        Ok(_) => serde_json::from_slice(&uncompressed_data)?,
+        Err(_) => serde_json::from_slice(&data)? 
+    })
+}
+
 #[derive(Deserialize)]
 pub struct CreateHearthstoneMatchPathInput {
     user_id: i64
@@ -737,6 +759,7 @@ pub async fn upload_hearthstone_logs_handler(mut body : web::Payload, path : web
     tokio::task::spawn(async move {
         match app.parse_hearthstone_power_logs(&data, &path.match_uuid, user_id).await {
             Ok(_) => {
+                tdb::queue_teammate_roster_sync(&*app.pool, &path.match_uuid).await?;
                 app.es_itf.request_sync_match(path.match_uuid.clone(), None).await.unwrap();
                 Ok(())
             },
@@ -0,0 +1,182 @@
+use squadov_common::{
+    SquadOvError,
+    SquadOvGames,
+    blob,
+    teammates::db as tdb,
+};
+use squadov_common::hearthstone::{
+    self,
+    GameType,
+    db as hdb,
+    power_parser::HearthstoneGameState,
+    game_state::{
+        HearthstoneGameLog,
+        HearthstoneGameAction,
+    },
+    hsreplay::{
+        self as hsr,
+        HearthstoneReplay,
+    },
+};
+use crate::api;
+use actix_web::{web, HttpResponse, http::header};
+use chrono::{DateTime, Utc};
+use sha2::{Sha256, Digest};
+use sqlx::{Transaction, Postgres};
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+impl api::ApiApplication {
+    // Imported matches have no server to dedupe against (the NULL server IP keeps them out of the uniqueness constraint
+    // on server-side matches).
+    async fn create_hearthstone_hsreplay_match(&self, tx: &mut Transaction<'_, Postgres>, timestamp: &DateTime<Utc>) -> Result<Uuid, SquadOvError> {
+        let mt = self.create_new_match(tx, SquadOvGames::Hearthstone).await?;
+        sqlx::query!(
+            "
+            INSERT INTO squadov.hearthstone_matches (
+                match_uuid,
+                server_ip,
+                port,
+                game_id,
+                match_day,
+                match_time
+            )
+            VALUES (
+                $1,
+                NULL,
+                0,
+                0,
+                $2,
+                $3
+            )
+            ",
+            mt.uuid,
+            timestamp.date().naive_utc(),
+            timestamp
+        )
+            .execute(tx)
+            .await?;
+        Ok(mt.uuid)
+    }
+
+    // Rebuilds the game log from what we stored when the match's power logs were originally parsed. Note that only the
+    // latest snapshot gets loaded since the actions are enough to replay the game.
+    pub async fn get_hearthstone_game_log_for_user(&self, match_uuid: &Uuid, user_id: i64) -> Result<HearthstoneGameLog, SquadOvError> {
+        let action_blob_uuid: Uuid = sqlx::query_scalar(
+            "
+            SELECT actions_blob_uuid
+            FROM squadov.hearthstone_match_action_blobs
+            WHERE match_uuid = $1 AND user_id = $2
+            ",
+        )
+            .bind(match_uuid)
+            .bind(user_id)
+            .fetch_optional(&*self.pool)
+            .await?
+            .ok_or(SquadOvError::NotFound)?;
+
+        let bucket = blob::get_blob_bucket(&*self.pool, &action_blob_uuid).await?;
+        let manager = self.get_blob_manager(&bucket).await?;
+        let raw_actions = manager.get_blob(&action_blob_uuid, true).await?;
+
+        let mut log = HearthstoneGameLog::new();
+        log.actions = serde_json::from_slice::<Vec<HearthstoneGameAction>>(&raw_actions)?;
+        log.blocks = hdb::get_hearthstone_game_blocks_for_match(&*self.pool, match_uuid, user_id).await?
+            .into_iter()
+            .map(|x| { (x.block_id.clone(), x) })
+            .collect();
+
+        if let Some(snapshot_uuid) = hdb::get_hearthstone_snapshots_for_match(&*self.pool, match_uuid, user_id).await?.last() {
+            log.current_state = hdb::get_hearthstone_snapshot(&*self.pool, snapshot_uuid).await?;
+        }
+        Ok(log)
+    }
+
+    // HSReplay files don't contain the game server connection info that we'd normally use to identify the match
+    // so imported matches are identified by the hash of the file instead. Importing the same file again just gives back
+    // the match it was imported as the first time.
+    pub async fn import_hearthstone_replay(&self, replay: HearthstoneReplay, file_hash: &str, user_id: i64) -> Result<Uuid, SquadOvError> {
+        let existing_match_uuid = hdb::get_hearthstone_match_for_hsreplay_import(&*self.pool, file_hash).await?;
+        if let Some(match_uuid) = existing_match_uuid.as_ref() {
+            if hdb::is_hearthstone_match_viewed_by_user(&*self.pool, match_uuid, user_id).await? {
+                return Ok(match_uuid.clone());
+            }
+        }
+
+        let mut state = HearthstoneGameState::default();
+        state.game_type = replay.game_type;
+        state.format_type = replay.format_type;
+        state.scenario_id = replay.scenario_id;
+        state.match_winner_player_id = replay.match_winner_player_id();
+
+        let local_player_id = replay.guess_local_player_id();
+        let start_time = replay.start_time.clone();
+        let players = replay.players.clone();
+        let game = Arc::new(RwLock::new(replay.log));
+
+        let mut tx = self.pool.begin().await?;
+        let match_uuid = match existing_match_uuid {
+            Some(x) => x,
+            None => {
+                let match_uuid = self.create_hearthstone_hsreplay_match(&mut tx, &start_time).await?;
+                hdb::store_hearthstone_hsreplay_import(&mut tx, file_hash, &match_uuid).await?;
+                match_uuid
+            }
+        };
+        let view_uuid = self.create_hearthstone_match_view(&mut tx, &match_uuid, user_id).await?;
+
+        for (player_id, name) in &players {
+            let player = hearthstone::HearthstonePlayer{
+                name: name.clone(),
+                local: local_player_id == Some(*player_id),
+                side: *player_id,
+                card_back_id: 0,
+                medal_info: hearthstone::HearthstonePlayerMedalInfo::new(),
+                arena_wins: 0,
+                arena_loss: 0,
+                tavern_brawl_wins: 0,
+                tavern_brawl_loss: 0,
+                battlegrounds_rating: None,
+                duels_casual_rating: None,
+                duels_heroic_rating: None,
+            };
+            self.store_hearthstone_match_player(&mut tx, *player_id, &player, &view_uuid, user_id).await?;
+        }
+
+        self.store_hearthstone_match_metadata(&mut tx, &state, &match_uuid, user_id).await?;
+        self.store_hearthstone_match_game_log(&mut tx, game.clone(), &match_uuid, user_id).await?;
+        self.update_hearthstone_deck_analytics(&mut tx, game.clone(), state.match_winner_player_id, &match_uuid, user_id).await?;
+        if state.game_type == GameType::Battlegrounds || state.game_type == GameType::BattlegroundsFriendly {
+            self.update_hearthstone_battlegrounds_analytics(&mut tx, game, &match_uuid, user_id).await?;
+        }
+        tx.commit().await?;
+        Ok(match_uuid)
+    }
+}
+
+pub async fn export_hearthstone_match_hsreplay_handler(path : web::Path<super::HearthstoneMatchGetInput>, app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, SquadOvError> {
+    let packet = hdb::get_hearthstone_game_packet(&*app.pool, &path.match_uuid, path.user_id).await?;
+    let log = app.get_hearthstone_game_log_for_user(&path.match_uuid, path.user_id).await?;
+    let xml = hsr::export_hsreplay_xml(&log, packet.metadata.game_type, packet.metadata.format_type, packet.metadata.scenario_id)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/xml; charset=utf-8")
+        .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.xml\"", &path.match_uuid)))
+        .body(xml))
+}
+
+pub async fn import_hearthstone_match_hsreplay_handler(body: web::Bytes, path: web::Path<super::HearthstoneUserMatchInput>, app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, SquadOvError> {
+    let replay = match hsr::import_hsreplay_xml(&body[..]) {
+        Ok(x) => x,
+        Err(err) => {
+            log::warn!("Failed to import HSReplay file: {:?}", err);
+            return Err(SquadOvError::BadRequest);
+        }
+    };
+
+    let file_hash = hex::encode(Sha256::digest(&body[..]));
+    let match_uuid = app.import_hearthstone_replay(replay, &file_hash, path.user_id).await?;
+    tdb::queue_teammate_roster_sync(&*app.pool, &match_uuid).await?;
+    app.es_itf.request_sync_match(match_uuid.clone(), None).await?;
+    Ok(HttpResponse::Ok().json(&match_uuid))
+}
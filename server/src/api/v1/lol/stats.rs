@@ -0,0 +1,24 @@
+use squadov_common::{
+    SquadOvError,
+    riot::{
+        db,
+        stats::lol::{
+            LolStatsFilters,
+            LolStatsSummary,
+        },
+    },
+};
+use crate::api;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct LolUserStatsInput {
+    puuid: String,
+}
+
+pub async fn get_lol_player_stats_summary_handler(data : web::Path<LolUserStatsInput>, query: web::Query<api::PaginationParameters>, filters: web::Json<LolStatsFilters>, app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, SquadOvError> {
+    let rows = db::get_lol_player_match_stats_rows(&*app.pool, &data.puuid, query.start, query.end, &filters).await?;
+    Ok(HttpResponse::Ok().json(LolStatsSummary::from_rows(&rows)))
+}
@@ -0,0 +1,51 @@
+use squadov_common::{
+    SquadOvError,
+    riot::{
+        db,
+        games::lol::timeline::{
+            self,
+            LOL_DEFAULT_WARD_GRID_SIZE,
+        },
+    },
+};
+use crate::api;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct LolWardHeatmapQuery {
+    grid_size: Option<i32>,
+}
+
+pub async fn get_lol_match_team_diffs_handler(data : web::Path<super::LolMatchInput>, app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, SquadOvError> {
+    let participants = db::get_lol_match_timeline_participants(&*app.pool, &data.match_uuid).await?;
+    let frames = db::get_lol_match_timeline_frame_rows(&*app.pool, &data.match_uuid).await?;
+    Ok(HttpResponse::Ok().json(timeline::compute_lol_team_diffs(&participants, &frames)))
+}
+
+pub async fn get_lol_match_objective_timeline_handler(data : web::Path<super::LolMatchInput>, app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, SquadOvError> {
+    let participants = db::get_lol_match_timeline_participants(&*app.pool, &data.match_uuid).await?;
+    let events = db::get_lol_match_timeline_event_rows(&*app.pool, &data.match_uuid, &["ELITE_MONSTER_KILL", "BUILDING_KILL"]).await?;
+    Ok(HttpResponse::Ok().json(timeline::compute_lol_objective_timeline(&participants, &events)))
+}
+
+pub async fn get_lol_match_lane_phase_handler(data : web::Path<super::LolMatchInput>, app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, SquadOvError> {
+    let participants = db::get_lol_match_timeline_participants(&*app.pool, &data.match_uuid).await?;
+    let frames = db::get_lol_match_timeline_frame_rows(&*app.pool, &data.match_uuid).await?;
+    Ok(HttpResponse::Ok().json(timeline::compute_lol_lane_phase(&participants, &frames)))
+}
+
+pub async fn get_lol_match_kill_participation_handler(data : web::Path<super::LolMatchInput>, app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, SquadOvError> {
+    let participants = db::get_lol_match_timeline_participants(&*app.pool, &data.match_uuid).await?;
+    let frames = db::get_lol_match_timeline_frame_rows(&*app.pool, &data.match_uuid).await?;
+    let events = db::get_lol_match_timeline_event_rows(&*app.pool, &data.match_uuid, &["CHAMPION_KILL"]).await?;
+    Ok(HttpResponse::Ok().json(timeline::compute_lol_kill_participation(&participants, &frames, &events)))
+}
+
+pub async fn get_lol_match_ward_heatmap_handler(data : web::Path<super::LolMatchInput>, query: web::Query<LolWardHeatmapQuery>, app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, SquadOvError> {
+    let frames = db::get_lol_match_timeline_frame_rows(&*app.pool, &data.match_uuid).await?;
+    let events = db::get_lol_match_timeline_event_rows(&*app.pool, &data.match_uuid, &["WARD_PLACED", "WARD_KILL"]).await?;
+    Ok(HttpResponse::Ok().json(timeline::compute_lol_ward_heatmap(&frames, &events, query.grid_size.unwrap_or(LOL_DEFAULT_WARD_GRID_SIZE))))
+}
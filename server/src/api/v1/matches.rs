@@ -39,6 +39,7 @@ use squadov_common::{
         self,
         SquadOvSubTiers,
     },
+    features,
 };
 use std::sync::Arc;
 use chrono::{Utc, Duration};
@@ -330,19 +331,7 @@ async fn get_cached_user_tier(app: Arc<api::ApiApplication>, user_id: i64) -> Re
 
 impl api::ApiApplication {
     pub async fn is_user_allowed_to_es_search(&self, user_id: i64) -> Result<bool, SquadOvError> {
-        Ok(
-            !sqlx::query!(
-                r#"
-                SELECT disable_es_search AS "disable_es_search!"
-                FROM squadov.user_feature_flags
-                WHERE user_id = $1
-                "#,
-                user_id
-            )
-                .fetch_one(&*self.pool)
-                .await?
-                .disable_es_search
-        )
+        Ok(!features::get_cached_evaluated_feature_flags(&*self.pool, user_id).await?.flags.disable_es_search)
     }
 
     async fn is_match_favorite_by_user(&self, match_uuid: &Uuid, user_id: i64) -> Result<Option<String>, SquadOvError> {
@@ -364,7 +353,7 @@ impl api::ApiApplication {
         )
     }
 
-    async fn add_match_favorite_for_user(&self, match_uuid: &Uuid, user_id: i64, reason: &str) -> Result<(), SquadOvError> {
+    pub async fn add_match_favorite_for_user(&self, match_uuid: &Uuid, user_id: i64, reason: &str) -> Result<(), SquadOvError> {
         sqlx::query!(
             r#"
             INSERT INTO squadov.user_favorite_matches (
@@ -392,7 +381,7 @@ impl api::ApiApplication {
         Ok(())
     }
 
-    async fn remove_match_favorite_for_user(&self, match_uuid: &Uuid, user_id: i64) -> Result<(), SquadOvError> {
+    pub async fn remove_match_favorite_for_user(&self, match_uuid: &Uuid, user_id: i64) -> Result<(), SquadOvError> {
         sqlx::query!(
             r#"
             DELETE FROM squadov.user_favorite_matches
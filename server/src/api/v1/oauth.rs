@@ -17,6 +17,8 @@ use squadov_common::{
         eventsub::{
             TWITCH_CHANNEL_SUBSCRIBE,
             TWITCH_CHANNEL_UNSUB,
+            TWITCH_STREAM_ONLINE,
+            TWITCH_STREAM_OFFLINE,
         },
     },
     discord::{
@@ -118,6 +120,7 @@ pub async fn handle_twitch_oauth_callback_handler(app : web::Data<Arc<api::ApiAp
     redirect_url.set_query(None);
 
     let token = squadov_common::twitch::oauth::exchange_authorization_code_for_access_token(
+        &app.config.twitch.id_url,
         &app.config.twitch.client_id,
         &app.config.twitch.client_secret,
         &redirect_url.as_str(),
@@ -156,6 +159,10 @@ pub async fn handle_twitch_oauth_callback_handler(app : web::Data<Arc<api::ApiAp
         // These EventSub lets us detect changes automatically to who's actually subscribed to the Twitch channel (one on sub and one on unsub).
         app.twitch_api.register_eventsub_subscription(TWITCH_CHANNEL_SUBSCRIBE, condition.clone(), transport.clone()).await?;
         app.twitch_api.register_eventsub_subscription(TWITCH_CHANNEL_UNSUB, condition.clone(), transport.clone()).await?;
+
+        // And these let us tell squadmates when the user goes live and find the stream's VOD once it's over.
+        app.twitch_api.register_eventsub_subscription(TWITCH_STREAM_ONLINE, condition.clone(), transport.clone()).await?;
+        app.twitch_api.register_eventsub_subscription(TWITCH_STREAM_OFFLINE, condition.clone(), transport.clone()).await?;
         
         // We also need to do an initial subscriber sync. This should be fairly quick as long as Pokimane's not joining
         // SquadOV. To be safe we can just throw it onto RabbitMQ to make sure this process is reliable as well.
@@ -18,6 +18,7 @@ use squadov_common::{
     VodAssociation,
     matches,
     vod::db as vdb,
+    storage,
 };
 use serde::Deserialize;
 use std::sync::Arc;
@@ -188,6 +189,9 @@ pub async fn create_new_share_connection_handler(app : web::Data<Arc<api::ApiApp
         if role.is_none() {
             return Err(SquadOvError::Unauthorized);
         }
+
+        // Sharing a VOD into the squad counts against the squad's storage quota too.
+        storage::check_squad_storage_quota(&*app.pool, *squad_id).await?;
     }
     
     if data.conn.dest_user_id.is_some() {
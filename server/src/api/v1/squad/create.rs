@@ -3,7 +3,10 @@ use serde::Deserialize;
 use crate::api;
 use crate::api::auth::{SquadOVSession, SquadOVUser};
 use std::sync::Arc;
-use squadov_common::SquadOvError;
+use squadov_common::{
+    SquadOvError,
+    features,
+};
 use sqlx::{Transaction, Executor, Postgres};
 use chrono::Utc;
 use sqlx::Row;
@@ -22,6 +25,7 @@ impl api::ApiApplication {
     }
 
     async fn create_squad(&self, tx: &mut Transaction<'_, Postgres>, squad_name: &str, owner_id: i64, default: bool) -> Result<i64, SquadOvError> {
+        let max_squad_size = features::get_cached_evaluated_feature_flags(&*self.pool, owner_id).await?.flags.max_squad_size;
         let squad_id: i64 = tx.fetch_one(
             sqlx::query!(
                 "
@@ -31,15 +35,18 @@ impl api::ApiApplication {
                     is_default,
                     max_members
                 )
-                SELECT $1, $2, $3, max_squad_size 
-                FROM squadov.user_feature_flags
-                WHERE user_id = $4
+                VALUES (
+                    $1,
+                    $2,
+                    $3,
+                    $4
+                )
                 RETURNING id
                 ",
                 squad_name,
                 Utc::now(),
                 default,
-                owner_id,
+                max_squad_size,
             )
         ).await?.get(0);
 
@@ -1,7 +1,7 @@
 use actix_web::{web, HttpResponse, HttpRequest, HttpMessage};
 use crate::api;
 use std::sync::Arc;
-use squadov_common::{SquadOvError, SquadRole};
+use squadov_common::{SquadOvError, SquadRole, subscriptions};
 use crate::api::auth::SquadOVSession;
 use sqlx::{Transaction, Postgres};
 use uuid::Uuid;
@@ -50,9 +50,17 @@ impl api::ApiApplication {
 }
 
 pub async fn delete_squad_handler(app : web::Data<Arc<api::ApiApplication>>, data: web::Path<super::SquadSelectionInput>) -> Result<HttpResponse, SquadOvError> {
+    // Anyone with a team seat in this squad loses it once the squad is gone.
+    let seat_users = subscriptions::get_team_seat_user_ids_for_squad(&*app.pool, data.squad_id).await?;
+
     let mut tx = app.pool.begin().await?;
     app.delete_squad(&mut tx, data.squad_id).await?;
     tx.commit().await?;
+
+    for user_id in seat_users {
+        app.update_user_subscription(user_id).await?;
+        app.discord.request_sync_user(user_id).await?;
+    }
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -67,8 +75,13 @@ async fn generic_remove_user_from_squad_handler(app : web::Data<Arc<api::ApiAppl
 
     let mut tx = app.pool.begin().await?;
     app.leave_squad(&mut tx, squad_id, user_id).await?;
+    let removed_seats = subscriptions::remove_user_team_seats_for_squad(&mut tx, squad_id, user_id).await?;
     tx.commit().await?;
 
+    if !removed_seats.is_empty() {
+        app.update_user_subscription(user_id).await?;
+        app.discord.request_sync_user(user_id).await?;
+    }
     Ok(())
 }
 
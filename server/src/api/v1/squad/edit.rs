@@ -11,27 +11,23 @@ pub struct EditSquadInput {
     squad_name: String
 }
 
-pub async fn edit_user_max_squad_size_from_feature_flags<'a, T>(ex: T, user_id: i64) -> Result<(), SquadOvError>
+pub async fn edit_user_max_squad_size<'a, T>(ex: T, user_id: i64, max_squad_size: Option<i64>) -> Result<(), SquadOvError>
 where
     T: Executor<'a, Database = Postgres>
 {
     sqlx::query!(
         "
         UPDATE squadov.squads AS s
-        SET max_members = sub.max_squad_size
-        FROM (
-            SELECT squad_id, uf.max_squad_size
-            FROM squadov.squad_role_assignments AS sra
-            INNER JOIN squadov.user_feature_flags AS uf
-                ON uf.user_id = sra.user_id
-            WHERE sra.user_id = $1
-                AND sra.squad_role = 'Owner'
-        ) AS sub
-        WHERE sub.squad_id = s.id
+        SET max_members = $2
+        FROM squadov.squad_role_assignments AS sra
+        WHERE sra.squad_id = s.id
+            AND sra.user_id = $1
+            AND sra.squad_role = 'Owner'
             AND NOT s.is_public
             AND NOT s.is_discoverable
         ",
         user_id,
+        max_squad_size,
     )
         .execute(ex)
         .await?;
@@ -14,7 +14,8 @@ use squadov_common::{
             SquadInviteLink,
             PublicSquadInviteLink,
         }
-    }
+    },
+    steam::friends,
 };
 use sqlx::{Transaction, Executor, Postgres, Row};
 use serde::{Serialize, Deserialize};
@@ -31,6 +32,12 @@ pub struct CreateSquadInviteInput {
     emails: Vec<String>,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct CreateSquadInviteFromSuggestionsInput {
+    user_ids: Vec<i64>,
+}
+
 struct SquadOvInviteCreationHandle {
     invite_uuid: Uuid,
     username: Option<String>,
@@ -133,6 +140,43 @@ impl api::ApiApplication {
         )
     }
 
+    // Failing to send the emails shouldn't fail the invite since the invites show up in the app anyway.
+    async fn send_squad_invite_emails(&self, squad_id: i64, inviter_username: &str, invites: HashMap<String, SquadOvInviteCreationHandle>) {
+        match self.email.send_bulk_templated_email(&self.config.email.invite_template, invites.into_iter().map(|(email, invite)| {
+            let (accept, reject) = match self.generate_invite_accept_reject_url(squad_id, &invite.invite_uuid, invite.username.is_some()) {
+                Ok(x) => x,
+                Err(err) => {
+                    log::warn!("Failed to generate invite accept/reject URL: {:?}", err);
+                    return None
+                }
+            };
+
+            Some(EmailTemplate{
+                to: EmailUser{
+                    email: email,
+                    name: invite.username,
+                },
+                params: vec![
+                    (String::from("product_url"), String::from("https://www.squadov.gg")),
+                    (String::from("product_name"), String::from("SquadOV")),
+                    (String::from("invite_sender_name"), inviter_username.to_string()),
+                    (String::from("accept_url"), accept),
+                    (String::from("decline_url"), reject),
+                ].into_iter().collect()
+            })
+        })
+            .filter(|x| {
+                x.is_some()
+            })
+            .map(|x| {
+                x.unwrap()
+            })
+            .collect::<Vec<EmailTemplate>>()).await {
+                Ok(_) => (),
+                Err(err) => log::warn!("Failed to send squad invite emails: {:?}", err),
+            };
+    }
+
     pub async fn delete_squad_invite(&self, tx: &mut Transaction<'_, Postgres>, squad_id: i64, invite_uuid: &Uuid) -> Result<(), SquadOvError> {
         sqlx::query!(
             "
@@ -467,41 +511,32 @@ pub async fn create_squad_invite_handler(app : web::Data<Arc<api::ApiApplication
 
     // Now that we've tracked all the invites in the database, we can go about sending email invites for all the
     // users in question.
-    match app.email.send_bulk_templated_email(&app.config.email.invite_template, invites.into_iter().map(|(email, invite)| {
-        let (accept, reject) = match app.generate_invite_accept_reject_url(path.squad_id, &invite.invite_uuid, invite.username.is_some()) {
-            Ok(x) => x,
-            Err(err) => {
-                log::warn!("Failed to generate invite accept/reject URL: {:?}", err);
-                return None
-            }
-        };
-
-        Some(EmailTemplate{
-            to: EmailUser{
-                email: email,
-                name: invite.username,
-            },
-            params: vec![
-                (String::from("product_url"), String::from("https://www.squadov.gg")),
-                (String::from("product_name"), String::from("SquadOV")),
-                (String::from("invite_sender_name"), session.user.username.clone()),
-                (String::from("accept_url"), accept),
-                (String::from("decline_url"), reject),
-            ].into_iter().collect()
-        })
-    })
-        .filter(|x| {
-            x.is_some()
-        })
-        .map(|x| {
-            x.unwrap()
-        })
-        .collect::<Vec<EmailTemplate>>()).await {
-            Ok(_) => (),
-            Err(err) => log::warn!("Failed to send squad invite emails: {:?}", err),
-        };
+    app.send_squad_invite_emails(path.squad_id, &session.user.username, invites).await;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Bulk invite from the user's friend suggestions. Only users that are actually suggested to the user can be
+// invited this way so this can't be used to get around anyone's suggestion settings.
+pub async fn create_squad_invite_from_suggestions_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<super::SquadSelectionInput>, data: web::Json<CreateSquadInviteFromSuggestionsInput>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+
+    let usernames: Vec<String> = friends::get_friend_suggestions(&*app.pool, session.user.id, usize::MAX).await?
+        .into_iter()
+        .filter(|x| { data.user_ids.contains(&x.user_id) })
+        .map(|x| { x.username })
+        .collect();
 
-        Ok(HttpResponse::NoContent().finish())
+    if usernames.is_empty() {
+        return Err(SquadOvError::BadRequest);
+    }
+
+    let mut tx = app.pool.begin().await?;
+    let invites = app.create_squad_invite(&mut tx, path.squad_id, session.user.id, &usernames, &[]).await?;
+    tx.commit().await?;
+
+    app.send_squad_invite_emails(path.squad_id, &session.user.username, invites).await;
+    Ok(HttpResponse::NoContent().finish())
 }
 
 pub async fn accept_squad_invite_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<super::SquadInviteInput>) -> Result<HttpResponse, SquadOvError> {
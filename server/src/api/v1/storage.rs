@@ -0,0 +1,179 @@
+use actix_web::{web, HttpResponse};
+use crate::api::{
+    self,
+    auth::SquadOVSession,
+};
+use squadov_common::{
+    SquadOvError,
+    storage::{
+        self,
+        StorageUsageSummary,
+        StorageCleanupSuggestion,
+        STORAGE_CLEANUP_SUGGESTION_COUNT,
+    },
+    vod::{
+        VodSegmentId,
+        container_format_to_extension,
+        container_format_to_fastify_extension,
+    },
+};
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use chrono::Utc;
+use uuid::Uuid;
+
+// How many resources each pass of the reconciliation looks at.
+const STORAGE_RECONCILIATION_BATCH_SIZE: i64 = 500;
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct StorageSquadInput {
+    pub squad_id: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct UserStorageUsageResponse {
+    #[serde(flatten)]
+    pub summary: StorageUsageSummary,
+    // What the user could delete first to free up space.
+    pub suggestions: Vec<StorageCleanupSuggestion>,
+}
+
+pub async fn get_user_storage_usage_handler(app : web::Data<Arc<api::ApiApplication>>, session: SquadOVSession) -> Result<HttpResponse, SquadOvError> {
+    Ok(HttpResponse::Ok().json(UserStorageUsageResponse{
+        summary: storage::get_user_storage_usage_summary(&*app.pool, session.user.id).await?,
+        suggestions: storage::get_user_storage_cleanup_suggestions(&*app.pool, session.user.id, STORAGE_CLEANUP_SUGGESTION_COUNT).await?,
+    }))
+}
+
+pub async fn get_squad_storage_usage_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<StorageSquadInput>) -> Result<HttpResponse, SquadOvError> {
+    Ok(HttpResponse::Ok().json(
+        storage::get_squad_storage_usage_summary(&*app.pool, path.squad_id).await?
+    ))
+}
+
+#[derive(Serialize, Default, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct StorageReconciliationReport {
+    // Resources that weren't being tracked at all.
+    pub backfilled: i64,
+    pub checked: i64,
+    // Resources whose size didn't match what's actually stored.
+    pub corrected: i64,
+    // Resources that no longer exist in storage.
+    pub removed: i64,
+}
+
+impl api::ApiApplication {
+    async fn measure_vod_segment(&self, bucket: &str, segment: &VodSegmentId) -> Result<Option<i64>, SquadOvError> {
+        let manager = self.get_vod_manager(bucket).await?;
+        match manager.get_segment_size_bytes(segment).await {
+            Ok(x) => Ok(Some(x)),
+            Err(SquadOvError::NotFound) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    // Whatever's in the partition that the combat log parser didn't track object by object (e.g. reports).
+    async fn measure_untracked_combat_log_bytes(&self, partition_id: &str) -> Result<i64, SquadOvError> {
+        let total = self.cl_itf.get_partition_size_bytes(partition_id).await?;
+        let tracked = storage::get_tracked_combat_log_object_bytes(&*self.pool, partition_id).await?;
+        Ok((total - tracked).max(0))
+    }
+}
+
+// The storage backend is the source of truth so we periodically go back and check that the sizes we recorded
+// at upload time are still right. This also picks up anything that was stored before we started tracking usage.
+pub async fn reconcile_storage_usage(app: Arc<api::ApiApplication>) -> Result<StorageReconciliationReport, SquadOvError> {
+    let mut report = StorageReconciliationReport::default();
+
+    // VODs that we fail to measure here get retried after everything else so they can't hold up the next batch.
+    let untracked_vods = storage::get_untracked_cloud_vods(&*app.pool, STORAGE_RECONCILIATION_BATCH_SIZE).await?;
+    storage::mark_cloud_vod_reconcile_attempts(&*app.pool, &untracked_vods.iter().map(|x| { x.video_uuid.clone() }).collect::<Vec<Uuid>>()).await?;
+    for vod in untracked_vods {
+        let mut segments = vec![
+            if vod.has_fastify {
+                format!("fastify.{}", container_format_to_fastify_extension(&vod.raw_container_format))
+            } else {
+                format!("video.{}", container_format_to_extension(&vod.raw_container_format))
+            }
+        ];
+
+        if vod.has_preview {
+            segments.push(String::from("preview.mp4"));
+        }
+
+        if vod.has_thumbnail {
+            segments.push(String::from("thumbnail.jpg"));
+        }
+
+        for segment_name in segments {
+            let segment = VodSegmentId{
+                video_uuid: vod.video_uuid.clone(),
+                quality: String::from("source"),
+                segment_name,
+            };
+
+            match app.measure_vod_segment(&vod.bucket, &segment).await {
+                Ok(Some(bytes)) => {
+                    storage::track_vod_segment_storage_usage(&*app.pool, &segment, &vod.bucket, bytes).await?;
+                    report.backfilled += 1;
+                },
+                Ok(None) => (),
+                Err(err) => log::warn!("Failed to measure VOD segment [{}]: {:?}", segment.get_fname(), err),
+            }
+        }
+    }
+
+    // Give the combat log parser a day to finish writing the log before measuring it.
+    for partition_id in storage::get_untracked_combat_logs(&*app.pool, &(Utc::now() - chrono::Duration::days(1)), STORAGE_RECONCILIATION_BATCH_SIZE).await? {
+        match app.measure_untracked_combat_log_bytes(&partition_id).await {
+            // Empty logs get tracked too so that they don't keep getting picked up here.
+            Ok(bytes) => {
+                storage::track_combat_log_storage_usage(&*app.pool, &partition_id, &partition_id, bytes).await?;
+                report.backfilled += 1;
+            },
+            Err(err) => log::warn!("Failed to measure combat log [{}]: {:?}", &partition_id, err),
+        }
+    }
+
+    for resource in storage::get_storage_resources_to_reconcile(&*app.pool, STORAGE_RECONCILIATION_BATCH_SIZE).await? {
+        report.checked += 1;
+
+        let bytes = if let Some(key) = resource.combat_log_object_key() {
+            app.cl_itf.get_object_size_bytes(key).await
+        } else if let Some(partition_id) = resource.partition_id.as_ref() {
+            app.measure_untracked_combat_log_bytes(partition_id).await.map(Some)
+        } else if let (Some(bucket), Some(segment)) = (resource.bucket.as_ref(), resource.vod_segment()) {
+            app.measure_vod_segment(bucket, &segment).await
+        } else {
+            // Nothing we can measure this against so leave it as is.
+            Ok(Some(resource.bytes))
+        };
+
+        // One bad resource shouldn't hold up the rest of the batch.
+        let bytes = match bytes {
+            Ok(x) => x,
+            Err(err) => {
+                log::warn!("Failed to measure storage resource [{} {}]: {:?}", resource.resource_type, &resource.resource_key, err);
+                continue;
+            }
+        };
+
+        match bytes {
+            Some(bytes) => {
+                if bytes != resource.bytes {
+                    report.corrected += 1;
+                }
+                storage::mark_storage_resource_reconciled(&*app.pool, &resource, bytes).await?;
+            },
+            None => {
+                storage::untrack_storage_resource(&*app.pool, resource.resource_type, &resource.resource_key).await?;
+                report.removed += 1;
+            },
+        }
+    }
+
+    Ok(report)
+}
@@ -16,8 +16,14 @@ use squadov_common::{
         subscription::{
             StripeSubscription,
             StripeListSubscriptionsRequest,
-        }
+        },
+        events::{
+            self,
+            StripeWebhookEventStatus,
+            StripeWebhookTask,
+        },
     },
+    rabbitmq::RabbitMqListener,
     subscriptions::{
         self,
         SquadOvSubTiers,
@@ -37,6 +43,11 @@ use std::{
 use uuid::Uuid;
 use sqlx::{Executor, Postgres};
 use chrono::{DateTime, Utc};
+use async_trait::async_trait;
+use serde::Serialize;
+
+// Delay before the queue hands a failed event back to us. The queue backs this off further on every retry.
+const STRIPE_WEBHOOK_RETRY_BASE_MS: i64 = 5000;
 
 async fn update_user_subscription_from_line_item<'a, T>(ex: T, app: Arc<ApiApplication>, user_id: i64, d: &StripeInvoiceLineItem) -> Result<bool, SquadOvError>
 where
@@ -75,6 +86,26 @@ where
     }
 }
 
+async fn refresh_team_subscription_users(app: Arc<ApiApplication>, user_ids: &[i64]) -> Result<(), SquadOvError> {
+    for user_id in user_ids {
+        app.update_user_subscription(*user_id).await?;
+        app.discord.request_sync_user(*user_id).await?;
+    }
+    Ok(())
+}
+
+// Team subscriptions get tied to their squad through the subscription's metadata. Returns false if the subscription isn't for a team.
+async fn sync_team_subscription(app: Arc<ApiApplication>, owner_user_id: i64, sub: &StripeSubscription) -> Result<bool, SquadOvError> {
+    Ok(
+        if let Some(affected_users) = subscriptions::sync_team_subscription_from_stripe(&app.stripe, &*app.pool, owner_user_id, sub).await? {
+            refresh_team_subscription_users(app.clone(), &affected_users).await?;
+            true
+        } else {
+            false
+        }
+    )
+}
+
 async fn handle_invoice_paid(app: Arc<ApiApplication>, event: StripeTypedWebhookEvent<StripeInvoice>) -> Result<(), SquadOvError> {
     // Invoice has been paid - find the customer and make sure we track them as having the proper subscription.
     // Note that if we don't have a customer ID stored for this person, we need to find them.
@@ -94,7 +125,16 @@ async fn handle_invoice_paid(app: Arc<ApiApplication>, event: StripeTypedWebhook
     };
 
     let user = user::get_squadov_user_from_id(&*app.pool, user_id).await?;
+
+    // A renewal of a team subscription extends everyone's seat rather than the owner's personal subscription.
+    let (team_subscriptions, affected_users) = subscriptions::sync_team_subscriptions_from_stripe_invoice(&app.stripe, &*app.pool, user_id, &event.data.object).await?;
+    refresh_team_subscription_users(app.clone(), &affected_users).await?;
+
     for d in &event.data.object.lines.data {
+        if d.subscription.as_ref().map(|x| { team_subscriptions.contains(x) }).unwrap_or(false) {
+            continue;
+        }
+
         if update_user_subscription_from_line_item(&*app.pool, app.clone(), user_id, &d).await? {
             app.segment.track(&user.uuid.to_string(), "start_subscription").await?;
             app.discord.request_sync_user(user_id).await?;
@@ -113,10 +153,20 @@ async fn handle_invoice_payment_failed(app: Arc<ApiApplication>, event: StripeTy
     if let Some(user_id) = user_id {
         let user = user::get_squadov_user_from_id(&*app.pool, user_id).await?;
         app.segment.track(&user.uuid.to_string(), "payment_failed").await?;
-        let mut tx = app.pool.begin().await?;
-        subscriptions::set_user_sub_tier(&mut tx, user_id, SquadOvSubTiers::Basic, None).await?;
-        tx.commit().await?;
-        app.update_user_subscription(user_id).await?;
+
+        // A failed payment on a team subscription is dealt with when Stripe updates the subscription's status.
+        let is_team = if let Some(sub) = event.data.object.subscription.as_ref() {
+            subscriptions::get_team_subscription_from_stripe(&*app.pool, sub).await?.is_some()
+        } else {
+            false
+        };
+
+        if !is_team {
+            let mut tx = app.pool.begin().await?;
+            subscriptions::set_user_sub_tier(&mut tx, user_id, SquadOvSubTiers::Basic, None).await?;
+            tx.commit().await?;
+            app.update_user_subscription(user_id).await?;
+        }
     }
 
     full_sync_stripe_from_customer_id(app.clone(), &event.data.object.customer).await?;
@@ -150,8 +200,26 @@ async fn handle_checkout_session_completed(app: Arc<ApiApplication>, event: Stri
             }
         }
     }
+
+    // One time gift purchases only have the gift's details in the session metadata.
+    let mut gift_recipient: Option<i64> = None;
+    if let Some(recipient) = event.data.object.metadata.get("gift_recipient_id") {
+        if event.data.object.payment_status.as_deref() == Some("paid") {
+            let recipient = recipient.parse::<i64>()?;
+            let tier = SquadOvSubTiers::from_str(event.data.object.metadata.get("gift_tier").ok_or(SquadOvError::BadRequest)?)?;
+            let days = event.data.object.metadata.get("gift_days").ok_or(SquadOvError::BadRequest)?.parse::<i64>()?;
+            if subscriptions::create_gift_subscription(&mut tx, Some(user.id), recipient, &tier, days, Some(event.data.object.id.as_str())).await?.is_some() {
+                app.segment.track(&user.uuid.to_string(), "gift_subscription").await?;
+                gift_recipient = Some(recipient);
+            }
+        }
+    }
     tx.commit().await?;
-    
+
+    if let Some(recipient) = gift_recipient {
+        app.update_user_subscription(recipient).await?;
+        app.discord.request_sync_user(recipient).await?;
+    }
     app.update_user_subscription(user.id).await?;
 
     if let Some(customer) = event.data.object.customer.as_ref() {
@@ -164,6 +232,11 @@ async fn handle_customer_subscription_update(app: Arc<ApiApplication>, event: St
     let user_id = subscriptions::get_user_id_from_stripe_customer_id(&*app.pool, &event.data.object.customer).await?;
 
     if let Some(user_id) = user_id {
+        // Team subscriptions never touch the owner's personal subscription.
+        if sync_team_subscription(app.clone(), user_id, &event.data.object).await? {
+            return Ok(());
+        }
+
         let user = user::get_squadov_user_from_id(&*app.pool, user_id).await?;
         let mut tx = app.pool.begin().await?;
         if event.data.object.status.is_valid() {
@@ -186,6 +259,18 @@ async fn handle_customer_subscription_update(app: Arc<ApiApplication>, event: St
     Ok(())
 }
 
+// Returns false for event types that we don't do anything with.
+async fn handle_stripe_webhook_event(app: Arc<ApiApplication>, event: StripeGenericWebhookEvent) -> Result<bool, SquadOvError> {
+    match event.event_type.as_str() {
+        "invoice.paid" => handle_invoice_paid(app, StripeTypedWebhookEvent::<StripeInvoice>::try_from(event)?).await?,
+        "invoice.payment_failed" => handle_invoice_payment_failed(app, StripeTypedWebhookEvent::<StripeInvoice>::try_from(event)?).await?,
+        "checkout.session.completed" => handle_checkout_session_completed(app, StripeTypedWebhookEvent::<StripeCheckoutSession>::try_from(event)?).await?,
+        "customer.subscription.deleted" | "customer.subscription.updated" => handle_customer_subscription_update(app, StripeTypedWebhookEvent::<StripeSubscription>::try_from(event)?).await?,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
 pub async fn stripe_webhook_handler(app: web::Data<Arc<ApiApplication>>, payload: web::Bytes, sig: StripeSignature) -> Result<HttpResponse, SquadOvError> {
     // Pull the Stripe-Signature header and verify the signature to ensure that the request came from Stripe.
     let str_payload = String::from_utf8(payload.to_vec())?;
@@ -193,19 +278,127 @@ pub async fn stripe_webhook_handler(app: web::Data<Arc<ApiApplication>>, payload
         return Err(SquadOvError::Unauthorized);
     }
 
-    let event: StripeGenericWebhookEvent = serde_json::from_str(&str_payload)?; 
-    log::info!("Handle Stripe Webhook: {} - {}", &event.id, &event.event_type);
-    match event.event_type.as_str() {
-        "invoice.paid" => handle_invoice_paid(app.as_ref().clone(), StripeTypedWebhookEvent::<StripeInvoice>::try_from(event)?).await?,
-        "invoice.payment_failed" => handle_invoice_payment_failed(app.as_ref().clone(), StripeTypedWebhookEvent::<StripeInvoice>::try_from(event)?).await?,
-        "checkout.session.completed" => handle_checkout_session_completed(app.as_ref().clone(), StripeTypedWebhookEvent::<StripeCheckoutSession>::try_from(event)?).await?,
-        "customer.subscription.deleted" | "customer.subscription.updated" => handle_customer_subscription_update(app.as_ref().clone(), StripeTypedWebhookEvent::<StripeSubscription>::try_from(event)?).await?,
-        _ => (),
+    // The event gets stored before anything else happens so that we only tell Stripe we got it once it's safe in the database.
+    // Stripe retries deliveries that we already have so those are acknowledged without being queued up again.
+    let raw: serde_json::Value = serde_json::from_str(&str_payload)?;
+    let event: StripeGenericWebhookEvent = serde_json::from_value(raw.clone())?;
+    log::info!("Receive Stripe Webhook: {} - {}", &event.id, &event.event_type);
+    if events::store_stripe_webhook_event(&*app.pool, &event.id, &event.event_type, &raw).await? {
+        app.stripe_webhooks.request_process_event(&event.id).await?;
     }
 
     Ok(HttpResponse::NoContent().finish())
 }
 
+pub async fn process_stripe_webhook_event(app: Arc<ApiApplication>, event_id: &str) -> Result<(), SquadOvError> {
+    let record = match events::claim_stripe_webhook_event(&*app.pool, event_id).await? {
+        Some(x) => x,
+        None => {
+            log::info!("Skipping Stripe Webhook (already handled or in progress): {}", event_id);
+            return Ok(());
+        }
+    };
+
+    log::info!("Handle Stripe Webhook: {} - {} [Attempt {}]", &record.event_id, &record.event_type, record.attempts);
+    let result = match record.payload {
+        Some(payload) => match serde_json::from_value::<StripeGenericWebhookEvent>(payload) {
+            Ok(event) => handle_stripe_webhook_event(app.clone(), event).await,
+            Err(err) => Err(err.into()),
+        },
+        None => Err(SquadOvError::InternalError(format!("Stripe webhook event has no payload: {}", event_id))),
+    };
+
+    match result {
+        Ok(handled) => {
+            events::finish_stripe_webhook_event(&*app.pool, event_id, if handled { StripeWebhookEventStatus::Succeeded } else { StripeWebhookEventStatus::Ignored }, None).await?;
+            Ok(())
+        },
+        Err(err) => {
+            log::warn!("Failed to handle Stripe Webhook {}: {:?}", event_id, &err);
+            events::finish_stripe_webhook_event(&*app.pool, event_id, StripeWebhookEventStatus::Failed, Some(&format!("{:?}", err))).await?;
+            if StripeWebhookEventStatus::Failed.can_retry(record.attempts) {
+                Err(SquadOvError::Defer(STRIPE_WEBHOOK_RETRY_BASE_MS))
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+pub struct StripeWebhookEventConsumer {
+    app: Arc<ApiApplication>,
+}
+
+impl StripeWebhookEventConsumer {
+    pub fn new(app: Arc<ApiApplication>) -> Self {
+        Self {
+            app,
+        }
+    }
+}
+
+#[async_trait]
+impl RabbitMqListener for StripeWebhookEventConsumer {
+    async fn handle(&self, data: &[u8], _queue: &str, _priority: u8) -> Result<(), SquadOvError> {
+        let task: StripeWebhookTask = serde_json::from_slice(data)?;
+        match task {
+            StripeWebhookTask::Process{event_id} => process_stripe_webhook_event(self.app.clone(), &event_id).await?,
+        };
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct StripeReconciliationMismatch {
+    pub customer_id: String,
+    pub user_id: Option<i64>,
+    // What we had stored vs. what Stripe says it should be (None if the sync failed).
+    pub stored_tier: Option<SquadOvSubTiers>,
+    pub stripe_tier: Option<SquadOvSubTiers>,
+    pub error: Option<String>,
+}
+
+// Runs a full sync for every Stripe customer we know about and reports the ones where our state didn't match Stripe's.
+// The full sync fixes the mismatches so a non-empty result means some webhook was missed or mishandled.
+pub async fn reconcile_stripe_customers(app: Arc<ApiApplication>) -> Result<Vec<StripeReconciliationMismatch>, SquadOvError> {
+    let mut ret: Vec<StripeReconciliationMismatch> = vec![];
+    for customer_id in subscriptions::get_all_stripe_customer_ids(&*app.pool).await? {
+        let user_id = subscriptions::get_user_id_from_stripe_customer_id(&*app.pool, &customer_id).await?;
+        let stored_tier = match user_id {
+            Some(x) => Some(subscriptions::get_user_personal_sub_tier(&*app.pool, x).await?),
+            None => None,
+        };
+
+        if let Err(err) = full_sync_stripe_from_customer_id(app.clone(), &customer_id).await {
+            ret.push(StripeReconciliationMismatch{
+                customer_id,
+                user_id,
+                stored_tier,
+                stripe_tier: None,
+                error: Some(format!("{:?}", err)),
+            });
+            continue;
+        }
+
+        let stripe_tier = match user_id {
+            Some(x) => Some(subscriptions::get_user_personal_sub_tier(&*app.pool, x).await?),
+            None => None,
+        };
+
+        if stored_tier != stripe_tier {
+            ret.push(StripeReconciliationMismatch{
+                customer_id,
+                user_id,
+                stored_tier,
+                stripe_tier,
+                error: None,
+            });
+        }
+    }
+    Ok(ret)
+}
+
 pub async fn full_sync_stripe_from_customer_id(app: Arc<ApiApplication>, customer_id: &str) -> Result<(), SquadOvError> {
     // Note that if we don't have a customer ID stored for this person, we need to find them.
     // At this point, it's a bit of legacy to not be able to have this info since we now create that information when the user goes to checkout.
@@ -223,7 +416,7 @@ pub async fn full_sync_stripe_from_customer_id(app: Arc<ApiApplication>, custome
 
         user.id
     };
-    let current_sub_tier = subscriptions::get_user_sub_tier(&*app.pool, user_id).await?;
+    let current_sub_tier = subscriptions::get_user_personal_sub_tier(&*app.pool, user_id).await?;
 
     let active_subscriptions = app.stripe.list_subscriptions(StripeListSubscriptionsRequest{
         customer: Some(customer_id.to_string())
@@ -234,6 +427,10 @@ pub async fn full_sync_stripe_from_customer_id(app: Arc<ApiApplication>, custome
     let mut sub_end_tm: DateTime<Utc> = Utc::now();
     let mut is_trial: bool = false;
     for sub in active_subscriptions {
+        if sync_team_subscription(app.clone(), user_id, &sub).await? {
+            continue;
+        }
+
         if !sub.status.is_valid() {
             continue;
         }
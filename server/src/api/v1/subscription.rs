@@ -22,6 +22,7 @@ use squadov_common::{
             StripeSearchProductsRequest,
         },
         price::{
+            StripePrice,
             ListAllPricesRequest,
             StripeRecurring,
             StripeRecurringInterval,
@@ -49,6 +50,7 @@ use squadov_common::{
     },
     user::{
         self,
+        SquadOVUser,
         SupportLevel,
     },
     rabbitmq::{
@@ -182,9 +184,60 @@ pub struct CheckoutQuery {
     pub currency: StripeCurrency,
 }
 
+// Finds the price of the active product with the given metadata. A recurring interval of None is for one time purchases.
+pub async fn find_stripe_price(app: &api::ApiApplication, metadata: HashMap<String, String>, interval: Option<StripeRecurringInterval>, currency: &StripeCurrency) -> Result<StripePrice, SquadOvError> {
+    let mut products = app.stripe.search_products(StripeSearchProductsRequest{
+        active: Some(true),
+        metadata: Some(metadata),
+    }).await?;
+
+    let p = products.data.pop().ok_or(SquadOvError::NotFound)?;
+    let mut pricing = app.stripe.list_all_prices(ListAllPricesRequest{
+        product: Some(p.id.clone()),
+        recurring: interval.map(|x| {
+            StripeRecurring{
+                interval: Some(x),
+            }
+        }),
+        currency: Some(currency.clone()),
+    }).await?;
+
+    pricing.data.pop().ok_or(SquadOvError::NotFound)
+}
+
+pub async fn get_or_create_stripe_customer(app: &api::ApiApplication, user: &SquadOVUser) -> Result<String, SquadOvError> {
+    Ok(
+        if let Some(customer) = subscriptions::get_user_stripe_customer_id(&*app.pool, user.id).await? {
+            customer
+        } else {
+            // Create the customer here and link to our own systems here. This way we don't
+            // have to rely on other hacks to try and find the user later.
+            let customer = app.stripe.create_a_customer(&user.email, &user.username).await?;
+
+            let mut tx = app.pool.begin().await?;
+            subscriptions::associate_user_id_with_customer_id(&mut tx, user.id, &customer.id).await?;
+            tx.commit().await?;
+
+            customer.id
+        }
+    )
+}
+
+pub fn stripe_checkout_discounts(discounts: &[SquadOvDiscount]) -> Vec<StripeCheckoutDiscount> {
+    if let Some(d) = discounts.first() {
+        vec![StripeCheckoutDiscount{
+            coupon: Some(d.id.clone()),
+            promotion_code: None,
+        }]
+    } else {
+        vec![]
+    }
+}
+
 pub async fn start_subscription_checkout_handler(app : web::Data<Arc<api::ApiApplication>>, session: SquadOVSession, query: web::Query<CheckoutQuery>) -> Result<HttpResponse, SquadOvError> {
     // If the user has an active subscription, then ship them to the customer portal instead.
-    let current_tier = subscriptions::get_user_sub_tier(&*app.pool, session.user.id).await?;
+    // Team seats and gifts don't count here since the user should still be able to pay for their own subscription.
+    let current_tier = subscriptions::get_user_personal_sub_tier(&*app.pool, session.user.id).await?;
     if current_tier.has_subscription() {
         start_subscription_manage_handler(app, session).await
     } else {
@@ -196,84 +249,46 @@ pub async fn start_subscription_checkout_handler(app : web::Data<Arc<api::ApiApp
             true
         };
 
-        let mut products = app.stripe.search_products(StripeSearchProductsRequest{
-            active: Some(true),
-            metadata: Some(HashMap::from([
+        let price = find_stripe_price(
+            &app,
+            HashMap::from([
                 ("tier".to_string(), format!("{}", query.tier))
-            ]))
-        }).await?;
+            ]),
+            Some(if query.annual { StripeRecurringInterval::Year } else { StripeRecurringInterval::Month }),
+            &query.currency,
+        ).await?;
+        let existing_customer = get_or_create_stripe_customer(&app, &session.user).await?;
 
-        if let Some(p) = products.data.pop() {
-            let mut pricing = app.stripe.list_all_prices(ListAllPricesRequest{
-                product: Some(p.id.clone()),
-                recurring: Some(StripeRecurring{
-                    interval: Some(
-                        if query.annual {
-                            StripeRecurringInterval::Year
-                        } else {
-                            StripeRecurringInterval::Month
-                        }
-                    )
-                }),
-                currency: Some(query.currency.clone()),
-            }).await?;
-
-            if let Some(price) = pricing.data.pop() {
-                let existing_customer = if let Some(customer) = subscriptions::get_user_stripe_customer_id(&*app.pool, session.user.id).await? {
-                    customer
-                } else {
-                    // Create the customer here and link to our own systems here. This way we don't
-                    // have to rely on other hacks to try and find the user later.
-                    let customer = app.stripe.create_a_customer(&session.user.email, &session.user.username).await?;
-
-                    let mut tx = app.pool.begin().await?;
-                    subscriptions::associate_user_id_with_customer_id(&mut tx, session.user.id, &customer.id).await?;
-                    tx.commit().await?;
-
-                    customer.id
-                };
-
-                // Now we have the product + price + any potential discounts we want to apply.
-                // We can go ahead and create the Stripe checkout session.
-                let session = app.stripe.create_a_session(StripeCreateSessionRequest{
-                    cancel_url: format!("{}/subscription?success=0&tier={}&annual={}", &app.config.squadov.app_url, &query.tier, query.annual),
-                    success_url: format!("{}/subscription?success=1&tier={}&annual={}", &app.config.squadov.app_url, &query.tier, query.annual),
-                    mode: StripeCheckoutSessionMode::Subscription,
-                    line_items: vec![
-                        StripeCheckoutLineItem{
-                            price: price.id,
-                            quantity: Some(1),
-                            subscription: None,
-                        }
-                    ],
-                    discounts: if let Some(d) = discounts.first() {
-                        vec![StripeCheckoutDiscount{
-                            coupon: Some(d.id.clone()),
-                            promotion_code: None,
-                        }]
-                    } else {
-                        vec![]
-                    },
-                    client_reference_id: Some(session.user.uuid.to_string()),
-                    customer: Some(existing_customer),
-                    customer_email: None,
-                    subscription_data: if can_do_trial {
-                        Some(StripeCheckoutSubscriptionData{
-                            trial_period_days: Some(7),
-                        })
-                    } else {
-                        None
-                    },
-                    allow_promotion_codes: discounts.is_empty(),
-                }).await?;
-
-                Ok(HttpResponse::Ok().json(session.url))
+        // Now we have the product + price + any potential discounts we want to apply.
+        // We can go ahead and create the Stripe checkout session.
+        let session = app.stripe.create_a_session(StripeCreateSessionRequest{
+            cancel_url: format!("{}/subscription?success=0&tier={}&annual={}", &app.config.squadov.app_url, &query.tier, query.annual),
+            success_url: format!("{}/subscription?success=1&tier={}&annual={}", &app.config.squadov.app_url, &query.tier, query.annual),
+            mode: StripeCheckoutSessionMode::Subscription,
+            line_items: vec![
+                StripeCheckoutLineItem{
+                    price: price.id,
+                    quantity: Some(1),
+                    subscription: None,
+                }
+            ],
+            discounts: stripe_checkout_discounts(&discounts),
+            client_reference_id: Some(session.user.uuid.to_string()),
+            customer: Some(existing_customer),
+            customer_email: None,
+            subscription_data: if can_do_trial {
+                Some(StripeCheckoutSubscriptionData{
+                    trial_period_days: Some(7),
+                    ..StripeCheckoutSubscriptionData::default()
+                })
             } else {
-                Err(SquadOvError::NotFound)
-            }
-        } else {
-            Err(SquadOvError::NotFound)
-        }
+                None
+            },
+            allow_promotion_codes: discounts.is_empty(),
+            metadata: HashMap::new(),
+        }).await?;
+
+        Ok(HttpResponse::Ok().json(session.url))
     }
 }
 
@@ -292,6 +307,12 @@ pub async fn get_user_tier_handler(app : web::Data<Arc<api::ApiApplication>>, se
     ))
 }
 
+pub async fn get_user_sub_entitlements_handler(app : web::Data<Arc<api::ApiApplication>>, session: SquadOVSession) -> Result<HttpResponse, SquadOvError> {
+    Ok(HttpResponse::Ok().json(
+        subscriptions::get_user_sub_entitlements(&*app.pool, session.user.id).await?
+    ))
+}
+
 impl api::ApiApplication {
     pub async fn update_user_subscription(&self, user_id: i64) -> Result<(), SquadOvError> {
         let tier = subscriptions::get_user_sub_tier(&*self.pool, user_id).await?;
@@ -368,8 +389,13 @@ impl api::ApiApplication {
                 }).await?;
             },
         }
-        vdb::update_user_vods_expiration_from_feature_flags(&mut tx, user_id).await?;
-        v1::edit_user_max_squad_size_from_feature_flags(&mut tx, user_id).await?;
+        tx.commit().await?;
+
+        // Rules and overrides get evaluated against the stored flags so this can only happen once those are committed.
+        let evaluated = v1::get_evaluated_feature_flags(&*self.pool, user_id, None).await?;
+        let mut tx = self.pool.begin().await?;
+        vdb::update_user_vods_expiration(&mut tx, user_id, evaluated.flags.vod_retention).await?;
+        v1::edit_user_max_squad_size(&mut tx, user_id, evaluated.flags.max_squad_size).await?;
         tx.commit().await?;
         Ok(())
     }
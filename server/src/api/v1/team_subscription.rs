@@ -0,0 +1,166 @@
+use actix_web::{
+    web,
+    HttpResponse,
+};
+use crate::api::{
+    self,
+    auth::SquadOVSession,
+    v1,
+};
+use std::sync::Arc;
+use std::collections::HashMap;
+use squadov_common::{
+    SquadOvError,
+    stripe::{
+        price::StripeRecurringInterval,
+        checkout::{
+            StripeCreateSessionRequest,
+            StripeCheckoutSessionMode,
+            StripeCheckoutLineItem,
+            StripeCheckoutSubscriptionData,
+        },
+        currency::StripeCurrency,
+        customer_portal::StripeCreatePortalSessionRequest,
+    },
+    subscriptions::{
+        self,
+        SquadOvSubTiers,
+        TeamSubscription,
+        TeamSubscriptionSeat,
+    },
+};
+use serde::{Serialize, Deserialize};
+
+// Stripe requires some upper bound on quantity and this is well above any squad we've seen paying.
+const MAX_TEAM_SUBSCRIPTION_SEATS: i32 = 500;
+
+#[derive(Deserialize)]
+pub struct TeamSubscriptionSquadInput {
+    pub squad_id: i64,
+}
+
+#[derive(Deserialize)]
+pub struct TeamSubscriptionSeatInput {
+    pub squad_id: i64,
+    pub team_subscription_id: i64,
+    pub user_id: i64,
+}
+
+#[derive(Deserialize)]
+pub struct TeamCheckoutQuery {
+    pub tier: SquadOvSubTiers,
+    pub annual: bool,
+    pub seats: i32,
+    #[serde(default="StripeCurrency::default")]
+    pub currency: StripeCurrency,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct TeamSubscriptionWithSeats {
+    #[serde(flatten)]
+    pub subscription: TeamSubscription,
+    pub assigned: Vec<TeamSubscriptionSeat>,
+}
+
+pub async fn start_team_subscription_checkout_handler(app : web::Data<Arc<api::ApiApplication>>, session: SquadOVSession, path: web::Path<TeamSubscriptionSquadInput>, query: web::Query<TeamCheckoutQuery>) -> Result<HttpResponse, SquadOvError> {
+    if query.seats < 1 || query.seats > MAX_TEAM_SUBSCRIPTION_SEATS {
+        return Err(SquadOvError::BadRequest);
+    }
+
+    // A squad only gets one team subscription at a time. Changing the tier or number of seats goes through the customer portal.
+    let existing = subscriptions::get_team_subscriptions_for_squad(&*app.pool, path.squad_id).await?;
+    if let Some(active) = existing.iter().find(|x| { x.is_active() }) {
+        if active.owner_user_id != session.user.id {
+            return Err(SquadOvError::Duplicate);
+        }
+
+        let portal = app.stripe.create_a_portal_session(StripeCreatePortalSessionRequest{
+            customer: subscriptions::get_user_stripe_customer_id(&*app.pool, session.user.id).await?.ok_or(SquadOvError::NotFound)?,
+            return_url: Some(format!("{}/squad/{}", &app.config.squadov.app_url, path.squad_id)),
+        }).await?;
+        return Ok(HttpResponse::Ok().json(portal.url));
+    }
+
+    let price = v1::find_stripe_price(
+        &app,
+        HashMap::from([
+            ("team_tier".to_string(), format!("{}", query.tier))
+        ]),
+        Some(if query.annual { StripeRecurringInterval::Year } else { StripeRecurringInterval::Month }),
+        &query.currency,
+    ).await?;
+    let customer = v1::get_or_create_stripe_customer(&app, &session.user).await?;
+
+    let checkout = app.stripe.create_a_session(StripeCreateSessionRequest{
+        cancel_url: format!("{}/squad/{}?teamSubscription=0", &app.config.squadov.app_url, path.squad_id),
+        success_url: format!("{}/squad/{}?teamSubscription=1", &app.config.squadov.app_url, path.squad_id),
+        mode: StripeCheckoutSessionMode::Subscription,
+        line_items: vec![
+            StripeCheckoutLineItem{
+                price: price.id,
+                quantity: Some(query.seats),
+                subscription: None,
+            }
+        ],
+        discounts: vec![],
+        client_reference_id: Some(session.user.uuid.to_string()),
+        customer: Some(customer),
+        customer_email: None,
+        // The squad ID needs to be on the subscription itself since that's all we get in later subscription events.
+        subscription_data: Some(StripeCheckoutSubscriptionData{
+            trial_period_days: None,
+            metadata: HashMap::from([
+                ("squad_id".to_string(), path.squad_id.to_string()),
+            ]),
+        }),
+        allow_promotion_codes: true,
+        metadata: HashMap::new(),
+    }).await?;
+
+    Ok(HttpResponse::Ok().json(checkout.url))
+}
+
+pub async fn list_team_subscriptions_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<TeamSubscriptionSquadInput>) -> Result<HttpResponse, SquadOvError> {
+    let mut ret: Vec<TeamSubscriptionWithSeats> = vec![];
+    for subscription in subscriptions::get_team_subscriptions_for_squad(&*app.pool, path.squad_id).await? {
+        let assigned = subscriptions::get_team_subscription_seats(&*app.pool, subscription.id).await?;
+        ret.push(TeamSubscriptionWithSeats{
+            subscription,
+            assigned,
+        });
+    }
+    Ok(HttpResponse::Ok().json(ret))
+}
+
+async fn get_squad_team_subscription(app: &api::ApiApplication, squad_id: i64, team_subscription_id: i64) -> Result<TeamSubscription, SquadOvError> {
+    let sub = subscriptions::get_team_subscription(&*app.pool, team_subscription_id).await?;
+    if sub.squad_id != Some(squad_id) {
+        return Err(SquadOvError::NotFound);
+    }
+    Ok(sub)
+}
+
+pub async fn assign_team_subscription_seat_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<TeamSubscriptionSeatInput>) -> Result<HttpResponse, SquadOvError> {
+    get_squad_team_subscription(&app, path.squad_id, path.team_subscription_id).await?;
+    if app.get_squad_user_role(path.squad_id, path.user_id).await?.is_none() {
+        return Err(SquadOvError::BadRequest);
+    }
+
+    let mut tx = app.pool.begin().await?;
+    subscriptions::assign_team_subscription_seat(&mut tx, path.team_subscription_id, path.user_id).await?;
+    tx.commit().await?;
+
+    app.update_user_subscription(path.user_id).await?;
+    app.discord.request_sync_user(path.user_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn remove_team_subscription_seat_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<TeamSubscriptionSeatInput>) -> Result<HttpResponse, SquadOvError> {
+    get_squad_team_subscription(&app, path.squad_id, path.team_subscription_id).await?;
+    subscriptions::remove_team_subscription_seats(&*app.pool, path.team_subscription_id, &[path.user_id]).await?;
+
+    app.update_user_subscription(path.user_id).await?;
+    app.discord.request_sync_user(path.user_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
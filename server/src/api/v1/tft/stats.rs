@@ -0,0 +1,28 @@
+use squadov_common::{
+    SquadOvError,
+    riot::{
+        db,
+        stats::tft::{
+            TftStatsFilters,
+            TftStatsSummary,
+        },
+    },
+};
+use crate::api;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct TftUserStatsInput {
+    puuid: String,
+}
+
+pub async fn get_tft_player_stats_summary_handler(data : web::Path<TftUserStatsInput>, query: web::Query<api::PaginationParameters>, filters: web::Json<TftStatsFilters>, app : web::Data<Arc<api::ApiApplication>>) -> Result<HttpResponse, SquadOvError> {
+    let placements = db::get_tft_player_placement_rows(&*app.pool, &data.puuid, query.start, query.end, &filters).await?;
+    let match_uuids: Vec<Uuid> = placements.iter().map(|x| { x.match_uuid.clone() }).collect();
+    let traits = db::get_tft_player_active_trait_rows(&*app.pool, &data.puuid, &match_uuids).await?;
+    let units = db::get_tft_player_unit_rows(&*app.pool, &data.puuid, &match_uuids).await?;
+    Ok(HttpResponse::Ok().json(TftStatsSummary::from_rows(&placements, &traits, &units)))
+}
@@ -1,20 +1,39 @@
-use actix_web::{web, HttpResponse, HttpRequest};
+use actix_web::{web, HttpResponse, HttpRequest, HttpMessage};
 use squadov_common::{
     SquadOvError,
     twitch::{
-        api::TwitchSubscriptionAlt,
+        api::{
+            TwitchSubscriptionAlt,
+            EventSubCondition,
+            EventSubTransport,
+        },
         eventsub::{
             self,
+            TwitchStreamOnlineEvent,
+            TwitchStreamOfflineEvent,
             TWITCH_CHANNEL_SUBSCRIBE,
             TWITCH_CHANNEL_UNSUB,
+            TWITCH_STREAM_ONLINE,
+            TWITCH_STREAM_OFFLINE,
+        },
+        streams::{
+            self,
+            TwitchMatchLink,
+            TwitchStreamEvent,
+            TwitchStreamEventHub,
         },
     },
+    vod::db as vdb,
+};
+use crate::api::{
+    ApiApplication,
+    auth::{SquadOVSession, SquadOvMachineId},
+    v1::GenericMatchPathInput,
 };
-use crate::api::ApiApplication;
 use std::sync::Arc;
-use serde::Deserialize;
-use sha2::Sha256;
-use hmac::{Hmac, Mac};
+use serde::{Serialize, Deserialize};
+use chrono::Utc;
+use uuid::Uuid;
 
 #[cfg(feature = "eventloop")]
 use squadov_common::{
@@ -39,22 +58,24 @@ pub struct TwitchEventSubNotification {
     event: Option<serde_json::Value>,
 }
 
-async fn handle_twitch_eventsub_challenge(app : web::Data<Arc<ApiApplication>>, challenge: String, raw_data: web::Bytes, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct TwitchMatchLinkOutput {
+    #[serde(flatten)]
+    link: TwitchMatchLink,
+    jump_url: String,
+}
+
+// Every message (not just the challenge) needs to be checked since the endpoint is public.
+fn verify_twitch_eventsub_request(app: &ApiApplication, raw_data: &[u8], req: &HttpRequest) -> Result<(), SquadOvError> {
     let headers = req.headers();
     let message_id = headers.get("Twitch-Eventsub-Message-Id").ok_or(SquadOvError::Forbidden)?.to_str()?;
     let message_timestamp =  headers.get("Twitch-Eventsub-Message-Timestamp").ok_or(SquadOvError::Forbidden)?.to_str()?;
+    let signature = headers.get("Twitch-Eventsub-Message-Signature").ok_or(SquadOvError::Forbidden)?.to_str()?;
+    eventsub::verify_twitch_eventsub_message(&app.config.squadov.hashid_salt, message_id, message_timestamp, raw_data, signature, Utc::now())
+}
 
-    let mut mac = Hmac::<Sha256>::new_from_slice(app.config.squadov.hashid_salt.as_bytes())?;
-    mac.update(message_id.as_bytes());
-    mac.update(message_timestamp.as_bytes());
-    mac.update(&raw_data);
-    let ref_signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
-    let test_signature = headers.get("Twitch-Eventsub-Message-Signature").ok_or(SquadOvError::Forbidden)?.to_str()?;
-
-    if test_signature != ref_signature {
-        return Err(SquadOvError::Forbidden);
-    }
-    
+async fn handle_twitch_eventsub_challenge(app : web::Data<Arc<ApiApplication>>, challenge: String, raw_data: web::Bytes) -> Result<HttpResponse, SquadOvError> {
     // Keep track of subscription in the database just in case there's a day where we want to mass delete them all.
     let parsed: TwitchEventSubNotification = serde_json::from_slice(&raw_data)?;
     let raw: serde_json::Value = serde_json::from_slice(&raw_data)?;
@@ -74,11 +95,69 @@ impl ApiApplication {
         Ok(())
     }
 
+    async fn handle_twitch_stream_online(&self, stream_events: &TwitchStreamEventHub, event: TwitchStreamOnlineEvent) -> Result<(), SquadOvError> {
+        if !streams::start_twitch_stream(&*self.pool, &event).await? {
+            return Ok(());
+        }
+
+        for s in streams::get_live_twitch_streams_for_twitch_user(&*self.pool, &event.broadcaster_user_id).await? {
+            if s.stream_id != event.id {
+                continue;
+            }
+
+            // Not being able to notify squadmates shouldn't make Twitch resend the notification.
+            if let Err(err) = stream_events.publish(&TwitchStreamEvent{stream: s, live: true}).await {
+                log::warn!("Failed to publish Twitch stream event: {:?}", err);
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_twitch_stream_offline(&self, stream_events: &TwitchStreamEventHub, event: TwitchStreamOfflineEvent) -> Result<(), SquadOvError> {
+        let live = streams::get_live_twitch_streams_for_twitch_user(&*self.pool, &event.broadcaster_user_id).await?;
+        for s in streams::end_twitch_streams(&*self.pool, &event.broadcaster_user_id, &Utc::now()).await? {
+            self.twitch_itf.request_link_stream_vods(&s.id).await?;
+        }
+
+        for s in live {
+            if let Err(err) = stream_events.publish(&TwitchStreamEvent{stream: s, live: false}).await {
+                log::warn!("Failed to publish Twitch stream event: {:?}", err);
+            }
+        }
+        Ok(())
+    }
+
+    // For Twitch accounts that were linked before we started listening for streams going live.
+    pub async fn register_missing_twitch_stream_eventsubs(&self) -> Result<i64, SquadOvError> {
+        let transport = EventSubTransport{
+            method: String::from("webhook"),
+            callback: format!("{}/twitch/eventsub", &self.config.twitch.eventsub_hostname),
+            secret: self.config.squadov.hashid_salt.clone(),
+        };
+
+        let mut count: i64 = 0;
+        for sub in &[TWITCH_STREAM_ONLINE, TWITCH_STREAM_OFFLINE] {
+            for twitch_user_id in streams::get_twitch_accounts_missing_eventsub(&*self.pool, sub).await? {
+                let condition = EventSubCondition{
+                    broadcaster_user_id: twitch_user_id.clone(),
+                };
+
+                match self.twitch_api.register_eventsub_subscription(sub, condition, transport.clone()).await {
+                    Ok(_) => count += 1,
+                    // Twitch already has it but we never stored the challenge.
+                    Err(SquadOvError::Duplicate) => (),
+                    Err(err) => log::warn!("Failed to register Twitch {} EventSub for {}: {:?}", sub, &twitch_user_id, err),
+                }
+            }
+        }
+        Ok(count)
+    }
+
     #[cfg(feature = "eventloop")]
     pub async fn reverify_twitch_account_access_tokens(&self) -> Result<(), SquadOvError> {
         let accounts = twitch::get_twitch_accounts_need_validation(&*self.pool).await?;
         for a in accounts {
-            if oauth::validate_access_token(&a.access_token).await? {
+            if oauth::validate_access_token(&self.config.twitch.id_url, &a.access_token).await? {
                 twitch::update_twitch_account_last_update(&*self.pool, &a.access_token).await?;
             } else {
                 twitch::delete_twitch_account(&*self.pool, &a.access_token).await?;
@@ -88,12 +167,19 @@ impl ApiApplication {
     }
 }
 
-pub async fn on_twitch_eventsub_handler(app : web::Data<Arc<ApiApplication>>, data: web::Bytes, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+pub async fn on_twitch_eventsub_handler(app : web::Data<Arc<ApiApplication>>, stream_events: web::Data<Arc<TwitchStreamEventHub>>, data: web::Bytes, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    verify_twitch_eventsub_request(&app, &data, &req)?;
+
     let parsed: TwitchEventSubNotification = serde_json::from_slice(&data)?;
     if let Some(challenge) = parsed.challenge {
-        handle_twitch_eventsub_challenge(app, challenge.clone(), data, req).await
+        handle_twitch_eventsub_challenge(app, challenge.clone(), data).await
     } else {
         let message_type = req.headers().get("Twitch-Eventsub-Message-Type").ok_or(SquadOvError::BadRequest)?.to_str()?;
+        if message_type == "revocation" {
+            log::warn!("Twitch revoked EventSub subscription: {} [{}]", &parsed.subscription.id, &parsed.subscription.sub_type);
+            return Ok(HttpResponse::NoContent().finish());
+        }
+
         if message_type != "notification" {
             return Err(SquadOvError::BadRequest);
         }
@@ -108,10 +194,45 @@ pub async fn on_twitch_eventsub_handler(app : web::Data<Arc<ApiApplication>>, da
                     app.handle_twitch_unsub(serde_json::from_value::<TwitchSubscriptionAlt>(event)?).await?;
                     Ok(HttpResponse::NoContent().finish())
                 },
+                TWITCH_STREAM_ONLINE => {
+                    app.handle_twitch_stream_online(&stream_events, serde_json::from_value::<TwitchStreamOnlineEvent>(event)?).await?;
+                    Ok(HttpResponse::NoContent().finish())
+                },
+                TWITCH_STREAM_OFFLINE => {
+                    app.handle_twitch_stream_offline(&stream_events, serde_json::from_value::<TwitchStreamOfflineEvent>(event)?).await?;
+                    Ok(HttpResponse::NoContent().finish())
+                },
                 _ => Err(SquadOvError::BadRequest)
             }
         } else {
             Err(SquadOvError::BadRequest)
         }
     }
-}
\ No newline at end of file
+}
+
+// Squadmates (in any squad) that are currently live on Twitch.
+pub async fn get_live_squadmate_streams_handler(app : web::Data<Arc<ApiApplication>>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+
+    let user_ids: Vec<i64> = app.get_user_ids_in_same_squad_as_users(&[session.user.id], None).await?.into_iter().filter(|x| { *x != session.user.id }).collect();
+    Ok(HttpResponse::Ok().json(streams::get_live_twitch_streams_for_users(&*app.pool, &user_ids).await?))
+}
+
+// Twitch archives/clips that cover the match for the users whose VODs the current user can see.
+pub async fn get_match_twitch_links_handler(app : web::Data<Arc<ApiApplication>>, match_path: web::Path<GenericMatchPathInput>, req: HttpRequest, machine_id: Option<web::Header<SquadOvMachineId>>) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+
+    let vods = vdb::find_accessible_vods_in_match_for_user(&*app.pool, &match_path.match_uuid, session.user.id, machine_id.map(|x| { x.id.clone() }).unwrap_or(String::new()).as_str()).await?;
+    let user_uuids: Vec<Uuid> = vods.into_iter().filter_map(|x| { x.user_uuid }).collect();
+
+    Ok(HttpResponse::Ok().json(
+        streams::get_twitch_match_links(&*app.pool, &match_path.match_uuid, &user_uuids).await?.into_iter().map(|link| {
+            TwitchMatchLinkOutput{
+                jump_url: link.jump_url(),
+                link,
+            }
+        }).collect::<Vec<TwitchMatchLinkOutput>>()
+    ))
+}
@@ -7,6 +7,8 @@ mod status;
 mod playtime;
 mod squad;
 mod analytics;
+mod friends;
+mod teammates;
 
 pub use profile::*;
 pub use accounts::*;
@@ -17,6 +19,8 @@ pub use status::*;
 pub use playtime::*;
 pub use squad::*;
 pub use analytics::*;
+pub use friends::*;
+pub use teammates::*;
 
 use serde::Deserialize;
 
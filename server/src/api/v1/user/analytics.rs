@@ -154,8 +154,8 @@ impl ApiApplication {
         Ok(ret)
     }
 
-    pub async fn analytics_identify_user(&self, user: &SquadOVUser, ip_addr: &str, anon_id: &str) -> Result<(), SquadOvError> {
-        let loc_data: Option<LocationData> = if !ip_addr.is_empty() {
+    pub async fn get_ip_location_data(&self, ip_addr: &str) -> Result<Option<LocationData>, SquadOvError> {
+        Ok(if !ip_addr.is_empty() {
             let parsed_ip = IpAddr::from_str(ip_addr)?;
 
             if parsed_ip.is_loopback() {
@@ -175,7 +175,11 @@ impl ApiApplication {
             }
         } else {
             None
-        };
+        })
+    }
+
+    pub async fn analytics_identify_user(&self, user: &SquadOVUser, ip_addr: &str, anon_id: &str) -> Result<(), SquadOvError> {
+        let loc_data: Option<LocationData> = self.get_ip_location_data(ip_addr).await?;
 
         // Get user hardware information.
         let hardware = get_hardware_for_user(&*self.pool, user.id).await?;
@@ -0,0 +1,44 @@
+use actix_web::{web, HttpResponse, HttpRequest, HttpMessage};
+use crate::api;
+use crate::api::auth::SquadOVSession;
+use squadov_common::{
+    SquadOvError,
+    csgo::db as csgo_db,
+    steam::friends::{
+        self,
+        FriendSuggestionSettings,
+    },
+};
+use std::sync::Arc;
+
+const MAX_FRIEND_SUGGESTIONS: usize = 50;
+
+pub async fn get_user_friend_suggestions_handler(app : web::Data<Arc<api::ApiApplication>>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+
+    // Refresh the user's friend lists in the background for next time (the worker skips lists synced in the last day).
+    let steam_ids: Vec<i64> = csgo_db::get_steam_ids_for_user(&*app.pool, session.user.id).await?.into_iter().collect();
+    if !steam_ids.is_empty() {
+        app.steam_itf.request_sync_steam_friends(&steam_ids).await?;
+    }
+
+    Ok(HttpResponse::Ok().json(
+        friends::get_friend_suggestions(&*app.pool, session.user.id, MAX_FRIEND_SUGGESTIONS).await?
+    ))
+}
+
+pub async fn get_user_friend_suggestion_settings_handler(app : web::Data<Arc<api::ApiApplication>>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+    Ok(HttpResponse::Ok().json(
+        friends::get_friend_suggestion_settings(&*app.pool, session.user.id).await?
+    ))
+}
+
+pub async fn edit_user_friend_suggestion_settings_handler(app : web::Data<Arc<api::ApiApplication>>, data: web::Json<FriendSuggestionSettings>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+    friends::update_friend_suggestion_settings(&*app.pool, session.user.id, &data).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
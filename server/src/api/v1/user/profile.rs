@@ -66,19 +66,15 @@ pub async fn edit_current_user_username_handler(app : web::Data<Arc<api::ApiAppl
 
     let mut tx = app.pool.begin().await?;
     // Edit the user in the database first so that in the rare case that this fails, we don't
-    // propagate the change to fusionauth.
+    // propagate the change to the identity provider.
     app.edit_user(&mut tx, &user).await?;
 
     // We need to also change the user's referral code since it's based off their username
     app.regenerate_user_referral_code(&mut tx, user.id).await?;
 
-    // Finally change the user in fusionauth. If the fusionauth change fails none of the above
+    // Finally change the user in the identity provider. If that change fails none of the above
     // gets changed since we still haven't committed the transaction yet.
-    let fa_user = app.clients.fusionauth.find_user_from_email_address(&session.user.email).await.map_err(|x| {
-        SquadOvError::InternalError(format!("Failed to find user from email address: {:?}", x))
-    })?;
-
-    app.clients.fusionauth.update_user_id(&fa_user.id, &user.username, &session.user.email).await?;
+    app.clients.identity.update_user(&session.user.email, &user.username, &session.user.email).await?;
     tx.commit().await?;
 
     // Also update the user's username in analytics.
@@ -103,16 +99,12 @@ pub async fn edit_current_user_email_handler(app : web::Data<Arc<api::ApiApplica
 
     let mut tx = app.pool.begin().await?;
     // Edit the user in the database first so that in the rare case that this fails, we don't
-    // propagate the change to fusionauth.
+    // propagate the change to the identity provider.
     app.edit_user(&mut tx, &user).await?;
 
-    // Finally change the user in fusionauth. If the fusionauth change fails none of the above
+    // Finally change the user in the identity provider. If that change fails none of the above
     // gets changed since we still haven't committed the transaction yet.
-    let fa_user = app.clients.fusionauth.find_user_from_email_address(&session.user.email).await.map_err(|x| {
-        SquadOvError::InternalError(format!("Failed to find user from email address: {:?}", x))
-    })?;
-
-    app.clients.fusionauth.update_user_id(&fa_user.id, &session.user.username, &user.email).await?;
+    app.clients.identity.update_user(&session.user.email, &session.user.username, &user.email).await?;
     tx.commit().await?;
 
     // Also update the user's email in analytics.
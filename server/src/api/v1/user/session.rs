@@ -18,15 +18,11 @@ async fn handle_session_user_backfill_tasks(app : web::Data<Arc<api::ApiApplicat
     }
 
     if session.user.registration_time.is_none() {
-        let fa_user = app.clients.fusionauth.find_user_from_email_address(&session.user.email).await.map_err(|x| {
-            SquadOvError::InternalError(format!("Failed to find user from email address: {:?}", x))
-        })?;
-        let reg = app.clients.fusionauth.find_auth_registration(&fa_user);
-        if reg.is_some() {
-            let reg_time = DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(reg.as_ref().unwrap().insert_instant / 1000, 0), Utc);
-            app.update_user_registration_time(session.user.id, &reg_time).await?;
+        let idp_user = app.clients.identity.find_user_from_email(&session.user.email).await?;
+        if let Some(reg_time) = idp_user.registration_time.as_ref() {
+            app.update_user_registration_time(session.user.id, reg_time).await?;
         } else {
-            log::warn!("Failed to find FusionAuth registration for user: {}", session.user.id);
+            log::warn!("Failed to find identity provider registration for user: {}", session.user.id);
         }
     }
     Ok(())
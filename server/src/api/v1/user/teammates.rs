@@ -0,0 +1,38 @@
+use actix_web::{web, HttpResponse, HttpRequest, HttpMessage};
+use crate::api;
+use crate::api::auth::SquadOVSession;
+use squadov_common::{
+    SquadOvError,
+    teammates::{
+        TeammateAnalyticsFilter,
+        TeammateAnalyticsSettings,
+        db as tdb,
+    },
+};
+use std::sync::Arc;
+
+// Per category (teammates, duos, trios, opponents, rivalries).
+const MAX_TEAMMATE_ANALYTICS_RESULTS: usize = 25;
+
+pub async fn get_user_teammate_analytics_handler(app : web::Data<Arc<api::ApiApplication>>, data: web::Json<TeammateAnalyticsFilter>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+    Ok(HttpResponse::Ok().json(
+        tdb::get_teammate_analytics(&*app.pool, session.user.id, &data, MAX_TEAMMATE_ANALYTICS_RESULTS).await?
+    ))
+}
+
+pub async fn get_user_teammate_analytics_settings_handler(app : web::Data<Arc<api::ApiApplication>>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+    Ok(HttpResponse::Ok().json(
+        tdb::get_teammate_analytics_settings(&*app.pool, session.user.id).await?
+    ))
+}
+
+pub async fn edit_user_teammate_analytics_settings_handler(app : web::Data<Arc<api::ApiApplication>>, data: web::Json<TeammateAnalyticsSettings>, req: HttpRequest) -> Result<HttpResponse, SquadOvError> {
+    let extensions = req.extensions();
+    let session = extensions.get::<SquadOVSession>().ok_or(SquadOvError::Unauthorized)?;
+    tdb::update_teammate_analytics_settings(&*app.pool, session.user.id, &data).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,3 +1,4 @@
+mod analytics;
 mod backfill;
 mod create;
 mod list;
@@ -62,6 +63,7 @@ impl Default for ValorantPlayerStatsSummary {
     }
 }
 
+pub use analytics::*;
 pub use backfill::*;
 pub use create::*;
 pub use list::*;
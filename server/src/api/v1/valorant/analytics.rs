@@ -0,0 +1,39 @@
+use squadov_common::{
+    SquadOvError,
+    riot::{
+        db,
+        games::valorant::analytics::{
+            ValorantMatchRoundAnalytics,
+            ValorantPlayerRoundAnalytics,
+        },
+    },
+};
+use crate::api;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct ValorantMatchRoundAnalyticsInput {
+    match_uuid: Uuid,
+}
+
+#[derive(Deserialize)]
+pub struct ValorantPlayerRoundAnalyticsInput {
+    puuid: String,
+}
+
+pub async fn get_valorant_match_round_analytics_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<ValorantMatchRoundAnalyticsInput>) -> Result<HttpResponse, SquadOvError> {
+    let economy = db::get_valorant_match_economy_rows(&*app.pool, &path.match_uuid).await?;
+    let first_kills = db::get_valorant_match_first_kills(&*app.pool, &path.match_uuid).await?;
+    let abilities = db::get_valorant_match_ability_casts(&*app.pool, &path.match_uuid).await?;
+    Ok(HttpResponse::Ok().json(ValorantMatchRoundAnalytics::from_rows(&economy, first_kills, &abilities, &app.config.valorant_economy)))
+}
+
+pub async fn get_valorant_player_round_analytics_handler(app : web::Data<Arc<api::ApiApplication>>, path: web::Path<ValorantPlayerRoundAnalyticsInput>) -> Result<HttpResponse, SquadOvError> {
+    let economy = db::get_valorant_player_economy_rows(&*app.pool, &path.puuid).await?;
+    let first_kills = db::get_valorant_player_first_kills(&*app.pool, &path.puuid).await?;
+    let abilities = db::get_valorant_player_ability_casts(&*app.pool, &path.puuid).await?;
+    Ok(HttpResponse::Ok().json(ValorantPlayerRoundAnalytics::from_rows(&path.puuid, &economy, &first_kills, &abilities, &app.config.valorant_economy)))
+}
@@ -235,7 +235,7 @@ impl api::ApiApplication {
         Ok(())
     }
 
-    async fn add_vod_favorite_for_user(&self, video_uuid: &Uuid, user_id: i64, reason: &str) -> Result<(), SquadOvError> {
+    pub async fn add_vod_favorite_for_user(&self, video_uuid: &Uuid, user_id: i64, reason: &str) -> Result<(), SquadOvError> {
         sqlx::query!(
             r#"
             INSERT INTO squadov.user_favorite_vods (
@@ -261,7 +261,7 @@ impl api::ApiApplication {
         Ok(())
     }
 
-    async fn remove_vod_favorite_for_user(&self, video_uuid: &Uuid, user_id: i64) -> Result<(), SquadOvError> {
+    pub async fn remove_vod_favorite_for_user(&self, video_uuid: &Uuid, user_id: i64) -> Result<(), SquadOvError> {
         sqlx::query!(
             r#"
             DELETE FROM squadov.user_favorite_vods
@@ -295,7 +295,7 @@ impl api::ApiApplication {
         )
     }
 
-    async fn add_vod_watchlist_for_user(&self, video_uuid: &Uuid, user_id: i64) -> Result<(), SquadOvError> {
+    pub async fn add_vod_watchlist_for_user(&self, video_uuid: &Uuid, user_id: i64) -> Result<(), SquadOvError> {
         sqlx::query!(
             r#"
             INSERT INTO squadov.user_watchlist_vods (
@@ -318,7 +318,7 @@ impl api::ApiApplication {
         Ok(())
     }
 
-    async fn remove_vod_watchlist_for_user(&self, video_uuid: &Uuid, user_id: i64) -> Result<(), SquadOvError> {
+    pub async fn remove_vod_watchlist_for_user(&self, video_uuid: &Uuid, user_id: i64) -> Result<(), SquadOvError> {
         sqlx::query!(
             r#"
             DELETE FROM squadov.user_watchlist_vods
@@ -28,6 +28,8 @@ use squadov_common::{
     },
     elastic::vod::ESVodDocument,
     rabbitmq::RABBITMQ_DEFAULT_PRIORITY,
+    storage,
+    features,
 };
 use std::sync::Arc;
 use std::convert::TryFrom;
@@ -78,6 +80,7 @@ pub struct ClipCommentPathInput {
 
 impl api::ApiApplication {
     async fn create_clip_for_vod(&self, vod_uuid: &Uuid, user_id: i64, title: &str, description: &str, game: SquadOvGames, accel: bool) -> Result<ClipResponse, SquadOvError> {
+        storage::check_user_storage_quota(&*self.pool, user_id).await?;
         let clip_uuid = Uuid::new_v4();
 
         let mut tx = self.pool.begin().await?;
@@ -165,7 +168,7 @@ impl api::ApiApplication {
         }).collect::<Result<Vec<VodClip>, SquadOvError>>()?)
     }
 
-    async fn list_user_accessible_clips(&self, user_id: i64, start: i64, end: i64, filter: &RecentMatchQuery, machine_id: &str) -> Result<Vec<VodClip>, SquadOvError> {
+    pub async fn list_user_accessible_clips(&self, user_id: i64, start: i64, end: i64, filter: &RecentMatchQuery, machine_id: &str) -> Result<Vec<VodClip>, SquadOvError> {
         let es_search = filter.to_es_search(user_id, Some(machine_id), true)
             .from(start)
             .size(end)
@@ -437,17 +440,9 @@ pub async fn create_staged_clip_for_vod_handler(pth: web::Path<CreateClipPathInp
         return Err(SquadOvError::BadRequest);
     }
 
-    let can_instant_clip = sqlx::query!(
-        "
-        SELECT can_instant_clip
-        FROM squadov.user_feature_flags
-        WHERE user_id = $1
-        ",
-        session.user.id,
-    )
-        .fetch_one(&*app.pool)
-        .await?
-        .can_instant_clip;
+    storage::check_user_storage_quota(&*app.pool, session.user.id).await?;
+
+    let can_instant_clip = features::get_cached_evaluated_feature_flags(&*app.pool, session.user.id).await?.flags.can_instant_clip;
 
     // Returns a 200 to prevent client crashing.
     if !data.execute && !can_instant_clip {
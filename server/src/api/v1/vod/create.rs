@@ -16,8 +16,18 @@ use squadov_common::{
         db as vdb,
         manager::StorageType,
     },
-    storage::CloudStorageLocation,
+    storage::{
+        self,
+        CloudStorageLocation,
+    },
     rabbitmq::RABBITMQ_DEFAULT_PRIORITY,
+    matches,
+    squad::events::{
+        NewMatchEvent,
+        NewMatchEventHub,
+    },
+    features,
+    teammates::db as tdb,
 };
 
 #[derive(Deserialize)]
@@ -85,7 +95,7 @@ impl api::ApiApplication {
     }
 }
 
-pub async fn associate_vod_handler(path: web::Path<VodAssociatePathInput>, data : web::Json<super::VodAssociateBodyInput>, app : web::Data<Arc<api::ApiApplication>>, machine_id: Option<web::Header<SquadOvMachineId>>, request : HttpRequest) -> Result<HttpResponse, SquadOvError> {
+pub async fn associate_vod_handler(path: web::Path<VodAssociatePathInput>, data : web::Json<super::VodAssociateBodyInput>, app : web::Data<Arc<api::ApiApplication>>, match_events: web::Data<Arc<NewMatchEventHub>>, machine_id: Option<web::Header<SquadOvMachineId>>, request : HttpRequest) -> Result<HttpResponse, SquadOvError> {
     let data = data.into_inner();
     if path.video_uuid != data.association.video_uuid {
         return Err(SquadOvError::BadRequest);
@@ -120,8 +130,9 @@ pub async fn associate_vod_handler(path: web::Path<VodAssociatePathInput>, data
         return Err(SquadOvError::Unauthorized);
     }
 
+    let vod_retention = features::get_cached_evaluated_feature_flags(&*app.pool, session.user.id).await?.flags.vod_retention;
     let mut tx = app.pool.begin().await?;
-    vdb::associate_vod(&mut tx, &data.association).await?;
+    vdb::associate_vod(&mut tx, &data.association, vod_retention).await?;
 
     let metadata_id = data.metadata.id.clone();
     let bucket = data.metadata.bucket.clone();
@@ -139,6 +150,12 @@ pub async fn associate_vod_handler(path: web::Path<VodAssociatePathInput>, data
         app.handle_vod_share(&mut tx, session.user.id, &data.association).await?;
     }
 
+    if !data.association.is_clip {
+        if let Some(match_uuid) = data.association.match_uuid.as_ref() {
+            tdb::queue_teammate_roster_sync(&mut tx, match_uuid).await?;
+        }
+    }
+
     // Upon association, the video *should* only exist in one place. Either on the cloud OR on the user's machine.
     if data.association.is_local {
         if machine_id.is_none() {
@@ -154,6 +171,27 @@ pub async fn associate_vod_handler(path: web::Path<VodAssociatePathInput>, data
     // At this point the VOD/clip should be ready for an initial sync to ES.
     app.es_itf.request_sync_vod(vec![data.association.video_uuid.clone()]).await?;
 
+    // Let anyone listening (e.g. GraphQL subscriptions) know that a new match VOD is available. This is purely a notification so failures shouldn't fail the request.
+    if !data.association.is_clip {
+        if let Some(match_uuid) = data.association.match_uuid.as_ref() {
+            match matches::get_game_for_match(&*app.pool, match_uuid).await {
+                Ok(game) => {
+                    let event = NewMatchEvent{
+                        match_uuid: match_uuid.clone(),
+                        video_uuid: data.association.video_uuid.clone(),
+                        game,
+                        user_id: session.user.id,
+                    };
+
+                    if let Err(err) = match_events.publish(&event).await {
+                        log::warn!("Failed to publish new match event: {:?}", err);
+                    }
+                },
+                Err(err) => log::warn!("Failed to get game for new match event: {:?}", err),
+            }
+        }
+    }
+
     // Note that we don't want to spawn a task directly here to "fastify" the VOD
     // because it does take a significant amount of memory/disk space to do so.
     // So we toss it to the local job queue so we can better limit the amount of resources we end up using.
@@ -198,6 +236,8 @@ pub async fn create_vod_destination_handler(data : web::Json<VodCreateDestinatio
         Some(x) => x,
         None => return Err(SquadOvError::BadRequest)
     };
+
+    storage::check_user_storage_quota(&*app.pool, session.user.id).await?;
     
     let mut tx = app.pool.begin().await?;
     vdb::reserve_vod_uuid(&mut tx, &data.video_uuid, &data.container_format, session.user.id, false).await?;
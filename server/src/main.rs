@@ -100,6 +100,8 @@ async fn async_main(opts: Options) {
         }.create_pool(Some(deadpool_redis::Runtime::Tokio1)).unwrap());
 
         let user_status_tracker = squadov_common::squad::status::UserActivityStatusTracker::new(&config.redis, redis_pool.clone()).await;
+        let new_match_hub = squadov_common::squad::events::NewMatchEventHub::new(&config.redis, redis_pool.clone()).await;
+        let twitch_stream_hub = squadov_common::twitch::streams::TwitchStreamEventHub::new(&config.redis, redis_pool.clone()).await;
         
         // The API service is primarily used for dealing with API calls.actix_web
         // We're not going to have a web-based interface at the moment (only going to be desktop client-based)
@@ -132,6 +134,8 @@ async fn async_main(opts: Options) {
                 )
                 .wrap(Logger::default())
                 .app_data(web::Data::new(user_status_tracker.clone()))
+                .app_data(web::Data::new(new_match_hub.clone()))
+                .app_data(web::Data::new(twitch_stream_hub.clone()))
                 .app_data(web::Data::new(app.clone()))
                 .service(api_service::create_service(config.server.graphql_debug))
             })
@@ -7,8 +7,21 @@ use structopt::StructOpt;
 use std::{fs, sync::Arc};
 use uuid::Uuid;
 use squadov_common::{
-    rabbitmq::RABBITMQ_MAINTENANCE_PRIORITY,
+    hearthstone::db as hdb,
+    rabbitmq::{RABBITMQ_MAINTENANCE_PRIORITY, RabbitMqInterface},
+    riot::{
+        api::purge_expired_riot_api_cache,
+        db as riot_db,
+    },
+    stripe::events,
+    subscriptions,
+    teammates::db as tdb,
+    wow::reports::{
+        WowReportTypes,
+        stats::{self as wow_stats, WowUnitStatSummary},
+    },
 };
+use chrono::Utc;
 
 #[derive(StructOpt, Debug)]
 struct Options {
@@ -114,6 +127,264 @@ pub fn start_expired_vods_cleanup_loop(app: Arc<api::ApiApplication>) {
     });
 }
 
+pub fn start_expired_entitlements_loop(app: Arc<api::ApiApplication>) {
+    tokio::task::spawn(async move {
+        // Stripe tells us when personal and team subscriptions end but nothing tells us when a gift runs out
+        // or a team subscription's grace period ends so the affected users' feature flags get recomputed here.
+        let mut last_check = Utc::now() - chrono::Duration::days(1);
+        loop {
+            log::info!("Doing expired entitlements loop...");
+            let now = Utc::now();
+            let users = subscriptions::get_users_with_expired_entitlements(&*app.pool, &last_check).await.unwrap_or(vec![]);
+
+            log::info!("Found {} Users with Expired Entitlements", users.len());
+            for x in users {
+                match app.update_user_subscription(x).await {
+                    Ok(_) => (),
+                    Err(err) => log::warn!("...Failed to update user subscription [{}] {:?}", x, err),
+                }
+
+                match app.discord.request_sync_user(x).await {
+                    Ok(_) => (),
+                    Err(err) => log::warn!("...Failed to request Discord sync [{}] {:?}", x, err),
+                }
+            }
+            last_check = now;
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
+        }
+    });
+}
+
+pub fn start_stale_stripe_webhooks_loop(app: Arc<api::ApiApplication>) {
+    tokio::task::spawn(async move {
+        // Events normally get handled right after they come in; anything left behind here lost its queue message
+        // (or its worker) and gets queued up again.
+        loop {
+            log::info!("Doing stale Stripe webhooks loop...");
+            let events = events::get_stale_stripe_webhook_event_ids(&*app.pool, &(Utc::now() - chrono::Duration::minutes(15))).await.unwrap_or(vec![]);
+
+            log::info!("Found {} Stale Stripe Webhooks", events.len());
+            for x in &events {
+                match app.stripe_webhooks.request_process_event(x).await {
+                    Ok(_) => (),
+                    Err(err) => log::warn!("...Failed to requeue Stripe webhook [{}] {:?}", x, err),
+                }
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(900)).await;
+        }
+    });
+}
+
+pub fn start_stripe_reconciliation_loop(app: Arc<api::ApiApplication>) {
+    tokio::task::spawn(async move {
+        loop {
+            log::info!("Doing Stripe reconciliation loop...");
+            match api::v1::reconcile_stripe_customers(app.clone()).await {
+                Ok(mismatches) => {
+                    log::info!("Found {} Stripe Mismatches", mismatches.len());
+                    for x in mismatches {
+                        log::warn!("...Stripe mismatch for customer [{}] user {:?}: {:?} -> {:?} {:?}", &x.customer_id, x.user_id, x.stored_tier, x.stripe_tier, x.error);
+                    }
+                },
+                Err(err) => log::warn!("Failed to reconcile Stripe customers: {:?}", err),
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(86400)).await;
+        }
+    });
+}
+
+pub fn start_storage_reconciliation_loop(app: Arc<api::ApiApplication>) {
+    tokio::task::spawn(async move {
+        loop {
+            log::info!("Doing storage reconciliation loop...");
+            match api::v1::reconcile_storage_usage(app.clone()).await {
+                Ok(report) => log::info!("Reconciled Storage Usage: {:?}", report),
+                Err(err) => log::warn!("Failed to reconcile storage usage: {:?}", err),
+            }
+
+            // Each pass only looks at a batch of resources so this needs to run fairly often to get through everything.
+            tokio::time::sleep(tokio::time::Duration::from_secs(1800)).await;
+        }
+    });
+}
+
+pub fn start_login_session_location_loop(app: Arc<api::ApiApplication>) {
+    tokio::task::spawn(async move {
+        loop {
+            let has_more = match app.resolve_pending_login_sessions(100).await {
+                Ok(count) => {
+                    if count > 0 {
+                        log::info!("Resolved {} Login Sessions", count);
+                    }
+                    count == 100
+                },
+                Err(err) => {
+                    log::warn!("Failed to resolve pending login sessions: {:?}", err);
+                    false
+                },
+            };
+
+            // New device emails go out from here so this should run often enough that they still feel timely.
+            if !has_more {
+                tokio::time::sleep(tokio::time::Duration::from_secs(15)).await;
+            }
+        }
+    });
+}
+
+pub fn start_valorant_ability_backfill_loop(app: Arc<api::ApiApplication>) {
+    tokio::task::spawn(async move {
+        loop {
+            match riot_db::get_valorant_matches_for_ability_backfill(&*app.pool, 100).await {
+                Ok(match_uuids) => {
+                    if !match_uuids.is_empty() {
+                        log::info!("Requesting Valorant Ability Backfill for {} Matches", match_uuids.len());
+                    }
+
+                    for match_uuid in match_uuids {
+                        if let Err(err) = app.valorant_itf.request_valorant_match_ability_backfill(&match_uuid).await {
+                            log::warn!("Failed to request Valorant ability backfill for {}: {:?}", &match_uuid, err);
+                        }
+                    }
+                },
+                Err(err) => log::warn!("Failed to get Valorant matches for ability backfill: {:?}", err),
+            }
+
+            // These go through the backfill rate limit so only feed in a small batch at a time.
+            tokio::time::sleep(tokio::time::Duration::from_secs(600)).await;
+        }
+    });
+}
+
+pub fn start_lol_position_repair_loop(app: Arc<api::ApiApplication>) {
+    tokio::task::spawn(async move {
+        loop {
+            match riot_db::get_lol_matches_for_position_repair(&*app.pool, 100).await {
+                Ok(match_uuids) => {
+                    if !match_uuids.is_empty() {
+                        log::info!("Requesting LoL Position Repair for {} Matches", match_uuids.len());
+                    }
+
+                    for match_uuid in match_uuids {
+                        if let Err(err) = app.lol_itf.request_repair_lol_match_timeline_positions(&match_uuid).await {
+                            log::warn!("Failed to request LoL position repair for {}: {:?}", &match_uuid, err);
+                        }
+                    }
+                },
+                Err(err) => log::warn!("Failed to get LoL matches for position repair: {:?}", err),
+            }
+
+            // These go through the backfill rate limit so only feed in a small batch at a time.
+            tokio::time::sleep(tokio::time::Duration::from_secs(600)).await;
+        }
+    });
+}
+
+pub fn start_teammate_roster_sync_loop(app: Arc<api::ApiApplication>) {
+    tokio::task::spawn(async move {
+        loop {
+            match tdb::get_vods_for_teammate_roster_sync(&*app.pool, 100).await {
+                Ok(video_uuids) => {
+                    if !video_uuids.is_empty() {
+                        log::info!("Requesting Teammate Roster Sync for {} VODs", video_uuids.len());
+                    }
+
+                    for video_uuid in video_uuids {
+                        if let Err(err) = app.es_itf.request_sync_teammate_roster(&video_uuid).await {
+                            log::warn!("Failed to request teammate roster sync for {}: {:?}", &video_uuid, err);
+                        }
+                    }
+                },
+                Err(err) => log::warn!("Failed to get VODs for teammate roster sync: {:?}", err),
+            }
+
+            // Most of these get handled by the ES sync right away so this is mainly working through the backfill.
+            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+        }
+    });
+}
+
+// The summaries are already sitting in each match's summary.avro report so this just copies them over instead of
+// going through a queue to re-parse the combat log.
+pub fn start_wow_stat_summary_backfill_loop(app: Arc<api::ApiApplication>) {
+    tokio::task::spawn(async move {
+        loop {
+            match wow_stats::get_wow_match_views_for_stat_summary_backfill(&*app.pool, 100).await {
+                Ok(views) => {
+                    if !views.is_empty() {
+                        log::info!("Backfilling WoW Stat Summaries for {} Match Views", views.len());
+                    }
+
+                    for view in views {
+                        // Views that lost their combat log don't have anything to backfill.
+                        if let Some(partition_id) = view.combat_log_partition_id.as_ref() {
+                            let summaries = match app.cl_itf.get_report_avro::<WowUnitStatSummary>(partition_id, WowReportTypes::Stats as i32, "summary.avro").await {
+                                Ok(x) => x,
+                                Err(err) => {
+                                    log::warn!("Failed to get WoW stat summary report for {}: {:?}", &view.view_id, err);
+                                    continue;
+                                }
+                            };
+
+                            if let Err(err) = wow_stats::store_wow_stat_summaries_for_partition(&*app.pool, partition_id, &summaries).await {
+                                log::warn!("Failed to store WoW stat summaries for {}: {:?}", &view.view_id, err);
+                                continue;
+                            }
+                        }
+
+                        if let Err(err) = wow_stats::finish_wow_match_view_stat_summary_backfill(&*app.pool, &view.view_id).await {
+                            log::warn!("Failed to finish WoW stat summary backfill for {}: {:?}", &view.view_id, err);
+                        }
+                    }
+                },
+                Err(err) => log::warn!("Failed to get WoW match views for stat summary backfill: {:?}", err),
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+        }
+    });
+}
+
+pub fn start_hearthstone_deck_analytics_backfill_loop(app: Arc<api::ApiApplication>) {
+    tokio::task::spawn(async move {
+        loop {
+            match hdb::get_hearthstone_matches_for_deck_analytics_backfill(&*app.pool, 10).await {
+                Ok(matches) => {
+                    if !matches.is_empty() {
+                        log::info!("Backfilling Hearthstone Deck Analytics for {} Matches", matches.len());
+                    }
+
+                    for m in matches {
+                        if let Err(err) = app.backfill_hearthstone_deck_analytics(&m.match_uuid, m.user_id).await {
+                            log::warn!("Failed to backfill Hearthstone deck analytics for {} [{}]: {:?}", &m.match_uuid, m.user_id, err);
+                        }
+                    }
+                },
+                Err(err) => log::warn!("Failed to get Hearthstone matches for deck analytics backfill: {:?}", err),
+            }
+
+            // Every match has to have its logs re-parsed so only do a few at a time.
+            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+        }
+    });
+}
+
+pub fn start_riot_api_cache_purge_loop(app: Arc<api::ApiApplication>) {
+    tokio::task::spawn(async move {
+        loop {
+            match purge_expired_riot_api_cache(&*app.pool).await {
+                Ok(count) => log::info!("Purged {} Expired Riot API Cache Entries", count),
+                Err(err) => log::warn!("Failed to purge expired Riot API cache entries: {:?}", err),
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
+        }
+    });
+}
+
 fn main() -> std::io::Result<()> {
     std::env::set_var("RUST_BACKTRACE", "1");
     std::env::set_var("RUST_LOG", "info,singleton_event_processing_worker=debug,actix_web=debug,actix_http=debug,librdkafka=info,rdkafka::client=info,sqlx=info");
@@ -134,6 +405,7 @@ fn main() -> std::io::Result<()> {
     config.rabbitmq.enable_twitch = true;
     config.rabbitmq.enable_sharing = false;
     config.rabbitmq.enable_elasticsearch = false;
+    config.rabbitmq.enable_stripe = true;
     config.rabbitmq.prefetch_count = 2;
 
     tokio::runtime::Builder::new_multi_thread()
@@ -148,6 +420,20 @@ fn main() -> std::io::Result<()> {
                 api::start_event_loop(app.clone());
                 start_unpublished_clips_cleanup_loop(app.clone());
                 start_expired_vods_cleanup_loop(app.clone());
+                start_expired_entitlements_loop(app.clone());
+                start_storage_reconciliation_loop(app.clone());
+                start_login_session_location_loop(app.clone());
+                start_valorant_ability_backfill_loop(app.clone());
+                start_lol_position_repair_loop(app.clone());
+                start_riot_api_cache_purge_loop(app.clone());
+                start_teammate_roster_sync_loop(app.clone());
+                start_wow_stat_summary_backfill_loop(app.clone());
+                start_hearthstone_deck_analytics_backfill_loop(app.clone());
+
+                // Stripe webhooks only ever get processed here (enable_stripe is forced on above).
+                RabbitMqInterface::add_listener(app.rabbitmq.clone(), config.rabbitmq.stripe_queue.clone(), Arc::new(api::v1::StripeWebhookEventConsumer::new(app.clone())), config.rabbitmq.prefetch_count).await.unwrap();
+                start_stale_stripe_webhooks_loop(app.clone());
+                start_stripe_reconciliation_loop(app.clone());
 
                 loop {
                     async_std::task::sleep(std::time::Duration::from_secs(1)).await;
@@ -0,0 +1,64 @@
+use squadov_common::SquadOvError;
+use crate::{
+    filter::CsgoDemoFilter,
+    summary::summarize_demo_file,
+};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+
+pub struct CsgoBatchResult {
+    pub processed: usize,
+    pub failed: usize,
+}
+
+pub fn find_demos_in_dir(dir: &Path) -> Result<Vec<PathBuf>, SquadOvError> {
+    let mut demos: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|x| { x.ok() })
+        .map(|x| { x.path() })
+        .filter(|x| { x.is_file() && x.extension().map(|ext| { ext == "dem" }).unwrap_or(false) })
+        .collect();
+    demos.sort();
+    Ok(demos)
+}
+
+// Parses every demo in the directory in parallel and writes out one <demo name>.json summary per demo into the output
+// directory. Demos that fail to parse still get a JSON file with the error so that one bad demo doesn't stop the batch.
+pub fn process_demo_dir(dir: &Path, output_dir: &Path, filter: &CsgoDemoFilter, jobs: Option<usize>) -> Result<CsgoBatchResult, SquadOvError> {
+    let demos = find_demos_in_dir(dir)?;
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut pool = rayon::ThreadPoolBuilder::new();
+    if let Some(jobs) = jobs {
+        pool = pool.num_threads(jobs);
+    }
+    let pool = pool.build().map_err(|x| { SquadOvError::InternalError(format!("Failed to create thread pool: {}", x)) })?;
+
+    let results: Vec<Result<bool, SquadOvError>> = pool.install(|| {
+        demos.par_iter().map(|path| {
+            log::info!("Processing CS:GO demo: {}", path.display());
+            let summary = summarize_demo_file(path, filter);
+            let failed = summary.get("error").is_some();
+            if failed {
+                log::warn!("Failed to parse CS:GO demo {}: {}", path.display(), &summary["error"]);
+            }
+
+            let stem = path.file_stem().map(|x| { x.to_string_lossy().to_string() }).unwrap_or(String::from("demo"));
+            let out = std::fs::File::create(output_dir.join(format!("{}.json", stem)))?;
+            serde_json::to_writer_pretty(out, &summary)?;
+            Ok(failed)
+        }).collect()
+    });
+
+    let mut ret = CsgoBatchResult{
+        processed: 0,
+        failed: 0,
+    };
+
+    for r in results {
+        if r? {
+            ret.failed += 1;
+        }
+        ret.processed += 1;
+    }
+    Ok(ret)
+}
@@ -0,0 +1,164 @@
+use squadov_common::{
+    SquadOvError,
+    csgo::{
+        demo::CsgoDemo,
+        math::CsgoVector,
+        schema::CsgoCommonEventContainer,
+    },
+};
+use chrono::{DateTime, Utc, TimeZone};
+use uuid::Uuid;
+use std::io::Write;
+use std::path::Path;
+
+// The common event container is what the server stores for every demo. When running offline there's no
+// real match/view to attach it to so we give it a stable view UUID to keep the output deterministic.
+pub fn demo_to_event_container(demo: &CsgoDemo, ref_timestamp: Option<DateTime<Utc>>) -> Result<CsgoCommonEventContainer, SquadOvError> {
+    let ref_timestamp = ref_timestamp.unwrap_or_else(|| { Utc.timestamp(0, 0) });
+    let mut container = CsgoCommonEventContainer::from_demo(demo, &ref_timestamp)?;
+    container.view_uuid = Uuid::nil();
+    Ok(container)
+}
+
+pub fn write_event_container_json<W: Write>(demo: &CsgoDemo, ref_timestamp: Option<DateTime<Utc>>, out: W) -> Result<(), SquadOvError> {
+    let container = demo_to_event_container(demo, ref_timestamp)?;
+    serde_json::to_writer_pretty(out, &container)?;
+    Ok(())
+}
+
+fn csv_escape(val: &str) -> String {
+    if val.contains(',') || val.contains('"') || val.contains('\n') {
+        format!("\"{}\"", val.replace('"', "\"\""))
+    } else {
+        val.to_string()
+    }
+}
+
+fn csv_option<T: ToString>(val: Option<T>) -> String {
+    val.map(|x| { x.to_string() }).unwrap_or(String::new())
+}
+
+fn csv_vector(val: Option<&CsgoVector>) -> [String; 3] {
+    match val {
+        Some(v) => [v.x.to_string(), v.y.to_string(), v.z.to_string()],
+        None => [String::new(), String::new(), String::new()],
+    }
+}
+
+fn write_csv_row<W: Write>(out: &mut W, row: &[String]) -> Result<(), SquadOvError> {
+    let row: Vec<String> = row.iter().map(|x| { csv_escape(x) }).collect();
+    writeln!(out, "{}", row.join(","))?;
+    Ok(())
+}
+
+pub fn write_kills_csv<W: Write>(demo: &CsgoDemo, mut out: W) -> Result<(), SquadOvError> {
+    write_csv_row(&mut out, &[
+        "round", "tick", "victim", "killer", "assister", "weapon", "headshot", "wallbang", "smoke", "blind", "noscope", "flash_assist",
+        "victim_x", "victim_y", "victim_z", "killer_x", "killer_y", "killer_z",
+    ].iter().map(|x| { x.to_string() }).collect::<Vec<_>>())?;
+
+    for r in &demo.rounds {
+        for k in &r.kills {
+            let mut row = vec![
+                r.round_num.to_string(),
+                k.tick.to_string(),
+                k.victim.to_string(),
+                csv_option(k.killer),
+                csv_option(k.assister),
+                format!("{:?}", k.weapon),
+                k.headshot.to_string(),
+                k.wallbang.to_string(),
+                k.smoke.to_string(),
+                k.blind.to_string(),
+                k.noscope.to_string(),
+                k.flash_assist.to_string(),
+            ];
+            row.extend_from_slice(&csv_vector(k.victim_position.as_ref()));
+            row.extend_from_slice(&csv_vector(k.killer_position.as_ref()));
+            write_csv_row(&mut out, &row)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_damage_csv<W: Write>(demo: &CsgoDemo, mut out: W) -> Result<(), SquadOvError> {
+    write_csv_row(&mut out, &[
+        "round", "tick", "attacker", "receiver", "weapon", "hitgroup", "damage_health", "damage_armor", "remaining_health", "remaining_armor",
+    ].iter().map(|x| { x.to_string() }).collect::<Vec<_>>())?;
+
+    for r in &demo.rounds {
+        for d in &r.damage {
+            write_csv_row(&mut out, &[
+                r.round_num.to_string(),
+                d.tick.to_string(),
+                csv_option(d.attacker),
+                d.receiver.to_string(),
+                format!("{:?}", d.weapon),
+                format!("{:?}", d.hitgroup),
+                d.damage_health.to_string(),
+                d.damage_armor.to_string(),
+                d.remaining_health.to_string(),
+                d.remaining_armor.to_string(),
+            ])?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_rounds_csv<W: Write>(demo: &CsgoDemo, mut out: W) -> Result<(), SquadOvError> {
+    write_csv_row(&mut out, &[
+        "round", "start_tick", "freeze_end_tick", "end_tick", "winner", "win_reason", "mvp", "kills", "damage", "utility", "blinds",
+    ].iter().map(|x| { x.to_string() }).collect::<Vec<_>>())?;
+
+    for r in &demo.rounds {
+        write_csv_row(&mut out, &[
+            r.round_num.to_string(),
+            r.round_start_tick.to_string(),
+            csv_option(r.freeze_end_tick),
+            csv_option(r.round_end_tick),
+            r.round_winner.map(|x| { format!("{:?}", x) }).unwrap_or(String::new()),
+            r.round_win_reason.map(|x| { format!("{:?}", x) }).unwrap_or(String::new()),
+            csv_option(r.round_mvp),
+            r.kills.len().to_string(),
+            r.damage.len().to_string(),
+            r.utility.len().to_string(),
+            r.blinds.len().to_string(),
+        ])?;
+    }
+    Ok(())
+}
+
+// Writes kills.csv, damage.csv, and rounds.csv into the given directory.
+pub fn write_csv_dir(demo: &CsgoDemo, dir: &Path) -> Result<(), SquadOvError> {
+    std::fs::create_dir_all(dir)?;
+    write_kills_csv(demo, std::io::BufWriter::new(std::fs::File::create(dir.join("kills.csv"))?))?;
+    write_damage_csv(demo, std::io::BufWriter::new(std::fs::File::create(dir.join("damage.csv"))?))?;
+    write_rounds_csv(demo, std::io::BufWriter::new(std::fs::File::create(dir.join("rounds.csv"))?))?;
+    Ok(())
+}
+
+// Human readable dump of the server classes (and their flattened props) along with the string tables
+// the demo created. Mostly useful when figuring out which prop to pull for some new stat.
+pub fn write_tables<W: Write>(demo: &CsgoDemo, mut out: W) -> Result<(), SquadOvError> {
+    writeln!(out, "== Data Tables ==")?;
+    if let Some(dt) = demo.entities.data_table() {
+        let dt = dt.read()?;
+        let mut class_ids: Vec<&i32> = dt.classes.keys().collect();
+        class_ids.sort();
+        for id in class_ids {
+            let class = &dt.classes[id];
+            writeln!(out, "[{}] {} ({})", class.class_id, &class.name, &class.dt_name)?;
+            for (idx, prop) in class.props.iter().enumerate() {
+                writeln!(out, "\t{}: {}", idx, prop.full_name())?;
+            }
+        }
+    } else {
+        writeln!(out, "No data tables found.")?;
+    }
+
+    writeln!(out, "== String Tables ==")?;
+    for (idx, table) in demo.string_tables().iter().enumerate() {
+        writeln!(out, "{}: {:?}", idx, table)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use squadov_common::csgo::demo::CsgoDemo;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug, Default, Clone)]
+pub struct CsgoDemoFilter {
+    /// Only keep these rounds (0-indexed). Can be specified multiple times.
+    #[structopt(long)]
+    pub round: Vec<usize>,
+    #[structopt(long)]
+    pub start_tick: Option<i32>,
+    #[structopt(long)]
+    pub end_tick: Option<i32>,
+}
+
+impl CsgoDemoFilter {
+    fn tick_in_range(&self, tick: i32) -> bool {
+        self.start_tick.map(|x| { tick >= x }).unwrap_or(true) && self.end_tick.map(|x| { tick <= x }).unwrap_or(true)
+    }
+
+    fn round_in_range(&self, start_tick: i32, end_tick: Option<i32>) -> bool {
+        // Rounds that haven't ended are treated as going on forever.
+        let end_tick = end_tick.unwrap_or(i32::MAX);
+        self.start_tick.map(|x| { end_tick >= x }).unwrap_or(true) && self.end_tick.map(|x| { start_tick <= x }).unwrap_or(true)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.round.is_empty() && self.start_tick.is_none() && self.end_tick.is_none()
+    }
+
+    // Removes every round and event that doesn't match the filter.
+    pub fn apply(&self, demo: &mut CsgoDemo) {
+        if self.is_empty() {
+            return;
+        }
+
+        demo.rounds.retain(|r| {
+            (self.round.is_empty() || self.round.contains(&r.round_num)) && self.round_in_range(r.round_start_tick, r.round_end_tick)
+        });
+
+        for r in &mut demo.rounds {
+            r.kills.retain(|x| { self.tick_in_range(x.tick) });
+            r.damage.retain(|x| { self.tick_in_range(x.tick) });
+            r.utility.retain(|x| { self.tick_in_range(x.tick) });
+            r.blinds.retain(|x| { self.tick_in_range(x.tick) });
+            r.positions.retain(|x| { self.tick_in_range(x.tick) });
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod filter;
+pub mod summary;
+pub mod export;
+pub mod batch;
@@ -1,21 +1,113 @@
 use structopt::StructOpt;
-use squadov_common::csgo::parser::CsgoDemoParser;
+use squadov_common::SquadOvError;
+use csgo_demo_parser::{
+    batch::process_demo_dir,
+    export,
+    filter::CsgoDemoFilter,
+    summary::{
+        CsgoDemoSummary,
+        demo_file_name,
+        parse_demo_file,
+    },
+};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::io::Write;
 
 #[derive(StructOpt, Debug)]
-struct Options {
-    #[structopt(short, long)]
-    file: String,
+#[structopt(name = "csgo_demo_parser")]
+enum Command {
+    /// Print a JSON summary of the demo (header, players, and per-round counts).
+    Summary {
+        #[structopt(short, long, parse(from_os_str))]
+        file: PathBuf,
+        #[structopt(flatten)]
+        filter: CsgoDemoFilter,
+    },
+    /// Output the full common event container that the server would store for this demo.
+    Json {
+        #[structopt(short, long, parse(from_os_str))]
+        file: PathBuf,
+        /// Writes to stdout if not specified.
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+        /// RFC3339 timestamp that tick 0 of the demo corresponds to. Defaults to the Unix epoch.
+        #[structopt(long)]
+        reference_time: Option<DateTime<Utc>>,
+        #[structopt(flatten)]
+        filter: CsgoDemoFilter,
+    },
+    /// Write kills.csv, damage.csv, and rounds.csv into the output directory.
+    Csv {
+        #[structopt(short, long, parse(from_os_str))]
+        file: PathBuf,
+        #[structopt(short, long, parse(from_os_str))]
+        output_dir: PathBuf,
+        #[structopt(flatten)]
+        filter: CsgoDemoFilter,
+    },
+    /// Dump the server classes and string tables found in the demo.
+    Tables {
+        #[structopt(short, long, parse(from_os_str))]
+        file: PathBuf,
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Summarize every .dem file in a directory in parallel.
+    Batch {
+        #[structopt(short, long, parse(from_os_str))]
+        dir: PathBuf,
+        #[structopt(short, long, parse(from_os_str))]
+        output_dir: PathBuf,
+        #[structopt(short, long)]
+        jobs: Option<usize>,
+        #[structopt(flatten)]
+        filter: CsgoDemoFilter,
+    },
+}
+
+fn open_output(output: Option<&Path>) -> Result<Box<dyn Write>, SquadOvError> {
+    Ok(match output {
+        Some(p) => Box::new(std::io::BufWriter::new(std::fs::File::create(p)?)),
+        None => Box::new(std::io::stdout()),
+    })
+}
+
+fn run(cmd: Command) -> Result<(), SquadOvError> {
+    match cmd {
+        Command::Summary{file, filter} => {
+            let demo = parse_demo_file(&file, &filter)?;
+            let summary = CsgoDemoSummary::from_demo(&demo_file_name(&file), &demo);
+            println!("{}", serde_json::to_string_pretty(&summary)?);
+        },
+        Command::Json{file, output, reference_time, filter} => {
+            let demo = parse_demo_file(&file, &filter)?;
+            export::write_event_container_json(&demo, reference_time, open_output(output.as_deref())?)?;
+        },
+        Command::Csv{file, output_dir, filter} => {
+            let demo = parse_demo_file(&file, &filter)?;
+            export::write_csv_dir(&demo, &output_dir)?;
+        },
+        Command::Tables{file, output} => {
+            let demo = parse_demo_file(&file, &CsgoDemoFilter::default())?;
+            export::write_tables(&demo, open_output(output.as_deref())?)?;
+        },
+        Command::Batch{dir, output_dir, jobs, filter} => {
+            let result = process_demo_dir(&dir, &output_dir, &filter, jobs)?;
+            log::info!("Processed {} demos ({} failed).", result.processed, result.failed);
+        },
+    };
+    Ok(())
 }
 
 fn main() {
-    std::env::set_var("RUST_LOG", "debug");
+    if std::env::var("RUST_LOG").is_err() {
+        std::env::set_var("RUST_LOG", "info");
+    }
     env_logger::init();
 
-    let opts = Options::from_args();
-    println!("FILE: {}", opts.file);
-
-    let demo = CsgoDemoParser::from_path(std::path::Path::new(&opts.file)).unwrap();
-    println!("Demo Header: {:?}", demo.header);
-    println!("Demo Start Tick: {:?}", demo.game_start_tick);
-    println!("Demo Rounds: {:?}", demo.rounds);
-}
\ No newline at end of file
+    if let Err(err) = run(Command::from_args()) {
+        log::error!("{}", err);
+        std::process::exit(1);
+    }
+}
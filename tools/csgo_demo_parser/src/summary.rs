@@ -0,0 +1,120 @@
+use squadov_common::{
+    SquadOvError,
+    csgo::{
+        demo::{
+            CsgoDemo,
+            CsgoTeam,
+            CsgoRoundWin,
+        },
+        parser::CsgoDemoParser,
+    },
+};
+use crate::filter::CsgoDemoFilter;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoDemoPlayerSummary {
+    pub user_id: i32,
+    pub steam_id: u64,
+    pub name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoDemoRoundSummary {
+    pub round_num: usize,
+    pub start_tick: i32,
+    pub freeze_end_tick: Option<i32>,
+    pub end_tick: Option<i32>,
+    pub winner: Option<CsgoTeam>,
+    pub win_reason: Option<CsgoRoundWin>,
+    pub mvp: Option<i32>,
+    pub kills: usize,
+    pub damage: usize,
+    pub utility: usize,
+    pub blinds: usize,
+    pub position_samples: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct CsgoDemoSummary {
+    pub file: String,
+    pub map: String,
+    pub server: String,
+    pub client: String,
+    pub network_protocol: i32,
+    pub playback_ticks: i32,
+    pub playback_time: f32,
+    pub tick_rate: f32,
+    pub game_start_tick: Option<i32>,
+    pub players: Vec<CsgoDemoPlayerSummary>,
+    pub rounds: Vec<CsgoDemoRoundSummary>,
+}
+
+impl CsgoDemoSummary {
+    pub fn from_demo(file: &str, demo: &CsgoDemo) -> Self {
+        let mut players: Vec<CsgoDemoPlayerSummary> = demo.player_info.iter().map(|(user_id, p)| {
+            CsgoDemoPlayerSummary{
+                user_id: *user_id,
+                steam_id: p.xuid,
+                name: p.name.clone(),
+            }
+        }).collect();
+        players.sort_by_key(|x| { x.user_id });
+
+        Self {
+            file: file.to_string(),
+            map: demo.header.map_name.clone(),
+            server: demo.header.server_name.clone(),
+            client: demo.header.client_name.clone(),
+            network_protocol: demo.header.network_protocol,
+            playback_ticks: demo.header.playback_ticks,
+            playback_time: demo.header.playback_time,
+            tick_rate: demo.tick_rate(),
+            game_start_tick: demo.game_start_tick,
+            players,
+            rounds: demo.rounds.iter().map(|r| {
+                CsgoDemoRoundSummary{
+                    round_num: r.round_num,
+                    start_tick: r.round_start_tick,
+                    freeze_end_tick: r.freeze_end_tick,
+                    end_tick: r.round_end_tick,
+                    winner: r.round_winner,
+                    win_reason: r.round_win_reason,
+                    mvp: r.round_mvp,
+                    kills: r.kills.len(),
+                    damage: r.damage.len(),
+                    utility: r.utility.len(),
+                    blinds: r.blinds.len(),
+                    position_samples: r.positions.len(),
+                }
+            }).collect(),
+        }
+    }
+}
+
+pub fn demo_file_name(path: &Path) -> String {
+    path.file_name().map(|x| { x.to_string_lossy().to_string() }).unwrap_or(String::new())
+}
+
+pub fn parse_demo_file(path: &Path, filter: &CsgoDemoFilter) -> Result<CsgoDemo, SquadOvError> {
+    let mut demo = CsgoDemoParser::from_path(path)?;
+    filter.apply(&mut demo);
+    Ok(demo)
+}
+
+// Either the summary or the reason why we couldn't parse the demo. This is what gets written out for every demo when
+// processing a directory and what the golden tests compare against.
+pub fn summarize_demo_file(path: &Path, filter: &CsgoDemoFilter) -> serde_json::Value {
+    let file = demo_file_name(path);
+    match parse_demo_file(path, filter) {
+        Ok(demo) => serde_json::to_value(CsgoDemoSummary::from_demo(&file, &demo)).unwrap_or(serde_json::Value::Null),
+        Err(err) => serde_json::json!({
+            "file": file,
+            "error": format!("{}", err),
+        }),
+    }
+}
@@ -0,0 +1,111 @@
+use csgo_demo_parser::{
+    export::{write_kills_csv, write_damage_csv, write_rounds_csv},
+    filter::CsgoDemoFilter,
+    summary::{parse_demo_file, summarize_demo_file},
+};
+use std::path::{Path, PathBuf};
+
+// Set this environment variable to rewrite the golden files with whatever the parser currently outputs.
+const UPDATE_GOLDEN_ENV: &'static str = "CSGO_DEMO_UPDATE_GOLDEN";
+
+fn data_dir() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests");
+    path.push("data");
+    path
+}
+
+fn golden_path(demo: &Path) -> PathBuf {
+    let stem = demo.file_stem().unwrap().to_string_lossy().to_string();
+    demo.with_file_name(format!("{}.golden.json", stem))
+}
+
+#[test]
+fn test_demo_summaries_match_golden() {
+    let mut demos: Vec<PathBuf> = std::fs::read_dir(data_dir()).unwrap()
+        .map(|x| { x.unwrap().path() })
+        .filter(|x| { x.extension().map(|ext| { ext == "dem" }).unwrap_or(false) })
+        .collect();
+    demos.sort();
+    assert!(!demos.is_empty());
+
+    let update = std::env::var(UPDATE_GOLDEN_ENV).is_ok();
+    for demo in demos {
+        let summary = summarize_demo_file(&demo, &CsgoDemoFilter::default());
+        let golden = golden_path(&demo);
+        if update {
+            std::fs::write(&golden, serde_json::to_string_pretty(&summary).unwrap() + "\n").unwrap();
+            continue;
+        }
+
+        let expected: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(&golden).unwrap_or_else(|_| { panic!("Missing golden file: {}", golden.display()) })
+        ).unwrap();
+        assert_eq!(summary, expected, "Summary mismatch for {}", demo.display());
+    }
+}
+
+#[test]
+fn test_demo_csvs_match_golden() {
+    let demo_path = data_dir().join("two_rounds.dem");
+    let demo = parse_demo_file(&demo_path, &CsgoDemoFilter::default()).unwrap();
+
+    let update = std::env::var(UPDATE_GOLDEN_ENV).is_ok();
+    let outputs: Vec<(&str, Vec<u8>)> = vec![
+        ("kills", { let mut out = vec![]; write_kills_csv(&demo, &mut out).unwrap(); out }),
+        ("damage", { let mut out = vec![]; write_damage_csv(&demo, &mut out).unwrap(); out }),
+        ("rounds", { let mut out = vec![]; write_rounds_csv(&demo, &mut out).unwrap(); out }),
+    ];
+
+    for (name, out) in outputs {
+        let golden = demo_path.with_file_name(format!("two_rounds.{}.csv", name));
+        if update {
+            std::fs::write(&golden, &out).unwrap();
+            continue;
+        }
+
+        let expected = std::fs::read_to_string(&golden).unwrap_or_else(|_| { panic!("Missing golden file: {}", golden.display()) });
+        assert_eq!(String::from_utf8(out).unwrap(), expected, "CSV mismatch for {}", golden.display());
+    }
+}
+
+#[test]
+fn test_filter_excludes_rounds_outside_tick_range() {
+    let demo = data_dir().join("two_rounds.dem");
+    let unfiltered = summarize_demo_file(&demo, &CsgoDemoFilter::default());
+    assert_eq!(unfiltered["rounds"].as_array().unwrap().len(), 2);
+
+    // The first round ends at tick 200 so only the second round is left.
+    let filter = CsgoDemoFilter{
+        round: vec![],
+        start_tick: Some(250),
+        end_tick: None,
+    };
+    let summary = summarize_demo_file(&demo, &filter);
+    let rounds = summary["rounds"].as_array().unwrap();
+    assert_eq!(rounds.len(), 1);
+    assert_eq!(rounds[0]["roundNum"], 1);
+    assert_eq!(rounds[0]["kills"], 1);
+
+    // Events in a kept round that fall outside the range are dropped too.
+    let filter = CsgoDemoFilter{
+        round: vec![],
+        start_tick: None,
+        end_tick: Some(335),
+    };
+    let summary = summarize_demo_file(&demo, &filter);
+    let rounds = summary["rounds"].as_array().unwrap();
+    assert_eq!(rounds.len(), 2);
+    assert_eq!(rounds[1]["kills"], 0);
+    assert_eq!(rounds[1]["damage"], 1);
+    assert_eq!(rounds[1]["utility"], 1);
+
+    let filter = CsgoDemoFilter{
+        round: vec![],
+        start_tick: Some(1000),
+        end_tick: None,
+    };
+    let summary = summarize_demo_file(&demo, &filter);
+    assert_eq!(summary["rounds"], serde_json::json!([]));
+    assert_eq!(summary["map"], "de_dust2");
+}
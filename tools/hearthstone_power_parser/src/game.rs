@@ -0,0 +1,65 @@
+use squadov_common::{
+    SquadOvError,
+    hearthstone::{
+        HearthstoneRawLog,
+        game_state::HearthstoneGameLog,
+        power_parser::{HearthstonePowerLogParser, HearthstoneGameState},
+    },
+};
+use crate::{
+    input::PowerLogLine,
+    summary::HearthstoneGameSummary,
+};
+use serde::Serialize;
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct PowerLogLineDiagnostic {
+    pub line: usize,
+    pub log: String,
+    pub error: String,
+}
+
+pub struct ParsedHearthstoneGame {
+    pub first_line: usize,
+    pub last_line: usize,
+    pub state: HearthstoneGameState,
+    pub log: HearthstoneGameLog,
+    pub summary: HearthstoneGameSummary,
+    pub diagnostics: Vec<PowerLogLineDiagnostic>,
+    // Which FSM state handled each line. Only available when parsing with tracing enabled.
+    pub trace: Option<String>,
+}
+
+pub fn parse_game(lines: Vec<PowerLogLine>, trace: bool) -> Result<ParsedHearthstoneGame, SquadOvError> {
+    let first_line = lines.first().map(|x| { x.line }).unwrap_or(0);
+    let last_line = lines.last().map(|x| { x.line }).unwrap_or(0);
+    let (line_numbers, raw): (Vec<usize>, Vec<HearthstoneRawLog>) = lines.into_iter().map(|x| { (x.line, x.raw) }).unzip();
+
+    // We'd rather skip a bad line and report it than throw out the entire game.
+    let mut parser = HearthstonePowerLogParser::new(trace);
+    parser.lenient = true;
+    parser.parse(&raw)?;
+
+    let log = std::mem::replace(&mut *parser.fsm.game.write()?, HearthstoneGameLog::new());
+    let summary = HearthstoneGameSummary::new(&parser.state, &log);
+    Ok(ParsedHearthstoneGame{
+        first_line,
+        last_line,
+        summary,
+        diagnostics: parser.diagnostics.iter().map(|x| {
+            PowerLogLineDiagnostic{
+                line: line_numbers[x.index],
+                log: x.log.clone(),
+                error: x.error.clone(),
+            }
+        }).collect(),
+        trace: if trace { Some(parser.fsm.raw_logs_to_string()) } else { None },
+        state: parser.state,
+        log,
+    })
+}
+
+pub fn parse_games(games: Vec<Vec<PowerLogLine>>, trace: bool) -> Result<Vec<ParsedHearthstoneGame>, SquadOvError> {
+    games.into_iter().map(|x| { parse_game(x, trace) }).collect()
+}
@@ -0,0 +1,66 @@
+use squadov_common::{
+    SquadOvError,
+    hearthstone::HearthstoneRawLog,
+};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use std::path::Path;
+
+// A single log line along with where it came from in the input file so that errors can point back to it.
+pub struct PowerLogLine {
+    pub line: usize,
+    pub raw: HearthstoneRawLog,
+}
+
+// Power.log lines look like: D 20:00:00.1234567 GameState.DebugPrintPower() - CREATE_GAME
+// The log only stores the time of day so the caller needs to tell us what day the log starts on.
+pub fn parse_power_log_text(text: &str, date: NaiveDate) -> Vec<PowerLogLine> {
+    let mut ret: Vec<PowerLogLine> = Vec::new();
+    let mut current_date = date;
+    let mut last_time: Option<NaiveTime> = None;
+
+    for (idx, line) in text.lines().enumerate() {
+        let tokens: Vec<&str> = line.trim_end().splitn(3, ' ').collect();
+        if tokens.len() != 3 {
+            continue;
+        }
+
+        let tm = match NaiveTime::parse_from_str(tokens[1], "%H:%M:%S%.f") {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+
+        // Lines aren't always perfectly in order so only assume we crossed midnight if the time jumped backwards by a lot.
+        if let Some(last) = last_time {
+            if last.signed_duration_since(tm) > Duration::hours(12) {
+                current_date = current_date.succ();
+            }
+        }
+        last_time = Some(tm);
+
+        ret.push(PowerLogLine{
+            line: idx + 1,
+            raw: HearthstoneRawLog{
+                time: DateTime::<Utc>::from_utc(current_date.and_time(tm), Utc),
+                section: String::from("Power"),
+                log: tokens[2].to_string(),
+            },
+        });
+    }
+    ret
+}
+
+// Accepts either a raw Power.log or the JSON array of HearthstoneRawLog that the client uploads.
+pub fn read_power_logs(path: &Path, date: Option<NaiveDate>) -> Result<Vec<PowerLogLine>, SquadOvError> {
+    let data = std::fs::read_to_string(path)?;
+    if data.trim_start().starts_with('[') {
+        let logs: Vec<HearthstoneRawLog> = serde_json::from_str(&data)?;
+        Ok(logs.into_iter().enumerate().map(|(idx, raw)| {
+            PowerLogLine{
+                line: idx + 1,
+                raw,
+            }
+        }).collect())
+    } else {
+        Ok(parse_power_log_text(&data, date.unwrap_or_else(|| { Utc::today().naive_utc() })))
+    }
+}
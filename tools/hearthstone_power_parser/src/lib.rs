@@ -0,0 +1,5 @@
+pub mod input;
+pub mod split;
+pub mod game;
+pub mod summary;
+pub mod output;
\ No newline at end of file
@@ -1,47 +1,128 @@
 use structopt::StructOpt;
-use serde_json;
-use std::fs::File;
-use std::io::{BufReader, BufWriter, Write};
-use squadov_common::hearthstone::{HearthstoneRawLog, power_parser::HearthstonePowerLogParser};
+use squadov_common::SquadOvError;
+use hearthstone_power_parser::{
+    game::{ParsedHearthstoneGame, parse_games},
+    input::read_power_logs,
+    output,
+    split::split_games,
+};
+use chrono::NaiveDate;
+use std::path::{Path, PathBuf};
+use std::io::Write;
 
 #[derive(StructOpt, Debug)]
-struct Options {
+struct InputOptions {
+    /// Either a raw Power.log or a JSON array of logs as uploaded by the client.
+    #[structopt(short, long, parse(from_os_str))]
+    file: PathBuf,
+    /// Day (YYYY-MM-DD) the first line of a raw Power.log is from. Defaults to today.
+    #[structopt(long)]
+    date: Option<NaiveDate>,
+    /// Only output the game with this (0-based) index in the file.
     #[structopt(short, long)]
-    file: String,
-    #[structopt(short, long)]
-    output: String
+    game: Option<usize>,
 }
 
-fn main() {
-    std::env::set_var("RUST_LOG", "info");
-    env_logger::init();
+#[derive(StructOpt, Debug)]
+#[structopt(name = "hearthstone_power_parser")]
+enum Command {
+    /// Output the parsed game logs (summary, snapshots, actions and blocks) as JSON.
+    Json {
+        #[structopt(flatten)]
+        input: InputOptions,
+        /// Writes to stdout if not specified.
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Output every action as newline-delimited JSON.
+    Actions {
+        #[structopt(flatten)]
+        input: InputOptions,
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Print the winner, turn count and final board of every game.
+    Summary {
+        #[structopt(flatten)]
+        input: InputOptions,
+    },
+    /// Print every line the parser failed to handle. Exits with an error if there were any.
+    Diagnostics {
+        #[structopt(flatten)]
+        input: InputOptions,
+    },
+    /// Dump which FSM state handled each line.
+    Trace {
+        #[structopt(flatten)]
+        input: InputOptions,
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+}
+
+fn open_output(output: Option<&Path>) -> Result<Box<dyn Write>, SquadOvError> {
+    Ok(match output {
+        Some(p) => Box::new(std::io::BufWriter::new(std::fs::File::create(p)?)),
+        None => Box::new(std::io::stdout()),
+    })
+}
+
+fn load_games(input: &InputOptions, trace: bool) -> Result<Vec<ParsedHearthstoneGame>, SquadOvError> {
+    let lines = read_power_logs(&input.file, input.date)?;
+    let games = parse_games(split_games(lines), trace)?;
+    log::info!("Found {} game(s) in {}.", games.len(), input.file.display());
+    Ok(games)
+}
 
-    let opts = Options::from_args();
-    println!("FILE: {}", opts.file);
-    let file = File::open(opts.file).unwrap();
-    let reader = BufReader::new(file);
-    let raw_json_logs : serde_json::Value = serde_json::from_reader(reader).unwrap();
-    let power_logs: Vec<HearthstoneRawLog> = serde_json::from_value(raw_json_logs).unwrap();
-    let mut parser = HearthstonePowerLogParser::new(true);
-    parser.parse(&power_logs).unwrap();
-
-    println!("OUTPUT: {}", opts.output);
-    let file = File::create(opts.output).unwrap();
-    let mut writer = BufWriter::new(file);
-    writeln!(&mut writer, "============== LOGS ==============").unwrap();
-    writeln!(&mut writer, "{}", parser.fsm.raw_logs_to_string()).unwrap();
-    writeln!(&mut writer, "=================== BASIC STATE ====================").unwrap();
-    writeln!(&mut writer, "{}", parser.state).unwrap();
-    writeln!(&mut writer, "=================== SNAPSHOTS ====================").unwrap();
-    for snap in &parser.fsm.game.read().unwrap().snapshots {
-        writeln!(&mut writer, "{}", snap).unwrap();
+fn select_games<'a>(input: &InputOptions, games: &'a [ParsedHearthstoneGame]) -> Result<Vec<(usize, &'a ParsedHearthstoneGame)>, SquadOvError> {
+    let selected: Vec<(usize, &ParsedHearthstoneGame)> = games.iter()
+        .enumerate()
+        .filter(|(idx, _)| { input.game.map(|x| { x == *idx }).unwrap_or(true) })
+        .collect();
+
+    if input.game.is_some() && selected.is_empty() {
+        return Err(SquadOvError::InternalError(format!("Game {} not found ({} games in file).", input.game.unwrap(), games.len())));
     }
-    writeln!(&mut writer, "=================== ACTIONS ====================").unwrap();
-    for action in &parser.fsm.game.read().unwrap().actions {
-        writeln!(&mut writer, "{}", action).unwrap();
+    Ok(selected)
+}
+
+fn run(cmd: Command) -> Result<(), SquadOvError> {
+    match cmd {
+        Command::Json{input, output} => {
+            let games = load_games(&input, false)?;
+            output::write_games_json(&select_games(&input, &games)?, open_output(output.as_deref())?)?;
+        },
+        Command::Actions{input, output} => {
+            let games = load_games(&input, false)?;
+            output::write_actions_ndjson(&select_games(&input, &games)?, open_output(output.as_deref())?)?;
+        },
+        Command::Summary{input} => {
+            let games = load_games(&input, false)?;
+            output::write_summaries_json(&select_games(&input, &games)?, std::io::stdout())?;
+        },
+        Command::Diagnostics{input} => {
+            let games = load_games(&input, false)?;
+            let count = output::write_diagnostics(&select_games(&input, &games)?, std::io::stdout())?;
+            if count > 0 {
+                return Err(SquadOvError::InternalError(format!("{} line(s) failed to parse.", count)));
+            }
+        },
+        Command::Trace{input, output} => {
+            let games = load_games(&input, true)?;
+            output::write_trace(&select_games(&input, &games)?, open_output(output.as_deref())?)?;
+        },
+    };
+    Ok(())
+}
+
+fn main() {
+    if std::env::var("RUST_LOG").is_err() {
+        std::env::set_var("RUST_LOG", "info");
     }
-    writeln!(&mut writer, "=================== BLOCKS ====================").unwrap();
-    for (_bid, block) in &parser.fsm.game.read().unwrap().blocks {
-        writeln!(&mut writer, "{}", block).unwrap();
+    env_logger::init();
+
+    if let Err(err) = run(Command::from_args()) {
+        log::error!("{}", err);
+        std::process::exit(1);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,122 @@
+use squadov_common::{
+    SquadOvError,
+    hearthstone::game_state::{
+        HearthstoneGameAction,
+        HearthstoneGameBlock,
+        HearthstoneGameSnapshot,
+    },
+};
+use crate::{
+    game::{ParsedHearthstoneGame, PowerLogLineDiagnostic},
+    summary::HearthstoneGameSummary,
+};
+use serde::Serialize;
+use std::io::Write;
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct HearthstoneGameOutput<'a> {
+    pub game_index: usize,
+    pub first_line: usize,
+    pub last_line: usize,
+    pub summary: &'a HearthstoneGameSummary,
+    pub diagnostics: &'a [PowerLogLineDiagnostic],
+    pub snapshots: &'a [HearthstoneGameSnapshot],
+    pub actions: &'a [HearthstoneGameAction],
+    pub blocks: Vec<&'a HearthstoneGameBlock>,
+}
+
+impl<'a> HearthstoneGameOutput<'a> {
+    pub fn new(game_index: usize, game: &'a ParsedHearthstoneGame) -> Self {
+        let mut blocks: Vec<&HearthstoneGameBlock> = game.log.blocks.values().collect();
+        blocks.sort_by_key(|x| { (x.start_action_index, x.end_action_index) });
+
+        Self {
+            game_index,
+            first_line: game.first_line,
+            last_line: game.last_line,
+            summary: &game.summary,
+            diagnostics: &game.diagnostics,
+            snapshots: &game.log.snapshots,
+            actions: &game.log.actions,
+            blocks,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+struct HearthstoneActionLine<'a> {
+    game_index: usize,
+    action_index: usize,
+    #[serde(flatten)]
+    action: &'a HearthstoneGameAction,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+struct HearthstoneSummaryOutput<'a> {
+    game_index: usize,
+    first_line: usize,
+    last_line: usize,
+    #[serde(flatten)]
+    summary: &'a HearthstoneGameSummary,
+    num_diagnostics: usize,
+}
+
+pub fn write_games_json<W: Write>(games: &[(usize, &ParsedHearthstoneGame)], mut w: W) -> Result<(), SquadOvError> {
+    let output: Vec<HearthstoneGameOutput> = games.iter().map(|(idx, g)| { HearthstoneGameOutput::new(*idx, g) }).collect();
+    serde_json::to_writer_pretty(&mut w, &output)?;
+    writeln!(&mut w)?;
+    Ok(())
+}
+
+// One JSON object per line so that the output can be streamed into other tools.
+pub fn write_actions_ndjson<W: Write>(games: &[(usize, &ParsedHearthstoneGame)], mut w: W) -> Result<(), SquadOvError> {
+    for (game_index, g) in games {
+        for (action_index, action) in g.log.actions.iter().enumerate() {
+            serde_json::to_writer(&mut w, &HearthstoneActionLine{
+                game_index: *game_index,
+                action_index,
+                action,
+            })?;
+            writeln!(&mut w)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_summaries_json<W: Write>(games: &[(usize, &ParsedHearthstoneGame)], mut w: W) -> Result<(), SquadOvError> {
+    let output: Vec<HearthstoneSummaryOutput> = games.iter().map(|(idx, g)| {
+        HearthstoneSummaryOutput{
+            game_index: *idx,
+            first_line: g.first_line,
+            last_line: g.last_line,
+            summary: &g.summary,
+            num_diagnostics: g.diagnostics.len(),
+        }
+    }).collect();
+    serde_json::to_writer_pretty(&mut w, &output)?;
+    writeln!(&mut w)?;
+    Ok(())
+}
+
+pub fn write_diagnostics<W: Write>(games: &[(usize, &ParsedHearthstoneGame)], mut w: W) -> Result<usize, SquadOvError> {
+    let mut count = 0;
+    for (game_index, g) in games {
+        for d in &g.diagnostics {
+            writeln!(&mut w, "game {} line {}: {}", game_index, d.line, d.error)?;
+            writeln!(&mut w, "    {}", d.log)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+pub fn write_trace<W: Write>(games: &[(usize, &ParsedHearthstoneGame)], mut w: W) -> Result<(), SquadOvError> {
+    for (game_index, g) in games {
+        writeln!(&mut w, "============== GAME {} (lines {}-{}) ==============", game_index, g.first_line, g.last_line)?;
+        writeln!(&mut w, "{}", g.trace.as_ref().map(|x| { x.as_str() }).unwrap_or(""))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use squadov_common::hearthstone::HearthstoneRawLog;
+use crate::input::PowerLogLine;
+
+pub fn is_game_start(raw: &HearthstoneRawLog) -> bool {
+    let mut tokens = raw.log.splitn(2, '-');
+    let func = tokens.next().unwrap_or("").trim();
+    let log = tokens.next().unwrap_or("").trim();
+    func == "GameState.DebugPrintPower()" && log == "CREATE_GAME"
+}
+
+// Power.log keeps getting appended to until the client restarts so one file can contain several games.
+// Every game starts with a CREATE_GAME so anything before the first one gets dropped. Logs that don't
+// have a CREATE_GAME at all are treated as a single game.
+pub fn split_games(lines: Vec<PowerLogLine>) -> Vec<Vec<PowerLogLine>> {
+    if !lines.iter().any(|x| { is_game_start(&x.raw) }) {
+        return if lines.is_empty() { vec![] } else { vec![lines] };
+    }
+
+    let mut games: Vec<Vec<PowerLogLine>> = Vec::new();
+    for l in lines {
+        if is_game_start(&l.raw) {
+            games.push(Vec::new());
+        }
+
+        if let Some(g) = games.last_mut() {
+            g.push(l);
+        }
+    }
+    games
+}
@@ -0,0 +1,85 @@
+use squadov_common::hearthstone::{
+    GameType,
+    FormatType,
+    game_state::{
+        HearthstoneGameLog,
+        HearthstoneEntity,
+    },
+    power_parser::HearthstoneGameState,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct HearthstoneGameSummary {
+    pub game_type: GameType,
+    pub format_type: FormatType,
+    pub scenario_id: i32,
+    // Player ID -> player name.
+    pub players: BTreeMap<i32, String>,
+    pub winner_player_id: Option<i32>,
+    pub turns: i32,
+    // Player ID -> hero card ID.
+    pub heroes: BTreeMap<i32, String>,
+    // Player ID -> card IDs of the minions in play, left to right.
+    pub boards: BTreeMap<i32, Vec<String>>,
+    pub num_actions: usize,
+    pub num_blocks: usize,
+    pub num_snapshots: usize,
+}
+
+fn tag_is(e: &HearthstoneEntity, tag: &str, value: &str) -> bool {
+    e.tags.get(tag).map(|x| { x == value }).unwrap_or(false)
+}
+
+fn tag_i32(e: &HearthstoneEntity, tag: &str) -> Option<i32> {
+    e.tags.get(tag).and_then(|x| { x.parse().ok() })
+}
+
+impl HearthstoneGameSummary {
+    pub fn new(state: &HearthstoneGameState, log: &HearthstoneGameLog) -> Self {
+        let snapshot = &log.current_state;
+
+        let mut entities: Vec<&HearthstoneEntity> = snapshot.entities.values().collect();
+        entities.sort_by_key(|x| { x.entity_id });
+
+        let mut heroes: BTreeMap<i32, String> = BTreeMap::new();
+        let mut minions: BTreeMap<i32, Vec<(i32, i32, String)>> = BTreeMap::new();
+        for e in entities {
+            if !tag_is(e, "ZONE", "PLAY") {
+                continue;
+            }
+
+            let controller = match tag_i32(e, "CONTROLLER") {
+                Some(x) => x,
+                None => continue,
+            };
+            let card_id = e.card_id().unwrap_or(String::new());
+
+            // Heroes can get replaced mid-game in which case the newer hero is the one with the larger entity ID.
+            if tag_is(e, "CARDTYPE", "HERO") {
+                heroes.insert(controller, card_id);
+            } else if tag_is(e, "CARDTYPE", "MINION") {
+                minions.entry(controller).or_default().push((tag_i32(e, "ZONE_POSITION").unwrap_or(0), e.entity_id, card_id));
+            }
+        }
+
+        Self {
+            game_type: state.game_type,
+            format_type: state.format_type,
+            scenario_id: state.scenario_id,
+            players: state.player_map().iter().map(|(id, name)| { (*id, name.clone()) }).collect(),
+            winner_player_id: snapshot.get_match_winner_player_id(),
+            turns: snapshot.entities.get(&snapshot.game_entity_id).and_then(|x| { tag_i32(x, "TURN") }).unwrap_or(0),
+            heroes,
+            boards: minions.into_iter().map(|(controller, mut cards)| {
+                cards.sort();
+                (controller, cards.into_iter().map(|(_, _, card_id)| { card_id }).collect())
+            }).collect(),
+            num_actions: log.actions.len(),
+            num_blocks: log.blocks.len(),
+            num_snapshots: log.snapshots.len(),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use hearthstone_power_parser::{
+    game::{ParsedHearthstoneGame, parse_game, parse_games},
+    input::read_power_logs,
+    split::split_games,
+};
+use squadov_common::hearthstone::{
+    GameType,
+    HearthstoneRawLog,
+    power_parser::HearthstonePowerLogParser,
+};
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+fn data_dir() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests");
+    path.push("data");
+    path
+}
+
+fn load_games(file: &str) -> Vec<ParsedHearthstoneGame> {
+    let mut path = data_dir();
+    path.push(file);
+    let lines = read_power_logs(&path, Some(NaiveDate::from_ymd(2022, 7, 27))).unwrap();
+    parse_games(split_games(lines), false).unwrap()
+}
+
+struct ExpectedGame {
+    file: &'static str,
+    game_type: GameType,
+    winner: i32,
+    turns: i32,
+    heroes: Vec<(i32, &'static str)>,
+    boards: Vec<(i32, Vec<&'static str>)>,
+}
+
+#[test]
+fn test_corpus() {
+    let expected = vec![
+        ExpectedGame{
+            file: "constructed.log",
+            game_type: GameType::Ranked,
+            winner: 1,
+            turns: 5,
+            heroes: vec![(1, "HERO_08"), (2, "HERO_01")],
+            boards: vec![(1, vec!["CS2_182"]), (2, vec!["CS2_120"])],
+        },
+        ExpectedGame{
+            file: "arena.log",
+            game_type: GameType::Arena,
+            winner: 2,
+            turns: 4,
+            heroes: vec![(1, "HERO_05"), (2, "HERO_09")],
+            boards: vec![(2, vec!["CS2_182", "CS2_120"])],
+        },
+        ExpectedGame{
+            file: "duels.log",
+            game_type: GameType::PvpDr,
+            winner: 1,
+            turns: 3,
+            heroes: vec![(1, "PVPDR_Hero_Rexxar")],
+            boards: vec![(1, vec!["EX1_029", "CS2_172"])],
+        },
+        ExpectedGame{
+            file: "battlegrounds.log",
+            game_type: GameType::Battlegrounds,
+            winner: 5,
+            turns: 6,
+            heroes: vec![(5, "TB_BaconShop_HERO_56"), (13, "TB_BaconShopBob")],
+            boards: vec![(5, vec!["BGS_019", "BGS_004"]), (13, vec!["BGS_039"])],
+        },
+    ];
+
+    for e in expected {
+        let games = load_games(e.file);
+        assert_eq!(games.len(), 1, "{}", e.file);
+
+        let game = &games[0];
+        assert!(game.diagnostics.is_empty(), "{}", e.file);
+        assert_eq!(game.summary.game_type, e.game_type, "{}", e.file);
+        assert_eq!(game.summary.winner_player_id, Some(e.winner), "{}", e.file);
+        assert_eq!(game.summary.turns, e.turns, "{}", e.file);
+
+        let heroes: BTreeMap<i32, String> = e.heroes.into_iter().map(|(id, card)| { (id, card.to_string()) }).collect();
+        assert_eq!(game.summary.heroes, heroes, "{}", e.file);
+
+        let boards: BTreeMap<i32, Vec<String>> = e.boards.into_iter().map(|(id, cards)| {
+            (id, cards.into_iter().map(|x| { x.to_string() }).collect())
+        }).collect();
+        assert_eq!(game.summary.boards, boards, "{}", e.file);
+    }
+}
+
+#[test]
+fn test_split_multiple_games() {
+    let games = load_games("multiple_games.log");
+    assert_eq!(games.len(), 2);
+
+    // The leftover line before the first CREATE_GAME shouldn't be part of either game.
+    assert_eq!(games[0].first_line, 2);
+    assert_eq!(games[1].first_line, games[0].last_line + 1);
+
+    assert_eq!(games[0].summary.winner_player_id, Some(1));
+    assert_eq!(games[0].summary.turns, 2);
+    assert_eq!(games[1].summary.winner_player_id, Some(2));
+    assert_eq!(games[1].summary.turns, 3);
+}
+
+#[test]
+fn test_malformed_lines() {
+    let mut path = data_dir();
+    path.push("malformed.log");
+    let lines = read_power_logs(&path, Some(NaiveDate::from_ymd(2022, 7, 27))).unwrap();
+    let game = parse_game(lines, false).unwrap();
+
+    let bad_lines: Vec<usize> = game.diagnostics.iter().map(|x| { x.line }).collect();
+    assert_eq!(bad_lines, vec![48, 55]);
+
+    // Everything else should still get parsed.
+    assert_eq!(game.summary.winner_player_id, Some(1));
+    assert_eq!(game.summary.turns, 2);
+    assert_eq!(game.summary.boards.get(&1), Some(&vec![String::from("CS2_120")]));
+}
+
+#[test]
+fn test_malformed_lines_fail_strict_parse() {
+    let mut path = data_dir();
+    path.push("malformed.log");
+    let raw: Vec<HearthstoneRawLog> = read_power_logs(&path, Some(NaiveDate::from_ymd(2022, 7, 27))).unwrap()
+        .into_iter()
+        .map(|x| { x.raw })
+        .collect();
+
+    // The server stores whatever gets parsed so it shouldn't ever silently skip lines.
+    let mut parser = HearthstonePowerLogParser::new(false);
+    assert!(parser.parse(&raw).is_err());
+    assert!(parser.diagnostics.is_empty());
+}
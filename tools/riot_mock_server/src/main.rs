@@ -0,0 +1,39 @@
+use structopt::StructOpt;
+use squadov_common::{
+    SquadOvError,
+    riot::mock::{RiotMockServer, RiotMockFault},
+};
+use std::path::PathBuf;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "riot_mock_server")]
+struct Options {
+    /// JSON file mapping Riot API paths to fixture files (relative to the manifest).
+    #[structopt(short, long, parse(from_os_str))]
+    manifest: PathBuf,
+    #[structopt(short, long, default_value = "127.0.0.1:8089")]
+    bind: String,
+    /// JSON array of faults to start with (more can be POSTed to /__mock/faults).
+    #[structopt(long, parse(from_os_str))]
+    faults: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), SquadOvError> {
+    std::env::set_var("RUST_LOG", "info,actix_web=debug");
+    env_logger::init();
+
+    let opts = Options::from_args();
+    let server = RiotMockServer::start(&opts.manifest, &opts.bind).await?;
+    if let Some(faults) = &opts.faults {
+        let faults: Vec<RiotMockFault> = serde_json::from_slice(&std::fs::read(faults)?)?;
+        for f in faults {
+            server.add_fault(f);
+        }
+    }
+
+    log::info!("Riot mock server listening on {}", server.base_url());
+    tokio::signal::ctrl_c().await?;
+    server.stop().await;
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use structopt::StructOpt;
+use squadov_common::{
+    SquadOvError,
+    steam::mock::SteamMockServer,
+};
+use std::path::PathBuf;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "steam_mock_server")]
+struct Options {
+    /// JSON file with the API key, players and friendships to start with.
+    #[structopt(short, long, parse(from_os_str))]
+    fixtures: Option<PathBuf>,
+    #[structopt(short, long, default_value = "127.0.0.1:8092")]
+    bind: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), SquadOvError> {
+    std::env::set_var("RUST_LOG", "info,actix_web=debug");
+    env_logger::init();
+
+    let opts = Options::from_args();
+    let server = SteamMockServer::start(opts.fixtures.as_deref(), &opts.bind).await?;
+
+    // Friendships and privacy settings are changed with POST /__mock/friendships and POST /__mock/players/{steam_id}/privacy.
+    log::info!("Steam mock server listening on {} (set steam.base_url = {})", server.base_url(), server.base_url());
+    tokio::signal::ctrl_c().await?;
+    server.stop().await;
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use structopt::StructOpt;
+use squadov_common::{
+    SquadOvError,
+    stripe::mock::StripeMockServer,
+};
+use std::path::PathBuf;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "stripe_mock_server")]
+struct Options {
+    /// JSON file with the Stripe objects to start with, keyed by collection (e.g. products, prices, customers).
+    #[structopt(short, long, parse(from_os_str))]
+    fixtures: Option<PathBuf>,
+    #[structopt(short, long, default_value = "127.0.0.1:8090")]
+    bind: String,
+    /// Where to deliver webhook events (e.g. http://127.0.0.1:8080/webhooks/stripe).
+    #[structopt(long)]
+    webhook_url: Option<String>,
+    /// Must match stripe.webhook_secret in the server config.
+    #[structopt(long, default_value = "whsec_mock")]
+    webhook_secret: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), SquadOvError> {
+    std::env::set_var("RUST_LOG", "info,actix_web=debug");
+    env_logger::init();
+
+    let opts = Options::from_args();
+    let server = StripeMockServer::start(opts.fixtures.as_deref(), &opts.bind).await?;
+    if let Some(url) = &opts.webhook_url {
+        server.set_webhook_target(url, &opts.webhook_secret);
+    }
+
+    log::info!("Stripe mock server listening on {}", server.base_url());
+    tokio::signal::ctrl_c().await?;
+    server.stop().await;
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use structopt::StructOpt;
+use squadov_common::{
+    SquadOvError,
+    twitch::mock::TwitchMockServer,
+};
+use std::path::PathBuf;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "twitch_mock_server")]
+struct Options {
+    /// JSON file with the channels, videos and clips to start with.
+    #[structopt(short, long, parse(from_os_str))]
+    fixtures: Option<PathBuf>,
+    #[structopt(short, long, default_value = "127.0.0.1:8091")]
+    bind: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), SquadOvError> {
+    std::env::set_var("RUST_LOG", "info,actix_web=debug");
+    env_logger::init();
+
+    let opts = Options::from_args();
+    let server = TwitchMockServer::start(opts.fixtures.as_deref(), &opts.bind).await?;
+
+    // Streams are started/stopped with POST /__mock/streams/{broadcaster_id}/online (or /offline).
+    log::info!("Twitch mock server listening on {} (set twitch.helix_url = {} and twitch.id_url = {})", server.base_url(), server.helix_url(), server.id_url());
+    tokio::signal::ctrl_c().await?;
+    server.stop().await;
+    Ok(())
+}